@@ -116,8 +116,9 @@ export async function getFedCMStatus() {
     });
 }
 
-export async function getPasswordPolicy() {
-    return await fetch('/auth/v1/password_policy', {
+export async function getPasswordPolicy(global = false) {
+    const url = global ? '/auth/v1/password_policy?global=true' : '/auth/v1/password_policy';
+    return await fetch(url, {
         method: 'GET',
         headers: HEADERS.json,
     });
@@ -148,6 +149,26 @@ export async function resetPassword(uid, data, csrf) {
     });
 }
 
+export async function postUserNotMe(id, notMeId, csrf) {
+    return await fetch(`/auth/v1/users/${id}/not_me/${notMeId}`, {
+        method: 'POST',
+        headers: {
+            ...HEADERS.json,
+            'pwd-csrf-token': csrf,
+        },
+    });
+}
+
+export async function postUserUnlock(id, unlockId, csrf) {
+    return await fetch(`/auth/v1/users/${id}/unlock/${unlockId}`, {
+        method: 'POST',
+        headers: {
+            ...HEADERS.json,
+            'pwd-csrf-token': csrf,
+        },
+    });
+}
+
 export async function postPasswordResetRequest(data) {
     return await fetch('/auth/v1/users/request_reset', {
         method: 'POST',
@@ -179,6 +200,13 @@ export async function postTestEvent() {
     });
 }
 
+export async function getRegisterFields() {
+    return await fetch('/auth/v1/users/register/fields', {
+        method: 'GET',
+        headers: HEADERS.json,
+    });
+}
+
 export async function registerUser(data) {
     return await fetch('/auth/v1/users/register', {
         method: 'POST',
@@ -217,6 +245,27 @@ export async function deleteUserDeviceRefresh(id, data) {
     });
 }
 
+export async function getUserTrustedDevices(id) {
+    return await fetch(`/auth/v1/users/${id}/trusted_devices`, {
+        method: 'GET',
+        headers: getCsrfHeaders(),
+    });
+}
+
+export async function deleteUserTrustedDevice(id, deviceId) {
+    return await fetch(`/auth/v1/users/${id}/trusted_devices/${deviceId}`, {
+        method: 'DELETE',
+        headers: getCsrfHeaders(),
+    });
+}
+
+export async function getUserFederations(id) {
+    return await fetch(`/auth/v1/users/${id}/federations`, {
+        method: 'GET',
+        headers: getCsrfHeaders(),
+    });
+}
+
 export async function getUserPasskeys(id) {
     return await fetch(`/auth/v1/users/${id}/webauthn`, {
         method: 'GET',
@@ -232,8 +281,8 @@ export async function postUserProviderLink(id, data) {
     });
 }
 
-export async function deleteUserProviderLink() {
-    return await fetch('/auth/v1/providers/link', {
+export async function deleteUserProviderLink(id) {
+    return await fetch(`/auth/v1/providers/${id}/link`, {
         method: 'DELETE',
         headers: getCsrfHeaders(),
     });
@@ -137,6 +137,40 @@ export async function putClientSecret(id) {
     return await checkRedirectForbidden(res);
 }
 
+export async function getClientSaml(id) {
+    const res = await fetch(`/auth/v1/clients/${id}/saml`, {
+        method: 'GET',
+        headers: HEADERS,
+    });
+    return await checkRedirectForbidden(res);
+}
+
+export async function putClientSaml(id, data) {
+    const res = await fetch(`/auth/v1/clients/${id}/saml`, {
+        method: 'PUT',
+        headers: getHeaders(),
+        body: JSON.stringify(data),
+    });
+    return await checkRedirectForbidden(res);
+}
+
+export async function postClientSamlMetadata(id, metadata) {
+    const res = await fetch(`/auth/v1/clients/${id}/saml/metadata`, {
+        method: 'POST',
+        headers: getHeaders(),
+        body: JSON.stringify({metadata}),
+    });
+    return await checkRedirectForbidden(res);
+}
+
+export async function deleteClientSaml(id) {
+    const res = await fetch(`/auth/v1/clients/${id}/saml`, {
+        method: 'DELETE',
+        headers: getHeaders(),
+    });
+    return await checkRedirectForbidden(res);
+}
+
 export async function getEncKeys() {
     const res = await fetch('/auth/v1/encryption/keys', {
         method: 'GET',
@@ -223,6 +257,40 @@ export async function putPasswordPolicy(data) {
     return await checkRedirectForbidden(res);
 }
 
+export async function getPasswordPolicies() {
+    const res = await fetch('/auth/v1/password_policies', {
+        method: 'GET',
+        headers: HEADERS,
+    });
+    return await checkRedirectForbidden(res);
+}
+
+export async function postPasswordPolicyScoped(data) {
+    const res = await fetch('/auth/v1/password_policies', {
+        method: 'POST',
+        headers: getHeaders(),
+        body: JSON.stringify(data),
+    });
+    return await checkRedirectForbidden(res);
+}
+
+export async function putPasswordPolicyScoped(id, data) {
+    const res = await fetch(`/auth/v1/password_policies/${id}`, {
+        method: 'PUT',
+        headers: getHeaders(),
+        body: JSON.stringify(data),
+    });
+    return await checkRedirectForbidden(res);
+}
+
+export async function deletePasswordPolicyScoped(id) {
+    const res = await fetch(`/auth/v1/password_policies/${id}`, {
+        method: 'DELETE',
+        headers: getHeaders(),
+    });
+    return await checkRedirectForbidden(res);
+}
+
 export async function postPasswordResetRequest(data) {
     const res = await fetch('/auth/v1/users/request_reset', {
         method: 'POST',
@@ -291,6 +359,14 @@ export async function postProviderLookup(data) {
     });
 }
 
+export async function postProviderSamlLookup(data) {
+    return await fetch('/auth/v1/providers/saml/lookup', {
+        method: 'POST',
+        headers: getHeaders(),
+        body: JSON.stringify(data),
+    });
+}
+
 export async function postRotateJwk() {
     const res = await fetch(`/auth/v1/oidc/rotate_jwk`, {
         method: 'POST',
@@ -387,6 +463,14 @@ export async function putUser(uid, data) {
     return await checkRedirectForbidden(res);
 }
 
+export async function postUserUnlock(uid) {
+    const res = await fetch(`/auth/v1/users/${uid}/unlock`, {
+        method: 'POST',
+        headers: getHeaders(),
+    });
+    return await checkRedirectForbidden(res);
+}
+
 export async function deleteUser(uid) {
     const res = await fetch(`/auth/v1/users/${uid}`, {
         method: 'DELETE',
@@ -515,6 +599,31 @@ export async function deleteBlacklistedIp(ip) {
     return await checkRedirectForbidden(res);
 }
 
+export async function getAllowlist() {
+    const res = await fetch('/auth/v1/blacklist/allowlist', {
+        method: 'GET',
+        headers: HEADERS,
+    });
+    return await checkRedirectForbidden(res);
+}
+
+export async function postAllowlist(data) {
+    const res = await fetch('/auth/v1/blacklist/allowlist', {
+        method: 'POST',
+        headers: getHeaders(),
+        body: JSON.stringify(data),
+    });
+    return await checkRedirectForbidden(res);
+}
+
+export async function deleteAllowlistedIp(ip) {
+    const res = await fetch(`/auth/v1/blacklist/allowlist/${ip}`, {
+        method: 'DELETE',
+        headers: getHeaders(),
+    });
+    return await checkRedirectForbidden(res);
+}
+
 export async function getRoles() {
     const res = await fetch('/auth/v1/roles', {
         method: 'GET',
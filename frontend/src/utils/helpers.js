@@ -25,7 +25,7 @@ export function extractFormErrors(err) {
 
 export function isDefaultScope(name) {
     return name === 'openid' || name === 'profile' || name === 'email' || name === 'groups'
-        || name === 'address' || name === 'phone';
+        || name === 'address' || name === 'phone' || name === 'organization';
 }
 
 /*
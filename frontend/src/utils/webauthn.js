@@ -49,6 +49,20 @@ export async function webauthnAuth(uid, data, errorMsg) {
                 msg: 'Authentication successful',
                 body,
             };
+        } else if (res.status === 403) {
+            let body = await res.json();
+            if (body.error === 'AccessDenied') {
+                // the user has no access to this client -> redirect with `access_denied`
+                return {
+                    err: false,
+                    msg: 'Access denied',
+                    body: {loc: body.message},
+                };
+            }
+            return {
+                err: true,
+                msg: 'Authentication Error',
+            };
         } else {
             console.error(res);
             return {
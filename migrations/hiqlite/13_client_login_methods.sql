@@ -0,0 +1,8 @@
+ALTER TABLE clients
+    ADD allowed_providers TEXT;
+ALTER TABLE clients
+    ADD allow_password_login INTEGER DEFAULT 1 NOT NULL;
+ALTER TABLE clients
+    ADD passkey_only INTEGER DEFAULT 0 NOT NULL;
+ALTER TABLE clients
+    ADD auto_redirect_provider TEXT;
@@ -0,0 +1,6 @@
+ALTER TABLE clients
+    ADD access_roles TEXT;
+ALTER TABLE clients
+    ADD access_groups TEXT;
+ALTER TABLE clients
+    ADD access_email_domains TEXT;
@@ -0,0 +1,30 @@
+CREATE TABLE clients_saml
+(
+    id             TEXT NOT NULL
+        CONSTRAINT clients_saml_pk
+            PRIMARY KEY
+        CONSTRAINT clients_saml_clients_id_fk
+            REFERENCES clients
+            ON UPDATE CASCADE ON DELETE CASCADE,
+    entity_id      TEXT NOT NULL,
+    acs_url        TEXT NOT NULL,
+    slo_url        TEXT,
+    name_id_format TEXT NOT NULL,
+    sp_cert        TEXT,
+    attr_mapping   TEXT
+) STRICT;
+
+CREATE UNIQUE INDEX clients_saml_entity_id_uindex
+    ON clients_saml (entity_id);
+
+CREATE TABLE saml_certs
+(
+    kid        TEXT    NOT NULL
+        CONSTRAINT saml_certs_pk
+            PRIMARY KEY
+        CONSTRAINT saml_certs_jwks_kid_fk
+            REFERENCES jwks
+            ON UPDATE CASCADE ON DELETE CASCADE,
+    created_at INTEGER NOT NULL,
+    cert_der   BLOB    NOT NULL
+) STRICT;
@@ -0,0 +1,4 @@
+ALTER TABLE auth_providers
+    ADD saml_idp_cert TEXT;
+ALTER TABLE auth_providers
+    ADD saml_attr_mapping BLOB;
@@ -0,0 +1,21 @@
+CREATE TABLE ip_blacklist
+(
+    cidr       TEXT    NOT NULL
+        CONSTRAINT ip_blacklist_pk
+            PRIMARY KEY,
+    exp        INTEGER,
+    reason     TEXT,
+    created_at INTEGER NOT NULL
+) STRICT;
+
+CREATE INDEX ip_blacklist_exp_index
+    ON ip_blacklist (exp);
+
+CREATE TABLE ip_allowlist
+(
+    cidr        TEXT    NOT NULL
+        CONSTRAINT ip_allowlist_pk
+            PRIMARY KEY,
+    description TEXT,
+    created_at  INTEGER NOT NULL
+) STRICT;
@@ -0,0 +1,2 @@
+ALTER TABLE users
+    ADD locked_until INTEGER;
@@ -0,0 +1,4 @@
+ALTER TABLE clients
+    ADD geo_allow_countries TEXT;
+ALTER TABLE clients
+    ADD geo_deny_countries TEXT;
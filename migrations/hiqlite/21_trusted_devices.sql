@@ -0,0 +1,23 @@
+CREATE TABLE trusted_devices
+(
+    id        TEXT    NOT NULL
+        CONSTRAINT trusted_devices_pk
+            PRIMARY KEY,
+    user_id   TEXT    NOT NULL
+        CONSTRAINT trusted_devices_users_id_fk
+            REFERENCES users
+            ON UPDATE CASCADE ON DELETE CASCADE,
+    name      TEXT    NOT NULL,
+    created   INTEGER NOT NULL,
+    last_used INTEGER NOT NULL,
+    exp       INTEGER NOT NULL,
+    ip        TEXT    NOT NULL,
+    country   TEXT,
+    asn       INTEGER
+) STRICT;
+
+CREATE INDEX trusted_devices_user_id_index
+    ON trusted_devices (user_id);
+
+CREATE INDEX trusted_devices_exp_index
+    ON trusted_devices (exp);
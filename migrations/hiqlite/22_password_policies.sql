@@ -0,0 +1,12 @@
+CREATE TABLE password_policies
+(
+    id      TEXT NOT NULL
+        CONSTRAINT password_policies_pk
+            PRIMARY KEY,
+    name    TEXT NOT NULL
+        CONSTRAINT password_policies_name_uindex
+            UNIQUE,
+    policy  BLOB NOT NULL,
+    groups  TEXT,
+    clients TEXT
+) STRICT;
@@ -0,0 +1,12 @@
+CREATE TABLE admin_roles
+(
+    id          TEXT NOT NULL
+        CONSTRAINT admin_roles_pk
+            PRIMARY KEY,
+    name        TEXT NOT NULL
+        CONSTRAINT admin_roles_name_uindex
+            UNIQUE,
+    description TEXT,
+    access      BLOB NOT NULL,
+    group_scope TEXT
+) STRICT;
@@ -0,0 +1,2 @@
+ALTER TABLE sessions
+    ADD impersonated_by TEXT;
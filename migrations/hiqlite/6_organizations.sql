@@ -0,0 +1,33 @@
+CREATE TABLE organizations
+(
+    id          TEXT NOT NULL
+        CONSTRAINT organizations_pk
+            PRIMARY KEY,
+    name        TEXT NOT NULL
+        CONSTRAINT organizations_name_uindex
+            UNIQUE,
+    description TEXT,
+    roles       TEXT NOT NULL
+) STRICT;
+
+CREATE TABLE org_members
+(
+    org_id       TEXT    NOT NULL
+        CONSTRAINT org_members_organizations_id_fk
+            REFERENCES organizations
+            ON UPDATE CASCADE ON DELETE CASCADE,
+    user_id      TEXT    NOT NULL
+        CONSTRAINT org_members_users_id_fk
+            REFERENCES users
+            ON UPDATE CASCADE ON DELETE CASCADE,
+    roles        TEXT    NOT NULL,
+    is_org_admin INTEGER NOT NULL DEFAULT 0,
+    CONSTRAINT org_members_pk
+        PRIMARY KEY (org_id, user_id)
+) STRICT;
+
+CREATE INDEX org_members_user_id_index
+    ON org_members (user_id);
+
+INSERT INTO scopes (id, name, attr_include_access, attr_include_id)
+VALUES ('Qx4b1SRxPGbzPWJ6NPCfKsUM', 'organization', NULL, NULL);
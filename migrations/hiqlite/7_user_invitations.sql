@@ -0,0 +1,27 @@
+CREATE TABLE user_invitations
+(
+    id           TEXT    NOT NULL
+        CONSTRAINT user_invitations_pk
+            PRIMARY KEY,
+    user_id      TEXT    NOT NULL
+        CONSTRAINT user_invitations_users_id_fk
+            REFERENCES users
+            ON UPDATE CASCADE ON DELETE CASCADE,
+    email        TEXT    NOT NULL,
+    roles        TEXT    NOT NULL,
+    groups       TEXT,
+    attributes   BLOB,
+    org_id       TEXT
+        CONSTRAINT user_invitations_organizations_id_fk
+            REFERENCES organizations
+            ON UPDATE CASCADE ON DELETE SET NULL,
+    org_roles    TEXT,
+    redirect_uri TEXT,
+    created_by   TEXT    NOT NULL,
+    created_at   INTEGER NOT NULL,
+    exp          INTEGER NOT NULL,
+    accepted_at  INTEGER
+) STRICT;
+
+CREATE INDEX user_invitations_user_id_index
+    ON user_invitations (user_id);
@@ -0,0 +1,19 @@
+ALTER TABLE user_attr_config
+    ADD registration INTEGER DEFAULT 0 NOT NULL;
+
+CREATE TABLE user_registrations
+(
+    id           TEXT    NOT NULL
+        CONSTRAINT user_registrations_pk
+            PRIMARY KEY,
+    email        TEXT    NOT NULL
+        CONSTRAINT user_registrations_email_uindex
+            UNIQUE,
+    given_name   TEXT    NOT NULL,
+    family_name  TEXT,
+    language     TEXT    NOT NULL,
+    redirect_uri TEXT,
+    attributes   BLOB,
+    ip           TEXT    NOT NULL,
+    created_at   INTEGER NOT NULL
+) STRICT;
@@ -0,0 +1,2 @@
+ALTER TABLE auth_providers
+    ADD jwks_uri TEXT;
@@ -0,0 +1,12 @@
+create table admin_roles
+(
+    id          varchar not null
+        constraint admin_roles_pk
+            primary key,
+    name        varchar not null
+        constraint admin_roles_name_uindex
+            unique,
+    description varchar,
+    access      bytea   not null,
+    group_scope varchar
+);
@@ -0,0 +1,33 @@
+create table organizations
+(
+    id          varchar not null
+        constraint organizations_pk
+            primary key,
+    name        varchar not null
+        constraint organizations_name_uindex
+            unique,
+    description varchar,
+    roles       varchar not null
+);
+
+create table org_members
+(
+    org_id       varchar not null
+        constraint org_members_organizations_id_fk
+            references organizations
+            on update cascade on delete cascade,
+    user_id      varchar not null
+        constraint org_members_users_id_fk
+            references users
+            on update cascade on delete cascade,
+    roles        varchar not null,
+    is_org_admin bool    not null default false,
+    constraint org_members_pk
+        primary key (org_id, user_id)
+);
+
+create index org_members_user_id_index
+    on org_members (user_id);
+
+insert into scopes (id, name, attr_include_access, attr_include_id)
+values ('Qx4b1SRxPGbzPWJ6NPCfKsUM', 'organization', null, null);
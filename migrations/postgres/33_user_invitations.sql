@@ -0,0 +1,27 @@
+create table user_invitations
+(
+    id           varchar not null
+        constraint user_invitations_pk
+            primary key,
+    user_id      varchar not null
+        constraint user_invitations_users_id_fk
+            references users
+            on update cascade on delete cascade,
+    email        varchar not null,
+    roles        varchar not null,
+    groups       varchar,
+    attributes   bytea,
+    org_id       varchar
+        constraint user_invitations_organizations_id_fk
+            references organizations
+            on update cascade on delete set null,
+    org_roles    varchar,
+    redirect_uri varchar,
+    created_by   varchar not null,
+    created_at   bigint  not null,
+    exp          bigint  not null,
+    accepted_at  bigint
+);
+
+create index user_invitations_user_id_index
+    on user_invitations (user_id);
@@ -0,0 +1,19 @@
+alter table user_attr_config
+    add registration bool default false not null;
+
+create table user_registrations
+(
+    id           varchar not null
+        constraint user_registrations_pk
+            primary key,
+    email        varchar not null
+        constraint user_registrations_email_uindex
+            unique,
+    given_name   varchar not null,
+    family_name  varchar,
+    language     varchar not null,
+    redirect_uri varchar,
+    attributes   bytea,
+    ip           varchar not null,
+    created_at   bigint  not null
+);
@@ -0,0 +1,8 @@
+alter table clients
+    add allowed_providers varchar;
+alter table clients
+    add allow_password_login bool default true not null;
+alter table clients
+    add passkey_only bool default false not null;
+alter table clients
+    add auto_redirect_provider varchar;
@@ -0,0 +1,6 @@
+alter table clients
+    add access_roles varchar;
+alter table clients
+    add access_groups varchar;
+alter table clients
+    add access_email_domains varchar;
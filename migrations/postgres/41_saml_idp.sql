@@ -0,0 +1,30 @@
+create table clients_saml
+(
+    id             varchar not null
+        constraint clients_saml_pk
+            primary key
+        constraint clients_saml_clients_id_fk
+            references clients
+            on update cascade on delete cascade,
+    entity_id      varchar not null,
+    acs_url        varchar not null,
+    slo_url        varchar,
+    name_id_format varchar not null,
+    sp_cert        varchar,
+    attr_mapping   varchar
+);
+
+create unique index clients_saml_entity_id_uindex
+    on clients_saml (entity_id);
+
+create table saml_certs
+(
+    kid        varchar not null
+        constraint saml_certs_pk
+            primary key
+        constraint saml_certs_jwks_kid_fk
+            references jwks
+            on update cascade on delete cascade,
+    created_at bigint  not null,
+    cert_der   bytea   not null
+);
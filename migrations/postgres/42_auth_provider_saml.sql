@@ -0,0 +1,4 @@
+alter table auth_providers
+    add saml_idp_cert varchar;
+alter table auth_providers
+    add saml_attr_mapping bytea;
@@ -0,0 +1,2 @@
+alter table auth_providers
+    add userinfo_mapping bytea;
@@ -0,0 +1,21 @@
+create table ip_blacklist
+(
+    cidr       varchar not null
+        constraint ip_blacklist_pk
+            primary key,
+    exp        bigint,
+    reason     varchar,
+    created_at bigint  not null
+);
+
+create index ip_blacklist_exp_index
+    on ip_blacklist (exp);
+
+create table ip_allowlist
+(
+    cidr        varchar not null
+        constraint ip_allowlist_pk
+            primary key,
+    description varchar,
+    created_at  bigint  not null
+);
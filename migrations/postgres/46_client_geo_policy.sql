@@ -0,0 +1,4 @@
+alter table clients
+    add geo_allow_countries varchar;
+alter table clients
+    add geo_deny_countries varchar;
@@ -0,0 +1,23 @@
+create table trusted_devices
+(
+    id        varchar not null
+        constraint trusted_devices_pk
+            primary key,
+    user_id   varchar not null
+        constraint trusted_devices_users_id_fk
+            references users
+            on update cascade on delete cascade,
+    name      varchar not null,
+    created   bigint  not null,
+    last_used bigint  not null,
+    exp       bigint  not null,
+    ip        varchar not null,
+    country   varchar,
+    asn       bigint
+);
+
+create index trusted_devices_user_id_index
+    on trusted_devices (user_id);
+
+create index trusted_devices_exp_index
+    on trusted_devices (exp);
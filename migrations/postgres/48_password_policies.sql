@@ -0,0 +1,12 @@
+create table password_policies
+(
+    id      varchar not null
+        constraint password_policies_pk
+            primary key,
+    name    varchar not null
+        constraint password_policies_name_uindex
+            unique,
+    policy  bytea   not null,
+    groups  varchar,
+    clients varchar
+);
@@ -0,0 +1,118 @@
+use crate::ReqPrincipal;
+use actix_web::{delete, get, post, put, web, HttpResponse};
+use rauthy_api_types::admin_roles::{AdminRoleRequest, AdminRoleResponse};
+use rauthy_error::ErrorResponse;
+use rauthy_models::entity::admin_roles::{AdminRole, AdminRoleEntity};
+
+/// Returns all scoped admin roles
+///
+/// **Permissions**
+/// - rauthy_admin
+#[utoipa::path(
+    get,
+    path = "/admin_roles",
+    tag = "admin_roles",
+    responses(
+        (status = 200, description = "Ok", body = [AdminRoleResponse]),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Forbidden", body = ErrorResponse),
+    ),
+)]
+#[get("/admin_roles")]
+pub async fn get_admin_roles(principal: ReqPrincipal) -> Result<HttpResponse, ErrorResponse> {
+    principal.validate_admin_session()?;
+
+    let roles = AdminRole::find_all()
+        .await?
+        .into_iter()
+        .map(AdminRoleResponse::from)
+        .collect::<Vec<_>>();
+    Ok(HttpResponse::Ok().json(roles))
+}
+
+/// Creates a new scoped admin role
+///
+/// The `name` must match an existing role. Each user with this role assigned will get the
+/// defined access rights for the admin API.
+/// Scoped admin roles can only be managed by a full `rauthy_admin` and never with an API key.
+///
+/// **Permissions**
+/// - rauthy_admin
+#[utoipa::path(
+    post,
+    path = "/admin_roles",
+    tag = "admin_roles",
+    request_body = AdminRoleRequest,
+    responses(
+        (status = 200, description = "Ok", body = AdminRoleResponse),
+        (status = 400, description = "BadRequest", body = ErrorResponse),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Forbidden", body = ErrorResponse),
+    ),
+)]
+#[post("/admin_roles")]
+pub async fn post_admin_role(
+    payload: actix_web_validator::Json<AdminRoleRequest>,
+    principal: ReqPrincipal,
+) -> Result<HttpResponse, ErrorResponse> {
+    principal.validate_admin_session()?;
+
+    let role = AdminRoleEntity::create(payload.into_inner()).await?;
+    Ok(HttpResponse::Ok().json(AdminRoleResponse::from(role)))
+}
+
+/// Modifies a scoped admin role
+///
+/// **Permissions**
+/// - rauthy_admin
+#[utoipa::path(
+    put,
+    path = "/admin_roles/{id}",
+    tag = "admin_roles",
+    request_body = AdminRoleRequest,
+    responses(
+        (status = 200, description = "Ok", body = AdminRoleResponse),
+        (status = 400, description = "BadRequest", body = ErrorResponse),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Forbidden", body = ErrorResponse),
+        (status = 404, description = "NotFound", body = ErrorResponse),
+    ),
+)]
+#[put("/admin_roles/{id}")]
+pub async fn put_admin_role(
+    id: web::Path<String>,
+    payload: actix_web_validator::Json<AdminRoleRequest>,
+    principal: ReqPrincipal,
+) -> Result<HttpResponse, ErrorResponse> {
+    principal.validate_admin_session()?;
+
+    let role = AdminRoleEntity::update(&id.into_inner(), payload.into_inner()).await?;
+    Ok(HttpResponse::Ok().json(AdminRoleResponse::from(role)))
+}
+
+/// Deletes a scoped admin role
+///
+/// The linked role itself will not be touched.
+///
+/// **Permissions**
+/// - rauthy_admin
+#[utoipa::path(
+    delete,
+    path = "/admin_roles/{id}",
+    tag = "admin_roles",
+    responses(
+        (status = 200, description = "Ok"),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Forbidden", body = ErrorResponse),
+    ),
+)]
+#[delete("/admin_roles/{id}")]
+pub async fn delete_admin_role(
+    id: web::Path<String>,
+    principal: ReqPrincipal,
+) -> Result<HttpResponse, ErrorResponse> {
+    principal.validate_admin_session()?;
+
+    AdminRoleEntity::delete(&id.into_inner()).await?;
+    Ok(HttpResponse::Ok().finish())
+}
@@ -1,12 +1,15 @@
 use crate::{map_auth_step, ReqPrincipal};
 use actix_web::http::header::{CACHE_CONTROL, CONTENT_TYPE, LOCATION};
-use actix_web::{delete, get, post, put, web, HttpRequest, HttpResponse};
+use actix_web::{delete, get, post, put, web, HttpRequest, HttpResponse, ResponseError};
 use actix_web_lab::__reexports::futures_util::StreamExt;
 use actix_web_validator::Json;
 use rauthy_api_types::auth_providers::{
-    ProviderCallbackRequest, ProviderLoginRequest, ProviderLookupRequest, ProviderRequest,
+    AuthProviderType, ProviderCallbackRequest, ProviderLoginRequest, ProviderLookupRequest,
+    ProviderRequest, ProviderSamlAcsRequest, ProviderSamlLookupRequest,
+};
+use rauthy_api_types::auth_providers::{
+    ProviderLookupResponse, ProviderResponse, ProviderSamlLookupResponse,
 };
-use rauthy_api_types::auth_providers::{ProviderLookupResponse, ProviderResponse};
 use rauthy_common::constants::{HEADER_HTML, HEADER_JSON};
 use rauthy_error::{ErrorResponse, ErrorResponseType};
 use rauthy_models::app_state::AppState;
@@ -15,9 +18,10 @@ use rauthy_models::entity::auth_providers::{
 };
 use rauthy_models::entity::colors::ColorEntity;
 use rauthy_models::entity::logos::{Logo, LogoType};
+use rauthy_models::entity::user_federations::UserFederation;
 use rauthy_models::entity::users::User;
 use rauthy_models::language::Language;
-use rauthy_models::templates::ProviderCallbackHtml;
+use rauthy_models::templates::{Error1Html, ErrorHtml, ProviderCallbackHtml};
 use tracing::debug;
 
 /// GET all upstream auth providers
@@ -68,7 +72,8 @@ pub async fn post_provider(
 ) -> Result<HttpResponse, ErrorResponse> {
     principal.validate_admin_session()?;
 
-    if !payload.use_pkce && payload.client_secret.is_none() {
+    if payload.typ != AuthProviderType::Saml && !payload.use_pkce && payload.client_secret.is_none()
+    {
         return Err(ErrorResponse::new(
             ErrorResponseType::BadRequest,
             "Must at least be a confidential client or use PKCE".to_string(),
@@ -112,6 +117,90 @@ pub async fn post_provider_lookup(
     Ok(HttpResponse::Ok().json(resp))
 }
 
+/// POST upstream SAML IdP metadata lookup
+///
+/// Fetches the metadata either from the given `metadata_url` or parses the given `metadata` XML
+/// and extracts everything needed to configure a SAML auth provider.
+///
+/// **Permissions**
+/// - `rauthy_admin`
+#[utoipa::path(
+    post,
+    path = "/providers/saml/lookup",
+    tag = "providers",
+    request_body = ProviderSamlLookupRequest,
+    responses(
+        (status = 200, description = "OK", body = ProviderSamlLookupResponse),
+        (status = 400, description = "BadRequest", body = ErrorResponse),
+    ),
+)]
+#[post("/providers/saml/lookup")]
+pub async fn post_provider_saml_lookup(
+    payload: Json<ProviderSamlLookupRequest>,
+    principal: ReqPrincipal,
+) -> Result<HttpResponse, ErrorResponse> {
+    principal.validate_admin_session()?;
+
+    let resp = AuthProvider::lookup_saml_metadata(&payload.into_inner()).await?;
+    Ok(HttpResponse::Ok().json(resp))
+}
+
+/// GET the SAML Service Provider metadata for upstream SAML IdPs
+///
+/// Contains the `AssertionConsumerService` and the certificates for all currently existing
+/// `RS256` keys, which are used to sign the `AuthnRequest`s.
+#[utoipa::path(
+    get,
+    path = "/providers/saml/metadata",
+    tag = "providers",
+    responses(
+        (status = 200, description = "OK"),
+    ),
+)]
+#[get("/providers/saml/metadata")]
+pub async fn get_provider_saml_metadata() -> Result<HttpResponse, ErrorResponse> {
+    let xml = AuthProvider::saml_sp_metadata().await?;
+    Ok(HttpResponse::Ok()
+        .insert_header((CONTENT_TYPE, "application/samlmetadata+xml"))
+        .body(xml))
+}
+
+/// SAML Assertion Consumer Service for upstream SAML IdPs (`HTTP-POST` binding)
+///
+/// The response will only be validated after the browser has been redirected to the provider
+/// callback page, which binds it to the session that started the login.
+#[utoipa::path(
+    post,
+    path = "/providers/saml/acs",
+    tag = "providers",
+    request_body(content = ProviderSamlAcsRequest, content_type = "application/x-www-form-urlencoded"),
+    responses(
+        (status = 303, description = "Redirect to the provider callback"),
+        (status = 403, description = "Forbidden"),
+        (status = 404, description = "NotFound"),
+    ),
+)]
+#[post("/providers/saml/acs")]
+pub async fn post_provider_saml_acs(
+    req: HttpRequest,
+    payload: actix_web_validator::Form<ProviderSamlAcsRequest>,
+) -> HttpResponse {
+    let payload = payload.into_inner();
+    match AuthProviderCallback::saml_acs(payload.saml_response, payload.relay_state).await {
+        Ok(location) => HttpResponse::SeeOther()
+            .insert_header((LOCATION, location))
+            .finish(),
+        Err(err) => {
+            // the browser has been sent here by the IdP -> render the error as HTML
+            let colors = ColorEntity::find_rauthy().await.unwrap_or_default();
+            let lang = Language::try_from(&req).unwrap_or_default();
+            let status = err.status_code();
+            let body = Error1Html::build(&colors, &lang, status, Some(err.message));
+            ErrorHtml::response(body, status)
+        }
+    }
+}
+
 /// Start the login flow for an upstream auth provider
 ///
 /// **Permissions**
@@ -197,9 +286,10 @@ pub async fn post_provider_callback(
 
 /// DELETE a link between an existing user account and an upstream provider
 ///
-/// This will always unlink the currently logged-in user from its registered
-/// upstream auth provider. The user account must have been set up with at least
-/// a password or a passkey. Otherwise, this endpoint will return an error.
+/// This will always unlink the currently logged-in user from its primary upstream auth provider.
+/// If other linked identities exist, the oldest one will become the new primary. Without any
+/// other linked identity, the user account must have been set up with at least a password or a
+/// passkey. Otherwise, this endpoint will return an error.
 #[utoipa::path(
     delete,
     path = "/providers/link",
@@ -214,7 +304,41 @@ pub async fn delete_provider_link(principal: ReqPrincipal) -> Result<HttpRespons
     principal.validate_session_auth()?;
 
     let user_id = principal.user_id()?.to_string();
-    let user = User::provider_unlink(user_id).await?;
+    let user = User::find(user_id).await?;
+    let Some(provider_id) = user.auth_provider_id else {
+        return Err(ErrorResponse::new(
+            ErrorResponseType::BadRequest,
+            "user is not federated",
+        ));
+    };
+
+    let user = User::provider_unlink(user.id, &provider_id).await?;
+    Ok(HttpResponse::Ok().json(user))
+}
+
+/// DELETE the link between the current user account and a specific upstream provider
+///
+/// The user account must either have another linked identity left, or it must have been set up
+/// with at least a password or a passkey. Otherwise, this endpoint will return an error.
+#[utoipa::path(
+    delete,
+    path = "/providers/{id}/link",
+    tag = "providers",
+    responses(
+        (status = 200, description = "OK"),
+        (status = 400, description = "BadRequest", body = ErrorResponse),
+        (status = 404, description = "NotFound", body = ErrorResponse),
+    ),
+)]
+#[delete("/providers/{id}/link")]
+pub async fn delete_provider_link_by_id(
+    provider_id: web::Path<String>,
+    principal: ReqPrincipal,
+) -> Result<HttpResponse, ErrorResponse> {
+    principal.validate_session_auth()?;
+
+    let user_id = principal.user_id()?.to_string();
+    let user = User::provider_unlink(user_id, &provider_id).await?;
     Ok(HttpResponse::Ok().json(user))
 }
 
@@ -261,7 +385,8 @@ pub async fn put_provider(
 ) -> Result<HttpResponse, ErrorResponse> {
     principal.validate_admin_session()?;
 
-    if !payload.use_pkce && payload.client_secret.is_none() {
+    if payload.typ != AuthProviderType::Saml && !payload.use_pkce && payload.client_secret.is_none()
+    {
         return Err(ErrorResponse::new(
             ErrorResponseType::BadRequest,
             "Must at least be a confidential client or use PKCE",
@@ -417,8 +542,9 @@ pub async fn put_provider_img(
 
 /// POST a link between an existing user account and an upstream provider
 ///
-/// This action will create a link between an already existing, non-linked account and a configured
-/// upstream auth provider. This can only be issued from within an authenticated, valid session.
+/// This action will create a link between an already existing account and a configured
+/// upstream auth provider. A user can link multiple providers, but only a single identity for
+/// each of them. This can only be issued from within an authenticated, valid session.
 #[utoipa::path(
     post,
     path = "/providers/{id}/link",
@@ -441,17 +567,22 @@ pub async fn post_provider_link(
     let user_id = principal.user_id()?.to_string();
     let user = User::find(user_id).await?;
 
-    // make sure the user is currently un-linked
-    if user.auth_provider_id.is_some() {
+    // make sure the user is not yet linked to this provider
+    let provider_id = provider_id.into_inner();
+    if UserFederation::find_for_user(&user.id)
+        .await?
+        .iter()
+        .any(|l| l.provider_id == provider_id)
+    {
         return Err(ErrorResponse::new(
             ErrorResponseType::BadRequest,
-            "user is already federated",
+            "user is already linked to this provider",
         ));
     }
 
     // set an encrypted cookie with the provider_id + user_id / email
     let link_cookie = AuthProviderLinkCookie {
-        provider_id: provider_id.into_inner(),
+        provider_id,
         user_id: user.id,
         user_email: user.email,
     };
@@ -2,15 +2,18 @@ use crate::ReqPrincipal;
 use actix_web::{delete, get, post, web, HttpResponse};
 use actix_web_validator::Json;
 use chrono::DateTime;
-use rauthy_api_types::blacklist::{BlacklistResponse, BlacklistedIp, IpBlacklistRequest};
-use rauthy_error::ErrorResponse;
+use rauthy_api_types::blacklist::{
+    AllowlistedIp, BlacklistResponse, BlacklistedIp, IpAllowlistRequest, IpBlacklistRequest,
+};
+use rauthy_error::{ErrorResponse, ErrorResponseType};
 use rauthy_models::app_state::AppState;
 use rauthy_models::entity::api_keys::{AccessGroup, AccessRights};
+use rauthy_models::entity::ip_blacklist::{
+    cidr_to_string, parse_cidr, IpAllowlistEntry, IpBlacklistEntry,
+};
 use rauthy_models::events::event::Event;
-use rauthy_models::events::ip_blacklist_handler::IpBlacklistReq;
-use tokio::sync::oneshot;
 
-/// Returns all blacklisted IP's
+/// Returns all blacklisted IP's and ranges
 ///
 /// **Permissions**
 /// - rauthy_admin
@@ -25,32 +28,26 @@ use tokio::sync::oneshot;
     ),
 )]
 #[get("/blacklist")]
-pub async fn get_blacklist(
-    data: web::Data<AppState>,
-    principal: ReqPrincipal,
-) -> Result<HttpResponse, ErrorResponse> {
+pub async fn get_blacklist(principal: ReqPrincipal) -> Result<HttpResponse, ErrorResponse> {
     principal.validate_api_key_or_admin_session(AccessGroup::Blacklist, AccessRights::Read)?;
 
-    let (tx, rx) = oneshot::channel();
-    data.tx_ip_blacklist
-        .send_async(IpBlacklistReq::GetBlacklistedIps(tx))
-        .await
-        .unwrap();
-
-    let ips = rx
-        .await
-        .unwrap()
+    let ips = IpBlacklistEntry::find_all()
+        .await?
         .into_iter()
-        .map(|(ip, exp)| BlacklistedIp {
-            ip,
-            exp: exp.timestamp(),
+        .map(|entry| BlacklistedIp {
+            ip: entry.cidr,
+            exp: entry.exp,
+            reason: entry.reason,
         })
         .collect();
 
     Ok(HttpResponse::Ok().json(BlacklistResponse { ips }))
 }
 
-/// Manually blacklist an IP
+/// Manually blacklist an IP or a CIDR range
+///
+/// Without an `exp`, the IP will be blacklisted permanently. IPs, which are fully covered by
+/// the allowlist, can't be blacklisted.
 ///
 /// **Permissions**
 /// - rauthy_admin
@@ -61,6 +58,7 @@ pub async fn get_blacklist(
     request_body = IpBlacklistRequest,
     responses(
         (status = 200, description = "Ok"),
+        (status = 400, description = "BadRequest", body = ErrorResponse),
         (status = 401, description = "Unauthorized", body = ErrorResponse),
         (status = 403, description = "Forbidden", body = ErrorResponse),
     ),
@@ -69,14 +67,23 @@ pub async fn get_blacklist(
 pub async fn post_blacklist(
     data: web::Data<AppState>,
     principal: ReqPrincipal,
-    payload: Json<IpBlacklistRequest>,
+    Json(payload): Json<IpBlacklistRequest>,
 ) -> Result<HttpResponse, ErrorResponse> {
     principal.validate_api_key_or_admin_session(AccessGroup::Blacklist, AccessRights::Create)?;
 
+    let cidr = parse_cidr(&payload.ip)?;
+    if IpAllowlistEntry::is_allowed(&cidr).await? {
+        return Err(ErrorResponse::new(
+            ErrorResponseType::BadRequest,
+            "This IP is part of the allowlist",
+        ));
+    }
+
     data.tx_events
         .send_async(Event::ip_blacklisted(
-            DateTime::from_timestamp(payload.exp, 0).unwrap_or_default(),
-            payload.ip.to_string(),
+            payload.exp.and_then(|ts| DateTime::from_timestamp(ts, 0)),
+            cidr_to_string(&cidr),
+            payload.reason,
         ))
         .await
         .unwrap();
@@ -84,7 +91,7 @@ pub async fn post_blacklist(
     Ok(HttpResponse::Ok().finish())
 }
 
-/// Manually delete a blacklisted IP
+/// Manually delete a blacklisted IP or CIDR range
 ///
 /// **Permissions**
 /// - rauthy_admin
@@ -94,11 +101,12 @@ pub async fn post_blacklist(
     tag = "blacklist",
     responses(
         (status = 200, description = "Ok"),
+        (status = 400, description = "BadRequest", body = ErrorResponse),
         (status = 401, description = "Unauthorized", body = ErrorResponse),
         (status = 403, description = "Forbidden", body = ErrorResponse),
     ),
 )]
-#[delete("/blacklist/{ip}")]
+#[delete("/blacklist/{ip:.*}")]
 pub async fn delete_blacklist(
     data: web::Data<AppState>,
     principal: ReqPrincipal,
@@ -106,10 +114,100 @@ pub async fn delete_blacklist(
 ) -> Result<HttpResponse, ErrorResponse> {
     principal.validate_api_key_or_admin_session(AccessGroup::Blacklist, AccessRights::Delete)?;
 
+    let cidr = parse_cidr(&ip)?;
     data.tx_events
-        .send_async(Event::ip_blacklist_removed(ip.into_inner()))
+        .send_async(Event::ip_blacklist_removed(cidr_to_string(&cidr)))
         .await
         .unwrap();
 
     Ok(HttpResponse::Ok().finish())
 }
+
+/// Returns all IP's and ranges on the allowlist, which can never be blacklisted
+///
+/// **Permissions**
+/// - rauthy_admin
+#[utoipa::path(
+    get,
+    path = "/blacklist/allowlist",
+    tag = "blacklist",
+    responses(
+        (status = 200, description = "Ok", body = [AllowlistedIp]),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Forbidden", body = ErrorResponse),
+    ),
+)]
+#[get("/blacklist/allowlist")]
+pub async fn get_allowlist(principal: ReqPrincipal) -> Result<HttpResponse, ErrorResponse> {
+    principal.validate_api_key_or_admin_session(AccessGroup::Blacklist, AccessRights::Read)?;
+
+    let ips = IpAllowlistEntry::find_all()
+        .await?
+        .into_iter()
+        .map(|entry| AllowlistedIp {
+            ip: entry.cidr,
+            description: entry.description,
+            created_at: entry.created_at,
+        })
+        .collect::<Vec<_>>();
+
+    Ok(HttpResponse::Ok().json(ips))
+}
+
+/// Add an IP or a CIDR range to the allowlist
+///
+/// Allowed IPs will never be blocked, even if they are part of a blacklisted range, and
+/// failed logins from them will not lead to a blacklisting.
+///
+/// **Permissions**
+/// - rauthy_admin
+#[utoipa::path(
+    post,
+    path = "/blacklist/allowlist",
+    tag = "blacklist",
+    request_body = IpAllowlistRequest,
+    responses(
+        (status = 200, description = "Ok"),
+        (status = 400, description = "BadRequest", body = ErrorResponse),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Forbidden", body = ErrorResponse),
+    ),
+)]
+#[post("/blacklist/allowlist")]
+pub async fn post_allowlist(
+    principal: ReqPrincipal,
+    Json(payload): Json<IpAllowlistRequest>,
+) -> Result<HttpResponse, ErrorResponse> {
+    principal.validate_api_key_or_admin_session(AccessGroup::Blacklist, AccessRights::Create)?;
+
+    IpAllowlistEntry::upsert(&payload.ip, payload.description).await?;
+
+    Ok(HttpResponse::Ok().finish())
+}
+
+/// Remove an IP or a CIDR range from the allowlist
+///
+/// **Permissions**
+/// - rauthy_admin
+#[utoipa::path(
+    delete,
+    path = "/blacklist/allowlist/{ip}",
+    tag = "blacklist",
+    responses(
+        (status = 200, description = "Ok"),
+        (status = 400, description = "BadRequest", body = ErrorResponse),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Forbidden", body = ErrorResponse),
+    ),
+)]
+#[delete("/blacklist/allowlist/{ip:.*}")]
+pub async fn delete_allowlist(
+    principal: ReqPrincipal,
+    ip: web::Path<String>,
+) -> Result<HttpResponse, ErrorResponse> {
+    principal.validate_api_key_or_admin_session(AccessGroup::Blacklist, AccessRights::Delete)?;
+
+    IpAllowlistEntry::delete(&ip).await?;
+
+    Ok(HttpResponse::Ok().finish())
+}
@@ -45,7 +45,7 @@ pub async fn post_events(
     )
     .await?;
 
-    Ok(HttpResponse::Ok().json(events))
+    Ok(HttpResponse::Ok().json(events.iter().map(Event::with_geo).collect::<Vec<_>>()))
 }
 
 /// Listen to the Events SSE stream
@@ -299,6 +299,8 @@ pub async fn post_fed_cm_token(
         None,
         AuthCodeFlow::No,
         DeviceCodeFlow::No,
+        None,
+        None,
     )
     .await?;
 
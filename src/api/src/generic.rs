@@ -1,33 +1,37 @@
 use crate::{Assets, ReqPrincipal};
 use actix_web::http::header::{HeaderValue, CONTENT_TYPE};
 use actix_web::http::{header, StatusCode};
-use actix_web::{get, post, put, web, HttpRequest, HttpResponse, Responder};
+use actix_web::{delete, get, post, put, web, HttpRequest, HttpResponse, Responder};
 use chrono::Utc;
 use cryptr::EncKeys;
 use rauthy_api_types::generic::{
     AppVersionResponse, Argon2ParamsResponse, EncKeyMigrateRequest, EncKeysResponse,
     HealthResponse, I18nContent, I18nRequest, LoginTimeResponse, PasswordHashTimesRequest,
-    PasswordPolicyRequest, PasswordPolicyResponse, SearchParams, SearchParamsType,
+    PasswordPolicyParams, PasswordPolicyRequest, PasswordPolicyResponse,
+    ScopedPasswordPolicyRequest, ScopedPasswordPolicyResponse, SearchParams, SearchParamsType,
 };
 use rauthy_common::constants::{
-    APPLICATION_JSON, APP_START, HEADER_ALLOW_ALL_ORIGINS, HEADER_HTML, HEALTH_CHECK_DELAY_SECS,
-    IDX_LOGIN_TIME, RAUTHY_VERSION, SUSPICIOUS_REQUESTS_BLACKLIST, SUSPICIOUS_REQUESTS_LOG,
+    ADMIN_FORCE_MFA, APPLICATION_JSON, APP_START, HEADER_ALLOW_ALL_ORIGINS, HEADER_HTML,
+    HEALTH_CHECK_DELAY_SECS, IDX_LOGIN_TIME, RAUTHY_VERSION, SUSPICIOUS_REQUESTS_BLACKLIST,
+    SUSPICIOUS_REQUESTS_LOG,
 };
 use rauthy_common::utils::real_ip_from_req;
-use rauthy_error::ErrorResponse;
+use rauthy_error::{ErrorResponse, ErrorResponseType};
 use rauthy_models::app_state::AppState;
 use rauthy_models::database::{Cache, DB};
 use rauthy_models::entity::api_keys::{AccessGroup, AccessRights};
 use rauthy_models::entity::app_version::LatestAppVersion;
 use rauthy_models::entity::auth_providers::AuthProviderTemplate;
 use rauthy_models::entity::colors::ColorEntity;
+use rauthy_models::entity::ip_blacklist::IpBlacklistEntry;
 use rauthy_models::entity::is_db_alive;
 use rauthy_models::entity::password::{PasswordHashTimes, PasswordPolicy};
+use rauthy_models::entity::password_policies::{ScopedPasswordPolicy, ScopedPasswordPolicyEntity};
 use rauthy_models::entity::pow::PowEntity;
 use rauthy_models::entity::sessions::Session;
 use rauthy_models::entity::users::User;
 use rauthy_models::events::event::Event;
-use rauthy_models::events::ip_blacklist_handler::{IpBlacklist, IpBlacklistReq};
+use rauthy_models::events::ip_blacklist_handler::{IpBlacklist, IpBlacklistCheck, IpBlacklistReq};
 use rauthy_models::i18n::account::I18nAccount;
 use rauthy_models::i18n::authorize::I18nAuthorize;
 use rauthy_models::i18n::device::I18nDevice;
@@ -35,8 +39,10 @@ use rauthy_models::i18n::email_confirm_change_html::I18nEmailConfirmChangeHtml;
 use rauthy_models::i18n::error::I18nError;
 use rauthy_models::i18n::index::I18nIndex;
 use rauthy_models::i18n::logout::I18nLogout;
+use rauthy_models::i18n::not_me::I18nNotMe;
 use rauthy_models::i18n::password_reset::I18nPasswordReset;
 use rauthy_models::i18n::register::I18nRegister;
+use rauthy_models::i18n::unlock::I18nUnlock;
 use rauthy_models::i18n::SsrJson;
 use rauthy_models::language::Language;
 use rauthy_models::templates::{
@@ -49,6 +55,7 @@ use semver::Version;
 use std::borrow::Cow;
 use std::ops::{Add, Sub};
 use std::str::FromStr;
+use tokio::sync::oneshot;
 use tracing::{error, info, warn};
 
 #[get("/")]
@@ -109,8 +116,10 @@ pub async fn post_i18n(
         }
         I18nContent::Index => I18nIndex::build(&lang).as_json(),
         I18nContent::Logout => I18nLogout::build(&lang).as_json(),
+        I18nContent::NotMe => I18nNotMe::build(&lang).as_json(),
         I18nContent::PasswordReset => I18nPasswordReset::build(&lang).as_json(),
         I18nContent::Register => I18nRegister::build(&lang).as_json(),
+        I18nContent::Unlock => I18nUnlock::build(&lang).as_json(),
     };
 
     Ok(HttpResponse::Ok()
@@ -286,7 +295,18 @@ pub async fn get_auth_check(principal: ReqPrincipal) -> Result<HttpResponse, Err
 )]
 #[get("/auth_check_admin")]
 pub async fn get_auth_check_admin(principal: ReqPrincipal) -> Result<HttpResponse, ErrorResponse> {
-    principal.validate_admin_session()?;
+    if principal.is_scoped_admin() {
+        // scoped admins may use the Admin UI, each single API call will check the access rights
+        principal.validate_session_auth()?;
+        if *ADMIN_FORCE_MFA && !principal.has_mfa_active() {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::MfaRequired,
+                "Rauthy admin access only allowed with MFA active",
+            ));
+        }
+    } else {
+        principal.validate_admin_session()?;
+    }
     Ok(HttpResponse::Ok().finish())
 }
 
@@ -427,7 +447,10 @@ pub async fn post_password_hash_times(
         .map(|r| HttpResponse::Ok().json(r))
 }
 
-/// Returns the currently configured password policy
+/// Returns the effective password policy for the current user
+///
+/// Scoped policies for the users' groups are merged into it. With `global=true`, the global
+/// default policy is returned instead.
 ///
 /// **Permissions**
 /// - authenticated
@@ -435,15 +458,25 @@ pub async fn post_password_hash_times(
     get,
     path = "/password_policy",
     tag = "generic",
+    params(PasswordPolicyParams),
     responses(
         (status = 200, description = "Ok", body = PasswordPolicyResponse),
         (status = 401, description = "Unauthorized"),
     ),
 )]
 #[get("/password_policy")]
-pub async fn get_password_policy(principal: ReqPrincipal) -> Result<HttpResponse, ErrorResponse> {
+pub async fn get_password_policy(
+    principal: ReqPrincipal,
+    params: actix_web_validator::Query<PasswordPolicyParams>,
+) -> Result<HttpResponse, ErrorResponse> {
     principal.validate_session_auth()?;
-    let rules = PasswordPolicy::find().await?;
+
+    let rules = if params.global.unwrap_or(false) {
+        PasswordPolicy::find().await?
+    } else {
+        let user = User::find(principal.user_id()?.to_string()).await?;
+        PasswordPolicy::find_for_user(&user).await?
+    };
     Ok(HttpResponse::Ok().json(PasswordPolicyResponse::from(rules)))
 }
 
@@ -476,6 +509,119 @@ pub async fn put_password_policy(
     Ok(HttpResponse::Ok().json(PasswordPolicyResponse::from(rules)))
 }
 
+/// Returns all scoped password policies
+///
+/// **Permissions**
+/// - rauthy_admin
+#[utoipa::path(
+    get,
+    path = "/password_policies",
+    tag = "generic",
+    responses(
+        (status = 200, description = "Ok", body = [ScopedPasswordPolicyResponse]),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden"),
+    ),
+)]
+#[get("/password_policies")]
+pub async fn get_password_policies(principal: ReqPrincipal) -> Result<HttpResponse, ErrorResponse> {
+    principal.validate_api_key_or_admin_session(AccessGroup::Secrets, AccessRights::Read)?;
+
+    let policies = ScopedPasswordPolicy::find_all()
+        .await?
+        .into_iter()
+        .map(ScopedPasswordPolicyResponse::from)
+        .collect::<Vec<_>>();
+    Ok(HttpResponse::Ok().json(policies))
+}
+
+/// Creates a new scoped password policy
+///
+/// The policy replaces the global one for all members of the assigned `groups`. If a user is a
+/// member of multiple groups with a policy, the strictest value of each rule wins.
+/// For the assigned `clients`, it will be enforced during each password login. If the password
+/// does not satisfy it, the login is rejected and the user gets an E-Mail to set a new one.
+///
+/// **Permissions**
+/// - rauthy_admin
+#[utoipa::path(
+    post,
+    path = "/password_policies",
+    tag = "generic",
+    request_body = ScopedPasswordPolicyRequest,
+    responses(
+        (status = 200, description = "Ok", body = ScopedPasswordPolicyResponse),
+        (status = 400, description = "BadRequest"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden"),
+    ),
+)]
+#[post("/password_policies")]
+pub async fn post_password_policy(
+    principal: ReqPrincipal,
+    req_data: actix_web_validator::Json<ScopedPasswordPolicyRequest>,
+) -> Result<HttpResponse, ErrorResponse> {
+    principal.validate_api_key_or_admin_session(AccessGroup::Secrets, AccessRights::Create)?;
+
+    let policy = ScopedPasswordPolicyEntity::create(req_data.into_inner()).await?;
+    Ok(HttpResponse::Ok().json(ScopedPasswordPolicyResponse::from(policy)))
+}
+
+/// Modifies a scoped password policy
+///
+/// **Permissions**
+/// - rauthy_admin
+#[utoipa::path(
+    put,
+    path = "/password_policies/{id}",
+    tag = "generic",
+    request_body = ScopedPasswordPolicyRequest,
+    responses(
+        (status = 200, description = "Ok", body = ScopedPasswordPolicyResponse),
+        (status = 400, description = "BadRequest"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden"),
+        (status = 404, description = "NotFound"),
+    ),
+)]
+#[put("/password_policies/{id}")]
+pub async fn put_password_policy_scoped(
+    id: web::Path<String>,
+    principal: ReqPrincipal,
+    req_data: actix_web_validator::Json<ScopedPasswordPolicyRequest>,
+) -> Result<HttpResponse, ErrorResponse> {
+    principal.validate_api_key_or_admin_session(AccessGroup::Secrets, AccessRights::Update)?;
+
+    let policy =
+        ScopedPasswordPolicyEntity::update(&id.into_inner(), req_data.into_inner()).await?;
+    Ok(HttpResponse::Ok().json(ScopedPasswordPolicyResponse::from(policy)))
+}
+
+/// Deletes a scoped password policy
+///
+/// **Permissions**
+/// - rauthy_admin
+#[utoipa::path(
+    delete,
+    path = "/password_policies/{id}",
+    tag = "generic",
+    responses(
+        (status = 200, description = "Ok"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden"),
+    ),
+)]
+#[delete("/password_policies/{id}")]
+pub async fn delete_password_policy(
+    id: web::Path<String>,
+    principal: ReqPrincipal,
+) -> Result<HttpResponse, ErrorResponse> {
+    principal.validate_api_key_or_admin_session(AccessGroup::Secrets, AccessRights::Delete)?;
+
+    ScopedPasswordPolicyEntity::delete(&id.into_inner()).await?;
+    Ok(HttpResponse::Ok().finish())
+}
+
 /// Ping -> Pong
 #[utoipa::path(
     get,
@@ -633,6 +779,7 @@ pub async fn catch_all(
     if *SUSPICIOUS_REQUESTS_BLACKLIST > 0
         && path.len() > 1
         && suspicious_request_block::is_scan_target(path)
+        && !is_blacklisted(&data, &ip).await
     {
         warn!(
             "Blacklisting suspicious target path request '{}' from {}",
@@ -643,7 +790,10 @@ pub async fn catch_all(
         ));
         if let Err(err) = data
             .tx_ip_blacklist
-            .send_async(IpBlacklistReq::Blacklist(IpBlacklist { ip, exp }))
+            .send_async(IpBlacklistReq::Blacklist(IpBlacklist {
+                ip: ip.clone(),
+                exp: Some(exp),
+            }))
             .await
         {
             error!(
@@ -651,6 +801,18 @@ pub async fn catch_all(
                 err
             );
         }
+
+        // no event for these to not spam notifications, the other nodes will pick it up
+        // during their next sync
+        tokio::spawn(async move {
+            let reason = Some("Suspicious request".to_string());
+            if let Err(err) = IpBlacklistEntry::upsert(&ip, Some(exp.timestamp()), reason).await {
+                error!(
+                    "Error persisting suspicious request blacklist entry: {:?}",
+                    err
+                );
+            }
+        });
     }
 
     Ok(HttpResponse::MovedPermanently()
@@ -661,6 +823,30 @@ pub async fn catch_all(
         .finish())
 }
 
+/// Scanners usually fire lots of requests in parallel. IPs that are already blacklisted in
+/// memory must not create a new DB entry for each single one of them.
+async fn is_blacklisted(data: &web::Data<AppState>, ip: &str) -> bool {
+    let (tx, rx) = oneshot::channel();
+    data.tx_ip_blacklist
+        .send_async(IpBlacklistReq::BlacklistCheck(IpBlacklistCheck {
+            ip: ip.to_string(),
+            tx,
+        }))
+        .await
+        .expect("ip blacklist recv not to be closed");
+
+    match rx.await {
+        Ok(exp) => exp.is_some_and(|exp| exp > Utc::now()),
+        Err(err) => {
+            error!(
+                "oneshot recv error in catch_all - this should never happen: {:?}",
+                err
+            );
+            false
+        }
+    }
+}
+
 #[get("/v1")]
 pub async fn redirect_v1() -> HttpResponse {
     HttpResponse::MovedPermanently()
@@ -0,0 +1,289 @@
+use crate::ReqPrincipal;
+use actix_web::{delete, get, post, web, HttpRequest, HttpResponse};
+use rauthy_api_types::invitations::{UserInvitationRequest, UserInvitationResponse};
+use rauthy_common::utils::real_ip_from_req;
+use rauthy_error::{ErrorResponse, ErrorResponseType};
+use rauthy_models::app_state::AppState;
+use rauthy_models::entity::api_keys::{AccessGroup, AccessRights};
+use rauthy_models::entity::organizations::OrgMember;
+use rauthy_models::entity::principal::Principal;
+use rauthy_models::entity::user_invitations::UserInvitation;
+use rauthy_models::events::event::Event;
+
+/// Returns all user invitations
+///
+/// Delegated admins will only get the invitations they have created themselves or the ones for
+/// organizations they are an org admin for.
+///
+/// **Permissions**
+/// - rauthy_admin
+/// - scoped `Users` admin
+/// - org admin
+#[utoipa::path(
+    get,
+    path = "/users/invitations",
+    tag = "invitations",
+    responses(
+        (status = 200, description = "Ok", body = [UserInvitationResponse]),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Forbidden", body = ErrorResponse),
+    ),
+)]
+#[get("/users/invitations")]
+pub async fn get_user_invitations(principal: ReqPrincipal) -> Result<HttpResponse, ErrorResponse> {
+    let invitations = if is_full_access(&principal, AccessRights::Read) {
+        UserInvitation::find_all().await?
+    } else {
+        principal.validate_session_auth()?;
+        principal.validate_not_impersonated()?;
+
+        let user_id = principal.user_id()?;
+        let admin_of = OrgMember::find_for_user(user_id)
+            .await?
+            .into_iter()
+            .filter(|m| m.is_org_admin)
+            .map(|m| m.org_id)
+            .collect::<Vec<_>>();
+        UserInvitation::find_all()
+            .await?
+            .into_iter()
+            .filter(|i| {
+                i.created_by == user_id
+                    || i.org_id
+                        .as_ref()
+                        .map(|id| admin_of.contains(id))
+                        .unwrap_or(false)
+            })
+            .collect()
+    };
+
+    let mut res = Vec::with_capacity(invitations.len());
+    for invitation in invitations {
+        res.push(invitation.into_response()?);
+    }
+    Ok(HttpResponse::Ok().json(res))
+}
+
+/// Invites a new user
+///
+/// Creates a pending user account and sends out an E-Mail with a link to set up the account.
+/// All pre-assigned roles, groups, attributes and the organization membership will only be
+/// applied after the account setup has been finished successfully.
+///
+/// A group scoped admin can only assign groups inside its scope and an org admin can only
+/// assign its own organization. Roles and attributes can only be assigned with full access.
+///
+/// **Permissions**
+/// - rauthy_admin
+/// - scoped `Users` admin
+/// - org admin
+#[utoipa::path(
+    post,
+    path = "/users/invitations",
+    tag = "invitations",
+    request_body = UserInvitationRequest,
+    responses(
+        (status = 200, description = "Ok", body = UserInvitationResponse),
+        (status = 400, description = "BadRequest", body = ErrorResponse),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Forbidden", body = ErrorResponse),
+        (status = 404, description = "NotFound", body = ErrorResponse),
+    ),
+)]
+#[post("/users/invitations")]
+pub async fn post_user_invitation(
+    data: web::Data<AppState>,
+    req: HttpRequest,
+    payload: actix_web_validator::Json<UserInvitationRequest>,
+    principal: ReqPrincipal,
+) -> Result<HttpResponse, ErrorResponse> {
+    let payload = payload.into_inner();
+    if !is_full_access(&principal, AccessRights::Create) {
+        validate_delegated_invitation(&principal, &payload).await?;
+    }
+
+    let (invitation, user) =
+        UserInvitation::create(&data, payload, created_by(&principal)?).await?;
+
+    data.tx_events
+        .send_async(Event::new_user(
+            user.email,
+            real_ip_from_req(&req)?.to_string(),
+        ))
+        .await
+        .unwrap();
+
+    Ok(HttpResponse::Ok().json(invitation.into_response()?))
+}
+
+/// Revokes a pending user invitation
+///
+/// This will delete the pending user account as well.
+///
+/// **Permissions**
+/// - rauthy_admin
+/// - the creator of the invitation
+/// - org admin
+#[utoipa::path(
+    delete,
+    path = "/users/invitations/{id}",
+    tag = "invitations",
+    responses(
+        (status = 200, description = "Ok"),
+        (status = 400, description = "BadRequest", body = ErrorResponse),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Forbidden", body = ErrorResponse),
+        (status = 404, description = "NotFound", body = ErrorResponse),
+    ),
+)]
+#[delete("/users/invitations/{id}")]
+pub async fn delete_user_invitation(
+    id: web::Path<String>,
+    principal: ReqPrincipal,
+) -> Result<HttpResponse, ErrorResponse> {
+    let invitation = UserInvitation::find(&id.into_inner()).await?;
+    validate_invitation_access(&principal, &invitation, AccessRights::Delete).await?;
+
+    invitation.revoke().await?;
+    Ok(HttpResponse::Ok().finish())
+}
+
+/// Re-sends the E-Mail for a pending user invitation
+///
+/// Any existing link for this invitation will be invalidated.
+///
+/// **Permissions**
+/// - rauthy_admin
+/// - the creator of the invitation
+/// - org admin
+#[utoipa::path(
+    post,
+    path = "/users/invitations/{id}/resend",
+    tag = "invitations",
+    responses(
+        (status = 200, description = "Ok"),
+        (status = 400, description = "BadRequest", body = ErrorResponse),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Forbidden", body = ErrorResponse),
+        (status = 404, description = "NotFound", body = ErrorResponse),
+    ),
+)]
+#[post("/users/invitations/{id}/resend")]
+pub async fn post_user_invitation_resend(
+    data: web::Data<AppState>,
+    id: web::Path<String>,
+    principal: ReqPrincipal,
+) -> Result<HttpResponse, ErrorResponse> {
+    let invitation = UserInvitation::find(&id.into_inner()).await?;
+    validate_invitation_access(&principal, &invitation, AccessRights::Update).await?;
+
+    invitation.resend(&data).await?;
+    Ok(HttpResponse::Ok().finish())
+}
+
+/// Returns the user id for a session or the name of the API Key
+fn created_by(principal: &Principal) -> Result<String, ErrorResponse> {
+    if let Some(api_key) = &principal.api_key {
+        Ok(api_key.name.clone())
+    } else {
+        principal.user_id().map(String::from)
+    }
+}
+
+/// Full access via an API Key or an admin session, which is not restricted to any group scope.
+fn is_full_access(principal: &Principal, access_rights: AccessRights) -> bool {
+    principal
+        .validate_api_key_or_admin_session(AccessGroup::Users, access_rights)
+        .is_ok()
+        && (principal.api_key.is_some() || !principal.is_scoped_admin())
+}
+
+/// Validates an invitation from a group scoped admin or an org admin.
+async fn validate_delegated_invitation(
+    principal: &Principal,
+    payload: &UserInvitationRequest,
+) -> Result<(), ErrorResponse> {
+    principal.validate_session_auth()?;
+    principal.validate_not_impersonated()?;
+
+    let has_roles = payload
+        .roles
+        .as_ref()
+        .map(|r| !r.is_empty())
+        .unwrap_or(false);
+    let has_attrs = payload
+        .attributes
+        .as_ref()
+        .map(|a| !a.is_empty())
+        .unwrap_or(false);
+    if has_roles || has_attrs {
+        return Err(ErrorResponse::new(
+            ErrorResponseType::Forbidden,
+            "Only a rauthy_admin is allowed to pre-assign roles or attributes",
+        ));
+    }
+
+    let groups = payload.groups.as_deref().unwrap_or_default();
+    if !groups.is_empty() {
+        principal.validate_admin_role(&AccessGroup::Users, &AccessRights::Create, Some(groups))?;
+        if let Some(scope) =
+            principal.admin_role_group_scope(&AccessGroup::Users, &AccessRights::Create)
+        {
+            if groups.iter().any(|g| !scope.contains(g)) {
+                return Err(ErrorResponse::new(
+                    ErrorResponseType::Forbidden,
+                    "The user groups must stay inside your admin role group scope",
+                ));
+            }
+        }
+    }
+
+    match &payload.org_id {
+        Some(org_id) => {
+            if !OrgMember::is_org_admin(org_id, principal.user_id()?).await? {
+                return Err(ErrorResponse::new(
+                    ErrorResponseType::Forbidden,
+                    "You are not an admin of this organization",
+                ));
+            }
+        }
+        None if groups.is_empty() => {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::Forbidden,
+                "Delegated admins must assign a group inside their scope or an organization",
+            ));
+        }
+        None => {}
+    }
+
+    Ok(())
+}
+
+/// Validates the access to an existing invitation for everyone but the invited user.
+async fn validate_invitation_access(
+    principal: &Principal,
+    invitation: &UserInvitation,
+    access_rights: AccessRights,
+) -> Result<(), ErrorResponse> {
+    if is_full_access(principal, access_rights) {
+        return Ok(());
+    }
+
+    principal.validate_session_auth()?;
+    principal.validate_not_impersonated()?;
+
+    let user_id = principal.user_id()?;
+    if invitation.created_by == user_id {
+        return Ok(());
+    }
+    if let Some(org_id) = &invitation.org_id {
+        if OrgMember::is_org_admin(org_id, user_id).await? {
+            return Ok(());
+        }
+    }
+
+    Err(ErrorResponse::new(
+        ErrorResponseType::Forbidden,
+        "You are not allowed to manage this invitation",
+    ))
+}
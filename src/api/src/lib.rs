@@ -16,6 +16,7 @@ use rauthy_models::AuthStep;
 use rust_embed::RustEmbed;
 use tracing::error;
 
+pub mod admin_roles;
 pub mod api_keys;
 pub mod auth_providers;
 pub mod blacklist;
@@ -24,9 +25,12 @@ pub mod events;
 pub mod fed_cm;
 pub mod generic;
 pub mod groups;
+pub mod invitations;
 pub mod oidc;
 pub mod openapi;
+pub mod organizations;
 pub mod roles;
+pub mod saml;
 pub mod scopes;
 pub mod sessions;
 pub mod users;
@@ -58,6 +62,22 @@ pub async fn map_auth_step(
             if let Some((name, value)) = res.header_origin {
                 resp.headers_mut().insert(name, value);
             }
+            if let Some(cookie) = res.trusted_device_cookie {
+                if let Err(err) = resp.add_cookie(&cookie) {
+                    error!(
+                        "Error adding trusted device cookie in 'map_auth_step' : {}",
+                        err
+                    );
+                }
+            }
+            if let Some(cookie) = res.known_devices_cookie {
+                if let Err(err) = resp.add_cookie(&cookie) {
+                    error!(
+                        "Error adding known devices cookie in 'map_auth_step' : {}",
+                        err
+                    );
+                }
+            }
             Ok(resp)
         }
 
@@ -19,7 +19,7 @@ use rauthy_common::constants::{
     AUTH_HEADER_ROLES, AUTH_HEADER_USER, COOKIE_MFA, COOKIE_SESSION, COOKIE_SESSION_FED_CM,
     DEVICE_GRANT_CODE_LIFETIME, DEVICE_GRANT_POLL_INTERVAL, DEVICE_GRANT_RATE_LIMIT,
     EXPERIMENTAL_FED_CM_ENABLE, GRANT_TYPE_DEVICE_CODE, HEADER_HTML, HEADER_RETRY_NOT_BEFORE,
-    OPEN_USER_REG, SESSION_LIFETIME,
+    MFA_TRUSTED_DEVICE_DAYS, OPEN_USER_REG, SESSION_LIFETIME,
 };
 use rauthy_common::utils::real_ip_from_req;
 use rauthy_error::{ErrorResponse, ErrorResponseType};
@@ -35,6 +35,7 @@ use rauthy_models::entity::ip_rate_limit::DeviceIpRateLimit;
 use rauthy_models::entity::jwk::{JWKSPublicKey, JwkKeyPair, JWKS};
 use rauthy_models::entity::pow::PowEntity;
 use rauthy_models::entity::sessions::Session;
+use rauthy_models::entity::trusted_devices::TrustedDevice;
 use rauthy_models::entity::users::User;
 use rauthy_models::entity::webauthn::WebauthnCookie;
 use rauthy_models::entity::well_known::WellKnown;
@@ -45,7 +46,7 @@ use rauthy_models::templates::{
 use rauthy_models::JwtCommonClaims;
 use rauthy_service::oidc::{authorize, logout, token_info, userinfo, validation};
 use rauthy_service::token_set::TokenSet;
-use rauthy_service::{login_delay, oidc};
+use rauthy_service::{brute_force, login_delay, oidc};
 use spow::pow::Pow;
 use std::borrow::Cow;
 use std::ops::Add;
@@ -121,7 +122,12 @@ pub async fn get_authorize(
         if let Ok(user) = User::find_by_email(mfa_cookie.email.clone()).await {
             // we need to check this, because a user could deactivate MFA in another browser or
             // be deleted while still having existing mfa cookies somewhere else
-            if user.has_webauthn_enabled() {
+            // On a trusted device, the user logs in with the password only.
+            if user.has_webauthn_enabled()
+                && TrustedDevice::find_valid_from_req(&req, &user.id)
+                    .await
+                    .is_none()
+            {
                 action = FrontendAction::MfaLogin(mfa_cookie.email);
 
                 // if the user must do another MFA login anyway, we do never force a new session creation,
@@ -144,12 +150,20 @@ pub async fn get_authorize(
         return Ok(ErrorHtml::response(body, status));
     }
 
-    let auth_providers_json = AuthProviderTemplate::get_all_json_template().await?;
+    let auth_providers_json = AuthProviderTemplate::get_json_template_for_client(&client).await?;
+    let auto_redirect_provider = client
+        .auto_redirect_provider
+        .as_deref()
+        .filter(|id| client.is_provider_allowed(id))
+        .unwrap_or_default();
     let tpl_data = Some(format!(
-        "{}\n{}\n{}",
-        client.name.unwrap_or_default(),
-        client.client_uri.unwrap_or_default(),
+        "{}\n{}\n{}\n{}\n{}\n{}",
+        client.name.as_deref().unwrap_or_default(),
+        client.client_uri.as_deref().unwrap_or_default(),
         *OPEN_USER_REG,
+        client.validate_password_login().is_ok(),
+        auto_redirect_provider,
+        *MFA_TRUSTED_DEVICE_DAYS,
     ));
 
     // if the user is still authenticated and everything is valid -> immediate refresh
@@ -243,6 +257,10 @@ pub async fn get_authorize(
         (status = 202, description = "Correct credentials and no MFA Login required, adds Location header"),
         (status = 400, description = "Missing / bad input data", body = ErrorResponse),
         (status = 401, description = "Bad input or CSRF Token error", body = ErrorResponse),
+        (status = 403, description = "The E-Mail domain is bound to the upstream provider with the ID from the `message`, or password logins are not allowed for the client, or `AccessDenied` with the redirect location in the `message`, or `EmailCodeRequired` for a new device", body = ErrorResponse),
+        (status = 409, description = "The user needs to select an organization", body = ErrorResponse),
+        (status = 428, description = "A possible brute-force attack has been detected and the login needs a solved PoW", body = ErrorResponse),
+        (status = 429, description = "The IP is blacklisted or the account is temporarily locked", body = ErrorResponse),
     ),
 )]
 #[post("/oidc/authorize")]
@@ -259,11 +277,22 @@ pub async fn post_authorize(
     // TODO refactor login delay to use Instant, which is a bit cleaner
     let start = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
 
+    let ip = real_ip_from_req(&req)?;
+    // a detected brute-force attack may lock the account or require a PoW for each login
+    brute_force::check_login(&payload.email, ip, payload.pow.as_deref()).await?;
+    let email = payload.email.clone();
+
     let session = principal.get_session()?;
 
     let mut has_password_been_hashed = false;
     let mut add_login_delay = true;
     let mut user_needs_mfa = false;
+    let mut user_needs_org_selection = false;
+    let mut user_needs_provider_login = false;
+    let mut user_needs_email_code = false;
+    let mut password_login_denied = false;
+    let mut user_access_denied = false;
+    let mut user_needs_password_change = false;
 
     let res = match authorize::post_authorize(
         &data,
@@ -273,6 +302,12 @@ pub async fn post_authorize(
         &mut has_password_been_hashed,
         &mut add_login_delay,
         &mut user_needs_mfa,
+        &mut user_needs_org_selection,
+        &mut user_needs_provider_login,
+        &mut user_needs_email_code,
+        &mut password_login_denied,
+        &mut user_access_denied,
+        &mut user_needs_password_change,
     )
     .await
     {
@@ -280,10 +315,24 @@ pub async fn post_authorize(
         Err(err) => {
             debug!("{:?}", err);
             // We always must return the exact same error type, no matter what the actual error is,
-            // to prevent information enumeration. The only exception is when the user needs to add
-            // a passkey to the account while having given the correct credentials. In that case,
-            // we return the original error to be able to display the info message in the UI.
-            if user_needs_mfa {
+            // to prevent information enumeration. The only exceptions are when the user needs to add
+            // a passkey to the account or needs to select an organization while having given the
+            // correct credentials. In that case, we return the original error to be able to
+            // display the info message or selection in the UI. The same goes for E-Mail domains
+            // bound to an upstream provider and clients without password logins, which only
+            // depend on public information. A denied client access happens after a successful
+            // authentication as well and will be forwarded to the client as `access_denied`.
+            // A new device, which must be confirmed with an E-Mail code, is only known after
+            // the correct password has been given too, as well as a password, which does not
+            // satisfy the password policy of the client.
+            if user_needs_mfa
+                || user_needs_org_selection
+                || user_needs_provider_login
+                || user_needs_email_code
+                || password_login_denied
+                || user_access_denied
+                || user_needs_password_change
+            {
                 // in this case, we can return directly without any login delay
                 return Err(err);
             }
@@ -299,8 +348,15 @@ pub async fn post_authorize(
         }
     };
 
-    let ip = real_ip_from_req(&req)?;
-    login_delay::handle_login_delay(&data, ip, start, res, has_password_been_hashed).await
+    login_delay::handle_login_delay(
+        &data,
+        ip,
+        start,
+        res,
+        has_password_been_hashed,
+        Some(&email),
+    )
+    .await
 }
 
 /// Immediate login refresh with valid session
@@ -317,6 +373,8 @@ pub async fn post_authorize(
         (status = 202, description = "Accepted"),
         (status = 400, description = "BadRequest", body = ErrorResponse),
         (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "`AccessDenied` with the redirect location in the `message`", body = ErrorResponse),
+        (status = 409, description = "The user needs to select an organization", body = ErrorResponse),
     ),
 )]
 #[post("/oidc/authorize/refresh")]
@@ -338,9 +396,15 @@ pub async fn post_authorize_refresh(
     )
     .await?;
 
-    let auth_step =
-        authorize::post_authorize_refresh(session, client, header_origin, req_data.into_inner())
-            .await?;
+    let auth_step = authorize::post_authorize_refresh(
+        &data,
+        &req,
+        session,
+        client,
+        header_origin,
+        req_data.into_inner(),
+    )
+    .await?;
     map_auth_step(auth_step, &req).await
 }
 
@@ -751,6 +815,7 @@ pub async fn post_session(
         groups: session.groups.as_deref().map(|v| v.into()),
         exp: OffsetDateTime::from_unix_timestamp(session.exp).unwrap(),
         timeout,
+        impersonated_by: session.impersonated_by.as_deref().map(|v| v.into()),
         state: SessionState::from(session.state()?),
     };
 
@@ -803,6 +868,7 @@ pub async fn get_session_info(data: web::Data<AppState>, principal: ReqPrincipal
         groups: session.groups.as_deref().map(|v| v.into()),
         exp: OffsetDateTime::from_unix_timestamp(session.exp).unwrap(),
         timeout,
+        impersonated_by: session.impersonated_by.as_deref().map(|v| v.into()),
         state: SessionState::from(
             session
                 .state()
@@ -855,6 +921,7 @@ pub async fn get_session_xsrf(
         groups: session.groups.as_deref().map(|v| v.into()),
         exp: OffsetDateTime::from_unix_timestamp(session.exp).unwrap(),
         timeout,
+        impersonated_by: session.impersonated_by.as_deref().map(|v| v.into()),
         state: SessionState::from(session.state()?),
     };
     Ok(HttpResponse::Ok().json(info))
@@ -875,6 +942,7 @@ pub async fn get_session_xsrf(
         (status = 200, description = "Ok", body = TokenSet),
         (status = 400, description = "BadRequest", body = ErrorResponse),
         (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "The user has no access to the client", body = OAuth2ErrorResponse),
         (status = 404, description = "NotFound", body = ErrorResponse),
     ),
 )]
@@ -896,6 +964,11 @@ pub async fn post_token(
 
     let start = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
     let has_password_been_hashed = payload.grant_type == "password";
+    let email = if has_password_been_hashed {
+        payload.username.clone()
+    } else {
+        None
+    };
 
     let res = match oidc::get_token_set(payload.into_inner(), &data, req).await {
         Ok((token_set, headers)) => {
@@ -908,6 +981,13 @@ pub async fn post_token(
         }
         Err(err) => {
             error!("{}", err.message);
+            if err.error == ErrorResponseType::AccessDenied {
+                // the credentials were valid, but the user has no access to this client
+                return Ok(HttpResponse::Forbidden().json(OAuth2ErrorResponse {
+                    error: OAuth2ErrorTypeResponse::AccessDenied,
+                    error_description: Some(err.message),
+                }));
+            }
             if !has_password_been_hashed {
                 return Err(err);
             }
@@ -916,7 +996,15 @@ pub async fn post_token(
         }
     };
 
-    login_delay::handle_login_delay(&data, ip, start, res, has_password_been_hashed).await
+    login_delay::handle_login_delay(
+        &data,
+        ip,
+        start,
+        res,
+        has_password_been_hashed,
+        email.as_deref(),
+    )
+    .await
 }
 
 /// The token introspection endpoint for OAuth2
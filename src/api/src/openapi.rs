@@ -1,11 +1,12 @@
 use crate::{
-    api_keys, auth_providers, blacklist, clients, events, fed_cm, generic, groups, oidc, roles,
-    scopes, sessions, users,
+    admin_roles, api_keys, auth_providers, blacklist, clients, events, fed_cm, generic, groups,
+    invitations, oidc, organizations, roles, saml, scopes, sessions, users,
 };
 use actix_web::web;
 use rauthy_api_types::{
-    api_keys::*, auth_providers::*, blacklist::*, clients::*, events::*, fed_cm::*, generic::*,
-    groups::*, oidc::*, roles::*, scopes::*, sessions::*, users::*,
+    admin_roles::*, api_keys::*, auth_providers::*, blacklist::*, clients::*, events::*, fed_cm::*,
+    generic::*, groups::*, invitations::*, oidc::*, organizations::*, roles::*, saml::*, scopes::*,
+    sessions::*, users::*,
 };
 use rauthy_common::constants::{PROXY_MODE, RAUTHY_VERSION};
 use rauthy_error::{ErrorResponse, ErrorResponseType};
@@ -19,6 +20,11 @@ use utoipa::{openapi, OpenApi};
 #[derive(OpenApi)]
 #[openapi(
     paths(
+        admin_roles::get_admin_roles,
+        admin_roles::post_admin_role,
+        admin_roles::put_admin_role,
+        admin_roles::delete_admin_role,
+
         api_keys::get_api_keys,
         api_keys::post_api_key,
         api_keys::put_api_key,
@@ -29,10 +35,14 @@ use utoipa::{openapi, OpenApi};
         auth_providers::post_providers,
         auth_providers::post_provider,
         auth_providers::post_provider_lookup,
+        auth_providers::post_provider_saml_lookup,
+        auth_providers::get_provider_saml_metadata,
+        auth_providers::post_provider_saml_acs,
         auth_providers::post_provider_login,
         auth_providers::post_provider_callback,
         auth_providers::post_provider_link,
         auth_providers::delete_provider_link,
+        auth_providers::delete_provider_link_by_id,
         auth_providers::get_providers_minimal,
         auth_providers::put_provider,
         auth_providers::delete_provider,
@@ -43,6 +53,9 @@ use utoipa::{openapi, OpenApi};
         blacklist::get_blacklist,
         blacklist::post_blacklist,
         blacklist::delete_blacklist,
+        blacklist::get_allowlist,
+        blacklist::post_allowlist,
+        blacklist::delete_allowlist,
 
         clients::get_clients,
         clients::get_client_by_id,
@@ -77,6 +90,10 @@ use utoipa::{openapi, OpenApi};
         generic::post_password_hash_times,
         generic::get_password_policy,
         generic::put_password_policy,
+        generic::get_password_policies,
+        generic::post_password_policy,
+        generic::put_password_policy_scoped,
+        generic::delete_password_policy,
         generic::get_health,
         generic::post_pow,
         generic::get_ready,
@@ -89,6 +106,20 @@ use utoipa::{openapi, OpenApi};
         groups::put_group,
         groups::delete_group,
 
+        organizations::get_organizations,
+        organizations::post_organization,
+        organizations::put_organization,
+        organizations::delete_organization,
+        organizations::get_org_members,
+        organizations::put_org_member,
+        organizations::delete_org_member,
+        organizations::post_org_member_invite,
+
+        invitations::get_user_invitations,
+        invitations::post_user_invitation,
+        invitations::delete_user_invitation,
+        invitations::post_user_invitation_resend,
+
         oidc::get_authorize,
         oidc::post_authorize,
         oidc::post_authorize_refresh,
@@ -113,6 +144,18 @@ use utoipa::{openapi, OpenApi};
         roles::put_role,
         roles::delete_role,
 
+        saml::get_saml_metadata,
+        saml::get_saml_sso,
+        saml::post_saml_sso,
+        saml::get_saml_idp_initiated,
+        saml::get_saml_resume,
+        saml::get_saml_slo,
+        saml::post_saml_slo,
+        saml::get_client_saml,
+        saml::put_client_saml,
+        saml::post_client_saml_metadata,
+        saml::delete_client_saml,
+
         scopes::get_scopes,
         scopes::post_scope,
         scopes::put_scope,
@@ -124,15 +167,23 @@ use utoipa::{openapi, OpenApi};
 
         users::get_users,
         users::post_users,
+        users::post_users_import,
+        users::get_users_export,
+        users::put_users_import,
         users::get_cust_attr,
         users::post_cust_attr,
         users::put_cust_attr,
         users::delete_cust_attr,
         users::get_users_register,
         users::post_users_register,
+        users::get_users_register_fields,
+        users::get_user_registrations,
+        users::post_user_registration_approve,
+        users::delete_user_registration,
         users::get_user_by_id,
         users::get_user_attr,
         users::put_user_attr,
+        users::get_user_federations,
         users::get_user_webid,
         users::get_user_webid_data,
         users::put_user_webid_data,
@@ -142,10 +193,18 @@ use utoipa::{openapi, OpenApi};
         users::post_webauthn_auth_finish,
         users::delete_webauthn,
         users::post_webauthn_reg_start,
+        users::get_user_trusted_devices,
+        users::delete_user_trusted_device,
         users::post_user_password_request_reset,
         users::get_user_by_email,
         users::put_user_by_id,
         users::put_user_self,
+        users::post_user_impersonate,
+        users::post_user_unlock,
+        users::get_user_unlock,
+        users::post_user_unlock_link,
+        users::get_user_not_me,
+        users::post_user_not_me,
         users::post_user_self_convert_passkey,
         users::delete_user_by_id,
     ),
@@ -165,6 +224,7 @@ use utoipa::{openapi, OpenApi};
             entity::scopes::Scope,
             entity::webauthn::WebauthnAdditionalData,
             entity::webauthn::WebauthnLoginReq,
+            entity::webauthn::WebauthnLoginAccessCheck,
             entity::webauthn::WebauthnServiceReq,
             entity::well_known::WellKnown,
 
@@ -176,6 +236,7 @@ use utoipa::{openapi, OpenApi};
             EventLevel,
             EventType,
             JktClaim,
+            OrgClaim,
             JwkKeyPairAlg,
             JwkKeyPairType,
             Language,
@@ -189,9 +250,11 @@ use utoipa::{openapi, OpenApi};
             AuthCodeRequest,
             AuthRequest,
             IpBlacklistRequest,
+            IpAllowlistRequest,
             ColorsRequest,
             DeviceGrantRequest,
             EncKeyMigrateRequest,
+            AdminRoleRequest,
             FedCMAssertionRequest,
             FedCMClientMetadataRequest,
             LoginRequest,
@@ -208,15 +271,33 @@ use utoipa::{openapi, OpenApi};
             NewUserRequest,
             NewUserRegistrationRequest,
             NewRoleRequest,
+            OrgInviteRequest,
+            OrgMemberRequest,
+            OrgRequest,
             PaginationParams,
             PasswordHashTimesRequest,
+            PasswordPolicyParams,
             PasswordPolicyRequest,
             PasswordResetRequest,
+            ProviderClaimMapping,
+            ProviderClaimMappingTarget,
             ProviderRequest,
             ProviderLoginRequest,
             ProviderLookupRequest,
             ProviderCallbackRequest,
+            ProviderSamlAcsRequest,
+            ProviderSamlAttrMapping,
+            ProviderSamlClaim,
+            ProviderSamlLookupRequest,
+            ProviderUserinfoMapping,
             RequestResetRequest,
+            SamlAttrMapping,
+            SamlAttrSource,
+            SamlClientRequest,
+            SamlMetadataRequest,
+            SamlNameIdFormat,
+            ScopedPasswordPolicyRequest,
+            SamlRequestParams,
             ScopeRequest,
             SessionState,
             TokenRequest,
@@ -228,24 +309,34 @@ use utoipa::{openapi, OpenApi};
             UserAttrConfigRequest,
             UserAttrValueRequest,
             UserAttrValuesUpdateRequest,
+            UserImportRequest,
             WebauthnRegStartRequest,
             WebauthnRegFinishRequest,
             WebauthnAuthStartRequest,
             WebauthnAuthFinishRequest,
             WebIdRequest,
 
+            AdminRoleResponse,
             ApiKeyResponse,
             ApiKeysResponse,
             AppVersionResponse,
+            AllowlistedIp,
             BlacklistResponse,
             BlacklistedIp,
             PasswordResetResponse,
+            OrgMemberResponse,
+            OrgResponse,
+            OrgSelection,
+            UserInvitationRequest,
+            UserInvitationResponse,
+            UserRegistrationResponse,
             LoginTimeResponse,
             ClientResponse,
             DeviceCodeResponse,
             DynamicClientResponse,
             ClientSecretResponse,
             EncKeysResponse,
+            GeoLocationResponse,
             HealthResponse,
             JWKSCerts,
             JWKSPublicKeyCerts,
@@ -256,14 +347,22 @@ use utoipa::{openapi, OpenApi};
             ProviderResponse,
             ProviderLinkedUserResponse,
             ProviderLookupResponse,
+            ProviderSamlLookupResponse,
+            SamlClientResponse,
             ScopeResponse,
+            ScopedPasswordPolicyResponse,
             SessionResponse,
             SessionInfoResponse,
             TokenInfo,
             UserAttrConfigResponse,
             UserAttrConfigValueResponse,
             UserAttrValueResponse,
+            TrustedDeviceResponse,
             UserAttrValuesResponse,
+            UserFederationResponse,
+            UserImportErrorResponse,
+            UserImportResponse,
+            UserUpsertResponse,
             Userinfo,
             UserValuesResponse,
             UserAccountTypeResponse,
@@ -280,6 +379,7 @@ use utoipa::{openapi, OpenApi};
     tags(
         (name = "oidc", description = "OpenID Connect endpoints"),
         (name = "clients", description = "OIDC Clients"),
+        (name = "saml", description = "SAML 2.0 IdP endpoints"),
         (name = "users", description = "Users endpoints"),
         (name = "mfa", description = "MFA endpoints"),
         (name = "sessions", description = "Sessions endpoints"),
@@ -291,6 +391,9 @@ use utoipa::{openapi, OpenApi};
         (name = "health", description = "Ping, Health, Ready Check"),
         (name = "blacklist", description = "IP Blacklist endpoints"),
         (name = "api_keys", description = "API Keys endpoints"),
+        (name = "admin_roles", description = "Scoped admin roles endpoints"),
+        (name = "organizations", description = "Organizations endpoints"),
+        (name = "invitations", description = "User invitations endpoints"),
         (name = "generic", description = "Generic endpoints"),
         (name = "webid", description = "WebID endpoints"),
         (name = "fed_cm", description = "Experimental FedCM endpoints"),
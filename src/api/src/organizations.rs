@@ -0,0 +1,395 @@
+use crate::ReqPrincipal;
+use actix_web::{delete, get, post, put, web, HttpRequest, HttpResponse};
+use rauthy_api_types::organizations::{
+    OrgInviteRequest, OrgMemberRequest, OrgMemberResponse, OrgRequest, OrgResponse,
+};
+use rauthy_common::utils::real_ip_from_req;
+use rauthy_error::{ErrorResponse, ErrorResponseType};
+use rauthy_models::app_state::AppState;
+use rauthy_models::entity::api_keys::{AccessGroup, AccessRights};
+use rauthy_models::entity::organizations::{OrgMember, Organization};
+use rauthy_models::entity::principal::Principal;
+use rauthy_models::entity::users::User;
+use rauthy_models::events::event::Event;
+
+/// Returns all organizations
+///
+/// A user without admin access will only get the organizations they are an org admin for.
+///
+/// **Permissions**
+/// - rauthy_admin
+/// - org admin
+#[utoipa::path(
+    get,
+    path = "/organizations",
+    tag = "organizations",
+    responses(
+        (status = 200, description = "Ok", body = [OrgResponse]),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Forbidden", body = ErrorResponse),
+    ),
+)]
+#[get("/organizations")]
+pub async fn get_organizations(principal: ReqPrincipal) -> Result<HttpResponse, ErrorResponse> {
+    let orgs = if principal
+        .validate_api_key_or_admin_session(AccessGroup::Organizations, AccessRights::Read)
+        .is_ok()
+    {
+        Organization::find_all().await?
+    } else {
+        principal.validate_session_auth()?;
+        principal.validate_not_impersonated()?;
+
+        let user_id = principal.user_id()?;
+        let admin_of = OrgMember::find_for_user(user_id)
+            .await?
+            .into_iter()
+            .filter(|m| m.is_org_admin)
+            .map(|m| m.org_id)
+            .collect::<Vec<_>>();
+        Organization::find_all()
+            .await?
+            .into_iter()
+            .filter(|o| admin_of.contains(&o.id))
+            .collect()
+    };
+
+    let res = orgs.into_iter().map(OrgResponse::from).collect::<Vec<_>>();
+    Ok(HttpResponse::Ok().json(res))
+}
+
+/// Creates a new organization
+///
+/// **Permissions**
+/// - rauthy_admin
+#[utoipa::path(
+    post,
+    path = "/organizations",
+    tag = "organizations",
+    request_body = OrgRequest,
+    responses(
+        (status = 200, description = "Ok", body = OrgResponse),
+        (status = 400, description = "BadRequest", body = ErrorResponse),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Forbidden", body = ErrorResponse),
+    ),
+)]
+#[post("/organizations")]
+pub async fn post_organization(
+    payload: actix_web_validator::Json<OrgRequest>,
+    principal: ReqPrincipal,
+) -> Result<HttpResponse, ErrorResponse> {
+    principal
+        .validate_api_key_or_admin_session(AccessGroup::Organizations, AccessRights::Create)?;
+
+    let org = Organization::create(payload.into_inner()).await?;
+    Ok(HttpResponse::Ok().json(OrgResponse::from(org)))
+}
+
+/// Modifies an organization
+///
+/// Roles that are removed from the organization will be removed from all its members as well.
+///
+/// **Permissions**
+/// - rauthy_admin
+#[utoipa::path(
+    put,
+    path = "/organizations/{id}",
+    tag = "organizations",
+    request_body = OrgRequest,
+    responses(
+        (status = 200, description = "Ok", body = OrgResponse),
+        (status = 400, description = "BadRequest", body = ErrorResponse),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Forbidden", body = ErrorResponse),
+        (status = 404, description = "NotFound", body = ErrorResponse),
+    ),
+)]
+#[put("/organizations/{id}")]
+pub async fn put_organization(
+    id: web::Path<String>,
+    payload: actix_web_validator::Json<OrgRequest>,
+    principal: ReqPrincipal,
+) -> Result<HttpResponse, ErrorResponse> {
+    principal
+        .validate_api_key_or_admin_session(AccessGroup::Organizations, AccessRights::Update)?;
+
+    let org = Organization::update(&id.into_inner(), payload.into_inner()).await?;
+    Ok(HttpResponse::Ok().json(OrgResponse::from(org)))
+}
+
+/// Deletes an organization and all its memberships
+///
+/// **Permissions**
+/// - rauthy_admin
+#[utoipa::path(
+    delete,
+    path = "/organizations/{id}",
+    tag = "organizations",
+    responses(
+        (status = 200, description = "Ok"),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Forbidden", body = ErrorResponse),
+    ),
+)]
+#[delete("/organizations/{id}")]
+pub async fn delete_organization(
+    id: web::Path<String>,
+    principal: ReqPrincipal,
+) -> Result<HttpResponse, ErrorResponse> {
+    principal
+        .validate_api_key_or_admin_session(AccessGroup::Organizations, AccessRights::Delete)?;
+
+    Organization::delete(&id.into_inner()).await?;
+    Ok(HttpResponse::Ok().finish())
+}
+
+/// Returns all members of an organization
+///
+/// **Permissions**
+/// - rauthy_admin
+/// - org admin
+#[utoipa::path(
+    get,
+    path = "/organizations/{id}/members",
+    tag = "organizations",
+    responses(
+        (status = 200, description = "Ok", body = [OrgMemberResponse]),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Forbidden", body = ErrorResponse),
+    ),
+)]
+#[get("/organizations/{id}/members")]
+pub async fn get_org_members(
+    id: web::Path<String>,
+    principal: ReqPrincipal,
+) -> Result<HttpResponse, ErrorResponse> {
+    let org_id = id.into_inner();
+    validate_org_access(&principal, &org_id, AccessRights::Read).await?;
+
+    let members = OrgMember::find_for_org(&org_id).await?;
+    let mut res = Vec::with_capacity(members.len());
+    for member in members {
+        let user = User::find(member.user_id.clone()).await?;
+        res.push(member.into_response(user.email));
+    }
+
+    Ok(HttpResponse::Ok().json(res))
+}
+
+/// Adds an existing user to an organization or modifies an existing membership
+///
+/// Org admins can only modify existing members, which are no org admins themselves.
+/// The `is_org_admin` flag can only be changed with full admin access.
+///
+/// **Permissions**
+/// - rauthy_admin
+/// - org admin
+#[utoipa::path(
+    put,
+    path = "/organizations/{id}/members/{user_id}",
+    tag = "organizations",
+    request_body = OrgMemberRequest,
+    responses(
+        (status = 200, description = "Ok", body = OrgMemberResponse),
+        (status = 400, description = "BadRequest", body = ErrorResponse),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Forbidden", body = ErrorResponse),
+        (status = 404, description = "NotFound", body = ErrorResponse),
+    ),
+)]
+#[put("/organizations/{id}/members/{user_id}")]
+pub async fn put_org_member(
+    path: web::Path<(String, String)>,
+    payload: actix_web_validator::Json<OrgMemberRequest>,
+    principal: ReqPrincipal,
+) -> Result<HttpResponse, ErrorResponse> {
+    let (org_id, user_id) = path.into_inner();
+    let payload = payload.into_inner();
+    let is_full_access = validate_org_access(&principal, &org_id, AccessRights::Update).await?;
+
+    let org = Organization::find(&org_id).await?;
+    let user = User::find(user_id.clone()).await?;
+
+    let is_org_admin = if is_full_access {
+        let existing = OrgMember::find(&org_id, &user_id).await.ok();
+        payload
+            .is_org_admin
+            .or_else(|| existing.map(|m| m.is_org_admin))
+            .unwrap_or(false)
+    } else {
+        if payload.is_org_admin.is_some() {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::Forbidden,
+                "Only an admin can change the org admin status",
+            ));
+        }
+        let existing = OrgMember::find(&org_id, &user_id).await?;
+        if existing.is_org_admin {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::Forbidden,
+                "Org admins cannot modify other org admins",
+            ));
+        }
+        false
+    };
+
+    let member = OrgMember::try_build(&org, user.id, payload.roles, is_org_admin)?;
+    member.save().await?;
+
+    Ok(HttpResponse::Ok().json(member.into_response(user.email)))
+}
+
+/// Removes a user from an organization
+///
+/// Org admins cannot remove other org admins.
+///
+/// **Permissions**
+/// - rauthy_admin
+/// - org admin
+#[utoipa::path(
+    delete,
+    path = "/organizations/{id}/members/{user_id}",
+    tag = "organizations",
+    responses(
+        (status = 200, description = "Ok"),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Forbidden", body = ErrorResponse),
+        (status = 404, description = "NotFound", body = ErrorResponse),
+    ),
+)]
+#[delete("/organizations/{id}/members/{user_id}")]
+pub async fn delete_org_member(
+    path: web::Path<(String, String)>,
+    principal: ReqPrincipal,
+) -> Result<HttpResponse, ErrorResponse> {
+    let (org_id, user_id) = path.into_inner();
+    let is_full_access = validate_org_access(&principal, &org_id, AccessRights::Delete).await?;
+
+    let member = OrgMember::find(&org_id, &user_id).await?;
+    if !is_full_access && member.is_org_admin && principal.user_id()? != user_id {
+        return Err(ErrorResponse::new(
+            ErrorResponseType::Forbidden,
+            "Org admins cannot remove other org admins",
+        ));
+    }
+
+    OrgMember::delete(&org_id, &user_id).await?;
+    Ok(HttpResponse::Ok().finish())
+}
+
+/// Invites a user by email into an organization
+///
+/// If the user does not exist yet, a new account will be created and the user will receive
+/// the usual E-Mail to set up the account. In this case, `given_name` is required.
+/// Existing accounts can only be added directly by a `rauthy_admin` or an API key, because
+/// they would join the organization without their consent otherwise.
+///
+/// **Permissions**
+/// - rauthy_admin
+/// - org admin
+#[utoipa::path(
+    post,
+    path = "/organizations/{id}/members/invite",
+    tag = "organizations",
+    request_body = OrgInviteRequest,
+    responses(
+        (status = 200, description = "Ok", body = OrgMemberResponse),
+        (status = 400, description = "BadRequest", body = ErrorResponse),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Forbidden", body = ErrorResponse),
+        (status = 404, description = "NotFound", body = ErrorResponse),
+    ),
+)]
+#[post("/organizations/{id}/members/invite")]
+pub async fn post_org_member_invite(
+    data: web::Data<AppState>,
+    req: HttpRequest,
+    id: web::Path<String>,
+    payload: actix_web_validator::Json<OrgInviteRequest>,
+    principal: ReqPrincipal,
+) -> Result<HttpResponse, ErrorResponse> {
+    let org_id = id.into_inner();
+    let payload = payload.into_inner();
+    let is_full_access = validate_org_access(&principal, &org_id, AccessRights::Create).await?;
+
+    let org = Organization::find(&org_id).await?;
+
+    let user = match User::find_by_email(payload.email.clone()).await {
+        Ok(user) => {
+            if !is_full_access {
+                return Err(ErrorResponse::new(
+                    ErrorResponseType::Forbidden,
+                    "Existing accounts can only be added to an organization by a rauthy_admin",
+                ));
+            }
+            if OrgMember::find(&org_id, &user.id).await.is_ok() {
+                return Err(ErrorResponse::new(
+                    ErrorResponseType::BadRequest,
+                    "User is already a member of this organization",
+                ));
+            }
+            user
+        }
+        Err(err) if err.error == ErrorResponseType::NotFound => {
+            let Some(given_name) = payload.given_name else {
+                return Err(ErrorResponse::new(
+                    ErrorResponseType::BadRequest,
+                    "'given_name' is required when inviting a new user",
+                ));
+            };
+            let new_user = User {
+                email: payload.email.to_lowercase(),
+                given_name,
+                family_name: payload.family_name,
+                ..Default::default()
+            };
+            let user = User::create(&data, new_user, None).await?;
+
+            data.tx_events
+                .send_async(Event::new_user(
+                    user.email.clone(),
+                    real_ip_from_req(&req)?.to_string(),
+                ))
+                .await
+                .unwrap();
+
+            user
+        }
+        Err(err) => return Err(err),
+    };
+
+    let member = OrgMember::try_build(&org, user.id, payload.roles, false)?;
+    member.save().await?;
+
+    Ok(HttpResponse::Ok().json(member.into_response(user.email)))
+}
+
+/// Validates the access to the members of the given organization.
+///
+/// Returns `true` if the principal has full access via an admin session or an API key,
+/// or `false` if it is an org admin for this organization.
+async fn validate_org_access(
+    principal: &Principal,
+    org_id: &str,
+    access_rights: AccessRights,
+) -> Result<bool, ErrorResponse> {
+    if principal
+        .validate_api_key_or_admin_session(AccessGroup::Organizations, access_rights)
+        .is_ok()
+    {
+        return Ok(true);
+    }
+
+    principal.validate_session_auth()?;
+    principal.validate_not_impersonated()?;
+
+    if OrgMember::is_org_admin(org_id, principal.user_id()?).await? {
+        Ok(false)
+    } else {
+        Err(ErrorResponse::new(
+            ErrorResponseType::Forbidden,
+            "You are not an admin of this organization",
+        ))
+    }
+}
@@ -0,0 +1,333 @@
+use crate::ReqPrincipal;
+use actix_web::http::header::CONTENT_TYPE;
+use actix_web::{delete, get, post, put, web, HttpRequest, HttpResponse, ResponseError};
+use rauthy_api_types::saml::{
+    SamlClientRequest, SamlClientResponse, SamlIdpInitiatedParams, SamlMetadataRequest,
+    SamlRequestParams, SamlResumeParams,
+};
+use rauthy_error::{ErrorResponse, ErrorResponseType};
+use rauthy_models::app_state::AppState;
+use rauthy_models::entity::api_keys::{AccessGroup, AccessRights};
+use rauthy_models::entity::colors::ColorEntity;
+use rauthy_models::language::Language;
+use rauthy_models::templates::{Error1Html, ErrorHtml};
+use rauthy_service::saml;
+use rauthy_service::saml::SamlBinding;
+
+/// SAML endpoints are navigated to by the browser -> render errors as HTML
+async fn error_html(req: &HttpRequest, err: ErrorResponse) -> HttpResponse {
+    let colors = ColorEntity::find_rauthy().await.unwrap_or_default();
+    let lang = Language::try_from(req).unwrap_or_default();
+    let status = err.status_code();
+    let body = Error1Html::build(&colors, &lang, status, Some(err.message));
+    ErrorHtml::response(body, status)
+}
+
+/// SAML 2.0 IdP metadata
+///
+/// Contains the signing certificates for all currently existing `RS256` keys. Service Providers
+/// should refresh this regularly, because the certificates change with each JWK rotation.
+#[utoipa::path(
+    get,
+    path = "/saml/metadata",
+    tag = "saml",
+    responses(
+        (status = 200, description = "Ok"),
+    ),
+)]
+#[get("/saml/metadata")]
+pub async fn get_saml_metadata(data: web::Data<AppState>) -> Result<HttpResponse, ErrorResponse> {
+    let xml = saml::idp_metadata(&data).await?;
+    Ok(HttpResponse::Ok()
+        .insert_header((CONTENT_TYPE, "application/samlmetadata+xml"))
+        .body(xml))
+}
+
+/// SAML Single Sign-On with the `HTTP-Redirect` binding
+#[utoipa::path(
+    get,
+    path = "/saml/sso",
+    tag = "saml",
+    params(SamlRequestParams),
+    responses(
+        (status = 302, description = "Redirect to the login"),
+        (status = 400, description = "BadRequest"),
+        (status = 404, description = "NotFound"),
+    ),
+)]
+#[get("/saml/sso")]
+pub async fn get_saml_sso(
+    data: web::Data<AppState>,
+    req: HttpRequest,
+    params: actix_web_validator::Query<SamlRequestParams>,
+) -> HttpResponse {
+    match saml::sso(&data, &req, params.into_inner(), SamlBinding::Redirect).await {
+        Ok(resp) => resp,
+        Err(err) => error_html(&req, err).await,
+    }
+}
+
+/// SAML Single Sign-On with the `HTTP-POST` binding
+#[utoipa::path(
+    post,
+    path = "/saml/sso",
+    tag = "saml",
+    request_body(content = SamlRequestParams, content_type = "application/x-www-form-urlencoded"),
+    responses(
+        (status = 302, description = "Redirect to the login"),
+        (status = 400, description = "BadRequest"),
+        (status = 404, description = "NotFound"),
+    ),
+)]
+#[post("/saml/sso")]
+pub async fn post_saml_sso(
+    data: web::Data<AppState>,
+    req: HttpRequest,
+    params: actix_web_validator::Form<SamlRequestParams>,
+) -> HttpResponse {
+    match saml::sso(&data, &req, params.into_inner(), SamlBinding::Post).await {
+        Ok(resp) => resp,
+        Err(err) => error_html(&req, err).await,
+    }
+}
+
+/// Starts an IdP-initiated SAML login for the given client
+#[utoipa::path(
+    get,
+    path = "/saml/init/{client_id}",
+    tag = "saml",
+    params(SamlIdpInitiatedParams),
+    responses(
+        (status = 302, description = "Redirect to the login"),
+        (status = 404, description = "NotFound"),
+    ),
+)]
+#[get("/saml/init/{client_id}")]
+pub async fn get_saml_idp_initiated(
+    data: web::Data<AppState>,
+    req: HttpRequest,
+    client_id: web::Path<String>,
+    params: actix_web_validator::Query<SamlIdpInitiatedParams>,
+) -> HttpResponse {
+    match saml::idp_initiated(
+        &data,
+        client_id.into_inner(),
+        params.into_inner().relay_state,
+    )
+    .await
+    {
+        Ok(resp) => resp,
+        Err(err) => error_html(&req, err).await,
+    }
+}
+
+/// Internal redirect target after a successful login, which posts the assertion to the SP
+#[utoipa::path(
+    get,
+    path = "/saml/resume",
+    tag = "saml",
+    params(SamlResumeParams),
+    responses(
+        (status = 200, description = "Auto-submitting form to the SPs ACS URL"),
+        (status = 401, description = "Unauthorized"),
+    ),
+)]
+#[get("/saml/resume")]
+pub async fn get_saml_resume(
+    data: web::Data<AppState>,
+    req: HttpRequest,
+    params: actix_web_validator::Query<SamlResumeParams>,
+    principal: ReqPrincipal,
+) -> HttpResponse {
+    match saml::resume(&data, params.into_inner(), principal.session.as_ref()).await {
+        Ok(resp) => resp,
+        Err(err) => error_html(&req, err).await,
+    }
+}
+
+/// SAML Single Logout with the `HTTP-Redirect` binding
+#[utoipa::path(
+    get,
+    path = "/saml/slo",
+    tag = "saml",
+    params(SamlRequestParams),
+    responses(
+        (status = 200, description = "Auto-submitting form with the LogoutResponse"),
+        (status = 400, description = "BadRequest"),
+    ),
+)]
+#[get("/saml/slo")]
+pub async fn get_saml_slo(
+    data: web::Data<AppState>,
+    req: HttpRequest,
+    params: actix_web_validator::Query<SamlRequestParams>,
+    principal: ReqPrincipal,
+) -> HttpResponse {
+    let session = principal.into_inner().session;
+    match saml::slo(
+        &data,
+        &req,
+        params.into_inner(),
+        SamlBinding::Redirect,
+        session,
+    )
+    .await
+    {
+        Ok(resp) => resp,
+        Err(err) => error_html(&req, err).await,
+    }
+}
+
+/// SAML Single Logout with the `HTTP-POST` binding
+#[utoipa::path(
+    post,
+    path = "/saml/slo",
+    tag = "saml",
+    request_body(content = SamlRequestParams, content_type = "application/x-www-form-urlencoded"),
+    responses(
+        (status = 200, description = "Auto-submitting form with the LogoutResponse"),
+        (status = 400, description = "BadRequest"),
+    ),
+)]
+#[post("/saml/slo")]
+pub async fn post_saml_slo(
+    data: web::Data<AppState>,
+    req: HttpRequest,
+    params: actix_web_validator::Form<SamlRequestParams>,
+    principal: ReqPrincipal,
+) -> HttpResponse {
+    let session = principal.into_inner().session;
+    match saml::slo(&data, &req, params.into_inner(), SamlBinding::Post, session).await {
+        Ok(resp) => resp,
+        Err(err) => error_html(&req, err).await,
+    }
+}
+
+/// Returns the SAML Service Provider config for a client
+///
+/// **Permissions**
+/// - rauthy_admin
+#[utoipa::path(
+    get,
+    path = "/clients/{id}/saml",
+    tag = "clients",
+    responses(
+        (status = 200, description = "Ok", body = SamlClientResponse),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Forbidden", body = ErrorResponse),
+        (status = 404, description = "NotFound", body = ErrorResponse),
+    ),
+)]
+#[get("/clients/{id}/saml")]
+pub async fn get_client_saml(
+    data: web::Data<AppState>,
+    id: web::Path<String>,
+    principal: ReqPrincipal,
+) -> Result<HttpResponse, ErrorResponse> {
+    principal.validate_api_key_or_admin_session(AccessGroup::Clients, AccessRights::Read)?;
+
+    saml::get_client_saml(&data, id.into_inner())
+        .await
+        .map(|r| HttpResponse::Ok().json(r))
+}
+
+/// Creates or updates the SAML Service Provider config for a client
+///
+/// Rauthy will add its internal `resume_uri` to the `redirect_uris` of the client.
+///
+/// **Permissions**
+/// - rauthy_admin
+#[utoipa::path(
+    put,
+    path = "/clients/{id}/saml",
+    tag = "clients",
+    request_body = SamlClientRequest,
+    responses(
+        (status = 200, description = "Ok", body = SamlClientResponse),
+        (status = 400, description = "BadRequest", body = ErrorResponse),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Forbidden", body = ErrorResponse),
+        (status = 404, description = "NotFound", body = ErrorResponse),
+    ),
+)]
+#[put("/clients/{id}/saml")]
+pub async fn put_client_saml(
+    data: web::Data<AppState>,
+    id: web::Path<String>,
+    payload: actix_web_validator::Json<SamlClientRequest>,
+    principal: ReqPrincipal,
+) -> Result<HttpResponse, ErrorResponse> {
+    principal.validate_api_key_or_admin_session(AccessGroup::Clients, AccessRights::Update)?;
+
+    let id = id.into_inner();
+    if &id == "rauthy" {
+        return Err(ErrorResponse::new(
+            ErrorResponseType::BadRequest,
+            "The `rauthy` client cannot be a SAML Service Provider".to_string(),
+        ));
+    }
+
+    saml::put_client_saml(&data, id, payload.into_inner())
+        .await
+        .map(|r| HttpResponse::Ok().json(r))
+}
+
+/// Imports the SAML metadata of a Service Provider for a client
+///
+/// Existing attribute mappings will be kept.
+///
+/// **Permissions**
+/// - rauthy_admin
+#[utoipa::path(
+    post,
+    path = "/clients/{id}/saml/metadata",
+    tag = "clients",
+    request_body = SamlMetadataRequest,
+    responses(
+        (status = 200, description = "Ok", body = SamlClientResponse),
+        (status = 400, description = "BadRequest", body = ErrorResponse),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Forbidden", body = ErrorResponse),
+        (status = 404, description = "NotFound", body = ErrorResponse),
+    ),
+)]
+#[post("/clients/{id}/saml/metadata")]
+pub async fn post_client_saml_metadata(
+    data: web::Data<AppState>,
+    id: web::Path<String>,
+    payload: actix_web_validator::Json<SamlMetadataRequest>,
+    principal: ReqPrincipal,
+) -> Result<HttpResponse, ErrorResponse> {
+    principal.validate_api_key_or_admin_session(AccessGroup::Clients, AccessRights::Update)?;
+
+    saml::import_client_saml_metadata(&data, id.into_inner(), &payload.metadata)
+        .await
+        .map(|r| HttpResponse::Ok().json(r))
+}
+
+/// Deletes the SAML Service Provider config for a client
+///
+/// **Permissions**
+/// - rauthy_admin
+#[utoipa::path(
+    delete,
+    path = "/clients/{id}/saml",
+    tag = "clients",
+    responses(
+        (status = 200, description = "Ok"),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Forbidden", body = ErrorResponse),
+        (status = 404, description = "NotFound", body = ErrorResponse),
+    ),
+)]
+#[delete("/clients/{id}/saml")]
+pub async fn delete_client_saml(
+    data: web::Data<AppState>,
+    id: web::Path<String>,
+    principal: ReqPrincipal,
+) -> Result<HttpResponse, ErrorResponse> {
+    principal.validate_api_key_or_admin_session(AccessGroup::Clients, AccessRights::Delete)?;
+
+    saml::delete_client_saml(&data, id.into_inner()).await?;
+    Ok(HttpResponse::Ok().finish())
+}
@@ -1,7 +1,7 @@
 use crate::ReqPrincipal;
 use actix_web::{delete, get, web, HttpResponse};
 use actix_web_validator::Query;
-use rauthy_api_types::generic::PaginationParams;
+use rauthy_api_types::generic::{GeoLocationResponse, PaginationParams};
 use rauthy_api_types::sessions::{SessionResponse, SessionState};
 use rauthy_common::constants::SSP_THRESHOLD;
 use rauthy_error::ErrorResponse;
@@ -10,6 +10,7 @@ use rauthy_models::entity::continuation_token::ContinuationToken;
 use rauthy_models::entity::refresh_tokens::RefreshToken;
 use rauthy_models::entity::sessions::Session;
 use rauthy_models::entity::users::User;
+use rauthy_models::geoip;
 
 /// Returns all existing sessions
 ///
@@ -83,6 +84,8 @@ pub async fn get_sessions(
                 exp: s.exp,
                 last_seen: s.last_seen,
                 remote_ip: s.remote_ip.as_deref(),
+                remote_geo: geoip::lookup_str(s.remote_ip.as_deref())
+                    .map(GeoLocationResponse::from),
             })
         }
 
@@ -137,9 +140,20 @@ pub async fn delete_sessions_for_user(
     path: web::Path<String>,
     principal: ReqPrincipal,
 ) -> Result<HttpResponse, ErrorResponse> {
-    principal.validate_api_key_or_admin_session(AccessGroup::Sessions, AccessRights::Delete)?;
-
     let uid = path.into_inner();
+    if principal.is_scoped_admin() {
+        let user = User::find(uid.clone()).await?;
+        principal
+            .validate_api_key_or_admin_session_for_user(
+                AccessGroup::Sessions,
+                AccessRights::Delete,
+                &user,
+            )
+            .await?;
+    } else {
+        principal.validate_api_key_or_admin_session(AccessGroup::Sessions, AccessRights::Delete)?;
+    }
+
     Session::invalidate_for_user(&uid).await?;
     RefreshToken::invalidate_for_user(&uid).await?;
 
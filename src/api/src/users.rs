@@ -1,21 +1,25 @@
 use crate::ReqPrincipal;
-use actix_web::http::header::{ACCEPT, LOCATION};
+use actix_web::http::header::{ACCEPT, CONTENT_DISPOSITION, CONTENT_TYPE, LOCATION};
 use actix_web::http::StatusCode;
 use actix_web::{delete, get, post, put, web, HttpRequest, HttpResponse, ResponseError};
 use actix_web_validator::{Json, Query};
+use futures_util::StreamExt;
 use rauthy_api_types::generic::{PaginationParams, PasswordPolicyResponse};
-use rauthy_api_types::oidc::PasswordResetResponse;
+use rauthy_api_types::oidc::{PasswordResetResponse, SessionInfoResponse};
 use rauthy_api_types::users::{
     DeviceRequest, DeviceResponse, MfaPurpose, NewUserRegistrationRequest, NewUserRequest,
-    PasskeyResponse, PasswordResetRequest, RequestResetRequest, UpdateUserRequest,
-    UpdateUserSelfRequest, UserAttrConfigRequest, UserAttrConfigResponse, UserAttrValueResponse,
-    UserAttrValuesResponse, UserAttrValuesUpdateRequest, UserResponse, WebIdRequest, WebIdResponse,
+    PasskeyResponse, PasswordResetRequest, RequestResetRequest, TrustedDeviceResponse,
+    UpdateUserRequest, UpdateUserSelfRequest, UserAttrConfigRequest, UserAttrConfigResponse,
+    UserAttrValueResponse, UserAttrValuesResponse, UserAttrValuesUpdateRequest, UserExportParams,
+    UserFederationResponse, UserImportParams, UserImportRequest, UserImportResponse,
+    UserRegistrationResponse, UserResponse, UserUpsertResponse, WebIdRequest, WebIdResponse,
     WebauthnAuthFinishRequest, WebauthnAuthStartRequest, WebauthnAuthStartResponse,
     WebauthnRegFinishRequest, WebauthnRegStartRequest,
 };
 use rauthy_common::constants::{
     COOKIE_MFA, ENABLE_WEB_ID, HEADER_ALLOW_ALL_ORIGINS, HEADER_HTML, HEADER_JSON, OPEN_USER_REG,
-    PWD_CSRF_HEADER, PWD_RESET_COOKIE, SSP_THRESHOLD, TEXT_TURTLE, USER_REG_DOMAIN_BLACKLIST,
+    PWD_CSRF_HEADER, PWD_RESET_COOKIE, RAUTHY_ADMIN_ROLE, SESSION_LIFETIME_IMPERSONATION,
+    SSP_THRESHOLD, TEXT_TURTLE, USER_REG_APPROVAL, USER_REG_DOMAIN_BLACKLIST,
     USER_REG_DOMAIN_RESTRICTION, USER_REG_OPEN_REDIRECT,
 };
 use rauthy_common::utils::real_ip_from_req;
@@ -27,9 +31,13 @@ use rauthy_models::entity::clients::Client;
 use rauthy_models::entity::colors::ColorEntity;
 use rauthy_models::entity::continuation_token::ContinuationToken;
 use rauthy_models::entity::devices::DeviceEntity;
-use rauthy_models::entity::password::PasswordPolicy;
 use rauthy_models::entity::pow::PowEntity;
+use rauthy_models::entity::principal::Principal;
+use rauthy_models::entity::sessions::{Session, SessionState};
+use rauthy_models::entity::trusted_devices::TrustedDevice;
 use rauthy_models::entity::user_attr::{UserAttrConfigEntity, UserAttrValueEntity};
+use rauthy_models::entity::user_federations::UserFederation;
+use rauthy_models::entity::user_registrations::UserRegistration;
 use rauthy_models::entity::users::User;
 use rauthy_models::entity::users_values::UserValues;
 use rauthy_models::entity::webauthn;
@@ -37,9 +45,15 @@ use rauthy_models::entity::webauthn::{PasskeyEntity, WebauthnAdditionalData};
 use rauthy_models::entity::webids::WebId;
 use rauthy_models::events::event::Event;
 use rauthy_models::language::Language;
+use rauthy_models::security_notifications::SecurityNotification;
 use rauthy_models::templates::{Error1Html, Error3Html, ErrorHtml, UserRegisterHtml};
-use rauthy_service::password_reset;
+use rauthy_service::user_import::{
+    UserUpsertImport, USER_IMPORT_MAX_BYTES, USER_UPSERT_MAX_LINE_BYTES,
+};
+use rauthy_service::{brute_force, password_reset, user_export, user_import};
 use spow::pow::Pow;
+use std::ops::Add;
+use time::OffsetDateTime;
 use tracing::{error, warn};
 
 /// Returns all existing users
@@ -68,9 +82,31 @@ pub async fn get_users(
     principal: ReqPrincipal,
     params: Query<PaginationParams>,
 ) -> Result<HttpResponse, ErrorResponse> {
-    principal.validate_api_key_or_admin_session(AccessGroup::Users, AccessRights::Read)?;
+    // group scoped admins will only ever see the users inside their scope
+    let scoped_users =
+        match principal.validate_api_key_or_admin_session(AccessGroup::Users, AccessRights::Read) {
+            Ok(_) => None,
+            Err(err) => {
+                let groups = principal
+                    .admin_role_group_scope(&AccessGroup::Users, &AccessRights::Read)
+                    .unwrap_or_default();
+                if groups.is_empty() || principal.api_key.is_some() {
+                    return Err(err);
+                }
+                principal.validate_admin_role(
+                    &AccessGroup::Users,
+                    &AccessRights::Read,
+                    Some(&groups),
+                )?;
+
+                Some(User::find_in_groups_simple(&groups).await?)
+            }
+        };
 
-    let user_count = User::count().await?;
+    let user_count = match &scoped_users {
+        None => User::count().await?,
+        Some(users) => users.len() as i64,
+    };
 
     if user_count >= *SSP_THRESHOLD as i64 || params.page_size.is_some() {
         let page_size = params.page_size.unwrap_or(15) as i64;
@@ -82,8 +118,12 @@ pub async fn get_users(
             None
         };
 
-        let (users, continuation_token) =
-            User::find_paginated(continuation_token, page_size, offset, backwards).await?;
+        let (users, continuation_token) = match scoped_users {
+            None => User::find_paginated(continuation_token, page_size, offset, backwards).await?,
+            Some(users) => {
+                User::paginate_simple(users, continuation_token, page_size, offset, backwards)
+            }
+        };
         let x_page_count = (user_count as f64 / page_size as f64).ceil() as u32;
 
         if let Some(token) = continuation_token {
@@ -101,7 +141,10 @@ pub async fn get_users(
                 .json(users))
         }
     } else {
-        let users = User::find_all_simple().await?;
+        let users = match scoped_users {
+            None => User::find_all_simple().await?,
+            Some(users) => users,
+        };
         Ok(HttpResponse::Ok()
             .insert_header(("x-user-count", user_count))
             .json(users))
@@ -130,7 +173,19 @@ pub async fn post_users(
     principal: ReqPrincipal,
     user: Json<NewUserRequest>,
 ) -> Result<HttpResponse, ErrorResponse> {
-    principal.validate_api_key_or_admin_session(AccessGroup::Users, AccessRights::Create)?;
+    if principal.is_scoped_admin() && principal.api_key.is_none() {
+        // the new user must end up inside the scope and must not receive any roles
+        let groups = user.groups.clone().unwrap_or_default();
+        principal.validate_admin_role(&AccessGroup::Users, &AccessRights::Create, Some(&groups))?;
+        validate_scoped_admin_user_values(
+            &principal,
+            &AccessRights::Create,
+            (&[], &[]),
+            (&user.roles, &groups),
+        )?;
+    } else {
+        principal.validate_api_key_or_admin_session(AccessGroup::Users, AccessRights::Create)?;
+    }
 
     let user = User::create_from_new(&data, user.into_inner()).await?;
 
@@ -154,6 +209,210 @@ pub async fn post_users(
     Ok(HttpResponse::Ok().json(user.into_response(None)))
 }
 
+/// Bulk import users from another system
+///
+/// Accepts either a JSON array of `UserImportRequest`s or a CSV file with
+/// `Content-Type: text/csv`. The CSV header must use the same field names, `groups` and `roles`
+/// are comma separated inside a single quoted field.
+///
+/// Existing password hashes can be imported in `argon2id`, `bcrypt`, `pbkdf2-sha256`,
+/// `pbkdf2-sha512`, `scrypt` or `sha512-crypt` format. Legacy hashes will be upgraded to
+/// `argon2id` with the next successful login. Users without a hash will receive an E-Mail to
+/// set up their account.
+///
+/// Invalid entries and already existing E-Mails will be skipped and returned in the response.
+///
+/// Roles and password hashes can only be imported with a `rauthy_admin` session.
+///
+/// **Permissions**
+/// - rauthy_admin
+#[utoipa::path(
+    post,
+    path = "/users/import",
+    tag = "users",
+    request_body = [UserImportRequest],
+    responses(
+        (status = 200, description = "Ok", body = UserImportResponse),
+        (status = 400, description = "BadRequest", body = ErrorResponse),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Forbidden", body = ErrorResponse),
+    ),
+)]
+#[post("/users/import")]
+pub async fn post_users_import(
+    data: web::Data<AppState>,
+    req: HttpRequest,
+    principal: ReqPrincipal,
+    payload: web::Payload,
+) -> Result<HttpResponse, ErrorResponse> {
+    principal.validate_api_key_or_admin_session(AccessGroup::Users, AccessRights::Create)?;
+
+    let body = payload
+        .to_bytes_limited(USER_IMPORT_MAX_BYTES)
+        .await
+        .map_err(|_| {
+            ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                format!(
+                    "The import must not exceed {} MB",
+                    USER_IMPORT_MAX_BYTES / 1024 / 1024
+                ),
+            )
+        })?
+        .map_err(|err| {
+            ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                format!("Cannot read the payload: {}", err),
+            )
+        })?;
+
+    let is_csv = req
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.starts_with("text/csv"));
+    let users = if is_csv {
+        user_import::parse_csv(&body)?
+    } else {
+        user_import::parse_json(&body)?
+    };
+
+    // API keys and scoped admins must not be able to create admins or choose passwords
+    let privileged = principal.validate_admin_session().is_ok();
+    user_import::validate_import_privileges(&users, privileged)?;
+
+    let ip = real_ip_from_req(&req)?.to_string();
+    let res = user_import::import_users(&data, users, ip).await?;
+    Ok(HttpResponse::Ok().json(res))
+}
+
+/// Export all users as JSON lines
+///
+/// Each line contains a single user with roles, groups, `UserValues`, custom attributes and
+/// federation links. The export is streamed and can be imported again with `PUT /users/import`.
+///
+/// Password hashes and passkeys are only included with `with_secrets=true`, which requires a
+/// `rauthy_admin` session. API keys can never export them.
+///
+/// **Permissions**
+/// - rauthy_admin
+#[utoipa::path(
+    get,
+    path = "/users/export",
+    tag = "users",
+    params(UserExportParams),
+    responses(
+        (status = 200, description = "Ok", content_type = "application/x-ndjson", body = String),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Forbidden", body = ErrorResponse),
+    ),
+)]
+#[get("/users/export")]
+pub async fn get_users_export(
+    principal: ReqPrincipal,
+    params: Query<UserExportParams>,
+) -> Result<HttpResponse, ErrorResponse> {
+    let with_secrets = params.with_secrets.unwrap_or(false);
+    if with_secrets {
+        principal.validate_admin_session()?;
+    } else {
+        principal.validate_api_key_or_admin_session(AccessGroup::Users, AccessRights::Read)?;
+    }
+
+    let (tx, rx) = flume::bounded(64);
+    tokio::spawn(user_export::export_users(tx, with_secrets));
+
+    let stream = rx
+        .into_stream()
+        .map(|line| line.map(web::Bytes::from).map_err(actix_web::Error::from));
+    Ok(HttpResponse::Ok()
+        .content_type("application/x-ndjson")
+        .insert_header((CONTENT_DISPOSITION, "attachment; filename=\"users.jsonl\""))
+        .streaming(stream))
+}
+
+/// Import users from a JSON lines export
+///
+/// Accepts the format from `GET /users/export` and processes it line by line while it is
+/// being uploaded. Existing users are matched by id first and by E-Mail second and will be
+/// overwritten, all others are created. Importing the same data multiple times leads to the
+/// same result.
+///
+/// Roles, groups, custom attributes and federation links, which do not exist in this
+/// instance, are dropped. Use `dry_run=true` to get the report without changing any data.
+///
+/// Roles, password hashes and passkeys are only imported with a `rauthy_admin` session.
+/// Otherwise, existing users keep theirs and new users are created without any.
+///
+/// **Permissions**
+/// - rauthy_admin
+#[utoipa::path(
+    put,
+    path = "/users/import",
+    tag = "users",
+    params(UserImportParams),
+    request_body(content = String, content_type = "application/x-ndjson"),
+    responses(
+        (status = 200, description = "Ok", body = UserUpsertResponse),
+        (status = 400, description = "BadRequest", body = ErrorResponse),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Forbidden", body = ErrorResponse),
+    ),
+)]
+#[put("/users/import")]
+pub async fn put_users_import(
+    principal: ReqPrincipal,
+    params: Query<UserImportParams>,
+    mut payload: web::Payload,
+) -> Result<HttpResponse, ErrorResponse> {
+    principal.validate_api_key_or_admin_session(AccessGroup::Users, AccessRights::Create)?;
+    principal.validate_api_key_or_admin_session(AccessGroup::Users, AccessRights::Update)?;
+
+    let privileged = principal.validate_admin_session().is_ok();
+    let mut import = UserUpsertImport::new(params.dry_run.unwrap_or(false), privileged).await?;
+    let mut buf: Vec<u8> = Vec::with_capacity(64 * 1024);
+    while let Some(chunk) = payload.next().await {
+        let chunk = chunk.map_err(|err| {
+            ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                format!("Cannot read the payload: {}", err),
+            )
+        })?;
+
+        let mut rest = &chunk[..];
+        while !rest.is_empty() {
+            let (part, line_end) = match rest.iter().position(|b| *b == b'\n') {
+                Some(pos) => (&rest[..pos], Some(pos + 1)),
+                None => (rest, None),
+            };
+
+            // never buffer more than a single line, no matter how the body is chunked
+            if buf.len() + part.len() > USER_UPSERT_MAX_LINE_BYTES {
+                return Err(ErrorResponse::new(
+                    ErrorResponseType::BadRequest,
+                    format!(
+                        "A single line must not exceed {} MB",
+                        USER_UPSERT_MAX_LINE_BYTES / 1024 / 1024
+                    ),
+                ));
+            }
+            buf.extend_from_slice(part);
+
+            let Some(end) = line_end else {
+                break;
+            };
+            import.import_line(&buf).await;
+            buf.clear();
+            rest = &rest[end..];
+        }
+    }
+    if !buf.is_empty() {
+        import.import_line(&buf).await;
+    }
+
+    Ok(HttpResponse::Ok().json(import.finish()))
+}
+
 /// Get the configured / allowed additional custom user attribute
 #[utoipa::path(
     get,
@@ -280,18 +539,53 @@ pub async fn get_users_register(req: HttpRequest) -> Result<HttpResponse, ErrorR
     Ok(HttpResponse::Ok().insert_header(HEADER_HTML).body(body))
 }
 
+/// Returns the custom user attributes, that must be provided during the open user registration
+#[utoipa::path(
+    get,
+    path = "/users/register/fields",
+    tag = "users",
+    responses(
+        (status = 200, description = "Ok", body = UserAttrConfigResponse),
+        (status = 403, description = "Forbidden: Open registration may be not allowed via config"),
+    ),
+)]
+#[get("/users/register/fields")]
+pub async fn get_users_register_fields() -> Result<HttpResponse, ErrorResponse> {
+    if !*OPEN_USER_REG {
+        return Err(ErrorResponse::new(
+            ErrorResponseType::Forbidden,
+            "Open User Registration is not allowed".to_string(),
+        ));
+    }
+
+    let values = UserAttrConfigEntity::find_for_registration()
+        .await?
+        .into_iter()
+        .map(|v| v.into())
+        .collect();
+    Ok(HttpResponse::Ok()
+        .insert_header(HEADER_ALLOW_ALL_ORIGINS)
+        .json(UserAttrConfigResponse { values }))
+}
+
 /// Creates a new user with almost all values set to default
 ///
 /// This is the endpoint for the possibly allowed open user registration endpoint and can be
 /// accessed by anyone, if configured.<br>
 /// A Proof of Work (PoW) must be computed by the client to fight automatic bots and spammers.
+///
+/// Values for all custom attributes flagged for the registration must be provided.
+/// If `USER_REG_APPROVAL` is enabled, the registration will wait for an admin approval and the
+/// endpoint returns a 202 instead.
 #[utoipa::path(
     post,
     path = "/users/register",
     tag = "users",
     request_body = NewUserRegistrationRequest,
     responses(
+        (status = 202, description = "Accepted: Registration awaits approval"),
         (status = 204, description = "NoContent"),
+        (status = 400, description = "BadRequest", body = ErrorResponse),
         (status = 403, description = "Forbidden", body = ErrorResponse),
     ),
 )]
@@ -345,11 +639,31 @@ pub async fn post_users_register(
         }
     }
 
+    UserAttrConfigEntity::validate_registration_values(
+        req_data.attributes.as_deref().unwrap_or_default(),
+    )
+    .await?;
+
     // validate the PoW
     let challenge = Pow::validate(&req_data.pow)?;
     PowEntity::check_prevent_reuse(challenge.to_string()).await?;
 
     let lang = Language::try_from(&req).unwrap_or_default();
+
+    if *USER_REG_APPROVAL {
+        let ip = real_ip_from_req(&req)?.to_string();
+        let reg = UserRegistration::create(req_data.into_inner(), lang, ip.clone()).await?;
+
+        data.tx_events
+            .send_async(Event::user_registration_pending(reg.email, ip))
+            .await
+            .unwrap();
+
+        return Ok(HttpResponse::Accepted()
+            .insert_header(HEADER_ALLOW_ALL_ORIGINS)
+            .finish());
+    }
+
     let user = User::create_from_reg(&data, req_data.into_inner(), lang).await?;
 
     data.tx_events
@@ -365,6 +679,100 @@ pub async fn post_users_register(
         .finish())
 }
 
+/// Returns all pending registrations, that wait for an approval
+///
+/// **Permissions**
+/// - rauthy_admin
+#[utoipa::path(
+    get,
+    path = "/users/registrations",
+    tag = "users",
+    responses(
+        (status = 200, description = "Ok", body = [UserRegistrationResponse]),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Forbidden", body = ErrorResponse),
+    ),
+)]
+#[get("/users/registrations")]
+pub async fn get_user_registrations(
+    principal: ReqPrincipal,
+) -> Result<HttpResponse, ErrorResponse> {
+    principal.validate_api_key_or_admin_session(AccessGroup::Users, AccessRights::Read)?;
+
+    let regs = UserRegistration::find_all().await?;
+    let mut res = Vec::with_capacity(regs.len());
+    for reg in regs {
+        res.push(reg.into_response()?);
+    }
+    Ok(HttpResponse::Ok().json(res))
+}
+
+/// Approves a pending registration
+///
+/// This creates the new user, which will receive the E-Mail to set up the account afterward.
+///
+/// **Permissions**
+/// - rauthy_admin
+#[utoipa::path(
+    post,
+    path = "/users/registrations/{id}/approve",
+    tag = "users",
+    responses(
+        (status = 200, description = "Ok", body = UserResponse),
+        (status = 400, description = "BadRequest", body = ErrorResponse),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Forbidden", body = ErrorResponse),
+        (status = 404, description = "NotFound", body = ErrorResponse),
+    ),
+)]
+#[post("/users/registrations/{id}/approve")]
+pub async fn post_user_registration_approve(
+    data: web::Data<AppState>,
+    req: HttpRequest,
+    id: web::Path<String>,
+    principal: ReqPrincipal,
+) -> Result<HttpResponse, ErrorResponse> {
+    principal.validate_api_key_or_admin_session(AccessGroup::Users, AccessRights::Create)?;
+
+    let reg = UserRegistration::find(&id.into_inner()).await?;
+    let user = reg.approve(&data).await?;
+
+    data.tx_events
+        .send_async(Event::new_user(
+            user.email.clone(),
+            real_ip_from_req(&req)?.to_string(),
+        ))
+        .await
+        .unwrap();
+
+    Ok(HttpResponse::Ok().json(user.into_response(None)))
+}
+
+/// Rejects and deletes a pending registration
+///
+/// **Permissions**
+/// - rauthy_admin
+#[utoipa::path(
+    delete,
+    path = "/users/registrations/{id}",
+    tag = "users",
+    responses(
+        (status = 200, description = "Ok"),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Forbidden", body = ErrorResponse),
+    ),
+)]
+#[delete("/users/registrations/{id}")]
+pub async fn delete_user_registration(
+    id: web::Path<String>,
+    principal: ReqPrincipal,
+) -> Result<HttpResponse, ErrorResponse> {
+    principal.validate_api_key_or_admin_session(AccessGroup::Users, AccessRights::Delete)?;
+
+    UserRegistration::delete(&id.into_inner()).await?;
+    Ok(HttpResponse::Ok().finish())
+}
+
 /// Returns a single user by its *id*
 #[utoipa::path(
     get,
@@ -388,10 +796,21 @@ pub async fn get_user_by_id(
         .is_ok();
     if !api_key_or_admin {
         principal.validate_session_auth()?;
-        principal.is_user(&id)?;
+        if !principal.is_scoped_admin() {
+            principal.is_user(&id)?;
+        }
     }
 
     let user = User::find(id).await?;
+    if !api_key_or_admin && principal.is_user(&user.id).is_err() {
+        principal
+            .validate_api_key_or_admin_session_for_user(
+                AccessGroup::Users,
+                AccessRights::Read,
+                &user,
+            )
+            .await?;
+    }
     let values = UserValues::find(&user.id).await?;
 
     Ok(HttpResponse::Ok().json(user.into_response(values)))
@@ -412,9 +831,22 @@ pub async fn get_user_attr(
     path: web::Path<String>,
     principal: ReqPrincipal,
 ) -> Result<HttpResponse, ErrorResponse> {
-    principal.validate_api_key_or_admin_session(AccessGroup::UserAttributes, AccessRights::Read)?;
+    let id = path.into_inner();
+    if principal.is_scoped_admin() {
+        let user = User::find(id.clone()).await?;
+        principal
+            .validate_api_key_or_admin_session_for_user(
+                AccessGroup::UserAttributes,
+                AccessRights::Read,
+                &user,
+            )
+            .await?;
+    } else {
+        principal
+            .validate_api_key_or_admin_session(AccessGroup::UserAttributes, AccessRights::Read)?;
+    }
 
-    let values = UserAttrValueEntity::find_for_user(&path.into_inner())
+    let values = UserAttrValueEntity::find_for_user(&id)
         .await?
         .drain(..)
         .map(UserAttrValueResponse::from)
@@ -440,10 +872,22 @@ pub async fn put_user_attr(
     principal: ReqPrincipal,
     req_data: Json<UserAttrValuesUpdateRequest>,
 ) -> Result<HttpResponse, ErrorResponse> {
-    principal
-        .validate_api_key_or_admin_session(AccessGroup::UserAttributes, AccessRights::Update)?;
+    let id = path.into_inner();
+    if principal.is_scoped_admin() {
+        let user = User::find(id.clone()).await?;
+        principal
+            .validate_api_key_or_admin_session_for_user(
+                AccessGroup::UserAttributes,
+                AccessRights::Update,
+                &user,
+            )
+            .await?;
+    } else {
+        principal
+            .validate_api_key_or_admin_session(AccessGroup::UserAttributes, AccessRights::Update)?;
+    }
 
-    let values = UserAttrValueEntity::update_for_user(&path.into_inner(), req_data.into_inner())
+    let values = UserAttrValueEntity::update_for_user(&id, req_data.into_inner())
         .await?
         .drain(..)
         .map(UserAttrValueResponse::from)
@@ -451,6 +895,32 @@ pub async fn put_user_attr(
     Ok(HttpResponse::Ok().json(UserAttrValuesResponse { values }))
 }
 
+/// GET all upstream identities, which are linked to this user
+#[utoipa::path(
+    get,
+    path = "/users/{id}/federations",
+    tag = "users",
+    responses(
+        (status = 200, description = "Ok", body = [UserFederationResponse]),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Forbidden", body = ErrorResponse),
+    ),
+)]
+#[get("/users/{id}/federations")]
+pub async fn get_user_federations(
+    path: web::Path<String>,
+    principal: ReqPrincipal,
+) -> Result<HttpResponse, ErrorResponse> {
+    let user_id = path.into_inner();
+    principal.validate_user_or_admin(&user_id)?;
+
+    let user = User::find(user_id).await?;
+    let resp =
+        UserFederation::find_for_user_response(&user.id, user.auth_provider_id.as_deref()).await?;
+
+    Ok(HttpResponse::Ok().json(resp))
+}
+
 /// GET all devices for this user linked via the `device_code` flow
 #[utoipa::path(
     get,
@@ -498,6 +968,7 @@ pub async fn put_user_device_name(
 ) -> Result<HttpResponse, ErrorResponse> {
     let user_id = path.into_inner();
     principal.validate_user_or_admin(&user_id)?;
+    principal.validate_not_impersonated()?;
 
     let payload = payload.into_inner();
     if let Some(name) = &payload.name {
@@ -528,6 +999,7 @@ pub async fn delete_user_device(
 ) -> Result<HttpResponse, ErrorResponse> {
     let user_id = path.into_inner();
     principal.validate_user_or_admin(&user_id)?;
+    principal.validate_not_impersonated()?;
 
     let payload = payload.into_inner();
     let device = DeviceEntity::find(&payload.device_id).await?;
@@ -543,6 +1015,63 @@ pub async fn delete_user_device(
     Ok(HttpResponse::Ok().finish())
 }
 
+/// GET all trusted devices for this user, which skip the 2nd factor during login
+#[utoipa::path(
+    get,
+    path = "/users/{id}/trusted_devices",
+    tag = "users",
+    responses(
+        (status = 200, description = "Ok", body = [TrustedDeviceResponse]),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Forbidden", body = ErrorResponse),
+    ),
+)]
+#[get("/users/{id}/trusted_devices")]
+pub async fn get_user_trusted_devices(
+    path: web::Path<String>,
+    req: HttpRequest,
+    principal: ReqPrincipal,
+) -> Result<HttpResponse, ErrorResponse> {
+    let user_id = path.into_inner();
+    principal.validate_user_or_admin(&user_id)?;
+
+    let current = TrustedDevice::id_from_req(&req);
+    let resp = TrustedDevice::find_for_user(&user_id)
+        .await?
+        .into_iter()
+        .map(|d| d.into_response(current.as_deref()))
+        .collect::<Vec<TrustedDeviceResponse>>();
+
+    Ok(HttpResponse::Ok().json(resp))
+}
+
+/// DELETE a trusted device for this user
+///
+/// The next login on this device will require the 2nd factor again.
+#[utoipa::path(
+    delete,
+    path = "/users/{id}/trusted_devices/{device_id}",
+    tag = "users",
+    responses(
+        (status = 200, description = "Ok"),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Forbidden", body = ErrorResponse),
+    ),
+)]
+#[delete("/users/{id}/trusted_devices/{device_id}")]
+pub async fn delete_user_trusted_device(
+    path: web::Path<(String, String)>,
+    principal: ReqPrincipal,
+) -> Result<HttpResponse, ErrorResponse> {
+    let (user_id, device_id) = path.into_inner();
+    principal.validate_user_or_admin(&user_id)?;
+    principal.validate_not_impersonated()?;
+
+    TrustedDevice::delete(&user_id, &device_id).await?;
+
+    Ok(HttpResponse::Ok().finish())
+}
+
 /// Endpoint for resetting passwords
 ///
 /// The `id` is the user id and `reset_id` is a random 64 character long string sent via E-Mail for a
@@ -575,6 +1104,132 @@ pub async fn get_user_email_confirm(
     }
 }
 
+/// Confirmation page for the unlock link from the account locked E-Mail
+///
+/// The `id` is the user id and `unlock_id` is a random 64 character long string sent via E-Mail
+/// when the account has been locked with `ACCOUNT_LOCKOUT_UNLOCK_EMAIL` enabled. Opening the link
+/// does not change anything, so that link scanners in mail clients cannot unlock the account.
+/// The action happens after the confirmation with `POST /users/{id}/unlock/{unlock_id}`.
+#[utoipa::path(
+    get,
+    path = "/users/{id}/unlock/{unlock_id}",
+    tag = "users",
+    responses(
+        (status = 200, description = "Ok"),
+        (status = 400, description = "BadRequest", body = ErrorResponse),
+        (status = 404, description = "NotFound", body = ErrorResponse),
+    ),
+)]
+#[get("/users/{id}/unlock/{unlock_id}")]
+pub async fn get_user_unlock(path: web::Path<(String, String)>, req: HttpRequest) -> HttpResponse {
+    let lang = Language::try_from(&req).unwrap_or_default();
+    let (user_id, unlock_id) = path.into_inner();
+    match User::unlock_confirm_html(&req, user_id, unlock_id).await {
+        Ok(html) => HttpResponse::Ok().insert_header(HEADER_HTML).body(html),
+        Err(err) => {
+            let colors = ColorEntity::find_rauthy().await.unwrap_or_default();
+            let status = err.status_code();
+            let body = Error3Html::build(&colors, &lang, status, Some(err.message));
+            ErrorHtml::response(body, status)
+        }
+    }
+}
+
+/// Unlocks an account via the link from the account locked E-Mail
+///
+/// Needs the CSRF token from the confirmation page in the `pwd-csrf-token` header. The
+/// `Location` header contains the account page the user should be redirected to.
+#[utoipa::path(
+    post,
+    path = "/users/{id}/unlock/{unlock_id}",
+    tag = "users",
+    responses(
+        (status = 202, description = "Accepted"),
+        (status = 400, description = "BadRequest", body = ErrorResponse),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 404, description = "NotFound", body = ErrorResponse),
+    ),
+)]
+#[post("/users/{id}/unlock/{unlock_id}")]
+pub async fn post_user_unlock_link(
+    data: web::Data<AppState>,
+    path: web::Path<(String, String)>,
+    req: HttpRequest,
+) -> Result<HttpResponse, ErrorResponse> {
+    let (user_id, unlock_id) = path.into_inner();
+    let email = User::unlock_via_magic_link(&data, &req, user_id, unlock_id).await?;
+    if let Err(err) = brute_force::unlock_account(&email).await {
+        error!(
+            "Error removing the brute-force lock for {}: {:?}",
+            email, err
+        );
+    }
+
+    Ok(HttpResponse::Accepted()
+        .insert_header((LOCATION, "/auth/v1/account"))
+        .finish())
+}
+
+/// Confirmation page for the "this wasn't me" link
+///
+/// The `id` is the user id and `not_me_id` is a random 64 character long string sent via E-Mail
+/// with a security notification. Opening the link does not change anything, so that link
+/// scanners in mail clients cannot log the user out. The action happens after the confirmation
+/// with `POST /users/{id}/not_me/{not_me_id}`.
+#[utoipa::path(
+    get,
+    path = "/users/{id}/not_me/{not_me_id}",
+    tag = "users",
+    responses(
+        (status = 200, description = "Ok"),
+        (status = 400, description = "BadRequest", body = ErrorResponse),
+        (status = 404, description = "NotFound", body = ErrorResponse),
+    ),
+)]
+#[get("/users/{id}/not_me/{not_me_id}")]
+pub async fn get_user_not_me(path: web::Path<(String, String)>, req: HttpRequest) -> HttpResponse {
+    let lang = Language::try_from(&req).unwrap_or_default();
+    let (user_id, not_me_id) = path.into_inner();
+    match User::not_me_confirm_html(&req, user_id, not_me_id).await {
+        Ok(html) => HttpResponse::Ok().insert_header(HEADER_HTML).body(html),
+        Err(err) => {
+            let colors = ColorEntity::find_rauthy().await.unwrap_or_default();
+            let status = err.status_code();
+            let body = Error3Html::build(&colors, &lang, status, Some(err.message));
+            ErrorHtml::response(body, status)
+        }
+    }
+}
+
+/// Reports a security notification as unknown via the "this wasn't me" link
+///
+/// Needs the CSRF token from the confirmation page in the `pwd-csrf-token` header. All sessions
+/// and refresh tokens for the user will be invalidated and trusted devices revoked. The
+/// `Location` header contains the new password reset link the user should be redirected to.
+#[utoipa::path(
+    post,
+    path = "/users/{id}/not_me/{not_me_id}",
+    tag = "users",
+    responses(
+        (status = 202, description = "Accepted"),
+        (status = 400, description = "BadRequest", body = ErrorResponse),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 404, description = "NotFound", body = ErrorResponse),
+    ),
+)]
+#[post("/users/{id}/not_me/{not_me_id}")]
+pub async fn post_user_not_me(
+    data: web::Data<AppState>,
+    path: web::Path<(String, String)>,
+    req: HttpRequest,
+) -> Result<HttpResponse, ErrorResponse> {
+    let (user_id, not_me_id) = path.into_inner();
+    let location = User::not_me_via_magic_link(&data, &req, user_id, not_me_id).await?;
+    Ok(HttpResponse::Accepted()
+        .insert_header((LOCATION, location))
+        .finish())
+}
+
 /// Endpoint for resetting passwords
 ///
 /// The `id` is the user id and `reset_id` is a random 64 character long string sent via E-Mail for a
@@ -604,24 +1259,14 @@ pub async fn get_user_password_reset(
     let no_html = accept == "application/json";
 
     match password_reset::handle_get_pwd_reset(req, user_id, reset_id, no_html).await {
-        Ok((content, cookie)) => {
+        Ok((content, password_policy, cookie)) => {
             if no_html {
-                let password_policy = match PasswordPolicy::find().await {
-                    Ok(policy) => PasswordPolicyResponse::from(policy),
-                    Err(err) => {
-                        let colors = ColorEntity::find_rauthy().await.unwrap_or_default();
-                        let status = err.status_code();
-                        let body = Error3Html::build(&colors, &lang, status, Some(err.message));
-                        return ErrorHtml::response(body, status);
-                    }
-                };
-
                 HttpResponse::Ok()
                     .cookie(cookie)
                     .insert_header(HEADER_JSON)
                     .json(PasswordResetResponse {
                         csrf_token: content,
-                        password_policy,
+                        password_policy: PasswordPolicyResponse::from(password_policy),
                     })
             } else {
                 HttpResponse::Ok()
@@ -717,7 +1362,16 @@ pub async fn get_user_webauthn_passkeys(
     {
         // make sure a non-admin can only access its own information
         principal.validate_session_auth()?;
-        principal.is_user(&id)?;
+        if principal.is_user(&id).is_err() {
+            let user = User::find(id.clone()).await?;
+            principal
+                .validate_api_key_or_admin_session_for_user(
+                    AccessGroup::Users,
+                    AccessRights::Read,
+                    &user,
+                )
+                .await?;
+        }
     }
 
     let pks = PasskeyEntity::find_for_user(&id)
@@ -823,6 +1477,7 @@ pub async fn post_webauthn_auth_start(
 pub async fn post_webauthn_auth_finish(
     data: web::Data<AppState>,
     id: web::Path<String>,
+    req: HttpRequest,
     req_data: Json<WebauthnAuthFinishRequest>,
 ) -> Result<HttpResponse, ErrorResponse> {
     let id = id.into_inner();
@@ -832,7 +1487,36 @@ pub async fn post_webauthn_auth_finish(
     // This here will simply fail, if the secret code from the /start does not exist.
 
     let res = webauthn::auth_finish(&data, id, req_data.into_inner()).await?;
-    Ok(res.into_response())
+    if let WebauthnAdditionalData::Login(login_req) = &res {
+        login_req.validate_access(&data, &req).await?;
+    }
+
+    let trusted_device = match &res {
+        WebauthnAdditionalData::Login(login_req) if login_req.trust_device => {
+            Some(TrustedDevice::create(&req, login_req.user_id.clone()).await?)
+        }
+        _ => None,
+    };
+    let known_devices_cookie = match &res {
+        WebauthnAdditionalData::Login(login_req) if SecurityNotification::is_enabled() => {
+            let user = User::find(login_req.user_id.clone()).await?;
+            SecurityNotification::check_new_login(&data, &req, &user).await
+        }
+        _ => None,
+    };
+
+    let mut resp = res.into_response();
+    if let Some(device) = trusted_device {
+        if let Err(err) = resp.add_cookie(&device.build_cookie()) {
+            error!("Error adding trusted device cookie: {}", err);
+        }
+    }
+    if let Some(cookie) = known_devices_cookie {
+        if let Err(err) = resp.add_cookie(&cookie) {
+            error!("Error adding known devices cookie: {}", err);
+        }
+    }
+    Ok(resp)
 }
 
 /// Deletes the WebAuthn Device for this user in the given slot
@@ -852,6 +1536,7 @@ pub async fn post_webauthn_auth_finish(
 )]
 #[delete("/users/{id}/webauthn/delete/{name}")]
 pub async fn delete_webauthn(
+    data: web::Data<AppState>,
     path: web::Path<(String, String)>,
     principal: ReqPrincipal,
 ) -> Result<HttpResponse, ErrorResponse> {
@@ -863,18 +1548,28 @@ pub async fn delete_webauthn(
             false
         }
     };
+    principal.validate_not_impersonated()?;
 
     let (id, name) = path.into_inner();
 
     // validate that Principal matches the user or is an admin
     if !is_admin {
-        principal.is_user(&id)?;
+        if principal.is_user(&id).is_err() {
+            let user = User::find(id.clone()).await?;
+            principal
+                .validate_admin_role_for_user(&AccessGroup::Users, &AccessRights::Update, &user)
+                .await?;
+        }
         warn!("Passkey delete for user {} for key {}", id, name);
     } else {
         warn!("Passkey delete from admin for user {} for key {}", id, name);
     }
 
-    PasskeyEntity::delete(id, name).await?;
+    PasskeyEntity::delete(id.clone(), name.clone()).await?;
+    let user = User::find(id).await?;
+    SecurityNotification::PasskeyRemoved(name)
+        .send(&data, &user)
+        .await;
     // // if we delete a passkey, we must check if this is the last existing one for the user
     // let pks = PasskeyEntity::find_for_user(&data, &id).await?;
     //
@@ -951,6 +1646,7 @@ pub async fn post_webauthn_reg_start(
         .await
     } else {
         principal.validate_session_auth()?;
+        principal.validate_not_impersonated()?;
         // this endpoint is a CSRF check exception inside the Principal Middleware -> check here!
         principal.validate_session_csrf_exception(&req)?;
 
@@ -1007,7 +1703,15 @@ pub async fn post_webauthn_reg_finish(
         let id = id.into_inner();
         principal.is_user(&id)?;
 
-        webauthn::reg_finish(&data, id, req_data.into_inner()).await?;
+        let req_data = req_data.into_inner();
+        let name = req_data.passkey_name.clone();
+        webauthn::reg_finish(&data, id.clone(), req_data).await?;
+
+        let user = User::find(id).await?;
+        SecurityNotification::PasskeyAdded(name)
+            .send(&data, &user)
+            .await;
+
         Ok(HttpResponse::Created().finish())
     }
 }
@@ -1195,9 +1899,20 @@ pub async fn get_user_by_email(
     path: web::Path<String>,
     principal: ReqPrincipal,
 ) -> Result<HttpResponse, ErrorResponse> {
-    principal.validate_api_key_or_admin_session(AccessGroup::Users, AccessRights::Read)?;
-
-    let user = User::find_by_email(path.into_inner()).await?;
+    let user = if principal.is_scoped_admin() {
+        let user = User::find_by_email(path.into_inner()).await?;
+        principal
+            .validate_api_key_or_admin_session_for_user(
+                AccessGroup::Users,
+                AccessRights::Read,
+                &user,
+            )
+            .await?;
+        user
+    } else {
+        principal.validate_api_key_or_admin_session(AccessGroup::Users, AccessRights::Read)?;
+        User::find_by_email(path.into_inner()).await?
+    };
     let values = UserValues::find(&user.id).await?;
 
     Ok(HttpResponse::Ok().json(user.into_response(values)))
@@ -1226,10 +1941,26 @@ pub async fn put_user_by_id(
     principal: ReqPrincipal,
     user: Json<UpdateUserRequest>,
 ) -> Result<HttpResponse, ErrorResponse> {
-    principal.validate_api_key_or_admin_session(AccessGroup::Users, AccessRights::Update)?;
+    let id = id.into_inner();
+    if principal.is_scoped_admin() {
+        let current = User::find(id.clone()).await?;
+        principal
+            .validate_api_key_or_admin_session_for_user(
+                AccessGroup::Users,
+                AccessRights::Update,
+                &current,
+            )
+            .await?;
+
+        if principal.api_key.is_none() {
+            validate_scoped_admin_user_update(&principal, &current, &user)?;
+        }
+    } else {
+        principal.validate_api_key_or_admin_session(AccessGroup::Users, AccessRights::Update)?;
+    }
 
     let (user, user_values, is_new_admin) =
-        User::update(&data, id.into_inner(), user.into_inner(), None).await?;
+        User::update(&data, id, user.into_inner(), None).await?;
 
     if is_new_admin {
         data.tx_events
@@ -1244,6 +1975,139 @@ pub async fn put_user_by_id(
     Ok(HttpResponse::Ok().json(user.into_response(user_values)))
 }
 
+/// Unlocks a user account
+///
+/// Removes a lock after too many failed logins and resets the failed logins counter.
+///
+/// **Permissions**
+/// - rauthy_admin
+#[utoipa::path(
+    post,
+    path = "/users/{id}/unlock",
+    tag = "users",
+    responses(
+        (status = 200, description = "Ok", body = UserResponse),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Forbidden", body = ErrorResponse),
+        (status = 404, description = "NotFound", body = ErrorResponse),
+    ),
+)]
+#[post("/users/{id}/unlock")]
+pub async fn post_user_unlock(
+    data: web::Data<AppState>,
+    id: web::Path<String>,
+    req: HttpRequest,
+    principal: ReqPrincipal,
+) -> Result<HttpResponse, ErrorResponse> {
+    let mut user = User::find(id.into_inner()).await?;
+    principal
+        .validate_api_key_or_admin_session_for_user(AccessGroup::Users, AccessRights::Update, &user)
+        .await?;
+
+    let ip = real_ip_from_req(&req).ok().map(|ip| ip.to_string());
+    user.unlock(&data, ip).await?;
+    brute_force::unlock_account(&user.email).await?;
+
+    let values = UserValues::find(&user.id).await?;
+    Ok(HttpResponse::Ok().json(user.into_response(values)))
+}
+
+/// Starts an impersonation session for the given user
+///
+/// The current admin session will be logged out and replaced with a new, time-limited session
+/// for the target user. The lifetime can be set with `SESSION_LIFETIME_IMPERSONATION`.
+/// Tokens issued during this session will contain an `act` claim with the admin's user id and
+/// will never include a refresh token.
+///
+/// Users with the `rauthy_admin` role cannot be impersonated.
+///
+/// **Permissions**
+/// - rauthy_admin
+#[utoipa::path(
+    post,
+    path = "/users/{id}/impersonate",
+    tag = "users",
+    responses(
+        (status = 200, description = "Ok", body = SessionInfoResponse),
+        (status = 400, description = "BadRequest", body = ErrorResponse),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Forbidden", body = ErrorResponse),
+    ),
+)]
+#[post("/users/{id}/impersonate")]
+pub async fn post_user_impersonate(
+    data: web::Data<AppState>,
+    id: web::Path<String>,
+    req: HttpRequest,
+    principal: ReqPrincipal,
+) -> Result<HttpResponse, ErrorResponse> {
+    // Note: This is not allowed with an ApiKey on purpose
+    principal.validate_admin_session()?;
+
+    let id = id.into_inner();
+    let admin_id = principal.user_id()?.to_string();
+    if id == admin_id {
+        return Err(ErrorResponse::new(
+            ErrorResponseType::BadRequest,
+            "You cannot impersonate yourself",
+        ));
+    }
+
+    let user = User::find(id).await?;
+    if !user.enabled {
+        return Err(ErrorResponse::new(
+            ErrorResponseType::BadRequest,
+            "Disabled users cannot be impersonated",
+        ));
+    }
+    if user.get_roles().contains(&*RAUTHY_ADMIN_ROLE) {
+        return Err(ErrorResponse::new(
+            ErrorResponseType::Forbidden,
+            "A rauthy_admin cannot be impersonated",
+        ));
+    }
+    let admin = User::find(admin_id.clone()).await?;
+
+    let ip = real_ip_from_req(&req)?.to_string();
+    let mut session = Session::try_new(&user, *SESSION_LIFETIME_IMPERSONATION, Some(ip.clone()))?;
+    session.state = SessionState::Auth.as_str().to_string();
+    session.impersonated_by = Some(admin_id);
+    session.save().await?;
+
+    // the admin session will be replaced in the browser -> log it out properly
+    if let Some(admin_session) = principal.session.clone() {
+        admin_session.invalidate().await?;
+    }
+
+    warn!(
+        "Admin {} started impersonating user {}",
+        admin.email, user.email
+    );
+    data.tx_events
+        .send_async(Event::user_impersonated(&admin.email, &user.email, ip))
+        .await
+        .unwrap();
+
+    let timeout = OffsetDateTime::from_unix_timestamp(session.last_seen)
+        .unwrap()
+        .add(time::Duration::seconds(data.session_timeout as i64));
+    let info = SessionInfoResponse {
+        id: session.id.as_str().into(),
+        csrf_token: Some(session.csrf_token.as_str().into()),
+        user_id: session.user_id.as_deref().map(|v| v.into()),
+        roles: session.roles.as_deref().map(|v| v.into()),
+        groups: session.groups.as_deref().map(|v| v.into()),
+        exp: OffsetDateTime::from_unix_timestamp(session.exp).unwrap(),
+        timeout,
+        state: session.state()?.into(),
+        impersonated_by: session.impersonated_by.as_deref().map(|v| v.into()),
+    };
+
+    Ok(HttpResponse::Ok()
+        .cookie(session.client_cookie())
+        .json(info))
+}
+
 /// Allows modification of specific user values from the user himself
 ///
 /// **Permissions**
@@ -1267,6 +2131,7 @@ pub async fn put_user_self(
     user: Json<UpdateUserSelfRequest>,
 ) -> Result<HttpResponse, ErrorResponse> {
     principal.validate_session_auth()?;
+    principal.validate_not_impersonated()?;
 
     // make sure the logged in user can only update itself
     let id = id.into_inner();
@@ -1301,6 +2166,7 @@ pub async fn post_user_self_convert_passkey(
     principal: ReqPrincipal,
 ) -> Result<HttpResponse, ErrorResponse> {
     principal.validate_session_auth()?;
+    principal.validate_not_impersonated()?;
 
     // make sure the logged in user can only update itself
     let id = id.into_inner();
@@ -1329,9 +2195,69 @@ pub async fn delete_user_by_id(
     path: web::Path<String>,
     principal: ReqPrincipal,
 ) -> Result<HttpResponse, ErrorResponse> {
-    principal.validate_api_key_or_admin_session(AccessGroup::Users, AccessRights::Delete)?;
-
-    let user = User::find(path.into_inner()).await?;
+    let user = if principal.is_scoped_admin() {
+        let user = User::find(path.into_inner()).await?;
+        principal
+            .validate_api_key_or_admin_session_for_user(
+                AccessGroup::Users,
+                AccessRights::Delete,
+                &user,
+            )
+            .await?;
+        user
+    } else {
+        principal.validate_api_key_or_admin_session(AccessGroup::Users, AccessRights::Delete)?;
+        User::find(path.into_inner()).await?
+    };
     user.delete().await?;
     Ok(HttpResponse::NoContent().finish())
 }
+
+/// Group scoped admins must not be able to grant roles or move a user outside their scope.
+fn validate_scoped_admin_user_update(
+    principal: &Principal,
+    current: &User,
+    req: &UpdateUserRequest,
+) -> Result<(), ErrorResponse> {
+    validate_scoped_admin_user_values(
+        principal,
+        &AccessRights::Update,
+        (&current.get_roles(), &current.get_groups()),
+        (&req.roles, req.groups.as_deref().unwrap_or_default()),
+    )
+}
+
+/// Compares the `(roles, groups)` a user has right now with the requested ones. Roles can only
+/// be modified by a `rauthy_admin`, and the groups must stay inside the admin role group scope.
+fn validate_scoped_admin_user_values(
+    principal: &Principal,
+    access_rights: &AccessRights,
+    (current_roles, current_groups): (&[String], &[String]),
+    (new_roles, new_groups): (&[String], &[String]),
+) -> Result<(), ErrorResponse> {
+    let mut current_roles = current_roles.to_vec();
+    let mut new_roles = new_roles.to_vec();
+    current_roles.sort();
+    new_roles.sort();
+    if current_roles != new_roles {
+        return Err(ErrorResponse::new(
+            ErrorResponseType::Forbidden,
+            "Only a rauthy_admin is allowed to modify user roles",
+        ));
+    }
+
+    if let Some(scope) = principal.admin_role_group_scope(&AccessGroup::Users, access_rights) {
+        let is_valid = new_groups.iter().any(|g| scope.contains(g))
+            && new_groups
+                .iter()
+                .all(|g| scope.contains(g) || current_groups.contains(g));
+        if !is_valid {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::Forbidden,
+                "The user groups must stay inside your admin role group scope",
+            ));
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,35 @@
+use crate::api_keys::ApiKeyAccess;
+use crate::cust_validation::validate_vec_groups;
+use rauthy_common::constants::{RE_ATTR_DESC, RE_GROUPS};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use validator::Validate;
+
+#[derive(Debug, Serialize, Deserialize, Validate, ToSchema)]
+pub struct AdminRoleRequest {
+    /// The name must match an existing Rauthy role. Each user with this role assigned
+    /// will get the admin access rights defined here.
+    ///
+    /// Validation: `^[a-z0-9-_/,:*]{2,64}$`
+    #[validate(regex(path = "*RE_GROUPS", code = "^[a-z0-9-_/,:*]{2,64}$"))]
+    pub name: String,
+    /// Validation: `[a-zA-Z0-9-_/\s]{0,128}`
+    #[validate(regex(path = "*RE_ATTR_DESC", code = "[a-zA-Z0-9-_/\\s]{0,128}"))]
+    pub description: Option<String>,
+    pub access: Vec<ApiKeyAccess>,
+    /// If set, access to `Users` is restricted to users that are a member of at least one
+    /// of these groups.
+    ///
+    /// Validation: `Vec<^[a-z0-9-_/,:*]{2,64}$>`
+    #[validate(custom(function = "validate_vec_groups"))]
+    pub group_scope: Option<Vec<String>>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct AdminRoleResponse {
+    pub id: String,
+    pub name: String,
+    pub description: Option<String>,
+    pub access: Vec<ApiKeyAccess>,
+    pub group_scope: Option<Vec<String>>,
+}
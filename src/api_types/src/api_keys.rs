@@ -16,6 +16,7 @@ pub enum AccessGroup {
     Scopes,
     UserAttributes,
     Users,
+    Organizations,
 }
 
 #[derive(Debug, PartialEq, Serialize, Deserialize, ToSchema)]
@@ -1,19 +1,151 @@
-use crate::cust_validation::validate_vec_scopes;
+use crate::cust_validation::{validate_vec_domain, validate_vec_scopes};
 use rauthy_common::constants::{
-    RE_ALNUM, RE_CLIENT_ID_EPHEMERAL, RE_CLIENT_NAME, RE_CODE_CHALLENGE, RE_PEM, RE_SCOPE_SPACE,
-    RE_URI,
+    RE_ALNUM, RE_CLIENT_ID_EPHEMERAL, RE_CLIENT_NAME, RE_CODE_CHALLENGE, RE_JSON_PATH, RE_PEM,
+    RE_SCOPE_SPACE, RE_URI,
 };
 use serde::{Deserialize, Serialize};
 use utoipa::ToSchema;
 use validator::Validate;
 
-#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
 #[serde(rename_all = "lowercase")]
 pub enum AuthProviderType {
     Custom,
     Github,
     Google,
     OIDC,
+    Saml,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ProviderClaimMappingTarget {
+    Role,
+    Group,
+    Attribute,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Validate, ToSchema)]
+pub struct ProviderClaimMapping {
+    /// JSON path to the upstream claim, like `$.groups[*]` or `$.realm_access.roles[*]`
+    ///
+    /// Validation: `[a-zA-Z0-9,.:/_\\-&?=~#!$'()*+%]`
+    #[validate(regex(path = "*RE_URI", code = "[a-zA-Z0-9,.:/_\\-&?=~#!$'()*+%]"))]
+    pub claim_path: String,
+    /// Mandatory for `role` and `group` mappings. Attribute mappings copy the claim value.
+    ///
+    /// Validation: `[a-zA-Z0-9,.:/_\\-&?=~#!$'()*+%]`
+    #[validate(regex(path = "*RE_URI", code = "[a-zA-Z0-9,.:/_\\-&?=~#!$'()*+%]"))]
+    pub claim_value: Option<String>,
+    pub target: ProviderClaimMappingTarget,
+    /// The name of the role or group, or the key of the custom user attribute
+    ///
+    /// Validation: `[a-zA-Z0-9,.:/_\\-&?=~#!$'()*+%]`
+    #[validate(regex(path = "*RE_URI", code = "[a-zA-Z0-9,.:/_\\-&?=~#!$'()*+%]"))]
+    pub target_name: String,
+    /// If `true`, the mapping will be applied with each login and a role or group will be removed
+    /// again, when the claim does not match anymore. Otherwise, it will only be applied when the
+    /// user is created.
+    pub sync: bool,
+}
+
+/// The user claim an upstream SAML attribute will be mapped to
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ProviderSamlClaim {
+    Sub,
+    Email,
+    GivenName,
+    FamilyName,
+    Name,
+    Locale,
+    Phone,
+    Birthdate,
+}
+
+impl ProviderSamlClaim {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Sub => "sub",
+            Self::Email => "email",
+            Self::GivenName => "given_name",
+            Self::FamilyName => "family_name",
+            Self::Name => "name",
+            Self::Locale => "locale",
+            Self::Phone => "phone",
+            Self::Birthdate => "birthdate",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Validate, ToSchema)]
+pub struct ProviderSamlAttrMapping {
+    /// The `Name` or `FriendlyName` of the upstream SAML attribute
+    ///
+    /// Validation: `[a-zA-Z0-9,.:/_\\-&?=~#!$'()*+%]`
+    #[validate(regex(path = "*RE_URI", code = "[a-zA-Z0-9,.:/_\\-&?=~#!$'()*+%]"))]
+    pub attribute: String,
+    pub claim: ProviderSamlClaim,
+}
+
+/// JSON paths into the userinfo response of OAuth2 providers, which do not return standard
+/// OIDC claims. Each path resolves to the first matching value.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, Validate, ToSchema)]
+pub struct ProviderUserinfoMapping {
+    /// The unique user id, like `$.id`
+    ///
+    /// Validation: `[a-zA-Z0-9,.:/_\\-&?=~#!$'"()*+%@<>\[\] ]{1,256}`
+    #[validate(regex(path = "*RE_JSON_PATH", code = "JSON path"))]
+    pub sub: Option<String>,
+    /// Validation: `[a-zA-Z0-9,.:/_\\-&?=~#!$'"()*+%@<>\[\] ]{1,256}`
+    #[validate(regex(path = "*RE_JSON_PATH", code = "JSON path"))]
+    pub email: Option<String>,
+    /// The value must either be a boolean or a `"true"` / `"false"` string
+    ///
+    /// Validation: `[a-zA-Z0-9,.:/_\\-&?=~#!$'"()*+%@<>\[\] ]{1,256}`
+    #[validate(regex(path = "*RE_JSON_PATH", code = "JSON path"))]
+    pub email_verified: Option<String>,
+    /// Validation: `[a-zA-Z0-9,.:/_\\-&?=~#!$'"()*+%@<>\[\] ]{1,256}`
+    #[validate(regex(path = "*RE_JSON_PATH", code = "JSON path"))]
+    pub given_name: Option<String>,
+    /// Validation: `[a-zA-Z0-9,.:/_\\-&?=~#!$'"()*+%@<>\[\] ]{1,256}`
+    #[validate(regex(path = "*RE_JSON_PATH", code = "JSON path"))]
+    pub family_name: Option<String>,
+    /// The URL of the users avatar, which will be available as the `picture` claim
+    ///
+    /// Validation: `[a-zA-Z0-9,.:/_\\-&?=~#!$'"()*+%@<>\[\] ]{1,256}`
+    #[validate(regex(path = "*RE_JSON_PATH", code = "JSON path"))]
+    pub avatar: Option<String>,
+
+    /// A secondary endpoint, which will be requested with the access token, if the userinfo
+    /// does not contain an E-Mail, like Githubs `https://api.github.com/user/emails`
+    ///
+    /// Validation: `[a-zA-Z0-9,.:/_\\-&?=~#!$'()*+%]`
+    #[validate(regex(path = "*RE_URI", code = "[a-zA-Z0-9,.:/_\\-&?=~#!$'()*+%]"))]
+    pub email_endpoint: Option<String>,
+    /// Mandatory with an `email_endpoint`, like `$[?@.primary == true].email`
+    ///
+    /// Validation: `[a-zA-Z0-9,.:/_\\-&?=~#!$'"()*+%@<>\[\] ]{1,256}`
+    #[validate(regex(path = "*RE_JSON_PATH", code = "JSON path"))]
+    pub email_endpoint_email: Option<String>,
+    /// The verified flag for the E-Mail from the `email_endpoint`. If not set or not resolvable,
+    /// the E-Mail is treated as unverified.
+    ///
+    /// Validation: `[a-zA-Z0-9,.:/_\\-&?=~#!$'"()*+%@<>\[\] ]{1,256}`
+    #[validate(regex(path = "*RE_JSON_PATH", code = "JSON path"))]
+    pub email_endpoint_verified: Option<String>,
+}
+
+impl ProviderUserinfoMapping {
+    pub fn is_empty(&self) -> bool {
+        self.sub.is_none()
+            && self.email.is_none()
+            && self.email_verified.is_none()
+            && self.given_name.is_none()
+            && self.family_name.is_none()
+            && self.avatar.is_none()
+            && self.email_endpoint.is_none()
+    }
 }
 
 #[derive(Debug, Deserialize, Validate, ToSchema)]
@@ -30,12 +162,22 @@ pub struct ProviderRequest {
     /// Validation: `[a-zA-Z0-9,.:/_\\-&?=~#!$'()*+%]`
     #[validate(regex(path = "*RE_URI", code = "[a-zA-Z0-9,.:/_\\-&?=~#!$'()*+%]"))]
     pub authorization_endpoint: String,
+    /// Mandatory for all types except `saml`
+    ///
     /// Validation: `[a-zA-Z0-9,.:/_\\-&?=~#!$'()*+%]`
     #[validate(regex(path = "*RE_URI", code = "[a-zA-Z0-9,.:/_\\-&?=~#!$'()*+%]"))]
-    pub token_endpoint: String,
+    pub token_endpoint: Option<String>,
+    /// Mandatory for all types except `saml`
+    ///
     /// Validation: `[a-zA-Z0-9,.:/_\\-&?=~#!$'()*+%]`
     #[validate(regex(path = "*RE_URI", code = "[a-zA-Z0-9,.:/_\\-&?=~#!$'()*+%]"))]
-    pub userinfo_endpoint: String,
+    pub userinfo_endpoint: Option<String>,
+    /// If not given, it will be discovered via the `issuer` during the first login with an
+    /// ID token.
+    ///
+    /// Validation: `[a-zA-Z0-9,.:/_\\-&?=~#!$'()*+%]`
+    #[validate(regex(path = "*RE_URI", code = "[a-zA-Z0-9,.:/_\\-&?=~#!$'()*+%]"))]
+    pub jwks_uri: Option<String>,
 
     pub danger_allow_insecure: Option<bool>,
     pub use_pkce: bool,
@@ -72,6 +214,30 @@ pub struct ProviderRequest {
     /// Validation: `[a-zA-Z0-9,.:/_\\-&?=~#!$'()*+%]`
     #[validate(regex(path = "*RE_URI", code = "[a-zA-Z0-9,.:/_\\-&?=~#!$'()*+%]"))]
     pub mfa_claim_value: Option<String>,
+    /// Rules to map upstream claims to roles, groups and custom user attributes
+    #[validate(nested)]
+    pub claim_mappings: Option<Vec<ProviderClaimMapping>>,
+    /// Users with an E-Mail from one of these domains will always be redirected to this
+    /// provider and local password logins will be rejected for them.
+    ///
+    /// Validation: `Vec<^([a-z0-9-]{1,63}\\.)+[a-z]{2,63}$>`
+    #[validate(custom(function = "validate_vec_domain"))]
+    pub email_domains: Option<Vec<String>>,
+
+    /// Mandatory for `saml` - the certificate the IdP signs its responses with, either as PEM
+    /// or as the base64 encoded DER from its metadata
+    ///
+    /// Validation: max length is 8192
+    #[validate(length(max = 8192))]
+    pub saml_idp_cert: Option<String>,
+    /// Additional mappings of upstream SAML attributes to user claims, for attribute names
+    /// which are not detected automatically
+    #[validate(nested)]
+    pub saml_attr_mapping: Option<Vec<ProviderSamlAttrMapping>>,
+
+    /// Custom mapping for OAuth2 providers with a non-standard userinfo response
+    #[validate(nested)]
+    pub userinfo_mapping: Option<ProviderUserinfoMapping>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Validate, ToSchema)]
@@ -132,10 +298,10 @@ pub struct ProviderLoginRequest {
 
 #[derive(Debug, Deserialize, Validate, ToSchema)]
 pub struct ProviderLookupRequest {
-    /// Validation: `[a-zA-Z0-9,.:/_\-&?=~#!$'()*+%]`
+    /// Validation: `[a-zA-Z0-9,.:/_\\-&?=~#!$'()*+%]`
     #[validate(regex(path = "*RE_URI", code = "[a-zA-Z0-9,.:/_\\-&?=~#!$'()*+%]"))]
     pub issuer: Option<String>,
-    /// Validation: `[a-zA-Z0-9,.:/_\-&?=~#!$'()*+%]`
+    /// Validation: `[a-zA-Z0-9,.:/_\\-&?=~#!$'()*+%]`
     #[validate(regex(path = "*RE_URI", code = "[a-zA-Z0-9,.:/_\\-&?=~#!$'()*+%]"))]
     pub metadata_url: Option<String>,
     pub danger_allow_insecure: Option<bool>,
@@ -143,6 +309,44 @@ pub struct ProviderLookupRequest {
     pub root_pem: Option<String>,
 }
 
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+pub struct ProviderSamlLookupRequest {
+    /// Validation: `[a-zA-Z0-9,.:/_\\-&?=~#!$'()*+%]`
+    #[validate(regex(path = "*RE_URI", code = "[a-zA-Z0-9,.:/_\\-&?=~#!$'()*+%]"))]
+    pub metadata_url: Option<String>,
+    /// The IdP metadata XML, if it cannot be fetched via a `metadata_url`
+    ///
+    /// Validation: max length is 131072
+    #[validate(length(max = 131072))]
+    pub metadata: Option<String>,
+    pub danger_allow_insecure: Option<bool>,
+    // no validation since it will throw an error later if not correctly formed
+    pub root_pem: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+pub struct ProviderSamlAcsRequest {
+    /// Validation: max length is 262144
+    #[serde(rename = "SAMLResponse")]
+    #[validate(length(max = 262144))]
+    pub saml_response: String,
+    /// Validation: `[a-zA-Z0-9]`
+    #[serde(rename = "RelayState")]
+    #[validate(regex(path = "*RE_ALNUM", code = "[a-zA-Z0-9]"))]
+    pub relay_state: String,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct ProviderSamlLookupResponse {
+    /// The `entityID` of the IdP
+    pub issuer: String,
+    /// The `SingleSignOnService` for the `HTTP-Redirect` binding
+    pub authorization_endpoint: String,
+    /// The default `entityID` Rauthy uses as a Service Provider
+    pub client_id: String,
+    pub saml_idp_cert: String,
+}
+
 #[derive(Debug, Clone, Serialize, ToSchema)]
 pub struct ProviderResponse {
     pub id: String,
@@ -154,6 +358,7 @@ pub struct ProviderResponse {
     pub authorization_endpoint: String,
     pub token_endpoint: String,
     pub userinfo_endpoint: String,
+    pub jwks_uri: Option<String>,
 
     pub client_id: String,
     pub client_secret: Option<String>,
@@ -163,6 +368,11 @@ pub struct ProviderResponse {
     pub admin_claim_value: Option<String>,
     pub mfa_claim_path: Option<String>,
     pub mfa_claim_value: Option<String>,
+    pub claim_mappings: Vec<ProviderClaimMapping>,
+    pub email_domains: Vec<String>,
+    pub saml_idp_cert: Option<String>,
+    pub saml_attr_mapping: Vec<ProviderSamlAttrMapping>,
+    pub userinfo_mapping: Option<ProviderUserinfoMapping>,
 
     pub danger_allow_insecure: bool,
     pub use_pkce: bool,
@@ -184,6 +394,7 @@ pub struct ProviderLookupResponse<'a> {
     pub authorization_endpoint: String,
     pub token_endpoint: String,
     pub userinfo_endpoint: String,
+    pub jwks_uri: String,
     pub scope: String,
     pub root_pem: &'a Option<String>,
     pub use_pkce: bool,
@@ -1,28 +1,51 @@
 use serde::{Deserialize, Serialize};
-use std::net::Ipv4Addr;
 use utoipa::ToSchema;
 use validator::Validate;
 
 #[derive(Debug, Serialize, Deserialize, Validate, ToSchema)]
 pub struct IpBlacklistRequest {
-    /// Validation: Ipv4Addr
-    #[schema(value_type = str)]
-    pub ip: Ipv4Addr,
+    /// Validation: IPv4 / IPv6 address or CIDR range like `10.0.0.0/8` or `2001:db8::/32`
+    #[validate(length(max = 43))]
+    pub ip: String,
     // TODO max validation for inner i64 is broken in the macro in v0.18.1
     // #[validate(range(min = 1719784800, max = 4070905200))]
-    /// Unix timestamp in seconds
+    /// Unix timestamp in seconds. If not given, the IP will be blacklisted permanently.
     #[validate(range(min = 1719784800))]
-    pub exp: i64,
+    pub exp: Option<i64>,
+    /// Validation: max length 128
+    #[validate(length(max = 128))]
+    pub reason: Option<String>,
 }
 
-#[derive(Debug, Clone, Serialize, ToSchema)]
+#[derive(Debug, Serialize, Deserialize, Validate, ToSchema)]
+pub struct IpAllowlistRequest {
+    /// Validation: IPv4 / IPv6 address or CIDR range like `10.0.0.0/8` or `2001:db8::/32`
+    #[validate(length(max = 43))]
+    pub ip: String,
+    /// Validation: max length 128
+    #[validate(length(max = 128))]
+    pub description: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct BlacklistResponse {
     pub ips: Vec<BlacklistedIp>,
 }
 
-#[derive(Debug, Clone, Serialize, ToSchema)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct BlacklistedIp {
+    /// single IP or CIDR range
+    pub ip: String,
+    /// Unix timestamp in seconds, `None` for permanent entries
+    pub exp: Option<i64>,
+    pub reason: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct AllowlistedIp {
+    /// single IP or CIDR range
     pub ip: String,
+    pub description: Option<String>,
     /// Unix timestamp in seconds
-    pub exp: i64,
+    pub created_at: i64,
 }
@@ -2,7 +2,7 @@ use crate::cust_validation::*;
 use crate::oidc::JwkKeyPairAlg;
 use css_color::Srgb;
 use rauthy_common::constants::{
-    RE_CLIENT_ID_EPHEMERAL, RE_CLIENT_NAME, RE_LOWERCASE, RE_SCOPE_SPACE,
+    RE_ALNUM_24, RE_CLIENT_ID_EPHEMERAL, RE_CLIENT_NAME, RE_LOWERCASE, RE_SCOPE_SPACE,
     RE_TOKEN_ENDPOINT_AUTH_METHOD, RE_URI,
 };
 use rauthy_error::ErrorResponse;
@@ -236,6 +236,49 @@ pub struct UpdateClientRequest {
     /// Validation: `Vec<^[a-zA-Z0-9\+.@/]{0,48}$>`
     #[validate(custom(function = "validate_vec_contact"))]
     pub contacts: Option<Vec<String>>,
+    /// IDs of the upstream auth providers, which may be used to log in to this client.
+    /// `None` allows all providers, an empty list disables upstream logins completely.
+    ///
+    /// Validation: `Vec<^[a-zA-Z0-9]{24}$>`
+    #[validate(custom(function = "validate_vec_provider_ids"))]
+    pub allowed_providers: Option<Vec<String>>,
+    /// Defaults to `true` if not given
+    pub allow_password_login: Option<bool>,
+    /// If `true`, only passwordless logins with a passkey will be accepted.
+    #[serde(default)]
+    pub passkey_only: bool,
+    /// If set, the login page will redirect to this upstream provider immediately.
+    ///
+    /// Validation: `^[a-zA-Z0-9]{24}$`
+    #[validate(regex(path = "*RE_ALNUM_24", code = "^[a-zA-Z0-9]{24}$"))]
+    pub auto_redirect_provider: Option<String>,
+    /// A user needs at least one of these roles or `access_groups` to access this client.
+    ///
+    /// Validation: `Vec<^[a-z0-9-_/,:*]{2,64}$>`
+    #[validate(custom(function = "validate_vec_roles"))]
+    pub access_roles: Option<Vec<String>>,
+    /// A user needs at least one of these groups or `access_roles` to access this client.
+    ///
+    /// Validation: `Vec<^[a-z0-9-_/,:*]{2,64}$>`
+    #[validate(custom(function = "validate_vec_groups"))]
+    pub access_groups: Option<Vec<String>>,
+    /// If set, only users with an E-Mail from one of these domains can access this client.
+    ///
+    /// Validation: `Vec<^([a-z0-9-]{1,63}\.)+[a-z]{2,63}$>`
+    #[validate(custom(function = "validate_vec_domain"))]
+    pub access_email_domains: Option<Vec<String>>,
+    /// If set, logins are only allowed from these countries, in addition to `GEOIP_COUNTRY_ALLOW`.
+    /// Needs a configured GeoIP database.
+    ///
+    /// Validation: `Vec<^[a-zA-Z]{2}$>`
+    #[validate(custom(function = "validate_vec_country_code"))]
+    pub geo_allow_countries: Option<Vec<String>>,
+    /// Logins from these countries are denied, in addition to `GEOIP_COUNTRY_DENY`.
+    /// Needs a configured GeoIP database.
+    ///
+    /// Validation: `Vec<^[a-zA-Z]{2}$>`
+    #[validate(custom(function = "validate_vec_country_code"))]
+    pub geo_deny_countries: Option<Vec<String>>,
 }
 
 #[derive(Debug, Serialize, Deserialize, ToSchema)]
@@ -262,6 +305,22 @@ pub struct ClientResponse {
     pub force_mfa: bool,
     pub client_uri: Option<String>,
     pub contacts: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub allowed_providers: Option<Vec<String>>,
+    pub allow_password_login: bool,
+    pub passkey_only: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub auto_redirect_provider: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub access_roles: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub access_groups: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub access_email_domains: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub geo_allow_countries: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub geo_deny_countries: Option<Vec<String>>,
 }
 
 #[derive(Debug, Serialize, Deserialize, ToSchema)]
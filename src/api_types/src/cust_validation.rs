@@ -1,5 +1,6 @@
 use rauthy_common::constants::{
-    RE_ATTR, RE_CHALLENGE, RE_CONTACT, RE_GRANT_TYPES, RE_GROUPS, RE_ORIGIN, RE_URI,
+    RE_ALNUM_24, RE_ATTR, RE_CHALLENGE, RE_CONTACT, RE_COUNTRY_CODE, RE_DOMAIN, RE_GRANT_TYPES,
+    RE_GROUPS, RE_LOWERCASE, RE_ORIGIN, RE_URI,
 };
 use validator::ValidationError;
 
@@ -41,6 +42,19 @@ pub fn validate_vec_challenge(value: &[String]) -> Result<(), ValidationError> {
     Ok(())
 }
 
+pub fn validate_vec_client_ids(value: &[String]) -> Result<(), ValidationError> {
+    let mut err = None;
+    value.iter().for_each(|v| {
+        if !RE_LOWERCASE.is_match(v) {
+            err = Some("^[a-z0-9-_/]{2,128}$");
+        }
+    });
+    if let Some(e) = err {
+        return Err(ValidationError::new(e));
+    }
+    Ok(())
+}
+
 pub fn validate_vec_contact(value: &[String]) -> Result<(), ValidationError> {
     let mut err = None;
     value.iter().for_each(|v| {
@@ -54,6 +68,32 @@ pub fn validate_vec_contact(value: &[String]) -> Result<(), ValidationError> {
     Ok(())
 }
 
+pub fn validate_vec_country_code(value: &[String]) -> Result<(), ValidationError> {
+    let mut err = None;
+    value.iter().for_each(|v| {
+        if !RE_COUNTRY_CODE.is_match(v) {
+            err = Some("^[a-zA-Z]{2}$");
+        }
+    });
+    if let Some(e) = err {
+        return Err(ValidationError::new(e));
+    }
+    Ok(())
+}
+
+pub fn validate_vec_domain(value: &[String]) -> Result<(), ValidationError> {
+    let mut err = None;
+    value.iter().for_each(|v| {
+        if !RE_DOMAIN.is_match(v) {
+            err = Some("^([a-z0-9-]{1,63}\\.)+[a-z]{2,63}$");
+        }
+    });
+    if let Some(e) = err {
+        return Err(ValidationError::new(e));
+    }
+    Ok(())
+}
+
 pub fn validate_vec_grant_types(value: &[String]) -> Result<(), ValidationError> {
     let mut err = None;
 
@@ -86,6 +126,19 @@ pub fn validate_vec_origin(value: &[String]) -> Result<(), ValidationError> {
     Ok(())
 }
 
+pub fn validate_vec_provider_ids(value: &[String]) -> Result<(), ValidationError> {
+    let mut err = None;
+    value.iter().for_each(|v| {
+        if !RE_ALNUM_24.is_match(v) {
+            err = Some("^[a-zA-Z0-9]{24}$");
+        }
+    });
+    if let Some(e) = err {
+        return Err(ValidationError::new(e));
+    }
+    Ok(())
+}
+
 pub fn validate_vec_uri(value: &[String]) -> Result<(), ValidationError> {
     let mut err = None;
     value.iter().for_each(|v| {
@@ -13,6 +13,8 @@ pub enum EventLevel {
 
 #[derive(Debug, PartialEq, Serialize, Deserialize, ToSchema)]
 pub enum EventType {
+    ClientAccessDenied,
+    ImpossibleTravel,
     InvalidLogins,
     IpBlacklisted,
     IpBlacklistRemoved,
@@ -26,7 +28,11 @@ pub enum EventType {
     RauthyUnhealthy,
     SecretsMigrated,
     UserEmailChange,
+    UserImpersonated,
+    UserLocked,
     UserPasswordReset,
+    UserRegistrationPending,
+    UserUnlocked,
     Test,
 }
 
@@ -1,4 +1,5 @@
-use rauthy_common::constants::{RE_ALNUM, RE_SEARCH};
+use crate::cust_validation::{validate_vec_client_ids, validate_vec_groups};
+use rauthy_common::constants::{RE_ALNUM, RE_CLIENT_NAME, RE_SEARCH};
 use serde::{Deserialize, Serialize};
 use utoipa::{IntoParams, ToSchema};
 use validator::Validate;
@@ -24,8 +25,10 @@ pub enum I18nContent {
     Error,
     Index,
     Logout,
+    NotMe,
     PasswordReset,
     Register,
+    Unlock,
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
@@ -46,6 +49,12 @@ pub struct PaginationParams {
     pub continuation_token: Option<String>,
 }
 
+#[derive(Debug, Deserialize, Validate, ToSchema, IntoParams)]
+pub struct PasswordPolicyParams {
+    /// Return the global default instead of the effective policy for the current user
+    pub global: Option<bool>,
+}
+
 #[derive(Debug, Serialize, Deserialize, Validate, ToSchema)]
 pub struct PasswordHashTimesRequest {
     #[validate(range(min = 500))]
@@ -82,6 +91,34 @@ pub struct PasswordPolicyRequest {
     /// Validation: `1 <= not_recently_used <= 10`
     #[validate(range(min = 1, max = 10))]
     pub not_recently_used: Option<i32>,
+    /// Minimum zxcvbn strength score - validation: `1 <= min_strength <= 4`
+    #[validate(range(min = 1, max = 4))]
+    pub min_strength: Option<i32>,
+    /// Check new passwords against the breached passwords corpus from `PASSWORD_BREACHED_PATH`
+    pub check_breached: Option<bool>,
+    /// Words, which must not be part of a new password. They are compared case-insensitive.
+    /// Validation: max 256 words
+    #[validate(length(max = 256))]
+    pub banned_words: Option<Vec<String>>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Validate, ToSchema)]
+pub struct ScopedPasswordPolicyRequest {
+    /// Validation: `[a-zA-Z0-9À-ÿ-\s]{2,128}`
+    #[validate(regex(path = "*RE_CLIENT_NAME", code = "[a-zA-Z0-9À-ſ-\\s]{2,128}"))]
+    pub name: String,
+    #[validate(nested)]
+    pub policy: PasswordPolicyRequest,
+    /// The policy applies to all members of these groups instead of the global one.
+    ///
+    /// Validation: `Vec<^[a-z0-9-_/,:*]{2,64}$>`
+    #[validate(custom(function = "validate_vec_groups"))]
+    pub groups: Option<Vec<String>>,
+    /// The policy is enforced during the password login for these clients.
+    ///
+    /// Validation: `Vec<^[a-z0-9-_/]{2,128}$>`
+    #[validate(custom(function = "validate_vec_client_ids"))]
+    pub clients: Option<Vec<String>>,
 }
 
 #[derive(Debug, Deserialize, Validate, ToSchema)]
@@ -137,6 +174,18 @@ pub struct EncKeysResponse<'a> {
     pub keys: Vec<&'a str>,
 }
 
+/// The location of an IP from the configured GeoIP databases
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct GeoLocationResponse {
+    /// ISO 3166-1 alpha-2 country code
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub country: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub asn: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub asn_org: Option<String>,
+}
+
 #[derive(Debug, Default, Serialize, ToSchema)]
 pub struct HealthResponse {
     pub db_healthy: bool,
@@ -166,4 +215,17 @@ pub struct PasswordPolicyResponse {
     pub valid_days: Option<i32>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub not_recently_used: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_strength: Option<i32>,
+    pub check_breached: bool,
+    pub banned_words: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ScopedPasswordPolicyResponse {
+    pub id: String,
+    pub name: String,
+    pub policy: PasswordPolicyResponse,
+    pub groups: Option<Vec<String>>,
+    pub clients: Option<Vec<String>>,
 }
@@ -0,0 +1,77 @@
+use crate::cust_validation::{validate_vec_groups, validate_vec_roles};
+use crate::generic::Language;
+use crate::users::{UserAttrValueRequest, UserAttrValueResponse};
+use rauthy_common::constants::{RE_ALNUM_24, RE_URI, RE_USER_NAME};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use validator::Validate;
+
+#[derive(Debug, Serialize, Deserialize, Validate, ToSchema)]
+pub struct UserInvitationRequest {
+    /// Validation: `email`
+    #[validate(email)]
+    pub email: String,
+    /// Validation: `[a-zA-Z0-9À-ÿ-\\s]{1,32}`
+    #[validate(regex(path = "*RE_USER_NAME", code = "[a-zA-Z0-9À-ſ-\\s]{1,32}"))]
+    pub given_name: String,
+    /// Validation: `[a-zA-Z0-9À-ÿ-\\s]{1,32}`
+    #[validate(regex(path = "*RE_USER_NAME", code = "[a-zA-Z0-9À-ſ-\\s]{1,32}"))]
+    pub family_name: Option<String>,
+    pub language: Option<Language>,
+    /// Roles that will be assigned when the invitation has been accepted.
+    /// Can only be set by a `rauthy_admin`.
+    ///
+    /// Validation: `Vec<^[a-z0-9-_/,:*]{2,64}$>`
+    #[validate(custom(function = "validate_vec_roles"))]
+    pub roles: Option<Vec<String>>,
+    /// Groups that will be assigned when the invitation has been accepted.
+    /// Group scoped admins must provide at least one group inside their scope.
+    ///
+    /// Validation: `Vec<^[a-z0-9-_/,:*]{2,64}$>`
+    #[validate(custom(function = "validate_vec_groups"))]
+    pub groups: Option<Vec<String>>,
+    /// Custom user attribute values that will be set when the invitation has been accepted.
+    /// Can only be set by a `rauthy_admin`.
+    #[validate(nested)]
+    pub attributes: Option<Vec<UserAttrValueRequest>>,
+    /// Optional organization the user will become a member of
+    ///
+    /// Validation: `^[a-zA-Z0-9]{24}$`
+    #[validate(regex(path = "*RE_ALNUM_24", code = "^[a-zA-Z0-9]{24}$"))]
+    pub org_id: Option<String>,
+    /// Must be a subset of the organizations' roles
+    ///
+    /// Validation: `Vec<^[a-z0-9-_/,:*]{2,64}$>`
+    #[validate(custom(function = "validate_vec_roles"))]
+    pub org_roles: Option<Vec<String>>,
+    /// Unix timestamp in seconds when the invitation expires.
+    /// Defaults to the lifetime of the magic link for new users.
+    #[validate(range(min = 1719784800))]
+    pub exp: Option<i64>,
+    /// Redirect URI used after the account setup has been finished
+    ///
+    /// Validation: `[a-zA-Z0-9,.:/_\-&?=~#!$'()*+%]+`
+    #[validate(regex(path = "*RE_URI", code = "[a-zA-Z0-9,.:/_\\-&?=~#!$'()*+%]+"))]
+    pub redirect_uri: Option<String>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct UserInvitationResponse {
+    pub id: String,
+    pub user_id: String,
+    pub email: String,
+    pub roles: Vec<String>,
+    pub groups: Option<Vec<String>>,
+    pub attributes: Option<Vec<UserAttrValueResponse>>,
+    pub org_id: Option<String>,
+    pub org_roles: Option<Vec<String>>,
+    pub redirect_uri: Option<String>,
+    /// The user id of the admin or the name of the API Key that created the invitation
+    pub created_by: String,
+    /// Unix timestamp in seconds
+    pub created_at: i64,
+    /// Unix timestamp in seconds
+    pub exp: i64,
+    /// Unix timestamp in seconds, `None` while the invitation is still pending
+    pub accepted_at: Option<i64>,
+}
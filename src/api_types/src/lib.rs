@@ -1,3 +1,4 @@
+pub mod admin_roles;
 pub mod api_keys;
 pub mod auth_providers;
 pub mod blacklist;
@@ -7,8 +8,11 @@ pub mod events;
 pub mod fed_cm;
 pub mod generic;
 pub mod groups;
+pub mod invitations;
 pub mod oidc;
+pub mod organizations;
 pub mod roles;
+pub mod saml;
 pub mod scopes;
 pub mod sessions;
 pub mod users;
@@ -4,8 +4,8 @@ use crate::sessions::SessionState;
 use actix_web::http::header;
 use actix_web::HttpRequest;
 use rauthy_common::constants::{
-    RE_ALNUM, RE_CLIENT_ID_EPHEMERAL, RE_CODE_VERIFIER, RE_GRANT_TYPES, RE_LOWERCASE,
-    RE_SCOPE_SPACE, RE_URI,
+    RE_ALNUM, RE_ALNUM_24, RE_CLIENT_ID_EPHEMERAL, RE_CODE_VERIFIER, RE_EMAIL_CODE, RE_GRANT_TYPES,
+    RE_LOWERCASE, RE_SCOPE_SPACE, RE_URI,
 };
 use rauthy_common::utils::base64_decode;
 use rauthy_error::{ErrorResponse, ErrorResponseType};
@@ -134,6 +134,26 @@ pub struct LoginRequest {
     /// Validation: `[a-zA-Z0-9]`
     #[validate(regex(path = "*RE_ALNUM", code = "[a-zA-Z0-9]"))]
     pub code_challenge_method: Option<String>,
+    /// The selected organization, if the `organization` scope is requested and the user is a
+    /// member of multiple organizations.
+    ///
+    /// Validation: `[a-zA-Z0-9]{24}`
+    #[validate(regex(path = "*RE_ALNUM_24", code = "[a-zA-Z0-9]{24}"))]
+    pub org_id: Option<String>,
+    /// A solved PoW, which is only required after a possible brute-force attack against the
+    /// account or from the client IP has been detected.
+    ///
+    /// Validation: `[a-zA-Z0-9,.:/_-&?=~#!$'()*+%]+$`
+    #[validate(regex(path = "*RE_URI", code = "[a-zA-Z0-9,.:/_-&?=~#!$'()*+%]+$"))]
+    pub pow: Option<String>,
+    /// Remember this device and skip the 2nd factor for `MFA_TRUSTED_DEVICE_DAYS` on it.
+    pub trust_device: Option<bool>,
+    /// The code from the E-Mail, which is required for logins from new devices without a
+    /// passkey, if `MFA_NEW_DEVICE_EMAIL_CODE` is enabled.
+    ///
+    /// Validation: `^[0-9]{6}$`
+    #[validate(regex(path = "*RE_EMAIL_CODE", code = "^[0-9]{6}$"))]
+    pub email_code: Option<String>,
 }
 
 #[derive(Debug, Deserialize, Validate, ToSchema)]
@@ -162,6 +182,12 @@ pub struct LoginRefreshRequest {
     /// Validation: `[a-zA-Z0-9]`
     #[validate(regex(path = "*RE_ALNUM", code = "[a-zA-Z0-9]"))]
     pub code_challenge_method: Option<String>,
+    /// The selected organization, if the `organization` scope is requested and the user is a
+    /// member of multiple organizations.
+    ///
+    /// Validation: `[a-zA-Z0-9]{24}`
+    #[validate(regex(path = "*RE_ALNUM_24", code = "[a-zA-Z0-9]{24}"))]
+    pub org_id: Option<String>,
 }
 
 #[derive(Debug, Deserialize, Validate, ToSchema, IntoParams)]
@@ -309,6 +335,20 @@ pub struct JktClaim {
     pub jkt: String,
 }
 
+/// RFC 8693 actor claim - the `sub` is the user id of the acting admin during impersonation
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ActClaim {
+    pub sub: String,
+}
+
+/// The `org` claim, added when the `organization` scope was requested
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct OrgClaim {
+    pub id: String,
+    pub name: String,
+    pub roles: Vec<String>,
+}
+
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
 pub enum JwkKeyPairAlg {
     RS256,
@@ -411,6 +451,9 @@ pub struct SessionInfoResponse<'a> {
     #[serde(with = "time::serde::rfc3339")]
     pub timeout: OffsetDateTime,
     pub state: SessionState,
+    /// The user id of the admin, if this is an impersonated session
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub impersonated_by: Option<Cow<'a, str>>,
 }
 
 #[derive(Debug, Default, Serialize, Deserialize, ToSchema)]
@@ -0,0 +1,80 @@
+use crate::cust_validation::validate_vec_roles;
+use rauthy_common::constants::{RE_ATTR_DESC, RE_CLIENT_NAME, RE_USER_NAME};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use validator::Validate;
+
+#[derive(Debug, Serialize, Deserialize, Validate, ToSchema)]
+pub struct OrgRequest {
+    /// Validation: `[a-zA-Z0-9À-ÿ-\\s]{2,128}`
+    #[validate(regex(path = "*RE_CLIENT_NAME", code = "[a-zA-Z0-9À-ſ-\\s]{2,128}"))]
+    pub name: String,
+    /// Validation: `[a-zA-Z0-9-_/\s]{0,128}`
+    #[validate(regex(path = "*RE_ATTR_DESC", code = "[a-zA-Z0-9-_/\\s]{0,128}"))]
+    pub description: Option<String>,
+    /// The roles that exist inside this organization and can be assigned to its members.
+    /// These are independent of the global Rauthy roles.
+    ///
+    /// Validation: `Vec<^[a-z0-9-_/,:*]{2,64}$>`
+    #[validate(custom(function = "validate_vec_roles"))]
+    pub roles: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Validate, ToSchema)]
+pub struct OrgMemberRequest {
+    /// Must be a subset of the organizations' roles
+    ///
+    /// Validation: `Vec<^[a-z0-9-_/,:*]{2,64}$>`
+    #[validate(custom(function = "validate_vec_roles"))]
+    pub roles: Vec<String>,
+    /// Org admins can manage the members of this organization. This can only be granted
+    /// by a `rauthy_admin`.
+    pub is_org_admin: Option<bool>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Validate, ToSchema)]
+pub struct OrgInviteRequest {
+    /// Validation: `email`
+    #[validate(email)]
+    pub email: String,
+    /// Only used if a new user must be created.
+    ///
+    /// Validation: `[a-zA-Z0-9À-ÿ-\\s]{1,32}`
+    #[validate(regex(path = "*RE_USER_NAME", code = "[a-zA-Z0-9À-ſ-\\s]{1,32}"))]
+    pub given_name: Option<String>,
+    /// Only used if a new user must be created.
+    ///
+    /// Validation: `[a-zA-Z0-9À-ÿ-\\s]{1,32}`
+    #[validate(regex(path = "*RE_USER_NAME", code = "[a-zA-Z0-9À-ſ-\\s]{1,32}"))]
+    pub family_name: Option<String>,
+    /// Must be a subset of the organizations' roles
+    ///
+    /// Validation: `Vec<^[a-z0-9-_/,:*]{2,64}$>`
+    #[validate(custom(function = "validate_vec_roles"))]
+    pub roles: Vec<String>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct OrgResponse {
+    pub id: String,
+    pub name: String,
+    pub description: Option<String>,
+    pub roles: Vec<String>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct OrgMemberResponse {
+    pub org_id: String,
+    pub user_id: String,
+    pub email: String,
+    pub roles: Vec<String>,
+    pub is_org_admin: bool,
+}
+
+/// Returned as JSON inside the error message, when a user is a member of multiple organizations
+/// and needs to select one during the authorization.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct OrgSelection {
+    pub id: String,
+    pub name: String,
+}
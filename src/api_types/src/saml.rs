@@ -0,0 +1,185 @@
+use rauthy_common::constants::{RE_ALNUM, RE_ATTR, RE_URI};
+use serde::{Deserialize, Serialize};
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+use utoipa::{IntoParams, ToSchema};
+use validator::Validate;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum SamlNameIdFormat {
+    /// `urn:oasis:names:tc:SAML:1.1:nameid-format:emailAddress` -> the users E-Mail
+    #[default]
+    Email,
+    /// `urn:oasis:names:tc:SAML:2.0:nameid-format:persistent` -> the users ID
+    Persistent,
+    /// `urn:oasis:names:tc:SAML:2.0:nameid-format:transient` -> a random value for each login
+    Transient,
+    /// `urn:oasis:names:tc:SAML:1.1:nameid-format:unspecified` -> the users ID
+    Unspecified,
+}
+
+impl Display for SamlNameIdFormat {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl FromStr for SamlNameIdFormat {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let slf = match s {
+            "email" => Self::Email,
+            "persistent" => Self::Persistent,
+            "transient" => Self::Transient,
+            "unspecified" => Self::Unspecified,
+            _ => return Err(()),
+        };
+        Ok(slf)
+    }
+}
+
+impl SamlNameIdFormat {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Email => "email",
+            Self::Persistent => "persistent",
+            Self::Transient => "transient",
+            Self::Unspecified => "unspecified",
+        }
+    }
+
+    pub fn as_urn(&self) -> &'static str {
+        match self {
+            Self::Email => "urn:oasis:names:tc:SAML:1.1:nameid-format:emailAddress",
+            Self::Persistent => "urn:oasis:names:tc:SAML:2.0:nameid-format:persistent",
+            Self::Transient => "urn:oasis:names:tc:SAML:2.0:nameid-format:transient",
+            Self::Unspecified => "urn:oasis:names:tc:SAML:1.1:nameid-format:unspecified",
+        }
+    }
+
+    pub fn from_urn(urn: &str) -> Option<Self> {
+        [
+            Self::Email,
+            Self::Persistent,
+            Self::Transient,
+            Self::Unspecified,
+        ]
+        .into_iter()
+        .find(|f| f.as_urn() == urn)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum SamlAttrSource {
+    UserId,
+    Email,
+    GivenName,
+    FamilyName,
+    Roles,
+    Groups,
+    /// A custom user attribute, the key must be given with `attr_key`
+    Attribute,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Validate, ToSchema)]
+pub struct SamlAttrMapping {
+    /// The `Name` of the SAML attribute inside the assertion
+    ///
+    /// Validation: `[a-zA-Z0-9,.:/_\\-&?=~#!$'()*+%]+$`
+    #[validate(regex(path = "*RE_URI", code = "[a-zA-Z0-9,.:/_\\-&?=~#!$'()*+%]+$"))]
+    pub name: String,
+    pub source: SamlAttrSource,
+    /// Mandatory for `source: attribute`
+    ///
+    /// Validation: `^[a-zA-Z0-9-_/]{2,32}$`
+    #[validate(regex(path = "*RE_ATTR", code = "^[a-zA-Z0-9-_/]{2,32}$"))]
+    pub attr_key: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+pub struct SamlClientRequest {
+    /// The `entityID` of the Service Provider
+    ///
+    /// Validation: `[a-zA-Z0-9,.:/_\\-&?=~#!$'()*+%]+$`
+    #[validate(regex(path = "*RE_URI", code = "[a-zA-Z0-9,.:/_\\-&?=~#!$'()*+%]+$"))]
+    pub entity_id: String,
+    /// The Assertion Consumer Service URL with the `HTTP-POST` binding
+    ///
+    /// Validation: `[a-zA-Z0-9,.:/_\\-&?=~#!$'()*+%]+$`
+    #[validate(regex(path = "*RE_URI", code = "[a-zA-Z0-9,.:/_\\-&?=~#!$'()*+%]+$"))]
+    pub acs_url: String,
+    /// The Single Logout Service URL with the `HTTP-POST` binding
+    ///
+    /// Validation: `[a-zA-Z0-9,.:/_\\-&?=~#!$'()*+%]+$`
+    #[validate(regex(path = "*RE_URI", code = "[a-zA-Z0-9,.:/_\\-&?=~#!$'()*+%]+$"))]
+    pub slo_url: Option<String>,
+    pub name_id_format: SamlNameIdFormat,
+    /// The SPs signing certificate in PEM format. If given, `AuthnRequest`s and `LogoutRequest`s
+    /// with the `HTTP-Redirect` binding must be signed.
+    #[validate(length(max = 8192))]
+    pub sp_cert: Option<String>,
+    /// If not given, `email`, `given_name`, `family_name`, `roles` and `groups` will be mapped.
+    #[validate(nested)]
+    pub attr_mapping: Option<Vec<SamlAttrMapping>>,
+}
+
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+pub struct SamlMetadataRequest {
+    /// The raw XML `EntityDescriptor` of the Service Provider
+    #[validate(length(max = 131072))]
+    pub metadata: String,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct SamlClientResponse {
+    pub id: String,
+    pub entity_id: String,
+    pub acs_url: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub slo_url: Option<String>,
+    pub name_id_format: SamlNameIdFormat,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sp_cert: Option<String>,
+    pub attr_mapping: Vec<SamlAttrMapping>,
+    /// The `redirect_uri` which Rauthy adds to the client to resume the login flow.
+    pub resume_uri: String,
+}
+
+/// The SAML protocol parameters for both the `HTTP-Redirect` and `HTTP-POST` bindings
+#[derive(Debug, Deserialize, Validate, ToSchema, IntoParams)]
+pub struct SamlRequestParams {
+    #[serde(rename = "SAMLRequest")]
+    #[validate(length(max = 65536))]
+    pub saml_request: String,
+    #[serde(rename = "RelayState")]
+    #[validate(length(max = 1024))]
+    pub relay_state: Option<String>,
+    /// Validation: `[a-zA-Z0-9,.:/_\\-&?=~#!$'()*+%]+$`
+    #[serde(rename = "SigAlg")]
+    #[validate(regex(path = "*RE_URI", code = "[a-zA-Z0-9,.:/_\\-&?=~#!$'()*+%]+$"))]
+    pub sig_alg: Option<String>,
+    #[serde(rename = "Signature")]
+    #[validate(length(max = 2048))]
+    pub signature: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Validate, ToSchema, IntoParams)]
+pub struct SamlIdpInitiatedParams {
+    /// Will be passed to the Service Provider as-is
+    #[serde(rename = "RelayState")]
+    #[validate(length(max = 1024))]
+    pub relay_state: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Validate, ToSchema, IntoParams)]
+pub struct SamlResumeParams {
+    /// Validation: `[a-zA-Z0-9]`
+    #[validate(regex(path = "*RE_ALNUM", code = "[a-zA-Z0-9]"))]
+    pub code: String,
+    /// Validation: `[a-zA-Z0-9]`
+    #[validate(regex(path = "*RE_ALNUM", code = "[a-zA-Z0-9]"))]
+    pub state: String,
+}
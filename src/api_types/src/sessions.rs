@@ -1,3 +1,4 @@
+use crate::generic::GeoLocationResponse;
 use serde::{Deserialize, Serialize};
 use utoipa::ToSchema;
 
@@ -22,4 +23,6 @@ pub struct SessionResponse<'a> {
     /// Unix timestamp in seconds
     pub last_seen: i64,
     pub remote_ip: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub remote_geo: Option<GeoLocationResponse>,
 }
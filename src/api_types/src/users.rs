@@ -1,12 +1,12 @@
 use crate::cust_validation::{validate_vec_groups, validate_vec_roles};
-use crate::generic::Language;
+use crate::generic::{GeoLocationResponse, Language};
 use crate::oidc::AddressClaim;
 use rauthy_common::constants::{
     RE_ALNUM_48, RE_ALNUM_64, RE_APP_ID, RE_ATTR, RE_ATTR_DESC, RE_CITY, RE_CLIENT_NAME,
     RE_DATE_STR, RE_MFA_CODE, RE_PHONE, RE_STREET, RE_URI, RE_USER_NAME,
 };
 use serde::{Deserialize, Serialize};
-use utoipa::ToSchema;
+use utoipa::{IntoParams, ToSchema};
 use validator::Validate;
 
 #[derive(Debug, Clone, Deserialize, Validate, ToSchema)]
@@ -78,6 +78,9 @@ pub struct NewUserRegistrationRequest {
     /// Validation: `[a-zA-Z0-9,.:/_\-&?=~#!$'()*+%]+`
     #[validate(regex(path = "*RE_URI", code = "[a-zA-Z0-9,.:/_\\-&?=~#!$'()*+%]+"))]
     pub redirect_uri: Option<String>,
+    /// Values for all custom user attributes, that are flagged for the registration
+    #[validate(nested)]
+    pub attributes: Option<Vec<UserAttrValueRequest>>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Validate, ToSchema)]
@@ -191,6 +194,8 @@ pub struct UserAttrConfigRequest {
     /// Validation: `^[a-zA-Z0-9-_/]{0,128}$`
     #[validate(regex(path = "*RE_ATTR_DESC", code = "[a-zA-Z0-9À-ÿ-\\s]{2,128}"))]
     pub desc: Option<String>,
+    /// If `true`, a value for this attribute must be provided during the open user registration
+    pub registration: Option<bool>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Validate, ToSchema)]
@@ -207,6 +212,52 @@ pub struct UserAttrValuesUpdateRequest {
     pub values: Vec<UserAttrValueRequest>,
 }
 
+#[derive(Debug, Deserialize, Validate, ToSchema, IntoParams)]
+pub struct UserExportParams {
+    /// Include password hashes and passkeys. Only allowed for a `rauthy_admin` session.
+    pub with_secrets: Option<bool>,
+}
+
+#[derive(Debug, Deserialize, Validate, ToSchema, IntoParams)]
+pub struct UserImportParams {
+    /// Only validate the import and report what would happen without changing any data
+    pub dry_run: Option<bool>,
+}
+
+/// A single user for the bulk import. Users without a `password_hash` will receive an E-Mail to
+/// set up their account, just like for `POST /users`.
+#[derive(Debug, Serialize, Deserialize, Validate, ToSchema)]
+pub struct UserImportRequest {
+    /// Validation: `email`
+    #[validate(email)]
+    pub email: String,
+    /// Validation: `[a-zA-Z0-9À-ÿ-\\s]{1,32}`
+    #[validate(regex(path = "*RE_USER_NAME", code = "[a-zA-Z0-9À-ſ-\\s]{1,32}"))]
+    pub family_name: Option<String>,
+    /// Validation: `[a-zA-Z0-9À-ÿ-\\s]{1,32}`
+    #[validate(regex(path = "*RE_USER_NAME", code = "[a-zA-Z0-9À-ſ-\\s]{1,32}"))]
+    pub given_name: String,
+    pub language: Option<Language>,
+    /// Validation: `Vec<^[a-z0-9-_/,:*]{2,64}$>`
+    #[validate(custom(function = "validate_vec_groups"))]
+    pub groups: Option<Vec<String>>,
+    /// Validation: `Vec<^[a-z0-9-_/,:*]{2,64}$>`
+    #[validate(custom(function = "validate_vec_roles"))]
+    pub roles: Option<Vec<String>>,
+    /// Defaults to `false`
+    pub email_verified: Option<bool>,
+    /// Existing hash from another system. Supported formats are `argon2id`, `bcrypt`,
+    /// `pbkdf2-sha256` / `pbkdf2-sha512` (Django, Keycloak or passlib notation), `scrypt` and
+    /// `sha512-crypt`. Legacy hashes will be upgraded to `argon2id` with the next login.
+    ///
+    /// Validation: `max length 1024`
+    #[validate(length(max = 1024))]
+    pub password_hash: Option<String>,
+    /// Unix timestamp in seconds
+    #[validate(range(min = 1719784800))]
+    pub user_expires: Option<i64>,
+}
+
 #[derive(Debug, Serialize, Deserialize, Validate, ToSchema)]
 pub struct WebauthnAuthStartRequest {
     pub purpose: MfaPurpose,
@@ -265,9 +316,29 @@ pub struct DeviceResponse {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub refresh_exp: Option<i64>,
     pub peer_ip: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub peer_geo: Option<GeoLocationResponse>,
     pub name: String,
 }
 
+#[derive(Debug, Serialize, ToSchema)]
+pub struct TrustedDeviceResponse {
+    pub id: String,
+    /// Derived from the `User-Agent` at the time the device has been trusted
+    pub name: String,
+    /// Unix timestamp in seconds
+    pub created: i64,
+    /// Unix timestamp in seconds
+    pub last_used: i64,
+    /// Unix timestamp in seconds
+    pub exp: i64,
+    pub ip: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub country: Option<String>,
+    /// `true` if the request for this listing comes from this very device
+    pub current: bool,
+}
+
 #[derive(Debug, Serialize, Deserialize, ToSchema)]
 pub struct PasskeyResponse {
     pub name: String,
@@ -282,6 +353,7 @@ pub struct PasskeyResponse {
 pub struct UserAttrConfigValueResponse {
     pub name: String,
     pub desc: Option<String>,
+    pub registration: bool,
 }
 
 #[derive(Debug, Serialize, Deserialize, ToSchema)]
@@ -300,6 +372,59 @@ pub struct UserAttrValuesResponse {
     pub values: Vec<UserAttrValueResponse>,
 }
 
+/// An upstream identity, which has been linked to a user account
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct UserFederationResponse {
+    pub provider_id: String,
+    pub provider_name: String,
+    pub federation_uid: String,
+    /// `true` for the identity, which is in sync with the users' E-Mail and names
+    pub is_primary: bool,
+    /// Unix timestamp in seconds
+    pub created_at: i64,
+    /// Unix timestamp in seconds
+    pub last_login: Option<i64>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct UserImportErrorResponse {
+    /// Index of the entry or line inside the request, starting at 1
+    pub entry: usize,
+    pub email: String,
+    pub error: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct UserImportResponse {
+    pub imported: usize,
+    /// Entries, which have been skipped because of an error
+    pub errors: Vec<UserImportErrorResponse>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct UserUpsertResponse {
+    pub dry_run: bool,
+    pub created: usize,
+    pub updated: usize,
+    pub failed: usize,
+    /// Details for failed lines, capped at the first 1000
+    pub errors: Vec<UserImportErrorResponse>,
+}
+
+/// A pending registration from the open user registration, that needs to be approved
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct UserRegistrationResponse {
+    pub id: String,
+    pub email: String,
+    pub given_name: String,
+    pub family_name: Option<String>,
+    pub language: Language,
+    pub attributes: Vec<UserAttrValueResponse>,
+    pub ip: String,
+    /// Unix timestamp in seconds
+    pub created_at: i64,
+}
+
 #[derive(Debug, Serialize, ToSchema)]
 pub struct Userinfo {
     pub id: String,
@@ -386,6 +511,12 @@ pub struct UserResponse {
     pub user_values: UserValuesResponse,
     pub auth_provider_id: Option<String>,
     pub federation_uid: Option<String>,
+    /// `true` while the account is locked because of too many failed logins
+    #[serde(default)]
+    pub locked: bool,
+    /// Unix timestamp in seconds, `None` for locks, which need a manual unlock
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub locked_until: Option<i64>,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
@@ -10,14 +10,14 @@ use cryptr::EncKeys;
 use hiqlite::params;
 use prometheus::Registry;
 use rauthy_common::constants::{
-    APP_START, RAUTHY_VERSION, SWAGGER_UI_EXTERNAL, SWAGGER_UI_INTERNAL,
+    APP_START, LDAP_ENABLE, RAUTHY_VERSION, SWAGGER_UI_EXTERNAL, SWAGGER_UI_INTERNAL,
 };
 use rauthy_common::utils::UseDummyAddress;
 use rauthy_common::{is_hiqlite, is_sqlite, password_hasher};
 use rauthy_handlers::openapi::ApiDoc;
 use rauthy_handlers::{
-    api_keys, auth_providers, blacklist, clients, events, fed_cm, generic, groups, oidc, roles,
-    scopes, sessions, users,
+    admin_roles, api_keys, auth_providers, blacklist, clients, events, fed_cm, generic, groups,
+    invitations, oidc, organizations, roles, saml, scopes, sessions, users,
 };
 use rauthy_middlewares::csrf_protection::CsrfProtectionMiddleware;
 use rauthy_middlewares::ip_blacklist::RauthyIpBlacklistMiddleware;
@@ -32,7 +32,9 @@ use rauthy_models::events::health_watch::watch_health;
 use rauthy_models::events::listener::EventListener;
 use rauthy_models::events::notifier::EventNotifier;
 use rauthy_models::events::{init_event_vars, ip_blacklist_handler};
+use rauthy_models::geoip;
 use rauthy_models::{email, ListenScheme};
+use rauthy_service::ldap::{self, LdapListenScheme};
 use spow::pow::Pow;
 use std::error::Error;
 use std::net::Ipv4Addr;
@@ -47,6 +49,7 @@ use utoipa_swagger_ui::SwaggerUi;
 mod dummy_data;
 mod logging;
 mod tls;
+mod user_transfer;
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn Error>> {
@@ -163,6 +166,14 @@ https://github.com/sebadob/rauthy/releases/tag/v0.27.0
         .await
         .expect("Database migration error");
 
+    if args.len() > 1 && (args[1] == "users-export" || args[1] == "users-import") {
+        if let Err(err) = user_transfer::run(&args).await {
+            error!("{}", err.message);
+        }
+        DB::client().shutdown().await.unwrap();
+        return Ok(());
+    }
+
     // events listener
     debug!("Starting Events handler");
     init_event_vars().unwrap();
@@ -174,6 +185,9 @@ https://github.com/sebadob/rauthy/releases/tag/v0.27.0
         rx_events,
     ));
 
+    debug!("Loading GeoIP databases");
+    geoip::init().unwrap();
+
     // spawn password hash limiter
     debug!("Starting Password Hasher");
     tokio::spawn(password_hasher::run());
@@ -200,6 +214,20 @@ https://github.com/sebadob/rauthy/releases/tag/v0.27.0
         }
     };
 
+    // optional read-only LDAP facade
+    if *LDAP_ENABLE {
+        debug!("Starting LDAP facade");
+        let scheme = LdapListenScheme::from_env();
+        let tls = if scheme.needs_tls() {
+            Some(tls::load_tls().await)
+        } else {
+            None
+        };
+        ldap::run(app_state.clone(), scheme, tls)
+            .await
+            .expect("Error starting the LDAP listener");
+    }
+
     // TODO remove this block check with the next minor version.
     // 0.27.0 had a bug that could have inserted NULL for password policy on update.
     if is_hiqlite() {
@@ -230,6 +258,9 @@ Error: {}
                 include_special: Some(1),
                 valid_days: Some(180),
                 not_recently_used: Some(3),
+                min_strength: None,
+                check_breached: false,
+                banned_words: Vec::default(),
             }
             .save()
             .await
@@ -417,14 +448,21 @@ async fn actix_main(app_state: web::Data<AppState>) -> std::io::Result<()> {
                             .service(auth_providers::post_provider_login)
                             .service(auth_providers::get_provider_delete_safe)
                             .service(auth_providers::post_provider_lookup)
+                            .service(auth_providers::post_provider_saml_lookup)
+                            .service(auth_providers::get_provider_saml_metadata)
+                            .service(auth_providers::post_provider_saml_acs)
                             .service(auth_providers::get_provider_callback_html)
                             .service(auth_providers::post_provider_callback)
                             .service(auth_providers::delete_provider_link)
+                            .service(auth_providers::delete_provider_link_by_id)
                             .service(auth_providers::put_provider)
                             .service(auth_providers::delete_provider)
                             .service(auth_providers::get_provider_img)
                             .service(auth_providers::put_provider_img)
                             .service(auth_providers::post_provider_link)
+                            .service(blacklist::get_allowlist)
+                            .service(blacklist::post_allowlist)
+                            .service(blacklist::delete_allowlist)
                             .service(blacklist::get_blacklist)
                             .service(blacklist::post_blacklist)
                             .service(blacklist::delete_blacklist)
@@ -485,6 +523,17 @@ async fn actix_main(app_state: web::Data<AppState>) -> std::io::Result<()> {
                             .service(clients::post_clients_dyn)
                             .service(clients::get_clients_dyn)
                             .service(clients::put_clients_dyn)
+                            .service(saml::get_saml_metadata)
+                            .service(saml::get_saml_sso)
+                            .service(saml::post_saml_sso)
+                            .service(saml::get_saml_idp_initiated)
+                            .service(saml::get_saml_resume)
+                            .service(saml::get_saml_slo)
+                            .service(saml::post_saml_slo)
+                            .service(saml::get_client_saml)
+                            .service(saml::put_client_saml)
+                            .service(saml::post_client_saml_metadata)
+                            .service(saml::delete_client_saml)
                             .service(generic::get_login_time)
                             .service(fed_cm::get_fed_cm_accounts)
                             .service(fed_cm::get_fed_cm_config)
@@ -494,8 +543,19 @@ async fn actix_main(app_state: web::Data<AppState>) -> std::io::Result<()> {
                             .service(fed_cm::get_fed_client_config)
                             .service(fed_cm::get_fed_cm_status)
                             .service(users::get_users)
+                            .service(invitations::get_user_invitations)
+                            .service(invitations::post_user_invitation)
+                            .service(invitations::delete_user_invitation)
+                            .service(invitations::post_user_invitation_resend)
                             .service(users::get_users_register)
                             .service(users::post_users_register)
+                            .service(users::post_users_import)
+                            .service(users::get_users_export)
+                            .service(users::put_users_import)
+                            .service(users::get_users_register_fields)
+                            .service(users::get_user_registrations)
+                            .service(users::post_user_registration_approve)
+                            .service(users::delete_user_registration)
                             .service(users::get_cust_attr)
                             .service(users::post_cust_attr)
                             .service(users::put_cust_attr)
@@ -503,12 +563,19 @@ async fn actix_main(app_state: web::Data<AppState>) -> std::io::Result<()> {
                             .service(users::get_user_by_id)
                             .service(users::get_user_attr)
                             .service(users::put_user_attr)
+                            .service(users::get_user_federations)
                             .service(users::get_user_devices)
                             .service(users::put_user_device_name)
                             .service(users::delete_user_device)
+                            .service(users::get_user_trusted_devices)
+                            .service(users::delete_user_trusted_device)
                             .service(users::get_user_webid_data)
                             .service(users::put_user_webid_data)
                             .service(users::get_user_email_confirm)
+                            .service(users::get_user_unlock)
+                            .service(users::post_user_unlock_link)
+                            .service(users::get_user_not_me)
+                            .service(users::post_user_not_me)
                             .service(users::post_user_self_convert_passkey)
                             .service(generic::post_password_hash_times)
                             .service(sessions::get_sessions)
@@ -520,6 +587,8 @@ async fn actix_main(app_state: web::Data<AppState>) -> std::io::Result<()> {
                             .service(users::post_users)
                             .service(users::put_user_by_id)
                             .service(users::put_user_self)
+                            .service(users::post_user_impersonate)
+                            .service(users::post_user_unlock)
                             .service(users::delete_user_by_id)
                             .service(users::post_user_password_request_reset)
                             .service(users::get_user_webauthn_passkeys)
@@ -530,6 +599,10 @@ async fn actix_main(app_state: web::Data<AppState>) -> std::io::Result<()> {
                             .service(users::delete_webauthn)
                             .service(generic::get_password_policy)
                             .service(generic::put_password_policy)
+                            .service(generic::get_password_policies)
+                            .service(generic::post_password_policy)
+                            .service(generic::put_password_policy_scoped)
+                            .service(generic::delete_password_policy)
                             .service(generic::post_pow)
                             .service(generic::get_search)
                             .service(groups::get_groups)
@@ -540,6 +613,18 @@ async fn actix_main(app_state: web::Data<AppState>) -> std::io::Result<()> {
                             .service(roles::post_role)
                             .service(roles::put_role)
                             .service(roles::delete_role)
+                            .service(admin_roles::get_admin_roles)
+                            .service(admin_roles::post_admin_role)
+                            .service(admin_roles::put_admin_role)
+                            .service(admin_roles::delete_admin_role)
+                            .service(organizations::get_organizations)
+                            .service(organizations::post_organization)
+                            .service(organizations::put_organization)
+                            .service(organizations::delete_organization)
+                            .service(organizations::get_org_members)
+                            .service(organizations::post_org_member_invite)
+                            .service(organizations::put_org_member)
+                            .service(organizations::delete_org_member)
                             .service(scopes::get_scopes)
                             .service(scopes::post_scope)
                             .service(scopes::put_scope)
@@ -0,0 +1,55 @@
+use rauthy_error::{ErrorResponse, ErrorResponseType};
+use rauthy_service::user_export;
+use rauthy_service::user_import::UserUpsertImport;
+use tokio::fs::File;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader, BufWriter};
+use tracing::info;
+
+/// Handles `users-export <file> [--with-secrets]` and `users-import <file> [--dry-run]`.
+/// Both use the normal config and database and exit afterward without starting the HTTP server.
+/// Having access to both, the CLI is always allowed to import roles and credentials.
+pub async fn run(args: &[String]) -> Result<(), ErrorResponse> {
+    let Some(path) = args.iter().skip(2).find(|arg| !arg.starts_with("--")) else {
+        return Err(ErrorResponse::new(
+            ErrorResponseType::BadRequest,
+            "Usage: rauthy users-export <file> [--with-secrets] | rauthy users-import <file> [--dry-run]",
+        ));
+    };
+
+    if args[1] == "users-export" {
+        let with_secrets = args.iter().any(|arg| arg == "--with-secrets");
+        export(path, with_secrets).await
+    } else {
+        let dry_run = args.iter().any(|arg| arg == "--dry-run");
+        import(path, dry_run).await
+    }
+}
+
+async fn export(path: &str, with_secrets: bool) -> Result<(), ErrorResponse> {
+    let (tx, rx) = flume::bounded(64);
+    tokio::spawn(user_export::export_users(tx, with_secrets));
+
+    let mut file = BufWriter::new(File::create(path).await?);
+    while let Ok(line) = rx.recv_async().await {
+        file.write_all(line?.as_bytes()).await?;
+    }
+    file.flush().await?;
+
+    info!("User export has been written to {}", path);
+    Ok(())
+}
+
+async fn import(path: &str, dry_run: bool) -> Result<(), ErrorResponse> {
+    let mut import = UserUpsertImport::new(dry_run, true).await?;
+
+    let mut reader = BufReader::new(File::open(path).await?);
+    let mut line = Vec::with_capacity(4 * 1024);
+    while reader.read_until(b'\n', &mut line).await? > 0 {
+        import.import_line(&line).await;
+        line.clear();
+    }
+
+    let report = import.finish();
+    println!("{}", serde_json::to_string_pretty(&report)?);
+    Ok(())
+}
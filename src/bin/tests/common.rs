@@ -108,6 +108,10 @@ pub async fn session_headers() -> (HeaderMap, TokenSet) {
         nonce: Some("MySuperNonce".to_string()),
         code_challenge: Some(challenge_s256),
         code_challenge_method: Some("S256".to_string()),
+        org_id: None,
+        pow: None,
+        trust_device: None,
+        email_code: None,
     };
 
     let res = client
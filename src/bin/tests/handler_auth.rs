@@ -7,7 +7,7 @@ use chrono::Utc;
 use ed25519_compact::Noise;
 use josekit::jwk;
 use pretty_assertions::assert_eq;
-use rauthy_api_types::clients::UpdateClientRequest;
+use rauthy_api_types::clients::{NewClientRequest, UpdateClientRequest};
 use rauthy_api_types::oidc::{
     JwkKeyPairAlg, LoginRequest, TokenInfo, TokenRequest, TokenValidationRequest,
 };
@@ -105,6 +105,10 @@ async fn test_authorization_code_flow() -> Result<(), Box<dyn Error>> {
         nonce: Some(nonce.to_owned()),
         code_challenge: Some(challenge_plain.to_owned()),
         code_challenge_method: Some("plain".to_string()),
+        org_id: None,
+        pow: None,
+        trust_device: None,
+        email_code: None,
     };
     let res = reqwest::Client::new()
         .post(&url_auth)
@@ -285,6 +289,15 @@ async fn test_authorization_code_flow() -> Result<(), Box<dyn Error>> {
         force_mfa: false,
         client_uri: None,
         contacts: None,
+        allowed_providers: None,
+        allow_password_login: None,
+        passkey_only: false,
+        auto_redirect_provider: None,
+        access_roles: None,
+        access_groups: None,
+        access_email_domains: None,
+        geo_allow_countries: None,
+        geo_deny_countries: None,
     };
     let url_client = format!("{}/clients/{}", backend_url, CLIENT_ID);
     let auth_headers = get_auth_headers().await?;
@@ -424,6 +437,10 @@ async fn test_concurrent_logins() -> Result<(), Box<dyn Error>> {
         nonce: None,
         code_challenge: Some(challenge_plain.to_owned()),
         code_challenge_method: None,
+        org_id: None,
+        pow: None,
+        trust_device: None,
+        email_code: None,
     };
 
     let start = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
@@ -475,6 +492,128 @@ async fn test_concurrent_logins() -> Result<(), Box<dyn Error>> {
     Ok(())
 }
 
+#[tokio::test]
+async fn test_password_login_denied() -> Result<(), Box<dyn Error>> {
+    let backend_url = get_backend_url();
+    let auth_headers = get_auth_headers().await?;
+    let client = reqwest::Client::new();
+
+    // a separate client without password logins to not interfere with the other tests
+    let client_id = "no_password_login";
+    let redirect_uri = "http://localhost:3000/oidc/callback";
+    let new_client = NewClientRequest {
+        id: client_id.to_string(),
+        secret: None,
+        name: None,
+        confidential: false,
+        redirect_uris: vec![redirect_uri.to_string()],
+        post_logout_redirect_uris: None,
+    };
+    let res = client
+        .post(format!("{}/clients", backend_url))
+        .headers(auth_headers.clone())
+        .json(&new_client)
+        .send()
+        .await?;
+    check_status(res, 200).await?;
+
+    let update_client = UpdateClientRequest {
+        id: client_id.to_string(),
+        name: None,
+        confidential: false,
+        redirect_uris: vec![redirect_uri.to_string()],
+        post_logout_redirect_uris: None,
+        allowed_origins: None,
+        enabled: true,
+        flows_enabled: vec!["authorization_code".to_string()],
+        access_token_alg: JwkKeyPairAlg::EdDSA,
+        id_token_alg: JwkKeyPairAlg::EdDSA,
+        auth_code_lifetime: 60,
+        access_token_lifetime: 60,
+        scopes: vec!["openid".to_string()],
+        default_scopes: vec!["openid".to_string()],
+        challenges: Some(vec!["plain".to_string()]),
+        force_mfa: false,
+        client_uri: None,
+        contacts: None,
+        allowed_providers: None,
+        allow_password_login: Some(false),
+        passkey_only: false,
+        auto_redirect_provider: None,
+        access_roles: None,
+        access_groups: None,
+        access_email_domains: None,
+        geo_allow_countries: None,
+        geo_deny_countries: None,
+    };
+    let url_client = format!("{}/clients/{}", backend_url, client_id);
+    let res = client
+        .put(&url_client)
+        .headers(auth_headers.clone())
+        .json(&update_client)
+        .send()
+        .await?;
+    check_status(res, 200).await?;
+
+    let challenge = "oDXug9zfYqfz8ejcqMpALRPXfW8QhbKV2AVuScAt8xrLKDAmaRYQ4yRi2uqcH9ys";
+    let url_auth = format!(
+        "{}/oidc/authorize?client_id={}&redirect_uri={}&response_type=code&code_challenge={}",
+        backend_url, client_id, redirect_uri, challenge
+    );
+    let res = reqwest::get(&url_auth).await?;
+    let res = check_status(res, 200).await?;
+    let headers = cookie_csrf_headers_from_res(res).await?;
+
+    let mut req_login = LoginRequest {
+        email: USERNAME.to_string(),
+        password: Some(PASSWORD.to_string()),
+        client_id: client_id.to_string(),
+        redirect_uri: redirect_uri.to_string(),
+        scopes: None,
+        state: None,
+        nonce: None,
+        code_challenge: Some(challenge.to_string()),
+        code_challenge_method: Some("plain".to_string()),
+        org_id: None,
+        pow: None,
+        trust_device: None,
+        email_code: None,
+    };
+
+    // The response must be exactly the same for existing and unknown users, with and without
+    // a (correct) password.
+    let mut errors = Vec::with_capacity(4);
+    for (email, password) in [
+        (USERNAME, Some(PASSWORD)),
+        (USERNAME, Some("IAmSoWrong1337")),
+        ("doesnotexist@localhost.de", Some(PASSWORD)),
+        ("doesnotexist@localhost.de", None),
+    ] {
+        req_login.email = email.to_string();
+        req_login.password = password.map(String::from);
+        let res = client
+            .post(&url_auth)
+            .headers(headers.clone())
+            .json(&req_login)
+            .send()
+            .await?;
+        let res = check_status(res, 403).await?;
+        let err = res.json::<ErrorResponse>().await?;
+        assert_eq!(err.error, ErrorResponseType::Forbidden);
+        errors.push(err.message);
+    }
+    assert!(errors.windows(2).all(|e| e[0] == e[1]));
+
+    let res = client
+        .delete(&url_client)
+        .headers(auth_headers)
+        .send()
+        .await?;
+    check_status(res, 200).await?;
+
+    Ok(())
+}
+
 #[tokio::test]
 async fn test_password_flow() -> Result<(), Box<dyn Error>> {
     let before = Utc::now().timestamp();
@@ -783,6 +922,10 @@ async fn test_authorization_code_flow_ephemeral_client() -> Result<(), Box<dyn E
         nonce: Some(nonce.to_owned()),
         code_challenge: Some(challenge_s256),
         code_challenge_method: Some("S256".to_string()),
+        org_id: None,
+        pow: None,
+        trust_device: None,
+        email_code: None,
     };
     let res = client
         .post(&url_auth)
@@ -77,6 +77,15 @@ async fn test_scopes() -> Result<(), Box<dyn Error>> {
         force_mfa: init_client.force_mfa,
         client_uri: init_client.client_uri,
         contacts: init_client.contacts,
+        allowed_providers: init_client.allowed_providers,
+        allow_password_login: Some(init_client.allow_password_login),
+        passkey_only: init_client.passkey_only,
+        auto_redirect_provider: init_client.auto_redirect_provider,
+        access_roles: init_client.access_roles,
+        access_groups: init_client.access_groups,
+        access_email_domains: init_client.access_email_domains,
+        geo_allow_countries: init_client.geo_allow_countries,
+        geo_deny_countries: init_client.geo_deny_countries,
     };
     let res = client
         .put(&url_client)
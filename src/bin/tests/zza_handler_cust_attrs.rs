@@ -36,6 +36,7 @@ async fn test_cust_attrs() -> Result<(), Box<dyn Error>> {
     let cust_attr = UserAttrConfigRequest {
         name: "cust1".to_string(),
         desc: Some("some description".to_string()),
+        registration: None,
     };
     let res = client
         .post(&url_attrs)
@@ -127,6 +128,15 @@ async fn test_cust_attrs() -> Result<(), Box<dyn Error>> {
         force_mfa: c.force_mfa,
         client_uri: None,
         contacts: None,
+        allowed_providers: None,
+        allow_password_login: None,
+        passkey_only: false,
+        auto_redirect_provider: None,
+        access_roles: None,
+        access_groups: None,
+        access_email_domains: None,
+        geo_allow_countries: None,
+        geo_deny_countries: None,
     };
     let res = client
         .put(&url_client)
@@ -177,6 +187,7 @@ async fn test_cust_attrs() -> Result<(), Box<dyn Error>> {
     let cust_attr_mod = UserAttrConfigRequest {
         name: "cust2".to_string(),
         desc: Some("some description 2".to_string()),
+        registration: None,
     };
     let url_attr_mod = format!("{}/users/attr/{}", backend_url, cust_attr.name);
     let res = client
@@ -1,6 +1,12 @@
-use crate::common::{check_status, get_auth_headers, get_backend_url};
+use crate::common::{
+    check_status, get_auth_headers, get_backend_url, CLIENT_ID, CLIENT_SECRET, PASSWORD, USERNAME,
+};
 use pretty_assertions::assert_eq;
-use rauthy_api_types::generic::{Language, PasswordPolicyRequest, PasswordPolicyResponse};
+use rauthy_api_types::generic::{
+    Language, PasswordPolicyRequest, PasswordPolicyResponse, ScopedPasswordPolicyRequest,
+    ScopedPasswordPolicyResponse,
+};
+use rauthy_api_types::oidc::TokenRequest;
 use rauthy_api_types::users::{NewUserRequest, UpdateUserRequest, UserResponse};
 use rauthy_error::{ErrorResponse, ErrorResponseType};
 use std::error::Error;
@@ -41,6 +47,9 @@ async fn test_password_policy() -> Result<(), Box<dyn Error>> {
         include_special: Some(5),
         valid_days: Some(60),
         not_recently_used: Some(7),
+        min_strength: None,
+        check_breached: None,
+        banned_words: None,
     };
     let res = reqwest::Client::new()
         .put(&url)
@@ -199,3 +208,68 @@ async fn test_password_policy() -> Result<(), Box<dyn Error>> {
 
     Ok(())
 }
+
+#[tokio::test]
+async fn test_password_flow_client_password_policy() -> Result<(), Box<dyn Error>> {
+    let auth_headers = get_auth_headers().await?;
+    let backend_url = get_backend_url();
+
+    // the password of the init admin is too short for this policy
+    let policy_req = ScopedPasswordPolicyRequest {
+        name: "IT Password Flow".to_string(),
+        policy: PasswordPolicyRequest {
+            length_min: 32,
+            length_max: 128,
+            include_lower_case: None,
+            include_upper_case: None,
+            include_digits: None,
+            include_special: None,
+            valid_days: None,
+            not_recently_used: None,
+            min_strength: None,
+            check_breached: None,
+            banned_words: None,
+        },
+        groups: None,
+        clients: Some(vec![CLIENT_ID.to_string()]),
+    };
+    let mut res = reqwest::Client::new()
+        .post(format!("{}/password_policies", backend_url))
+        .headers(auth_headers.clone())
+        .json(&policy_req)
+        .send()
+        .await?;
+    res = check_status(res, 200).await?;
+    let policy = res.json::<ScopedPasswordPolicyResponse>().await?;
+
+    let url = format!("{}/oidc/token", backend_url);
+    let body = TokenRequest {
+        grant_type: "password".to_string(),
+        code: None,
+        redirect_uri: None,
+        client_id: Some(CLIENT_ID.to_string()),
+        client_secret: Some(CLIENT_SECRET.to_string()),
+        code_verifier: None,
+        device_code: None,
+        username: Some(USERNAME.to_string()),
+        password: Some(PASSWORD.to_string()),
+        refresh_token: None,
+    };
+    let mut res = reqwest::Client::new().post(&url).form(&body).send().await?;
+    res = check_status(res, 403).await?;
+    let err = res.json::<ErrorResponse>().await?;
+    assert_eq!(err.error, ErrorResponseType::PasswordRefresh);
+
+    let res = reqwest::Client::new()
+        .delete(format!("{}/password_policies/{}", backend_url, policy.id))
+        .headers(auth_headers)
+        .send()
+        .await?;
+    check_status(res, 200).await?;
+
+    // without the policy, the login must work again
+    let res = reqwest::Client::new().post(&url).form(&body).send().await?;
+    check_status(res, 200).await?;
+
+    Ok(())
+}
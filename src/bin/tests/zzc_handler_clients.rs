@@ -118,6 +118,15 @@ async fn test_clients() -> Result<(), Box<dyn Error>> {
             "batman@localhost.de".to_string(),
             "@alfred:matrix.org".to_string(),
         ]),
+        allowed_providers: None,
+        allow_password_login: None,
+        passkey_only: false,
+        auto_redirect_provider: None,
+        access_roles: None,
+        access_groups: None,
+        access_email_domains: None,
+        geo_allow_countries: None,
+        geo_deny_countries: None,
     };
 
     let url_id = format!("{}/clients/{}", backend_url, client.id);
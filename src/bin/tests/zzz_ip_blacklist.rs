@@ -42,7 +42,11 @@ async fn test_ip_blacklist() -> Result<(), Box<dyn Error>> {
 
     // blacklist for 2 seconds
     let exp = Utc::now().add(chrono::Duration::seconds(2)).timestamp();
-    let payload = IpBlacklistRequest { ip, exp };
+    let payload = IpBlacklistRequest {
+        ip: ip.to_string(),
+        exp: Some(exp),
+        reason: None,
+    };
     let res = client
         .post(&url)
         .headers(auth_headers.clone())
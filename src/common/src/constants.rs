@@ -16,6 +16,18 @@ pub enum CookieMode {
     DangerInsecure,
 }
 
+/// Automatic response after a possible brute-force attack has been detected
+#[derive(Debug, PartialEq)]
+pub enum BruteForceAction {
+    None,
+    /// require a Proof-of-Work for each further login
+    Pow,
+    /// lock the account temporarily
+    Lock,
+    /// blacklist the IP
+    Blacklist,
+}
+
 pub const RAUTHY_VERSION: &str = env!("CARGO_PKG_VERSION");
 pub const CONTENT_TYPE_WEBP: &str = "image/webp";
 pub const HEADER_DPOP_NONCE: &str = "DPoP-Nonce";
@@ -33,6 +45,8 @@ pub const TOKEN_DPOP_NONCE: &str = "DPoP-nonce";
 pub const COOKIE_SESSION: &str = "RauthySession";
 pub const COOKIE_SESSION_FED_CM: &str = "RauthySessionFedCM";
 pub const COOKIE_MFA: &str = "RauthyMfa";
+pub const COOKIE_KNOWN_DEVICES: &str = "RauthyKnownDevices";
+pub const COOKIE_TRUSTED_DEVICE: &str = "RauthyTrustedDevice";
 pub const COOKIE_LOCALE: &str = "locale";
 pub const COOKIE_UPSTREAM_CALLBACK: &str = "UpstreamAuthCallback";
 pub const PROVIDER_LINK_COOKIE: &str = "rauthy-provider-link";
@@ -62,21 +76,39 @@ pub const CACHE_TTL_SESSION: Option<i64> = Some(14400);
 // in the current layout!
 pub const CACHE_TTL_USER: Option<i64> = Some(600);
 
+pub const IDX_ADMIN_ROLES: &str = "admin_roles_";
 pub const IDX_APP_VERSION: &str = "rauthy_app_version";
 pub const IDX_AUTH_PROVIDER: &str = "auth_provider_";
+pub const IDX_AUTH_PROVIDER_JWKS: &str = "auth_provider_jwks_";
 pub const IDX_AUTH_PROVIDER_LOGO: &str = "auth_provider_logo_";
 pub const IDX_AUTH_PROVIDER_TEMPLATE: &str = "provider_json_tpl";
+pub const IDX_BRUTE_FORCE_ACCOUNT: &str = "bf_account_";
+pub const IDX_BRUTE_FORCE_DETECTED: &str = "bf_detected_";
+pub const IDX_BRUTE_FORCE_IP: &str = "bf_ip_";
+pub const IDX_BRUTE_FORCE_LOCK: &str = "bf_lock_";
+pub const IDX_BRUTE_FORCE_POW: &str = "bf_pow_";
 pub const IDX_CLIENTS: &str = "clients_";
 pub const IDX_CLIENT_LOGO: &str = "client_logo_";
+pub const IDX_CLIENT_SAML: &str = "client_saml_";
+pub const IDX_GEO_LAST_LOGIN: &str = "geo_last_login_";
 pub const IDX_GROUPS: &str = "groups_";
+pub const IDX_IP_BLACKLIST_VERSION: &str = "ip_blacklist_version";
 pub const IDX_JWK_KID: &str = "jwk_kid_";
 pub const IDX_JWK_LATEST: &str = "jwk_latest_";
 pub const IDX_JWKS: &str = "jkws_";
+pub const IDX_LOGIN_EMAIL_CODE: &str = "login_email_code_";
+pub const IDX_LOGIN_FAILED_IP: &str = "login_failed_ip_";
 pub const IDX_LOGIN_TIME: &str = "login_time_";
 pub const IDX_MFA_APP: &str = "mfa_app_";
 pub const IDX_MFA_LOGIN_REQ: &str = "mfa_login_req_";
+pub const IDX_ORGANIZATIONS: &str = "organizations_";
+pub const IDX_PASSWORD_POLICIES: &str = "password_policies";
 pub const IDX_PASSWORD_RULES: &str = "password_rules_";
 pub const IDX_ROLES: &str = "roles_";
+pub const IDX_SAML_CERT: &str = "saml_cert_";
+pub const IDX_SAML_CERTS: &str = "saml_certs";
+pub const IDX_SAML_REQ: &str = "saml_req_";
+pub const IDX_SAML_SESSION_INDEX: &str = "saml_sidx_";
 pub const IDX_SCOPES: &str = "scopes_";
 pub const IDX_SESSIONS: &str = "sessions";
 pub const IDX_USERS: &str = "users_";
@@ -133,10 +165,14 @@ lazy_static! {
     pub static ref RE_CODE_CHALLENGE: Regex = Regex::new(r"^[a-zA-Z0-9-\._~]{43,128}$").unwrap();
     pub static ref RE_CODE_VERIFIER: Regex = Regex::new(r"^[a-zA-Z0-9-\._~+/=]+$").unwrap();
     pub static ref RE_CONTACT: Regex = Regex::new(r"^[a-zA-Z0-9\+.@/:]{0,48}$").unwrap();
+    pub static ref RE_COUNTRY_CODE: Regex = Regex::new(r"^[a-zA-Z]{2}$").unwrap();
     pub static ref RE_DATE_STR: Regex = Regex::new(r"^[0-9]{4}-[0-9]{2}-[0-9]{2}$").unwrap();
+    pub static ref RE_EMAIL_CODE: Regex = Regex::new(r"^[0-9]{6}$").unwrap();
+    pub static ref RE_DOMAIN: Regex = Regex::new(r"^([a-z0-9-]{1,63}\.)+[a-z]{2,63}$").unwrap();
     pub static ref RE_GRANT_TYPES: Regex = Regex::new(r"^(authorization_code|client_credentials|urn:ietf:params:oauth:grant-type:device_code|password|refresh_token)$").unwrap();
     pub static ref RE_GRANT_TYPES_EPHEMERAL: Regex = Regex::new(r"^(authorization_code|client_credentials|password|refresh_token)$").unwrap();
     pub static ref RE_GROUPS: Regex = Regex::new(r"^[a-z0-9-_/,:*]{2,64}$").unwrap();
+    pub static ref RE_JSON_PATH: Regex = Regex::new(r#"^[a-zA-Z0-9,.:/_\-&?=~#!$'"()*+%@<>\[\] ]{1,256}$"#).unwrap();
     pub static ref RE_LOWERCASE: Regex = Regex::new(r"^[a-z0-9-_/]{2,128}$").unwrap();
     pub static ref RE_LOWERCASE_SPACE: Regex = Regex::new(r"^[a-z0-9-_/\s]{2,128}$").unwrap();
     pub static ref RE_MFA_CODE: Regex = Regex::new(r"^[a-zA-Z0-9]{48}$").unwrap();
@@ -215,6 +251,104 @@ lazy_static! {
             _ => panic!("COOKIE_MODE must be one of: host, secure, danger-insecure")
         }
     };
+    pub static ref ACCOUNT_LOCKOUT_ATTEMPTS: i64 = env::var("ACCOUNT_LOCKOUT_ATTEMPTS")
+        .unwrap_or_else(|_| String::from("0"))
+        .parse::<i64>()
+        .expect("ACCOUNT_LOCKOUT_ATTEMPTS cannot be parsed to i64 - bad format");
+    pub static ref ACCOUNT_LOCKOUT_WINDOW: i64 = env::var("ACCOUNT_LOCKOUT_WINDOW")
+        .unwrap_or_else(|_| String::from("900"))
+        .parse::<i64>()
+        .expect("ACCOUNT_LOCKOUT_WINDOW cannot be parsed to i64 - bad format");
+    pub static ref ACCOUNT_LOCKOUT_DURATION: i64 = env::var("ACCOUNT_LOCKOUT_DURATION")
+        .unwrap_or_else(|_| String::from("900"))
+        .parse::<i64>()
+        .expect("ACCOUNT_LOCKOUT_DURATION cannot be parsed to i64 - bad format");
+    pub static ref ACCOUNT_LOCKOUT_UNLOCK_EMAIL: bool = env::var("ACCOUNT_LOCKOUT_UNLOCK_EMAIL")
+        .unwrap_or_else(|_| String::from("false"))
+        .parse::<bool>()
+        .expect("ACCOUNT_LOCKOUT_UNLOCK_EMAIL cannot be parsed to bool - bad format");
+
+    pub static ref BRUTE_FORCE_DETECTION: bool = env::var("BRUTE_FORCE_DETECTION")
+        .unwrap_or_else(|_| String::from("true"))
+        .parse::<bool>()
+        .expect("BRUTE_FORCE_DETECTION cannot be parsed to bool - bad format");
+    pub static ref BRUTE_FORCE_WINDOW: u32 = env::var("BRUTE_FORCE_WINDOW")
+        .unwrap_or_else(|_| String::from("900"))
+        .parse::<u32>()
+        .expect("BRUTE_FORCE_WINDOW cannot be parsed to u32 - bad format");
+    pub static ref BRUTE_FORCE_ACCOUNT_THRESHOLD: usize = env::var("BRUTE_FORCE_ACCOUNT_THRESHOLD")
+        .unwrap_or_else(|_| String::from("10"))
+        .parse::<usize>()
+        .expect("BRUTE_FORCE_ACCOUNT_THRESHOLD cannot be parsed to usize - bad format");
+    pub static ref BRUTE_FORCE_ACCOUNT_ACTION: BruteForceAction = {
+        let var = env::var("BRUTE_FORCE_ACCOUNT_ACTION").unwrap_or_else(|_| "pow".to_string());
+        match var.as_str() {
+            "none" => BruteForceAction::None,
+            "pow" => BruteForceAction::Pow,
+            "lock" => BruteForceAction::Lock,
+            _ => panic!("BRUTE_FORCE_ACCOUNT_ACTION must be one of: none, pow, lock")
+        }
+    };
+    pub static ref BRUTE_FORCE_IP_THRESHOLD: usize = env::var("BRUTE_FORCE_IP_THRESHOLD")
+        .unwrap_or_else(|_| String::from("10"))
+        .parse::<usize>()
+        .expect("BRUTE_FORCE_IP_THRESHOLD cannot be parsed to usize - bad format");
+    pub static ref BRUTE_FORCE_IP_ACTION: BruteForceAction = {
+        let var = env::var("BRUTE_FORCE_IP_ACTION").unwrap_or_else(|_| "blacklist".to_string());
+        match var.as_str() {
+            "none" => BruteForceAction::None,
+            "pow" => BruteForceAction::Pow,
+            "blacklist" => BruteForceAction::Blacklist,
+            _ => panic!("BRUTE_FORCE_IP_ACTION must be one of: none, pow, blacklist")
+        }
+    };
+    pub static ref BRUTE_FORCE_ACTION_DURATION: u32 = env::var("BRUTE_FORCE_ACTION_DURATION")
+        .unwrap_or_else(|_| String::from("3600"))
+        .parse::<u32>()
+        .expect("BRUTE_FORCE_ACTION_DURATION cannot be parsed to u32 - bad format");
+
+    pub static ref GEOIP_DB_PATH: Option<String> = env::var("GEOIP_DB_PATH")
+        .ok()
+        .filter(|path| !path.is_empty());
+    pub static ref GEOIP_ASN_DB_PATH: Option<String> = env::var("GEOIP_ASN_DB_PATH")
+        .ok()
+        .filter(|path| !path.is_empty());
+    pub static ref GEOIP_COUNTRY_ALLOW: Vec<String> = env::var("GEOIP_COUNTRY_ALLOW")
+        .unwrap_or_default()
+        .split(' ')
+        .filter_map(|c| c.is_empty().not().then_some(c.to_uppercase()))
+        .collect();
+    pub static ref GEOIP_COUNTRY_DENY: Vec<String> = env::var("GEOIP_COUNTRY_DENY")
+        .unwrap_or_default()
+        .split(' ')
+        .filter_map(|c| c.is_empty().not().then_some(c.to_uppercase()))
+        .collect();
+    pub static ref GEOIP_ALLOW_UNKNOWN: bool = env::var("GEOIP_ALLOW_UNKNOWN")
+        .unwrap_or_else(|_| String::from("true"))
+        .parse::<bool>()
+        .expect("GEOIP_ALLOW_UNKNOWN cannot be parsed to bool - bad format");
+    pub static ref GEOIP_IMPOSSIBLE_TRAVEL_SPEED: u32 = env::var("GEOIP_IMPOSSIBLE_TRAVEL_SPEED")
+        .unwrap_or_else(|_| String::from("1000"))
+        .parse::<u32>()
+        .expect("GEOIP_IMPOSSIBLE_TRAVEL_SPEED cannot be parsed to u32 - bad format");
+
+    pub static ref MFA_TRUSTED_DEVICE_DAYS: u16 = env::var("MFA_TRUSTED_DEVICE_DAYS")
+        .unwrap_or_else(|_| String::from("0"))
+        .parse::<u16>()
+        .expect("MFA_TRUSTED_DEVICE_DAYS cannot be parsed to u16 - bad format");
+    pub static ref MFA_NEW_DEVICE_EMAIL_CODE: bool = env::var("MFA_NEW_DEVICE_EMAIL_CODE")
+        .unwrap_or_else(|_| String::from("false"))
+        .parse::<bool>()
+        .expect("MFA_NEW_DEVICE_EMAIL_CODE cannot be parsed to bool - bad format");
+    pub static ref SECURITY_NOTIFICATIONS: bool = env::var("SECURITY_NOTIFICATIONS")
+        .unwrap_or_else(|_| String::from("false"))
+        .parse::<bool>()
+        .expect("SECURITY_NOTIFICATIONS cannot be parsed to bool - bad format");
+
+    pub static ref PASSWORD_BREACHED_PATH: Option<String> = env::var("PASSWORD_BREACHED_PATH")
+        .ok()
+        .filter(|path| !path.is_empty());
+
     pub static ref COOKIE_SET_PATH: bool = env::var("COOKIE_SET_PATH")
         .unwrap_or_else(|_| String::from("true"))
         .parse::<bool>()
@@ -264,6 +398,14 @@ lazy_static! {
     pub static ref PROVIDER_CALLBACK_URI_ENCODED: String = {
         PROVIDER_CALLBACK_URI.replace(':', "%3A").replace('/', "%2F")
     };
+    pub static ref PROVIDER_SAML_ACS_URI: String = format!(
+        "{}/saml/acs",
+        PROVIDER_CALLBACK_URI.strip_suffix("/callback").unwrap()
+    );
+    pub static ref PROVIDER_SAML_METADATA_URI: String = format!(
+        "{}/saml/metadata",
+        PROVIDER_CALLBACK_URI.strip_suffix("/callback").unwrap()
+    );
 
     pub static ref DEVICE_GRANT_CODE_LIFETIME: u16 = env::var("DEVICE_GRANT_CODE_LIFETIME")
         .unwrap_or_else(|_| String::from("300"))
@@ -363,6 +505,36 @@ lazy_static! {
         .parse::<bool>()
         .expect("EXPERIMENTAL_FED_CM_ENABLE cannot be parsed to bool - bad format");
 
+    pub static ref LDAP_ENABLE: bool = env::var("LDAP_ENABLE")
+        .unwrap_or_else(|_| String::from("false"))
+        .parse::<bool>()
+        .expect("LDAP_ENABLE cannot be parsed to bool - bad format");
+    pub static ref LDAP_BASE_DN: String = env::var("LDAP_BASE_DN").unwrap_or_else(|_| {
+        PUB_URL
+            .split(':')
+            .next()
+            .unwrap_or(&PUB_URL)
+            .split('.')
+            .map(|dc| format!("dc={}", dc))
+            .collect::<Vec<_>>()
+            .join(",")
+    });
+    pub static ref LDAP_SEARCH_GROUP: Option<String> = env::var("LDAP_SEARCH_GROUP")
+        .ok()
+        .filter(|group| !group.is_empty());
+    pub static ref LDAP_SIZE_LIMIT: usize = env::var("LDAP_SIZE_LIMIT")
+        .unwrap_or_else(|_| String::from("1000"))
+        .parse::<usize>()
+        .expect("LDAP_SIZE_LIMIT cannot be parsed to usize - bad format");
+    pub static ref LDAP_ALLOW_MFA_USERS: bool = env::var("LDAP_ALLOW_MFA_USERS")
+        .unwrap_or_else(|_| String::from("false"))
+        .parse::<bool>()
+        .expect("LDAP_ALLOW_MFA_USERS cannot be parsed to bool - bad format");
+    pub static ref LDAP_CACHE_LIFETIME: u16 = env::var("LDAP_CACHE_LIFETIME")
+        .unwrap_or_else(|_| String::from("60"))
+        .parse::<u16>()
+        .expect("LDAP_CACHE_LIFETIME cannot be parsed to u16 - bad format");
+
     pub static ref REFRESH_TOKEN_LIFETIME: u16 = env::var("REFRESH_TOKEN_LIFETIME")
        .unwrap_or_else(|_| String::from("48"))
        .parse::<u16>()
@@ -378,6 +550,10 @@ lazy_static! {
         .unwrap_or_else(|_| String::from("false"))
         .parse::<bool>()
         .expect("OPEN_USER_REG cannot be parsed to bool - bad format");
+    pub static ref USER_REG_APPROVAL: bool = env::var("USER_REG_APPROVAL")
+        .unwrap_or_else(|_| String::from("false"))
+        .parse::<bool>()
+        .expect("USER_REG_APPROVAL cannot be parsed to bool - bad format");
     pub static ref USER_REG_DOMAIN_RESTRICTION: Option<String> = {
         match env::var("USER_REG_DOMAIN_RESTRICTION") {
             Err(_) => None,
@@ -436,6 +612,10 @@ lazy_static! {
         .unwrap_or_else(|_| String::from("14400"))
         .parse::<u32>()
         .expect("SESSION_LIFETIME cannot be parsed to u32 - bad format");
+    pub static ref SESSION_LIFETIME_IMPERSONATION: u32 = env::var("SESSION_LIFETIME_IMPERSONATION")
+        .unwrap_or_else(|_| String::from("900"))
+        .parse::<u32>()
+        .expect("SESSION_LIFETIME_IMPERSONATION cannot be parsed to u32 - bad format");
     pub static ref SESSION_RENEW_MFA: bool = env::var("SESSION_RENEW_MFA")
         .unwrap_or_else(|_| String::from("false"))
         .parse::<bool>()
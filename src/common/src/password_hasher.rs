@@ -1,9 +1,12 @@
 use actix_web::web;
 use argon2::password_hash::SaltString;
 use argon2::{Algorithm, Argon2, PasswordHash, PasswordHasher, PasswordVerifier, Version};
+use base64::{engine, Engine};
 use once_cell::sync::Lazy;
 use rand_core::OsRng;
 use rauthy_error::{ErrorResponse, ErrorResponseType};
+use ring::pbkdf2;
+use std::num::NonZeroU32;
 use std::{env, thread};
 use tokio::time::Instant;
 use tracing::{debug, error, warn};
@@ -106,6 +109,168 @@ impl ComparePasswords {
     }
 }
 
+/// Password hash formats from other systems, which can be imported with users.
+/// They are verified at login and upgraded to argon2id with the current params afterward.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LegacyHash {
+    /// `$2a$`, `$2b$` or `$2y$`
+    Bcrypt,
+    /// Django / Authentik: `pbkdf2_sha256$<iterations>$<salt>$<base64 hash>`
+    Pbkdf2Django,
+    /// Keycloak / passlib: `$pbkdf2-sha256$<iterations>$<base64 salt>$<base64 hash>`
+    /// The iterations may be given as `i=<iterations>` as well, `sha512` works the same way.
+    Pbkdf2,
+    /// `$scrypt$ln=<log n>,r=<r>,p=<p>$<base64 salt>$<base64 hash>`
+    Scrypt,
+    /// `$6$[rounds=<rounds>$]<salt>$<hash>`
+    Sha512Crypt,
+}
+
+impl LegacyHash {
+    pub fn detect(hash: &str) -> Option<Self> {
+        if hash.starts_with("$2a$") || hash.starts_with("$2b$") || hash.starts_with("$2y$") {
+            Some(Self::Bcrypt)
+        } else if hash.starts_with("pbkdf2_") {
+            Some(Self::Pbkdf2Django)
+        } else if hash.starts_with("$pbkdf2-") {
+            Some(Self::Pbkdf2)
+        } else if hash.starts_with("$scrypt$") {
+            Some(Self::Scrypt)
+        } else if hash.starts_with("$6$") {
+            Some(Self::Sha512Crypt)
+        } else {
+            None
+        }
+    }
+
+    /// Checks if the given hash can be imported. Accepts argon2 and all legacy formats.
+    pub fn validate_import(hash: &str) -> Result<(), ErrorResponse> {
+        let is_valid = if hash.starts_with("$argon2") {
+            PasswordHash::new(hash).is_ok()
+        } else {
+            match Self::detect(hash) {
+                None => false,
+                Some(Self::Bcrypt) => {
+                    hash.len() == 60
+                        && hash
+                            .get(4..6)
+                            .and_then(|cost| cost.parse::<u32>().ok())
+                            .is_some_and(|cost| (4..=31).contains(&cost))
+                }
+                Some(Self::Pbkdf2Django) | Some(Self::Pbkdf2) => Pbkdf2Hash::parse(hash).is_some(),
+                Some(Self::Scrypt) => PasswordHash::new(hash).is_ok_and(|h| h.hash.is_some()),
+                Some(Self::Sha512Crypt) => {
+                    let parts = hash.split('$').skip(2).collect::<Vec<_>>();
+                    match parts.as_slice() {
+                        [salt, hash] => !salt.is_empty() && hash.len() == 86,
+                        [rounds, salt, hash] => {
+                            rounds.starts_with("rounds=") && !salt.is_empty() && hash.len() == 86
+                        }
+                        _ => false,
+                    }
+                }
+            }
+        };
+
+        if is_valid {
+            Ok(())
+        } else {
+            Err(ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                "Invalid or unsupported password hash format",
+            ))
+        }
+    }
+
+    fn verify(&self, plain: &str, hash: &str) -> bool {
+        match self {
+            Self::Bcrypt => bcrypt::verify(plain, hash).unwrap_or(false),
+            Self::Pbkdf2Django | Self::Pbkdf2 => {
+                Pbkdf2Hash::parse(hash).is_some_and(|h| h.verify(plain))
+            }
+            Self::Scrypt => PasswordHash::new(hash)
+                .is_ok_and(|h| scrypt::Scrypt.verify_password(plain.as_bytes(), &h).is_ok()),
+            Self::Sha512Crypt => sha_crypt::sha512_check(plain, hash).is_ok(),
+        }
+    }
+}
+
+struct Pbkdf2Hash {
+    algorithm: pbkdf2::Algorithm,
+    iterations: NonZeroU32,
+    salt: Vec<u8>,
+    hash: Vec<u8>,
+}
+
+impl Pbkdf2Hash {
+    fn parse(value: &str) -> Option<Self> {
+        if let Some(rest) = value.strip_prefix("pbkdf2_") {
+            // Django uses the salt as it is and only encodes the hash
+            let mut parts = rest.split('$');
+            let algorithm = Self::algorithm(parts.next()?)?;
+            let iterations = parts.next()?.parse().ok()?;
+            let salt = parts.next()?.as_bytes().to_vec();
+            let hash = decode_b64_lenient(parts.next()?)?;
+            Self::build(algorithm, iterations, salt, hash, parts.next())
+        } else {
+            let mut parts = value.strip_prefix("$pbkdf2-")?.split('$');
+            let algorithm = Self::algorithm(parts.next()?)?;
+            let iterations = parts.next()?;
+            let iterations = match iterations.strip_prefix("i=") {
+                Some(params) => params.split(',').next()?.parse().ok()?,
+                None => iterations.parse().ok()?,
+            };
+            let salt = decode_b64_lenient(parts.next()?)?;
+            let hash = decode_b64_lenient(parts.next()?)?;
+            Self::build(algorithm, iterations, salt, hash, parts.next())
+        }
+    }
+
+    fn build(
+        algorithm: pbkdf2::Algorithm,
+        iterations: NonZeroU32,
+        salt: Vec<u8>,
+        hash: Vec<u8>,
+        remaining: Option<&str>,
+    ) -> Option<Self> {
+        if remaining.is_some() || salt.is_empty() || hash.is_empty() {
+            return None;
+        }
+        Some(Self {
+            algorithm,
+            iterations,
+            salt,
+            hash,
+        })
+    }
+
+    fn algorithm(name: &str) -> Option<pbkdf2::Algorithm> {
+        match name {
+            "sha1" => Some(pbkdf2::PBKDF2_HMAC_SHA1),
+            "sha256" => Some(pbkdf2::PBKDF2_HMAC_SHA256),
+            "sha512" => Some(pbkdf2::PBKDF2_HMAC_SHA512),
+            _ => None,
+        }
+    }
+
+    fn verify(&self, plain: &str) -> bool {
+        pbkdf2::verify(
+            self.algorithm,
+            self.iterations,
+            &self.salt,
+            plain.as_bytes(),
+            &self.hash,
+        )
+        .is_ok()
+    }
+}
+
+/// Accepts standard base64 with or without padding and the passlib variant using `.` for `+`.
+fn decode_b64_lenient(value: &str) -> Option<Vec<u8>> {
+    let value = value.trim_end_matches('=').replace('.', "+");
+    engine::general_purpose::STANDARD_NO_PAD.decode(value).ok()
+}
+
 enum PasswordHashMessage {
     Hash(HashPassword),
     Compare(ComparePasswords),
@@ -173,17 +338,21 @@ fn compare_passwords(msg: ComparePasswords) {
 
     let mut is_match = false;
 
-    match PasswordHash::new(&msg.hash) {
-        Ok(parsed_hash) => {
-            if Argon2::default()
-                .verify_password(msg.plain_text.as_bytes(), &parsed_hash)
-                .is_ok()
-            {
-                is_match = true;
+    if let Some(legacy) = LegacyHash::detect(&msg.hash) {
+        is_match = legacy.verify(&msg.plain_text, &msg.hash);
+    } else {
+        match PasswordHash::new(&msg.hash) {
+            Ok(parsed_hash) => {
+                if Argon2::default()
+                    .verify_password(msg.plain_text.as_bytes(), &parsed_hash)
+                    .is_ok()
+                {
+                    is_match = true;
+                }
+            }
+            Err(err) => {
+                error!("Error parsing the original password hash: {}", err);
             }
-        }
-        Err(err) => {
-            error!("Error parsing the original password hash: {}", err);
         }
     }
 
@@ -202,6 +371,57 @@ mod tests {
     use std::time::{Duration, Instant};
     use tokio::time;
 
+    #[test]
+    fn test_legacy_hashes() {
+        let plain = "SuperSecure1337";
+        let hashes = [
+            (
+                "$2b$04$7CWRI46zDTe9WdvdFGnEt.wrNYDaRNHQZJ5TiB6CFsAtY8wBPv3Fm",
+                LegacyHash::Bcrypt,
+            ),
+            (
+                "pbkdf2_sha256$1000$Wd8lyhSBYEHE$IXkBF+kRdOP1QO06Aobc9gtOcJ4k+Z8gOBZRBUql8eo=",
+                LegacyHash::Pbkdf2Django,
+            ),
+            (
+                "$pbkdf2-sha256$1000$MDEyMzQ1Njc4OWFiY2RlZg$x7MXj/Sm6x6CHzNUG0fDdJpFNj1CdyRKxdN3tohFBa8",
+                LegacyHash::Pbkdf2,
+            ),
+            (
+                "$pbkdf2-sha512$i=1000,l=64$MDEyMzQ1Njc4OWFiY2RlZg$nu9Q1O798w7IOZkSt7w2gs9NOWket9WObAcuRguls3Mg8HRxWUIrHEKSaGabwfs1Bdz9ubn246FqRQwwWDe5pg",
+                LegacyHash::Pbkdf2,
+            ),
+            (
+                "$scrypt$ln=10,r=8,p=1$MDEyMzQ1Njc4OWFiY2RlZg$XPa8Y+S8feh2LlCZCJCMOlMNnqhMKk29kOcyX3Fpbks",
+                LegacyHash::Scrypt,
+            ),
+            (
+                "$6$saltsaltsalt$syOQ5DBvr3DYMVlkxLtrnbquMn4QVVHOng9zj7W7L/61/U6kgCbNLwh2oxxVlAz8./1GsL7DHwhuoEFB0sceS/",
+                LegacyHash::Sha512Crypt,
+            ),
+            (
+                "$6$rounds=5000$saltsaltsalt$syOQ5DBvr3DYMVlkxLtrnbquMn4QVVHOng9zj7W7L/61/U6kgCbNLwh2oxxVlAz8./1GsL7DHwhuoEFB0sceS/",
+                LegacyHash::Sha512Crypt,
+            ),
+        ];
+
+        for (hash, typ) in hashes {
+            assert_eq!(LegacyHash::detect(hash), Some(typ));
+            assert!(LegacyHash::validate_import(hash).is_ok());
+            assert!(typ.verify(plain, hash), "{}", hash);
+            assert!(!typ.verify("SuperSecure1338", hash), "{}", hash);
+        }
+
+        assert_eq!(
+            LegacyHash::detect("$argon2id$v=19$m=32768,t=3,p=2$abc$def"),
+            None
+        );
+        assert!(LegacyHash::validate_import("$2b$04$tooShort").is_err());
+        assert!(LegacyHash::validate_import("pbkdf2_md5$1000$salt$aGFzaA==").is_err());
+        assert!(LegacyHash::validate_import("$1$salt$hash").is_err());
+        assert!(LegacyHash::validate_import("plain").is_err());
+    }
+
     #[tokio::test]
     async fn test_limiter() {
         env::set_var("ARGON2_M_COST", "32768");
@@ -50,9 +50,15 @@ impl ResponseError for ErrorResponse {
             ErrorResponseType::BadRequest | ErrorResponseType::UseDpopNonce(_) => {
                 StatusCode::BAD_REQUEST
             }
-            ErrorResponseType::Forbidden => StatusCode::FORBIDDEN,
+            ErrorResponseType::AccessDenied
+            | ErrorResponseType::EmailCodeRequired
+            | ErrorResponseType::Forbidden
+            | ErrorResponseType::PasswordRefresh
+            | ErrorResponseType::ProviderLoginRequired => StatusCode::FORBIDDEN,
             ErrorResponseType::MfaRequired => StatusCode::NOT_ACCEPTABLE,
             ErrorResponseType::NotFound => StatusCode::NOT_FOUND,
+            ErrorResponseType::OrgSelectionRequired => StatusCode::CONFLICT,
+            ErrorResponseType::PowRequired => StatusCode::PRECONDITION_REQUIRED,
             ErrorResponseType::Disabled
             | ErrorResponseType::CSRFTokenError
             | ErrorResponseType::DPoP(_)
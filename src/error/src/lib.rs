@@ -8,12 +8,14 @@ pub mod error_impls;
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
 pub enum ErrorResponseType {
+    AccessDenied,
     BadRequest,
     Connection,
     CSRFTokenError,
     Database,
     DatabaseIo,
     Disabled,
+    EmailCodeRequired,
     // These String could be optimized in the future with borrowing
     // -> just not going down that rabbit hole for now
     DPoP(Option<String>),
@@ -25,8 +27,11 @@ pub enum ErrorResponseType {
     MfaRequired,
     NoSession,
     NotFound,
+    OrgSelectionRequired,
     PasswordExpired,
     PasswordRefresh,
+    PowRequired,
+    ProviderLoginRequired,
     SessionExpired,
     SessionTimeout,
     TooManyRequests(i64),
@@ -144,6 +144,10 @@ fn is_path_csrf_exception(path: &str) -> bool {
                 || path.ends_with("/logo")
                 || path.starts_with("/.well-known/")
                 || path.contains("/webauthn/auth/")
+                // SAML HTTP-POST bindings are cross-site form posts by design
+                || path == "/saml/sso"
+                || path == "/saml/slo"
+                || path == "/providers/saml/acs"
         }
     }
 }
@@ -163,11 +167,15 @@ mod tests {
         assert!(is_path_csrf_exception(
             "/auth/v1/.well-known/openid-configuration"
         ));
+        assert!(is_path_csrf_exception("/auth/v1/saml/sso"));
+        assert!(is_path_csrf_exception("/auth/v1/saml/slo"));
+        assert!(is_path_csrf_exception("/auth/v1/providers/saml/acs"));
 
         // denied
         assert!(!is_path_csrf_exception("/auth/v1/oidc/authorize/refresh"));
         assert!(!is_path_csrf_exception("/auth/v1/users"));
         assert!(!is_path_csrf_exception("/auth/v1/users/id123"));
+        assert!(!is_path_csrf_exception("/auth/v1/saml/resume"));
         assert!(!is_path_csrf_exception("/docs/v1/whatever"));
         assert!(!is_path_csrf_exception("/"));
         assert!(!is_path_csrf_exception("/value123"));
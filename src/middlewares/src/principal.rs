@@ -8,6 +8,7 @@ use rauthy_common::utils::real_ip_from_svc_req;
 use rauthy_error::{ErrorResponse, ErrorResponseType};
 use rauthy_models::api_cookie::ApiCookie;
 use rauthy_models::app_state::AppState;
+use rauthy_models::entity::admin_roles::AdminRole;
 use rauthy_models::entity::api_keys::{ApiKey, ApiKeyEntity};
 use rauthy_models::entity::principal::Principal;
 use rauthy_models::entity::sessions::Session;
@@ -69,6 +70,9 @@ where
             principal.api_key = get_api_key_from_headers(&req).await?;
             if let Some(s) = get_session_from_cookie(&req, data).await? {
                 principal.roles = s.roles_as_vec().unwrap_or_default();
+                if !principal.is_admin() && !s.is_impersonated() {
+                    principal.admin_roles = AdminRole::find_for_roles(&principal.roles).await?;
+                }
                 principal.session = Some(s);
             }
 
@@ -1,15 +1,19 @@
 use crate::app_state::AppState;
 use crate::entity::magic_links::MagicLink;
 use crate::entity::users::User;
+use crate::i18n::email_account_locked::I18nEmailAccountLocked;
 use crate::i18n::email_change_info_new::I18nEmailChangeInfoNew;
 use crate::i18n::email_confirm_change::I18nEmailConfirmChange;
+use crate::i18n::email_login_code::I18nEmailLoginCode;
 use crate::i18n::email_password_new::I18nEmailPasswordNew;
 use crate::i18n::email_reset::I18nEmailReset;
 use crate::i18n::email_reset_info::I18nEmailResetInfo;
+use crate::i18n::email_security_notification::I18nEmailSecurityNotification;
 use crate::i18n::SsrJson;
+use crate::security_notifications::SecurityNotification;
 use actix_web::web;
 use askama_actix::Template;
-use chrono::DateTime;
+use chrono::{DateTime, Utc};
 use lettre::message::{MultiPart, SinglePart};
 use lettre::transport::smtp::authentication;
 use lettre::{message, AsyncSmtpTransport, AsyncTransport};
@@ -49,6 +53,90 @@ pub struct EMailEventTxt<'a> {
     pub row_2: &'a str,
 }
 
+#[derive(Default, Template)]
+#[template(path = "email/account_locked.html")]
+pub struct EMailAccountLockedHtml<'a> {
+    pub email_sub_prefix: &'a str,
+    pub link: &'a str,
+    pub exp: &'a str,
+    // i18n
+    pub header: &'a str,
+    pub text: &'a str,
+    pub click_link: &'a str,
+    pub expires: &'a str,
+    pub button_text: &'a str,
+}
+
+#[derive(Default, Template)]
+#[template(path = "email/account_locked.txt")]
+pub struct EMailAccountLockedTxt<'a> {
+    pub email_sub_prefix: &'a str,
+    pub link: &'a str,
+    pub exp: &'a str,
+    // i18n
+    pub header: &'a str,
+    pub text: &'a str,
+    pub click_link: &'a str,
+    pub expires: &'a str,
+}
+
+#[derive(Default, Template)]
+#[template(path = "email/login_code.html")]
+pub struct EMailLoginCodeHtml<'a> {
+    pub email_sub_prefix: &'a str,
+    pub code: &'a str,
+    pub exp: &'a str,
+    // i18n
+    pub header: &'a str,
+    pub text: &'a str,
+    pub expires: &'a str,
+    pub ignore: &'a str,
+}
+
+#[derive(Default, Template)]
+#[template(path = "email/login_code.txt")]
+pub struct EMailLoginCodeTxt<'a> {
+    pub email_sub_prefix: &'a str,
+    pub code: &'a str,
+    pub exp: &'a str,
+    // i18n
+    pub header: &'a str,
+    pub text: &'a str,
+    pub expires: &'a str,
+    pub ignore: &'a str,
+}
+
+#[derive(Default, Template)]
+#[template(path = "email/security_notification.html")]
+pub struct EMailSecurityNotificationHtml<'a> {
+    pub email_sub_prefix: &'a str,
+    pub link: &'a str,
+    pub exp: &'a str,
+    pub details: Vec<(&'a str, String)>,
+    // i18n
+    pub header: &'a str,
+    pub text: &'a str,
+    pub ignore: &'a str,
+    pub not_me: &'a str,
+    pub expires: &'a str,
+    pub button_text: &'a str,
+}
+
+#[derive(Default, Template)]
+#[template(path = "email/security_notification.txt")]
+pub struct EMailSecurityNotificationTxt<'a> {
+    pub email_sub_prefix: &'a str,
+    pub link: &'a str,
+    pub exp: &'a str,
+    pub details: Vec<(&'a str, String)>,
+    // i18n
+    pub header: &'a str,
+    pub text: &'a str,
+    pub ignore: &'a str,
+    pub not_me: &'a str,
+    pub expires: &'a str,
+}
+
 #[derive(Default, Template)]
 #[template(path = "email/change_info_new.html")]
 pub struct EMailChangeInfoNewHtml<'a> {
@@ -301,6 +389,215 @@ pub async fn send_email_confirm_change(
     }
 }
 
+pub async fn send_account_locked(data: &web::Data<AppState>, magic_link: &MagicLink, user: &User) {
+    let link = format!(
+        "{}/users/{}/unlock/{}",
+        data.issuer, magic_link.user_id, &magic_link.id,
+    );
+    let exp = email_ts_prettify(magic_link.exp);
+
+    let i18n = I18nEmailAccountLocked::build(&user.language);
+    let text = EMailAccountLockedTxt {
+        email_sub_prefix: &EMAIL_SUB_PREFIX,
+        link: &link,
+        exp: &exp,
+        header: i18n.header,
+        text: i18n.text,
+        click_link: i18n.click_link,
+        expires: i18n.expires,
+    };
+
+    let html = EMailAccountLockedHtml {
+        email_sub_prefix: &EMAIL_SUB_PREFIX,
+        link: &link,
+        exp: &exp,
+        header: i18n.header,
+        text: i18n.text,
+        click_link: i18n.click_link,
+        expires: i18n.expires,
+        button_text: i18n.button_text,
+    };
+
+    let req = EMail {
+        recipient_name: user.email_recipient_name(),
+        address: user.email.to_string(),
+        subject: format!("{} - {}", *EMAIL_SUB_PREFIX, i18n.subject),
+        text: text
+            .render()
+            .expect("Template rendering: EMailAccountLockedTxt"),
+        html: Some(
+            html.render()
+                .expect("Template rendering: EMailAccountLockedHtml"),
+        ),
+    };
+
+    let tx = &data.tx_email;
+    let res = tx.send_timeout(req, Duration::from_secs(10)).await;
+    match res {
+        Ok(_) => {}
+        Err(ref e) => {
+            error!(
+                "Error sending account unlock email request for user '{}': {:?}",
+                user.email, e
+            );
+        }
+    }
+}
+
+pub async fn send_login_code(data: &web::Data<AppState>, user: &User, code: &str, exp: i64) {
+    let exp = email_ts_prettify(exp);
+
+    let i18n = I18nEmailLoginCode::build(&user.language);
+    let text = EMailLoginCodeTxt {
+        email_sub_prefix: &EMAIL_SUB_PREFIX,
+        code,
+        exp: &exp,
+        header: i18n.header,
+        text: i18n.text,
+        expires: i18n.expires,
+        ignore: i18n.ignore,
+    };
+
+    let html = EMailLoginCodeHtml {
+        email_sub_prefix: &EMAIL_SUB_PREFIX,
+        code,
+        exp: &exp,
+        header: i18n.header,
+        text: i18n.text,
+        expires: i18n.expires,
+        ignore: i18n.ignore,
+    };
+
+    let req = EMail {
+        recipient_name: user.email_recipient_name(),
+        address: user.email.to_string(),
+        subject: format!("{} - {}", *EMAIL_SUB_PREFIX, i18n.subject),
+        text: text
+            .render()
+            .expect("Template rendering: EMailLoginCodeTxt"),
+        html: Some(
+            html.render()
+                .expect("Template rendering: EMailLoginCodeHtml"),
+        ),
+    };
+
+    let tx = &data.tx_email;
+    let res = tx.send_timeout(req, Duration::from_secs(10)).await;
+    match res {
+        Ok(_) => {}
+        Err(ref e) => {
+            error!(
+                "Error sending login code for user '{}': {:?}",
+                user.email, e
+            );
+        }
+    }
+}
+
+pub async fn send_security_notification(
+    data: &web::Data<AppState>,
+    magic_link: &MagicLink,
+    user: &User,
+    notification: &SecurityNotification,
+) {
+    let link = format!(
+        "{}/users/{}/not_me/{}",
+        data.issuer, magic_link.user_id, &magic_link.id,
+    );
+    let exp = email_ts_prettify(magic_link.exp);
+    let now = email_ts_prettify(Utc::now().timestamp());
+
+    let i18n = I18nEmailSecurityNotification::build(&user.language);
+    let (subject, text, mut details) = match notification {
+        SecurityNotification::NewLogin {
+            ip,
+            country,
+            device,
+        } => {
+            let ip = match country {
+                Some(country) => format!("{} ({})", ip, country),
+                None => ip.clone(),
+            };
+            (
+                i18n.subject_new_login,
+                i18n.text_new_login,
+                vec![(i18n.label_device, device.clone()), (i18n.label_ip, ip)],
+            )
+        }
+        SecurityNotification::PasskeyAdded(name) => (
+            i18n.subject_passkey_added,
+            i18n.text_passkey_added,
+            vec![(i18n.label_name, name.clone())],
+        ),
+        SecurityNotification::PasskeyRemoved(name) => (
+            i18n.subject_passkey_removed,
+            i18n.text_passkey_removed,
+            vec![(i18n.label_name, name.clone())],
+        ),
+        SecurityNotification::PasswordChanged => (
+            i18n.subject_password_changed,
+            i18n.text_password_changed,
+            Vec::with_capacity(1),
+        ),
+        SecurityNotification::ProviderLinked(name) => (
+            i18n.subject_provider_linked,
+            i18n.text_provider_linked,
+            vec![(i18n.label_name, name.clone())],
+        ),
+    };
+    details.push((i18n.label_time, now));
+
+    let text_tpl = EMailSecurityNotificationTxt {
+        email_sub_prefix: &EMAIL_SUB_PREFIX,
+        link: &link,
+        exp: &exp,
+        details: details.clone(),
+        header: i18n.header,
+        text,
+        ignore: i18n.ignore,
+        not_me: i18n.not_me,
+        expires: i18n.expires,
+    };
+
+    let html = EMailSecurityNotificationHtml {
+        email_sub_prefix: &EMAIL_SUB_PREFIX,
+        link: &link,
+        exp: &exp,
+        details,
+        header: i18n.header,
+        text,
+        ignore: i18n.ignore,
+        not_me: i18n.not_me,
+        expires: i18n.expires,
+        button_text: i18n.button_text,
+    };
+
+    let req = EMail {
+        recipient_name: user.email_recipient_name(),
+        address: user.email.to_string(),
+        subject: format!("{} - {}", *EMAIL_SUB_PREFIX, subject),
+        text: text_tpl
+            .render()
+            .expect("Template rendering: EMailSecurityNotificationTxt"),
+        html: Some(
+            html.render()
+                .expect("Template rendering: EMailSecurityNotificationHtml"),
+        ),
+    };
+
+    let tx = &data.tx_email;
+    let res = tx.send_timeout(req, Duration::from_secs(10)).await;
+    match res {
+        Ok(_) => {}
+        Err(ref e) => {
+            error!(
+                "Error sending security notification for user '{}': {:?}",
+                user.email, e
+            );
+        }
+    }
+}
+
 pub async fn send_pwd_reset(data: &web::Data<AppState>, magic_link: &MagicLink, user: &User) {
     let link = format!(
         "{}/users/{}/reset/{}?type={}",
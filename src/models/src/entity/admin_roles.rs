@@ -0,0 +1,390 @@
+use crate::database::{Cache, DB};
+use crate::entity::api_keys::{AccessGroup, AccessRights, ApiKeyAccess};
+use crate::entity::groups::Group;
+use crate::entity::roles::Role;
+use hiqlite::{params, Param};
+use rauthy_api_types::admin_roles::{AdminRoleRequest, AdminRoleResponse};
+use rauthy_common::constants::{CACHE_TTL_APP, IDX_ADMIN_ROLES, RAUTHY_ADMIN_ROLE};
+use rauthy_common::is_hiqlite;
+use rauthy_common::utils::new_store_id;
+use rauthy_error::{ErrorResponse, ErrorResponseType};
+use serde::{Deserialize, Serialize};
+use sqlx::{query, query_as, FromRow};
+
+/// The raw database representation of an `AdminRole`
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct AdminRoleEntity {
+    pub id: String,
+    pub name: String,
+    pub description: Option<String>,
+    pub access: Vec<u8>,
+    pub group_scope: Option<String>,
+}
+
+// CRUD
+impl AdminRoleEntity {
+    pub async fn create(req: AdminRoleRequest) -> Result<AdminRole, ErrorResponse> {
+        Self::validate_name(&req.name).await?;
+
+        let mut roles = AdminRole::find_all().await?;
+        if roles.iter().any(|r| r.name == req.name) {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                "An admin role for this role name already exists",
+            ));
+        }
+
+        let access = req
+            .access
+            .into_iter()
+            .map(ApiKeyAccess::from)
+            .collect::<Vec<_>>();
+        let slf = Self {
+            id: new_store_id(),
+            name: req.name,
+            description: req.description,
+            access: bincode::serialize(&access)?,
+            group_scope: Group::sanitize(req.group_scope).await?,
+        };
+
+        if is_hiqlite() {
+            DB::client()
+                .execute(
+                    r#"
+INSERT INTO admin_roles (id, name, description, access, group_scope)
+VALUES ($1, $2, $3, $4, $5)"#,
+                    params!(
+                        slf.id.clone(),
+                        slf.name.clone(),
+                        slf.description.clone(),
+                        slf.access.clone(),
+                        slf.group_scope.clone()
+                    ),
+                )
+                .await?;
+        } else {
+            query!(
+                r#"
+INSERT INTO admin_roles (id, name, description, access, group_scope)
+VALUES ($1, $2, $3, $4, $5)"#,
+                slf.id,
+                slf.name,
+                slf.description,
+                slf.access,
+                slf.group_scope,
+            )
+            .execute(DB::conn())
+            .await?;
+        }
+
+        let role = AdminRole::try_from(slf)?;
+        roles.push(role.clone());
+        DB::client()
+            .put(Cache::App, IDX_ADMIN_ROLES, &roles, CACHE_TTL_APP)
+            .await?;
+
+        Ok(role)
+    }
+
+    pub async fn delete(id: &str) -> Result<(), ErrorResponse> {
+        if is_hiqlite() {
+            DB::client()
+                .execute("DELETE FROM admin_roles WHERE id = $1", params!(id))
+                .await?;
+        } else {
+            query!("DELETE FROM admin_roles WHERE id = $1", id)
+                .execute(DB::conn())
+                .await?;
+        }
+
+        DB::client().delete(Cache::App, IDX_ADMIN_ROLES).await?;
+        Ok(())
+    }
+
+    /// Deletes a possibly existing admin role for the given (Rauthy) role name.
+    /// Does nothing, if none exists.
+    pub async fn delete_by_name(name: &str) -> Result<(), ErrorResponse> {
+        if is_hiqlite() {
+            DB::client()
+                .execute("DELETE FROM admin_roles WHERE name = $1", params!(name))
+                .await?;
+        } else {
+            query!("DELETE FROM admin_roles WHERE name = $1", name)
+                .execute(DB::conn())
+                .await?;
+        }
+
+        DB::client().delete(Cache::App, IDX_ADMIN_ROLES).await?;
+        Ok(())
+    }
+
+    pub async fn find(id: &str) -> Result<Self, ErrorResponse> {
+        let res = if is_hiqlite() {
+            DB::client()
+                .query_as_one("SELECT * FROM admin_roles WHERE id = $1", params!(id))
+                .await?
+        } else {
+            query_as!(Self, "SELECT * FROM admin_roles WHERE id = $1", id)
+                .fetch_one(DB::conn())
+                .await?
+        };
+
+        Ok(res)
+    }
+
+    /// Keeps the admin role in sync when the linked Rauthy role gets renamed.
+    pub async fn rename(old_name: &str, new_name: &str) -> Result<(), ErrorResponse> {
+        if is_hiqlite() {
+            DB::client()
+                .execute(
+                    "UPDATE admin_roles SET name = $1 WHERE name = $2",
+                    params!(new_name, old_name),
+                )
+                .await?;
+        } else {
+            query!(
+                "UPDATE admin_roles SET name = $1 WHERE name = $2",
+                new_name,
+                old_name,
+            )
+            .execute(DB::conn())
+            .await?;
+        }
+
+        DB::client().delete(Cache::App, IDX_ADMIN_ROLES).await?;
+        Ok(())
+    }
+
+    pub async fn update(id: &str, req: AdminRoleRequest) -> Result<AdminRole, ErrorResponse> {
+        let mut slf = Self::find(id).await?;
+
+        if slf.name != req.name {
+            Self::validate_name(&req.name).await?;
+            if AdminRole::find_all()
+                .await?
+                .iter()
+                .any(|r| r.name == req.name)
+            {
+                return Err(ErrorResponse::new(
+                    ErrorResponseType::BadRequest,
+                    "An admin role for this role name already exists",
+                ));
+            }
+        }
+
+        let access = req
+            .access
+            .into_iter()
+            .map(ApiKeyAccess::from)
+            .collect::<Vec<_>>();
+        slf.name = req.name;
+        slf.description = req.description;
+        slf.access = bincode::serialize(&access)?;
+        slf.group_scope = Group::sanitize(req.group_scope).await?;
+
+        if is_hiqlite() {
+            DB::client()
+                .execute(
+                    r#"
+UPDATE admin_roles
+SET name = $1, description = $2, access = $3, group_scope = $4
+WHERE id = $5"#,
+                    params!(
+                        slf.name.clone(),
+                        slf.description.clone(),
+                        slf.access.clone(),
+                        slf.group_scope.clone(),
+                        slf.id.clone()
+                    ),
+                )
+                .await?;
+        } else {
+            query!(
+                r#"
+UPDATE admin_roles
+SET name = $1, description = $2, access = $3, group_scope = $4
+WHERE id = $5"#,
+                slf.name,
+                slf.description,
+                slf.access,
+                slf.group_scope,
+                slf.id,
+            )
+            .execute(DB::conn())
+            .await?;
+        }
+
+        DB::client().delete(Cache::App, IDX_ADMIN_ROLES).await?;
+
+        AdminRole::try_from(slf)
+    }
+}
+
+impl AdminRoleEntity {
+    async fn validate_name(name: &str) -> Result<(), ErrorResponse> {
+        if name == *RAUTHY_ADMIN_ROLE {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                "The 'rauthy_admin' role always has full access and cannot be restricted",
+            ));
+        }
+
+        if !Role::find_all().await?.iter().any(|r| r.name == name) {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                "An admin role must be linked to an existing role",
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+/// A scoped administrator role definition.
+///
+/// Each user, that has a Rauthy role with the same `name` assigned, will get the access rights
+/// defined in `access` for the admin API. If a `group_scope` is set, access to the `Users`
+/// group is restricted to users that are a member of at least one of these groups.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AdminRole {
+    pub id: String,
+    pub name: String,
+    pub description: Option<String>,
+    pub access: Vec<ApiKeyAccess>,
+    pub group_scope: Option<Vec<String>>,
+}
+
+impl AdminRole {
+    pub async fn find_all() -> Result<Vec<Self>, ErrorResponse> {
+        let client = DB::client();
+        if let Some(slf) = client.get(Cache::App, IDX_ADMIN_ROLES).await? {
+            return Ok(slf);
+        }
+
+        let entities: Vec<AdminRoleEntity> = if is_hiqlite() {
+            client
+                .query_as("SELECT * FROM admin_roles", params!())
+                .await?
+        } else {
+            query_as!(AdminRoleEntity, "SELECT * FROM admin_roles")
+                .fetch_all(DB::conn())
+                .await?
+        };
+
+        let mut res = Vec::with_capacity(entities.len());
+        for entity in entities {
+            res.push(Self::try_from(entity)?);
+        }
+
+        client
+            .put(Cache::App, IDX_ADMIN_ROLES, &res, CACHE_TTL_APP)
+            .await?;
+
+        Ok(res)
+    }
+
+    /// Returns all admin roles that are linked to one of the given role names.
+    pub async fn find_for_roles(roles: &[String]) -> Result<Vec<Self>, ErrorResponse> {
+        if roles.is_empty() {
+            return Ok(Vec::default());
+        }
+
+        let res = Self::find_all()
+            .await?
+            .into_iter()
+            .filter(|r| roles.contains(&r.name))
+            .collect();
+        Ok(res)
+    }
+}
+
+impl AdminRole {
+    #[inline(always)]
+    pub fn has_access(&self, group: &AccessGroup, access_rights: &AccessRights) -> bool {
+        self.access
+            .iter()
+            .any(|a| &a.group == group && a.access_rights.contains(access_rights))
+    }
+
+    /// Returns `true` if this role is not restricted to any groups, or if at least one of
+    /// the given `groups` is inside the `group_scope`.
+    #[inline(always)]
+    pub fn is_in_scope(&self, groups: Option<&[String]>) -> bool {
+        match &self.group_scope {
+            None => true,
+            Some(scope) => groups
+                .map(|groups| groups.iter().any(|g| scope.contains(g)))
+                .unwrap_or(false),
+        }
+    }
+}
+
+impl TryFrom<AdminRoleEntity> for AdminRole {
+    type Error = ErrorResponse;
+
+    fn try_from(value: AdminRoleEntity) -> Result<Self, Self::Error> {
+        let access = bincode::deserialize::<Vec<ApiKeyAccess>>(&value.access)?;
+        let group_scope = value
+            .group_scope
+            .map(|g| g.split(',').map(|g| g.trim().to_string()).collect());
+
+        Ok(Self {
+            id: value.id,
+            name: value.name,
+            description: value.description,
+            access,
+            group_scope,
+        })
+    }
+}
+
+impl From<AdminRole> for AdminRoleResponse {
+    fn from(value: AdminRole) -> Self {
+        Self {
+            id: value.id,
+            name: value.name,
+            description: value.description,
+            access: value
+                .access
+                .into_iter()
+                .map(rauthy_api_types::api_keys::ApiKeyAccess::from)
+                .collect(),
+            group_scope: value.group_scope,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_admin_role_access_and_scope() {
+        let role = AdminRole {
+            id: "123".to_string(),
+            name: "user-manager".to_string(),
+            description: None,
+            access: vec![ApiKeyAccess {
+                group: AccessGroup::Users,
+                access_rights: vec![AccessRights::Read, AccessRights::Update],
+            }],
+            group_scope: Some(vec!["dept_a".to_string()]),
+        };
+
+        assert!(role.has_access(&AccessGroup::Users, &AccessRights::Read));
+        assert!(role.has_access(&AccessGroup::Users, &AccessRights::Update));
+        assert!(!role.has_access(&AccessGroup::Users, &AccessRights::Delete));
+        assert!(!role.has_access(&AccessGroup::Clients, &AccessRights::Read));
+
+        let groups = vec!["dept_b".to_string(), "dept_a".to_string()];
+        assert!(role.is_in_scope(Some(&groups)));
+        assert!(!role.is_in_scope(Some(&groups[..1])));
+        assert!(!role.is_in_scope(None));
+
+        let role = AdminRole {
+            group_scope: None,
+            ..role
+        };
+        assert!(role.is_in_scope(None));
+    }
+}
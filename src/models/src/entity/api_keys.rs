@@ -321,6 +321,7 @@ pub enum AccessGroup {
     Scopes,
     UserAttributes,
     Users,
+    Organizations,
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -425,6 +426,7 @@ impl From<AccessGroup> for rauthy_api_types::api_keys::AccessGroup {
             AccessGroup::Scopes => Self::Scopes,
             AccessGroup::UserAttributes => Self::UserAttributes,
             AccessGroup::Users => Self::Users,
+            AccessGroup::Organizations => Self::Organizations,
         }
     }
 }
@@ -467,6 +469,7 @@ impl From<rauthy_api_types::api_keys::AccessGroup> for AccessGroup {
             rauthy_api_types::api_keys::AccessGroup::Scopes => Self::Scopes,
             rauthy_api_types::api_keys::AccessGroup::UserAttributes => Self::UserAttributes,
             rauthy_api_types::api_keys::AccessGroup::Users => Self::Users,
+            rauthy_api_types::api_keys::AccessGroup::Organizations => Self::Organizations,
         }
     }
 }
@@ -18,6 +18,8 @@ pub struct AuthCode {
     pub challenge_method: Option<String>,
     pub nonce: Option<String>,
     pub scopes: Vec<String>,
+    #[serde(default)]
+    pub org_id: Option<String>,
 }
 
 // CRUD
@@ -54,6 +56,7 @@ impl AuthCode {
         challenge_method: Option<String>,
         nonce: Option<String>,
         scopes: Vec<String>,
+        org_id: Option<String>,
         lifetime_secs: i32,
     ) -> Self {
         let id = get_rand(64);
@@ -70,6 +73,7 @@ impl AuthCode {
             challenge_method,
             nonce,
             scopes,
+            org_id,
         }
     }
 }
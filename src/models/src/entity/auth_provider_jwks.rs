@@ -0,0 +1,388 @@
+use crate::database::{Cache, DB};
+use crate::entity::auth_providers::AuthProvider;
+use chrono::Utc;
+use jwt_simple::prelude::*;
+use rauthy_common::constants::{APPLICATION_JSON, CACHE_TTL_APP, IDX_AUTH_PROVIDER_JWKS};
+use rauthy_common::utils::base64_url_no_pad_decode;
+use rauthy_error::{ErrorResponse, ErrorResponseType};
+use reqwest::header::ACCEPT;
+use std::collections::HashSet;
+use tracing::{debug, error};
+
+/// An unknown `kid` will only trigger a re-fetch of the upstream JWKS, if the cached one is
+/// older than this. Prevents hammering the upstream provider with crafted tokens.
+const JWKS_MIN_REFRESH_SECS: i64 = 60;
+
+#[derive(Debug, Deserialize)]
+struct JwksResponse {
+    keys: Vec<AuthProviderJwk>,
+}
+
+/// A single public key from an upstream providers' `jwks_uri`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct AuthProviderJwk {
+    kty: String,
+    kid: Option<String>,
+    alg: Option<String>,
+    crv: Option<String>,
+    // RSA
+    n: Option<String>,
+    e: Option<String>,
+    // EC + OKP
+    x: Option<String>,
+    y: Option<String>,
+}
+
+impl AuthProviderJwk {
+    fn verify(
+        &self,
+        alg: &str,
+        token: &str,
+        options: VerificationOptions,
+    ) -> Result<JWTClaims<NoCustomClaims>, ErrorResponse> {
+        if let Some(key_alg) = &self.alg {
+            if key_alg != alg {
+                return Err(ErrorResponse::new(
+                    ErrorResponseType::Unauthorized,
+                    format!("ID token `alg` {} does not match the JWK", alg),
+                ));
+            }
+        }
+
+        let res = match (self.kty.as_str(), alg) {
+            ("RSA", "RS256") => RS256PublicKey::from_components(&self.n()?, &self.e()?)
+                .and_then(|pk| pk.verify_token::<NoCustomClaims>(token, Some(options))),
+            ("RSA", "RS384") => RS384PublicKey::from_components(&self.n()?, &self.e()?)
+                .and_then(|pk| pk.verify_token::<NoCustomClaims>(token, Some(options))),
+            ("RSA", "RS512") => RS512PublicKey::from_components(&self.n()?, &self.e()?)
+                .and_then(|pk| pk.verify_token::<NoCustomClaims>(token, Some(options))),
+            ("RSA", "PS256") => PS256PublicKey::from_components(&self.n()?, &self.e()?)
+                .and_then(|pk| pk.verify_token::<NoCustomClaims>(token, Some(options))),
+            ("RSA", "PS384") => PS384PublicKey::from_components(&self.n()?, &self.e()?)
+                .and_then(|pk| pk.verify_token::<NoCustomClaims>(token, Some(options))),
+            ("RSA", "PS512") => PS512PublicKey::from_components(&self.n()?, &self.e()?)
+                .and_then(|pk| pk.verify_token::<NoCustomClaims>(token, Some(options))),
+            ("EC", "ES256") if self.crv.as_deref() == Some("P-256") => {
+                ES256PublicKey::from_bytes(&self.ec_point()?)
+                    .and_then(|pk| pk.verify_token::<NoCustomClaims>(token, Some(options)))
+            }
+            ("EC", "ES384") if self.crv.as_deref() == Some("P-384") => {
+                ES384PublicKey::from_bytes(&self.ec_point()?)
+                    .and_then(|pk| pk.verify_token::<NoCustomClaims>(token, Some(options)))
+            }
+            ("OKP", "EdDSA") if self.crv.as_deref() == Some("Ed25519") => {
+                Ed25519PublicKey::from_bytes(&self.x()?)
+                    .and_then(|pk| pk.verify_token::<NoCustomClaims>(token, Some(options)))
+            }
+            (kty, alg) => {
+                return Err(ErrorResponse::new(
+                    ErrorResponseType::Unauthorized,
+                    format!("Unsupported upstream JWK kty / alg: {} / {}", kty, alg),
+                ));
+            }
+        };
+
+        res.map_err(|err| {
+            error!("Upstream ID token validation error: {}", err);
+            ErrorResponse::new(
+                ErrorResponseType::Unauthorized,
+                format!("Invalid upstream ID token: {}", err),
+            )
+        })
+    }
+
+    fn n(&self) -> Result<Vec<u8>, ErrorResponse> {
+        Self::decode_component(&self.n, "n")
+    }
+
+    fn e(&self) -> Result<Vec<u8>, ErrorResponse> {
+        Self::decode_component(&self.e, "e")
+    }
+
+    fn x(&self) -> Result<Vec<u8>, ErrorResponse> {
+        Self::decode_component(&self.x, "x")
+    }
+
+    /// Returns the uncompressed SEC1 encoded point for EC keys.
+    fn ec_point(&self) -> Result<Vec<u8>, ErrorResponse> {
+        let x = self.x()?;
+        let y = Self::decode_component(&self.y, "y")?;
+
+        let mut point = Vec::with_capacity(1 + x.len() + y.len());
+        point.push(0x04);
+        point.extend_from_slice(&x);
+        point.extend_from_slice(&y);
+        Ok(point)
+    }
+
+    fn decode_component(value: &Option<String>, name: &str) -> Result<Vec<u8>, ErrorResponse> {
+        if let Some(value) = value {
+            Ok(base64_url_no_pad_decode(value)?)
+        } else {
+            Err(ErrorResponse::new(
+                ErrorResponseType::Unauthorized,
+                format!("No '{}' in upstream JWK", name),
+            ))
+        }
+    }
+}
+
+/// The cached JWKS from an upstream providers' `jwks_uri`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct AuthProviderJwks {
+    keys: Vec<AuthProviderJwk>,
+    fetched_at: i64,
+}
+
+impl AuthProviderJwks {
+    #[inline(always)]
+    fn cache_idx(provider_id: &str) -> String {
+        format!("{}{}", IDX_AUTH_PROVIDER_JWKS, provider_id)
+    }
+
+    async fn fetch(
+        provider: &AuthProvider,
+        jwks_uri: &str,
+        client: &reqwest::Client,
+    ) -> Result<Self, ErrorResponse> {
+        debug!("Fetching upstream JWKS from {}", jwks_uri);
+        let res = client
+            .get(jwks_uri)
+            .header(ACCEPT, APPLICATION_JSON)
+            .send()
+            .await?;
+
+        let status = res.status();
+        if !status.is_success() {
+            let body = res.text().await.unwrap_or_default();
+            let err = format!("HTTP {} during GET {}: {}", status, jwks_uri, body);
+            error!("{}", err);
+            return Err(ErrorResponse::new(ErrorResponseType::Connection, err));
+        }
+
+        let slf = Self {
+            keys: res.json::<JwksResponse>().await?.keys,
+            fetched_at: Utc::now().timestamp(),
+        };
+
+        DB::client()
+            .put(
+                Cache::App,
+                Self::cache_idx(&provider.id),
+                &slf,
+                CACHE_TTL_APP,
+            )
+            .await?;
+
+        Ok(slf)
+    }
+
+    async fn find(
+        provider: &AuthProvider,
+        jwks_uri: &str,
+        client: &reqwest::Client,
+    ) -> Result<Self, ErrorResponse> {
+        if let Some(slf) = DB::client()
+            .get(Cache::App, Self::cache_idx(&provider.id))
+            .await?
+        {
+            return Ok(slf);
+        }
+        Self::fetch(provider, jwks_uri, client).await
+    }
+
+    pub(crate) async fn invalidate(provider_id: &str) -> Result<(), ErrorResponse> {
+        DB::client()
+            .delete(Cache::App, Self::cache_idx(provider_id))
+            .await?;
+        Ok(())
+    }
+
+    /// Returns `true` if the `kid` is unknown and the keys are old enough to be re-fetched.
+    fn needs_refresh(&self, kid: Option<&str>, now: i64) -> bool {
+        let is_known = match kid {
+            Some(kid) => self.keys.iter().any(|k| k.kid.as_deref() == Some(kid)),
+            None => !self.keys.is_empty(),
+        };
+        !is_known && now - self.fetched_at > JWKS_MIN_REFRESH_SECS
+    }
+
+    fn verify_id_token(
+        &self,
+        issuer: &str,
+        client_id: &str,
+        alg: &str,
+        kid: Option<&str>,
+        id_token: &str,
+        nonce: &str,
+    ) -> Result<(), ErrorResponse> {
+        let options = VerificationOptions {
+            allowed_issuers: Some(HashSet::from_strings(&[issuer])),
+            allowed_audiences: Some(HashSet::from_strings(&[client_id])),
+            required_nonce: Some(nonce.to_string()),
+            ..Default::default()
+        };
+
+        // Without a `kid`, we try all keys with a matching type.
+        let mut res = Err(ErrorResponse::new(
+            ErrorResponseType::Unauthorized,
+            "No matching upstream JWK found for the ID token",
+        ));
+        for key in self
+            .keys
+            .iter()
+            .filter(|k| kid.is_none() || k.kid.as_deref() == kid)
+        {
+            res = key.verify(alg, id_token, options.clone());
+            if res.is_ok() {
+                break;
+            }
+        }
+        let claims = res?;
+
+        if claims.expires_at.is_none() {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::Unauthorized,
+                "Upstream ID token without an `exp` claim",
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Fully validates an upstream ID token: signature against the providers' JWKS,
+    /// `iss`, `aud`, `exp` and the `nonce`, which has been bound to the login callback.
+    /// Keys will be re-fetched once for an unknown `kid` to handle upstream key rotations.
+    pub(crate) async fn validate_id_token(
+        provider: &AuthProvider,
+        jwks_uri: &str,
+        client: &reqwest::Client,
+        id_token: &str,
+        nonce: &str,
+    ) -> Result<(), ErrorResponse> {
+        let metadata = Token::decode_metadata(id_token).map_err(|_| {
+            ErrorResponse::new(
+                ErrorResponseType::Unauthorized,
+                "Malformed upstream ID token header",
+            )
+        })?;
+        let alg = metadata.algorithm();
+        let kid = metadata.key_id();
+
+        let mut jwks = Self::find(provider, jwks_uri, client).await?;
+        if jwks.needs_refresh(kid, Utc::now().timestamp()) {
+            debug!("Unknown upstream `kid` - re-fetching the JWKS");
+            jwks = Self::fetch(provider, jwks_uri, client).await?;
+        }
+
+        jwks.verify_id_token(
+            &provider.issuer,
+            &provider.client_id,
+            alg,
+            kid,
+            id_token,
+            nonce,
+        )?;
+
+        debug!("upstream ID token is valid");
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rauthy_common::utils::base64_url_no_pad_encode;
+
+    const ISSUER: &str = "https://upstream.example.com";
+    const CLIENT_ID: &str = "rauthy";
+    const NONCE: &str = "nonce123";
+
+    fn jwks_for(key_pair: &Ed25519KeyPair, kid: &str, fetched_at: i64) -> AuthProviderJwks {
+        AuthProviderJwks {
+            keys: vec![AuthProviderJwk {
+                kty: "OKP".to_string(),
+                kid: Some(kid.to_string()),
+                alg: Some("EdDSA".to_string()),
+                crv: Some("Ed25519".to_string()),
+                n: None,
+                e: None,
+                x: Some(base64_url_no_pad_encode(&key_pair.public_key().to_bytes())),
+                y: None,
+            }],
+            fetched_at,
+        }
+    }
+
+    fn sign(key_pair: &Ed25519KeyPair, aud: &str, nonce: Option<&str>) -> String {
+        let mut claims = Claims::create(Duration::from_mins(5))
+            .with_issuer(ISSUER)
+            .with_audience(aud);
+        if let Some(nonce) = nonce {
+            claims = claims.with_nonce(nonce);
+        }
+        key_pair.sign(claims).unwrap()
+    }
+
+    fn verify(jwks: &AuthProviderJwks, token: &str) -> Result<(), ErrorResponse> {
+        let metadata = Token::decode_metadata(token).unwrap();
+        jwks.verify_id_token(
+            ISSUER,
+            CLIENT_ID,
+            metadata.algorithm(),
+            metadata.key_id(),
+            token,
+            NONCE,
+        )
+    }
+
+    #[test]
+    fn test_verify_id_token() {
+        let key_pair = Ed25519KeyPair::generate().with_key_id("key1");
+        let jwks = jwks_for(&key_pair, "key1", Utc::now().timestamp());
+
+        let token = sign(&key_pair, CLIENT_ID, Some(NONCE));
+        assert!(verify(&jwks, &token).is_ok());
+
+        // wrong signature with the same `kid`
+        let other = Ed25519KeyPair::generate().with_key_id("key1");
+        let token = sign(&other, CLIENT_ID, Some(NONCE));
+        assert!(verify(&jwks, &token).is_err());
+
+        // wrong `aud`
+        let token = sign(&key_pair, "other-client", Some(NONCE));
+        assert!(verify(&jwks, &token).is_err());
+
+        // wrong or missing `nonce`
+        let token = sign(&key_pair, CLIENT_ID, Some("other-nonce"));
+        assert!(verify(&jwks, &token).is_err());
+        let token = sign(&key_pair, CLIENT_ID, None);
+        assert!(verify(&jwks, &token).is_err());
+
+        // unknown `kid`
+        let rotated = Ed25519KeyPair::generate().with_key_id("key2");
+        let token = sign(&rotated, CLIENT_ID, Some(NONCE));
+        assert!(verify(&jwks, &token).is_err());
+    }
+
+    #[test]
+    fn test_needs_refresh() {
+        let key_pair = Ed25519KeyPair::generate();
+        let now = Utc::now().timestamp();
+
+        let jwks = jwks_for(&key_pair, "key1", now - JWKS_MIN_REFRESH_SECS - 1);
+        assert!(!jwks.needs_refresh(Some("key1"), now));
+        assert!(!jwks.needs_refresh(None, now));
+        assert!(jwks.needs_refresh(Some("key2"), now));
+
+        // an unknown `kid` must not trigger a re-fetch for recently fetched keys
+        let jwks = jwks_for(&key_pair, "key1", now - JWKS_MIN_REFRESH_SECS + 5);
+        assert!(!jwks.needs_refresh(Some("key2"), now));
+
+        let empty = AuthProviderJwks {
+            keys: Vec::default(),
+            fetched_at: now - JWKS_MIN_REFRESH_SECS - 1,
+        };
+        assert!(empty.needs_refresh(None, now));
+    }
+}
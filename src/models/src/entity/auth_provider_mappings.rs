@@ -0,0 +1,251 @@
+use crate::entity::groups::Group;
+use crate::entity::roles::Role;
+use crate::entity::user_attr::UserAttrConfigEntity;
+use crate::entity::users::User;
+use rauthy_api_types::auth_providers::{ProviderClaimMapping, ProviderClaimMappingTarget};
+use rauthy_api_types::users::UserAttrValueRequest;
+use rauthy_error::{ErrorResponse, ErrorResponseType};
+use serde_json::Value;
+use serde_json_path::JsonPath;
+use std::collections::HashSet;
+use tracing::{debug, error};
+
+/// Validates all claim mappings for an auth provider against the existing roles, groups and
+/// custom user attributes.
+pub(crate) async fn validate_claim_mappings(
+    mappings: &[ProviderClaimMapping],
+) -> Result<(), ErrorResponse> {
+    if mappings.is_empty() {
+        return Ok(());
+    }
+
+    let roles = Role::find_all().await?;
+    let groups = Group::find_all().await?;
+    let attrs = UserAttrConfigEntity::find_all().await?;
+
+    for mapping in mappings {
+        JsonPath::parse(&mapping.claim_path)?;
+
+        let exists = match mapping.target {
+            ProviderClaimMappingTarget::Role => roles.iter().any(|r| r.name == mapping.target_name),
+            ProviderClaimMappingTarget::Group => {
+                groups.iter().any(|g| g.name == mapping.target_name)
+            }
+            ProviderClaimMappingTarget::Attribute => {
+                attrs.iter().any(|a| a.name == mapping.target_name)
+            }
+        };
+        if !exists {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                format!(
+                    "Claim mapping target '{}' does not exist",
+                    mapping.target_name
+                ),
+            ));
+        }
+
+        if mapping.target != ProviderClaimMappingTarget::Attribute && mapping.claim_value.is_none()
+        {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                format!(
+                    "Claim mapping for '{}' requires a `claim_value`",
+                    mapping.target_name
+                ),
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+/// Applies the claim mappings to the users' roles and groups and returns the attribute values,
+/// which need to be updated after the user has been saved.
+///
+/// For an existing user, only mappings with `sync` enabled will be applied.
+pub(crate) fn apply_claim_mappings(
+    mappings: &[ProviderClaimMapping],
+    claims: &Value,
+    user: &mut User,
+    is_new_user: bool,
+) -> Vec<UserAttrValueRequest> {
+    let mut roles_add = HashSet::new();
+    let mut roles_remove = HashSet::new();
+    let mut groups_add = HashSet::new();
+    let mut groups_remove = HashSet::new();
+    let mut attributes = Vec::new();
+
+    for mapping in mappings.iter().filter(|m| is_new_user || m.sync) {
+        let path = match JsonPath::parse(&mapping.claim_path) {
+            Ok(path) => path,
+            Err(err) => {
+                error!(
+                    "Error parsing JsonPath from: '{}\nError: {}",
+                    mapping.claim_path, err
+                );
+                continue;
+            }
+        };
+        let nodes = path.query(claims).all();
+        debug!(
+            "claim mapping {} -> {:?}: {:?}",
+            mapping.claim_path, mapping.target, nodes
+        );
+
+        match mapping.target {
+            ProviderClaimMappingTarget::Attribute => {
+                let value = match nodes.len() {
+                    0 if is_new_user => continue,
+                    // this will delete an existing value
+                    0 => Value::Null,
+                    1 => nodes[0].clone(),
+                    _ => Value::Array(nodes.into_iter().cloned().collect()),
+                };
+                attributes.push(UserAttrValueRequest {
+                    key: mapping.target_name.clone(),
+                    value,
+                });
+            }
+
+            ProviderClaimMappingTarget::Role | ProviderClaimMappingTarget::Group => {
+                let expected = mapping.claim_value.as_deref().unwrap_or_default();
+                let is_match = nodes.iter().any(|v| claim_matches(v, expected));
+
+                let (add, remove) = if mapping.target == ProviderClaimMappingTarget::Role {
+                    (&mut roles_add, &mut roles_remove)
+                } else {
+                    (&mut groups_add, &mut groups_remove)
+                };
+                if is_match {
+                    add.insert(mapping.target_name.as_str());
+                } else if mapping.sync {
+                    remove.insert(mapping.target_name.as_str());
+                }
+            }
+        }
+    }
+
+    // Multiple mappings can point to the same target. A single match is enough to keep it.
+    if !roles_add.is_empty() || !roles_remove.is_empty() {
+        let roles = merge_names(user.get_roles(), &roles_add, &roles_remove);
+        user.roles = roles.join(",");
+    }
+    if !groups_add.is_empty() || !groups_remove.is_empty() {
+        let groups = merge_names(user.get_groups(), &groups_add, &groups_remove);
+        user.groups = if groups.is_empty() {
+            None
+        } else {
+            Some(groups.join(","))
+        };
+    }
+
+    attributes
+}
+
+/// Compares string claims directly and all others by their JSON representation, which makes it
+/// possible to map for instance booleans or numbers as well.
+fn claim_matches(value: &Value, expected: &str) -> bool {
+    if let Some(s) = value.as_str() {
+        s == expected
+    } else {
+        value.to_string() == expected
+    }
+}
+
+fn merge_names(current: Vec<String>, add: &HashSet<&str>, remove: &HashSet<&str>) -> Vec<String> {
+    let mut res = current
+        .into_iter()
+        .filter(|name| {
+            !name.is_empty() && (add.contains(name.as_str()) || !remove.contains(name.as_str()))
+        })
+        .collect::<Vec<_>>();
+    for name in add {
+        if !res.iter().any(|n| n == name) {
+            res.push(name.to_string());
+        }
+    }
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mapping(
+        path: &str,
+        value: Option<&str>,
+        target: ProviderClaimMappingTarget,
+        name: &str,
+        sync: bool,
+    ) -> ProviderClaimMapping {
+        ProviderClaimMapping {
+            claim_path: path.to_string(),
+            claim_value: value.map(String::from),
+            target,
+            target_name: name.to_string(),
+            sync,
+        }
+    }
+
+    #[test]
+    fn test_apply_claim_mappings() {
+        let claims = serde_json::json!({
+            "groups": ["admins", "devs"],
+            "realm_access": {
+                "roles": ["ops"]
+            },
+            "department": "it"
+        });
+        let mappings = vec![
+            mapping(
+                "$.groups[*]",
+                Some("devs"),
+                ProviderClaimMappingTarget::Role,
+                "developer",
+                true,
+            ),
+            mapping(
+                "$.realm_access.roles[*]",
+                Some("finance"),
+                ProviderClaimMappingTarget::Role,
+                "accountant",
+                true,
+            ),
+            mapping(
+                "$.groups[*]",
+                Some("admins"),
+                ProviderClaimMappingTarget::Group,
+                "admins",
+                false,
+            ),
+            mapping(
+                "$.department",
+                None,
+                ProviderClaimMappingTarget::Attribute,
+                "department",
+                true,
+            ),
+        ];
+
+        // new users get all mappings
+        let mut user = User::default();
+        let attrs = apply_claim_mappings(&mappings, &claims, &mut user, true);
+        assert_eq!(user.roles, "developer");
+        assert_eq!(user.groups.as_deref(), Some("admins"));
+        assert_eq!(attrs.len(), 1);
+        assert_eq!(attrs[0].key, "department");
+        assert_eq!(attrs[0].value, Value::from("it"));
+
+        // existing users only get synced mappings, manually added values are kept
+        let mut user = User {
+            roles: "accountant,manual".to_string(),
+            groups: None,
+            ..Default::default()
+        };
+        let attrs = apply_claim_mappings(&mappings, &claims, &mut user, false);
+        assert_eq!(user.roles, "manual,developer");
+        assert_eq!(user.groups, None);
+        assert_eq!(attrs.len(), 1);
+    }
+}
@@ -0,0 +1,334 @@
+use crate::entity::auth_providers::{AuthProvider, AuthProviderCallback, AuthProviderType};
+use crate::entity::saml_certs::SamlCert;
+use crate::saml::sp::{
+    build_sp_metadata, IdpMetadata, ResponseValidation, SpAuthnRequest, UpstreamAssertion,
+};
+use chrono::Utc;
+use cryptr::utils::secure_random_alnum;
+use rauthy_api_types::auth_providers::{
+    ProviderSamlAttrMapping, ProviderSamlClaim, ProviderSamlLookupRequest,
+    ProviderSamlLookupResponse,
+};
+use rauthy_common::constants::{
+    PROVIDER_CALLBACK_URI, PROVIDER_SAML_ACS_URI, PROVIDER_SAML_METADATA_URI, PUB_URL,
+};
+use rauthy_error::{ErrorResponse, ErrorResponseType};
+use serde_json::{Map, Value};
+use tracing::error;
+
+const CLAIMS: [ProviderSamlClaim; 8] = [
+    ProviderSamlClaim::Sub,
+    ProviderSamlClaim::Email,
+    ProviderSamlClaim::GivenName,
+    ProviderSamlClaim::FamilyName,
+    ProviderSamlClaim::Name,
+    ProviderSamlClaim::Locale,
+    ProviderSamlClaim::Phone,
+    ProviderSamlClaim::Birthdate,
+];
+
+/// The attribute names used by the most common IdPs (LDAP style, `urn:oid` as used by
+/// Shibboleth and the ADFS claim types), which are checked if no explicit mapping exists.
+fn default_attrs(claim: ProviderSamlClaim) -> &'static [&'static str] {
+    match claim {
+        ProviderSamlClaim::Sub => &[],
+        ProviderSamlClaim::Email => &[
+            "mail",
+            "email",
+            "emailAddress",
+            "urn:oid:0.9.2342.19200300.100.1.3",
+            "http://schemas.xmlsoap.org/ws/2005/05/identity/claims/emailaddress",
+        ],
+        ProviderSamlClaim::GivenName => &[
+            "givenName",
+            "given_name",
+            "urn:oid:2.5.4.42",
+            "http://schemas.xmlsoap.org/ws/2005/05/identity/claims/givenname",
+        ],
+        ProviderSamlClaim::FamilyName => &[
+            "sn",
+            "surname",
+            "family_name",
+            "urn:oid:2.5.4.4",
+            "http://schemas.xmlsoap.org/ws/2005/05/identity/claims/surname",
+        ],
+        ProviderSamlClaim::Name => &[
+            "displayName",
+            "cn",
+            "name",
+            "urn:oid:2.16.840.1.113730.3.1.241",
+            "urn:oid:2.5.4.3",
+            "http://schemas.xmlsoap.org/ws/2005/05/identity/claims/name",
+        ],
+        ProviderSamlClaim::Locale => &[
+            "preferredLanguage",
+            "locale",
+            "urn:oid:2.16.840.1.113730.3.1.39",
+        ],
+        ProviderSamlClaim::Phone => &["telephoneNumber", "phone", "urn:oid:2.5.4.20"],
+        ProviderSamlClaim::Birthdate => &["birthdate", "dateOfBirth"],
+    }
+}
+
+#[inline]
+fn cert_common_name() -> &'static str {
+    PUB_URL.split(':').next().unwrap_or(&PUB_URL)
+}
+
+impl AuthProvider {
+    /// Fetches and parses the metadata of an upstream SAML IdP.
+    pub async fn lookup_saml_metadata(
+        payload: &ProviderSamlLookupRequest,
+    ) -> Result<ProviderSamlLookupResponse, ErrorResponse> {
+        let xml = if let Some(url) = &payload.metadata_url {
+            let client = Self::build_client(
+                payload.danger_allow_insecure.unwrap_or(false),
+                payload.root_pem.as_deref(),
+            )?;
+            let res = client.get(url).send().await?;
+            if !res.status().is_success() {
+                let err = format!(
+                    "HTTP {} during SAML metadata lookup from {}",
+                    res.status().as_u16(),
+                    url
+                );
+                error!("{}", err);
+                return Err(ErrorResponse::new(ErrorResponseType::BadRequest, err));
+            }
+            res.text().await?
+        } else if let Some(xml) = &payload.metadata {
+            xml.clone()
+        } else {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                "Either `metadata_url` or `metadata` must be given",
+            ));
+        };
+
+        let metadata = IdpMetadata::parse(&xml)?;
+        Ok(ProviderSamlLookupResponse {
+            issuer: metadata.entity_id,
+            authorization_endpoint: metadata.sso_url,
+            client_id: PROVIDER_SAML_METADATA_URI.clone(),
+            saml_idp_cert: metadata.cert_pem,
+        })
+    }
+
+    /// The metadata of Rauthy as a SAML Service Provider, which can be imported into an IdP.
+    pub async fn saml_sp_metadata() -> Result<String, ErrorResponse> {
+        // makes sure that a certificate for the latest key exists
+        SamlCert::find_signing_key(cert_common_name()).await?;
+        let certs = SamlCert::find_all()
+            .await?
+            .iter()
+            .map(|c| c.cert_b64())
+            .collect::<Vec<_>>();
+
+        Ok(build_sp_metadata(
+            &PROVIDER_SAML_METADATA_URI,
+            &PROVIDER_SAML_ACS_URI,
+            &certs,
+        ))
+    }
+}
+
+impl AuthProviderCallback {
+    /// Stores the `SAMLResponse` posted to the ACS for the upstream login referenced by the
+    /// `RelayState` and returns the location of the provider callback page.
+    ///
+    /// The response is validated in `login_finish()` when the browser continues the login,
+    /// which binds it to the cookie, XSRF token and PKCE verifier of the initiating session.
+    pub async fn saml_acs(
+        saml_response: String,
+        relay_state: String,
+    ) -> Result<String, ErrorResponse> {
+        let mut slf = Self::find(relay_state).await?;
+        if slf.typ != AuthProviderType::Saml || slf.saml_code.is_some() {
+            Self::delete(slf.callback_id).await?;
+
+            error!("unexpected SAML response for upstream callback");
+            return Err(ErrorResponse::new(
+                ErrorResponseType::Forbidden,
+                "unexpected SAML response",
+            ));
+        }
+
+        let code = secure_random_alnum(48);
+        let location = format!(
+            "{}?code={}&state={}",
+            *PROVIDER_CALLBACK_URI, code, slf.callback_id
+        );
+        slf.saml_code = Some(code);
+        slf.saml_response = Some(saml_response);
+        slf.save().await?;
+
+        Ok(location)
+    }
+}
+
+/// Returns the `ID` of the `AuthnRequest` and the redirect location to the IdP.
+pub(crate) async fn build_authn_request(
+    sp_entity_id: &str,
+    sso_url: &str,
+    relay_state: &str,
+) -> Result<(String, String), ErrorResponse> {
+    let (key, _) = SamlCert::find_signing_key(cert_common_name()).await?;
+    SpAuthnRequest {
+        sp_entity_id,
+        acs_url: &PROVIDER_SAML_ACS_URI,
+        sso_url,
+    }
+    .build_redirect(relay_state, Utc::now().timestamp(), &key)
+}
+
+/// Validates the `SAMLResponse` and returns the assertion as JSON claims, which can be handled
+/// like any other upstream ID token.
+pub(crate) fn validate_response(
+    provider: &AuthProvider,
+    saml_response: &str,
+    request_id: &str,
+) -> Result<Vec<u8>, ErrorResponse> {
+    let Some(cert) = provider.saml_idp_cert.as_deref() else {
+        return Err(ErrorResponse::new(
+            ErrorResponseType::Internal,
+            "SAML provider without an IdP certificate",
+        ));
+    };
+
+    let assertion = ResponseValidation {
+        idp_entity_id: &provider.issuer,
+        idp_cert_pem: cert,
+        sp_entity_id: &provider.client_id,
+        acs_url: &PROVIDER_SAML_ACS_URI,
+        request_id,
+        now: Utc::now().timestamp(),
+    }
+    .validate(saml_response)?;
+
+    claims_json(&assertion, &provider.get_saml_attr_mapping()?)
+}
+
+/// Converts the assertion into a JSON object. All attributes are available by their `Name`
+/// (and `FriendlyName`) for the claim paths and mappings of the provider. Single values become
+/// a string, multiple values an array. The standard claims are resolved afterward and will
+/// overwrite any attribute with the same name.
+fn claims_json(
+    assertion: &UpstreamAssertion,
+    mappings: &[ProviderSamlAttrMapping],
+) -> Result<Vec<u8>, ErrorResponse> {
+    let mut map = Map::with_capacity(assertion.attributes.len() + CLAIMS.len());
+
+    for attr in &assertion.attributes {
+        let value = if attr.values.len() == 1 {
+            Value::String(attr.values[0].clone())
+        } else {
+            Value::Array(attr.values.iter().cloned().map(Value::String).collect())
+        };
+        if let Some(friendly_name) = &attr.friendly_name {
+            map.entry(friendly_name.clone())
+                .or_insert_with(|| value.clone());
+        }
+        map.insert(attr.name.clone(), value);
+    }
+
+    // these would break the deserialization of the claims with a mismatching type
+    map.remove("address");
+    map.remove("email_verified");
+
+    let first_value = |names: &[&str]| {
+        assertion
+            .attributes
+            .iter()
+            .find(|a| {
+                names
+                    .iter()
+                    .any(|n| a.name == *n || a.friendly_name.as_deref() == Some(n))
+            })
+            .and_then(|a| a.values.first())
+            .cloned()
+    };
+
+    for claim in CLAIMS {
+        let mapped = mappings
+            .iter()
+            .filter(|m| m.claim == claim)
+            .map(|m| m.attribute.as_str())
+            .collect::<Vec<_>>();
+        let value = if mapped.is_empty() {
+            first_value(default_attrs(claim))
+        } else {
+            first_value(&mapped)
+        };
+
+        match value {
+            Some(v) => map.insert(claim.as_str().to_string(), Value::String(v)),
+            None => map.remove(claim.as_str()),
+        };
+    }
+
+    if !map.contains_key("sub") {
+        map.insert("sub".to_string(), Value::String(assertion.name_id.clone()));
+    }
+    // an `emailAddress` NameID is a good fallback, if the IdP does not release the attribute
+    if !map.contains_key("email") && assertion.name_id.contains('@') {
+        map.insert(
+            "email".to_string(),
+            Value::String(assertion.name_id.clone()),
+        );
+    }
+
+    Ok(serde_json::to_vec(&map)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::saml::sp::UpstreamAttribute;
+
+    #[test]
+    fn test_saml_claims_json() {
+        let assertion = UpstreamAssertion {
+            name_id: "AAdzZWNyZXQx".to_string(),
+            session_index: None,
+            attributes: vec![
+                UpstreamAttribute {
+                    name: "urn:oid:0.9.2342.19200300.100.1.3".to_string(),
+                    friendly_name: Some("mail".to_string()),
+                    values: vec!["alice@example.com".to_string()],
+                },
+                UpstreamAttribute {
+                    name: "urn:oid:2.5.4.42".to_string(),
+                    friendly_name: None,
+                    values: vec!["Alice".to_string()],
+                },
+                UpstreamAttribute {
+                    name: "memberOf".to_string(),
+                    friendly_name: None,
+                    values: vec!["admins".to_string(), "users".to_string()],
+                },
+                UpstreamAttribute {
+                    name: "employeeNumber".to_string(),
+                    friendly_name: None,
+                    values: vec!["1337".to_string()],
+                },
+            ],
+        };
+
+        let bytes = claims_json(&assertion, &[]).unwrap();
+        let json: Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(json["sub"], "AAdzZWNyZXQx");
+        assert_eq!(json["email"], "alice@example.com");
+        assert_eq!(json["mail"], "alice@example.com");
+        assert_eq!(json["given_name"], "Alice");
+        assert_eq!(json["memberOf"], serde_json::json!(["admins", "users"]));
+        assert!(json.get("family_name").is_none());
+
+        let mappings = vec![ProviderSamlAttrMapping {
+            attribute: "employeeNumber".to_string(),
+            claim: ProviderSamlClaim::Sub,
+        }];
+        let bytes = claims_json(&assertion, &mappings).unwrap();
+        let json: Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(json["sub"], "1337");
+    }
+}
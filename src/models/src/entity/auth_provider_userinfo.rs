@@ -0,0 +1,224 @@
+use rauthy_api_types::auth_providers::ProviderUserinfoMapping;
+use rauthy_common::constants::APPLICATION_JSON;
+use rauthy_error::{ErrorResponse, ErrorResponseType};
+use reqwest::header::{ACCEPT, AUTHORIZATION};
+use serde_json::Value;
+use serde_json_path::JsonPath;
+use tracing::{debug, error};
+
+/// Makes sure that all paths of a mapping can be parsed before it is saved.
+pub(crate) fn validate_userinfo_mapping(
+    mapping: &ProviderUserinfoMapping,
+) -> Result<(), ErrorResponse> {
+    for path in [
+        &mapping.sub,
+        &mapping.email,
+        &mapping.email_verified,
+        &mapping.given_name,
+        &mapping.family_name,
+        &mapping.avatar,
+        &mapping.email_endpoint_email,
+        &mapping.email_endpoint_verified,
+    ]
+    .into_iter()
+    .flatten()
+    {
+        JsonPath::parse(path)?;
+    }
+
+    if mapping.email_endpoint.is_some() && mapping.email_endpoint_email.is_none() {
+        return Err(ErrorResponse::new(
+            ErrorResponseType::BadRequest,
+            "An `email_endpoint` needs the `email_endpoint_email` path",
+        ));
+    }
+
+    Ok(())
+}
+
+/// Resolves the mapped paths from a non-standard userinfo response and adds them as standard
+/// claims to it. All original values are kept, so they can still be used for all other claim
+/// paths and mappings of the provider.
+pub(crate) async fn map_userinfo(
+    mapping: &ProviderUserinfoMapping,
+    client: &reqwest::Client,
+    access_token: &str,
+    userinfo: &[u8],
+) -> Result<Vec<u8>, ErrorResponse> {
+    let mut json = serde_json::from_slice::<Value>(userinfo)?;
+    apply_userinfo_paths(mapping, &mut json)?;
+
+    let has_email = json.get("email").map(|v| v.is_string()).unwrap_or(false);
+    if let (false, Some(endpoint)) = (has_email, &mapping.email_endpoint) {
+        debug!(
+            "Fetching the user E-Mail from the secondary endpoint {}",
+            endpoint
+        );
+
+        let res = client
+            .get(endpoint)
+            .header(AUTHORIZATION, format!("Bearer {}", access_token))
+            .header(ACCEPT, APPLICATION_JSON)
+            .send()
+            .await?;
+        if !res.status().is_success() {
+            let err = format!(
+                "HTTP {} during GET {} for the upstream user E-Mail",
+                res.status().as_u16(),
+                endpoint
+            );
+            error!("{}", err);
+            return Err(ErrorResponse::new(ErrorResponseType::Internal, err));
+        }
+        let secondary = res.json::<Value>().await?;
+        apply_email_endpoint_paths(mapping, &secondary, &mut json)?;
+    }
+
+    Ok(serde_json::to_vec(&json)?)
+}
+
+fn apply_userinfo_paths(
+    mapping: &ProviderUserinfoMapping,
+    json: &mut Value,
+) -> Result<(), ErrorResponse> {
+    let source = json.clone();
+    let Some(claims) = json.as_object_mut() else {
+        return Err(ErrorResponse::new(
+            ErrorResponseType::BadRequest,
+            "The userinfo response is not a JSON object",
+        ));
+    };
+
+    for (key, path) in [
+        ("sub", &mapping.sub),
+        ("email", &mapping.email),
+        ("given_name", &mapping.given_name),
+        ("family_name", &mapping.family_name),
+        ("picture", &mapping.avatar),
+    ] {
+        if let Some(value) = first_value(&source, path)?.and_then(into_string) {
+            claims.insert(key.to_string(), Value::String(value));
+        }
+    }
+    if let Some(verified) = first_value(&source, &mapping.email_verified)?.and_then(into_bool) {
+        claims.insert("email_verified".to_string(), Value::Bool(verified));
+    }
+
+    Ok(())
+}
+
+fn apply_email_endpoint_paths(
+    mapping: &ProviderUserinfoMapping,
+    secondary: &Value,
+    json: &mut Value,
+) -> Result<(), ErrorResponse> {
+    let Some(email) = first_value(secondary, &mapping.email_endpoint_email)?.and_then(into_string)
+    else {
+        return Ok(());
+    };
+
+    // A `verified` flag from the main userinfo belongs to the account and says nothing about
+    // this address. Only the endpoint itself can tell, if this exact E-Mail has been verified.
+    let verified = first_value(secondary, &mapping.email_endpoint_verified)?
+        .and_then(into_bool)
+        .unwrap_or(false);
+    json["email"] = Value::String(email);
+    json["email_verified"] = Value::Bool(verified);
+
+    Ok(())
+}
+
+fn first_value(json: &Value, path: &Option<String>) -> Result<Option<Value>, ErrorResponse> {
+    match path {
+        None => Ok(None),
+        Some(path) => Ok(JsonPath::parse(path)?.query(json).first().cloned()),
+    }
+}
+
+fn into_string(value: Value) -> Option<String> {
+    match value {
+        Value::String(s) => Some(s),
+        // numeric IDs are very common for OAuth2 providers
+        Value::Number(n) => Some(n.to_string()),
+        _ => None,
+    }
+}
+
+fn into_bool(value: Value) -> Option<bool> {
+    match value {
+        Value::Bool(b) => Some(b),
+        Value::String(s) => s.parse::<bool>().ok(),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_userinfo_mapping() {
+        let mapping = ProviderUserinfoMapping {
+            sub: Some("$.id".to_string()),
+            email: Some("$.account.email".to_string()),
+            email_verified: Some("$.account.verified".to_string()),
+            given_name: Some("$.username".to_string()),
+            family_name: None,
+            avatar: Some("$.avatar_url".to_string()),
+            email_endpoint: Some("https://example.com/user/emails".to_string()),
+            email_endpoint_email: Some("$[?@.primary == true].email".to_string()),
+            email_endpoint_verified: Some("$[?@.primary == true].verified".to_string()),
+        };
+        validate_userinfo_mapping(&mapping).unwrap();
+
+        let mut userinfo = json!({
+            "id": 1337,
+            "username": "alice",
+            "avatar_url": "https://example.com/alice.png",
+            "account": {
+                "verified": "true"
+            }
+        });
+        apply_userinfo_paths(&mapping, &mut userinfo).unwrap();
+        assert_eq!(userinfo["sub"], "1337");
+        assert_eq!(userinfo["given_name"], "alice");
+        assert_eq!(userinfo["picture"], "https://example.com/alice.png");
+        assert_eq!(userinfo["email_verified"], true);
+        assert!(userinfo.get("email").is_none());
+        // the original values must still exist for claim mappings
+        assert_eq!(userinfo["id"], 1337);
+
+        let secondary = json!([
+            {"email": "old@example.com", "primary": false, "verified": true},
+            {"email": "alice@example.com", "primary": true, "verified": false}
+        ]);
+        apply_email_endpoint_paths(&mapping, &secondary, &mut userinfo).unwrap();
+        assert_eq!(userinfo["email"], "alice@example.com");
+        assert_eq!(userinfo["email_verified"], false);
+
+        // without a verified path for the endpoint, the account level value must not be used
+        let mut userinfo = json!({"id": 1337, "account": {"verified": true}});
+        let unverified = ProviderUserinfoMapping {
+            email_endpoint_verified: None,
+            ..mapping
+        };
+        apply_userinfo_paths(&unverified, &mut userinfo).unwrap();
+        assert_eq!(userinfo["email_verified"], true);
+        apply_email_endpoint_paths(&unverified, &secondary, &mut userinfo).unwrap();
+        assert_eq!(userinfo["email"], "alice@example.com");
+        assert_eq!(userinfo["email_verified"], false);
+
+        // nothing must change, if the endpoint did not return a matching E-Mail
+        let mut userinfo = json!({"id": 1337, "email_verified": true});
+        apply_email_endpoint_paths(&unverified, &json!([]), &mut userinfo).unwrap();
+        assert!(userinfo.get("email").is_none());
+        assert_eq!(userinfo["email_verified"], true);
+
+        let invalid = ProviderUserinfoMapping {
+            email_endpoint: Some("https://example.com/user/emails".to_string()),
+            ..Default::default()
+        };
+        assert!(validate_userinfo_mapping(&invalid).is_err());
+    }
+}
@@ -1,10 +1,12 @@
 use crate::app_state::{AppState, DbTxn};
 use crate::database::{Cache, DB};
 use crate::entity::clients_dyn::ClientDyn;
+use crate::entity::clients_saml::ClientSaml;
 use crate::entity::jwk::JwkKeyPairAlg;
 use crate::entity::scopes::Scope;
 use crate::entity::users::User;
-use crate::ListenScheme;
+use crate::events::event::Event;
+use crate::{geoip, ListenScheme};
 use actix_web::http::header;
 use actix_web::http::header::{HeaderName, HeaderValue};
 use actix_web::{web, HttpRequest};
@@ -19,7 +21,8 @@ use rauthy_common::constants::{
     ADDITIONAL_ALLOWED_ORIGIN_SCHEMES, ADMIN_FORCE_MFA, APPLICATION_JSON, CACHE_TTL_APP,
     CACHE_TTL_DYN_CLIENT, CACHE_TTL_EPHEMERAL_CLIENT, DYN_CLIENT_DEFAULT_TOKEN_LIFETIME,
     DYN_CLIENT_SECRET_AUTO_ROTATE, ENABLE_EPHEMERAL_CLIENTS, EPHEMERAL_CLIENTS_ALLOWED_FLOWS,
-    EPHEMERAL_CLIENTS_ALLOWED_SCOPES, EPHEMERAL_CLIENTS_FORCE_MFA, PROXY_MODE, RAUTHY_VERSION,
+    EPHEMERAL_CLIENTS_ALLOWED_SCOPES, EPHEMERAL_CLIENTS_FORCE_MFA, GEOIP_COUNTRY_ALLOW,
+    GEOIP_COUNTRY_DENY, PROXY_MODE, RAUTHY_VERSION,
 };
 use rauthy_common::is_hiqlite;
 use rauthy_common::utils::{get_rand, real_ip_from_req};
@@ -28,6 +31,7 @@ use reqwest::header::CONTENT_TYPE;
 use reqwest::{tls, Url};
 use serde::{Deserialize, Serialize};
 use sqlx::{FromRow, Row};
+use std::net::IpAddr;
 use std::str::FromStr;
 use std::sync::OnceLock;
 use std::time::Duration;
@@ -70,6 +74,21 @@ pub struct Client {
     pub force_mfa: bool,
     pub client_uri: Option<String>,
     pub contacts: Option<String>,
+    /// CSV list of upstream auth provider IDs, that may be used for logins. `None` allows all.
+    pub allowed_providers: Option<String>,
+    pub allow_password_login: bool,
+    pub passkey_only: bool,
+    pub auto_redirect_provider: Option<String>,
+    /// CSV list of roles, from which a user needs at least one to access this client
+    pub access_roles: Option<String>,
+    /// CSV list of groups, from which a user needs at least one to access this client
+    pub access_groups: Option<String>,
+    /// CSV list of E-Mail domains, which are allowed to access this client
+    pub access_email_domains: Option<String>,
+    /// CSV list of ISO country codes, from which logins are allowed for this client
+    pub geo_allow_countries: Option<String>,
+    /// CSV list of ISO country codes, from which logins are denied for this client
+    pub geo_deny_countries: Option<String>,
 }
 
 // CRUD
@@ -296,6 +315,7 @@ VALUES ($1, $2, $3, $4)"#,
         if self.is_dynamic() {
             ClientDyn::delete_from_cache(&self.id).await?;
         }
+        ClientSaml::delete_from_cache(&self.id).await?;
 
         Ok(())
     }
@@ -432,8 +452,11 @@ UPDATE clients
 SET name = $1, enabled = $2, confidential = $3, secret = $4, secret_kid = $5, redirect_uris = $6,
 post_logout_redirect_uris = $7, allowed_origins = $8, flows_enabled = $9, access_token_alg = $10,
 id_token_alg = $11, auth_code_lifetime = $12, access_token_lifetime = $13, scopes = $14,
-default_scopes = $15, challenge = $16, force_mfa= $17, client_uri = $18, contacts = $19
-WHERE id = $20"#,
+default_scopes = $15, challenge = $16, force_mfa= $17, client_uri = $18, contacts = $19,
+allowed_providers = $20, allow_password_login = $21, passkey_only = $22,
+auto_redirect_provider = $23, access_roles = $24, access_groups = $25, access_email_domains = $26,
+geo_allow_countries = $27, geo_deny_countries = $28
+WHERE id = $29"#,
             params!(
                 &self.name,
                 self.enabled,
@@ -454,6 +477,15 @@ WHERE id = $20"#,
                 self.force_mfa,
                 &self.client_uri,
                 &self.contacts,
+                &self.allowed_providers,
+                self.allow_password_login,
+                self.passkey_only,
+                &self.auto_redirect_provider,
+                &self.access_roles,
+                &self.access_groups,
+                &self.access_email_domains,
+                &self.geo_allow_countries,
+                &self.geo_deny_countries,
                 &self.id
             ),
         ));
@@ -466,8 +498,11 @@ UPDATE clients
 SET name = $1, enabled = $2, confidential = $3, secret = $4, secret_kid = $5, redirect_uris = $6,
 post_logout_redirect_uris = $7, allowed_origins = $8, flows_enabled = $9, access_token_alg = $10,
 id_token_alg = $11, auth_code_lifetime = $12, access_token_lifetime = $13, scopes = $14,
-default_scopes = $15, challenge = $16, force_mfa= $17, client_uri = $18, contacts = $19
-WHERE id = $20"#,
+default_scopes = $15, challenge = $16, force_mfa= $17, client_uri = $18, contacts = $19,
+allowed_providers = $20, allow_password_login = $21, passkey_only = $22,
+auto_redirect_provider = $23, access_roles = $24, access_groups = $25, access_email_domains = $26,
+geo_allow_countries = $27, geo_deny_countries = $28
+WHERE id = $29"#,
             self.name,
             self.enabled,
             self.confidential,
@@ -487,6 +522,15 @@ WHERE id = $20"#,
             self.force_mfa,
             self.client_uri,
             self.contacts,
+            self.allowed_providers,
+            self.allow_password_login,
+            self.passkey_only,
+            self.auto_redirect_provider,
+            self.access_roles,
+            self.access_groups,
+            self.access_email_domains,
+            self.geo_allow_countries,
+            self.geo_deny_countries,
             self.id,
         )
         .execute(&mut **txn)
@@ -511,8 +555,11 @@ UPDATE clients
 SET name = $1, enabled = $2, confidential = $3, secret = $4, secret_kid = $5, redirect_uris = $6,
 post_logout_redirect_uris = $7, allowed_origins = $8, flows_enabled = $9, access_token_alg = $10,
 id_token_alg = $11, auth_code_lifetime = $12, access_token_lifetime = $13, scopes = $14,
-default_scopes = $15, challenge = $16, force_mfa= $17, client_uri = $18, contacts = $19
-WHERE id = $20"#,
+default_scopes = $15, challenge = $16, force_mfa= $17, client_uri = $18, contacts = $19,
+allowed_providers = $20, allow_password_login = $21, passkey_only = $22,
+auto_redirect_provider = $23, access_roles = $24, access_groups = $25, access_email_domains = $26,
+geo_allow_countries = $27, geo_deny_countries = $28
+WHERE id = $29"#,
                     params!(
                         self.name.clone(),
                         self.enabled,
@@ -533,6 +580,15 @@ WHERE id = $20"#,
                         self.force_mfa,
                         self.client_uri.clone(),
                         self.contacts.clone(),
+                        self.allowed_providers.clone(),
+                        self.allow_password_login,
+                        self.passkey_only,
+                        self.auto_redirect_provider.clone(),
+                        self.access_roles.clone(),
+                        self.access_groups.clone(),
+                        self.access_email_domains.clone(),
+                        self.geo_allow_countries.clone(),
+                        self.geo_deny_countries.clone(),
                         self.id.clone()
                     ),
                 )
@@ -544,8 +600,11 @@ UPDATE clients
 SET name = $1, enabled = $2, confidential = $3, secret = $4, secret_kid = $5, redirect_uris = $6,
 post_logout_redirect_uris = $7, allowed_origins = $8, flows_enabled = $9, access_token_alg = $10,
 id_token_alg = $11, auth_code_lifetime = $12, access_token_lifetime = $13, scopes = $14,
-default_scopes = $15, challenge = $16, force_mfa= $17, client_uri = $18, contacts = $19
-WHERE id = $20"#,
+default_scopes = $15, challenge = $16, force_mfa= $17, client_uri = $18, contacts = $19,
+allowed_providers = $20, allow_password_login = $21, passkey_only = $22,
+auto_redirect_provider = $23, access_roles = $24, access_groups = $25, access_email_domains = $26,
+geo_allow_countries = $27, geo_deny_countries = $28
+WHERE id = $29"#,
                 self.name,
                 self.enabled,
                 self.confidential,
@@ -565,6 +624,15 @@ WHERE id = $20"#,
                 self.force_mfa,
                 self.client_uri,
                 self.contacts,
+                self.allowed_providers,
+                self.allow_password_login,
+                self.passkey_only,
+                self.auto_redirect_provider,
+                self.access_roles,
+                self.access_groups,
+                self.access_email_domains,
+                self.geo_allow_countries,
+                self.geo_deny_countries,
                 self.id,
             )
             .execute(DB::conn())
@@ -600,6 +668,15 @@ WHERE id = $20"#,
         // we need to keep some old and possibly user-modified values
         new_client.id = current.id;
         new_client.force_mfa = current.force_mfa;
+        new_client.allowed_providers = current.allowed_providers;
+        new_client.allow_password_login = current.allow_password_login;
+        new_client.passkey_only = current.passkey_only;
+        new_client.auto_redirect_provider = current.auto_redirect_provider;
+        new_client.access_roles = current.access_roles;
+        new_client.access_groups = current.access_groups;
+        new_client.access_email_domains = current.access_email_domains;
+        new_client.geo_allow_countries = current.geo_allow_countries;
+        new_client.geo_deny_countries = current.geo_deny_countries;
         new_client.scopes = current.scopes;
         new_client.default_scopes = current.default_scopes;
 
@@ -755,6 +832,15 @@ impl Client {
         Some(res)
     }
 
+    pub fn get_allowed_providers(&self) -> Option<Vec<String>> {
+        self.allowed_providers.as_ref().map(|ids| {
+            ids.split(',')
+                .filter(|id| !id.is_empty())
+                .map(String::from)
+                .collect()
+        })
+    }
+
     pub fn get_contacts(&self) -> Option<Vec<String>> {
         if let Some(contacts) = &self.contacts {
             let mut res = Vec::new();
@@ -914,6 +1000,224 @@ impl Client {
         }
     }
 
+    /// Validates if a local login with a password is allowed for this client. This is not the
+    /// case if either `allow_password_login` is disabled, or `passkey_only` is set.
+    ///
+    /// Just like with `force_mfa`, the "rauthy" client is the exception to prevent lockouts.
+    pub fn validate_password_login(&self) -> Result<(), ErrorResponse> {
+        if &self.id == "rauthy" || (self.allow_password_login && !self.passkey_only) {
+            Ok(())
+        } else {
+            trace!("Password login is not allowed for this client");
+            Err(ErrorResponse::new(
+                ErrorResponseType::Forbidden,
+                "Password logins are not allowed for this client",
+            ))
+        }
+    }
+
+    /// Validates the access policy and the GeoIP policy of this client for a login.
+    ///
+    /// This must only be called after the user has been authenticated, to never reveal any
+    /// policy decisions or create events for unauthenticated requests.
+    pub async fn validate_login_access(
+        &self,
+        data: &web::Data<AppState>,
+        user: &User,
+        ip: Option<IpAddr>,
+    ) -> Result<(), ErrorResponse> {
+        self.validate_user_access(data, user, ip.map(|ip| ip.to_string()))
+            .await?;
+        self.validate_geo_access(data, user, ip).await
+    }
+
+    /// Validates the access policy of this client for the given user and sends out a
+    /// `ClientAccessDenied` event on failure.
+    pub async fn validate_user_access(
+        &self,
+        data: &web::Data<AppState>,
+        user: &User,
+        ip: Option<String>,
+    ) -> Result<(), ErrorResponse> {
+        if self.has_user_access(user) {
+            return Ok(());
+        }
+
+        debug!(
+            "User {} has no access to client {} because of the access policy",
+            user.email, self.id
+        );
+        data.tx_events
+            .send_async(Event::client_access_denied(&self.id, &user.email, ip))
+            .await
+            .unwrap();
+
+        Err(ErrorResponse::new(
+            ErrorResponseType::AccessDenied,
+            "The user has no access to this client",
+        ))
+    }
+
+    /// Validates the global and client specific GeoIP country policies for a login from the
+    /// given IP. Successful logins will be tracked for the impossible travel detection.
+    pub async fn validate_geo_access(
+        &self,
+        data: &web::Data<AppState>,
+        user: &User,
+        ip: Option<IpAddr>,
+    ) -> Result<(), ErrorResponse> {
+        if !geoip::is_enabled() {
+            return Ok(());
+        }
+        let Some(ip) = ip else {
+            return Ok(());
+        };
+
+        let location = geoip::lookup(ip);
+        let country = location.as_ref().and_then(|l| l.country.as_deref());
+        if !self.is_country_allowed(country) {
+            debug!(
+                "User {} has no access to client {} from country {:?}",
+                user.email, self.id, country
+            );
+            data.tx_events
+                .send_async(Event::client_geo_access_denied(
+                    &self.id,
+                    &user.email,
+                    country,
+                    Some(ip.to_string()),
+                ))
+                .await
+                .unwrap();
+
+            return Err(ErrorResponse::new(
+                ErrorResponseType::AccessDenied,
+                "Logins from this location are not allowed",
+            ));
+        }
+
+        geoip::on_login(data, &user.id, &user.email, ip).await;
+        Ok(())
+    }
+
+    /// The global policy always applies, even for the `rauthy` client.
+    fn is_country_allowed(&self, country: Option<&str>) -> bool {
+        if !geoip::is_country_allowed(country, &GEOIP_COUNTRY_ALLOW, &GEOIP_COUNTRY_DENY) {
+            return false;
+        }
+
+        let allow = self
+            .geo_allow_countries
+            .as_deref()
+            .map(|c| c.split(',').collect::<Vec<_>>())
+            .unwrap_or_default();
+        let deny = self
+            .geo_deny_countries
+            .as_deref()
+            .map(|c| c.split(',').collect::<Vec<_>>())
+            .unwrap_or_default();
+        geoip::is_country_allowed(country, &allow, &deny)
+    }
+
+    /// Converts a denied access into an error, which contains the location for redirecting
+    /// back to the client with an `access_denied` error, like RFC6749 4.1.2.1 describes.
+    /// The `redirect_uri` must have been validated before.
+    pub fn access_denied_redirect(
+        err: ErrorResponse,
+        redirect_uri: &str,
+        state: Option<&str>,
+    ) -> ErrorResponse {
+        if err.error != ErrorResponseType::AccessDenied {
+            return err;
+        }
+
+        let Ok(mut loc) = Url::parse(redirect_uri) else {
+            return err;
+        };
+        {
+            // keeps any existing query params and takes care of the encoding
+            let mut query = loc.query_pairs_mut();
+            query.append_pair("error", "access_denied");
+            if let Some(state) = state {
+                query.append_pair("state", state);
+            }
+        }
+        ErrorResponse::new(ErrorResponseType::AccessDenied, loc.to_string())
+    }
+
+    /// If roles and groups are both set, a user needs to match at least one of them.
+    /// The E-Mail domain must always match, if any are configured.
+    fn has_user_access(&self, user: &User) -> bool {
+        if &self.id == "rauthy" {
+            return true;
+        }
+
+        if self.access_roles.is_some() || self.access_groups.is_some() {
+            let user_roles = user.get_roles();
+            let user_groups = user.get_groups();
+            let role_match = self
+                .access_roles
+                .as_deref()
+                .map(|roles| {
+                    roles
+                        .split(',')
+                        .any(|r| user_roles.iter().any(|ur| ur == r))
+                })
+                .unwrap_or(false);
+            let group_match = self
+                .access_groups
+                .as_deref()
+                .map(|groups| {
+                    groups
+                        .split(',')
+                        .any(|g| user_groups.iter().any(|ug| ug == g))
+                })
+                .unwrap_or(false);
+
+            if !role_match && !group_match {
+                return false;
+            }
+        }
+
+        if let Some(domains) = &self.access_email_domains {
+            let user_domain = user
+                .email
+                .rsplit_once('@')
+                .map(|(_, d)| d)
+                .unwrap_or_default();
+            if !domains.split(',').any(|d| d == user_domain) {
+                return false;
+            }
+        }
+
+        true
+    }
+
+    /// Validates, that the given upstream auth provider may be used to log in to this client.
+    pub fn validate_provider_login(&self, provider_id: &str) -> Result<(), ErrorResponse> {
+        if self.is_provider_allowed(provider_id) {
+            Ok(())
+        } else {
+            Err(ErrorResponse::new(
+                ErrorResponseType::Forbidden,
+                "This auth provider is not allowed for this client",
+            ))
+        }
+    }
+
+    pub fn is_provider_allowed(&self, provider_id: &str) -> bool {
+        if &self.id == "rauthy" {
+            return true;
+        }
+        if self.passkey_only {
+            return false;
+        }
+        match &self.allowed_providers {
+            None => true,
+            Some(ids) => ids.split(',').any(|id| id == provider_id),
+        }
+    }
+
     // Validates the `Origin` HTTP Header from an incoming request and compares it to the
     // `allowed_origins`. If the Origin is an external one and allowed by the config, it returns
     // the correct `ACCESS_CONTROL_ALLOW_ORIGIN` header which can then be inserted into the
@@ -1180,6 +1484,7 @@ impl From<Client> for ClientResponse {
         let default_scopes = client.get_default_scopes();
         let challenges = client.get_challenges();
         let contacts = client.get_contacts();
+        let allowed_providers = client.get_allowed_providers();
 
         let access_token_alg = JwkKeyPairAlg::from_str(&client.access_token_alg)
             .expect("internal JwkKeyPairAlg conversion to always succeed")
@@ -1207,6 +1512,25 @@ impl From<Client> for ClientResponse {
             force_mfa: client.force_mfa,
             client_uri: client.client_uri,
             contacts,
+            allowed_providers,
+            allow_password_login: client.allow_password_login,
+            passkey_only: client.passkey_only,
+            auto_redirect_provider: client.auto_redirect_provider,
+            access_roles: client
+                .access_roles
+                .map(|v| v.split(',').map(String::from).collect()),
+            access_groups: client
+                .access_groups
+                .map(|v| v.split(',').map(String::from).collect()),
+            access_email_domains: client
+                .access_email_domains
+                .map(|v| v.split(',').map(String::from).collect()),
+            geo_allow_countries: client
+                .geo_allow_countries
+                .map(|v| v.split(',').map(String::from).collect()),
+            geo_deny_countries: client
+                .geo_deny_countries
+                .map(|v| v.split(',').map(String::from).collect()),
         }
     }
 }
@@ -1242,6 +1566,15 @@ impl From<EphemeralClientRequest> for Client {
             force_mfa: *EPHEMERAL_CLIENTS_FORCE_MFA,
             client_uri: value.client_uri,
             contacts: value.contacts.map(|c| c.join(",")),
+            allowed_providers: None,
+            allow_password_login: true,
+            passkey_only: false,
+            auto_redirect_provider: None,
+            access_roles: None,
+            access_groups: None,
+            access_email_domains: None,
+            geo_allow_countries: None,
+            geo_deny_countries: None,
         }
     }
 }
@@ -1279,6 +1612,15 @@ impl Default for Client {
             force_mfa: false,
             client_uri: None,
             contacts: None,
+            allowed_providers: None,
+            allow_password_login: true,
+            passkey_only: false,
+            auto_redirect_provider: None,
+            access_roles: None,
+            access_groups: None,
+            access_email_domains: None,
+            geo_allow_countries: None,
+            geo_deny_countries: None,
         }
     }
 }
@@ -1483,6 +1825,15 @@ mod tests {
             force_mfa: false,
             client_uri: Some("http://localhost:1337".to_string()),
             contacts: Some("batman@localhost.de,@alfred:matrix.org".to_string()),
+            allowed_providers: None,
+            allow_password_login: true,
+            passkey_only: false,
+            auto_redirect_provider: None,
+            access_roles: None,
+            access_groups: None,
+            access_email_domains: None,
+            geo_allow_countries: None,
+            geo_deny_countries: None,
         };
 
         assert_eq!(client.get_access_token_alg().unwrap(), JwkKeyPairAlg::EdDSA);
@@ -1685,6 +2036,81 @@ mod tests {
         assert_eq!(is_ext, false);
     }
 
+    #[test]
+    fn test_has_user_access() {
+        let mut user = User {
+            email: "alfred@batcave.io".to_string(),
+            roles: "user,butler".to_string(),
+            groups: Some("wayne".to_string()),
+            ..Default::default()
+        };
+        let mut client = Client {
+            id: "batcomputer".to_string(),
+            ..Default::default()
+        };
+        assert!(client.has_user_access(&user));
+
+        client.access_roles = Some("admin".to_string());
+        assert!(!client.has_user_access(&user));
+
+        // a single role or group match is enough
+        client.access_groups = Some("wayne,gotham".to_string());
+        assert!(client.has_user_access(&user));
+        client.access_groups = None;
+        client.access_roles = Some("admin,butler".to_string());
+        assert!(client.has_user_access(&user));
+
+        // the E-Mail domain must always match in addition
+        client.access_email_domains = Some("wayne.corp".to_string());
+        assert!(!client.has_user_access(&user));
+        user.email = "alfred@wayne.corp".to_string();
+        assert!(client.has_user_access(&user));
+
+        // the rauthy client is always accessible
+        user.roles = String::default();
+        assert!(!client.has_user_access(&user));
+        client.id = "rauthy".to_string();
+        assert!(client.has_user_access(&user));
+    }
+
+    #[test]
+    fn test_is_country_allowed() {
+        let mut client = Client {
+            id: "batcomputer".to_string(),
+            ..Default::default()
+        };
+        assert!(client.is_country_allowed(Some("US")));
+        assert!(client.is_country_allowed(None));
+
+        client.geo_allow_countries = Some("US,CA".to_string());
+        assert!(client.is_country_allowed(Some("CA")));
+        assert!(!client.is_country_allowed(Some("DE")));
+
+        client.geo_deny_countries = Some("CA".to_string());
+        assert!(!client.is_country_allowed(Some("CA")));
+        assert!(client.is_country_allowed(Some("US")));
+    }
+
+    #[test]
+    fn test_access_denied_redirect() {
+        let err = ErrorResponse::new(ErrorResponseType::AccessDenied, "denied");
+        let res = Client::access_denied_redirect(
+            err,
+            "https://app.example.com/cb?tenant=1",
+            Some("a b&c=d"),
+        );
+        assert_eq!(res.error, ErrorResponseType::AccessDenied);
+        assert_eq!(
+            res.message,
+            "https://app.example.com/cb?tenant=1&error=access_denied&state=a+b%26c%3Dd"
+        );
+
+        let err = ErrorResponse::new(ErrorResponseType::Forbidden, "forbidden");
+        let res = Client::access_denied_redirect(err, "https://app.example.com/cb", None);
+        assert_eq!(res.error, ErrorResponseType::Forbidden);
+        assert_eq!(res.message, "forbidden");
+    }
+
     #[test]
     fn test_from_ephemeral_client() {
         let example_client_res_resp = r#"{
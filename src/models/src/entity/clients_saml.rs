@@ -0,0 +1,343 @@
+use crate::database::{Cache, DB};
+use hiqlite::{params, Param};
+use rauthy_api_types::saml::{
+    SamlAttrMapping, SamlAttrSource, SamlClientRequest, SamlClientResponse, SamlNameIdFormat,
+};
+use rauthy_common::constants::{
+    CACHE_TTL_APP, IDX_CLIENT_SAML, IDX_SAML_REQ, IDX_SAML_SESSION_INDEX,
+};
+use rauthy_common::is_hiqlite;
+use rauthy_error::{ErrorResponse, ErrorResponseType};
+use serde::{Deserialize, Serialize};
+use sqlx::{query, query_as, FromRow};
+use std::str::FromStr;
+
+/// How long a SAML login may take, until the user has authenticated.
+const SAML_AUTH_REQ_LIFETIME: i64 = 900;
+
+/// The Service Provider config for a client, when Rauthy acts as a SAML 2.0 IdP for it.
+/// The `id` is the `client_id` of the corresponding `Client`.
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct ClientSaml {
+    pub id: String,
+    pub entity_id: String,
+    pub acs_url: String,
+    pub slo_url: Option<String>,
+    pub name_id_format: String,
+    /// The SPs signing certificate in PEM format
+    pub sp_cert: Option<String>,
+    /// JSON serialized `Vec<SamlAttrMapping>`
+    pub attr_mapping: Option<String>,
+}
+
+// CRUD
+impl ClientSaml {
+    pub async fn delete(id: String) -> Result<(), ErrorResponse> {
+        if is_hiqlite() {
+            DB::client()
+                .execute(
+                    "DELETE FROM clients_saml WHERE id = $1",
+                    params!(id.clone()),
+                )
+                .await?;
+        } else {
+            query!("DELETE FROM clients_saml WHERE id = $1", id)
+                .execute(DB::conn())
+                .await?;
+        }
+
+        Self::delete_from_cache(&id).await
+    }
+
+    /// This only deletes a `ClientSaml` from the cache.
+    /// The deletion at database level happens via the foreign key cascade.
+    pub async fn delete_from_cache(id: &str) -> Result<(), ErrorResponse> {
+        DB::client().delete(Cache::App, Self::cache_idx(id)).await?;
+        Ok(())
+    }
+
+    pub async fn find(id: String) -> Result<Self, ErrorResponse> {
+        let client = DB::client();
+        if let Some(slf) = client.get(Cache::App, Self::cache_idx(&id)).await? {
+            return Ok(slf);
+        }
+
+        let slf = if is_hiqlite() {
+            client
+                .query_as_one("SELECT * FROM clients_saml WHERE id = $1", params!(id))
+                .await?
+        } else {
+            query_as!(Self, "SELECT * FROM clients_saml WHERE id = $1", id)
+                .fetch_one(DB::conn())
+                .await?
+        };
+
+        client
+            .put(Cache::App, Self::cache_idx(&slf.id), &slf, CACHE_TTL_APP)
+            .await?;
+
+        Ok(slf)
+    }
+
+    pub async fn find_by_entity_id(entity_id: String) -> Result<Self, ErrorResponse> {
+        let id: Option<String> = if is_hiqlite() {
+            DB::client()
+                .query_as_optional::<Self, _>(
+                    "SELECT * FROM clients_saml WHERE entity_id = $1",
+                    params!(entity_id),
+                )
+                .await?
+                .map(|slf| slf.id)
+        } else {
+            query!(
+                "SELECT id FROM clients_saml WHERE entity_id = $1",
+                entity_id
+            )
+            .fetch_optional(DB::conn())
+            .await?
+            .map(|row| row.id)
+        };
+
+        match id {
+            Some(id) => Self::find(id).await,
+            None => Err(ErrorResponse::new(
+                ErrorResponseType::NotFound,
+                "Unknown SAML Service Provider",
+            )),
+        }
+    }
+
+    pub async fn upsert(&self) -> Result<(), ErrorResponse> {
+        if is_hiqlite() {
+            DB::client()
+                .execute(
+                    r#"
+INSERT INTO clients_saml
+(id, entity_id, acs_url, slo_url, name_id_format, sp_cert, attr_mapping)
+VALUES ($1, $2, $3, $4, $5, $6, $7)
+ON CONFLICT(id) DO UPDATE
+SET entity_id = $2, acs_url = $3, slo_url = $4, name_id_format = $5, sp_cert = $6,
+attr_mapping = $7"#,
+                    params!(
+                        self.id.clone(),
+                        self.entity_id.clone(),
+                        self.acs_url.clone(),
+                        self.slo_url.clone(),
+                        self.name_id_format.clone(),
+                        self.sp_cert.clone(),
+                        self.attr_mapping.clone()
+                    ),
+                )
+                .await?;
+        } else {
+            query!(
+                r#"
+INSERT INTO clients_saml
+(id, entity_id, acs_url, slo_url, name_id_format, sp_cert, attr_mapping)
+VALUES ($1, $2, $3, $4, $5, $6, $7)
+ON CONFLICT(id) DO UPDATE
+SET entity_id = $2, acs_url = $3, slo_url = $4, name_id_format = $5, sp_cert = $6,
+attr_mapping = $7"#,
+                self.id,
+                self.entity_id,
+                self.acs_url,
+                self.slo_url,
+                self.name_id_format,
+                self.sp_cert,
+                self.attr_mapping,
+            )
+            .execute(DB::conn())
+            .await?;
+        }
+
+        DB::client()
+            .put(Cache::App, Self::cache_idx(&self.id), self, CACHE_TTL_APP)
+            .await?;
+
+        Ok(())
+    }
+}
+
+impl ClientSaml {
+    #[inline]
+    fn cache_idx(id: &str) -> String {
+        format!("{}{}", IDX_CLIENT_SAML, id)
+    }
+
+    pub fn from_request(id: String, req: SamlClientRequest) -> Result<Self, ErrorResponse> {
+        if let Some(mappings) = &req.attr_mapping {
+            for mapping in mappings {
+                if mapping.source == SamlAttrSource::Attribute && mapping.attr_key.is_none() {
+                    return Err(ErrorResponse::new(
+                        ErrorResponseType::BadRequest,
+                        format!("SAML attribute '{}' is missing the attr_key", mapping.name),
+                    ));
+                }
+            }
+        }
+
+        let attr_mapping = match req.attr_mapping {
+            None => None,
+            Some(mappings) if mappings.is_empty() => None,
+            Some(mappings) => Some(serde_json::to_string(&mappings)?),
+        };
+
+        Ok(Self {
+            id,
+            entity_id: req.entity_id,
+            acs_url: req.acs_url,
+            slo_url: req.slo_url,
+            name_id_format: req.name_id_format.as_str().to_string(),
+            sp_cert: req.sp_cert,
+            attr_mapping,
+        })
+    }
+
+    pub fn get_attr_mappings(&self) -> Vec<SamlAttrMapping> {
+        if let Some(mapping) = &self.attr_mapping {
+            if let Ok(mappings) = serde_json::from_str(mapping) {
+                return mappings;
+            }
+        }
+
+        [
+            ("email", SamlAttrSource::Email),
+            ("given_name", SamlAttrSource::GivenName),
+            ("family_name", SamlAttrSource::FamilyName),
+            ("roles", SamlAttrSource::Roles),
+            ("groups", SamlAttrSource::Groups),
+        ]
+        .into_iter()
+        .map(|(name, source)| SamlAttrMapping {
+            name: name.to_string(),
+            source,
+            attr_key: None,
+        })
+        .collect()
+    }
+
+    pub fn get_name_id_format(&self) -> SamlNameIdFormat {
+        SamlNameIdFormat::from_str(&self.name_id_format).unwrap_or_default()
+    }
+
+    pub fn into_response(self, resume_uri: String) -> SamlClientResponse {
+        let attr_mapping = self.get_attr_mappings();
+        let name_id_format = self.get_name_id_format();
+        SamlClientResponse {
+            id: self.id,
+            entity_id: self.entity_id,
+            acs_url: self.acs_url,
+            slo_url: self.slo_url,
+            name_id_format,
+            sp_cert: self.sp_cert,
+            attr_mapping,
+            resume_uri,
+        }
+    }
+}
+
+/// A pending SAML login, while the user authenticates via the `/authorize` flow.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SamlAuthReq {
+    pub id: String,
+    pub client_id: String,
+    /// The `ID` of the `AuthnRequest` or `None` for IdP-initiated logins
+    pub request_id: Option<String>,
+    pub relay_state: Option<String>,
+}
+
+impl SamlAuthReq {
+    pub async fn delete(&self) -> Result<(), ErrorResponse> {
+        DB::client()
+            .delete(Cache::App, Self::cache_idx(&self.id))
+            .await?;
+        Ok(())
+    }
+
+    pub async fn find(id: &str) -> Result<Self, ErrorResponse> {
+        DB::client()
+            .get(Cache::App, Self::cache_idx(id))
+            .await?
+            .ok_or_else(|| {
+                ErrorResponse::new(
+                    ErrorResponseType::NotFound,
+                    "SAML login request not found or expired",
+                )
+            })
+    }
+
+    pub async fn save(&self) -> Result<(), ErrorResponse> {
+        DB::client()
+            .put(
+                Cache::App,
+                Self::cache_idx(&self.id),
+                self,
+                Some(SAML_AUTH_REQ_LIFETIME),
+            )
+            .await?;
+        Ok(())
+    }
+
+    #[inline]
+    fn cache_idx(id: &str) -> String {
+        format!("{}{}", IDX_SAML_REQ, id)
+    }
+}
+
+/// Maps the `SessionIndex` inside issued assertions back to the Rauthy `Session`, which makes
+/// Single Logout possible even if the browser does not send the session cookie cross-site.
+pub struct SamlSessionIndex;
+
+impl SamlSessionIndex {
+    /// The `SessionIndex` is derived from the session id without exposing it. It is bound to
+    /// the SP, so each SP gets a different value for the same session.
+    pub fn build(session_id: &str, sp_entity_id: &str) -> String {
+        let input = format!("{}\n{}", session_id, sp_entity_id);
+        hex::encode(openssl::sha::sha256(input.as_bytes()))
+    }
+
+    /// Returns the session id only if the `SessionIndex` has been issued to the given SP.
+    pub async fn find_session_id(
+        session_index: &str,
+        sp_entity_id: &str,
+    ) -> Result<Option<String>, ErrorResponse> {
+        let idx = format!("{}{}", IDX_SAML_SESSION_INDEX, session_index);
+        let session_id: Option<String> = DB::client().get(Cache::App, idx).await?;
+        Ok(session_id.filter(|sid| Self::build(sid, sp_entity_id) == session_index))
+    }
+
+    pub async fn save(
+        session_index: &str,
+        session_id: &str,
+        ttl: i64,
+    ) -> Result<(), ErrorResponse> {
+        let idx = format!("{}{}", IDX_SAML_SESSION_INDEX, session_index);
+        DB::client()
+            .put(Cache::App, idx, &session_id.to_string(), Some(ttl))
+            .await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_session_index_bound_to_sp() {
+        let idx = SamlSessionIndex::build("sid123", "https://sp1.example.com");
+        assert_eq!(
+            idx,
+            SamlSessionIndex::build("sid123", "https://sp1.example.com")
+        );
+        assert_ne!(
+            idx,
+            SamlSessionIndex::build("sid123", "https://sp2.example.com")
+        );
+        assert_ne!(
+            idx,
+            SamlSessionIndex::build("sid456", "https://sp1.example.com")
+        );
+        assert_ne!(idx, hex::encode(openssl::sha::sha256(b"sid123")));
+    }
+}
@@ -1,7 +1,9 @@
 use crate::database::{Cache, DB};
 use crate::entity::refresh_tokens_devices::RefreshTokenDevice;
+use crate::geoip;
 use chrono::{DateTime, Utc};
 use hiqlite::{params, Param};
+use rauthy_api_types::generic::GeoLocationResponse;
 use rauthy_api_types::users::DeviceResponse;
 use rauthy_common::constants::{
     CACHE_TTL_DEVICE_CODE, DEVICE_GRANT_CODE_LIFETIME, DEVICE_GRANT_USER_CODE_LENGTH,
@@ -201,6 +203,7 @@ impl From<DeviceEntity> for DeviceResponse {
             created: value.created,
             access_exp: value.access_exp,
             refresh_exp: value.refresh_exp,
+            peer_geo: geoip::lookup_str(Some(&value.peer_ip)).map(GeoLocationResponse::from),
             peer_ip: value.peer_ip,
             name: value.name,
         }
@@ -1,4 +1,5 @@
 use crate::database::{Cache, DB};
+use crate::entity::password_policies::ScopedPasswordPolicyEntity;
 use crate::entity::users::User;
 use hiqlite::{params, Param, Params};
 use rauthy_api_types::groups::NewGroupRequest;
@@ -200,6 +201,8 @@ impl Group {
             txn.commit().await?;
         }
 
+        ScopedPasswordPolicyEntity::rename_group(&group.name, &new_group.name).await?;
+
         let groups = Group::find_all()
             .await?
             .into_iter()
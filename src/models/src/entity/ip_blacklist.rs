@@ -0,0 +1,284 @@
+use crate::database::{Cache, DB};
+use chrono::Utc;
+use cidr::IpCidr;
+use hiqlite::{params, Param};
+use rauthy_common::constants::IDX_IP_BLACKLIST_VERSION;
+use rauthy_common::is_hiqlite;
+use rauthy_error::{ErrorResponse, ErrorResponseType};
+use serde::{Deserialize, Serialize};
+use sqlx::{query, query_as, FromRow};
+use std::str::FromStr;
+
+/// Parses a single IP or a CIDR range, IPv4 and IPv6.
+pub fn parse_cidr(value: &str) -> Result<IpCidr, ErrorResponse> {
+    IpCidr::from_str(value.trim()).map_err(|_| {
+        ErrorResponse::new(
+            ErrorResponseType::BadRequest,
+            format!("'{}' is not a valid IP address or CIDR range", value),
+        )
+    })
+}
+
+/// Single addresses are stored as plain IPs, which makes them match the IPs inside events
+/// and keeps the lookup for the by far most common case a simple `HashMap` access.
+pub fn cidr_to_string(cidr: &IpCidr) -> String {
+    if cidr.is_host_address() {
+        cidr.first_address().to_string()
+    } else {
+        format!("{}/{}", cidr.first_address(), cidr.network_length())
+    }
+}
+
+/// The blacklist and allowlist are cached in memory on each node. Every modification bumps
+/// this version, which makes all nodes reload both lists from the database.
+async fn bump_version() -> Result<(), ErrorResponse> {
+    DB::client()
+        .put(
+            Cache::App,
+            IDX_IP_BLACKLIST_VERSION,
+            &Utc::now().timestamp_micros(),
+            Some(i64::MAX),
+        )
+        .await?;
+    Ok(())
+}
+
+pub async fn lists_version() -> Result<Option<i64>, ErrorResponse> {
+    Ok(DB::client()
+        .get(Cache::App, IDX_IP_BLACKLIST_VERSION)
+        .await?)
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct IpBlacklistEntry {
+    pub cidr: String,
+    /// `None` for permanent entries
+    pub exp: Option<i64>,
+    pub reason: Option<String>,
+    pub created_at: i64,
+}
+
+impl IpBlacklistEntry {
+    /// Returns all entries, which are not expired yet.
+    pub async fn find_all() -> Result<Vec<Self>, ErrorResponse> {
+        let now = Utc::now().timestamp();
+
+        let res = if is_hiqlite() {
+            DB::client()
+                .query_as(
+                    "SELECT * FROM ip_blacklist WHERE exp IS NULL OR exp > $1",
+                    params!(now),
+                )
+                .await?
+        } else {
+            query_as!(
+                Self,
+                "SELECT * FROM ip_blacklist WHERE exp IS NULL OR exp > $1",
+                now
+            )
+            .fetch_all(DB::conn())
+            .await?
+        };
+
+        Ok(res)
+    }
+
+    /// Inserts or updates an entry. IPs and ranges, which are covered by the allowlist,
+    /// will be ignored and `Ok(false)` is returned in that case.
+    pub async fn upsert(
+        ip_or_cidr: &str,
+        exp: Option<i64>,
+        reason: Option<String>,
+    ) -> Result<bool, ErrorResponse> {
+        let cidr = parse_cidr(ip_or_cidr)?;
+        if IpAllowlistEntry::is_allowed(&cidr).await? {
+            return Ok(false);
+        }
+
+        let cidr = cidr_to_string(&cidr);
+        let created_at = Utc::now().timestamp();
+
+        if is_hiqlite() {
+            DB::client()
+                .execute(
+                    r#"
+INSERT INTO ip_blacklist (cidr, exp, reason, created_at)
+VALUES ($1, $2, $3, $4)
+ON CONFLICT(cidr) DO UPDATE
+SET exp = $2, reason = $3"#,
+                    params!(cidr, exp, reason, created_at),
+                )
+                .await?;
+        } else {
+            query!(
+                r#"
+INSERT INTO ip_blacklist (cidr, exp, reason, created_at)
+VALUES ($1, $2, $3, $4)
+ON CONFLICT(cidr) DO UPDATE
+SET exp = $2, reason = $3"#,
+                cidr,
+                exp,
+                reason,
+                created_at,
+            )
+            .execute(DB::conn())
+            .await?;
+        }
+
+        bump_version().await?;
+        Ok(true)
+    }
+
+    pub async fn delete(ip_or_cidr: &str) -> Result<(), ErrorResponse> {
+        let cidr = cidr_to_string(&parse_cidr(ip_or_cidr)?);
+
+        if is_hiqlite() {
+            DB::client()
+                .execute("DELETE FROM ip_blacklist WHERE cidr = $1", params!(cidr))
+                .await?;
+        } else {
+            query!("DELETE FROM ip_blacklist WHERE cidr = $1", cidr)
+                .execute(DB::conn())
+                .await?;
+        }
+
+        bump_version().await?;
+        Ok(())
+    }
+
+    /// Cleans up expired entries. Each node removes them from memory on its own, so there is
+    /// no need to bump the version here.
+    pub async fn delete_expired() -> Result<usize, ErrorResponse> {
+        let now = Utc::now().timestamp();
+
+        let rows_affected = if is_hiqlite() {
+            DB::client()
+                .execute("DELETE FROM ip_blacklist WHERE exp < $1", params!(now))
+                .await?
+        } else {
+            query!("DELETE FROM ip_blacklist WHERE exp < $1", now)
+                .execute(DB::conn())
+                .await?
+                .rows_affected() as usize
+        };
+
+        Ok(rows_affected)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct IpAllowlistEntry {
+    pub cidr: String,
+    pub description: Option<String>,
+    pub created_at: i64,
+}
+
+impl IpAllowlistEntry {
+    pub async fn find_all() -> Result<Vec<Self>, ErrorResponse> {
+        let res = if is_hiqlite() {
+            DB::client()
+                .query_as("SELECT * FROM ip_allowlist", params!())
+                .await?
+        } else {
+            query_as!(Self, "SELECT * FROM ip_allowlist")
+                .fetch_all(DB::conn())
+                .await?
+        };
+
+        Ok(res)
+    }
+
+    /// Checks if the given IP or range is fully covered by any entry on the allowlist.
+    pub async fn is_allowed(cidr: &IpCidr) -> Result<bool, ErrorResponse> {
+        Ok(Self::find_all()
+            .await?
+            .iter()
+            .filter_map(|entry| parse_cidr(&entry.cidr).ok())
+            .any(|allowed| {
+                allowed.contains(&cidr.first_address()) && allowed.contains(&cidr.last_address())
+            }))
+    }
+
+    pub async fn upsert(
+        ip_or_cidr: &str,
+        description: Option<String>,
+    ) -> Result<(), ErrorResponse> {
+        let cidr = cidr_to_string(&parse_cidr(ip_or_cidr)?);
+        let created_at = Utc::now().timestamp();
+
+        if is_hiqlite() {
+            DB::client()
+                .execute(
+                    r#"
+INSERT INTO ip_allowlist (cidr, description, created_at)
+VALUES ($1, $2, $3)
+ON CONFLICT(cidr) DO UPDATE
+SET description = $2"#,
+                    params!(cidr, description, created_at),
+                )
+                .await?;
+        } else {
+            query!(
+                r#"
+INSERT INTO ip_allowlist (cidr, description, created_at)
+VALUES ($1, $2, $3)
+ON CONFLICT(cidr) DO UPDATE
+SET description = $2"#,
+                cidr,
+                description,
+                created_at,
+            )
+            .execute(DB::conn())
+            .await?;
+        }
+
+        bump_version().await?;
+        Ok(())
+    }
+
+    pub async fn delete(ip_or_cidr: &str) -> Result<(), ErrorResponse> {
+        let cidr = cidr_to_string(&parse_cidr(ip_or_cidr)?);
+
+        if is_hiqlite() {
+            DB::client()
+                .execute("DELETE FROM ip_allowlist WHERE cidr = $1", params!(cidr))
+                .await?;
+        } else {
+            query!("DELETE FROM ip_allowlist WHERE cidr = $1", cidr)
+                .execute(DB::conn())
+                .await?;
+        }
+
+        bump_version().await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_cidr() {
+        let cidr = parse_cidr("192.168.14.1").unwrap();
+        assert!(cidr.is_host_address());
+        assert_eq!(cidr_to_string(&cidr), "192.168.14.1");
+
+        let cidr = parse_cidr("10.0.0.0/8").unwrap();
+        assert_eq!(cidr_to_string(&cidr), "10.0.0.0/8");
+        assert!(cidr.contains(&"10.10.1.1".parse().unwrap()));
+        assert!(!cidr.contains(&"11.0.0.1".parse().unwrap()));
+
+        let cidr = parse_cidr("2001:db8::/32").unwrap();
+        assert_eq!(cidr_to_string(&cidr), "2001:db8::/32");
+        assert!(cidr.contains(&"2001:db8:1::1".parse().unwrap()));
+
+        let cidr = parse_cidr(" ::1 ").unwrap();
+        assert_eq!(cidr_to_string(&cidr), "::1");
+
+        // host bits must not be set
+        assert!(parse_cidr("10.0.0.1/8").is_err());
+        assert!(parse_cidr("10.0.0.0/33").is_err());
+        assert!(parse_cidr("localhost").is_err());
+    }
+}
@@ -1086,6 +1086,7 @@ mod tests {
                 auth_time: None,
                 cnf: None,
                 did: None,
+                org: None,
             },
             coarsetime::Duration::from_secs(300),
         );
@@ -18,6 +18,16 @@ pub enum MagicLinkUsage {
     EmailChange(String),
     PasswordReset(Option<String>),
     NewUser(Option<String>),
+    /// Account setup for an invited user. Contains the `UserInvitation` id.
+    Invitation(String),
+    /// Unlocks an account, which has been locked after too many failed logins.
+    Unlock,
+    /// "This wasn't me" link from a security notification, which logs the user out everywhere
+    /// and starts a password reset.
+    NotMe,
+    /// Forced password change, because the current password does not satisfy the policy of the
+    /// client with the contained id.
+    PasswordChange(String),
 }
 
 impl TryFrom<&String> for MagicLinkUsage {
@@ -35,6 +45,7 @@ impl TryFrom<&str> for MagicLinkUsage {
         let (ty, v) = value.split_once('$').unwrap_or((value, ""));
         let slf = match ty {
             "email_change" => MagicLinkUsage::EmailChange(v.to_string()),
+            "invitation" => MagicLinkUsage::Invitation(v.to_string()),
             "new_user" => {
                 if !v.is_empty() {
                     MagicLinkUsage::NewUser(Some(v.to_string()))
@@ -42,6 +53,9 @@ impl TryFrom<&str> for MagicLinkUsage {
                     MagicLinkUsage::NewUser(None)
                 }
             }
+            "unlock" => MagicLinkUsage::Unlock,
+            "not_me" => MagicLinkUsage::NotMe,
+            "password_change" => MagicLinkUsage::PasswordChange(v.to_string()),
             "password_reset" => {
                 if !v.is_empty() {
                     MagicLinkUsage::PasswordReset(Some(v.to_string()))
@@ -67,6 +81,7 @@ impl Display for MagicLinkUsage {
         // It also makes splitting of the value quite easy.
         match self {
             MagicLinkUsage::EmailChange(email) => write!(f, "email_change${}", email),
+            MagicLinkUsage::Invitation(id) => write!(f, "invitation${}", id),
             MagicLinkUsage::NewUser(redirect_uri) => {
                 if let Some(uri) = redirect_uri {
                     write!(f, "new_user${}", uri)
@@ -81,6 +96,9 @@ impl Display for MagicLinkUsage {
                     write!(f, "password_reset")
                 }
             }
+            MagicLinkUsage::Unlock => write!(f, "unlock"),
+            MagicLinkUsage::NotMe => write!(f, "not_me"),
+            MagicLinkUsage::PasswordChange(client_id) => write!(f, "password_change${}", client_id),
         }
     }
 }
@@ -329,7 +347,11 @@ impl IdxMagicLinkPasswordUser {
 
 #[cfg(test)]
 mod tests {
-    use crate::entity::magic_links::MagicLinkUsage;
+    use crate::entity::magic_links::{MagicLink, MagicLinkUsage};
+    use actix_web::test::TestRequest;
+    use rauthy_common::constants::PWD_CSRF_HEADER;
+    use rauthy_error::ErrorResponseType;
+    use time::OffsetDateTime;
 
     #[test]
     fn test_magic_link_usage_conversions() {
@@ -357,5 +379,70 @@ mod tests {
         let s = ml.to_string();
         let ml_from = MagicLinkUsage::try_from(&s).unwrap();
         assert_eq!(ml, ml_from);
+
+        let ml = MagicLinkUsage::Invitation("4FnSaDYmVwfOyxjCbwHnTxMo".to_string());
+        let s = ml.to_string();
+        assert_eq!(s, "invitation$4FnSaDYmVwfOyxjCbwHnTxMo");
+        let ml_from = MagicLinkUsage::try_from(&s).unwrap();
+        assert_eq!(ml, ml_from);
+
+        let ml = MagicLinkUsage::Unlock;
+        let s = ml.to_string();
+        assert_eq!(s, "unlock");
+        let ml_from = MagicLinkUsage::try_from(&s).unwrap();
+        assert_eq!(ml, ml_from);
+
+        let ml = MagicLinkUsage::NotMe;
+        let s = ml.to_string();
+        assert_eq!(s, "not_me");
+        let ml_from = MagicLinkUsage::try_from(&s).unwrap();
+        assert_eq!(ml, ml_from);
+
+        let ml = MagicLinkUsage::PasswordChange("my_client".to_string());
+        let s = ml.to_string();
+        assert_eq!(s, "password_change$my_client");
+        let ml_from = MagicLinkUsage::try_from(&s).unwrap();
+        assert_eq!(ml, ml_from);
+    }
+
+    #[test]
+    fn test_validate_csrf() {
+        let ml = MagicLink {
+            id: "unlock123".to_string(),
+            user_id: "user123".to_string(),
+            csrf_token: "csrf123".to_string(),
+            cookie: None,
+            exp: OffsetDateTime::now_utc().unix_timestamp() + 60,
+            used: false,
+            usage: MagicLinkUsage::Unlock.to_string(),
+        };
+
+        // the confirmation page can be opened without any CSRF token
+        let req = TestRequest::default().to_http_request();
+        assert!(ml.validate("user123", &req, false).is_ok());
+
+        // the action itself needs the token from the confirmation page
+        let err = ml.validate("user123", &req, true).unwrap_err();
+        assert_eq!(err.error, ErrorResponseType::Unauthorized);
+
+        let req = TestRequest::default()
+            .insert_header((PWD_CSRF_HEADER, "invalid"))
+            .to_http_request();
+        let err = ml.validate("user123", &req, true).unwrap_err();
+        assert_eq!(err.error, ErrorResponseType::Unauthorized);
+
+        let req = TestRequest::default()
+            .insert_header((PWD_CSRF_HEADER, "csrf123"))
+            .to_http_request();
+        assert!(ml.validate("user123", &req, true).is_ok());
+
+        // bound to the user
+        let err = ml.validate("user456", &req, true).unwrap_err();
+        assert_eq!(err.error, ErrorResponseType::BadRequest);
+
+        // and it can only be used once
+        let mut ml = ml;
+        ml.used = true;
+        assert!(ml.validate("user123", &req, true).is_err());
     }
 }
@@ -3,13 +3,19 @@ use hiqlite::params;
 use rauthy_common::is_hiqlite;
 use sqlx::query;
 
+pub mod admin_roles;
 pub mod api_keys;
 pub mod app_version;
 pub mod auth_codes;
 mod auth_provider_cust_impl;
+mod auth_provider_jwks;
+mod auth_provider_mappings;
+mod auth_provider_saml;
+mod auth_provider_userinfo;
 pub mod auth_providers;
 pub mod clients;
 pub mod clients_dyn;
+pub mod clients_saml;
 pub mod colors;
 pub mod config;
 pub mod continuation_token;
@@ -18,20 +24,28 @@ pub mod devices;
 pub mod dpop_proof;
 pub mod fed_cm;
 pub mod groups;
+pub mod ip_blacklist;
 pub mod ip_rate_limit;
 pub mod jwk;
 pub mod jwk_token_validation;
 pub mod logos;
 pub mod magic_links;
+pub mod organizations;
 pub mod password;
+pub mod password_policies;
 pub mod pow;
 pub mod principal;
 pub mod refresh_tokens;
 pub mod refresh_tokens_devices;
 pub mod roles;
+pub mod saml_certs;
 pub mod scopes;
 pub mod sessions;
+pub mod trusted_devices;
 pub mod user_attr;
+pub mod user_federations;
+pub mod user_invitations;
+pub mod user_registrations;
 pub mod users;
 pub mod users_values;
 pub mod webauthn;
@@ -0,0 +1,520 @@
+use crate::database::{Cache, DB};
+use hiqlite::{params, Param};
+use rauthy_api_types::oidc::OrgClaim;
+use rauthy_api_types::organizations::{OrgMemberResponse, OrgRequest, OrgResponse, OrgSelection};
+use rauthy_common::constants::{CACHE_TTL_APP, IDX_ORGANIZATIONS};
+use rauthy_common::is_hiqlite;
+use rauthy_common::utils::new_store_id;
+use rauthy_error::{ErrorResponse, ErrorResponseType};
+use serde::{Deserialize, Serialize};
+use sqlx::{query, query_as, FromRow};
+
+/// An organization (tenant) with its own set of roles, that can be assigned to its members.
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct Organization {
+    pub id: String,
+    pub name: String,
+    pub description: Option<String>,
+    /// Comma separated list of the roles that exist inside this organization
+    pub roles: String,
+}
+
+// CRUD
+impl Organization {
+    pub async fn create(req: OrgRequest) -> Result<Self, ErrorResponse> {
+        let mut orgs = Self::find_all().await?;
+        if orgs.iter().any(|o| o.name == req.name) {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                "An organization with this name already exists",
+            ));
+        }
+
+        let slf = Self {
+            id: new_store_id(),
+            name: req.name,
+            description: req.description,
+            roles: Self::sanitize_roles(req.roles),
+        };
+
+        if is_hiqlite() {
+            DB::client()
+                .execute(
+                    r#"
+INSERT INTO organizations (id, name, description, roles)
+VALUES ($1, $2, $3, $4)"#,
+                    params!(
+                        slf.id.clone(),
+                        slf.name.clone(),
+                        slf.description.clone(),
+                        slf.roles.clone()
+                    ),
+                )
+                .await?;
+        } else {
+            query!(
+                r#"
+INSERT INTO organizations (id, name, description, roles)
+VALUES ($1, $2, $3, $4)"#,
+                slf.id,
+                slf.name,
+                slf.description,
+                slf.roles,
+            )
+            .execute(DB::conn())
+            .await?;
+        }
+
+        orgs.push(slf.clone());
+        DB::client()
+            .put(Cache::App, IDX_ORGANIZATIONS, &orgs, CACHE_TTL_APP)
+            .await?;
+
+        Ok(slf)
+    }
+
+    /// Deletes the organization. All memberships will be removed via the `ON DELETE CASCADE`.
+    pub async fn delete(id: &str) -> Result<(), ErrorResponse> {
+        if is_hiqlite() {
+            DB::client()
+                .execute("DELETE FROM organizations WHERE id = $1", params!(id))
+                .await?;
+        } else {
+            query!("DELETE FROM organizations WHERE id = $1", id)
+                .execute(DB::conn())
+                .await?;
+        }
+
+        DB::client().delete(Cache::App, IDX_ORGANIZATIONS).await?;
+        Ok(())
+    }
+
+    pub async fn find(id: &str) -> Result<Self, ErrorResponse> {
+        Self::find_all()
+            .await?
+            .into_iter()
+            .find(|o| o.id == id)
+            .ok_or_else(|| {
+                ErrorResponse::new(ErrorResponseType::NotFound, "Organization does not exist")
+            })
+    }
+
+    pub async fn find_all() -> Result<Vec<Self>, ErrorResponse> {
+        let client = DB::client();
+        if let Some(slf) = client.get(Cache::App, IDX_ORGANIZATIONS).await? {
+            return Ok(slf);
+        }
+
+        let res = if is_hiqlite() {
+            client
+                .query_as("SELECT * FROM organizations", params!())
+                .await?
+        } else {
+            query_as!(Self, "SELECT * FROM organizations")
+                .fetch_all(DB::conn())
+                .await?
+        };
+
+        client
+            .put(Cache::App, IDX_ORGANIZATIONS, &res, CACHE_TTL_APP)
+            .await?;
+
+        Ok(res)
+    }
+
+    /// Returns all organizations the given user is a member of.
+    pub async fn find_for_user(user_id: &str) -> Result<Vec<Self>, ErrorResponse> {
+        let members = OrgMember::find_for_user(user_id).await?;
+        if members.is_empty() {
+            return Ok(Vec::default());
+        }
+
+        let res = Self::find_all()
+            .await?
+            .into_iter()
+            .filter(|o| members.iter().any(|m| m.org_id == o.id))
+            .collect();
+        Ok(res)
+    }
+
+    pub async fn update(id: &str, req: OrgRequest) -> Result<Self, ErrorResponse> {
+        let orgs = Self::find_all().await?;
+        if orgs.iter().any(|o| o.id != id && o.name == req.name) {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                "An organization with this name already exists",
+            ));
+        }
+        let mut slf = Self::find(id).await?;
+
+        slf.name = req.name;
+        slf.description = req.description;
+        slf.roles = Self::sanitize_roles(req.roles);
+
+        if is_hiqlite() {
+            DB::client()
+                .execute(
+                    r#"
+UPDATE organizations
+SET name = $1, description = $2, roles = $3
+WHERE id = $4"#,
+                    params!(
+                        slf.name.clone(),
+                        slf.description.clone(),
+                        slf.roles.clone(),
+                        slf.id.clone()
+                    ),
+                )
+                .await?;
+        } else {
+            query!(
+                r#"
+UPDATE organizations
+SET name = $1, description = $2, roles = $3
+WHERE id = $4"#,
+                slf.name,
+                slf.description,
+                slf.roles,
+                slf.id,
+            )
+            .execute(DB::conn())
+            .await?;
+        }
+
+        // Roles that have been removed from the organization must not stay assigned to members.
+        // There will never be too many members for a single org to do this in-memory.
+        let roles = slf.get_roles();
+        for mut member in OrgMember::find_for_org(&slf.id).await? {
+            let member_roles = member.get_roles();
+            if member_roles.iter().any(|r| !roles.contains(r)) {
+                member.roles = member_roles
+                    .into_iter()
+                    .filter(|r| roles.contains(r))
+                    .collect::<Vec<_>>()
+                    .join(",");
+                member.save().await?;
+            }
+        }
+
+        DB::client().delete(Cache::App, IDX_ORGANIZATIONS).await?;
+
+        Ok(slf)
+    }
+}
+
+impl Organization {
+    pub fn get_roles(&self) -> Vec<String> {
+        if self.roles.is_empty() {
+            return Vec::default();
+        }
+        self.roles.split(',').map(|r| r.to_string()).collect()
+    }
+
+    /// Builds the `org` claim for the given user, if they are a member of any organization.
+    ///
+    /// If `org_id` is given, the user must be a member of exactly this organization.
+    /// If it is not given, the claim will only be built if the user is a member of a single
+    /// organization, because no decision can be made otherwise.
+    pub async fn claim_for_user(
+        user_id: &str,
+        org_id: Option<&str>,
+    ) -> Result<Option<OrgClaim>, ErrorResponse> {
+        let members = OrgMember::find_for_user(user_id).await?;
+
+        let member = if let Some(org_id) = org_id {
+            members.into_iter().find(|m| m.org_id == org_id)
+        } else if members.len() == 1 {
+            members.into_iter().next()
+        } else {
+            None
+        };
+
+        match member {
+            None => Ok(None),
+            Some(member) => {
+                let org = Self::find(&member.org_id).await?;
+                Ok(Some(OrgClaim {
+                    id: org.id,
+                    name: org.name,
+                    roles: member.get_roles(),
+                }))
+            }
+        }
+    }
+
+    /// Validates the organization selection during the authorization.
+    ///
+    /// Returns `Ok(None)` if the user is not a member of any organization, or the selected
+    /// org id if it could be resolved. Returns an `OrgSelectionRequired` error with the possible
+    /// choices as JSON inside the message, if the user is a member of multiple organizations and
+    /// none has been selected.
+    pub async fn validate_selection(
+        user_id: &str,
+        org_id: Option<String>,
+    ) -> Result<Option<String>, ErrorResponse> {
+        let orgs = Self::find_for_user(user_id).await?;
+
+        if let Some(org_id) = org_id {
+            return if orgs.iter().any(|o| o.id == org_id) {
+                Ok(Some(org_id))
+            } else {
+                Err(ErrorResponse::new(
+                    ErrorResponseType::Forbidden,
+                    "User is not a member of the selected organization",
+                ))
+            };
+        }
+
+        match orgs.len() {
+            0 => Ok(None),
+            1 => Ok(orgs.into_iter().next().map(|o| o.id)),
+            _ => {
+                let selection = orgs
+                    .into_iter()
+                    .map(|o| OrgSelection {
+                        id: o.id,
+                        name: o.name,
+                    })
+                    .collect::<Vec<_>>();
+                Err(ErrorResponse::new(
+                    ErrorResponseType::OrgSelectionRequired,
+                    serde_json::to_string(&selection)?,
+                ))
+            }
+        }
+    }
+
+    fn sanitize_roles(mut roles: Vec<String>) -> String {
+        roles.sort();
+        roles.dedup();
+        roles.join(",")
+    }
+}
+
+impl From<Organization> for OrgResponse {
+    fn from(value: Organization) -> Self {
+        let roles = value.get_roles();
+        Self {
+            id: value.id,
+            name: value.name,
+            description: value.description,
+            roles,
+        }
+    }
+}
+
+/// The membership of a user inside an `Organization`
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct OrgMember {
+    pub org_id: String,
+    pub user_id: String,
+    /// Comma separated list of org roles
+    pub roles: String,
+    pub is_org_admin: bool,
+}
+
+// CRUD
+impl OrgMember {
+    pub async fn delete(org_id: &str, user_id: &str) -> Result<(), ErrorResponse> {
+        if is_hiqlite() {
+            DB::client()
+                .execute(
+                    "DELETE FROM org_members WHERE org_id = $1 AND user_id = $2",
+                    params!(org_id, user_id),
+                )
+                .await?;
+        } else {
+            query!(
+                "DELETE FROM org_members WHERE org_id = $1 AND user_id = $2",
+                org_id,
+                user_id,
+            )
+            .execute(DB::conn())
+            .await?;
+        }
+
+        Ok(())
+    }
+
+    pub async fn find(org_id: &str, user_id: &str) -> Result<Self, ErrorResponse> {
+        let res = if is_hiqlite() {
+            DB::client()
+                .query_as_one(
+                    "SELECT * FROM org_members WHERE org_id = $1 AND user_id = $2",
+                    params!(org_id, user_id),
+                )
+                .await?
+        } else {
+            query_as!(
+                Self,
+                "SELECT * FROM org_members WHERE org_id = $1 AND user_id = $2",
+                org_id,
+                user_id,
+            )
+            .fetch_one(DB::conn())
+            .await?
+        };
+
+        Ok(res)
+    }
+
+    pub async fn find_for_org(org_id: &str) -> Result<Vec<Self>, ErrorResponse> {
+        let res = if is_hiqlite() {
+            DB::client()
+                .query_as(
+                    "SELECT * FROM org_members WHERE org_id = $1",
+                    params!(org_id),
+                )
+                .await?
+        } else {
+            query_as!(Self, "SELECT * FROM org_members WHERE org_id = $1", org_id)
+                .fetch_all(DB::conn())
+                .await?
+        };
+
+        Ok(res)
+    }
+
+    pub async fn find_for_user(user_id: &str) -> Result<Vec<Self>, ErrorResponse> {
+        let res = if is_hiqlite() {
+            DB::client()
+                .query_as(
+                    "SELECT * FROM org_members WHERE user_id = $1",
+                    params!(user_id),
+                )
+                .await?
+        } else {
+            query_as!(
+                Self,
+                "SELECT * FROM org_members WHERE user_id = $1",
+                user_id
+            )
+            .fetch_all(DB::conn())
+            .await?
+        };
+
+        Ok(res)
+    }
+
+    /// Inserts or updates this membership
+    pub async fn save(&self) -> Result<(), ErrorResponse> {
+        if is_hiqlite() {
+            DB::client()
+                .execute(
+                    r#"
+INSERT INTO org_members (org_id, user_id, roles, is_org_admin)
+VALUES ($1, $2, $3, $4)
+ON CONFLICT(org_id, user_id) DO UPDATE
+SET roles = $3, is_org_admin = $4"#,
+                    params!(
+                        self.org_id.clone(),
+                        self.user_id.clone(),
+                        self.roles.clone(),
+                        self.is_org_admin
+                    ),
+                )
+                .await?;
+        } else {
+            query!(
+                r#"
+INSERT INTO org_members (org_id, user_id, roles, is_org_admin)
+VALUES ($1, $2, $3, $4)
+ON CONFLICT(org_id, user_id) DO UPDATE
+SET roles = $3, is_org_admin = $4"#,
+                self.org_id,
+                self.user_id,
+                self.roles,
+                self.is_org_admin,
+            )
+            .execute(DB::conn())
+            .await?;
+        }
+
+        Ok(())
+    }
+}
+
+impl OrgMember {
+    /// Builds a new membership and makes sure, that only roles are assigned, that actually exist
+    /// inside the organization.
+    pub fn try_build(
+        org: &Organization,
+        user_id: String,
+        roles: Vec<String>,
+        is_org_admin: bool,
+    ) -> Result<Self, ErrorResponse> {
+        let org_roles = org.get_roles();
+        if let Some(role) = roles.iter().find(|r| !org_roles.contains(r)) {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                format!("Role '{}' does not exist in this organization", role),
+            ));
+        }
+
+        Ok(Self {
+            org_id: org.id.clone(),
+            user_id,
+            roles: Organization::sanitize_roles(roles),
+            is_org_admin,
+        })
+    }
+
+    pub fn get_roles(&self) -> Vec<String> {
+        if self.roles.is_empty() {
+            return Vec::default();
+        }
+        self.roles.split(',').map(|r| r.to_string()).collect()
+    }
+
+    /// Returns `true` if the given user is an admin for the given organization
+    pub async fn is_org_admin(org_id: &str, user_id: &str) -> Result<bool, ErrorResponse> {
+        match Self::find(org_id, user_id).await {
+            Ok(member) => Ok(member.is_org_admin),
+            Err(err) if err.error == ErrorResponseType::NotFound => Ok(false),
+            Err(err) => Err(err),
+        }
+    }
+}
+
+impl OrgMember {
+    pub fn into_response(self, email: String) -> OrgMemberResponse {
+        let roles = self.get_roles();
+        OrgMemberResponse {
+            org_id: self.org_id,
+            user_id: self.user_id,
+            email,
+            roles,
+            is_org_admin: self.is_org_admin,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_org_member_roles() {
+        let org = Organization {
+            id: "org1".to_string(),
+            name: "Acme".to_string(),
+            description: None,
+            roles: Organization::sanitize_roles(vec![
+                "user".to_string(),
+                "billing".to_string(),
+                "user".to_string(),
+            ]),
+        };
+        assert_eq!(org.roles, "billing,user");
+
+        let member =
+            OrgMember::try_build(&org, "u1".to_string(), vec!["user".to_string()], false).unwrap();
+        assert_eq!(member.get_roles(), vec!["user".to_string()]);
+
+        let res = OrgMember::try_build(&org, "u1".to_string(), vec!["admin".to_string()], true);
+        assert!(res.is_err());
+
+        let member = OrgMember::try_build(&org, "u1".to_string(), vec![], false).unwrap();
+        assert!(member.get_roles().is_empty());
+    }
+}
@@ -1,4 +1,6 @@
 use crate::database::{Cache, DB};
+use crate::entity::password_policies::ScopedPasswordPolicy;
+use crate::entity::users::User;
 use actix_web::web;
 use argon2::password_hash::SaltString;
 use argon2::{Algorithm, Argon2, PasswordHasher, Version};
@@ -9,13 +11,18 @@ use rauthy_api_types::generic::{
 };
 use rauthy_common::constants::{
     ARGON2ID_M_COST_MIN, ARGON2ID_T_COST_MIN, CACHE_TTL_APP, IDX_PASSWORD_RULES,
+    PASSWORD_BREACHED_PATH,
 };
 use rauthy_common::is_hiqlite;
-use rauthy_error::ErrorResponse;
+use rauthy_error::{ErrorResponse, ErrorResponseType};
+use ring::digest;
 use serde::{Deserialize, Serialize};
 use sqlx::{FromRow, Row};
-use std::cmp::max;
-use tokio::time;
+use std::cmp::{max, min};
+use std::io;
+use std::path::Path;
+use tokio::{fs, time};
+use tracing::warn;
 use utoipa::ToSchema;
 
 #[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
@@ -120,6 +127,44 @@ pub struct PasswordPolicy {
     pub include_special: Option<i32>,
     pub valid_days: Option<i32>,
     pub not_recently_used: Option<i32>,
+    /// Minimum zxcvbn strength score from `1` to `4`
+    pub min_strength: Option<i32>,
+    /// Only has an effect with a configured `PASSWORD_BREACHED_PATH`
+    pub check_breached: bool,
+    /// Always lowercase to compare them case-insensitive
+    pub banned_words: Vec<String>,
+}
+
+/// The policy before the extended checks have been added. It is needed to read existing policies
+/// from the database, because bincode cannot handle missing fields.
+#[derive(Debug, Serialize, Deserialize)]
+struct PasswordPolicyLegacy {
+    length_min: i32,
+    length_max: i32,
+    include_lower_case: Option<i32>,
+    include_upper_case: Option<i32>,
+    include_digits: Option<i32>,
+    include_special: Option<i32>,
+    valid_days: Option<i32>,
+    not_recently_used: Option<i32>,
+}
+
+impl From<PasswordPolicyLegacy> for PasswordPolicy {
+    fn from(value: PasswordPolicyLegacy) -> Self {
+        Self {
+            length_min: value.length_min,
+            length_max: value.length_max,
+            include_lower_case: value.include_lower_case,
+            include_upper_case: value.include_upper_case,
+            include_digits: value.include_digits,
+            include_special: value.include_special,
+            valid_days: value.valid_days,
+            not_recently_used: value.not_recently_used,
+            min_strength: None,
+            check_breached: false,
+            banned_words: Vec::default(),
+        }
+    }
 }
 
 // CRUD
@@ -144,7 +189,10 @@ impl PasswordPolicy {
                 .await?
                 .get("data")
         };
-        let policy = bincode::deserialize::<Self>(&bytes)?;
+        let policy = match bincode::deserialize::<Self>(&bytes) {
+            Ok(policy) => policy,
+            Err(_) => Self::from(bincode::deserialize::<PasswordPolicyLegacy>(&bytes)?),
+        };
 
         client
             .put(Cache::App, IDX_PASSWORD_RULES, &policy, CACHE_TTL_APP)
@@ -153,6 +201,15 @@ impl PasswordPolicy {
         Ok(policy)
     }
 
+    /// Returns the effective policy for the given user. This is the merged policy of all scoped
+    /// policies assigned to the users' groups, or the global default if there is none.
+    pub async fn find_for_user(user: &User) -> Result<Self, ErrorResponse> {
+        match ScopedPasswordPolicy::find_for_user(user).await? {
+            Some(policy) => Ok(policy),
+            None => Self::find().await,
+        }
+    }
+
     pub async fn save(&self) -> Result<(), ErrorResponse> {
         let slf = bincode::serialize(&self)?;
 
@@ -190,6 +247,251 @@ impl PasswordPolicy {
         self.include_special = req.include_special;
         self.valid_days = req.valid_days;
         self.not_recently_used = req.not_recently_used;
+        self.min_strength = req.min_strength;
+        self.check_breached = req.check_breached.unwrap_or(false);
+
+        let mut banned_words = req
+            .banned_words
+            .unwrap_or_default()
+            .into_iter()
+            .map(|w| w.trim().to_lowercase())
+            .filter(|w| !w.is_empty())
+            .collect::<Vec<_>>();
+        banned_words.sort();
+        banned_words.dedup();
+        self.banned_words = banned_words;
+    }
+
+    /// Merges the `other` policy into this one, while always keeping the stricter value for each
+    /// single rule.
+    pub fn merge_strictest(&mut self, other: &Self) {
+        fn max_opt(a: Option<i32>, b: Option<i32>) -> Option<i32> {
+            match (a, b) {
+                (Some(a), Some(b)) => Some(max(a, b)),
+                (a, b) => a.or(b),
+            }
+        }
+
+        self.length_min = max(self.length_min, other.length_min);
+        // a lower max length could contradict the min length of the other policy
+        self.length_max = max(self.length_max, other.length_max);
+        self.include_lower_case = max_opt(self.include_lower_case, other.include_lower_case);
+        self.include_upper_case = max_opt(self.include_upper_case, other.include_upper_case);
+        self.include_digits = max_opt(self.include_digits, other.include_digits);
+        self.include_special = max_opt(self.include_special, other.include_special);
+        self.valid_days = match (self.valid_days, other.valid_days) {
+            (Some(a), Some(b)) => Some(min(a, b)),
+            (a, b) => a.or(b),
+        };
+        self.not_recently_used = max_opt(self.not_recently_used, other.not_recently_used);
+        self.min_strength = max_opt(self.min_strength, other.min_strength);
+        self.check_breached = self.check_breached || other.check_breached;
+
+        for word in &other.banned_words {
+            if !self.banned_words.contains(word) {
+                self.banned_words.push(word.clone());
+            }
+        }
+        self.banned_words.sort();
+    }
+
+    /// Validates the given plain text password against all rules of this policy.
+    /// The `user_inputs` should contain personal values like the E-Mail, which lower the
+    /// strength score, if they are being used inside the password.
+    pub async fn validate(
+        &self,
+        plain_pwd: &str,
+        user_inputs: &[&str],
+    ) -> Result<(), ErrorResponse> {
+        if plain_pwd.len() < self.length_min as usize {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                format!("Minimum password length is {}", self.length_min),
+            ));
+        }
+        if plain_pwd.len() > self.length_max as usize {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                format!("Maximum password length is {}", self.length_max),
+            ));
+        }
+
+        let mut count_lower = 0;
+        let mut count_upper = 0;
+        let mut count_digit = 0;
+        let mut count_special = 0;
+
+        plain_pwd.chars().for_each(|c| {
+            if c.is_lowercase() {
+                count_lower += 1;
+            } else if c.is_uppercase() {
+                count_upper += 1;
+            } else if c.is_ascii_digit() {
+                count_digit += 1;
+            } else if !c.is_alphanumeric() {
+                count_special += 1;
+            }
+        });
+
+        let lower_req = self.include_lower_case.unwrap_or(0);
+        if lower_req > count_lower {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                format!(
+                    "New password does not include the minimum lower character count: {}",
+                    lower_req
+                ),
+            ));
+        }
+
+        let upper_req = self.include_upper_case.unwrap_or(0);
+        if upper_req > count_upper {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                format!(
+                    "New password does not include the minimum upper character count: {}",
+                    upper_req
+                ),
+            ));
+        }
+
+        let digit_req = self.include_digits.unwrap_or(0);
+        if digit_req > count_digit {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                format!(
+                    "New password does not include the minimum digit count: {}",
+                    digit_req
+                ),
+            ));
+        }
+
+        let special_req = self.include_special.unwrap_or(0);
+        if special_req > count_special {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                format!(
+                    "New password does not include the minimum special character count: {}",
+                    special_req
+                ),
+            ));
+        }
+
+        self.validate_extended(plain_pwd, user_inputs).await
+    }
+
+    /// Validates the checks, which go beyond the simple character counts.
+    async fn validate_extended(
+        &self,
+        plain_pwd: &str,
+        user_inputs: &[&str],
+    ) -> Result<(), ErrorResponse> {
+        if let Some(word) = self.find_banned_word(plain_pwd) {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                format!("The new password must not contain '{}'", word),
+            ));
+        }
+
+        if let Some(min_strength) = self.min_strength {
+            let score = strength_score(plain_pwd, user_inputs);
+            if score < min_strength {
+                return Err(ErrorResponse::new(
+                    ErrorResponseType::BadRequest,
+                    format!(
+                        "The new password is too weak - strength score {} is below the minimum of {}",
+                        score, min_strength
+                    ),
+                ));
+            }
+        }
+
+        if self.check_breached && is_breached(plain_pwd).await? {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                "The new password has been found in a known data breach",
+            ));
+        }
+
+        Ok(())
+    }
+
+    fn find_banned_word(&self, plain_pwd: &str) -> Option<&str> {
+        let pwd = plain_pwd.to_lowercase();
+        self.banned_words
+            .iter()
+            .find(|w| pwd.contains(w.as_str()))
+            .map(|w| w.as_str())
+    }
+}
+
+/// Returns the zxcvbn score from `0` (too guessable) to `4` (very unguessable).
+fn strength_score(plain_pwd: &str, user_inputs: &[&str]) -> i32 {
+    let score = zxcvbn::zxcvbn(plain_pwd, user_inputs).score();
+    u8::from(score) as i32
+}
+
+/// Looks up the password in a local copy of the HIBP Pwned Passwords corpus at
+/// `PASSWORD_BREACHED_PATH`. The corpus must be split into range files, which are named after the
+/// first 5 characters of the uppercase SHA-1 hash like the official downloader creates them.
+async fn is_breached(plain_pwd: &str) -> Result<bool, ErrorResponse> {
+    let Some(path) = PASSWORD_BREACHED_PATH.as_deref() else {
+        return Ok(false);
+    };
+
+    let hash = hex::encode_upper(digest::digest(
+        &digest::SHA1_FOR_LEGACY_USE_ONLY,
+        plain_pwd.as_bytes(),
+    ));
+    let (prefix, suffix) = hash.split_at(5);
+
+    let file = Path::new(path).join(format!("{}.txt", prefix));
+    match fs::read_to_string(&file).await {
+        Ok(content) => Ok(is_in_range(&content, suffix)),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => {
+            warn!(
+                "Breached passwords range file {} does not exist - the corpus is incomplete",
+                file.display()
+            );
+            Ok(false)
+        }
+        Err(err) => Err(ErrorResponse::new(
+            ErrorResponseType::Internal,
+            format!(
+                "Cannot read breached passwords range file {}: {}",
+                file.display(),
+                err
+            ),
+        )),
+    }
+}
+
+/// Each line of a range file contains the remaining 35 characters of the hash and the count,
+/// separated by `:`. Padding entries with a count of `0` are ignored.
+fn is_in_range(content: &str, suffix: &str) -> bool {
+    content.lines().any(|line| {
+        let (hash, count) = line.trim().split_once(':').unwrap_or((line.trim(), "1"));
+        hash.eq_ignore_ascii_case(suffix) && count.trim() != "0"
+    })
+}
+
+impl From<PasswordPolicyRequest> for PasswordPolicy {
+    fn from(req: PasswordPolicyRequest) -> Self {
+        let mut slf = Self {
+            length_min: req.length_min,
+            length_max: req.length_max,
+            include_lower_case: None,
+            include_upper_case: None,
+            include_digits: None,
+            include_special: None,
+            valid_days: None,
+            not_recently_used: None,
+            min_strength: None,
+            check_breached: false,
+            banned_words: Vec::default(),
+        };
+        slf.apply_req(req);
+        slf
     }
 }
 
@@ -204,6 +506,9 @@ impl From<PasswordPolicy> for PasswordPolicyResponse {
             include_special: r.include_special,
             valid_days: r.valid_days,
             not_recently_used: r.not_recently_used,
+            min_strength: r.min_strength,
+            check_breached: r.check_breached,
+            banned_words: r.banned_words,
         }
     }
 }
@@ -277,3 +582,101 @@ impl RecentPasswordsEntity {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy(min_strength: Option<i32>, banned_words: Vec<String>) -> PasswordPolicy {
+        PasswordPolicy {
+            length_min: 8,
+            length_max: 128,
+            include_lower_case: None,
+            include_upper_case: None,
+            include_digits: None,
+            include_special: None,
+            valid_days: None,
+            not_recently_used: None,
+            min_strength,
+            check_breached: false,
+            banned_words,
+        }
+    }
+
+    #[test]
+    fn test_legacy_policy_deserialize() {
+        let legacy = PasswordPolicyLegacy {
+            length_min: 14,
+            length_max: 128,
+            include_lower_case: Some(1),
+            include_upper_case: Some(1),
+            include_digits: Some(1),
+            include_special: None,
+            valid_days: Some(180),
+            not_recently_used: Some(3),
+        };
+        let bytes = bincode::serialize(&legacy).unwrap();
+
+        assert!(bincode::deserialize::<PasswordPolicy>(&bytes).is_err());
+        let policy =
+            PasswordPolicy::from(bincode::deserialize::<PasswordPolicyLegacy>(&bytes).unwrap());
+        assert_eq!(policy.length_min, 14);
+        assert_eq!(policy.include_special, None);
+        assert_eq!(policy.not_recently_used, Some(3));
+        assert_eq!(policy.min_strength, None);
+        assert!(!policy.check_breached);
+        assert!(policy.banned_words.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_validate_extended() {
+        let p = policy(None, vec!["rauthy".to_string()]);
+        assert!(p.validate_extended("MyRauthyPassword1", &[]).await.is_err());
+        assert!(p.validate_extended("SomethingElse123", &[]).await.is_ok());
+
+        let p = policy(Some(3), Vec::default());
+        assert!(p.validate_extended("password123", &[]).await.is_err());
+        assert!(p
+            .validate_extended("correct horse battery staple", &[])
+            .await
+            .is_ok());
+    }
+
+    #[test]
+    fn test_merge_strictest() {
+        let mut p = policy(Some(2), vec!["rauthy".to_string()]);
+        p.valid_days = Some(180);
+        p.include_digits = Some(1);
+
+        let mut other = policy(None, vec!["admin".to_string(), "rauthy".to_string()]);
+        other.length_min = 16;
+        other.valid_days = Some(90);
+        other.include_special = Some(2);
+        other.check_breached = true;
+
+        p.merge_strictest(&other);
+        assert_eq!(p.length_min, 16);
+        assert_eq!(p.length_max, 128);
+        assert_eq!(p.include_digits, Some(1));
+        assert_eq!(p.include_special, Some(2));
+        assert_eq!(p.valid_days, Some(90));
+        assert_eq!(p.min_strength, Some(2));
+        assert!(p.check_breached);
+        assert_eq!(
+            p.banned_words,
+            vec!["admin".to_string(), "rauthy".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_is_in_range() {
+        let content = "0018A45C4D1DEF81644B54AB7F969B88D65:1\r\n\
+            00D4F6E8FA6EECAD2A3AA415EEC418D38EC:2\r\n\
+            011053FD0102E94D6AE2F8B83D76FAF94F6:0\r\n";
+        assert!(is_in_range(content, "00D4F6E8FA6EECAD2A3AA415EEC418D38EC"));
+        assert!(is_in_range(content, "0018a45c4d1def81644b54ab7f969b88d65"));
+        // padding entry
+        assert!(!is_in_range(content, "011053FD0102E94D6AE2F8B83D76FAF94F6"));
+        assert!(!is_in_range(content, "11111111111111111111111111111111111"));
+    }
+}
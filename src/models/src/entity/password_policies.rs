@@ -0,0 +1,427 @@
+use crate::database::{Cache, DB};
+use crate::entity::clients::Client;
+use crate::entity::groups::Group;
+use crate::entity::password::PasswordPolicy;
+use crate::entity::users::User;
+use hiqlite::{params, Param};
+use rauthy_api_types::generic::{
+    PasswordPolicyRequest, PasswordPolicyResponse, ScopedPasswordPolicyRequest,
+    ScopedPasswordPolicyResponse,
+};
+use rauthy_common::constants::{CACHE_TTL_APP, IDX_PASSWORD_POLICIES};
+use rauthy_common::is_hiqlite;
+use rauthy_common::utils::new_store_id;
+use rauthy_error::{ErrorResponse, ErrorResponseType};
+use serde::{Deserialize, Serialize};
+use sqlx::{query, query_as, FromRow};
+
+/// The raw database representation of a `ScopedPasswordPolicy`
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct ScopedPasswordPolicyEntity {
+    pub id: String,
+    pub name: String,
+    pub policy: Vec<u8>,
+    pub groups: Option<String>,
+    pub clients: Option<String>,
+}
+
+// CRUD
+impl ScopedPasswordPolicyEntity {
+    pub async fn create(
+        req: ScopedPasswordPolicyRequest,
+    ) -> Result<ScopedPasswordPolicy, ErrorResponse> {
+        let mut policies = ScopedPasswordPolicy::find_all().await?;
+        if policies.iter().any(|p| p.name == req.name) {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                "A password policy with this name already exists",
+            ));
+        }
+
+        let slf = Self {
+            id: new_store_id(),
+            name: req.name,
+            policy: Self::policy_from_req(req.policy)?,
+            groups: Group::sanitize(req.groups).await?,
+            clients: Self::sanitize_clients(req.clients).await?,
+        };
+
+        if is_hiqlite() {
+            DB::client()
+                .execute(
+                    r#"
+INSERT INTO password_policies (id, name, policy, groups, clients)
+VALUES ($1, $2, $3, $4, $5)"#,
+                    params!(
+                        slf.id.clone(),
+                        slf.name.clone(),
+                        slf.policy.clone(),
+                        slf.groups.clone(),
+                        slf.clients.clone()
+                    ),
+                )
+                .await?;
+        } else {
+            query!(
+                r#"
+INSERT INTO password_policies (id, name, policy, groups, clients)
+VALUES ($1, $2, $3, $4, $5)"#,
+                slf.id,
+                slf.name,
+                slf.policy,
+                slf.groups,
+                slf.clients,
+            )
+            .execute(DB::conn())
+            .await?;
+        }
+
+        let policy = ScopedPasswordPolicy::try_from(slf)?;
+        policies.push(policy.clone());
+        DB::client()
+            .put(Cache::App, IDX_PASSWORD_POLICIES, &policies, CACHE_TTL_APP)
+            .await?;
+
+        Ok(policy)
+    }
+
+    pub async fn delete(id: &str) -> Result<(), ErrorResponse> {
+        if is_hiqlite() {
+            DB::client()
+                .execute("DELETE FROM password_policies WHERE id = $1", params!(id))
+                .await?;
+        } else {
+            query!("DELETE FROM password_policies WHERE id = $1", id)
+                .execute(DB::conn())
+                .await?;
+        }
+
+        DB::client()
+            .delete(Cache::App, IDX_PASSWORD_POLICIES)
+            .await?;
+        Ok(())
+    }
+
+    pub async fn find(id: &str) -> Result<Self, ErrorResponse> {
+        let res = if is_hiqlite() {
+            DB::client()
+                .query_as_one("SELECT * FROM password_policies WHERE id = $1", params!(id))
+                .await?
+        } else {
+            query_as!(Self, "SELECT * FROM password_policies WHERE id = $1", id)
+                .fetch_one(DB::conn())
+                .await?
+        };
+
+        Ok(res)
+    }
+
+    /// Keeps the group assignments in sync when a group gets renamed.
+    pub async fn rename_group(old_name: &str, new_name: &str) -> Result<(), ErrorResponse> {
+        let policies = ScopedPasswordPolicy::find_all().await?;
+
+        for policy in policies {
+            let Some(groups) = policy.groups else {
+                continue;
+            };
+            if !groups.iter().any(|g| g == old_name) {
+                continue;
+            }
+
+            let groups = groups
+                .into_iter()
+                .map(|g| {
+                    if g == old_name {
+                        new_name.to_string()
+                    } else {
+                        g
+                    }
+                })
+                .collect::<Vec<_>>()
+                .join(",");
+
+            if is_hiqlite() {
+                DB::client()
+                    .execute(
+                        "UPDATE password_policies SET groups = $1 WHERE id = $2",
+                        params!(groups, policy.id),
+                    )
+                    .await?;
+            } else {
+                query!(
+                    "UPDATE password_policies SET groups = $1 WHERE id = $2",
+                    groups,
+                    policy.id,
+                )
+                .execute(DB::conn())
+                .await?;
+            }
+        }
+
+        DB::client()
+            .delete(Cache::App, IDX_PASSWORD_POLICIES)
+            .await?;
+        Ok(())
+    }
+
+    pub async fn update(
+        id: &str,
+        req: ScopedPasswordPolicyRequest,
+    ) -> Result<ScopedPasswordPolicy, ErrorResponse> {
+        let mut slf = Self::find(id).await?;
+
+        if slf.name != req.name
+            && ScopedPasswordPolicy::find_all()
+                .await?
+                .iter()
+                .any(|p| p.name == req.name)
+        {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                "A password policy with this name already exists",
+            ));
+        }
+
+        slf.name = req.name;
+        slf.policy = Self::policy_from_req(req.policy)?;
+        slf.groups = Group::sanitize(req.groups).await?;
+        slf.clients = Self::sanitize_clients(req.clients).await?;
+
+        if is_hiqlite() {
+            DB::client()
+                .execute(
+                    r#"
+UPDATE password_policies
+SET name = $1, policy = $2, groups = $3, clients = $4
+WHERE id = $5"#,
+                    params!(
+                        slf.name.clone(),
+                        slf.policy.clone(),
+                        slf.groups.clone(),
+                        slf.clients.clone(),
+                        slf.id.clone()
+                    ),
+                )
+                .await?;
+        } else {
+            query!(
+                r#"
+UPDATE password_policies
+SET name = $1, policy = $2, groups = $3, clients = $4
+WHERE id = $5"#,
+                slf.name,
+                slf.policy,
+                slf.groups,
+                slf.clients,
+                slf.id,
+            )
+            .execute(DB::conn())
+            .await?;
+        }
+
+        DB::client()
+            .delete(Cache::App, IDX_PASSWORD_POLICIES)
+            .await?;
+
+        ScopedPasswordPolicy::try_from(slf)
+    }
+}
+
+impl ScopedPasswordPolicyEntity {
+    fn policy_from_req(req: PasswordPolicyRequest) -> Result<Vec<u8>, ErrorResponse> {
+        Ok(bincode::serialize(&PasswordPolicy::from(req))?)
+    }
+
+    /// Silently drops all clients, that do not exist, like `Group::sanitize` does for groups.
+    async fn sanitize_clients(
+        clients: Option<Vec<String>>,
+    ) -> Result<Option<String>, ErrorResponse> {
+        let Some(clients) = clients else {
+            return Ok(None);
+        };
+
+        let res = Client::find_all()
+            .await?
+            .into_iter()
+            .filter(|c| clients.contains(&c.id))
+            .map(|c| c.id)
+            .collect::<Vec<_>>();
+
+        if res.is_empty() {
+            Ok(None)
+        } else {
+            Ok(Some(res.join(",")))
+        }
+    }
+}
+
+/// A named password policy, which can be assigned to groups and clients.
+///
+/// For members of the assigned groups, it replaces the global default policy. If a user is a
+/// member of multiple groups with a policy, the strictest value of each rule wins. For the
+/// assigned clients, it will be enforced during each password login. If the password does not
+/// satisfy it, the user must set a new one before the login can succeed.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScopedPasswordPolicy {
+    pub id: String,
+    pub name: String,
+    pub policy: PasswordPolicy,
+    pub groups: Option<Vec<String>>,
+    pub clients: Option<Vec<String>>,
+}
+
+impl ScopedPasswordPolicy {
+    pub async fn find_all() -> Result<Vec<Self>, ErrorResponse> {
+        let client = DB::client();
+        if let Some(slf) = client.get(Cache::App, IDX_PASSWORD_POLICIES).await? {
+            return Ok(slf);
+        }
+
+        let entities: Vec<ScopedPasswordPolicyEntity> = if is_hiqlite() {
+            client
+                .query_as("SELECT * FROM password_policies", params!())
+                .await?
+        } else {
+            query_as!(
+                ScopedPasswordPolicyEntity,
+                "SELECT * FROM password_policies"
+            )
+            .fetch_all(DB::conn())
+            .await?
+        };
+
+        let mut res = Vec::with_capacity(entities.len());
+        for entity in entities {
+            res.push(Self::try_from(entity)?);
+        }
+
+        client
+            .put(Cache::App, IDX_PASSWORD_POLICIES, &res, CACHE_TTL_APP)
+            .await?;
+
+        Ok(res)
+    }
+
+    /// Returns the merged policy of all policies assigned to the given client, if any.
+    pub async fn find_for_client(client_id: &str) -> Result<Option<PasswordPolicy>, ErrorResponse> {
+        let policies = Self::find_all().await?.into_iter().filter(|p| {
+            p.clients
+                .as_ref()
+                .map(|clients| clients.iter().any(|c| c == client_id))
+                .unwrap_or(false)
+        });
+        Ok(Self::merge(policies))
+    }
+
+    /// Returns the merged policy of all policies assigned to the groups of the given user, if any.
+    pub async fn find_for_user(user: &User) -> Result<Option<PasswordPolicy>, ErrorResponse> {
+        let user_groups = user.get_groups();
+        if user_groups.is_empty() {
+            return Ok(None);
+        }
+
+        let policies = Self::find_all()
+            .await?
+            .into_iter()
+            .filter(|p| p.is_assigned_to(&user_groups));
+        Ok(Self::merge(policies))
+    }
+}
+
+impl ScopedPasswordPolicy {
+    #[inline(always)]
+    fn is_assigned_to(&self, user_groups: &[String]) -> bool {
+        self.groups
+            .as_ref()
+            .map(|groups| groups.iter().any(|g| user_groups.contains(g)))
+            .unwrap_or(false)
+    }
+
+    fn merge(policies: impl Iterator<Item = Self>) -> Option<PasswordPolicy> {
+        policies.map(|p| p.policy).reduce(|mut acc, p| {
+            acc.merge_strictest(&p);
+            acc
+        })
+    }
+}
+
+impl TryFrom<ScopedPasswordPolicyEntity> for ScopedPasswordPolicy {
+    type Error = ErrorResponse;
+
+    fn try_from(value: ScopedPasswordPolicyEntity) -> Result<Self, Self::Error> {
+        let policy = bincode::deserialize::<PasswordPolicy>(&value.policy)?;
+        let split = |v: String| v.split(',').map(|v| v.trim().to_string()).collect();
+
+        Ok(Self {
+            id: value.id,
+            name: value.name,
+            policy,
+            groups: value.groups.map(split),
+            clients: value.clients.map(split),
+        })
+    }
+}
+
+impl From<ScopedPasswordPolicy> for ScopedPasswordPolicyResponse {
+    fn from(value: ScopedPasswordPolicy) -> Self {
+        Self {
+            id: value.id,
+            name: value.name,
+            policy: PasswordPolicyResponse::from(value.policy),
+            groups: value.groups,
+            clients: value.clients,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scoped(groups: Option<Vec<&str>>, length_min: i32) -> ScopedPasswordPolicy {
+        ScopedPasswordPolicy {
+            id: new_store_id(),
+            name: "test".to_string(),
+            policy: PasswordPolicy {
+                length_min,
+                length_max: 128,
+                include_lower_case: None,
+                include_upper_case: None,
+                include_digits: None,
+                include_special: None,
+                valid_days: None,
+                not_recently_used: None,
+                min_strength: None,
+                check_breached: false,
+                banned_words: Vec::default(),
+            },
+            groups: groups.map(|g| g.into_iter().map(String::from).collect()),
+            clients: None,
+        }
+    }
+
+    #[test]
+    fn test_scoped_policy_assignment_and_merge() {
+        let user_groups = vec!["admins".to_string(), "dev".to_string()];
+
+        let admins = scoped(Some(vec!["admins"]), 16);
+        let dev = scoped(Some(vec!["dev", "ops"]), 12);
+        let customers = scoped(Some(vec!["customers"]), 8);
+        let unassigned = scoped(None, 24);
+
+        assert!(admins.is_assigned_to(&user_groups));
+        assert!(dev.is_assigned_to(&user_groups));
+        assert!(!customers.is_assigned_to(&user_groups));
+        assert!(!unassigned.is_assigned_to(&user_groups));
+
+        let merged = ScopedPasswordPolicy::merge(
+            vec![dev, admins, customers, unassigned]
+                .into_iter()
+                .filter(|p| p.is_assigned_to(&user_groups)),
+        )
+        .unwrap();
+        assert_eq!(merged.length_min, 16);
+
+        assert!(ScopedPasswordPolicy::merge(Vec::default().into_iter()).is_none());
+    }
+}
@@ -16,6 +16,12 @@ impl PowEntity {
         Ok(pow)
     }
 
+    /// Validates a solved PoW and prevents a future re-use
+    pub async fn validate(pow: &str) -> Result<(), ErrorResponse> {
+        let challenge = Pow::validate(pow)?;
+        Self::check_prevent_reuse(challenge.to_string()).await
+    }
+
     /// Checks re-usages of PoWs and prevents a future re-use
     pub async fn check_prevent_reuse(challenge: String) -> Result<(), ErrorResponse> {
         let client = DB::client();
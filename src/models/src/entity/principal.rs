@@ -1,5 +1,7 @@
+use crate::entity::admin_roles::AdminRole;
 use crate::entity::api_keys::{AccessGroup, AccessRights, ApiKey};
 use crate::entity::sessions::{Session, SessionState};
+use crate::entity::users::User;
 use actix_web::{web, HttpRequest};
 use rauthy_common::constants::{ADMIN_FORCE_MFA, RAUTHY_ADMIN_ROLE};
 use rauthy_error::{ErrorResponse, ErrorResponseType};
@@ -11,6 +13,8 @@ pub struct Principal {
     pub session: Option<Session>,
     pub api_key: Option<ApiKey>,
     pub roles: Vec<String>,
+    /// Scoped admin roles, that are linked to any of the `roles`
+    pub admin_roles: Vec<AdminRole>,
 }
 
 impl Principal {
@@ -48,6 +52,13 @@ impl Principal {
         self.roles.contains(&*RAUTHY_ADMIN_ROLE)
     }
 
+    /// Returns `true` if this principal has any scoped admin role while not being a
+    /// full `rauthy_admin`.
+    #[inline(always)]
+    pub fn is_scoped_admin(&self) -> bool {
+        !self.is_admin() && !self.admin_roles.is_empty()
+    }
+
     #[inline(always)]
     pub fn is_user(&self, id: &str) -> Result<(), ErrorResponse> {
         if self.user_id() != Ok(id) {
@@ -103,7 +114,8 @@ impl Principal {
     /// Validates the Principal's session to only allow authorized Rauthy admin access.
     #[inline(always)]
     pub fn validate_admin_session(&self) -> Result<(), ErrorResponse> {
-        let _session = self.validate_session_auth()?;
+        let session = self.validate_session_auth()?;
+        Self::deny_impersonated(session)?;
         if !self.is_admin() {
             return Err(ErrorResponse::new(
                 ErrorResponseType::Forbidden,
@@ -121,16 +133,153 @@ impl Principal {
         Ok(())
     }
 
+    /// Validates the Principal's session against its scoped admin roles.
+    ///
+    /// If `target_groups` is `None`, only admin roles without any `group_scope` will be
+    /// accepted. This makes sure, that group scoped admins can never access resources in
+    /// endpoints that do not explicitly check the scope.
+    pub fn validate_admin_role(
+        &self,
+        access_group: &AccessGroup,
+        access_rights: &AccessRights,
+        target_groups: Option<&[String]>,
+    ) -> Result<(), ErrorResponse> {
+        let session = self.validate_session_auth()?;
+        Self::deny_impersonated(session)?;
+
+        let has_access = self
+            .admin_roles
+            .iter()
+            .any(|r| r.has_access(access_group, access_rights) && r.is_in_scope(target_groups));
+        if !has_access {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::Forbidden,
+                format!(
+                    "Insufficient admin role permissions. Needed: {:?} / {:?}",
+                    access_group, access_rights,
+                ),
+            ));
+        }
+
+        if *ADMIN_FORCE_MFA && !self.has_mfa_active() {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::MfaRequired,
+                "Rauthy admin access only allowed with MFA active",
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Returns the combined `group_scope` of all admin roles granting the given access, or
+    /// `None`, if any of them is not restricted to groups at all.
+    pub fn admin_role_group_scope(
+        &self,
+        access_group: &AccessGroup,
+        access_rights: &AccessRights,
+    ) -> Option<Vec<String>> {
+        let mut res = Vec::new();
+        for role in self
+            .admin_roles
+            .iter()
+            .filter(|r| r.has_access(access_group, access_rights))
+        {
+            match &role.group_scope {
+                None => return None,
+                Some(scope) => {
+                    for group in scope {
+                        if !res.contains(group) {
+                            res.push(group.clone());
+                        }
+                    }
+                }
+            }
+        }
+        Some(res)
+    }
+
     /// Validates an ApiKey OR a valid admin session.
     /// If both are given, the ApiKey will have the higher priority since it is more specific.
     /// Returns an error with an invalid ApiKey even when a valid session exists.
+    ///
+    /// Scoped admin roles are only accepted, if they do not have any `group_scope`.
     #[inline(always)]
     pub fn validate_api_key_or_admin_session(
         &self,
         access_group: AccessGroup,
         access_rights: AccessRights,
     ) -> Result<(), ErrorResponse> {
-        match self.validate_api_key(access_group, access_rights) {
+        self.validate_api_key_or_admin_session_scoped(access_group, access_rights, None)
+    }
+
+    /// The same as `validate_api_key_or_admin_session()`, but additionally accepts scoped admin
+    /// roles for the given `user`. Check `validate_admin_role_for_user()` for the details.
+    pub async fn validate_api_key_or_admin_session_for_user(
+        &self,
+        access_group: AccessGroup,
+        access_rights: AccessRights,
+        user: &User,
+    ) -> Result<(), ErrorResponse> {
+        match self.validate_api_key(access_group.clone(), access_rights.clone()) {
+            Ok(_) => Ok(()),
+            Err(err) if err.error == ErrorResponseType::Forbidden => Err(err),
+            Err(_) if self.is_scoped_admin() => {
+                self.validate_admin_role_for_user(&access_group, &access_rights, user)
+                    .await
+            }
+            Err(_) => self.validate_admin_session(),
+        }
+    }
+
+    /// Validates the scoped admin roles for the given `user`, who must be a member of at least
+    /// one of the scoped groups. Apart from read access, the `user` must not be an admin
+    /// itself, or a scoped admin could take over accounts with higher privileges.
+    pub async fn validate_admin_role_for_user(
+        &self,
+        access_group: &AccessGroup,
+        access_rights: &AccessRights,
+        user: &User,
+    ) -> Result<(), ErrorResponse> {
+        self.validate_admin_role(access_group, access_rights, Some(&user.get_groups()))?;
+        if access_rights != &AccessRights::Read {
+            let admin_roles = AdminRole::find_all().await?;
+            self.validate_admin_role_target(&user.get_roles(), &admin_roles)?;
+        }
+        Ok(())
+    }
+
+    /// Only a `rauthy_admin` may modify users, that have the `rauthy_admin` role or any role
+    /// that is linked to one of the given `admin_roles`.
+    fn validate_admin_role_target(
+        &self,
+        target_roles: &[String],
+        admin_roles: &[AdminRole],
+    ) -> Result<(), ErrorResponse> {
+        if self.is_admin() {
+            return Ok(());
+        }
+
+        let is_privileged = target_roles
+            .iter()
+            .any(|r| r == &*RAUTHY_ADMIN_ROLE || admin_roles.iter().any(|ar| &ar.name == r));
+        if is_privileged {
+            Err(ErrorResponse::new(
+                ErrorResponseType::Forbidden,
+                "Only a rauthy_admin is allowed to modify other administrators",
+            ))
+        } else {
+            Ok(())
+        }
+    }
+
+    #[inline(always)]
+    fn validate_api_key_or_admin_session_scoped(
+        &self,
+        access_group: AccessGroup,
+        access_rights: AccessRights,
+        target_groups: Option<&[String]>,
+    ) -> Result<(), ErrorResponse> {
+        match self.validate_api_key(access_group.clone(), access_rights.clone()) {
             Ok(_) => Ok(()),
 
             Err(err) => {
@@ -139,6 +288,8 @@ impl Principal {
                     // for the needed permissions in any case -> better DX and debugging
                     // without real security issues
                     Err(err)
+                } else if self.is_scoped_admin() {
+                    self.validate_admin_role(&access_group, &access_rights, target_groups)
                 } else {
                     self.validate_admin_session()
                 }
@@ -196,6 +347,28 @@ impl Principal {
         }
     }
 
+    /// Returns an error if the session is impersonated by an admin. Use this to protect
+    /// sensitive account actions.
+    #[inline(always)]
+    pub fn validate_not_impersonated(&self) -> Result<(), ErrorResponse> {
+        match &self.session {
+            Some(session) => Self::deny_impersonated(session),
+            None => Ok(()),
+        }
+    }
+
+    #[inline(always)]
+    fn deny_impersonated(session: &Session) -> Result<(), ErrorResponse> {
+        if session.is_impersonated() {
+            Err(ErrorResponse::new(
+                ErrorResponseType::Forbidden,
+                "This action is not allowed during impersonation",
+            ))
+        } else {
+            Ok(())
+        }
+    }
+
     #[inline(always)]
     pub fn validate_session_auth_or_init(&self) -> Result<(), ErrorResponse> {
         if let Some(session) = &self.session {
@@ -243,3 +416,78 @@ impl Principal {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::entity::api_keys::ApiKeyAccess;
+
+    #[test]
+    fn test_validate_admin_role_target() {
+        let admin_roles = vec![AdminRole {
+            id: "123".to_string(),
+            name: "user-manager".to_string(),
+            description: None,
+            access: vec![ApiKeyAccess {
+                group: AccessGroup::Users,
+                access_rights: vec![AccessRights::Read, AccessRights::Update],
+            }],
+            group_scope: Some(vec!["dept_a".to_string()]),
+        }];
+        let scoped = Principal {
+            roles: vec!["user-manager".to_string()],
+            admin_roles: admin_roles.clone(),
+            ..Default::default()
+        };
+        let admin = Principal {
+            roles: vec![RAUTHY_ADMIN_ROLE.clone()],
+            ..Default::default()
+        };
+
+        let user = vec!["user".to_string()];
+        let rauthy_admin = vec!["user".to_string(), RAUTHY_ADMIN_ROLE.clone()];
+        let scoped_admin = vec!["user-manager".to_string()];
+
+        assert!(scoped
+            .validate_admin_role_target(&user, &admin_roles)
+            .is_ok());
+        assert!(scoped.validate_admin_role_target(&[], &admin_roles).is_ok());
+
+        // a scoped admin must never be able to modify other admins
+        let err = scoped
+            .validate_admin_role_target(&rauthy_admin, &admin_roles)
+            .unwrap_err();
+        assert_eq!(err.error, ErrorResponseType::Forbidden);
+        let err = scoped
+            .validate_admin_role_target(&scoped_admin, &admin_roles)
+            .unwrap_err();
+        assert_eq!(err.error, ErrorResponseType::Forbidden);
+
+        assert!(admin
+            .validate_admin_role_target(&rauthy_admin, &admin_roles)
+            .is_ok());
+        assert!(admin
+            .validate_admin_role_target(&scoped_admin, &admin_roles)
+            .is_ok());
+    }
+
+    #[test]
+    fn test_validate_not_impersonated() {
+        let mut session = Session::new(60, None);
+        session.user_id = Some("user123".to_string());
+        let mut principal = Principal {
+            session: Some(session.clone()),
+            ..Default::default()
+        };
+        assert!(principal.validate_not_impersonated().is_ok());
+
+        session.impersonated_by = Some("admin123".to_string());
+        principal.session = Some(session);
+        let err = principal.validate_not_impersonated().unwrap_err();
+        assert_eq!(err.error, ErrorResponseType::Forbidden);
+
+        // API keys can never be impersonated
+        let principal = Principal::default();
+        assert!(principal.validate_not_impersonated().is_ok());
+    }
+}
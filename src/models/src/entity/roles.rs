@@ -1,4 +1,5 @@
 use crate::database::{Cache, DB};
+use crate::entity::admin_roles::AdminRoleEntity;
 use crate::entity::users::User;
 use hiqlite::{params, Param, Params};
 use rauthy_api_types::roles::NewRoleRequest;
@@ -102,6 +103,8 @@ impl Role {
             txn.commit().await?;
         }
 
+        AdminRoleEntity::delete_by_name(&role.name).await?;
+
         let roles = Role::find_all()
             .await?
             .into_iter()
@@ -202,6 +205,10 @@ impl Role {
             txn.commit().await?;
         }
 
+        if role.name != new_role.name {
+            AdminRoleEntity::rename(&role.name, &new_role.name).await?;
+        }
+
         let roles = Role::find_all()
             .await?
             .into_iter()
@@ -0,0 +1,200 @@
+use crate::database::{Cache, DB};
+use crate::entity::jwk::{JwkKeyPair, JwkKeyPairAlg};
+use chrono::Utc;
+use hiqlite::{params, Param};
+use openssl::asn1::Asn1Time;
+use openssl::bn::{BigNum, MsbOption};
+use openssl::hash::MessageDigest;
+use openssl::nid::Nid;
+use openssl::pkey::{PKey, Private};
+use openssl::x509::{X509NameBuilder, X509};
+use rauthy_common::constants::{CACHE_TTL_APP, IDX_SAML_CERT, IDX_SAML_CERTS};
+use rauthy_common::is_hiqlite;
+use rauthy_common::utils::base64_encode;
+use rauthy_error::{ErrorResponse, ErrorResponseType};
+use serde::{Deserialize, Serialize};
+use sqlx::{query, query_as, FromRow};
+use tracing::info;
+
+/// Validity of the self-signed SAML signing certificates. The certificate lifetime is
+/// decoupled from the JWK rotation, which will remove a certificate together with its key.
+const SAML_CERT_VALIDITY_DAYS: u32 = 3650;
+
+/// A self-signed X.509 certificate for an `RS256` JWK, which is used to sign SAML assertions.
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct SamlCert {
+    pub kid: String,
+    pub created_at: i64,
+    pub cert_der: Vec<u8>,
+}
+
+// CRUD
+impl SamlCert {
+    /// Returns all existing certificates. Certificates for rotated and cleaned up JWKs are
+    /// removed via the foreign key cascade.
+    pub async fn find_all() -> Result<Vec<Self>, ErrorResponse> {
+        let client = DB::client();
+        if let Some(slf) = client.get(Cache::App, IDX_SAML_CERTS).await? {
+            return Ok(slf);
+        }
+
+        let res = if is_hiqlite() {
+            client
+                .query_as(
+                    "SELECT * FROM saml_certs ORDER BY created_at DESC",
+                    params!(),
+                )
+                .await?
+        } else {
+            query_as!(Self, "SELECT * FROM saml_certs ORDER BY created_at DESC")
+                .fetch_all(DB::conn())
+                .await?
+        };
+
+        client
+            .put(Cache::App, IDX_SAML_CERTS, &res, CACHE_TTL_APP)
+            .await?;
+
+        Ok(res)
+    }
+
+    async fn find_by_kid(kid: String) -> Result<Option<Self>, ErrorResponse> {
+        let idx = format!("{}{}", IDX_SAML_CERT, kid);
+        let client = DB::client();
+        if let Some(slf) = client.get(Cache::App, &idx).await? {
+            return Ok(Some(slf));
+        }
+
+        let slf: Option<Self> = if is_hiqlite() {
+            client
+                .query_as_optional("SELECT * FROM saml_certs WHERE kid = $1", params!(kid))
+                .await?
+        } else {
+            query_as!(Self, "SELECT * FROM saml_certs WHERE kid = $1", kid)
+                .fetch_optional(DB::conn())
+                .await?
+        };
+
+        if let Some(slf) = &slf {
+            client.put(Cache::App, idx, slf, CACHE_TTL_APP).await?;
+        }
+
+        Ok(slf)
+    }
+
+    async fn insert(&self) -> Result<(), ErrorResponse> {
+        // with multiple nodes, we might race with another one generating a cert for the same key
+        if is_hiqlite() {
+            DB::client()
+                .execute(
+                    r#"
+INSERT INTO saml_certs (kid, created_at, cert_der)
+VALUES ($1, $2, $3)
+ON CONFLICT(kid) DO NOTHING"#,
+                    params!(self.kid.clone(), self.created_at, self.cert_der.clone()),
+                )
+                .await?;
+        } else {
+            query!(
+                r#"
+INSERT INTO saml_certs (kid, created_at, cert_der)
+VALUES ($1, $2, $3)
+ON CONFLICT(kid) DO NOTHING"#,
+                self.kid,
+                self.created_at,
+                self.cert_der,
+            )
+            .execute(DB::conn())
+            .await?;
+        }
+
+        DB::client().delete(Cache::App, IDX_SAML_CERTS).await?;
+
+        Ok(())
+    }
+
+    /// Returns the latest `RS256` key pair together with its certificate. The certificate will
+    /// be generated, if it does not exist yet.
+    pub async fn find_signing_key(
+        common_name: &str,
+    ) -> Result<(PKey<Private>, Self), ErrorResponse> {
+        let kp = JwkKeyPair::find_latest(JwkKeyPairAlg::RS256).await?;
+        let key = Self::private_key(&kp)?;
+
+        if let Some(slf) = Self::find_by_kid(kp.kid.clone()).await? {
+            return Ok((key, slf));
+        }
+
+        info!(
+            "Generating a new SAML signing certificate for kid {}",
+            kp.kid
+        );
+        let slf = Self::generate(&kp.kid, &key, common_name)?;
+        slf.insert().await?;
+
+        // make sure we use the same cert as all other nodes in case of a race
+        let slf = Self::find_by_kid(kp.kid).await?.unwrap_or(slf);
+        Ok((key, slf))
+    }
+}
+
+impl SamlCert {
+    /// The base64 encoded DER certificate, as it is used inside `<ds:X509Certificate>`
+    pub fn cert_b64(&self) -> String {
+        base64_encode(&self.cert_der)
+    }
+
+    fn generate(kid: &str, key: &PKey<Private>, common_name: &str) -> Result<Self, ErrorResponse> {
+        let cert_der = Self::build_self_signed(key, common_name).map_err(|err| {
+            ErrorResponse::new(
+                ErrorResponseType::Internal,
+                format!("Cannot build SAML signing certificate: {}", err),
+            )
+        })?;
+
+        Ok(Self {
+            kid: kid.to_string(),
+            created_at: Utc::now().timestamp(),
+            cert_der,
+        })
+    }
+
+    fn build_self_signed(
+        key: &PKey<Private>,
+        common_name: &str,
+    ) -> Result<Vec<u8>, openssl::error::ErrorStack> {
+        let mut name = X509NameBuilder::new()?;
+        name.append_entry_by_nid(Nid::COMMONNAME, common_name)?;
+        name.append_entry_by_nid(Nid::ORGANIZATIONNAME, "Rauthy SAML IdP")?;
+        let name = name.build();
+
+        let mut serial = BigNum::new()?;
+        serial.rand(128, MsbOption::MAYBE_ZERO, false)?;
+
+        let mut builder = X509::builder()?;
+        builder.set_version(2)?;
+        builder.set_serial_number(serial.to_asn1_integer()?.as_ref())?;
+        builder.set_subject_name(&name)?;
+        builder.set_issuer_name(&name)?;
+        builder.set_pubkey(key)?;
+        builder.set_not_before(Asn1Time::days_from_now(0)?.as_ref())?;
+        builder.set_not_after(Asn1Time::days_from_now(SAML_CERT_VALIDITY_DAYS)?.as_ref())?;
+        builder.sign(key, MessageDigest::sha256())?;
+
+        builder.build().to_der()
+    }
+
+    /// The JWK key pair bytes are DER, either in PKCS#1 or PKCS#8 format.
+    fn private_key(kp: &JwkKeyPair) -> Result<PKey<Private>, ErrorResponse> {
+        PKey::private_key_from_der(&kp.bytes)
+            .or_else(|_| {
+                openssl::rsa::Rsa::private_key_from_der(&kp.bytes).and_then(PKey::from_rsa)
+            })
+            .map_err(|err| {
+                ErrorResponse::new(
+                    ErrorResponseType::Internal,
+                    format!("Cannot read RS256 key for SAML signing: {}", err),
+                )
+            })
+    }
+}
@@ -414,11 +414,15 @@ impl Scope {
     }
 
     /// Returns `true` if the given scope is not a default OIDC scope.
-    /// Note: `groups` is not a default scope, but it will be handled like one for performance
-    /// and efficiency reasons.
+    /// Note: `groups` and `organization` are not default scopes, but they will be handled like
+    /// one for performance and efficiency reasons.
     #[inline]
     pub fn is_custom(scope: &str) -> bool {
-        scope != "openid" && scope != "profile" && scope != "email" && scope != "groups"
+        scope != "openid"
+            && scope != "profile"
+            && scope != "email"
+            && scope != "groups"
+            && scope != "organization"
     }
 }
 
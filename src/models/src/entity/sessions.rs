@@ -35,6 +35,10 @@ pub struct Session {
     pub exp: i64,
     pub last_seen: i64,
     pub remote_ip: Option<String>,
+    /// The user id of the admin, if this is an impersonated session
+    #[serde(default)]
+    #[sqlx(default)]
+    pub impersonated_by: Option<String>,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
@@ -435,11 +439,12 @@ OFFSET $2"#,
                 .execute(
                     r#"
 INSERT INTO
-sessions (id, csrf_token, user_id, roles, groups, is_mfa, state, exp, last_seen, remote_ip)
-VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+sessions (id, csrf_token, user_id, roles, groups, is_mfa, state, exp, last_seen, remote_ip,
+impersonated_by)
+VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
 ON CONFLICT(id) DO UPDATE
 SET user_id = $3, roles = $4, groups = $5, is_mfa = $6, state = $7, exp = $8, last_seen = $9,
-remote_ip = $10"#,
+remote_ip = $10, impersonated_by = $11"#,
                     params!(
                         &self.id,
                         &self.csrf_token,
@@ -450,7 +455,8 @@ remote_ip = $10"#,
                         state_str,
                         self.exp,
                         self.last_seen,
-                        &self.remote_ip
+                        &self.remote_ip,
+                        &self.impersonated_by
                     ),
                 )
                 .await?;
@@ -458,11 +464,12 @@ remote_ip = $10"#,
             sqlx::query!(
                 r#"
 INSERT INTO
-sessions (id, csrf_token, user_id, roles, groups, is_mfa, state, exp, last_seen, remote_ip)
-VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+sessions (id, csrf_token, user_id, roles, groups, is_mfa, state, exp, last_seen, remote_ip,
+impersonated_by)
+VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
 ON CONFLICT(id) DO UPDATE
 SET user_id = $3, roles = $4, groups = $5, is_mfa = $6, state = $7, exp = $8, last_seen = $9,
-remote_ip = $10"#,
+remote_ip = $10, impersonated_by = $11"#,
                 self.id,
                 self.csrf_token,
                 self.user_id,
@@ -473,6 +480,7 @@ remote_ip = $10"#,
                 self.exp,
                 self.last_seen,
                 self.remote_ip,
+                self.impersonated_by,
             )
             .execute(DB::conn())
             .await?;
@@ -579,6 +587,7 @@ impl Session {
                 .unix_timestamp(),
             last_seen: now.unix_timestamp(),
             remote_ip: remote_ip.map(|ip| ip.to_string()),
+            impersonated_by: None,
         }
     }
 
@@ -629,6 +638,7 @@ impl Session {
             exp,
             last_seen: now.unix_timestamp(),
             remote_ip,
+            impersonated_by: None,
         })
     }
 
@@ -703,6 +713,11 @@ impl Session {
         Ok(())
     }
 
+    #[inline(always)]
+    pub fn is_impersonated(&self) -> bool {
+        self.impersonated_by.is_some()
+    }
+
     #[inline(always)]
     pub fn state(&self) -> Result<SessionState, ErrorResponse> {
         SessionState::from_str(self.state.as_str())
@@ -0,0 +1,512 @@
+use crate::api_cookie::ApiCookie;
+use crate::app_state::AppState;
+use crate::database::{Cache, DB};
+use crate::email;
+use crate::entity::users::User;
+use crate::geoip;
+use crate::geoip::GeoLocation;
+use actix_web::cookie::Cookie;
+use actix_web::http::header;
+use actix_web::{web, HttpRequest};
+use chrono::Utc;
+use cidr::{IpCidr, IpInet};
+use hiqlite::{params, Param};
+use rand::Rng;
+use rauthy_api_types::users::TrustedDeviceResponse;
+use rauthy_common::constants::{
+    COOKIE_TRUSTED_DEVICE, IDX_LOGIN_EMAIL_CODE, MFA_TRUSTED_DEVICE_DAYS,
+};
+use rauthy_common::is_hiqlite;
+use rauthy_common::utils::{get_rand, real_ip_from_req};
+use rauthy_error::{ErrorResponse, ErrorResponseType};
+use ring::constant_time;
+use serde::{Deserialize, Serialize};
+use sqlx::{query, query_as, FromRow};
+use std::net::IpAddr;
+use tracing::{debug, info};
+
+/// Without an ASN database, a device is only trusted inside the same network range.
+const TRUST_RANGE_V4: u8 = 16;
+const TRUST_RANGE_V6: u8 = 48;
+
+const LOGIN_CODE_LIFETIME: i64 = 300;
+const LOGIN_CODE_MAX_ATTEMPTS: u8 = 5;
+/// A new code will only be sent out after this many seconds, the existing one stays valid.
+const LOGIN_CODE_RESEND_SECS: i64 = 60;
+
+/// A browser, which has been trusted by the user after a successful login with the 2nd factor.
+/// The trust is bound to the network it has been created from. A login from a new country,
+/// another ASN, or a different IP range without an ASN database, requires the MFA step again.
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct TrustedDevice {
+    pub id: String,
+    pub user_id: String,
+    pub name: String,
+    pub created: i64,
+    pub last_used: i64,
+    pub exp: i64,
+    pub ip: String,
+    pub country: Option<String>,
+    pub asn: Option<i64>,
+}
+
+// CRUD
+impl TrustedDevice {
+    pub async fn create(req: &HttpRequest, user_id: String) -> Result<Self, ErrorResponse> {
+        let ip = real_ip_from_req(req)?;
+        let location = geoip::lookup(ip).unwrap_or_default();
+        let name = device_name(
+            req.headers()
+                .get(header::USER_AGENT)
+                .and_then(|v| v.to_str().ok())
+                .unwrap_or_default(),
+        );
+
+        let now = Utc::now().timestamp();
+        let slf = Self {
+            id: get_rand(48),
+            user_id,
+            name,
+            created: now,
+            last_used: now,
+            exp: now + *MFA_TRUSTED_DEVICE_DAYS as i64 * 86400,
+            ip: ip.to_string(),
+            country: location.country,
+            asn: location.asn.map(i64::from),
+        };
+
+        if is_hiqlite() {
+            DB::client()
+                .execute(
+                    r#"
+INSERT INTO trusted_devices
+(id, user_id, name, created, last_used, exp, ip, country, asn)
+VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)"#,
+                    params!(
+                        slf.id.clone(),
+                        slf.user_id.clone(),
+                        slf.name.clone(),
+                        slf.created,
+                        slf.last_used,
+                        slf.exp,
+                        slf.ip.clone(),
+                        slf.country.clone(),
+                        slf.asn
+                    ),
+                )
+                .await?;
+        } else {
+            query!(
+                r#"
+INSERT INTO trusted_devices
+(id, user_id, name, created, last_used, exp, ip, country, asn)
+VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)"#,
+                slf.id,
+                slf.user_id,
+                slf.name,
+                slf.created,
+                slf.last_used,
+                slf.exp,
+                slf.ip,
+                slf.country,
+                slf.asn,
+            )
+            .execute(DB::conn())
+            .await?;
+        }
+
+        info!("New trusted device '{}' for user {}", slf.name, slf.user_id);
+        Ok(slf)
+    }
+
+    pub async fn find(id: &str) -> Result<Self, ErrorResponse> {
+        let slf = if is_hiqlite() {
+            DB::client()
+                .query_as_one("SELECT * FROM trusted_devices WHERE id = $1", params!(id))
+                .await?
+        } else {
+            query_as!(Self, "SELECT * FROM trusted_devices WHERE id = $1", id)
+                .fetch_one(DB::conn())
+                .await?
+        };
+        Ok(slf)
+    }
+
+    pub async fn find_for_user(user_id: &str) -> Result<Vec<Self>, ErrorResponse> {
+        let now = Utc::now().timestamp();
+
+        let res = if is_hiqlite() {
+            DB::client()
+                .query_as(
+                    "SELECT * FROM trusted_devices WHERE user_id = $1 AND exp > $2",
+                    params!(user_id, now),
+                )
+                .await?
+        } else {
+            query_as!(
+                Self,
+                "SELECT * FROM trusted_devices WHERE user_id = $1 AND exp > $2",
+                user_id,
+                now
+            )
+            .fetch_all(DB::conn())
+            .await?
+        };
+        Ok(res)
+    }
+
+    pub async fn delete(user_id: &str, id: &str) -> Result<(), ErrorResponse> {
+        if is_hiqlite() {
+            DB::client()
+                .execute(
+                    "DELETE FROM trusted_devices WHERE user_id = $1 AND id = $2",
+                    params!(user_id, id),
+                )
+                .await?;
+        } else {
+            query!(
+                "DELETE FROM trusted_devices WHERE user_id = $1 AND id = $2",
+                user_id,
+                id
+            )
+            .execute(DB::conn())
+            .await?;
+        }
+        Ok(())
+    }
+
+    pub async fn delete_for_user(user_id: &str) -> Result<(), ErrorResponse> {
+        if is_hiqlite() {
+            DB::client()
+                .execute(
+                    "DELETE FROM trusted_devices WHERE user_id = $1",
+                    params!(user_id),
+                )
+                .await?;
+        } else {
+            query!("DELETE FROM trusted_devices WHERE user_id = $1", user_id)
+                .execute(DB::conn())
+                .await?;
+        }
+        Ok(())
+    }
+
+    pub async fn delete_expired() -> Result<usize, ErrorResponse> {
+        let now = Utc::now().timestamp();
+
+        let rows_affected = if is_hiqlite() {
+            DB::client()
+                .execute("DELETE FROM trusted_devices WHERE exp < $1", params!(now))
+                .await?
+        } else {
+            query!("DELETE FROM trusted_devices WHERE exp < $1", now)
+                .execute(DB::conn())
+                .await?
+                .rows_affected() as usize
+        };
+
+        Ok(rows_affected)
+    }
+
+    async fn update_last_used(&mut self) -> Result<(), ErrorResponse> {
+        self.last_used = Utc::now().timestamp();
+
+        if is_hiqlite() {
+            DB::client()
+                .execute(
+                    "UPDATE trusted_devices SET last_used = $1 WHERE id = $2",
+                    params!(self.last_used, self.id.clone()),
+                )
+                .await?;
+        } else {
+            query!(
+                "UPDATE trusted_devices SET last_used = $1 WHERE id = $2",
+                self.last_used,
+                self.id,
+            )
+            .execute(DB::conn())
+            .await?;
+        }
+        Ok(())
+    }
+}
+
+impl TrustedDevice {
+    #[inline]
+    pub fn is_enabled() -> bool {
+        *MFA_TRUSTED_DEVICE_DAYS > 0
+    }
+
+    pub fn build_cookie(&self) -> Cookie<'static> {
+        let max_age = self.exp - Utc::now().timestamp();
+        ApiCookie::build(COOKIE_TRUSTED_DEVICE, self.id.clone(), max_age)
+    }
+
+    /// Returns the device ID from the trusted device cookie, if any exists.
+    pub fn id_from_req(req: &HttpRequest) -> Option<String> {
+        ApiCookie::from_req(req, COOKIE_TRUSTED_DEVICE)
+    }
+
+    /// Returns the trusted device from the request cookie, if it belongs to this user, has not
+    /// expired yet, and the request comes from the same network the trust has been given in.
+    pub async fn find_valid_from_req(req: &HttpRequest, user_id: &str) -> Option<Self> {
+        if !Self::is_enabled() {
+            return None;
+        }
+        let id = Self::id_from_req(req)?;
+        let ip = real_ip_from_req(req).ok()?;
+
+        let mut slf = Self::find(&id).await.ok()?;
+        if slf.user_id != user_id || slf.exp < Utc::now().timestamp() {
+            return None;
+        }
+        if !slf.is_trusted_location(ip, geoip::lookup(ip).as_ref()) {
+            debug!(
+                "Ignoring trusted device {} for user {} from a new location",
+                slf.id, slf.user_id
+            );
+            return None;
+        }
+
+        if let Err(err) = slf.update_last_used().await {
+            debug!("Cannot update trusted device last_used: {:?}", err);
+        }
+        Some(slf)
+    }
+
+    fn is_trusted_location(&self, ip: IpAddr, location: Option<&GeoLocation>) -> bool {
+        if let Some(country) = &self.country {
+            if location.and_then(|l| l.country.as_ref()) != Some(country) {
+                return false;
+            }
+        }
+
+        if let Some(asn) = self.asn {
+            return location.and_then(|l| l.asn).map(i64::from) == Some(asn);
+        }
+
+        match self.ip.parse::<IpAddr>() {
+            Ok(trusted_ip) => ip_range(trusted_ip) == ip_range(ip),
+            Err(_) => false,
+        }
+    }
+
+    pub fn into_response(self, current_id: Option<&str>) -> TrustedDeviceResponse {
+        TrustedDeviceResponse {
+            current: current_id == Some(self.id.as_str()),
+            id: self.id,
+            name: self.name,
+            created: self.created,
+            last_used: self.last_used,
+            exp: self.exp,
+            ip: self.ip,
+            country: self.country,
+        }
+    }
+}
+
+fn ip_range(ip: IpAddr) -> IpCidr {
+    let len = if ip.is_ipv4() {
+        TRUST_RANGE_V4
+    } else {
+        TRUST_RANGE_V6
+    };
+    IpInet::new(ip, len)
+        .expect("valid network length for the address family")
+        .network()
+}
+
+/// Builds a human-readable name like `Firefox on Linux` from the `User-Agent`. Trusted devices
+/// are never matched by their name, it only helps the user to recognize them.
+pub(crate) fn device_name(user_agent: &str) -> String {
+    // the order matters, because most browsers include the names of others for compatibility
+    let browser = [
+        ("Edg/", "Edge"),
+        ("OPR/", "Opera"),
+        ("Firefox/", "Firefox"),
+        ("Chrome/", "Chrome"),
+        ("Safari/", "Safari"),
+    ]
+    .into_iter()
+    .find(|(id, _)| user_agent.contains(id))
+    .map(|(_, name)| name);
+
+    let os = [
+        ("Android", "Android"),
+        ("iPhone", "iOS"),
+        ("iPad", "iPadOS"),
+        ("Windows", "Windows"),
+        ("Mac OS X", "macOS"),
+        ("Linux", "Linux"),
+    ]
+    .into_iter()
+    .find(|(id, _)| user_agent.contains(id))
+    .map(|(_, name)| name);
+
+    match (browser, os) {
+        (Some(browser), Some(os)) => format!("{} on {}", browser, os),
+        (Some(name), None) | (None, Some(name)) => name.to_string(),
+        (None, None) => "Unknown Device".to_string(),
+    }
+}
+
+/// One-time code sent via E-Mail to confirm logins from new devices for users without a
+/// passkey, if `MFA_NEW_DEVICE_EMAIL_CODE` is enabled.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LoginEmailCode {
+    code: String,
+    attempts: u8,
+    created: i64,
+}
+
+impl LoginEmailCode {
+    /// Generates a new code for the user, which replaces any existing one, and sends it out.
+    ///
+    /// A code, which has been sent out less than `LOGIN_CODE_RESEND_SECS` ago, will be kept
+    /// without sending another E-Mail. Invalid attempts are carried over to the new code, and
+    /// once they are used up, no new code is sent out until the old one has expired.
+    pub async fn send(data: &web::Data<AppState>, user: &User) -> Result<(), ErrorResponse> {
+        let client = DB::client();
+        let idx = format!("{}{}", IDX_LOGIN_EMAIL_CODE, user.id);
+        let now = Utc::now().timestamp();
+
+        let existing: Option<Self> = client.get(Cache::App, &idx).await?;
+        let attempts = match existing {
+            Some(existing) if existing.attempts >= LOGIN_CODE_MAX_ATTEMPTS => {
+                return Err(ErrorResponse::new(
+                    ErrorResponseType::TooManyRequests(existing.created + LOGIN_CODE_LIFETIME),
+                    "Too many invalid E-Mail codes",
+                ));
+            }
+            Some(existing) if existing.created > now - LOGIN_CODE_RESEND_SECS => {
+                return Ok(());
+            }
+            Some(existing) => existing.attempts,
+            None => 0,
+        };
+
+        let slf = Self {
+            code: format!("{:06}", rand::thread_rng().gen_range(0..1_000_000)),
+            attempts,
+            created: now,
+        };
+        client
+            .put(Cache::App, idx, &slf, Some(LOGIN_CODE_LIFETIME))
+            .await?;
+
+        email::send_login_code(data, user, &slf.code, now + LOGIN_CODE_LIFETIME).await;
+        Ok(())
+    }
+
+    /// Validates and consumes the code. Returns `Ok(false)` if no usable code exists, because
+    /// it has expired or all attempts have been used up. A new one must be sent out in that
+    /// case.
+    pub async fn validate(user_id: &str, code: &str) -> Result<bool, ErrorResponse> {
+        let client = DB::client();
+        let idx = format!("{}{}", IDX_LOGIN_EMAIL_CODE, user_id);
+
+        let slf: Option<Self> = client.get(Cache::App, &idx).await?;
+        let Some(mut slf) = slf else {
+            return Ok(false);
+        };
+        if slf.attempts >= LOGIN_CODE_MAX_ATTEMPTS {
+            return Ok(false);
+        }
+
+        if constant_time::verify_slices_are_equal(slf.code.as_bytes(), code.as_bytes()).is_ok() {
+            client.delete(Cache::App, idx).await?;
+            return Ok(true);
+        }
+
+        // an exhausted code is kept until it expires, so `send` cannot reset the attempts
+        slf.attempts += 1;
+        let ttl = slf.created + LOGIN_CODE_LIFETIME - Utc::now().timestamp();
+        if ttl > 0 {
+            client.put(Cache::App, idx, &slf, Some(ttl)).await?;
+        }
+        Err(ErrorResponse::new(
+            ErrorResponseType::Unauthorized,
+            "Invalid E-Mail code",
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn device(ip: &str, country: Option<&str>, asn: Option<i64>) -> TrustedDevice {
+        TrustedDevice {
+            id: "id".to_string(),
+            user_id: "user".to_string(),
+            name: "Firefox on Linux".to_string(),
+            created: 0,
+            last_used: 0,
+            exp: 0,
+            ip: ip.to_string(),
+            country: country.map(String::from),
+            asn,
+        }
+    }
+
+    fn location(country: Option<&str>, asn: Option<u32>) -> GeoLocation {
+        GeoLocation {
+            country: country.map(String::from),
+            asn,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_trusted_location() {
+        let ip = |ip: &str| ip.parse::<IpAddr>().unwrap();
+
+        // without any GeoIP data, the IP range decides
+        let d = device("192.168.10.20", None, None);
+        assert!(d.is_trusted_location(ip("192.168.200.1"), None));
+        assert!(!d.is_trusted_location(ip("192.169.10.20"), None));
+
+        let d = device("2001:db8:1:1::1", None, None);
+        assert!(d.is_trusted_location(ip("2001:db8:1:ffff::1"), None));
+        assert!(!d.is_trusted_location(ip("2001:db8:2::1"), None));
+        assert!(!d.is_trusted_location(ip("192.168.10.20"), None));
+
+        // a new country always requires the 2nd factor
+        let d = device("192.168.10.20", Some("DE"), None);
+        let de = location(Some("DE"), None);
+        assert!(d.is_trusted_location(ip("192.168.10.1"), Some(&de)));
+        assert!(!d.is_trusted_location(ip("192.168.10.1"), None));
+        let at = location(Some("AT"), None);
+        assert!(!d.is_trusted_location(ip("192.168.10.1"), Some(&at)));
+
+        // with an ASN, the IP may change freely inside the same network
+        let d = device("192.168.10.20", Some("DE"), Some(3320));
+        let same_asn = location(Some("DE"), Some(3320));
+        assert!(d.is_trusted_location(ip("10.0.0.1"), Some(&same_asn)));
+        let other_asn = location(Some("DE"), Some(6805));
+        assert!(!d.is_trusted_location(ip("192.168.10.20"), Some(&other_asn)));
+    }
+
+    #[test]
+    fn test_device_name() {
+        assert_eq!(
+            device_name("Mozilla/5.0 (X11; Linux x86_64; rv:131.0) Gecko/20100101 Firefox/131.0"),
+            "Firefox on Linux"
+        );
+        assert_eq!(
+            device_name(
+                "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) \
+                Chrome/129.0.0.0 Safari/537.36 Edg/129.0.0.0"
+            ),
+            "Edge on Windows"
+        );
+        assert_eq!(
+            device_name(
+                "Mozilla/5.0 (iPhone; CPU iPhone OS 17_6 like Mac OS X) AppleWebKit/605.1.15 \
+                (KHTML, like Gecko) Version/17.6 Mobile/15E148 Safari/604.1"
+            ),
+            "Safari on iOS"
+        );
+        assert_eq!(device_name("curl/8.10.1"), "Unknown Device");
+    }
+}
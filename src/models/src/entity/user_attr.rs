@@ -4,8 +4,8 @@ use crate::entity::scopes::Scope;
 use crate::entity::users::User;
 use hiqlite::{params, Param, Params};
 use rauthy_api_types::users::{
-    UserAttrConfigRequest, UserAttrConfigValueResponse, UserAttrValueResponse,
-    UserAttrValuesUpdateRequest,
+    UserAttrConfigRequest, UserAttrConfigValueResponse, UserAttrValueRequest,
+    UserAttrValueResponse, UserAttrValuesUpdateRequest,
 };
 use rauthy_common::constants::{CACHE_TTL_APP, CACHE_TTL_USER, IDX_USER_ATTR_CONFIG};
 use rauthy_common::is_hiqlite;
@@ -21,6 +21,8 @@ use std::collections::HashSet;
 pub struct UserAttrConfigEntity {
     pub name: String,
     pub desc: Option<String>,
+    /// If `true`, a value for this attribute must be provided during the open user registration
+    pub registration: bool,
 }
 
 // CRUD
@@ -45,18 +47,24 @@ impl UserAttrConfigEntity {
             ));
         }
 
+        let registration = new_attr.registration.unwrap_or(false);
         if is_hiqlite() {
             DB::client()
                 .execute(
-                    "INSERT INTO user_attr_config (name, \"desc\") VALUES ($1, $2)",
-                    params!(&new_attr.name, &new_attr.desc),
+                    r#"
+INSERT INTO user_attr_config (name, "desc", registration)
+VALUES ($1, $2, $3)"#,
+                    params!(&new_attr.name, &new_attr.desc, registration),
                 )
                 .await?;
         } else {
             sqlx::query!(
-                "INSERT INTO user_attr_config (name, \"desc\") VALUES ($1, $2)",
+                r#"
+INSERT INTO user_attr_config (name, "desc", registration)
+VALUES ($1, $2, $3)"#,
                 new_attr.name,
                 new_attr.desc,
+                registration,
             )
             .execute(DB::conn())
             .await?;
@@ -66,6 +74,7 @@ impl UserAttrConfigEntity {
         let slf = Self {
             name: new_attr.name.clone(),
             desc: new_attr.desc.clone(),
+            registration,
         };
         attrs.push(slf.clone());
         DB::client()
@@ -235,6 +244,7 @@ impl UserAttrConfigEntity {
 
         slf.name.clone_from(&req_data.name);
         slf.desc.clone_from(&req_data.desc);
+        slf.registration = req_data.registration.unwrap_or(false);
 
         let client = DB::client();
         let mut scope_updates = Vec::new();
@@ -316,8 +326,11 @@ impl UserAttrConfigEntity {
             // need another user_attr_values update here
 
             txn.push((
-                "UPDATE user_attr_config SET name  = $1, \"desc\" = $2 WHERE name = $3",
-                params!(&slf.name, &slf.desc, name),
+                r#"
+UPDATE user_attr_config
+SET name  = $1, "desc" = $2, registration = $3
+WHERE name = $4"#,
+                params!(&slf.name, &slf.desc, slf.registration, name),
             ));
 
             client.txn(txn).await?;
@@ -333,9 +346,13 @@ impl UserAttrConfigEntity {
             // need another user_attr_values update here
 
             sqlx::query!(
-                "UPDATE user_attr_config SET name  = $1, \"desc\" = $2 WHERE name = $3",
+                r#"
+UPDATE user_attr_config
+SET name  = $1, "desc" = $2, registration = $3
+WHERE name = $4"#,
                 slf.name,
                 slf.desc,
+                slf.registration,
                 name,
             )
             .execute(&mut *txn)
@@ -367,6 +384,64 @@ impl UserAttrConfigEntity {
         Ok(set)
     }
 
+    /// Returns all attributes, that must be provided during the open user registration
+    pub async fn find_for_registration() -> Result<Vec<Self>, ErrorResponse> {
+        let res = Self::find_all()
+            .await?
+            .into_iter()
+            .filter(|a| a.registration)
+            .collect();
+        Ok(res)
+    }
+
+    /// Validates the attribute values from an open user registration.
+    ///
+    /// All attributes flagged for the registration must have a non-empty value and no other
+    /// attributes are allowed, because the user could set any value otherwise.
+    pub async fn validate_registration_values(
+        values: &[UserAttrValueRequest],
+    ) -> Result<(), ErrorResponse> {
+        let attrs = Self::find_for_registration().await?;
+        Self::check_registration_values(&attrs, values)
+    }
+
+    fn check_registration_values(
+        attrs: &[Self],
+        values: &[UserAttrValueRequest],
+    ) -> Result<(), ErrorResponse> {
+        if let Some(value) = values
+            .iter()
+            .find(|v| !attrs.iter().any(|a| a.name == v.key))
+        {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                format!(
+                    "Attribute '{}' is not allowed during registration",
+                    value.key
+                ),
+            ));
+        }
+
+        for attr in attrs {
+            let is_empty = match values.iter().find(|v| v.key == attr.name) {
+                None => true,
+                Some(v) => match &v.value {
+                    Value::Null => true,
+                    Value::String(s) => s.trim().is_empty(),
+                    _ => false,
+                },
+            };
+            if is_empty {
+                return Err(ErrorResponse::new(
+                    ErrorResponseType::BadRequest,
+                    format!("Attribute '{}' is required", attr.name),
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
     pub fn names_hash_set(mut slf: Vec<Self>) -> HashSet<String> {
         let mut res = HashSet::with_capacity(slf.len());
         slf.drain(..).for_each(|s| {
@@ -381,6 +456,7 @@ impl From<UserAttrConfigEntity> for UserAttrConfigValueResponse {
         Self {
             name: value.name,
             desc: value.desc,
+            registration: value.registration,
         }
     }
 }
@@ -566,7 +642,7 @@ ON CONFLICT(user_id, key) DO UPDATE SET value = $3"#,
 
 impl UserAttrValueEntity {
     #[inline]
-    fn cache_idx(user_id: &str) -> String {
+    pub(crate) fn cache_idx(user_id: &str) -> String {
         format!("{}{}", IDX_USER_ATTR_CONFIG, user_id)
     }
 }
@@ -580,3 +656,68 @@ impl From<UserAttrValueEntity> for UserAttrValueResponse {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_check_registration_values() {
+        let attrs = vec![UserAttrConfigEntity {
+            name: "employee_id".to_string(),
+            desc: None,
+            registration: true,
+        }];
+        let value = |key: &str, value: Value| UserAttrValueRequest {
+            key: key.to_string(),
+            value,
+        };
+
+        assert!(UserAttrConfigEntity::check_registration_values(
+            &attrs,
+            &[value("employee_id", json!("1337"))]
+        )
+        .is_ok());
+        assert!(UserAttrConfigEntity::check_registration_values(
+            &attrs,
+            &[value("employee_id", json!(1337))]
+        )
+        .is_ok());
+
+        // missing or empty required values
+        let err = UserAttrConfigEntity::check_registration_values(&attrs, &[]).unwrap_err();
+        assert_eq!(err.error, ErrorResponseType::BadRequest);
+        assert_eq!(err.message, "Attribute 'employee_id' is required");
+        for empty in [json!(null), json!(""), json!("  ")] {
+            assert!(UserAttrConfigEntity::check_registration_values(
+                &attrs,
+                &[value("employee_id", empty)]
+            )
+            .is_err());
+        }
+
+        // attributes not flagged for the registration must be rejected
+        let err = UserAttrConfigEntity::check_registration_values(
+            &attrs,
+            &[
+                value("employee_id", json!("1337")),
+                value("is_admin", json!(true)),
+            ],
+        )
+        .unwrap_err();
+        assert_eq!(err.error, ErrorResponseType::BadRequest);
+        assert_eq!(
+            err.message,
+            "Attribute 'is_admin' is not allowed during registration"
+        );
+
+        // without any flagged attributes, nothing must be given at all
+        assert!(UserAttrConfigEntity::check_registration_values(&[], &[]).is_ok());
+        assert!(UserAttrConfigEntity::check_registration_values(
+            &[],
+            &[value("employee_id", json!("1337"))]
+        )
+        .is_err());
+    }
+}
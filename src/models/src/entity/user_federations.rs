@@ -0,0 +1,237 @@
+use crate::database::DB;
+use crate::entity::auth_providers::AuthProvider;
+use chrono::Utc;
+use hiqlite::{params, Param};
+use rauthy_api_types::users::UserFederationResponse;
+use rauthy_common::is_hiqlite;
+use rauthy_error::ErrorResponse;
+use serde::{Deserialize, Serialize};
+use sqlx::{query, query_as, FromRow};
+
+/// A link between a user and an upstream identity. A user can have a single identity for each
+/// auth provider, but multiple providers at the same time.
+///
+/// The `auth_provider_id` and `federation_uid` on the user itself always mirror the primary
+/// identity, which keeps the E-Mail and names in sync.
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct UserFederation {
+    pub user_id: String,
+    pub provider_id: String,
+    pub federation_uid: String,
+    pub created_at: i64,
+    pub last_login: Option<i64>,
+}
+
+// CRUD
+impl UserFederation {
+    pub async fn create(
+        user_id: String,
+        provider_id: String,
+        federation_uid: String,
+    ) -> Result<Self, ErrorResponse> {
+        let slf = Self {
+            user_id,
+            provider_id,
+            federation_uid,
+            created_at: Utc::now().timestamp(),
+            last_login: None,
+        };
+
+        if is_hiqlite() {
+            DB::client()
+                .execute(
+                    r#"
+INSERT INTO user_federations (user_id, provider_id, federation_uid, created_at)
+VALUES ($1, $2, $3, $4)"#,
+                    params!(
+                        slf.user_id.clone(),
+                        slf.provider_id.clone(),
+                        slf.federation_uid.clone(),
+                        slf.created_at
+                    ),
+                )
+                .await?;
+        } else {
+            query!(
+                r#"
+INSERT INTO user_federations (user_id, provider_id, federation_uid, created_at)
+VALUES ($1, $2, $3, $4)"#,
+                slf.user_id,
+                slf.provider_id,
+                slf.federation_uid,
+                slf.created_at,
+            )
+            .execute(DB::conn())
+            .await?;
+        }
+
+        Ok(slf)
+    }
+
+    /// Inserts or overwrites the link with all values as they are. Only used for user imports.
+    pub async fn upsert(&self) -> Result<(), ErrorResponse> {
+        if is_hiqlite() {
+            DB::client()
+                .execute(
+                    r#"
+INSERT INTO user_federations (user_id, provider_id, federation_uid, created_at, last_login)
+VALUES ($1, $2, $3, $4, $5)
+ON CONFLICT(provider_id, federation_uid) DO UPDATE
+SET user_id = $1, created_at = $4, last_login = $5"#,
+                    params!(
+                        &self.user_id,
+                        &self.provider_id,
+                        &self.federation_uid,
+                        self.created_at,
+                        self.last_login
+                    ),
+                )
+                .await?;
+        } else {
+            query!(
+                r#"
+INSERT INTO user_federations (user_id, provider_id, federation_uid, created_at, last_login)
+VALUES ($1, $2, $3, $4, $5)
+ON CONFLICT(provider_id, federation_uid) DO UPDATE
+SET user_id = $1, created_at = $4, last_login = $5"#,
+                self.user_id,
+                self.provider_id,
+                self.federation_uid,
+                self.created_at,
+                self.last_login,
+            )
+            .execute(DB::conn())
+            .await?;
+        }
+
+        Ok(())
+    }
+
+    pub async fn delete(user_id: &str, provider_id: &str) -> Result<(), ErrorResponse> {
+        if is_hiqlite() {
+            DB::client()
+                .execute(
+                    "DELETE FROM user_federations WHERE user_id = $1 AND provider_id = $2",
+                    params!(user_id, provider_id),
+                )
+                .await?;
+        } else {
+            query!(
+                "DELETE FROM user_federations WHERE user_id = $1 AND provider_id = $2",
+                user_id,
+                provider_id
+            )
+            .execute(DB::conn())
+            .await?;
+        }
+
+        Ok(())
+    }
+
+    pub async fn find(provider_id: &str, federation_uid: &str) -> Result<Self, ErrorResponse> {
+        let res = if is_hiqlite() {
+            DB::client()
+                .query_as_one(
+                    "SELECT * FROM user_federations WHERE provider_id = $1 AND federation_uid = $2",
+                    params!(provider_id, federation_uid),
+                )
+                .await?
+        } else {
+            query_as!(
+                Self,
+                "SELECT * FROM user_federations WHERE provider_id = $1 AND federation_uid = $2",
+                provider_id,
+                federation_uid
+            )
+            .fetch_one(DB::conn())
+            .await?
+        };
+
+        Ok(res)
+    }
+
+    pub async fn find_for_user(user_id: &str) -> Result<Vec<Self>, ErrorResponse> {
+        let res = if is_hiqlite() {
+            DB::client()
+                .query_as(
+                    "SELECT * FROM user_federations WHERE user_id = $1 ORDER BY created_at ASC",
+                    params!(user_id),
+                )
+                .await?
+        } else {
+            query_as!(
+                Self,
+                "SELECT * FROM user_federations WHERE user_id = $1 ORDER BY created_at ASC",
+                user_id
+            )
+            .fetch_all(DB::conn())
+            .await?
+        };
+
+        Ok(res)
+    }
+
+    pub async fn update_last_login(&self) -> Result<(), ErrorResponse> {
+        let now = Utc::now().timestamp();
+
+        if is_hiqlite() {
+            DB::client()
+                .execute(
+                    r#"
+UPDATE user_federations SET last_login = $1
+WHERE provider_id = $2 AND federation_uid = $3"#,
+                    params!(now, self.provider_id.clone(), self.federation_uid.clone()),
+                )
+                .await?;
+        } else {
+            query!(
+                r#"
+UPDATE user_federations SET last_login = $1
+WHERE provider_id = $2 AND federation_uid = $3"#,
+                now,
+                self.provider_id,
+                self.federation_uid,
+            )
+            .execute(DB::conn())
+            .await?;
+        }
+
+        Ok(())
+    }
+}
+
+impl UserFederation {
+    /// Returns all linked identities for the user with the name of each provider.
+    pub async fn find_for_user_response(
+        user_id: &str,
+        primary_provider_id: Option<&str>,
+    ) -> Result<Vec<UserFederationResponse>, ErrorResponse> {
+        let links = Self::find_for_user(user_id).await?;
+        if links.is_empty() {
+            return Ok(Vec::default());
+        }
+
+        let providers = AuthProvider::find_all().await?;
+        let res = links
+            .into_iter()
+            .map(|link| {
+                let provider_name = providers
+                    .iter()
+                    .find(|p| p.id == link.provider_id)
+                    .map(|p| p.name.clone())
+                    .unwrap_or_default();
+
+                UserFederationResponse {
+                    is_primary: primary_provider_id == Some(link.provider_id.as_str()),
+                    provider_id: link.provider_id,
+                    provider_name,
+                    federation_uid: link.federation_uid,
+                    created_at: link.created_at,
+                    last_login: link.last_login,
+                }
+            })
+            .collect();
+
+        Ok(res)
+    }
+}
@@ -0,0 +1,580 @@
+use crate::app_state::AppState;
+use crate::database::DB;
+use crate::email::send_pwd_reset;
+use crate::entity::groups::Group;
+use crate::entity::magic_links::{MagicLink, MagicLinkUsage};
+use crate::entity::organizations::{OrgMember, Organization};
+use crate::entity::roles::Role;
+use crate::entity::user_attr::{UserAttrConfigEntity, UserAttrValueEntity};
+use crate::entity::users::User;
+use actix_web::web;
+use chrono::Utc;
+use hiqlite::{params, Param};
+use rauthy_api_types::invitations::{UserInvitationRequest, UserInvitationResponse};
+use rauthy_api_types::users::{UserAttrValueRequest, UserAttrValueResponse};
+use rauthy_common::is_hiqlite;
+use rauthy_common::utils::new_store_id;
+use rauthy_error::{ErrorResponse, ErrorResponseType};
+use serde::{Deserialize, Serialize};
+use sqlx::{query, query_as, FromRow};
+
+/// An invitation for a new user with pre-assigned roles, groups, attributes and an optional
+/// organization membership.
+///
+/// The user account is created in a pending state right away, but all assignments are only
+/// applied after the invited user has finished the account setup via the magic link.
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct UserInvitation {
+    pub id: String,
+    pub user_id: String,
+    pub email: String,
+    /// Comma separated list of roles
+    pub roles: String,
+    /// Comma separated list of groups
+    pub groups: Option<String>,
+    /// JSON serialized `Vec<UserAttrValueRequest>`
+    pub attributes: Option<Vec<u8>>,
+    pub org_id: Option<String>,
+    /// Comma separated list of org roles
+    pub org_roles: Option<String>,
+    pub redirect_uri: Option<String>,
+    pub created_by: String,
+    pub created_at: i64,
+    pub exp: i64,
+    pub accepted_at: Option<i64>,
+}
+
+// CRUD
+impl UserInvitation {
+    /// Creates the invitation together with the pending user account and sends out the
+    /// E-Mail with the magic link for the account setup.
+    ///
+    /// The caller MUST validate, that the creator is allowed to assign the requested values.
+    pub async fn create(
+        data: &web::Data<AppState>,
+        req: UserInvitationRequest,
+        created_by: String,
+    ) -> Result<(Self, User), ErrorResponse> {
+        let email = req.email.to_lowercase();
+        User::is_email_free(email.clone()).await?;
+
+        let now = Utc::now().timestamp();
+        let exp = req
+            .exp
+            .unwrap_or_else(|| now + data.ml_lt_pwd_first as i64 * 60);
+        if exp <= now {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                "The invitation expiry must be in the future",
+            ));
+        }
+
+        let roles = Role::sanitize(req.roles.unwrap_or_default()).await?;
+        let groups = Group::sanitize(req.groups).await?;
+
+        let attributes = match req.attributes {
+            Some(values) if !values.is_empty() => {
+                let existing = UserAttrConfigEntity::find_all_as_set().await?;
+                if let Some(value) = values.iter().find(|v| !existing.contains(&v.key)) {
+                    return Err(ErrorResponse::new(
+                        ErrorResponseType::BadRequest,
+                        format!("User attribute '{}' does not exist", value.key),
+                    ));
+                }
+                Some(serde_json::to_vec(&values)?)
+            }
+            _ => None,
+        };
+
+        let (org_id, org_roles) = match req.org_id {
+            None => (None, None),
+            Some(org_id) => {
+                let org = Organization::find(&org_id).await?;
+                let member = OrgMember::try_build(
+                    &org,
+                    String::default(),
+                    req.org_roles.unwrap_or_default(),
+                    false,
+                )?;
+                (Some(org.id), Some(member.roles))
+            }
+        };
+
+        let new_user = User {
+            email: email.clone(),
+            given_name: req.given_name,
+            family_name: req.family_name,
+            language: req.language.map(|l| l.into()).unwrap_or_default(),
+            ..Default::default()
+        };
+        let user = User::insert(new_user).await?;
+
+        let slf = Self {
+            id: new_store_id(),
+            user_id: user.id.clone(),
+            email,
+            roles,
+            groups,
+            attributes,
+            org_id,
+            org_roles,
+            redirect_uri: req.redirect_uri,
+            created_by,
+            created_at: now,
+            exp,
+            accepted_at: None,
+        };
+
+        if let Err(err) = slf.insert().await {
+            // do not leave an orphaned, pending user behind
+            user.delete().await?;
+            return Err(err);
+        }
+
+        slf.send_magic_link(data, &user).await?;
+
+        Ok((slf, user))
+    }
+
+    async fn insert(&self) -> Result<(), ErrorResponse> {
+        if is_hiqlite() {
+            DB::client()
+                .execute(
+                    r#"
+INSERT INTO user_invitations
+(id, user_id, email, roles, groups, attributes, org_id, org_roles, redirect_uri, created_by,
+created_at, exp, accepted_at)
+VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13)"#,
+                    params!(
+                        self.id.clone(),
+                        self.user_id.clone(),
+                        self.email.clone(),
+                        self.roles.clone(),
+                        self.groups.clone(),
+                        self.attributes.clone(),
+                        self.org_id.clone(),
+                        self.org_roles.clone(),
+                        self.redirect_uri.clone(),
+                        self.created_by.clone(),
+                        self.created_at,
+                        self.exp,
+                        self.accepted_at
+                    ),
+                )
+                .await?;
+        } else {
+            query!(
+                r#"
+INSERT INTO user_invitations
+(id, user_id, email, roles, groups, attributes, org_id, org_roles, redirect_uri, created_by,
+created_at, exp, accepted_at)
+VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13)"#,
+                self.id,
+                self.user_id,
+                self.email,
+                self.roles,
+                self.groups,
+                self.attributes,
+                self.org_id,
+                self.org_roles,
+                self.redirect_uri,
+                self.created_by,
+                self.created_at,
+                self.exp,
+                self.accepted_at,
+            )
+            .execute(DB::conn())
+            .await?;
+        }
+
+        Ok(())
+    }
+
+    pub async fn find(id: &str) -> Result<Self, ErrorResponse> {
+        let res = if is_hiqlite() {
+            DB::client()
+                .query_as_one("SELECT * FROM user_invitations WHERE id = $1", params!(id))
+                .await?
+        } else {
+            query_as!(Self, "SELECT * FROM user_invitations WHERE id = $1", id)
+                .fetch_one(DB::conn())
+                .await?
+        };
+
+        Ok(res)
+    }
+
+    pub async fn find_all() -> Result<Vec<Self>, ErrorResponse> {
+        let res = if is_hiqlite() {
+            DB::client()
+                .query_as(
+                    "SELECT * FROM user_invitations ORDER BY created_at DESC",
+                    params!(),
+                )
+                .await?
+        } else {
+            query_as!(
+                Self,
+                "SELECT * FROM user_invitations ORDER BY created_at DESC"
+            )
+            .fetch_all(DB::conn())
+            .await?
+        };
+
+        Ok(res)
+    }
+
+    /// Returns the invitation for the given user, if it has not been accepted yet.
+    pub async fn find_pending_for_user(user_id: &str) -> Result<Option<Self>, ErrorResponse> {
+        let res = if is_hiqlite() {
+            DB::client()
+                .query_as(
+                    r#"
+SELECT * FROM user_invitations
+WHERE user_id = $1 AND accepted_at IS NULL"#,
+                    params!(user_id),
+                )
+                .await?
+        } else {
+            query_as!(
+                Self,
+                r#"
+SELECT * FROM user_invitations
+WHERE user_id = $1 AND accepted_at IS NULL"#,
+                user_id
+            )
+            .fetch_all(DB::conn())
+            .await?
+        };
+
+        Ok(res.into_iter().next())
+    }
+
+    /// Revokes a pending invitation. This deletes the pending user account, which will remove
+    /// the invitation itself via the `ON DELETE CASCADE`.
+    pub async fn revoke(&self) -> Result<(), ErrorResponse> {
+        self.validate_pending()?;
+
+        let user = User::find(self.user_id.clone()).await?;
+        if user.password.is_some() || user.has_webauthn_enabled() {
+            // should never happen, but never delete an account that has been set up already
+            return Err(ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                "The invited user has already set up the account",
+            ));
+        }
+        user.delete().await
+    }
+
+    /// Invalidates any existing magic link and sends out a new one for the remaining lifetime
+    /// of the invitation.
+    pub async fn resend(&self, data: &web::Data<AppState>) -> Result<(), ErrorResponse> {
+        self.validate_pending()?;
+        self.validate_not_expired()?;
+
+        if let Ok(mut ml) = MagicLink::find_by_user(self.user_id.clone()).await {
+            if ml.exp > Utc::now().timestamp() {
+                ml.invalidate().await?;
+            }
+        }
+
+        let user = User::find(self.user_id.clone()).await?;
+        self.send_magic_link(data, &user).await
+    }
+
+    /// Applies all pre-assigned values to the invited user inside a single transaction and marks
+    /// the invitation as accepted. The given `user` is saved in the same transaction, which
+    /// makes it possible to set new credentials at the same time.
+    ///
+    /// Must only be called after the magic link for this invitation has been validated.
+    pub async fn accept(mut self, mut user: User) -> Result<Self, ErrorResponse> {
+        self.validate_pending()?;
+        self.validate_not_expired()?;
+
+        if user.id != self.user_id {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::Forbidden,
+                "The invitation belongs to another user",
+            ));
+        }
+
+        // roles, groups, attributes or org roles may have been deleted in the meantime
+        user.roles = Role::sanitize(
+            user.get_roles()
+                .into_iter()
+                .chain(self.get_roles())
+                .collect(),
+        )
+        .await?;
+        user.groups = Group::sanitize(Some(
+            user.get_groups()
+                .into_iter()
+                .chain(self.get_groups())
+                .collect(),
+        ))
+        .await?;
+
+        let existing_attrs = UserAttrConfigEntity::find_all_as_set().await?;
+        let mut attributes = Vec::new();
+        for attr in self.get_attributes()? {
+            if existing_attrs.contains(&attr.key) {
+                attributes.push((attr.key, serde_json::to_vec(&attr.value)?));
+            }
+        }
+
+        let member = match &self.org_id {
+            None => None,
+            Some(org_id) => {
+                let org = Organization::find(org_id).await?;
+                let org_roles = org.get_roles();
+                let roles = self
+                    .get_org_roles()
+                    .into_iter()
+                    .filter(|r| org_roles.contains(r))
+                    .collect();
+                Some(OrgMember::try_build(&org, user.id.clone(), roles, false)?)
+            }
+        };
+
+        let now = Utc::now().timestamp();
+        self.accepted_at = Some(now);
+
+        if is_hiqlite() {
+            let mut txn = Vec::with_capacity(3 + attributes.len());
+
+            user.clone().save_txn_append(&mut txn);
+            for (key, value) in attributes {
+                txn.push((
+                    r#"
+INSERT INTO user_attr_values (user_id, key, value)
+VALUES ($1, $2, $3)
+ON CONFLICT(user_id, key) DO UPDATE SET value = $3"#,
+                    params!(user.id.clone(), key, value),
+                ));
+            }
+            if let Some(member) = &member {
+                txn.push((
+                    r#"
+INSERT INTO org_members (org_id, user_id, roles, is_org_admin)
+VALUES ($1, $2, $3, $4)
+ON CONFLICT(org_id, user_id) DO UPDATE SET roles = $3"#,
+                    params!(
+                        member.org_id.clone(),
+                        member.user_id.clone(),
+                        member.roles.clone(),
+                        member.is_org_admin
+                    ),
+                ));
+            }
+            txn.push((
+                "UPDATE user_invitations SET accepted_at = $1 WHERE id = $2",
+                params!(now, self.id.clone()),
+            ));
+
+            for res in DB::client().txn(txn).await? {
+                res?;
+            }
+        } else {
+            let mut txn = DB::txn().await?;
+
+            user.save_txn(&mut txn).await?;
+            for (key, value) in attributes {
+                query!(
+                    r#"
+INSERT INTO user_attr_values (user_id, key, value)
+VALUES ($1, $2, $3)
+ON CONFLICT(user_id, key) DO UPDATE SET value = $3"#,
+                    user.id,
+                    key,
+                    value,
+                )
+                .execute(&mut *txn)
+                .await?;
+            }
+            if let Some(member) = &member {
+                query!(
+                    r#"
+INSERT INTO org_members (org_id, user_id, roles, is_org_admin)
+VALUES ($1, $2, $3, $4)
+ON CONFLICT(org_id, user_id) DO UPDATE SET roles = $3"#,
+                    member.org_id,
+                    member.user_id,
+                    member.roles,
+                    member.is_org_admin,
+                )
+                .execute(&mut *txn)
+                .await?;
+            }
+            query!(
+                "UPDATE user_invitations SET accepted_at = $1 WHERE id = $2",
+                now,
+                self.id,
+            )
+            .execute(&mut *txn)
+            .await?;
+
+            txn.commit().await?;
+        }
+
+        User::invalidate_cache(&user.id, &user.email).await?;
+        UserAttrValueEntity::clear_cache(UserAttrValueEntity::cache_idx(&user.id)).await?;
+
+        Ok(self)
+    }
+}
+
+impl UserInvitation {
+    pub fn get_attributes(&self) -> Result<Vec<UserAttrValueRequest>, ErrorResponse> {
+        match &self.attributes {
+            None => Ok(Vec::default()),
+            Some(bytes) => Ok(serde_json::from_slice(bytes)?),
+        }
+    }
+
+    pub fn get_groups(&self) -> Vec<String> {
+        split_csv(self.groups.as_deref())
+    }
+
+    pub fn get_org_roles(&self) -> Vec<String> {
+        split_csv(self.org_roles.as_deref())
+    }
+
+    pub fn get_roles(&self) -> Vec<String> {
+        split_csv(Some(self.roles.as_str()))
+    }
+
+    pub fn into_response(self) -> Result<UserInvitationResponse, ErrorResponse> {
+        let attributes = self.get_attributes()?;
+        let groups = self.get_groups();
+        let org_roles = self.org_roles.as_ref().map(|_| self.get_org_roles());
+        let roles = self.get_roles();
+
+        Ok(UserInvitationResponse {
+            id: self.id,
+            user_id: self.user_id,
+            email: self.email,
+            roles,
+            groups: if groups.is_empty() {
+                None
+            } else {
+                Some(groups)
+            },
+            attributes: if attributes.is_empty() {
+                None
+            } else {
+                Some(
+                    attributes
+                        .into_iter()
+                        .map(|a| UserAttrValueResponse {
+                            key: a.key,
+                            value: a.value,
+                        })
+                        .collect(),
+                )
+            },
+            org_id: self.org_id,
+            org_roles,
+            redirect_uri: self.redirect_uri,
+            created_by: self.created_by,
+            created_at: self.created_at,
+            exp: self.exp,
+            accepted_at: self.accepted_at,
+        })
+    }
+
+    pub fn is_pending(&self) -> bool {
+        self.accepted_at.is_none()
+    }
+
+    async fn send_magic_link(
+        &self,
+        data: &web::Data<AppState>,
+        user: &User,
+    ) -> Result<(), ErrorResponse> {
+        // round up to not expire the magic link before the invitation itself
+        let lifetime_minutes = (self.exp - Utc::now().timestamp() + 59) / 60;
+        let ml = MagicLink::create(
+            user.id.clone(),
+            lifetime_minutes,
+            MagicLinkUsage::Invitation(self.id.clone()),
+        )
+        .await?;
+        send_pwd_reset(data, &ml, user).await;
+
+        Ok(())
+    }
+
+    pub fn validate_not_expired(&self) -> Result<(), ErrorResponse> {
+        if self.exp < Utc::now().timestamp() {
+            Err(ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                "This invitation has expired already",
+            ))
+        } else {
+            Ok(())
+        }
+    }
+
+    pub fn validate_pending(&self) -> Result<(), ErrorResponse> {
+        if self.is_pending() {
+            Ok(())
+        } else {
+            Err(ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                "This invitation has been accepted already",
+            ))
+        }
+    }
+}
+
+#[inline]
+fn split_csv(value: Option<&str>) -> Vec<String> {
+    match value {
+        None | Some("") => Vec::default(),
+        Some(v) => v.split(',').map(|s| s.to_string()).collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_invitation_values() {
+        let attributes = vec![UserAttrValueRequest {
+            key: "department".to_string(),
+            value: serde_json::Value::String("sales".to_string()),
+        }];
+        let invitation = UserInvitation {
+            id: "inv1".to_string(),
+            user_id: "u1".to_string(),
+            email: "invited@localhost.de".to_string(),
+            roles: "admin,user".to_string(),
+            groups: None,
+            attributes: Some(serde_json::to_vec(&attributes).unwrap()),
+            org_id: Some("org1".to_string()),
+            org_roles: Some(String::default()),
+            redirect_uri: None,
+            created_by: "admin".to_string(),
+            created_at: 1719784800,
+            exp: 1719784800,
+            accepted_at: None,
+        };
+
+        assert!(invitation.is_pending());
+        assert!(invitation.validate_not_expired().is_err());
+        assert_eq!(
+            invitation.get_roles(),
+            vec!["admin".to_string(), "user".to_string()]
+        );
+        assert!(invitation.get_groups().is_empty());
+        assert!(invitation.get_org_roles().is_empty());
+
+        let res = invitation.into_response().unwrap();
+        assert_eq!(res.groups, None);
+        assert_eq!(res.org_roles, Some(Vec::default()));
+        let attrs = res.attributes.unwrap();
+        assert_eq!(attrs.len(), 1);
+        assert_eq!(attrs[0].key, "department");
+    }
+}
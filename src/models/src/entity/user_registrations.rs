@@ -0,0 +1,278 @@
+use crate::app_state::AppState;
+use crate::database::DB;
+use crate::entity::user_attr::UserAttrValueEntity;
+use crate::entity::users::User;
+use crate::language::Language;
+use actix_web::web;
+use chrono::Utc;
+use hiqlite::{params, Param};
+use rauthy_api_types::users::{
+    NewUserRegistrationRequest, UserAttrValueRequest, UserAttrValueResponse,
+    UserAttrValuesUpdateRequest, UserRegistrationResponse,
+};
+use rauthy_common::is_hiqlite;
+use rauthy_common::utils::new_store_id;
+use rauthy_error::{ErrorResponse, ErrorResponseType};
+use serde::{Deserialize, Serialize};
+use sqlx::{query, query_as, FromRow};
+
+/// A registration from the open user registration, which is waiting for an admin approval,
+/// if `USER_REG_APPROVAL` is enabled.
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct UserRegistration {
+    pub id: String,
+    pub email: String,
+    pub given_name: String,
+    pub family_name: Option<String>,
+    pub language: String,
+    pub redirect_uri: Option<String>,
+    /// JSON serialized `Vec<UserAttrValueRequest>`
+    pub attributes: Option<Vec<u8>>,
+    pub ip: String,
+    pub created_at: i64,
+}
+
+// CRUD
+impl UserRegistration {
+    pub async fn create(
+        req_data: NewUserRegistrationRequest,
+        lang: Language,
+        ip: String,
+    ) -> Result<Self, ErrorResponse> {
+        let email = req_data.email.to_lowercase();
+        User::is_email_free(email.clone()).await?;
+        if Self::find_by_email(&email).await.is_ok() {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                "E-Mail is already in use",
+            ));
+        }
+
+        let attributes = match req_data.attributes {
+            Some(values) if !values.is_empty() => Some(serde_json::to_vec(&values)?),
+            _ => None,
+        };
+
+        let slf = Self {
+            id: new_store_id(),
+            email,
+            given_name: req_data.given_name,
+            family_name: req_data.family_name,
+            language: lang.as_str().to_string(),
+            redirect_uri: req_data.redirect_uri,
+            attributes,
+            ip,
+            created_at: Utc::now().timestamp(),
+        };
+
+        if is_hiqlite() {
+            DB::client()
+                .execute(
+                    r#"
+INSERT INTO user_registrations
+(id, email, given_name, family_name, language, redirect_uri, attributes, ip, created_at)
+VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)"#,
+                    params!(
+                        slf.id.clone(),
+                        slf.email.clone(),
+                        slf.given_name.clone(),
+                        slf.family_name.clone(),
+                        slf.language.clone(),
+                        slf.redirect_uri.clone(),
+                        slf.attributes.clone(),
+                        slf.ip.clone(),
+                        slf.created_at
+                    ),
+                )
+                .await?;
+        } else {
+            query!(
+                r#"
+INSERT INTO user_registrations
+(id, email, given_name, family_name, language, redirect_uri, attributes, ip, created_at)
+VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)"#,
+                slf.id,
+                slf.email,
+                slf.given_name,
+                slf.family_name,
+                slf.language,
+                slf.redirect_uri,
+                slf.attributes,
+                slf.ip,
+                slf.created_at,
+            )
+            .execute(DB::conn())
+            .await?;
+        }
+
+        Ok(slf)
+    }
+
+    pub async fn delete(id: &str) -> Result<(), ErrorResponse> {
+        if is_hiqlite() {
+            DB::client()
+                .execute("DELETE FROM user_registrations WHERE id = $1", params!(id))
+                .await?;
+        } else {
+            query!("DELETE FROM user_registrations WHERE id = $1", id)
+                .execute(DB::conn())
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    pub async fn find(id: &str) -> Result<Self, ErrorResponse> {
+        let res = if is_hiqlite() {
+            DB::client()
+                .query_as_one(
+                    "SELECT * FROM user_registrations WHERE id = $1",
+                    params!(id),
+                )
+                .await?
+        } else {
+            query_as!(Self, "SELECT * FROM user_registrations WHERE id = $1", id)
+                .fetch_one(DB::conn())
+                .await?
+        };
+
+        Ok(res)
+    }
+
+    pub async fn find_all() -> Result<Vec<Self>, ErrorResponse> {
+        let res = if is_hiqlite() {
+            DB::client()
+                .query_as(
+                    "SELECT * FROM user_registrations ORDER BY created_at ASC",
+                    params!(),
+                )
+                .await?
+        } else {
+            query_as!(
+                Self,
+                "SELECT * FROM user_registrations ORDER BY created_at ASC"
+            )
+            .fetch_all(DB::conn())
+            .await?
+        };
+
+        Ok(res)
+    }
+
+    async fn find_by_email(email: &str) -> Result<Self, ErrorResponse> {
+        let res = if is_hiqlite() {
+            DB::client()
+                .query_as_one(
+                    "SELECT * FROM user_registrations WHERE email = $1",
+                    params!(email),
+                )
+                .await?
+        } else {
+            query_as!(
+                Self,
+                "SELECT * FROM user_registrations WHERE email = $1",
+                email
+            )
+            .fetch_one(DB::conn())
+            .await?
+        };
+
+        Ok(res)
+    }
+}
+
+impl UserRegistration {
+    /// Creates the user from this registration, which will send out the E-Mail to set up
+    /// the account, and removes the registration afterward.
+    pub async fn approve(self, data: &web::Data<AppState>) -> Result<User, ErrorResponse> {
+        let attributes = self.get_attributes()?;
+        let new_user = User {
+            email: self.email,
+            given_name: self.given_name,
+            family_name: self.family_name,
+            language: Language::from(self.language),
+            ..Default::default()
+        };
+        let user = User::create(data, new_user, self.redirect_uri).await?;
+
+        if !attributes.is_empty() {
+            UserAttrValueEntity::update_for_user(
+                &user.id,
+                UserAttrValuesUpdateRequest { values: attributes },
+            )
+            .await?;
+        }
+
+        Self::delete(&self.id).await?;
+
+        Ok(user)
+    }
+
+    pub fn get_attributes(&self) -> Result<Vec<UserAttrValueRequest>, ErrorResponse> {
+        match &self.attributes {
+            None => Ok(Vec::default()),
+            Some(bytes) => Ok(serde_json::from_slice(bytes)?),
+        }
+    }
+
+    pub fn into_response(self) -> Result<UserRegistrationResponse, ErrorResponse> {
+        let attributes = self
+            .get_attributes()?
+            .into_iter()
+            .map(|a| UserAttrValueResponse {
+                key: a.key,
+                value: a.value,
+            })
+            .collect();
+
+        Ok(UserRegistrationResponse {
+            id: self.id,
+            email: self.email,
+            given_name: self.given_name,
+            family_name: self.family_name,
+            language: Language::from(self.language).into(),
+            attributes,
+            ip: self.ip,
+            created_at: self.created_at,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_user_registration_attributes() {
+        let mut reg = UserRegistration {
+            id: "reg123".to_string(),
+            email: "alfred@batcave.io".to_string(),
+            given_name: "Alfred".to_string(),
+            family_name: None,
+            language: "de".to_string(),
+            redirect_uri: None,
+            attributes: None,
+            ip: "127.0.0.1".to_string(),
+            created_at: 1_700_000_000,
+        };
+        assert!(reg.get_attributes().unwrap().is_empty());
+
+        let values = vec![UserAttrValueRequest {
+            key: "employee_id".to_string(),
+            value: json!("1337"),
+        }];
+        reg.attributes = Some(serde_json::to_vec(&values).unwrap());
+        let attrs = reg.get_attributes().unwrap();
+        assert_eq!(attrs.len(), 1);
+        assert_eq!(attrs[0].key, "employee_id");
+        assert_eq!(attrs[0].value, json!("1337"));
+
+        let res = reg.into_response().unwrap();
+        assert_eq!(res.id, "reg123");
+        assert_eq!(res.email, "alfred@batcave.io");
+        assert_eq!(res.attributes.len(), 1);
+        assert_eq!(res.attributes[0].key, "employee_id");
+        assert_eq!(res.attributes[0].value, json!("1337"));
+    }
+}
@@ -1,20 +1,28 @@
 use crate::app_state::{AppState, DbTxn};
 use crate::database::{Cache, DB};
-use crate::email::{send_email_change_info_new, send_email_confirm_change, send_pwd_reset};
+use crate::email::{
+    send_account_locked, send_email_change_info_new, send_email_confirm_change, send_pwd_reset,
+};
 use crate::entity::colors::ColorEntity;
 use crate::entity::continuation_token::ContinuationToken;
 use crate::entity::groups::Group;
 use crate::entity::magic_links::{MagicLink, MagicLinkUsage};
 use crate::entity::password::PasswordPolicy;
 use crate::entity::password::RecentPasswordsEntity;
+use crate::entity::password_policies::ScopedPasswordPolicy;
 use crate::entity::refresh_tokens::RefreshToken;
 use crate::entity::roles::Role;
 use crate::entity::sessions::Session;
+use crate::entity::trusted_devices::TrustedDevice;
+use crate::entity::user_attr::UserAttrValueEntity;
+use crate::entity::user_federations::UserFederation;
+use crate::entity::user_invitations::UserInvitation;
 use crate::entity::users_values::UserValues;
 use crate::entity::webauthn::{PasskeyEntity, WebauthnServiceReq};
 use crate::events::event::Event;
 use crate::language::Language;
-use crate::templates::UserEmailChangeConfirmHtml;
+use crate::security_notifications::SecurityNotification;
+use crate::templates::{UserEmailChangeConfirmHtml, UserNotMeHtml, UserUnlockHtml};
 use actix_web::{web, HttpRequest};
 use argon2::PasswordHash;
 use chrono::Utc;
@@ -22,11 +30,13 @@ use hiqlite::{params, Param, Params};
 use rauthy_api_types::generic::SearchParamsIdx;
 use rauthy_api_types::users::{
     NewUserRegistrationRequest, NewUserRequest, UpdateUserRequest, UpdateUserSelfRequest,
-    UserAccountTypeResponse, UserResponse, UserResponseSimple, UserValuesResponse,
+    UserAccountTypeResponse, UserAttrValuesUpdateRequest, UserResponse, UserResponseSimple,
+    UserValuesResponse,
 };
 use rauthy_common::constants::{
-    CACHE_TTL_APP, CACHE_TTL_USER, IDX_USERS, IDX_USER_COUNT, RAUTHY_ADMIN_ROLE,
-    WEBAUTHN_NO_PASSWORD_EXPIRY,
+    ACCOUNT_LOCKOUT_ATTEMPTS, ACCOUNT_LOCKOUT_DURATION, ACCOUNT_LOCKOUT_UNLOCK_EMAIL,
+    ACCOUNT_LOCKOUT_WINDOW, CACHE_TTL_APP, CACHE_TTL_USER, IDX_USERS, IDX_USER_COUNT,
+    RAUTHY_ADMIN_ROLE, WEBAUTHN_NO_PASSWORD_EXPIRY,
 };
 use rauthy_common::is_hiqlite;
 use rauthy_common::password_hasher::{ComparePasswords, HashPassword};
@@ -85,6 +95,8 @@ pub struct User {
     pub user_expires: Option<i64>,
     pub auth_provider_id: Option<String>,
     pub federation_uid: Option<String>,
+    /// Unix timestamp in seconds. `User::LOCKED_UNTIL_UNLOCK` for locks without expiry.
+    pub locked_until: Option<i64>,
 }
 
 // CRUD
@@ -196,6 +208,17 @@ impl User {
         new_user.language = lang;
         let new_user = User::create(data, new_user, req_data.redirect_uri).await?;
 
+        // the values must have been validated against the registration attributes beforehand
+        if let Some(values) = req_data.attributes {
+            if !values.is_empty() {
+                UserAttrValueEntity::update_for_user(
+                    &new_user.id,
+                    UserAttrValuesUpdateRequest { values },
+                )
+                .await?;
+            }
+        }
+
         Ok(new_user)
     }
 
@@ -292,29 +315,13 @@ impl User {
         Ok(slf)
     }
 
+    /// Resolves the user through any of its linked upstream identities.
     pub async fn find_by_federation(
         auth_provider_id: &str,
         federation_uid: &str,
     ) -> Result<Self, ErrorResponse> {
-        let slf = if is_hiqlite() {
-            DB::client()
-                .query_as_one(
-                    "SELECT * FROM users WHERE auth_provider_id = $1 AND federation_uid = $2",
-                    params!(auth_provider_id, federation_uid),
-                )
-                .await?
-        } else {
-            sqlx::query_as!(
-                Self,
-                "SELECT * FROM users WHERE auth_provider_id = $1 AND federation_uid = $2",
-                auth_provider_id,
-                federation_uid
-            )
-            .fetch_one(DB::conn())
-            .await?
-        };
-
-        Ok(slf)
+        let link = UserFederation::find(auth_provider_id, federation_uid).await?;
+        Self::find(link.user_id).await
     }
 
     pub async fn find_all() -> Result<Vec<Self>, ErrorResponse> {
@@ -331,6 +338,31 @@ impl User {
         Ok(res)
     }
 
+    /// Returns up to `limit` users with an id greater than `after_id`, ordered by id.
+    /// In contrast to an `OFFSET`, this stays fast for huge tables, which makes it a good fit for
+    /// iterating over all users.
+    pub async fn find_page_after(after_id: &str, limit: i64) -> Result<Vec<Self>, ErrorResponse> {
+        let res = if is_hiqlite() {
+            DB::client()
+                .query_as(
+                    "SELECT * FROM users WHERE id > $1 ORDER BY id ASC LIMIT $2",
+                    params!(after_id, limit),
+                )
+                .await?
+        } else {
+            sqlx::query_as!(
+                Self,
+                "SELECT * FROM users WHERE id > $1 ORDER BY id ASC LIMIT $2",
+                after_id,
+                limit
+            )
+            .fetch_all(DB::conn())
+            .await?
+        };
+
+        Ok(res)
+    }
+
     pub async fn find_all_simple() -> Result<Vec<UserResponseSimple>, ErrorResponse> {
         let res = if is_hiqlite() {
             DB::client()
@@ -368,6 +400,32 @@ impl User {
         Ok(res)
     }
 
+    /// Returns all users that are a member of at least one of the given groups.
+    /// This is a very expensive query using `LIKE`, use only when necessary.
+    pub async fn find_in_groups_simple(
+        groups: &[String],
+    ) -> Result<Vec<UserResponseSimple>, ErrorResponse> {
+        let mut res: Vec<UserResponseSimple> = Vec::new();
+
+        for group in groups {
+            for user in Self::find_with_group(group).await? {
+                // LIKE may give us false positives with similar group names
+                if !user.get_groups().contains(group) || res.iter().any(|u| u.id == user.id) {
+                    continue;
+                }
+                res.push(UserResponseSimple {
+                    id: user.id,
+                    email: user.email,
+                    created_at: user.created_at,
+                    last_login: user.last_login,
+                });
+            }
+        }
+        res.sort_by(|a, b| a.created_at.cmp(&b.created_at));
+
+        Ok(res)
+    }
+
     /// This is a very expensive query using `LIKE`, use only when necessary.
     pub async fn find_with_role(role_name: &str) -> Result<Vec<Self>, ErrorResponse> {
         let like = format!("%{role_name}%");
@@ -581,6 +639,55 @@ OFFSET $2"#,
         Ok((res, token))
     }
 
+    /// Paginates an already loaded list of users, ordered by `created_at`, in the same way as
+    /// `find_paginated()`. Used for group scoped admins, which only ever see a subset of users.
+    pub fn paginate_simple(
+        users: Vec<UserResponseSimple>,
+        continuation_token: Option<ContinuationToken>,
+        page_size: i64,
+        offset: i64,
+        backwards: bool,
+    ) -> (Vec<UserResponseSimple>, Option<ContinuationToken>) {
+        let page_size = page_size.max(0) as usize;
+        let offset = offset.max(0) as usize;
+
+        let res: Vec<UserResponseSimple> = match (continuation_token, backwards) {
+            (Some(token), true) => {
+                let mut res = users
+                    .into_iter()
+                    .rev()
+                    .filter(|u| u.created_at <= token.ts && u.id != token.id)
+                    .skip(offset + page_size)
+                    .take(page_size)
+                    .collect::<Vec<_>>();
+                res.reverse();
+                res
+            }
+            (Some(token), false) => users
+                .into_iter()
+                .filter(|u| u.created_at >= token.ts && u.id != token.id)
+                .skip(offset)
+                .take(page_size)
+                .collect(),
+            (None, true) => {
+                let mut res = users
+                    .into_iter()
+                    .rev()
+                    .skip(offset)
+                    .take(page_size)
+                    .collect::<Vec<_>>();
+                res.reverse();
+                res
+            }
+            (None, false) => users.into_iter().skip(offset).take(page_size).collect(),
+        };
+
+        let token = res
+            .last()
+            .map(|entry| ContinuationToken::new(entry.id.clone(), entry.created_at));
+        (res, token)
+    }
+
     pub async fn insert(new_user: User) -> Result<Self, ErrorResponse> {
         let lang = new_user.language.as_str();
 
@@ -641,20 +748,46 @@ VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14)"#,
         Ok(new_user)
     }
 
-    pub async fn provider_unlink(user_id: String) -> Result<Self, ErrorResponse> {
+    /// Removes the link to the given upstream provider. If it was the primary one, another
+    /// linked identity will take its place, if any exists.
+    pub async fn provider_unlink(
+        user_id: String,
+        provider_id: &str,
+    ) -> Result<Self, ErrorResponse> {
         // we need to find the user first and validate that it has been set up properly
-        // to work without a provider
+        // to work without this provider
         let mut slf = Self::find(user_id).await?;
-        if slf.password.is_none() && !slf.has_webauthn_enabled() {
+        let links = UserFederation::find_for_user(&slf.id).await?;
+        if !links.iter().any(|l| l.provider_id == provider_id) {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::NotFound,
+                "The user is not linked to this provider",
+            ));
+        }
+        let remaining = links
+            .into_iter()
+            .filter(|l| l.provider_id != provider_id)
+            .collect::<Vec<_>>();
+
+        if remaining.is_empty() && slf.password.is_none() && !slf.has_webauthn_enabled() {
             return Err(ErrorResponse::new(
                 ErrorResponseType::BadRequest,
-                "You must have at least a password or passkey set up before you can remove a provider link",
+                "You must have at least a password, passkey or another provider set up before you can remove a provider link",
             ));
         }
 
-        slf.auth_provider_id = None;
-        slf.federation_uid = None;
-        slf.save(None).await?;
+        UserFederation::delete(&slf.id, provider_id).await?;
+
+        if slf.auth_provider_id.as_deref() == Some(provider_id) {
+            if let Some(next) = remaining.into_iter().next() {
+                slf.auth_provider_id = Some(next.provider_id);
+                slf.federation_uid = Some(next.federation_uid);
+            } else {
+                slf.auth_provider_id = None;
+                slf.federation_uid = None;
+            }
+            slf.save(None).await?;
+        }
 
         Ok(slf)
     }
@@ -675,8 +808,8 @@ UPDATE USERS SET
 email = $1, given_name = $2, family_name = $3, password = $4, roles = $5, groups = $6, enabled = $7,
 email_verified = $8, password_expires = $9, last_login = $10, last_failed_login = $11,
 failed_login_attempts = $12, language = $13, webauthn_user_id = $14, user_expires = $15,
-auth_provider_id = $16, federation_uid = $17
-WHERE id = $18"#,
+auth_provider_id = $16, federation_uid = $17, locked_until = $18
+WHERE id = $19"#,
             params!(
                 self.email,
                 self.given_name,
@@ -695,6 +828,7 @@ WHERE id = $18"#,
                 self.user_expires,
                 self.auth_provider_id,
                 self.federation_uid,
+                self.locked_until,
                 self.id
             ),
         ));
@@ -715,8 +849,8 @@ UPDATE USERS SET
 email = $1, given_name = $2, family_name = $3, password = $4, roles = $5, groups = $6, enabled = $7,
 email_verified = $8, password_expires = $9, last_login = $10, last_failed_login = $11,
 failed_login_attempts = $12, language = $13, webauthn_user_id = $14, user_expires = $15,
-auth_provider_id = $16, federation_uid = $17
-WHERE id = $18"#,
+auth_provider_id = $16, federation_uid = $17, locked_until = $18
+WHERE id = $19"#,
         )
         .bind(&self.email)
         .bind(&self.given_name)
@@ -735,6 +869,7 @@ WHERE id = $18"#,
         .bind(self.user_expires)
         .bind(&self.auth_provider_id)
         .bind(&self.federation_uid)
+        .bind(self.locked_until)
         .bind(&self.id)
         .execute(&mut **txn)
         .await?;
@@ -758,8 +893,8 @@ UPDATE USERS SET
 email = $1, given_name = $2, family_name = $3, password = $4, roles = $5, groups = $6, enabled = $7,
 email_verified = $8, password_expires = $9, last_login = $10, last_failed_login = $11,
 failed_login_attempts = $12, language = $13, webauthn_user_id = $14, user_expires = $15,
-auth_provider_id = $16, federation_uid = $17
-WHERE id = $18"#,
+auth_provider_id = $16, federation_uid = $17, locked_until = $18
+WHERE id = $19"#,
                     params!(
                         &self.email,
                         &self.given_name,
@@ -778,6 +913,7 @@ WHERE id = $18"#,
                         self.user_expires,
                         &self.auth_provider_id,
                         &self.federation_uid,
+                        self.locked_until,
                         &self.id
                     ),
                 )
@@ -789,8 +925,8 @@ UPDATE USERS SET
 email = $1, given_name = $2, family_name = $3, password = $4, roles = $5, groups = $6, enabled = $7,
 email_verified = $8, password_expires = $9, last_login = $10, last_failed_login = $11,
 failed_login_attempts = $12, language = $13, webauthn_user_id = $14, user_expires = $15,
-auth_provider_id = $16, federation_uid = $17
-WHERE id = $18"#,
+auth_provider_id = $16, federation_uid = $17, locked_until = $18
+WHERE id = $19"#,
             )
             .bind(&self.email)
             .bind(&self.given_name)
@@ -809,6 +945,7 @@ WHERE id = $18"#,
             .bind(self.user_expires)
             .bind(&self.auth_provider_id)
             .bind(&self.federation_uid)
+            .bind(self.locked_until)
             .bind(&self.id)
             .execute(DB::conn())
             .await?;
@@ -981,6 +1118,9 @@ LIMIT $2"#,
                 ))
                 .await
                 .unwrap();
+            SecurityNotification::PasswordChanged
+                .send(data, &user)
+                .await;
         }
 
         if let Some(old_email) = old_email.as_ref() {
@@ -1167,82 +1307,20 @@ impl User {
         }
     }
 
+    /// Applies the effective password policy for this user, depending on its groups.
     pub async fn apply_password_rules(&mut self, plain_pwd: &str) -> Result<(), ErrorResponse> {
-        let rules = PasswordPolicy::find().await?;
-
-        if plain_pwd.len() < rules.length_min as usize {
-            return Err(ErrorResponse::new(
-                ErrorResponseType::BadRequest,
-                format!("Minimum password length is {}", rules.length_min),
-            ));
-        }
-        if plain_pwd.len() > rules.length_max as usize {
-            return Err(ErrorResponse::new(
-                ErrorResponseType::BadRequest,
-                format!("Maximum password length is {}", rules.length_max),
-            ));
-        }
-
-        let mut count_lower = 0;
-        let mut count_upper = 0;
-        let mut count_digit = 0;
-        let mut count_special = 0;
-
-        plain_pwd.chars().for_each(|c| {
-            if c.is_lowercase() {
-                count_lower += 1;
-            } else if c.is_uppercase() {
-                count_upper += 1;
-            } else if c.is_ascii_digit() {
-                count_digit += 1;
-            } else if !c.is_alphanumeric() {
-                count_special += 1;
-            }
-        });
-
-        let lower_req = rules.include_lower_case.unwrap_or(0);
-        if lower_req > count_lower {
-            return Err(ErrorResponse::new(
-                ErrorResponseType::BadRequest,
-                format!(
-                    "New password does not include the minimum lower character count: {}",
-                    lower_req
-                ),
-            ));
-        }
-
-        let upper_req = rules.include_upper_case.unwrap_or(0);
-        if upper_req > count_upper {
-            return Err(ErrorResponse::new(
-                ErrorResponseType::BadRequest,
-                format!(
-                    "New password does not include the minimum upper character count: {}",
-                    upper_req
-                ),
-            ));
-        }
-
-        let digit_req = rules.include_digits.unwrap_or(0);
-        if digit_req > count_digit {
-            return Err(ErrorResponse::new(
-                ErrorResponseType::BadRequest,
-                format!(
-                    "New password does not include the minimum digit count: {}",
-                    digit_req
-                ),
-            ));
-        }
+        let rules = PasswordPolicy::find_for_user(self).await?;
+        self.apply_password_policy(plain_pwd, &rules).await
+    }
 
-        let special_req = rules.include_special.unwrap_or(0);
-        if special_req > count_special {
-            return Err(ErrorResponse::new(
-                ErrorResponseType::BadRequest,
-                format!(
-                    "New password does not include the minimum special character count: {}",
-                    special_req
-                ),
-            ));
-        }
+    pub async fn apply_password_policy(
+        &mut self,
+        plain_pwd: &str,
+        rules: &PasswordPolicy,
+    ) -> Result<(), ErrorResponse> {
+        rules
+            .validate(plain_pwd, &self.password_user_inputs())
+            .await?;
 
         let new_hash = HashPassword::hash_password(plain_pwd.to_string()).await?;
         let mut new_recent = Vec::new();
@@ -1299,6 +1377,17 @@ impl User {
         Ok(())
     }
 
+    /// Personal values, which lower the strength score of a password, if they are being used
+    /// inside of it.
+    #[inline]
+    pub fn password_user_inputs(&self) -> [&str; 3] {
+        [
+            self.email.as_str(),
+            self.given_name.as_str(),
+            self.family_name.as_deref().unwrap_or_default(),
+        ]
+    }
+
     #[inline]
     pub fn check_enabled(&self) -> Result<(), ErrorResponse> {
         if !self.enabled {
@@ -1311,6 +1400,9 @@ impl User {
         Ok(())
     }
 
+    /// `locked_until` value for accounts, which stay locked until they are unlocked manually.
+    pub const LOCKED_UNTIL_UNLOCK: i64 = i64::MAX;
+
     #[inline]
     pub fn check_expired(&self) -> Result<(), ErrorResponse> {
         if let Some(ts) = self.user_expires {
@@ -1325,6 +1417,225 @@ impl User {
         Ok(())
     }
 
+    #[inline]
+    pub fn is_locked(&self) -> bool {
+        self.locked_until
+            .is_some_and(|ts| ts > Utc::now().timestamp())
+    }
+
+    #[inline]
+    pub fn check_locked(&self) -> Result<(), ErrorResponse> {
+        if self.is_locked() {
+            trace!("The user is locked");
+            return Err(ErrorResponse::new(
+                ErrorResponseType::Disabled,
+                "The account is locked",
+            ));
+        }
+        Ok(())
+    }
+
+    /// Increases the failed logins counter and locks the account, if the lockout policy
+    /// is enabled and the threshold has been reached inside `ACCOUNT_LOCKOUT_WINDOW`.
+    pub async fn on_failed_login(
+        &mut self,
+        data: &web::Data<AppState>,
+        ip: Option<String>,
+    ) -> Result<(), ErrorResponse> {
+        let now = Utc::now().timestamp();
+        let lockout_enabled = *ACCOUNT_LOCKOUT_ATTEMPTS > 0;
+
+        // with an active lockout policy, the counter starts over after a quiet window
+        let attempts = if lockout_enabled
+            && !self
+                .last_failed_login
+                .is_some_and(|ts| ts > now - *ACCOUNT_LOCKOUT_WINDOW)
+        {
+            1
+        } else {
+            self.failed_login_attempts.unwrap_or(0) + 1
+        };
+        self.last_failed_login = Some(now);
+        self.failed_login_attempts = Some(attempts);
+
+        let lock = lockout_enabled && attempts >= *ACCOUNT_LOCKOUT_ATTEMPTS && !self.is_locked();
+        if lock {
+            self.locked_until = if *ACCOUNT_LOCKOUT_DURATION > 0 {
+                Some(now + *ACCOUNT_LOCKOUT_DURATION)
+            } else {
+                Some(Self::LOCKED_UNTIL_UNLOCK)
+            };
+        }
+        self.save(None).await?;
+
+        if lock {
+            warn!(
+                "Locking account '{}' after {} failed logins",
+                self.email, attempts
+            );
+            let until = self
+                .locked_until
+                .filter(|ts| *ts != Self::LOCKED_UNTIL_UNLOCK);
+            data.tx_events
+                .send_async(Event::user_locked(self.email.clone(), until, ip))
+                .await
+                .unwrap();
+
+            if *ACCOUNT_LOCKOUT_UNLOCK_EMAIL {
+                let ml = MagicLink::create(
+                    self.id.clone(),
+                    data.ml_lt_pwd_reset as i64,
+                    MagicLinkUsage::Unlock,
+                )
+                .await?;
+                send_account_locked(data, &ml, self).await;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Removes an account lock and resets the failed logins counter.
+    pub async fn unlock(
+        &mut self,
+        data: &web::Data<AppState>,
+        ip: Option<String>,
+    ) -> Result<(), ErrorResponse> {
+        self.locked_until = None;
+        self.last_failed_login = None;
+        self.failed_login_attempts = None;
+        self.save(None).await?;
+
+        data.tx_events
+            .send_async(Event::user_unlocked(self.email.clone(), ip))
+            .await
+            .unwrap();
+
+        Ok(())
+    }
+
+    /// Validates an unlock link from the account locked E-Mail and returns the confirmation
+    /// page. Nothing is changed here, because link scanners in mail clients would unlock the
+    /// account otherwise.
+    pub async fn unlock_confirm_html(
+        req: &HttpRequest,
+        user_id: String,
+        unlock_id: String,
+    ) -> Result<String, ErrorResponse> {
+        let ml = Self::find_unlock_link(req, &user_id, &unlock_id, false).await?;
+
+        let colors = ColorEntity::find_rauthy().await?;
+        let lang = Language::try_from(req).unwrap_or_default();
+        Ok(UserUnlockHtml::build(&colors, &lang, &ml.csrf_token))
+    }
+
+    /// Handles the confirmed unlock link from the account locked E-Mail and returns the user's
+    /// E-Mail. The request must contain the CSRF token from the confirmation page.
+    pub async fn unlock_via_magic_link(
+        data: &web::Data<AppState>,
+        req: &HttpRequest,
+        user_id: String,
+        unlock_id: String,
+    ) -> Result<String, ErrorResponse> {
+        let mut ml = Self::find_unlock_link(req, &user_id, &unlock_id, true).await?;
+
+        let mut user = Self::find(user_id).await?;
+        ml.invalidate().await?;
+        if user.locked_until.is_some() {
+            let ip = real_ip_from_req(req).ok().map(|ip| ip.to_string());
+            user.unlock(data, ip).await?;
+        }
+
+        Ok(user.email)
+    }
+
+    /// Validates a "this wasn't me" link from a security notification and returns the
+    /// confirmation page. Nothing is changed here, because link scanners in mail clients would
+    /// log the user out otherwise.
+    pub async fn not_me_confirm_html(
+        req: &HttpRequest,
+        user_id: String,
+        not_me_id: String,
+    ) -> Result<String, ErrorResponse> {
+        let ml = Self::find_not_me_link(req, &user_id, &not_me_id, false).await?;
+
+        let colors = ColorEntity::find_rauthy().await?;
+        let lang = Language::try_from(req).unwrap_or_default();
+        Ok(UserNotMeHtml::build(&colors, &lang, &ml.csrf_token))
+    }
+
+    /// Handles the confirmed "this wasn't me" link from a security notification. Logs the user
+    /// out everywhere, revokes all trusted devices and returns the location of a new password
+    /// reset. The request must contain the CSRF token from the confirmation page.
+    pub async fn not_me_via_magic_link(
+        data: &web::Data<AppState>,
+        req: &HttpRequest,
+        user_id: String,
+        not_me_id: String,
+    ) -> Result<String, ErrorResponse> {
+        let mut ml = Self::find_not_me_link(req, &user_id, &not_me_id, true).await?;
+
+        let user = Self::find(user_id).await?;
+        ml.invalidate().await?;
+
+        warn!(
+            "Security notification reported as unknown by user '{}' from {:?} - invalidating all \
+            sessions",
+            user.email,
+            real_ip_from_req(req).ok()
+        );
+        Session::invalidate_for_user(&user.id).await?;
+        RefreshToken::invalidate_for_user(&user.id).await?;
+        TrustedDevice::delete_for_user(&user.id).await?;
+
+        let reset = MagicLink::create(
+            user.id.clone(),
+            data.ml_lt_pwd_reset as i64,
+            MagicLinkUsage::PasswordReset(None),
+        )
+        .await?;
+        Ok(format!(
+            "/auth/v1/users/{}/reset/{}?type={}",
+            user.id, reset.id, reset.usage
+        ))
+    }
+
+    async fn find_unlock_link(
+        req: &HttpRequest,
+        user_id: &str,
+        unlock_id: &str,
+        with_csrf: bool,
+    ) -> Result<MagicLink, ErrorResponse> {
+        let ml = MagicLink::find(unlock_id).await?;
+        ml.validate(user_id, req, with_csrf)?;
+
+        if MagicLinkUsage::try_from(&ml.usage)? != MagicLinkUsage::Unlock {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                "The Magic Link is not meant to be used to unlock an account",
+            ));
+        }
+        Ok(ml)
+    }
+
+    async fn find_not_me_link(
+        req: &HttpRequest,
+        user_id: &str,
+        not_me_id: &str,
+        with_csrf: bool,
+    ) -> Result<MagicLink, ErrorResponse> {
+        let ml = MagicLink::find(not_me_id).await?;
+        ml.validate(user_id, req, with_csrf)?;
+
+        if MagicLinkUsage::try_from(&ml.usage)? != MagicLinkUsage::NotMe {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                "The Magic Link is not meant to be used to report an unknown action",
+            ));
+        }
+        Ok(ml)
+    }
+
     pub async fn confirm_email_address(
         data: &web::Data<AppState>,
         req: HttpRequest,
@@ -1336,7 +1647,12 @@ impl User {
 
         let usage = MagicLinkUsage::try_from(&ml.usage)?;
         let new_email = match usage {
-            MagicLinkUsage::NewUser(_) | MagicLinkUsage::PasswordReset(_) => {
+            MagicLinkUsage::NewUser(_)
+            | MagicLinkUsage::PasswordReset(_)
+            | MagicLinkUsage::Invitation(_)
+            | MagicLinkUsage::Unlock
+            | MagicLinkUsage::NotMe
+            | MagicLinkUsage::PasswordChange(_) => {
                 return Err(ErrorResponse::new(
                     ErrorResponseType::BadRequest,
                     "The Magic Link is not meant to be used to confirm an E-Mail address"
@@ -1519,6 +1835,10 @@ impl User {
                 .unwrap_or_default(),
             auth_provider_id: self.auth_provider_id,
             federation_uid: self.federation_uid,
+            locked: self.is_locked(),
+            locked_until: self
+                .locked_until
+                .filter(|ts| *ts != Self::LOCKED_UNTIL_UNLOCK && *ts > Utc::now().timestamp()),
         }
     }
 
@@ -1533,10 +1853,16 @@ impl User {
                 String::from("Cannot validate argon2 param - password is not set"),
             ));
         }
-        let hash = PasswordHash::new(self.password.as_ref().unwrap())
-            .expect("Could not build Hash from password string");
-        let curr_params =
-            argon2::Params::try_from(&hash).expect("Could not extract params from hash");
+        // imported legacy hashes are never up-to-date and will be upgraded to argon2id
+        let Ok(hash) = PasswordHash::new(self.password.as_ref().unwrap()) else {
+            return Ok(false);
+        };
+        if hash.algorithm.as_str() != "argon2id" {
+            return Ok(false);
+        }
+        let Ok(curr_params) = argon2::Params::try_from(&hash) else {
+            return Ok(false);
+        };
 
         if curr_params.m_cost() == params.m_cost()
             && curr_params.t_cost() == params.t_cost()
@@ -1551,7 +1877,14 @@ impl User {
         self.get_roles().contains(&RAUTHY_ADMIN_ROLE)
     }
 
-    async fn is_email_free(email: String) -> Result<(), ErrorResponse> {
+    /// A `rauthy_admin` with a local password or passkey can always log in locally, even if the
+    /// E-Mail domain is bound to an upstream provider. Otherwise, a broken or compromised
+    /// upstream provider could lock out all admins.
+    pub fn is_home_realm_exempt(&self) -> bool {
+        self.is_admin() && (self.password.is_some() || self.has_webauthn_enabled())
+    }
+
+    pub(crate) async fn is_email_free(email: String) -> Result<(), ErrorResponse> {
         match User::find_by_email(email).await {
             Ok(_) => Err(ErrorResponse::new(
                 ErrorResponseType::BadRequest,
@@ -1616,7 +1949,17 @@ impl User {
         }
 
         let usage = if self.password.is_none() && !self.has_webauthn_enabled() {
-            MagicLinkUsage::NewUser(redirect_uri)
+            // an invited user must still get the pre-assigned values after the account setup
+            match UserInvitation::find_pending_for_user(&self.id).await? {
+                Some(invitation) => {
+                    if invitation.validate_not_expired().is_err() {
+                        // an expired invitation must be re-sent by an admin
+                        return Ok(());
+                    }
+                    MagicLinkUsage::Invitation(invitation.id)
+                }
+                None => MagicLinkUsage::NewUser(redirect_uri),
+            }
         } else {
             MagicLinkUsage::PasswordReset(redirect_uri)
         };
@@ -1626,6 +1969,48 @@ impl User {
         Ok(())
     }
 
+    /// Checks the password from a successful login against the scoped password policy of the
+    /// client. If it does not satisfy the policy, a magic link for a password change is sent
+    /// out and the login must be rejected.
+    pub async fn validate_client_password_policy(
+        &self,
+        data: &web::Data<AppState>,
+        client_id: &str,
+        plain_password: &str,
+    ) -> Result<(), ErrorResponse> {
+        let Some(policy) = ScopedPasswordPolicy::find_for_client(client_id).await? else {
+            return Ok(());
+        };
+
+        match policy
+            .validate(plain_password, &self.password_user_inputs())
+            .await
+        {
+            Ok(_) => Ok(()),
+            Err(err) if err.error == ErrorResponseType::BadRequest => {
+                debug!(
+                    "Password for user {} does not satisfy the policy for client {}: {}",
+                    self.id, client_id, err.message
+                );
+
+                let magic_link = MagicLink::create(
+                    self.id.clone(),
+                    data.ml_lt_pwd_reset as i64,
+                    MagicLinkUsage::PasswordChange(client_id.to_string()),
+                )
+                .await?;
+                send_pwd_reset(data, &magic_link, self).await;
+
+                Err(ErrorResponse::new(
+                    ErrorResponseType::PasswordRefresh,
+                    "The password does not satisfy the password policy for this client. \
+                    A reset E-Mail has been sent out.",
+                ))
+            }
+            Err(err) => Err(err),
+        }
+    }
+
     pub async fn validate_password(
         &self,
         data: &web::Data<AppState>,
@@ -1704,6 +2089,7 @@ impl Default for User {
             user_expires: None,
             auth_provider_id: None,
             federation_uid: None,
+            locked_until: None,
         }
     }
 }
@@ -1726,6 +2112,62 @@ mod tests {
     use pretty_assertions::assert_eq;
     use std::ops::Sub;
 
+    #[test]
+    fn test_is_home_realm_exempt() {
+        let mut user = User {
+            roles: "rauthy_admin,user".to_string(),
+            ..Default::default()
+        };
+        // an admin without any local credentials must log in upstream
+        user.password = None;
+        user.webauthn_user_id = None;
+        assert!(!user.is_home_realm_exempt());
+
+        user.password = Some("$argon2id$v=19$m=32768,t=3,p=2$hash".to_string());
+        assert!(user.is_home_realm_exempt());
+
+        user.password = None;
+        user.webauthn_user_id = Some("webauthn_id".to_string());
+        assert!(user.is_home_realm_exempt());
+
+        // normal users are never exempt
+        user.roles = "user".to_string();
+        user.password = Some("$argon2id$v=19$m=32768,t=3,p=2$hash".to_string());
+        assert!(!user.is_home_realm_exempt());
+    }
+
+    #[test]
+    fn test_paginate_simple() {
+        let users = (0..10)
+            .map(|i| UserResponseSimple {
+                id: format!("user{}", i),
+                email: format!("user{}@localhost.de", i),
+                created_at: 1_700_000_000 + i,
+                last_login: None,
+            })
+            .collect::<Vec<_>>();
+        let ids = |res: &[UserResponseSimple]| res.iter().map(|u| u.id.clone()).collect::<Vec<_>>();
+
+        let (res, token) = User::paginate_simple(users.clone(), None, 4, 0, false);
+        assert_eq!(ids(&res), vec!["user0", "user1", "user2", "user3"]);
+        let token = token.unwrap();
+        assert_eq!(token.id, "user3");
+
+        let (res, _) = User::paginate_simple(users.clone(), Some(token), 4, 0, false);
+        assert_eq!(ids(&res), vec!["user4", "user5", "user6", "user7"]);
+
+        let token = ContinuationToken::new("user7".to_string(), 1_700_000_007);
+        let (res, _) = User::paginate_simple(users.clone(), Some(token), 4, 0, true);
+        assert_eq!(ids(&res), vec!["user0", "user1", "user2"]);
+
+        let (res, _) = User::paginate_simple(users.clone(), None, 4, 0, true);
+        assert_eq!(ids(&res), vec!["user6", "user7", "user8", "user9"]);
+
+        let (res, token) = User::paginate_simple(users, None, 4, 12, false);
+        assert!(res.is_empty());
+        assert!(token.is_none());
+    }
+
     #[test]
     fn test_session_impl() {
         let mut user = User {
@@ -1752,6 +2194,7 @@ mod tests {
             ),
             auth_provider_id: None,
             federation_uid: None,
+            locked_until: None,
         };
         let session = Session::try_new(&user, 1, None);
         assert!(session.is_err());
@@ -1810,6 +2253,7 @@ mod tests {
             user_expires: None,
             auth_provider_id: None,
             federation_uid: None,
+            locked_until: None,
         };
 
         // enabled
@@ -1817,6 +2261,16 @@ mod tests {
         user.enabled = true;
         assert!(user.check_enabled().is_ok());
 
+        // account lock
+        assert!(user.check_locked().is_ok());
+        user.locked_until = Some(Utc::now().timestamp() + 60);
+        assert!(user.check_locked().is_err());
+        user.locked_until = Some(User::LOCKED_UNTIL_UNLOCK);
+        assert!(user.is_locked());
+        user.locked_until = Some(Utc::now().timestamp() - 1);
+        assert!(!user.is_locked());
+        user.locked_until = None;
+
         // password expiry
         assert!(check_password_expired(&user).is_ok());
         user.password_expires = Some(OffsetDateTime::now_utc().unix_timestamp() + 1);
@@ -1905,6 +2359,12 @@ mod tests {
         let res = user.is_argon2_uptodate(&wrapped_params)?;
         assert_eq!(res, false);
 
+        // legacy hashes must always be upgraded
+        user.password =
+            Some("$2b$04$7CWRI46zDTe9WdvdFGnEt.wrNYDaRNHQZJ5TiB6CFsAtY8wBPv3Fm".to_string());
+        let res = user.is_argon2_uptodate(&wrapped_params)?;
+        assert_eq!(res, false);
+
         Ok(())
     }
 }
@@ -1,12 +1,13 @@
 use crate::api_cookie::ApiCookie;
 use crate::app_state::{AppState, DbTxn};
 use crate::database::{Cache, DB};
+use crate::entity::clients::Client;
 use crate::entity::password::PasswordPolicy;
 use crate::entity::users::{AccountType, User};
 use actix_web::cookie::Cookie;
 use actix_web::http::header;
 use actix_web::http::header::HeaderValue;
-use actix_web::{web, HttpResponse};
+use actix_web::{web, HttpRequest, HttpResponse};
 use chrono::Utc;
 use cryptr::EncValue;
 use hiqlite::{params, Param, Params};
@@ -20,7 +21,7 @@ use rauthy_common::constants::{
 };
 use rauthy_common::is_hiqlite;
 use rauthy_common::utils::base64_decode;
-use rauthy_common::utils::{base64_encode, get_rand};
+use rauthy_common::utils::{base64_encode, get_rand, real_ip_from_req};
 use rauthy_error::{ErrorResponse, ErrorResponseType};
 use serde::{Deserialize, Serialize};
 use sqlx::FromRow;
@@ -148,6 +149,55 @@ VALUES ($1, $2, $3, $4, $5, $6, $7, $8)"#,
         Ok(())
     }
 
+    /// Inserts or overwrites the passkey with all values as they are. Only used for user imports.
+    pub async fn upsert(&self) -> Result<(), ErrorResponse> {
+        if is_hiqlite() {
+            DB::client()
+                .execute(
+                    r#"
+INSERT INTO passkeys
+(user_id, name, passkey_user_id, passkey, credential_id, registered, last_used, user_verified)
+VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+ON CONFLICT(user_id, name) DO UPDATE
+SET passkey_user_id = $3, passkey = $4, credential_id = $5, registered = $6, last_used = $7,
+user_verified = $8"#,
+                    params!(
+                        &self.user_id,
+                        &self.name,
+                        &self.passkey_user_id,
+                        &self.passkey,
+                        &self.credential_id,
+                        self.registered,
+                        self.last_used,
+                        self.user_verified
+                    ),
+                )
+                .await?;
+        } else {
+            sqlx::query!(
+                r#"
+INSERT INTO passkeys
+(user_id, name, passkey_user_id, passkey, credential_id, registered, last_used, user_verified)
+VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+ON CONFLICT(user_id, name) DO UPDATE
+SET passkey_user_id = $3, passkey = $4, credential_id = $5, registered = $6, last_used = $7,
+user_verified = $8"#,
+                self.user_id,
+                self.name,
+                self.passkey_user_id,
+                self.passkey,
+                self.credential_id,
+                self.registered,
+                self.last_used,
+                self.user_verified,
+            )
+            .execute(DB::conn())
+            .await?;
+        }
+
+        Self::clear_caches_by_id_name(&self.user_id, None, &self.name).await
+    }
+
     pub async fn count_for_user(user_id: String) -> Result<i64, ErrorResponse> {
         let count: i64 = if is_hiqlite() {
             DB::client()
@@ -184,7 +234,7 @@ VALUES ($1, $2, $3, $4, $5, $6, $7, $8)"#,
 
             // in this case, we need to check against the current password policy,
             // if the password should expire again
-            let policy = PasswordPolicy::find().await?;
+            let policy = PasswordPolicy::find_for_user(&user).await?;
             if let Some(valid_days) = policy.valid_days {
                 if user.password.is_some() {
                     user.password_expires = Some(
@@ -610,6 +660,27 @@ impl WebauthnData {
         Ok(())
     }
 
+    /// Validates the client access policies, if they have not been checked during the login
+    /// already. Returns an `access_denied` redirect to the client on failure.
+    pub async fn validate_access(
+        &self,
+        data: &web::Data<AppState>,
+        req: &HttpRequest,
+    ) -> Result<(), ErrorResponse> {
+        let Some(check) = &self.access_check else {
+            return Ok(());
+        };
+
+        let client = Client::find_maybe_ephemeral(check.client_id.clone()).await?;
+        let user = User::find(self.user_id.clone()).await?;
+        client
+            .validate_login_access(data, &user, real_ip_from_req(req).ok())
+            .await
+            .map_err(|err| {
+                Client::access_denied_redirect(err, &check.redirect_uri, check.state.as_deref())
+            })
+    }
+
     pub async fn find(code: String) -> Result<Self, ErrorResponse> {
         let res: Option<Self> = DB::client().get(Cache::Webauthn, code).await?;
         match res {
@@ -687,6 +758,20 @@ pub struct WebauthnLoginReq {
     pub user_id: String,
     pub header_loc: String,
     pub header_origin: Option<String>,
+    /// The user wants to trust this device after a successful MFA login
+    #[serde(default)]
+    pub trust_device: bool,
+    /// Logins without a password are only authenticated with the passkey. The client access
+    /// policies are checked after the ceremony in this case.
+    #[serde(default)]
+    pub access_check: Option<WebauthnLoginAccessCheck>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct WebauthnLoginAccessCheck {
+    pub client_id: String,
+    pub redirect_uri: String,
+    pub state: Option<String>,
 }
 
 // CRUD
@@ -125,6 +125,7 @@ impl WellKnown {
             "family_name".to_string(),
             "roles".to_string(),
             "groups".to_string(),
+            "org".to_string(),
             "custom".to_string(),
         ];
         let claim_types_supported = vec![
@@ -1,14 +1,18 @@
 use crate::database::DB;
 use crate::events::{
-    EVENT_LEVEL_FAILED_LOGIN, EVENT_LEVEL_FAILED_LOGINS_10, EVENT_LEVEL_FAILED_LOGINS_15,
-    EVENT_LEVEL_FAILED_LOGINS_20, EVENT_LEVEL_FAILED_LOGINS_25, EVENT_LEVEL_FAILED_LOGINS_7,
-    EVENT_LEVEL_IP_BLACKLISTED, EVENT_LEVEL_JWKS_ROTATE, EVENT_LEVEL_NEW_RAUTHY_ADMIN,
-    EVENT_LEVEL_NEW_RAUTHY_VERSION, EVENT_LEVEL_NEW_USER, EVENT_LEVEL_RAUTHY_HEALTHY,
-    EVENT_LEVEL_RAUTHY_START, EVENT_LEVEL_RAUTHY_UNHEALTHY, EVENT_LEVEL_SECRETS_MIGRATED,
-    EVENT_LEVEL_USER_EMAIL_CHANGE, EVENT_LEVEL_USER_PASSWORD_RESET,
+    EVENT_LEVEL_CLIENT_ACCESS_DENIED, EVENT_LEVEL_FAILED_LOGIN, EVENT_LEVEL_FAILED_LOGINS_10,
+    EVENT_LEVEL_FAILED_LOGINS_15, EVENT_LEVEL_FAILED_LOGINS_20, EVENT_LEVEL_FAILED_LOGINS_25,
+    EVENT_LEVEL_FAILED_LOGINS_7, EVENT_LEVEL_IMPOSSIBLE_TRAVEL, EVENT_LEVEL_IP_BLACKLISTED,
+    EVENT_LEVEL_JWKS_ROTATE, EVENT_LEVEL_NEW_RAUTHY_ADMIN, EVENT_LEVEL_NEW_RAUTHY_VERSION,
+    EVENT_LEVEL_NEW_USER, EVENT_LEVEL_RAUTHY_HEALTHY, EVENT_LEVEL_RAUTHY_START,
+    EVENT_LEVEL_RAUTHY_UNHEALTHY, EVENT_LEVEL_SECRETS_MIGRATED, EVENT_LEVEL_USER_EMAIL_CHANGE,
+    EVENT_LEVEL_USER_IMPERSONATED, EVENT_LEVEL_USER_LOCKED, EVENT_LEVEL_USER_PASSWORD_RESET,
+    EVENT_LEVEL_USER_REGISTRATION_PENDING,
 };
+use crate::geoip;
 use chrono::{DateTime, Timelike, Utc};
 use hiqlite::{params, Param, Row};
+use rauthy_api_types::generic::GeoLocationResponse;
 use rauthy_common::constants::EMAIL_SUB_PREFIX;
 use rauthy_common::is_hiqlite;
 use rauthy_common::utils::{get_local_hostname, get_rand};
@@ -138,6 +142,8 @@ impl EventLevel {
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum EventType {
+    ClientAccessDenied,
+    ImpossibleTravel,
     InvalidLogins,
     IpBlacklisted,
     IpBlacklistRemoved,
@@ -145,13 +151,17 @@ pub enum EventType {
     NewUserRegistered,
     NewRauthyAdmin,
     NewRauthyVersion,
-    PossibleBruteForce, // TODO
+    PossibleBruteForce,
     RauthyStarted,
     RauthyHealthy,
     RauthyUnhealthy,
     SecretsMigrated,
     UserEmailChange,
+    UserImpersonated,
+    UserLocked,
     UserPasswordReset,
+    UserRegistrationPending,
+    UserUnlocked,
     Test,
 }
 
@@ -165,6 +175,8 @@ impl Default for EventType {
 impl Display for EventType {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         match self {
+            EventType::ClientAccessDenied => write!(f, "Client access denied"),
+            EventType::ImpossibleTravel => write!(f, "Impossible travel"),
             EventType::InvalidLogins => write!(f, "Invalid logins"),
             EventType::IpBlacklisted => write!(f, "IP blacklisted"),
             EventType::IpBlacklistRemoved => write!(f, "IP blacklist removed"),
@@ -178,7 +190,13 @@ impl Display for EventType {
             EventType::RauthyUnhealthy => write!(f, "Rauthy is unhealthy"),
             EventType::SecretsMigrated => write!(f, "Secrets have been migrated"),
             EventType::UserEmailChange => write!(f, "User's E-Mail has been changed"),
+            EventType::UserImpersonated => write!(f, "User has been impersonated"),
+            EventType::UserLocked => write!(f, "User account has been locked"),
             EventType::UserPasswordReset => write!(f, "User has reset its password"),
+            EventType::UserRegistrationPending => {
+                write!(f, "New user registration awaits approval")
+            }
+            EventType::UserUnlocked => write!(f, "User account has been unlocked"),
             EventType::Test => write!(f, "TEST"),
         }
     }
@@ -187,6 +205,8 @@ impl Display for EventType {
 impl From<rauthy_api_types::events::EventType> for EventType {
     fn from(value: rauthy_api_types::events::EventType) -> Self {
         match value {
+            rauthy_api_types::events::EventType::ClientAccessDenied => Self::ClientAccessDenied,
+            rauthy_api_types::events::EventType::ImpossibleTravel => Self::ImpossibleTravel,
             rauthy_api_types::events::EventType::InvalidLogins => Self::InvalidLogins,
             rauthy_api_types::events::EventType::IpBlacklisted => Self::IpBlacklisted,
             rauthy_api_types::events::EventType::IpBlacklistRemoved => Self::IpBlacklistRemoved,
@@ -200,7 +220,13 @@ impl From<rauthy_api_types::events::EventType> for EventType {
             rauthy_api_types::events::EventType::RauthyUnhealthy => Self::RauthyUnhealthy,
             rauthy_api_types::events::EventType::SecretsMigrated => Self::SecretsMigrated,
             rauthy_api_types::events::EventType::UserEmailChange => Self::UserEmailChange,
+            rauthy_api_types::events::EventType::UserImpersonated => Self::UserImpersonated,
+            rauthy_api_types::events::EventType::UserLocked => Self::UserLocked,
             rauthy_api_types::events::EventType::UserPasswordReset => Self::UserPasswordReset,
+            rauthy_api_types::events::EventType::UserRegistrationPending => {
+                Self::UserRegistrationPending
+            }
+            rauthy_api_types::events::EventType::UserUnlocked => Self::UserUnlocked,
             rauthy_api_types::events::EventType::Test => Self::Test,
         }
     }
@@ -209,6 +235,8 @@ impl From<rauthy_api_types::events::EventType> for EventType {
 impl EventType {
     pub fn as_str(&self) -> &str {
         match self {
+            Self::ClientAccessDenied => "ClientAccessDenied",
+            Self::ImpossibleTravel => "ImpossibleTravel",
             Self::InvalidLogins => "InvalidLogins",
             Self::IpBlacklisted => "IpBlacklisted",
             Self::IpBlacklistRemoved => "IpBlacklistRemoved",
@@ -222,7 +250,11 @@ impl EventType {
             Self::RauthyUnhealthy => "RauthyUnhealthy",
             Self::SecretsMigrated => "SecretsMigrated",
             Self::UserEmailChange => "UserEmailChange",
+            Self::UserImpersonated => "UserImpersonated",
+            Self::UserLocked => "UserLocked",
             Self::UserPasswordReset => "UserPasswordReset",
+            Self::UserRegistrationPending => "UserRegistrationPending",
+            Self::UserUnlocked => "UserUnlocked",
             Self::Test => "TEST",
         }
     }
@@ -244,6 +276,12 @@ impl EventType {
             EventType::UserEmailChange => 12,
             EventType::UserPasswordReset => 13,
             EventType::Test => 14,
+            EventType::UserImpersonated => 15,
+            EventType::UserRegistrationPending => 16,
+            EventType::ClientAccessDenied => 17,
+            EventType::UserLocked => 18,
+            EventType::UserUnlocked => 19,
+            EventType::ImpossibleTravel => 20,
         }
     }
 }
@@ -251,6 +289,8 @@ impl EventType {
 impl From<String> for EventType {
     fn from(value: String) -> Self {
         match value.as_str() {
+            "ClientAccessDenied" => Self::ClientAccessDenied,
+            "ImpossibleTravel" => Self::ImpossibleTravel,
             "InvalidLogins" => Self::InvalidLogins,
             "IpBlacklisted" => Self::IpBlacklisted,
             "IpBlacklistRemoved" => Self::IpBlacklistRemoved,
@@ -264,7 +304,11 @@ impl From<String> for EventType {
             "RauthyUnhealthy" => Self::RauthyUnhealthy,
             "SecretsMigrated" => Self::SecretsMigrated,
             "UserEmailChange" => Self::UserEmailChange,
+            "UserImpersonated" => Self::UserImpersonated,
+            "UserLocked" => Self::UserLocked,
             "UserPasswordReset" => Self::UserPasswordReset,
+            "UserRegistrationPending" => Self::UserRegistrationPending,
+            "UserUnlocked" => Self::UserUnlocked,
             "TEST" => Self::Test,
             // just return test to never panic
             _ => Self::Test,
@@ -296,6 +340,12 @@ impl From<i64> for EventType {
             12 => EventType::UserEmailChange,
             13 => EventType::UserPasswordReset,
             14 => EventType::Test,
+            15 => EventType::UserImpersonated,
+            16 => EventType::UserRegistrationPending,
+            17 => EventType::ClientAccessDenied,
+            18 => EventType::UserLocked,
+            19 => EventType::UserUnlocked,
+            20 => EventType::ImpossibleTravel,
             _ => EventType::Test,
         }
     }
@@ -312,6 +362,14 @@ pub struct Event {
     pub text: Option<String>,
 }
 
+#[derive(Debug, Serialize)]
+pub struct EventWithGeo<'a> {
+    #[serde(flatten)]
+    event: &'a Event,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    geo: Option<GeoLocationResponse>,
+}
+
 impl<'r> From<hiqlite::Row<'r>> for Event {
     fn from(mut row: Row<'r>) -> Self {
         Self {
@@ -368,18 +426,26 @@ impl From<&Event> for Notification {
         let row_1 = format!("{} {}", d.format("%Y/%m/%d %H:%M:%S"), value.typ);
 
         let row_2 = match value.typ {
+            EventType::ImpossibleTravel => Some(format!(
+                "{} with {} km/h - IP: `{}`",
+                value.text.as_deref().unwrap_or_default(),
+                value.data.unwrap_or_default(),
+                value.ip.as_deref().unwrap_or_default()
+            )),
             EventType::InvalidLogins => Some(format!(
                 "{} invalid logins from IP: `{}`",
                 value.data.unwrap_or_default(),
                 value.ip.as_deref().unwrap_or_default()
             )),
             EventType::IpBlacklisted => {
-                let d =
-                    DateTime::from_timestamp(value.data.unwrap_or_default(), 0).unwrap_or_default();
+                let until = match value.data.and_then(|ts| DateTime::from_timestamp(ts, 0)) {
+                    None => "permanently".to_string(),
+                    Some(d) => format!("until {}", d.format("%Y/%m/%d %H:%M:%S")),
+                };
                 Some(format!(
-                    "IP `{}` blacklisted until {}",
+                    "IP `{}` blacklisted {}",
                     value.ip.as_deref().unwrap_or_default(),
-                    d.format("%Y/%m/%d %H:%M:%S"),
+                    until,
                 ))
             }
             EventType::IpBlacklistRemoved => Some(format!(
@@ -399,7 +465,8 @@ impl From<&Event> for Notification {
             )),
             EventType::NewRauthyVersion => value.text.clone(),
             EventType::PossibleBruteForce => Some(format!(
-                "From IP: `{}`",
+                "{} - last IP: `{}`",
+                value.text.as_deref().unwrap_or_default(),
                 value.ip.as_deref().unwrap_or_default()
             )),
             EventType::RauthyStarted => value.text.clone(),
@@ -407,7 +474,33 @@ impl From<&Event> for Notification {
             EventType::RauthyUnhealthy => value.text.clone(),
             EventType::SecretsMigrated => value.ip.clone(),
             EventType::UserEmailChange => value.text.clone(),
+            EventType::UserImpersonated => Some(format!(
+                "{} from IP: `{}`",
+                value.text.as_deref().unwrap_or_default(),
+                value.ip.as_deref().unwrap_or_default()
+            )),
+            EventType::UserLocked => {
+                let until = match value.data.and_then(|ts| DateTime::from_timestamp(ts, 0)) {
+                    None => "until it is unlocked".to_string(),
+                    Some(d) => format!("until {}", d.format("%Y/%m/%d %H:%M:%S")),
+                };
+                Some(format!(
+                    "Account `{}` locked {} - last IP: `{}`",
+                    value.text.as_deref().unwrap_or_default(),
+                    until,
+                    value.ip.as_deref().unwrap_or_default()
+                ))
+            }
             EventType::UserPasswordReset => value.text.clone(),
+            EventType::UserRegistrationPending => Some(format!(
+                "E-Mail `{}` awaits registration approval from IP: `{}`",
+                value.text.as_deref().unwrap_or_default(),
+                value.ip.as_deref().unwrap_or_default()
+            )),
+            EventType::UserUnlocked => Some(format!(
+                "Account `{}` has been unlocked",
+                value.text.as_deref().unwrap_or_default()
+            )),
             EventType::Test => value.text.clone(),
         };
 
@@ -570,7 +663,15 @@ ORDER BY timestamp DESC"#,
     }
 
     pub fn as_json(&self) -> String {
-        serde_json::to_string(self).unwrap()
+        serde_json::to_string(&self.with_geo()).unwrap()
+    }
+
+    /// Adds the GeoIP location of the events' IP, if any database has been configured.
+    pub fn with_geo(&self) -> EventWithGeo<'_> {
+        EventWithGeo {
+            event: self,
+            geo: geoip::lookup_str(self.ip.as_deref()).map(GeoLocationResponse::from),
+        }
     }
 }
 
@@ -597,6 +698,61 @@ impl Event {
     }
 
     /// The EventLevel will change depending on the amount of invalid logins
+    pub fn client_access_denied(client_id: &str, user_email: &str, ip: Option<String>) -> Self {
+        Self::new(
+            EVENT_LEVEL_CLIENT_ACCESS_DENIED.get().cloned().unwrap(),
+            EventType::ClientAccessDenied,
+            ip,
+            None,
+            Some(format!(
+                "User `{}` has no access to client `{}`",
+                user_email, client_id
+            )),
+        )
+    }
+
+    pub fn client_geo_access_denied(
+        client_id: &str,
+        user_email: &str,
+        country: Option<&str>,
+        ip: Option<String>,
+    ) -> Self {
+        Self::new(
+            EVENT_LEVEL_CLIENT_ACCESS_DENIED.get().cloned().unwrap(),
+            EventType::ClientAccessDenied,
+            ip,
+            None,
+            Some(format!(
+                "User `{}` has no access to client `{}` from country `{}`",
+                user_email,
+                client_id,
+                country.unwrap_or("unknown")
+            )),
+        )
+    }
+
+    /// `speed` is the travel speed in km/h, which would have been necessary between the logins.
+    pub fn impossible_travel(
+        user_email: &str,
+        from_country: Option<&str>,
+        to_country: Option<&str>,
+        speed: i64,
+        ip: String,
+    ) -> Self {
+        Self::new(
+            EVENT_LEVEL_IMPOSSIBLE_TRAVEL.get().cloned().unwrap(),
+            EventType::ImpossibleTravel,
+            Some(ip),
+            Some(speed),
+            Some(format!(
+                "User `{}` from `{}` to `{}`",
+                user_email,
+                from_country.unwrap_or("unknown"),
+                to_country.unwrap_or("unknown")
+            )),
+        )
+    }
+
     pub fn invalid_login(failed_logins: u32, ip: String) -> Self {
         let level = match failed_logins {
             l if l >= 25 => EVENT_LEVEL_FAILED_LOGINS_25.get().unwrap(),
@@ -615,23 +771,26 @@ impl Event {
         )
     }
 
-    pub fn brute_force(ip: String) -> Self {
+    /// `text` describes the detected attack pattern.
+    pub fn brute_force(ip: String, text: String) -> Self {
         Self::new(
             EventLevel::Critical,
             EventType::PossibleBruteForce,
             Some(ip),
             None,
-            None,
+            Some(text),
         )
     }
 
-    pub fn ip_blacklisted(exp: DateTime<Utc>, ip: String) -> Self {
+    /// `exp: None` blacklists the IP permanently. The optional `reason` will be persisted
+    /// together with the blacklist entry.
+    pub fn ip_blacklisted(exp: Option<DateTime<Utc>>, ip: String, reason: Option<String>) -> Self {
         Self::new(
             EVENT_LEVEL_IP_BLACKLISTED.get().cloned().unwrap(),
             EventType::IpBlacklisted,
             Some(ip),
-            Some(exp.timestamp()),
-            None,
+            exp.map(|exp| exp.timestamp()),
+            reason,
         )
     }
 
@@ -761,6 +920,30 @@ impl Event {
         )
     }
 
+    pub fn user_impersonated(admin_email: &str, user_email: &str, ip: String) -> Self {
+        Self::new(
+            EVENT_LEVEL_USER_IMPERSONATED.get().cloned().unwrap(),
+            EventType::UserImpersonated,
+            Some(ip),
+            None,
+            Some(format!(
+                "Admin `{}` impersonates `{}`",
+                admin_email, user_email
+            )),
+        )
+    }
+
+    /// `locked_until: None` for locks, which need a manual unlock.
+    pub fn user_locked(email: String, locked_until: Option<i64>, ip: Option<String>) -> Self {
+        Self::new(
+            EVENT_LEVEL_USER_LOCKED.get().cloned().unwrap(),
+            EventType::UserLocked,
+            ip,
+            locked_until,
+            Some(email),
+        )
+    }
+
     pub fn user_password_reset(text: String, ip: Option<String>) -> Self {
         Self::new(
             EVENT_LEVEL_USER_PASSWORD_RESET.get().cloned().unwrap(),
@@ -771,13 +954,43 @@ impl Event {
         )
     }
 
+    pub fn user_registration_pending(email: String, ip: String) -> Self {
+        Self::new(
+            EVENT_LEVEL_USER_REGISTRATION_PENDING
+                .get()
+                .cloned()
+                .unwrap(),
+            EventType::UserRegistrationPending,
+            Some(ip),
+            None,
+            Some(email),
+        )
+    }
+
+    pub fn user_unlocked(email: String, ip: Option<String>) -> Self {
+        Self::new(
+            EVENT_LEVEL_USER_LOCKED.get().cloned().unwrap(),
+            EventType::UserUnlocked,
+            ip,
+            None,
+            Some(email),
+        )
+    }
+
     pub fn fmt_data(&self) -> String {
         match self.typ {
+            EventType::ClientAccessDenied => self.text.clone().unwrap_or_default(),
+            EventType::ImpossibleTravel => format!(
+                "{} with {} km/h",
+                self.text.as_deref().unwrap_or_default(),
+                self.data.unwrap_or_default()
+            ),
             EventType::InvalidLogins => format!("Counter: {}", self.data.unwrap_or_default()),
             EventType::IpBlacklisted => {
-                let d =
-                    DateTime::from_timestamp(self.data.unwrap_or_default(), 0).unwrap_or_default();
-                format!("IP blacklisted until {}", d.format("%Y/%m/%d %H:%M:%S"))
+                match self.data.and_then(|ts| DateTime::from_timestamp(ts, 0)) {
+                    None => "IP blacklisted permanently".to_string(),
+                    Some(d) => format!("IP blacklisted until {}", d.format("%Y/%m/%d %H:%M:%S")),
+                }
             }
             EventType::IpBlacklistRemoved => "IP removed from blacklist".to_string(),
             EventType::JwksRotated => String::default(),
@@ -793,8 +1006,7 @@ impl Event {
                     self.text.as_deref().unwrap_or_default()
                 )
             }
-            // PossibleBruteForce is not yet implemented / recognized
-            EventType::PossibleBruteForce => String::default(),
+            EventType::PossibleBruteForce => self.text.clone().unwrap_or_default(),
             EventType::RauthyStarted => self.text.clone().unwrap(),
             EventType::RauthyHealthy => self.text.clone().unwrap(),
             EventType::RauthyUnhealthy => self.text.clone().unwrap(),
@@ -802,12 +1014,32 @@ impl Event {
             EventType::UserEmailChange => {
                 format!("User E-Mail: {}", self.text.as_deref().unwrap_or_default())
             }
+            EventType::UserImpersonated => self.text.clone().unwrap_or_default(),
+            EventType::UserLocked => {
+                match self.data.and_then(|ts| DateTime::from_timestamp(ts, 0)) {
+                    None => format!(
+                        "User {} locked until unlocked",
+                        self.text.as_deref().unwrap_or_default()
+                    ),
+                    Some(d) => format!(
+                        "User {} locked until {}",
+                        self.text.as_deref().unwrap_or_default(),
+                        d.format("%Y/%m/%d %H:%M:%S")
+                    ),
+                }
+            }
             EventType::UserPasswordReset => {
                 format!(
                     "User {} has reset its password",
                     self.text.as_deref().unwrap_or_default()
                 )
             }
+            EventType::UserRegistrationPending => {
+                format!("User E-Mail: {}", self.text.as_deref().unwrap_or_default())
+            }
+            EventType::UserUnlocked => {
+                format!("User E-Mail: {}", self.text.as_deref().unwrap_or_default())
+            }
             EventType::Test => {
                 format!("Test Message: {}", self.text.as_deref().unwrap_or_default())
             }
@@ -1,5 +1,9 @@
+use crate::entity::ip_blacklist::{lists_version, parse_cidr, IpAllowlistEntry, IpBlacklistEntry};
 use chrono::{DateTime, Utc};
+use cidr::IpCidr;
+use rauthy_error::ErrorResponse;
 use std::collections::HashMap;
+use std::net::IpAddr;
 use std::time::Duration;
 use tokio::sync::oneshot;
 use tracing::{debug, error};
@@ -7,128 +11,180 @@ use tracing::{debug, error};
 #[derive(Debug)]
 pub enum IpBlacklistReq {
     CheckExp,
+    Reload(IpLists),
     Blacklist(IpBlacklist),
     BlacklistCheck(IpBlacklistCheck),
     BlacklistDelete(String),
-    LoginCheck(IpFailedLoginCheck),
-    LoginFailedSet(IpLoginFailedSet),
-    LoginFailedDelete(String),
-    GetBlacklistedIps(oneshot::Sender<HashMap<String, DateTime<Utc>>>),
+    AllowCheck(IpAllowCheck),
 }
 
 #[derive(Debug)]
 pub struct IpBlacklist {
+    /// single IP or CIDR range
     pub ip: String,
-    pub exp: DateTime<Utc>,
+    /// `None` for permanent entries
+    pub exp: Option<DateTime<Utc>>,
 }
 
 #[derive(Debug)]
 pub struct IpBlacklistCheck {
     pub ip: String,
+    /// Permanent entries will return `DateTime::<Utc>::MAX_UTC`.
     pub tx: oneshot::Sender<Option<DateTime<Utc>>>,
 }
 
 #[derive(Debug)]
-pub struct IpFailedLoginCheck {
+pub struct IpAllowCheck {
     pub ip: String,
-    pub increase_counter: bool,
-    /// counter for invalid requests from this IP
-    pub tx: oneshot::Sender<Option<u32>>,
+    /// `true` if the IP is on the allowlist
+    pub tx: oneshot::Sender<bool>,
 }
 
-#[derive(Debug)]
-pub struct IpLoginFailedSet {
-    pub ip: String,
-    pub invalid_logins: u32,
+/// In-memory snapshot of the blacklist and allowlist. Single IPs are kept in a `HashMap`,
+/// so the check for the by far most common case does not need to iterate over all ranges.
+#[derive(Debug, Default)]
+pub struct IpLists {
+    ips: HashMap<IpAddr, Option<DateTime<Utc>>>,
+    ranges: Vec<(IpCidr, Option<DateTime<Utc>>)>,
+    allow: Vec<IpCidr>,
+}
+
+impl IpLists {
+    pub async fn load() -> Result<Self, ErrorResponse> {
+        let mut slf = Self {
+            allow: IpAllowlistEntry::find_all()
+                .await?
+                .iter()
+                .filter_map(|entry| parse_cidr(&entry.cidr).ok())
+                .collect(),
+            ..Default::default()
+        };
+
+        for entry in IpBlacklistEntry::find_all().await? {
+            if let Ok(cidr) = parse_cidr(&entry.cidr) {
+                let exp = entry.exp.and_then(|ts| DateTime::from_timestamp(ts, 0));
+                slf.insert(cidr, exp);
+            }
+        }
+
+        Ok(slf)
+    }
+
+    fn insert(&mut self, cidr: IpCidr, exp: Option<DateTime<Utc>>) {
+        // partially allowed ranges are fine, they will be checked for each IP anyway
+        if self.allow.iter().any(|allowed| {
+            allowed.contains(&cidr.first_address()) && allowed.contains(&cidr.last_address())
+        }) {
+            return;
+        }
+
+        if cidr.is_host_address() {
+            self.ips.insert(cidr.first_address(), exp);
+        } else if let Some(existing) = self.ranges.iter_mut().find(|(c, _)| c == &cidr) {
+            existing.1 = exp;
+        } else {
+            self.ranges.push((cidr, exp));
+        }
+    }
+
+    fn remove(&mut self, cidr: &IpCidr) {
+        if cidr.is_host_address() {
+            self.ips.remove(&cidr.first_address());
+        } else {
+            self.ranges.retain(|(c, _)| c != cidr);
+        }
+    }
+
+    fn remove_expired(&mut self) -> usize {
+        let now = Utc::now();
+        let before = self.ips.len() + self.ranges.len();
+        self.ips
+            .retain(|_, exp| !matches!(exp, Some(exp) if *exp <= now));
+        self.ranges
+            .retain(|(_, exp)| !matches!(exp, Some(exp) if *exp <= now));
+        before - self.ips.len() - self.ranges.len()
+    }
+
+    #[inline]
+    fn is_allowed(&self, ip: &IpAddr) -> bool {
+        self.allow.iter().any(|cidr| cidr.contains(ip))
+    }
+
+    /// Returns the latest expiry of all matching entries, if the IP is blocked.
+    fn check(&self, ip: &IpAddr) -> Option<DateTime<Utc>> {
+        if self.is_allowed(ip) {
+            return None;
+        }
+
+        let exp = self.ips.get(ip).into_iter().chain(
+            self.ranges
+                .iter()
+                .filter(|(cidr, _)| cidr.contains(ip))
+                .map(|(_, exp)| exp),
+        );
+        exp.map(|exp| exp.unwrap_or(DateTime::<Utc>::MAX_UTC)).max()
+    }
 }
 
-/// Handles blacklisted IP's and IP's with failed logins
+/// Handles blacklisted and allowed IP's.
+///
+/// The blacklist and allowlist are persisted in the database. Each node keeps a snapshot in
+/// memory, which will be reloaded as soon as another node changes any entry.
+/// Failed logins per IP are not tracked here, but in the replicated cache, so the counters
+/// are shared between all nodes.
 pub async fn run(tx: flume::Sender<IpBlacklistReq>, rx: flume::Receiver<IpBlacklistReq>) {
-    let mut data_blacklist: HashMap<String, DateTime<Utc>> = HashMap::with_capacity(2);
-    let mut data_failed_logins: HashMap<String, u32> = HashMap::with_capacity(2);
+    let mut lists = IpLists::default();
 
-    let mut exp_checker_handle = tokio::spawn(spawn_exp_checker(tx.clone()));
+    tokio::spawn(spawn_sync(tx));
 
     loop {
         match rx.recv_async().await {
             Ok(req) => match req {
                 IpBlacklistReq::CheckExp => {
-                    debug!("Running IpBlacklistReq::CheckExp");
-                    let now = Utc::now();
-                    let mut remove = Vec::default();
-                    for (k, v) in data_blacklist.iter() {
-                        if &now > v {
-                            remove.push(k.clone());
-                        }
-                    }
-
-                    debug!("Removing {} IPs in IpBlacklistReq::CheckExp", remove.len());
-                    for key in remove {
-                        data_blacklist.remove(&key);
-                    }
-
-                    if data_blacklist.is_empty() && !exp_checker_handle.is_finished() {
-                        exp_checker_handle.abort();
-                        debug!("IpBlacklist ExpChecker has been stopped");
+                    let removed = lists.remove_expired();
+                    if removed > 0 {
+                        debug!("Removed {} IPs in IpBlacklistReq::CheckExp", removed);
                     }
                 }
 
-                IpBlacklistReq::Blacklist(req) => {
-                    data_blacklist.insert(req.ip, req.exp);
-
-                    if exp_checker_handle.is_finished() {
-                        exp_checker_handle = tokio::spawn(spawn_exp_checker(tx.clone()));
-                    }
+                IpBlacklistReq::Reload(new_lists) => {
+                    debug!(
+                        "Reloaded IP blacklist with {} IPs, {} ranges and {} allowed ranges",
+                        new_lists.ips.len(),
+                        new_lists.ranges.len(),
+                        new_lists.allow.len(),
+                    );
+                    lists = new_lists;
                 }
 
+                IpBlacklistReq::Blacklist(req) => match parse_cidr(&req.ip) {
+                    Ok(cidr) => lists.insert(cidr, req.exp),
+                    Err(err) => error!("Cannot blacklist IP: {}", err.message),
+                },
+
                 IpBlacklistReq::BlacklistCheck(req) => {
-                    req.tx
-                        .send(data_blacklist.get(&req.ip).cloned())
-                        .expect("oneshot receiver to not be closed");
+                    let exp = req
+                        .ip
+                        .parse::<IpAddr>()
+                        .ok()
+                        .and_then(|ip| lists.check(&ip));
+                    req.tx.send(exp).expect("oneshot receiver to not be closed");
                 }
 
-                IpBlacklistReq::LoginCheck(req) => {
-                    let counter = if let Some(counter) = data_failed_logins.get_mut(&req.ip) {
-                        if req.increase_counter {
-                            *counter += 1;
-                            Some(*counter)
-                        } else {
-                            Some(*counter)
-                        }
-                    } else if req.increase_counter {
-                        data_failed_logins.insert(req.ip, 1);
-                        Some(1)
-                    } else {
-                        None
-                    };
-
+                IpBlacklistReq::AllowCheck(req) => {
+                    let allowed = req
+                        .ip
+                        .parse::<IpAddr>()
+                        .is_ok_and(|ip| lists.is_allowed(&ip));
                     req.tx
-                        .send(counter)
+                        .send(allowed)
                         .expect("oneshot receiver to not be closed");
                 }
 
-                IpBlacklistReq::LoginFailedSet(req) => {
-                    if let Some(counter) = data_failed_logins.get_mut(&req.ip) {
-                        if req.invalid_logins > *counter {
-                            *counter = req.invalid_logins;
-                        }
-                    } else {
-                        data_failed_logins.insert(req.ip, req.invalid_logins);
-                    }
-                }
-
                 IpBlacklistReq::BlacklistDelete(ip) => {
-                    data_blacklist.remove(&ip);
-                }
-
-                IpBlacklistReq::LoginFailedDelete(ip) => {
-                    data_failed_logins.remove(&ip);
-                }
-
-                IpBlacklistReq::GetBlacklistedIps(tx) => {
-                    // just clone the whole HashMap and don't do any iterations here
-                    // this handler is in a performance-critical spot.
-                    tx.send(data_blacklist.clone()).unwrap();
+                    if let Ok(cidr) = parse_cidr(&ip) {
+                        lists.remove(&cidr);
+                    }
                 }
             },
 
@@ -142,11 +198,83 @@ pub async fn run(tx: flume::Sender<IpBlacklistReq>, rx: flume::Receiver<IpBlackl
     }
 }
 
-async fn spawn_exp_checker(tx: flume::Sender<IpBlacklistReq>) {
-    debug!("IpBlacklist ExpChecker has been started");
+/// Removes expired entries and reloads the lists from the database, if they have been
+/// modified. The DB access happens here to never block the handler itself, which is in a
+/// performance-critical spot.
+async fn spawn_sync(tx: flume::Sender<IpBlacklistReq>) {
+    debug!("IpBlacklist sync has been started");
     let mut interval = tokio::time::interval(Duration::from_secs(10));
+    let mut loaded_version = None;
+
     loop {
+        // the first tick completes immediately and always loads the lists on startup
         interval.tick().await;
         tx.send_async(IpBlacklistReq::CheckExp).await.unwrap();
+
+        let version = match lists_version().await {
+            Ok(v) => v,
+            Err(err) => {
+                error!("Error looking up the IP blacklist version: {:?}", err);
+                continue;
+            }
+        };
+        if loaded_version.is_some() && loaded_version == version {
+            continue;
+        }
+
+        match IpLists::load().await {
+            Ok(lists) => {
+                tx.send_async(IpBlacklistReq::Reload(lists)).await.unwrap();
+                // use an empty version as the initial one to not load again for each tick
+                loaded_version = Some(version.unwrap_or_default());
+            }
+            Err(err) => {
+                error!(
+                    "Error loading the IP blacklist from the database: {:?}",
+                    err
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeDelta;
+
+    #[test]
+    fn test_ip_lists() {
+        let exp = Utc::now() + TimeDelta::minutes(10);
+        let mut lists = IpLists::default();
+        lists.insert(parse_cidr("192.168.1.10").unwrap(), Some(exp));
+        lists.insert(parse_cidr("10.0.0.0/8").unwrap(), None);
+        lists.insert(parse_cidr("2001:db8::/32").unwrap(), Some(exp));
+        lists.insert(
+            parse_cidr("172.16.0.1").unwrap(),
+            Some(Utc::now() - TimeDelta::minutes(1)),
+        );
+
+        let check = |lists: &IpLists, ip: &str| lists.check(&ip.parse().unwrap());
+        assert_eq!(check(&lists, "192.168.1.10"), Some(exp));
+        assert_eq!(check(&lists, "192.168.1.11"), None);
+        assert_eq!(check(&lists, "10.20.30.40"), Some(DateTime::<Utc>::MAX_UTC));
+        assert_eq!(check(&lists, "2001:db8:1::1"), Some(exp));
+        assert_eq!(check(&lists, "2001:db9::1"), None);
+
+        assert_eq!(lists.remove_expired(), 1);
+        assert_eq!(check(&lists, "172.16.0.1"), None);
+
+        // the allowlist always wins
+        lists.allow.push(parse_cidr("10.1.0.0/16").unwrap());
+        assert_eq!(check(&lists, "10.1.2.3"), None);
+        assert_eq!(check(&lists, "10.2.2.3"), Some(DateTime::<Utc>::MAX_UTC));
+        lists.insert(parse_cidr("10.1.2.3").unwrap(), None);
+        assert!(!lists
+            .ips
+            .contains_key(&"10.1.2.3".parse::<IpAddr>().unwrap()));
+
+        lists.remove(&parse_cidr("10.0.0.0/8").unwrap());
+        assert_eq!(check(&lists, "10.2.2.3"), None);
     }
 }
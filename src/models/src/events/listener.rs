@@ -1,6 +1,7 @@
 use crate::database::DB;
+use crate::entity::ip_blacklist::IpBlacklistEntry;
 use crate::events::event::{Event, EventLevel, EventType};
-use crate::events::ip_blacklist_handler::{IpBlacklist, IpBlacklistReq, IpLoginFailedSet};
+use crate::events::ip_blacklist_handler::{IpBlacklist, IpBlacklistReq};
 use crate::events::notifier::EventNotifier;
 use crate::events::EVENT_PERSIST_LEVEL;
 use actix_web_lab::sse;
@@ -56,6 +57,25 @@ impl EventListener {
             }
         }
 
+        // The blacklist is persisted only by the node, which created the event. All other
+        // nodes will update their in-memory lists via the event router.
+        match event.typ {
+            EventType::IpBlacklisted => {
+                let ip = event.ip.as_deref().unwrap_or_default();
+                if let Err(err) = IpBlacklistEntry::upsert(ip, event.data, event.text.clone()).await
+                {
+                    error!("Persisting IP blacklist entry: {:?}", err);
+                }
+            }
+            EventType::IpBlacklistRemoved => {
+                let ip = event.ip.as_deref().unwrap_or_default();
+                if let Err(err) = IpBlacklistEntry::delete(ip).await {
+                    error!("Deleting IP blacklist entry: {:?}", err);
+                }
+            }
+            _ => {}
+        }
+
         // notify raft members
         let mut fails = 0;
         while let Err(err) = DB::client().notify(&event).await {
@@ -170,26 +190,15 @@ impl EventListener {
                 EventRouterMsg::Event(event) => {
                     debug!("received new event in EventListener::router: {:?}", event);
 
-                    let sse_payload =
-                        sse::Event::Data(sse::Data::new(serde_json::to_string(&event).unwrap()));
+                    let sse_payload = sse::Event::Data(sse::Data::new(event.as_json()));
 
                     // deserialize the event and check for important updates
                     match event.typ {
-                        EventType::InvalidLogins => {
-                            tx_ip_blacklist
-                                .send_async(IpBlacklistReq::LoginFailedSet(IpLoginFailedSet {
-                                    ip: event.ip.unwrap_or_default(),
-                                    invalid_logins: event.data.unwrap_or_default() as u32,
-                                }))
-                                .await
-                                .unwrap();
-                        }
                         EventType::IpBlacklisted => {
                             tx_ip_blacklist
                                 .send_async(IpBlacklistReq::Blacklist(IpBlacklist {
                                     ip: event.ip.unwrap_or_default(),
-                                    exp: DateTime::from_timestamp(event.data.unwrap(), 0)
-                                        .unwrap_or_default(),
+                                    exp: event.data.and_then(|ts| DateTime::from_timestamp(ts, 0)),
                                 }))
                                 .await
                                 .unwrap();
@@ -202,6 +211,10 @@ impl EventListener {
                                 .await
                                 .unwrap();
                         }
+                        EventType::ClientAccessDenied => {}
+                        EventType::ImpossibleTravel => {}
+                        // failed logins per IP are tracked in the replicated cache
+                        EventType::InvalidLogins => {}
                         EventType::JwksRotated => {}
                         EventType::NewUserRegistered => {}
                         EventType::NewRauthyAdmin => {}
@@ -212,7 +225,11 @@ impl EventListener {
                         EventType::RauthyUnhealthy => {}
                         EventType::SecretsMigrated => {}
                         EventType::UserEmailChange => {}
+                        EventType::UserImpersonated => {}
+                        EventType::UserLocked => {}
                         EventType::UserPasswordReset => {}
+                        EventType::UserRegistrationPending => {}
+                        EventType::UserUnlocked => {}
                         EventType::Test => {}
                     }
 
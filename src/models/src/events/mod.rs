@@ -12,8 +12,12 @@ pub mod notifier;
 
 pub static EVENT_PERSIST_LEVEL: OnceLock<i16> = OnceLock::new();
 pub static EVENT_LEVEL_NEW_USER: OnceLock<EventLevel> = OnceLock::new();
+pub static EVENT_LEVEL_CLIENT_ACCESS_DENIED: OnceLock<EventLevel> = OnceLock::new();
 pub static EVENT_LEVEL_USER_EMAIL_CHANGE: OnceLock<EventLevel> = OnceLock::new();
 pub static EVENT_LEVEL_USER_PASSWORD_RESET: OnceLock<EventLevel> = OnceLock::new();
+pub static EVENT_LEVEL_USER_IMPERSONATED: OnceLock<EventLevel> = OnceLock::new();
+pub static EVENT_LEVEL_USER_LOCKED: OnceLock<EventLevel> = OnceLock::new();
+pub static EVENT_LEVEL_USER_REGISTRATION_PENDING: OnceLock<EventLevel> = OnceLock::new();
 pub static EVENT_LEVEL_NEW_RAUTHY_ADMIN: OnceLock<EventLevel> = OnceLock::new();
 pub static EVENT_LEVEL_NEW_RAUTHY_VERSION: OnceLock<EventLevel> = OnceLock::new();
 pub static EVENT_LEVEL_JWKS_ROTATE: OnceLock<EventLevel> = OnceLock::new();
@@ -22,6 +26,7 @@ pub static EVENT_LEVEL_RAUTHY_START: OnceLock<EventLevel> = OnceLock::new();
 pub static EVENT_LEVEL_RAUTHY_HEALTHY: OnceLock<EventLevel> = OnceLock::new();
 pub static EVENT_LEVEL_RAUTHY_UNHEALTHY: OnceLock<EventLevel> = OnceLock::new();
 pub static EVENT_LEVEL_IP_BLACKLISTED: OnceLock<EventLevel> = OnceLock::new();
+pub static EVENT_LEVEL_IMPOSSIBLE_TRAVEL: OnceLock<EventLevel> = OnceLock::new();
 pub static EVENT_LEVEL_FAILED_LOGINS_25: OnceLock<EventLevel> = OnceLock::new();
 pub static EVENT_LEVEL_FAILED_LOGINS_20: OnceLock<EventLevel> = OnceLock::new();
 pub static EVENT_LEVEL_FAILED_LOGINS_15: OnceLock<EventLevel> = OnceLock::new();
@@ -49,6 +54,36 @@ pub fn init_event_vars() -> Result<(), ErrorResponse> {
             EventLevel::Notice,
         ))
         .unwrap();
+    EVENT_LEVEL_USER_IMPERSONATED
+        .set(map_env_var_level(
+            "EVENT_LEVEL_USER_IMPERSONATED",
+            EventLevel::Warning,
+        ))
+        .unwrap();
+    EVENT_LEVEL_USER_LOCKED
+        .set(map_env_var_level(
+            "EVENT_LEVEL_USER_LOCKED",
+            EventLevel::Warning,
+        ))
+        .unwrap();
+    EVENT_LEVEL_USER_REGISTRATION_PENDING
+        .set(map_env_var_level(
+            "EVENT_LEVEL_USER_REGISTRATION_PENDING",
+            EventLevel::Notice,
+        ))
+        .unwrap();
+    EVENT_LEVEL_CLIENT_ACCESS_DENIED
+        .set(map_env_var_level(
+            "EVENT_LEVEL_CLIENT_ACCESS_DENIED",
+            EventLevel::Notice,
+        ))
+        .unwrap();
+    EVENT_LEVEL_IMPOSSIBLE_TRAVEL
+        .set(map_env_var_level(
+            "EVENT_LEVEL_IMPOSSIBLE_TRAVEL",
+            EventLevel::Warning,
+        ))
+        .unwrap();
     EVENT_LEVEL_NEW_RAUTHY_ADMIN
         .set(map_env_var_level(
             "EVENT_LEVEL_RAUTHY_ADMIN",
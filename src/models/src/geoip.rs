@@ -0,0 +1,339 @@
+use crate::app_state::AppState;
+use crate::database::{Cache, DB};
+use crate::events::event::Event;
+use actix_web::web;
+use chrono::Utc;
+use maxminddb::{geoip2, MaxMindDBError, Reader};
+use rauthy_api_types::generic::GeoLocationResponse;
+use rauthy_common::constants::{
+    GEOIP_ALLOW_UNKNOWN, GEOIP_ASN_DB_PATH, GEOIP_DB_PATH, GEOIP_IMPOSSIBLE_TRAVEL_SPEED,
+    IDX_GEO_LAST_LOGIN,
+};
+use rauthy_error::{ErrorResponse, ErrorResponseType};
+use serde::{Deserialize, Serialize};
+use std::net::IpAddr;
+use std::sync::OnceLock;
+use tracing::{error, info, warn};
+
+static READER_CITY: OnceLock<Option<Reader<Vec<u8>>>> = OnceLock::new();
+static READER_ASN: OnceLock<Option<Reader<Vec<u8>>>> = OnceLock::new();
+
+/// City level locations are not too accurate. Shorter distances will never be considered as
+/// impossible travel to not produce false positives for logins from the same region.
+const TRAVEL_MIN_DISTANCE_KM: f64 = 500.0;
+/// The last login location only needs to be kept as long as any travel could be impossible.
+const TRAVEL_TRACKING_TTL: i64 = 86400;
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct GeoLocation {
+    pub country: Option<String>,
+    pub asn: Option<u32>,
+    pub asn_org: Option<String>,
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+}
+
+impl From<GeoLocation> for GeoLocationResponse {
+    fn from(value: GeoLocation) -> Self {
+        Self {
+            country: value.country,
+            asn: value.asn,
+            asn_org: value.asn_org,
+        }
+    }
+}
+
+/// Loads the configured GeoIP databases into memory. Must be called once at startup.
+/// Without any configured database, all lookups and policies are disabled.
+pub fn init() -> Result<(), ErrorResponse> {
+    let city = open_reader(GEOIP_DB_PATH.as_deref())?;
+    let asn = open_reader(GEOIP_ASN_DB_PATH.as_deref())?;
+
+    READER_CITY.set(city).map_err(|_| {
+        ErrorResponse::new(
+            ErrorResponseType::Internal,
+            "GeoIP has been initialized already",
+        )
+    })?;
+    READER_ASN.set(asn).map_err(|_| {
+        ErrorResponse::new(
+            ErrorResponseType::Internal,
+            "GeoIP has been initialized already",
+        )
+    })?;
+
+    Ok(())
+}
+
+fn open_reader(path: Option<&str>) -> Result<Option<Reader<Vec<u8>>>, ErrorResponse> {
+    let Some(path) = path else {
+        return Ok(None);
+    };
+
+    let reader = Reader::open_readfile(path).map_err(|err| {
+        ErrorResponse::new(
+            ErrorResponseType::Internal,
+            format!("Cannot open GeoIP database '{}': {}", path, err),
+        )
+    })?;
+    info!(
+        "Loaded GeoIP database '{}' of type {}",
+        path, reader.metadata.database_type
+    );
+
+    Ok(Some(reader))
+}
+
+#[inline]
+pub fn is_enabled() -> bool {
+    READER_CITY.get().is_some_and(|r| r.is_some()) || READER_ASN.get().is_some_and(|r| r.is_some())
+}
+
+/// Looks up the location for the given IP. Returns `None` if GeoIP is disabled, or if the IP
+/// cannot be found in any database, which is always the case for private ranges.
+pub fn lookup(ip: IpAddr) -> Option<GeoLocation> {
+    let mut location = GeoLocation::default();
+
+    // A `GeoIP2-City` lookup works for `GeoIP2-Country` databases as well.
+    if let Some(reader) = READER_CITY.get().and_then(|r| r.as_ref()) {
+        match reader.lookup::<geoip2::City>(ip) {
+            Ok(city) => {
+                location.country = city
+                    .country
+                    .and_then(|c| c.iso_code)
+                    .map(|code| code.to_uppercase());
+                if let Some(loc) = city.location {
+                    location.latitude = loc.latitude;
+                    location.longitude = loc.longitude;
+                }
+            }
+            Err(MaxMindDBError::AddressNotFoundError(_)) => {}
+            Err(err) => error!("GeoIP lookup error for {}: {}", ip, err),
+        }
+    }
+
+    if let Some(reader) = READER_ASN.get().and_then(|r| r.as_ref()) {
+        match reader.lookup::<geoip2::Asn>(ip) {
+            Ok(asn) => {
+                location.asn = asn.autonomous_system_number;
+                location.asn_org = asn.autonomous_system_organization.map(String::from);
+            }
+            Err(MaxMindDBError::AddressNotFoundError(_)) => {}
+            Err(err) => error!("GeoIP ASN lookup error for {}: {}", ip, err),
+        }
+    }
+
+    if location == GeoLocation::default() {
+        None
+    } else {
+        Some(location)
+    }
+}
+
+/// Convenience function for IPs stored as strings, like inside sessions, devices and events.
+pub fn lookup_str(ip: Option<&str>) -> Option<GeoLocation> {
+    if !is_enabled() {
+        return None;
+    }
+    ip.and_then(|ip| ip.parse::<IpAddr>().ok()).and_then(lookup)
+}
+
+/// Checks the country against an allow- and deny-list. An empty allow-list allows every
+/// country, that is not denied. Unknown countries are only allowed with `GEOIP_ALLOW_UNKNOWN`,
+/// as soon as any list is set.
+pub fn is_country_allowed<S: AsRef<str>>(country: Option<&str>, allow: &[S], deny: &[S]) -> bool {
+    is_country_allowed_with(country, allow, deny, *GEOIP_ALLOW_UNKNOWN)
+}
+
+fn is_country_allowed_with<S: AsRef<str>>(
+    country: Option<&str>,
+    allow: &[S],
+    deny: &[S],
+    allow_unknown: bool,
+) -> bool {
+    if allow.is_empty() && deny.is_empty() {
+        return true;
+    }
+
+    let Some(country) = country else {
+        return allow_unknown;
+    };
+    if deny
+        .iter()
+        .any(|c| c.as_ref().eq_ignore_ascii_case(country))
+    {
+        return false;
+    }
+    allow.is_empty()
+        || allow
+            .iter()
+            .any(|c| c.as_ref().eq_ignore_ascii_case(country))
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct LastLogin {
+    country: Option<String>,
+    latitude: f64,
+    longitude: f64,
+    timestamp: i64,
+}
+
+/// Compares a successful login with the last one of this user and sends out an
+/// `ImpossibleTravel` event, if the user would have needed to travel faster than
+/// `GEOIP_IMPOSSIBLE_TRAVEL_SPEED`. This is only an indicator and never rejects a login.
+pub async fn on_login(data: &web::Data<AppState>, user_id: &str, email: &str, ip: IpAddr) {
+    if *GEOIP_IMPOSSIBLE_TRAVEL_SPEED == 0 {
+        return;
+    }
+    let Some(location) = lookup(ip) else {
+        return;
+    };
+
+    if let Err(err) = detect_impossible_travel(data, user_id, email, ip, location).await {
+        error!("Error during impossible travel detection: {:?}", err);
+    }
+}
+
+async fn detect_impossible_travel(
+    data: &web::Data<AppState>,
+    user_id: &str,
+    email: &str,
+    ip: IpAddr,
+    location: GeoLocation,
+) -> Result<(), ErrorResponse> {
+    let (Some(latitude), Some(longitude)) = (location.latitude, location.longitude) else {
+        return Ok(());
+    };
+    let current = LastLogin {
+        country: location.country,
+        latitude,
+        longitude,
+        timestamp: Utc::now().timestamp(),
+    };
+
+    let client = DB::client();
+    let idx = format!("{}{}", IDX_GEO_LAST_LOGIN, user_id);
+    let last: Option<LastLogin> = client.get(Cache::App, &idx).await?;
+    client
+        .put(Cache::App, idx, &current, Some(TRAVEL_TRACKING_TTL))
+        .await?;
+
+    let Some(last) = last else {
+        return Ok(());
+    };
+    let Some(speed) = travel_speed(&last, &current) else {
+        return Ok(());
+    };
+    if speed <= *GEOIP_IMPOSSIBLE_TRAVEL_SPEED as f64 {
+        return Ok(());
+    }
+
+    warn!(
+        "Impossible travel for user '{}' from {} to {} with {:.0} km/h",
+        email,
+        last.country.as_deref().unwrap_or("unknown"),
+        current.country.as_deref().unwrap_or("unknown"),
+        speed
+    );
+    data.tx_events
+        .send_async(Event::impossible_travel(
+            email,
+            last.country.as_deref(),
+            current.country.as_deref(),
+            speed as i64,
+            ip.to_string(),
+        ))
+        .await
+        .unwrap();
+
+    Ok(())
+}
+
+/// Returns the speed in km/h, which is needed to travel between both logins, as long as the
+/// distance is big enough to be meaningful.
+fn travel_speed(from: &LastLogin, to: &LastLogin) -> Option<f64> {
+    let distance = distance_km(from.latitude, from.longitude, to.latitude, to.longitude);
+    if distance < TRAVEL_MIN_DISTANCE_KM {
+        return None;
+    }
+
+    // prevent a division by zero for logins at the same second
+    let secs = (to.timestamp - from.timestamp).max(1);
+    Some(distance / (secs as f64 / 3600.0))
+}
+
+/// Great-circle distance between 2 coordinates with the haversine formula.
+fn distance_km(lat1: f64, lon1: f64, lat2: f64, lon2: f64) -> f64 {
+    const EARTH_RADIUS_KM: f64 = 6371.0;
+
+    let d_lat = (lat2 - lat1).to_radians();
+    let d_lon = (lon2 - lon1).to_radians();
+    let a = (d_lat / 2.0).sin().powi(2)
+        + lat1.to_radians().cos() * lat2.to_radians().cos() * (d_lon / 2.0).sin().powi(2);
+
+    2.0 * EARTH_RADIUS_KM * a.sqrt().asin()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_country_allowed() {
+        let empty: [&str; 0] = [];
+        assert!(is_country_allowed_with(Some("DE"), &empty, &empty, false));
+        assert!(is_country_allowed_with(None, &empty, &empty, false));
+
+        let deny = ["RU", "KP"];
+        assert!(is_country_allowed_with(Some("DE"), &empty, &deny, false));
+        assert!(!is_country_allowed_with(Some("RU"), &empty, &deny, true));
+        assert!(!is_country_allowed_with(Some("kp"), &empty, &deny, true));
+        assert!(is_country_allowed_with(None, &empty, &deny, true));
+        assert!(!is_country_allowed_with(None, &empty, &deny, false));
+
+        let allow = ["DE", "AT", "CH"];
+        assert!(is_country_allowed_with(Some("AT"), &allow, &empty, false));
+        assert!(!is_country_allowed_with(Some("US"), &allow, &empty, true));
+
+        // the deny-list always wins
+        let deny = ["CH"];
+        assert!(!is_country_allowed_with(Some("CH"), &allow, &deny, true));
+    }
+
+    #[test]
+    fn test_impossible_travel() {
+        // Berlin -> New York is roughly 6385 km
+        let dist = distance_km(52.52, 13.405, 40.7128, -74.006);
+        assert!((dist - 6385.0).abs() < 20.0, "{}", dist);
+        assert_eq!(distance_km(52.52, 13.405, 52.52, 13.405), 0.0);
+
+        let berlin = LastLogin {
+            country: Some("DE".to_string()),
+            latitude: 52.52,
+            longitude: 13.405,
+            timestamp: 0,
+        };
+        let mut new_york = LastLogin {
+            country: Some("US".to_string()),
+            latitude: 40.7128,
+            longitude: -74.006,
+            timestamp: 3600,
+        };
+        let speed = travel_speed(&berlin, &new_york).unwrap();
+        assert!(speed > 6000.0);
+
+        // a regular flight is fine
+        new_york.timestamp = 10 * 3600;
+        let speed = travel_speed(&berlin, &new_york).unwrap();
+        assert!(speed < 1000.0);
+
+        // Berlin -> Potsdam is too close to be meaningful
+        let potsdam = LastLogin {
+            country: Some("DE".to_string()),
+            latitude: 52.3906,
+            longitude: 13.0645,
+            timestamp: 1,
+        };
+        assert!(travel_speed(&berlin, &potsdam).is_none());
+    }
+}
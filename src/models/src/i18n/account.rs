@@ -34,6 +34,8 @@ pub struct I18nAccount<'a> {
     generate_random: &'a str,
     given_name: &'a str,
     groups: &'a str,
+    impersonation: &'a str,
+    impersonation_exp: &'a str,
     invalid_input: &'a str,
     key: &'a str,
     key_unique: &'a str,
@@ -60,6 +62,7 @@ pub struct I18nAccount<'a> {
     phone: &'a str,
     provider_link: &'a str,
     provider_link_desc: &'a str,
+    provider_linked: &'a str,
     provider_unlink: &'a str,
     provider_unlink_desc: &'a str,
     reg_date: &'a str,
@@ -67,6 +70,10 @@ pub struct I18nAccount<'a> {
     roles: &'a str,
     save: &'a str,
     street: &'a str,
+    trusted_device_current: &'a str,
+    trusted_device_revoke: &'a str,
+    trusted_devices: &'a str,
+    trusted_devices_desc: &'a str,
     user: &'a str,
     user_created: &'a str,
     user_enabled: &'a str,
@@ -139,6 +146,8 @@ external provider or by local password. Do you want to request a reset?"#,
             generate_random: "Generate Randomly",
             given_name: "Given Name",
             groups: "Groups",
+            impersonation: "Impersonation session - you are acting on behalf of this user.",
+            impersonation_exp: "This session will expire at",
             invalid_input: "Invalid Input",
             key: "Key",
             key_unique: "Key must be unique",
@@ -166,15 +175,21 @@ external provider or by local password. Do you want to request a reset?"#,
             provider_link: "Federate Account",
             provider_link_desc: r#"You can link this account to one of the following login providers.
 After activating this function, you will be redirected to the login page of the chosen one.
-After a successful login and if the email matches, your account will be linked."#,
+After a successful login, your account will be linked. You can link multiple providers and
+log in with any of them afterward."#,
+            provider_linked: "Linked Providers",
             provider_unlink: "Unlink Federation",
             provider_unlink_desc: r#"Only if you have set up at least a password or a passkey for this
-account, you can unlink it from the upstream provider."#,
+account or another provider is linked, you can unlink it from the upstream provider."#,
             reg_date: "Registration Date",
             reg_ip: "Registration from IP",
             roles: "Roles",
             save: "Save",
             street: "Street",
+            trusted_device_current: "This device",
+            trusted_device_revoke: "Revoke trust",
+            trusted_devices: "Trusted Devices",
+            trusted_devices_desc: "Logins from these devices skip the 2nd factor",
             user: "User",
             user_created: "User Created",
             user_enabled: "User Enabled",
@@ -235,6 +250,8 @@ Login entweder per externem Provider oder lokalem Password möglich. Passwort Re
             generate_random: "Zufällig generiert",
             given_name: "Vorname",
             groups: "Gruppen",
+            impersonation: "Impersonation Session - Sie handeln im Namen dieses Benutzers.",
+            impersonation_exp: "Diese Session läuft ab um",
             invalid_input: "Ungültige Eingaben",
             key: "Schlüssel",
             key_unique: "Schlüssel muss einzigartig sein",
@@ -262,16 +279,22 @@ Login entweder per externem Provider oder lokalem Password möglich. Passwort Re
             provider_link: "Account Verbinden",
             provider_link_desc: r#"Dieser Account kann mit einem der folgenden Login Provider
 verbunden werden. Nach der Aktivierung des Prozesses wird eine Weiterleitung auf die Login Seite
-des gewählten Providers ausgelöst. Nach erfolgreichem Login und bei Übereinstimmung der E-Mail
-Adressen wird dieser Account verknüpft."#,
+des gewählten Providers ausgelöst. Nach erfolgreichem Login wird dieser Account verknüpft.
+Es können mehrere Provider verbunden werden, mit denen anschließend jeweils ein Login möglich ist."#,
+            provider_linked: "Verbundene Provider",
             provider_unlink: "Verbindung Trennen",
             provider_unlink_desc: r#"Nur wenn mindestens ein Passwort oder ein Passkey für diesen
-Account gesetzt ist, kann die Verbindung zum Provider gelöst werden."#,
+Account gesetzt ist oder ein weiterer Provider verbunden ist, kann die Verbindung zum Provider
+gelöst werden."#,
             reg_date: "Datum der Registrierung",
             reg_ip: "Registrierung von IP",
             roles: "Rollen",
             save: "Speichern",
             street: "Straße",
+            trusted_device_current: "Dieses Gerät",
+            trusted_device_revoke: "Vertrauen entziehen",
+            trusted_devices: "Vertrauenswürdige Geräte",
+            trusted_devices_desc: "Logins von diesen Geräten überspringen den 2. Faktor",
             user: "Benutzer",
             user_created: "Benutzer erstellt",
             user_enabled: "Benutzer Aktiviert",
@@ -329,6 +352,8 @@ hinzufügen:"#,
             generate_random: "随机生成",
             given_name: "名",
             groups: "组",
+            impersonation: "模拟会话 - 您正在代表此用户进行操作。",
+            impersonation_exp: "此会话将于以下时间过期：",
             invalid_input: "无效输入",
             key: "密钥",
             key_unique: "密钥必须是唯一的",
@@ -355,14 +380,20 @@ hinzufügen:"#,
             phone: "手机",
             provider_link: "联合账户",
             provider_link_desc: r#"您可以将此账户连接到下列登陆提供者之一。
-激活此功能后，您将被重定向至所选提供者的登陆页面。在成功登陆后，如果电子邮件匹配，您的账户将被连接。"#,
+激活此功能后，您将被重定向至所选提供者的登陆页面。在成功登陆后，您的账户将被连接。
+您可以连接多个提供者，并使用其中任意一个登陆。"#,
+            provider_linked: "已连接的登陆提供者",
             provider_unlink: "取消联合",
-            provider_unlink_desc: r#"仅当您已设置至少一个密码或登陆密钥后，您才能和登陆提供者取消连接。"#,
+            provider_unlink_desc: r#"仅当您已设置至少一个密码或登陆密钥，或已连接其他登陆提供者后，您才能和登陆提供者取消连接。"#,
             reg_date: "注册日期",
             reg_ip: "注册IP地址",
             roles: "角色",
             save: "保存",
             street: "街道",
+            trusted_device_current: "当前设备",
+            trusted_device_revoke: "取消信任",
+            trusted_devices: "受信任的设备",
+            trusted_devices_desc: "从这些设备登录时将跳过第二因素认证",
             user: "用户",
             user_created: "创建于",
             user_enabled: "启用",
@@ -6,8 +6,11 @@ use serde::Serialize;
 #[serde(rename_all = "camelCase")]
 pub struct I18nAuthorize<'a> {
     client_force_mfa: &'a str,
+    client_password_disabled: &'a str,
     email: &'a str,
     email_bad_format: &'a str,
+    email_code: &'a str,
+    email_code_sent: &'a str,
     email_required: &'a str,
     email_sent_msg: &'a str,
     http_429: &'a str,
@@ -15,11 +18,14 @@ pub struct I18nAuthorize<'a> {
     invalid_key_used: &'a str,
     login: &'a str,
     mfa_ack: &'a str,
+    org_select: &'a str,
     password: &'a str,
+    password_change_required: &'a str,
     password_forgotten: &'a str,
     password_request: &'a str,
     password_required: &'a str,
     provide_mfa: &'a str,
+    remember_device: &'a str,
     request_expires: &'a str,
     sign_up: &'a str,
 }
@@ -43,8 +49,12 @@ impl I18nAuthorize<'_> {
         Self {
             client_force_mfa: r#"This login forces MFA to achieve higher security.
 To get access, you need to log in to your account and add at least one additional Passkey"#,
+            client_password_disabled: "Password logins are not allowed for this application",
             email: "E-Mail",
             email_bad_format: "Bad E-Mail format",
+            email_code: "E-Mail Code",
+            email_code_sent: "Login from a new device. Please enter the code we sent to your \
+                E-Mail.",
             email_required: "E-Mail is required",
             email_sent_msg: "If your E-Mail exists, a request has been sent",
             http_429: "Too many invalid inputs. Locked until:",
@@ -52,11 +62,15 @@ To get access, you need to log in to your account and add at least one additiona
             invalid_key_used: "Invalid Key",
             login: "Login",
             mfa_ack: "Acknowledged",
+            org_select: "Please select an organization",
             password: "Password",
+            password_change_required: "Your password does not satisfy the policy of this \
+                application. We have sent you an E-Mail to set a new one.",
             password_forgotten: "Password forgotten?",
             password_request: "Request",
             password_required: "Password is required",
             provide_mfa: "Please login with your MFA device",
+            remember_device: "Remember this device",
             request_expires: "Request expires",
             sign_up: "User Registration",
         }
@@ -67,8 +81,12 @@ To get access, you need to log in to your account and add at least one additiona
             client_force_mfa: r#"Dieser Login setzt MFA voraus für eine erhöhte Sicherheit.
 Um Zugang zu bekommen, müssen Sie sie in Ihren Account einloggen und mindestens einen Passkey
 hinzufügen."#,
+            client_password_disabled: "Logins mit Passwort sind für diese Anwendung nicht erlaubt",
             email: "E-Mail",
             email_bad_format: "Inkorrektes E-Mail Format",
+            email_code: "E-Mail Code",
+            email_code_sent: "Login von einem neuen Gerät. Bitte geben Sie den Code ein, den wir \
+                Ihnen per E-Mail gesendet haben.",
             email_required: "E-Mail ist notwendig",
             email_sent_msg: "Sollte Ihre Adresse registriert sein, wurde eine Nachricht versandt",
             http_429: "Zu viele ungültige Versuche. Gesperrt bis:",
@@ -76,11 +94,15 @@ hinzufügen."#,
             invalid_key_used: "Ungültiger Sicherheitsschlüssel",
             login: "Login",
             mfa_ack: "Bestätigt",
+            org_select: "Bitte wählen Sie eine Organisation",
             password: "Password",
+            password_change_required: "Ihr Passwort erfüllt nicht die Richtlinie dieser \
+                Anwendung. Wir haben Ihnen eine E-Mail gesendet, um ein neues zu setzen.",
             password_forgotten: "Password vergessen?",
             password_request: "Anfordern",
             password_required: "Password ist notwendig",
             provide_mfa: "Bitte stellen Sie Ihr MFA Gerät zur Verfügung",
+            remember_device: "Dieses Gerät merken",
             request_expires: "Anfrage läuft ab",
             sign_up: "Benutzer Registrierung",
         }
@@ -90,8 +112,11 @@ hinzufügen."#,
         Self {
             client_force_mfa: r#"本次登陆强制使用多因子认证以增强安全性。
 要完成登陆，请登入您的账户并添加一个登陆密钥。"#,
+            client_password_disabled: "此应用程序不允许使用密码登陆",
             email: "电子邮件地址",
             email_bad_format: "错误的电子邮件地址格式",
+            email_code: "邮件验证码",
+            email_code_sent: "检测到新设备登录。请输入我们发送到您电子邮件的验证码。",
             email_required: "电子邮件地址必填。",
             email_sent_msg: "如果您的电子邮件存在，我们已发送请求邮件。",
             http_429: "过多无效输入，已锁定至：",
@@ -99,11 +124,15 @@ hinzufügen."#,
             invalid_key_used: "无效密钥",
             login: "登陆",
             mfa_ack: "已确认",
+            org_select: "请选择一个组织",
             password: "密码",
+            password_change_required:
+                "您的密码不符合此应用程序的密码策略。我们已向您发送电子邮件以设置新密码。",
             password_forgotten: "忘记密码",
             password_request: "请求",
             password_required: "密码必填。",
             provide_mfa: "请使用MFA设备登陆",
+            remember_device: "记住此设备",
             request_expires: "请求过期",
             sign_up: "用户注册",
         }
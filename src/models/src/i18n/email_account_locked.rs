@@ -0,0 +1,64 @@
+use crate::i18n::SsrJson;
+use crate::language::Language;
+use serde::Serialize;
+
+#[derive(Debug, Serialize)]
+pub struct I18nEmailAccountLocked<'a> {
+    pub subject: &'a str,
+    pub header: &'a str,
+    pub text: &'a str,
+    pub click_link: &'a str,
+    pub expires: &'a str,
+    pub button_text: &'a str,
+}
+
+impl SsrJson for I18nEmailAccountLocked<'_> {
+    fn build(lang: &Language) -> Self {
+        match lang {
+            Language::En => Self::build_en(),
+            Language::De => Self::build_de(),
+            Language::ZhHans => Self::build_zh_hans(),
+        }
+    }
+
+    fn as_json(&self) -> String {
+        serde_json::to_string(self).unwrap()
+    }
+}
+
+impl I18nEmailAccountLocked<'_> {
+    fn build_en() -> Self {
+        Self {
+            subject: "Account locked",
+            header: "Your account has been locked for",
+            text: "Your account has been locked after too many failed logins. If these logins \
+                were not made by you, you should change your password after unlocking it.",
+            click_link: "You can unlock your account with the link below.",
+            expires: "Link expires:",
+            button_text: "Unlock Account",
+        }
+    }
+
+    fn build_de() -> Self {
+        Self {
+            subject: "Account gesperrt",
+            header: "Ihr Account wurde gesperrt für",
+            text: "Ihr Account wurde nach zu vielen fehlgeschlagenen Logins gesperrt. Falls diese \
+                Logins nicht von Ihnen stammen, sollten Sie nach dem Entsperren Ihr Passwort ändern.",
+            click_link: "Sie können Ihren Account mit dem unten stehenden Link entsperren.",
+            expires: "Link gültig bis:",
+            button_text: "Account Entsperren",
+        }
+    }
+
+    fn build_zh_hans() -> Self {
+        Self {
+            subject: "账户已锁定",
+            header: "您的账户已被锁定：",
+            text: "由于登录失败次数过多，您的账户已被锁定。如果这些登录尝试并非您本人所为，请在解锁后修改密码。",
+            click_link: "您可以通过下方链接解锁您的账户。",
+            expires: "链接过期时间",
+            button_text: "解锁账户",
+        }
+    }
+}
@@ -0,0 +1,61 @@
+use crate::i18n::SsrJson;
+use crate::language::Language;
+use serde::Serialize;
+
+#[derive(Debug, Serialize)]
+pub struct I18nEmailLoginCode<'a> {
+    pub subject: &'a str,
+    pub header: &'a str,
+    pub text: &'a str,
+    pub expires: &'a str,
+    pub ignore: &'a str,
+}
+
+impl SsrJson for I18nEmailLoginCode<'_> {
+    fn build(lang: &Language) -> Self {
+        match lang {
+            Language::En => Self::build_en(),
+            Language::De => Self::build_de(),
+            Language::ZhHans => Self::build_zh_hans(),
+        }
+    }
+
+    fn as_json(&self) -> String {
+        serde_json::to_string(self).unwrap()
+    }
+}
+
+impl I18nEmailLoginCode<'_> {
+    fn build_en() -> Self {
+        Self {
+            subject: "Login Code",
+            header: "New device login for",
+            text: "A login from a new device has been started. Enter the code below to confirm it.",
+            expires: "Code expires:",
+            ignore: "If you did not try to log in, someone else knows your password. You should \
+                change it as soon as possible.",
+        }
+    }
+
+    fn build_de() -> Self {
+        Self {
+            subject: "Login Code",
+            header: "Login von neuem Gerät für",
+            text: "Ein Login von einem neuen Gerät wurde gestartet. Geben Sie den folgenden Code \
+                ein, um ihn zu bestätigen.",
+            expires: "Code gültig bis:",
+            ignore: "Falls Sie sich nicht einloggen wollten, kennt eine andere Person Ihr \
+                Passwort. Sie sollten es so schnell wie möglich ändern.",
+        }
+    }
+
+    fn build_zh_hans() -> Self {
+        Self {
+            subject: "登录验证码",
+            header: "新设备登录：",
+            text: "有新设备正在登录。请输入以下验证码以确认。",
+            expires: "验证码过期时间",
+            ignore: "如果这不是您本人的登录操作，说明他人已知晓您的密码，请尽快修改密码。",
+        }
+    }
+}
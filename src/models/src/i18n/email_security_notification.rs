@@ -0,0 +1,118 @@
+use crate::i18n::SsrJson;
+use crate::language::Language;
+use serde::Serialize;
+
+#[derive(Debug, Serialize)]
+pub struct I18nEmailSecurityNotification<'a> {
+    pub header: &'a str,
+    pub subject_new_login: &'a str,
+    pub subject_passkey_added: &'a str,
+    pub subject_passkey_removed: &'a str,
+    pub subject_password_changed: &'a str,
+    pub subject_provider_linked: &'a str,
+    pub text_new_login: &'a str,
+    pub text_passkey_added: &'a str,
+    pub text_passkey_removed: &'a str,
+    pub text_password_changed: &'a str,
+    pub text_provider_linked: &'a str,
+    pub label_device: &'a str,
+    pub label_ip: &'a str,
+    pub label_name: &'a str,
+    pub label_time: &'a str,
+    pub ignore: &'a str,
+    pub not_me: &'a str,
+    pub expires: &'a str,
+    pub button_text: &'a str,
+}
+
+impl SsrJson for I18nEmailSecurityNotification<'_> {
+    fn build(lang: &Language) -> Self {
+        match lang {
+            Language::En => Self::build_en(),
+            Language::De => Self::build_de(),
+            Language::ZhHans => Self::build_zh_hans(),
+        }
+    }
+
+    fn as_json(&self) -> String {
+        serde_json::to_string(self).unwrap()
+    }
+}
+
+impl I18nEmailSecurityNotification<'_> {
+    fn build_en() -> Self {
+        Self {
+            header: "Security notification for",
+            subject_new_login: "New login",
+            subject_passkey_added: "Passkey added",
+            subject_passkey_removed: "Passkey removed",
+            subject_password_changed: "Password changed",
+            subject_provider_linked: "Provider linked",
+            text_new_login: "Your account has just been used to log in from a new device.",
+            text_passkey_added: "A new passkey has been added to your account.",
+            text_passkey_removed: "A passkey has been removed from your account.",
+            text_password_changed: "The password for your account has been changed.",
+            text_provider_linked: "An upstream login provider has been linked to your account.",
+            label_device: "Device:",
+            label_ip: "IP:",
+            label_name: "Name:",
+            label_time: "Time:",
+            ignore: "If this was you, you can ignore this E-Mail.",
+            not_me: "If this was not you, use the link below. It will log you out everywhere \
+                and start a password reset for your account.",
+            expires: "Link expires:",
+            button_text: "This wasn't me",
+        }
+    }
+
+    fn build_de() -> Self {
+        Self {
+            header: "Sicherheitshinweis für",
+            subject_new_login: "Neuer Login",
+            subject_passkey_added: "Passkey hinzugefügt",
+            subject_passkey_removed: "Passkey entfernt",
+            subject_password_changed: "Passwort geändert",
+            subject_provider_linked: "Provider verknüpft",
+            text_new_login: "Ihr Account wurde soeben für einen Login von einem neuen Gerät \
+                verwendet.",
+            text_passkey_added: "Ein neuer Passkey wurde zu Ihrem Account hinzugefügt.",
+            text_passkey_removed: "Ein Passkey wurde von Ihrem Account entfernt.",
+            text_password_changed: "Das Passwort für Ihren Account wurde geändert.",
+            text_provider_linked: "Ein externer Login Provider wurde mit Ihrem Account \
+                verknüpft.",
+            label_device: "Gerät:",
+            label_ip: "IP:",
+            label_name: "Name:",
+            label_time: "Zeit:",
+            ignore: "Falls Sie das waren, können Sie diese E-Mail ignorieren.",
+            not_me: "Falls Sie das nicht waren, nutzen Sie den unten stehenden Link. Sie werden \
+                überall ausgeloggt und ein Passwort Reset für Ihren Account wird gestartet.",
+            expires: "Link gültig bis:",
+            button_text: "Das war ich nicht",
+        }
+    }
+
+    fn build_zh_hans() -> Self {
+        Self {
+            header: "安全通知：",
+            subject_new_login: "新登录",
+            subject_passkey_added: "已添加通行密钥",
+            subject_passkey_removed: "已删除通行密钥",
+            subject_password_changed: "密码已修改",
+            subject_provider_linked: "已关联登录提供商",
+            text_new_login: "您的账户刚刚在新设备上登录。",
+            text_passkey_added: "您的账户已添加新的通行密钥。",
+            text_passkey_removed: "您的账户已删除一个通行密钥。",
+            text_password_changed: "您的账户密码已被修改。",
+            text_provider_linked: "您的账户已关联外部登录提供商。",
+            label_device: "设备：",
+            label_ip: "IP：",
+            label_name: "名称：",
+            label_time: "时间：",
+            ignore: "如果这是您本人的操作，请忽略此邮件。",
+            not_me: "如果这不是您本人的操作，请使用下方链接。您将在所有设备上退出登录，并开始重置账户密码。",
+            expires: "链接过期时间",
+            button_text: "这不是我",
+        }
+    }
+}
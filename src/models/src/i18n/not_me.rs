@@ -0,0 +1,59 @@
+use crate::i18n::SsrJson;
+use crate::language::Language;
+use serde::Serialize;
+
+#[derive(Debug, Serialize)]
+pub struct I18nNotMe<'a> {
+    pub title: &'a str,
+    pub text: &'a str,
+    pub confirm: &'a str,
+    pub cancel: &'a str,
+}
+
+impl SsrJson for I18nNotMe<'_> {
+    fn build(lang: &Language) -> Self {
+        match lang {
+            Language::En => Self::build_en(),
+            Language::De => Self::build_de(),
+            Language::ZhHans => Self::build_zh_hans(),
+        }
+    }
+
+    fn as_json(&self) -> String {
+        serde_json::to_string(self).unwrap()
+    }
+}
+
+impl I18nNotMe<'_> {
+    fn build_en() -> Self {
+        Self {
+            title: "This wasn't me",
+            text: "If you did not do what has been reported in the E-Mail, you will be logged out \
+            everywhere, all trusted devices will be removed and you will be forwarded to set a new \
+            password.",
+            confirm: "Log out everywhere",
+            cancel: "Cancel",
+        }
+    }
+
+    fn build_de() -> Self {
+        Self {
+            title: "Das war ich nicht",
+            text: "Falls Sie die in der E-Mail gemeldete Aktion nicht selbst ausgeführt haben, werden \
+            Sie überall abgemeldet, alle vertrauenswürdigen Geräte werden entfernt und Sie werden \
+            weitergeleitet, um ein neues Passwort zu setzen.",
+            confirm: "Überall abmelden",
+            cancel: "Abbrechen",
+        }
+    }
+
+    fn build_zh_hans() -> Self {
+        Self {
+            title: "这不是我本人的操作",
+            text: "如果电子邮件中报告的操作不是您本人所为，您将在所有设备上退出登录，所有受信任的设备将被移除，\
+            并且您将被转到设置新密码的页面。",
+            confirm: "在所有设备上退出登录",
+            cancel: "取消",
+        }
+    }
+}
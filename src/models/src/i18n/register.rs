@@ -5,6 +5,7 @@ use serde::Serialize;
 #[derive(Debug, Default, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct I18nRegister<'a> {
+    approval_pending: &'a str,
     domain_allowed: &'a str,
     domain_err: &'a str,
     domain_restricted: &'a str,
@@ -37,6 +38,7 @@ impl SsrJson for I18nRegister<'_> {
 impl I18nRegister<'_> {
     fn build_en() -> Self {
         Self {
+            approval_pending: "Your registration will be reviewed. You will receive an E-Mail after it has been approved.",
             domain_allowed: "Allowed domain:",
             domain_err: "E-Mail domain not allowed",
             domain_restricted: "E-Mail domains are restricted",
@@ -55,6 +57,7 @@ impl I18nRegister<'_> {
 
     fn build_de() -> Self {
         Self {
+            approval_pending: "Ihre Registrierung wird geprüft. Sie erhalten eine E-Mail, sobald sie freigegeben wurde.",
             domain_allowed: "Erlaubte Domain:",
             domain_err: "E-Mail Domain ist nicht erlaubt",
             domain_restricted: "E-Mail Domains sind beschränkt",
@@ -73,6 +76,7 @@ impl I18nRegister<'_> {
 
     fn build_zh_hans() -> Self {
         Self {
+            approval_pending: "您的注册正在审核中。批准后您将收到一封电子邮件。",
             domain_allowed: "允许的域名：",
             domain_err: "此电子邮件域名不被允许",
             domain_restricted: "电子邮件域名被限制",
@@ -0,0 +1,56 @@
+use crate::i18n::SsrJson;
+use crate::language::Language;
+use serde::Serialize;
+
+#[derive(Debug, Serialize)]
+pub struct I18nUnlock<'a> {
+    pub title: &'a str,
+    pub text: &'a str,
+    pub confirm: &'a str,
+    pub cancel: &'a str,
+}
+
+impl SsrJson for I18nUnlock<'_> {
+    fn build(lang: &Language) -> Self {
+        match lang {
+            Language::En => Self::build_en(),
+            Language::De => Self::build_de(),
+            Language::ZhHans => Self::build_zh_hans(),
+        }
+    }
+
+    fn as_json(&self) -> String {
+        serde_json::to_string(self).unwrap()
+    }
+}
+
+impl I18nUnlock<'_> {
+    fn build_en() -> Self {
+        Self {
+            title: "Unlock Account",
+            text: "Your account has been locked after too many failed logins. If these logins \
+            were not made by you, you should change your password after unlocking it.",
+            confirm: "Unlock",
+            cancel: "Cancel",
+        }
+    }
+
+    fn build_de() -> Self {
+        Self {
+            title: "Account Entsperren",
+            text: "Ihr Account wurde nach zu vielen fehlgeschlagenen Logins gesperrt. Falls diese \
+            Logins nicht von Ihnen stammen, sollten Sie nach dem Entsperren Ihr Passwort ändern.",
+            confirm: "Entsperren",
+            cancel: "Abbrechen",
+        }
+    }
+
+    fn build_zh_hans() -> Self {
+        Self {
+            title: "解锁账户",
+            text: "由于登录失败次数过多，您的账户已被锁定。如果这些登录尝试并非您本人所为，请在解锁后修改密码。",
+            confirm: "解锁",
+            cancel: "取消",
+        }
+    }
+}
@@ -5,8 +5,9 @@
 use crate::entity::sessions::Session;
 use crate::entity::users::User;
 use crate::entity::users_values::UserValues;
+use actix_web::cookie::Cookie;
 use actix_web::http::header::{HeaderName, HeaderValue};
-use rauthy_api_types::oidc::JktClaim;
+use rauthy_api_types::oidc::{ActClaim, JktClaim, OrgClaim};
 use rauthy_error::{ErrorResponse, ErrorResponseType};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
@@ -21,9 +22,12 @@ pub mod database;
 pub mod email;
 pub mod entity;
 pub mod events;
+pub mod geoip;
 pub mod i18n;
 pub mod language;
 pub mod migration;
+pub mod saml;
+pub mod security_notifications;
 pub mod templates;
 
 pub enum AuthStep {
@@ -38,6 +42,10 @@ pub struct AuthStepLoggedIn {
     pub header_loc: (HeaderName, HeaderValue),
     pub header_csrf: (HeaderName, HeaderValue),
     pub header_origin: Option<(HeaderName, HeaderValue)>,
+    /// Set when the user decided to trust this device during the login
+    pub trusted_device_cookie: Option<Cookie<'static>>,
+    /// Set when security notifications are enabled to recognize new devices
+    pub known_devices_cookie: Option<Cookie<'static>>,
 }
 
 pub struct AuthStepAwaitWebauthn {
@@ -192,6 +200,10 @@ pub struct JwtAccessClaims {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub cnf: Option<JktClaim>,
     #[serde(skip_serializing_if = "Option::is_none")]
+    pub act: Option<ActClaim>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub org: Option<OrgClaim>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub custom: Option<HashMap<String, serde_json::Value>>,
 }
 
@@ -225,6 +237,10 @@ pub struct JwtIdClaims {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub cnf: Option<JktClaim>,
     #[serde(skip_serializing_if = "Option::is_none")]
+    pub act: Option<ActClaim>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub org: Option<OrgClaim>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub custom: Option<HashMap<String, serde_json::Value>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub webid: Option<String>,
@@ -241,6 +257,9 @@ pub struct JwtRefreshClaims {
     pub cnf: Option<JktClaim>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub did: Option<String>,
+    /// The id of the selected organization to keep the `org` claim stable across refreshes
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub org: Option<String>,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
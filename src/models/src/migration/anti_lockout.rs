@@ -52,6 +52,15 @@ pub async fn anti_lockout(issuer: &str) -> Result<(), ErrorResponse> {
         force_mfa: *ADMIN_FORCE_MFA,
         client_uri: Some(PUB_URL_WITH_SCHEME.to_string()),
         contacts: RAUTHY_ADMIN_EMAIL.clone(),
+        allowed_providers: None,
+        allow_password_login: true,
+        passkey_only: false,
+        auto_redirect_provider: None,
+        access_roles: None,
+        access_groups: None,
+        access_email_domains: None,
+        geo_allow_countries: None,
+        geo_deny_countries: None,
     };
 
     // MUST NOT use `insert or replace` syntax
@@ -1,21 +1,31 @@
+use crate::entity::admin_roles::AdminRoleEntity;
 use crate::entity::api_keys::ApiKeyEntity;
 use crate::entity::auth_providers::AuthProvider;
 use crate::entity::clients::Client;
 use crate::entity::clients_dyn::ClientDyn;
+use crate::entity::clients_saml::ClientSaml;
 use crate::entity::colors::ColorEntity;
 use crate::entity::config::ConfigEntity;
 use crate::entity::devices::DeviceEntity;
 use crate::entity::groups::Group;
+use crate::entity::ip_blacklist::{IpAllowlistEntry, IpBlacklistEntry};
 use crate::entity::jwk::Jwk;
 use crate::entity::logos::Logo;
 use crate::entity::magic_links::MagicLink;
+use crate::entity::organizations::{OrgMember, Organization};
 use crate::entity::password::RecentPasswordsEntity;
+use crate::entity::password_policies::ScopedPasswordPolicyEntity;
 use crate::entity::refresh_tokens::RefreshToken;
 use crate::entity::refresh_tokens_devices::RefreshTokenDevice;
 use crate::entity::roles::Role;
+use crate::entity::saml_certs::SamlCert;
 use crate::entity::scopes::Scope;
 use crate::entity::sessions::Session;
+use crate::entity::trusted_devices::TrustedDevice;
 use crate::entity::user_attr::{UserAttrConfigEntity, UserAttrValueEntity};
+use crate::entity::user_federations::UserFederation;
+use crate::entity::user_invitations::UserInvitation;
+use crate::entity::user_registrations::UserRegistration;
 use crate::entity::users::User;
 use crate::entity::users_values::UserValues;
 use crate::entity::webauthn::PasskeyEntity;
@@ -26,6 +36,16 @@ use rauthy_error::ErrorResponse;
 use sqlx::{FromRow, Row};
 use tracing::{debug, info};
 
+/// Tables, which have been added after the legacy SQLite schema, only exist in newer sources.
+async fn sqlite_table_exists(db: &sqlx::SqlitePool, table: &str) -> Result<bool, ErrorResponse> {
+    let exists = sqlx::query("SELECT name FROM sqlite_master WHERE type = 'table' AND name = $1")
+        .bind(table)
+        .fetch_optional(db)
+        .await?
+        .is_some();
+    Ok(exists)
+}
+
 /// Migrates `MIGRATE_DB_FROM` to current database
 pub async fn migrate_from_sqlite(db_from: sqlx::SqlitePool) -> Result<(), ErrorResponse> {
     info!("Starting migration to another DB");
@@ -68,6 +88,72 @@ pub async fn migrate_from_sqlite(db_from: sqlx::SqlitePool) -> Result<(), ErrorR
         .await?;
     inserts::users(before).await?;
 
+    // USER FEDERATIONS
+    // The legacy SQLite schema does not have this table and only knows a single federation
+    // stored on the user itself.
+    debug!("Migrating table: user_federations");
+    let before = if sqlite_table_exists(&db_from, "user_federations").await? {
+        sqlx::query_as::<_, UserFederation>("SELECT * FROM user_federations")
+            .fetch_all(&db_from)
+            .await?
+    } else {
+        sqlx::query_as::<_, UserFederation>(
+            r#"
+SELECT id AS user_id, auth_provider_id AS provider_id, federation_uid, created_at,
+    NULL AS last_login
+FROM users
+WHERE auth_provider_id IS NOT NULL AND federation_uid IS NOT NULL"#,
+        )
+        .fetch_all(&db_from)
+        .await?
+    };
+    inserts::user_federations(before).await?;
+
+    // ORGANIZATIONS
+    if sqlite_table_exists(&db_from, "organizations").await? {
+        debug!("Migrating table: organizations");
+        let before = sqlx::query_as::<_, Organization>("SELECT * FROM organizations")
+            .fetch_all(&db_from)
+            .await?;
+        inserts::organizations(before).await?;
+    }
+
+    // ORG MEMBERS
+    if sqlite_table_exists(&db_from, "org_members").await? {
+        debug!("Migrating table: org_members");
+        let before = sqlx::query_as::<_, OrgMember>("SELECT * FROM org_members")
+            .fetch_all(&db_from)
+            .await?;
+        inserts::org_members(before).await?;
+    }
+
+    // USER INVITATIONS
+    if sqlite_table_exists(&db_from, "user_invitations").await? {
+        debug!("Migrating table: user_invitations");
+        let before = sqlx::query_as::<_, UserInvitation>("SELECT * FROM user_invitations")
+            .fetch_all(&db_from)
+            .await?;
+        inserts::user_invitations(before).await?;
+    }
+
+    // USER REGISTRATIONS
+    if sqlite_table_exists(&db_from, "user_registrations").await? {
+        debug!("Migrating table: user_registrations");
+        let before = sqlx::query_as::<_, UserRegistration>("SELECT * FROM user_registrations")
+            .fetch_all(&db_from)
+            .await?;
+        inserts::user_registrations(before).await?;
+    }
+
+    // TRUSTED DEVICES
+    if sqlite_table_exists(&db_from, "trusted_devices").await? {
+        debug!("Migrating table: trusted_devices");
+        let before = sqlx::query_as::<_, TrustedDevice>("SELECT * FROM trusted_devices")
+            .fetch_all(&db_from)
+            .await?;
+        inserts::trusted_devices(before).await?;
+    }
+
     // PASSKEYS
     debug!("Migrating table: passkeys");
     let before = sqlx::query_as::<_, PasskeyEntity>("SELECT * FROM passkeys")
@@ -83,6 +169,15 @@ pub async fn migrate_from_sqlite(db_from: sqlx::SqlitePool) -> Result<(), ErrorR
         .await?;
     inserts::clients(before).await?;
 
+    // CLIENTS SAML
+    if sqlite_table_exists(&db_from, "clients_saml").await? {
+        debug!("Migrating table: clients_saml");
+        let before = sqlx::query_as::<_, ClientSaml>("SELECT * FROM clients_saml")
+            .fetch_all(&db_from)
+            .await?;
+        inserts::clients_saml(before).await?;
+    }
+
     // CLIENTS DYN
     debug!("Migrating table: clients_dyn");
     let before = sqlx::query_as::<_, ClientDyn>("SELECT * FROM clients_dyn")
@@ -120,6 +215,15 @@ pub async fn migrate_from_sqlite(db_from: sqlx::SqlitePool) -> Result<(), ErrorR
         .await?;
     inserts::jwks(before).await?;
 
+    // SAML CERTS
+    if sqlite_table_exists(&db_from, "saml_certs").await? {
+        debug!("Migrating table: saml_certs");
+        let before = sqlx::query_as::<_, SamlCert>("SELECT * FROM saml_certs")
+            .fetch_all(&db_from)
+            .await?;
+        inserts::saml_certs(before).await?;
+    }
+
     // MAGIC LINKS
     debug!("Migrating table: magic_links");
     let before = sqlx::query_as::<_, MagicLink>("SELECT * FROM magic_links")
@@ -212,6 +316,43 @@ pub async fn migrate_from_sqlite(db_from: sqlx::SqlitePool) -> Result<(), ErrorR
         .await?;
     inserts::recent_passwords(before).await?;
 
+    // ADMIN ROLES
+    if sqlite_table_exists(&db_from, "admin_roles").await? {
+        debug!("Migrating table: admin_roles");
+        let before = sqlx::query_as::<_, AdminRoleEntity>("SELECT * FROM admin_roles")
+            .fetch_all(&db_from)
+            .await?;
+        inserts::admin_roles(before).await?;
+    }
+
+    // PASSWORD POLICIES
+    if sqlite_table_exists(&db_from, "password_policies").await? {
+        debug!("Migrating table: password_policies");
+        let before =
+            sqlx::query_as::<_, ScopedPasswordPolicyEntity>("SELECT * FROM password_policies")
+                .fetch_all(&db_from)
+                .await?;
+        inserts::password_policies(before).await?;
+    }
+
+    // IP BLACKLIST
+    if sqlite_table_exists(&db_from, "ip_blacklist").await? {
+        debug!("Migrating table: ip_blacklist");
+        let before = sqlx::query_as::<_, IpBlacklistEntry>("SELECT * FROM ip_blacklist")
+            .fetch_all(&db_from)
+            .await?;
+        inserts::ip_blacklist(before).await?;
+    }
+
+    // IP ALLOWLIST
+    if sqlite_table_exists(&db_from, "ip_allowlist").await? {
+        debug!("Migrating table: ip_allowlist");
+        let before = sqlx::query_as::<_, IpAllowlistEntry>("SELECT * FROM ip_allowlist")
+            .fetch_all(&db_from)
+            .await?;
+        inserts::ip_allowlist(before).await?;
+    }
+
     // WEBIDS
     debug!("Migrating table: webids");
     let before = sqlx::query_as::<_, WebId>("SELECT * FROM webids")
@@ -263,6 +404,48 @@ pub async fn migrate_from_postgres(db_from: sqlx::PgPool) -> Result<(), ErrorRes
         .await?;
     inserts::users(before).await?;
 
+    // USER FEDERATIONS
+    debug!("Migrating table: user_federations");
+    let before = sqlx::query_as::<_, UserFederation>("SELECT * FROM user_federations")
+        .fetch_all(&db_from)
+        .await?;
+    inserts::user_federations(before).await?;
+
+    // ORGANIZATIONS
+    debug!("Migrating table: organizations");
+    let before = sqlx::query_as::<_, Organization>("SELECT * FROM organizations")
+        .fetch_all(&db_from)
+        .await?;
+    inserts::organizations(before).await?;
+
+    // ORG MEMBERS
+    debug!("Migrating table: org_members");
+    let before = sqlx::query_as::<_, OrgMember>("SELECT * FROM org_members")
+        .fetch_all(&db_from)
+        .await?;
+    inserts::org_members(before).await?;
+
+    // USER INVITATIONS
+    debug!("Migrating table: user_invitations");
+    let before = sqlx::query_as::<_, UserInvitation>("SELECT * FROM user_invitations")
+        .fetch_all(&db_from)
+        .await?;
+    inserts::user_invitations(before).await?;
+
+    // USER REGISTRATIONS
+    debug!("Migrating table: user_registrations");
+    let before = sqlx::query_as::<_, UserRegistration>("SELECT * FROM user_registrations")
+        .fetch_all(&db_from)
+        .await?;
+    inserts::user_registrations(before).await?;
+
+    // TRUSTED DEVICES
+    debug!("Migrating table: trusted_devices");
+    let before = sqlx::query_as::<_, TrustedDevice>("SELECT * FROM trusted_devices")
+        .fetch_all(&db_from)
+        .await?;
+    inserts::trusted_devices(before).await?;
+
     // PASSKEYS
     debug!("Migrating table: passkeys");
     let before = sqlx::query_as::<_, PasskeyEntity>("SELECT * FROM passkeys")
@@ -278,6 +461,13 @@ pub async fn migrate_from_postgres(db_from: sqlx::PgPool) -> Result<(), ErrorRes
         .await?;
     inserts::clients(before).await?;
 
+    // CLIENTS SAML
+    debug!("Migrating table: clients_saml");
+    let before = sqlx::query_as::<_, ClientSaml>("SELECT * FROM clients_saml")
+        .fetch_all(&db_from)
+        .await?;
+    inserts::clients_saml(before).await?;
+
     // CLIENTS DYN
     debug!("Migrating table: clients_dyn");
     let before = sqlx::query_as::<_, ClientDyn>("SELECT * FROM clients_dyn")
@@ -315,6 +505,13 @@ pub async fn migrate_from_postgres(db_from: sqlx::PgPool) -> Result<(), ErrorRes
         .await?;
     inserts::jwks(before).await?;
 
+    // SAML CERTS
+    debug!("Migrating table: saml_certs");
+    let before = sqlx::query_as::<_, SamlCert>("SELECT * FROM saml_certs")
+        .fetch_all(&db_from)
+        .await?;
+    inserts::saml_certs(before).await?;
+
     // MAGIC LINKS
     debug!("Migrating table: magic_links");
     let before = sqlx::query_as::<_, MagicLink>("SELECT * FROM magic_links")
@@ -410,6 +607,34 @@ pub async fn migrate_from_postgres(db_from: sqlx::PgPool) -> Result<(), ErrorRes
         .await?;
     inserts::recent_passwords(before).await?;
 
+    // ADMIN ROLES
+    debug!("Migrating table: admin_roles");
+    let before = sqlx::query_as::<_, AdminRoleEntity>("SELECT * FROM admin_roles")
+        .fetch_all(&db_from)
+        .await?;
+    inserts::admin_roles(before).await?;
+
+    // PASSWORD POLICIES
+    debug!("Migrating table: password_policies");
+    let before = sqlx::query_as::<_, ScopedPasswordPolicyEntity>("SELECT * FROM password_policies")
+        .fetch_all(&db_from)
+        .await?;
+    inserts::password_policies(before).await?;
+
+    // IP BLACKLIST
+    debug!("Migrating table: ip_blacklist");
+    let before = sqlx::query_as::<_, IpBlacklistEntry>("SELECT * FROM ip_blacklist")
+        .fetch_all(&db_from)
+        .await?;
+    inserts::ip_blacklist(before).await?;
+
+    // IP ALLOWLIST
+    debug!("Migrating table: ip_allowlist");
+    let before = sqlx::query_as::<_, IpAllowlistEntry>("SELECT * FROM ip_allowlist")
+        .fetch_all(&db_from)
+        .await?;
+    inserts::ip_allowlist(before).await?;
+
     // WEBIDS
     debug!("Migrating table: webids");
     let before = sqlx::query_as::<_, WebId>("SELECT * FROM webids")
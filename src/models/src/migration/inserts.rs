@@ -1,22 +1,32 @@
 use crate::database::DB;
+use crate::entity::admin_roles::AdminRoleEntity;
 use crate::entity::api_keys::ApiKeyEntity;
 use crate::entity::auth_providers::AuthProvider;
 use crate::entity::clients::Client;
 use crate::entity::clients_dyn::ClientDyn;
+use crate::entity::clients_saml::ClientSaml;
 use crate::entity::colors::ColorEntity;
 use crate::entity::config::ConfigEntity;
 use crate::entity::devices::DeviceEntity;
 use crate::entity::groups::Group;
+use crate::entity::ip_blacklist::{IpAllowlistEntry, IpBlacklistEntry};
 use crate::entity::jwk::Jwk;
 use crate::entity::logos::Logo;
 use crate::entity::magic_links::MagicLink;
+use crate::entity::organizations::{OrgMember, Organization};
 use crate::entity::password::RecentPasswordsEntity;
+use crate::entity::password_policies::ScopedPasswordPolicyEntity;
 use crate::entity::refresh_tokens::RefreshToken;
 use crate::entity::refresh_tokens_devices::RefreshTokenDevice;
 use crate::entity::roles::Role;
+use crate::entity::saml_certs::SamlCert;
 use crate::entity::scopes::Scope;
 use crate::entity::sessions::Session;
+use crate::entity::trusted_devices::TrustedDevice;
 use crate::entity::user_attr::{UserAttrConfigEntity, UserAttrValueEntity};
+use crate::entity::user_federations::UserFederation;
+use crate::entity::user_invitations::UserInvitation;
+use crate::entity::user_registrations::UserRegistration;
 use crate::entity::users::User;
 use crate::entity::users_values::UserValues;
 use crate::entity::webauthn::PasskeyEntity;
@@ -26,6 +36,43 @@ use hiqlite::{params, Param};
 use rauthy_common::is_hiqlite;
 use rauthy_error::ErrorResponse;
 
+pub async fn admin_roles(data_before: Vec<AdminRoleEntity>) -> Result<(), ErrorResponse> {
+    if is_hiqlite() {
+        DB::client()
+            .execute("DELETE FROM admin_roles", params!())
+            .await?;
+        for b in data_before {
+            DB::client()
+                .execute(
+                    r#"
+INSERT INTO admin_roles (id, name, description, access, group_scope)
+VALUES ($1, $2, $3, $4, $5)"#,
+                    params!(b.id, b.name, b.description, b.access, b.group_scope),
+                )
+                .await?;
+        }
+    } else {
+        sqlx::query("DELETE FROM admin_roles")
+            .execute(DB::conn())
+            .await?;
+        for b in data_before {
+            sqlx::query!(
+                r#"
+INSERT INTO admin_roles (id, name, description, access, group_scope)
+VALUES ($1, $2, $3, $4, $5)"#,
+                b.id,
+                b.name,
+                b.description,
+                b.access,
+                b.group_scope
+            )
+            .execute(DB::conn())
+            .await?;
+        }
+    }
+    Ok(())
+}
+
 pub async fn api_keys(data_before: Vec<ApiKeyEntity>) -> Result<(), ErrorResponse> {
     if is_hiqlite() {
         DB::client()
@@ -125,8 +172,10 @@ pub async fn auth_providers(data_before: Vec<AuthProvider>) -> Result<(), ErrorR
 INSERT INTO
 auth_providers (id, enabled, name, typ, issuer, authorization_endpoint, token_endpoint,
 userinfo_endpoint, client_id, secret, scope, admin_claim_path, admin_claim_value, mfa_claim_path,
-mfa_claim_value, allow_insecure_requests, use_pkce, root_pem)
-VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18)"#,
+mfa_claim_value, allow_insecure_requests, use_pkce, root_pem, jwks_uri, claim_mappings,
+email_domains, saml_idp_cert, saml_attr_mapping, userinfo_mapping)
+VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18, $19, $20,
+$21, $22, $23, $24)"#,
                     params!(
                         b.id,
                         b.enabled,
@@ -145,7 +194,13 @@ VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $
                         b.mfa_claim_value,
                         b.allow_insecure_requests,
                         b.use_pkce,
-                        b.root_pem
+                        b.root_pem,
+                        b.jwks_uri,
+                        b.claim_mappings,
+                        b.email_domains,
+                        b.saml_idp_cert,
+                        b.saml_attr_mapping,
+                        b.userinfo_mapping
                     ),
                 )
                 .await?;
@@ -160,8 +215,10 @@ VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $
 INSERT INTO
 auth_providers (id, enabled, name, typ, issuer, authorization_endpoint, token_endpoint,
 userinfo_endpoint, client_id, secret, scope, admin_claim_path, admin_claim_value, mfa_claim_path,
-mfa_claim_value, allow_insecure_requests, use_pkce, root_pem)
-VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18)"#,
+mfa_claim_value, allow_insecure_requests, use_pkce, root_pem, jwks_uri, claim_mappings,
+email_domains, saml_idp_cert, saml_attr_mapping, userinfo_mapping)
+VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18, $19, $20,
+$21, $22, $23, $24)"#,
                 b.id,
                 b.enabled,
                 b.name,
@@ -179,7 +236,13 @@ VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $
                 b.mfa_claim_value,
                 b.allow_insecure_requests,
                 b.use_pkce,
-                b.root_pem
+                b.root_pem,
+                b.jwks_uri,
+                b.claim_mappings,
+                b.email_domains,
+                b.saml_idp_cert,
+                b.saml_attr_mapping,
+                b.userinfo_mapping
             )
             .execute(DB::conn())
             .await?;
@@ -236,30 +299,42 @@ pub async fn clients(data_before: Vec<Client>) -> Result<(), ErrorResponse> {
 INSERT INTO clients
 (id, name, enabled, confidential, secret, secret_kid, redirect_uris, post_logout_redirect_uris,
 allowed_origins, flows_enabled, access_token_alg, id_token_alg, auth_code_lifetime,
-access_token_lifetime, scopes, default_scopes, challenge, force_mfa, client_uri, contacts)
-VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18, $19, $20)"#,
+access_token_lifetime, scopes, default_scopes, challenge, force_mfa, client_uri, contacts,
+allowed_providers, allow_password_login, passkey_only, auto_redirect_provider, access_roles,
+access_groups, access_email_domains, geo_allow_countries, geo_deny_countries)
+VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18, $19, $20,
+$21, $22, $23, $24, $25, $26, $27, $28, $29)"#,
                     params!(
-            b.id,
-            b.name,
-            b.enabled,
-            b.confidential,
-            b.secret,
-            b.secret_kid,
-            b.redirect_uris,
-            b.post_logout_redirect_uris,
-            b.allowed_origins,
-            b.flows_enabled,
-            b.access_token_alg,
-            b.id_token_alg,
-            b.auth_code_lifetime,
-            b.access_token_lifetime,
-            b.scopes,
-            b.default_scopes,
-            b.challenge,
-            b.force_mfa,
-            b.client_uri,
-            b.contacts
-        )
+                        b.id,
+                        b.name,
+                        b.enabled,
+                        b.confidential,
+                        b.secret,
+                        b.secret_kid,
+                        b.redirect_uris,
+                        b.post_logout_redirect_uris,
+                        b.allowed_origins,
+                        b.flows_enabled,
+                        b.access_token_alg,
+                        b.id_token_alg,
+                        b.auth_code_lifetime,
+                        b.access_token_lifetime,
+                        b.scopes,
+                        b.default_scopes,
+                        b.challenge,
+                        b.force_mfa,
+                        b.client_uri,
+                        b.contacts,
+                        b.allowed_providers,
+                        b.allow_password_login,
+                        b.passkey_only,
+                        b.auto_redirect_provider,
+                        b.access_roles,
+                        b.access_groups,
+                        b.access_email_domains,
+                        b.geo_allow_countries,
+                        b.geo_deny_countries
+                    ),
                 )
                 .await?;
         }
@@ -269,35 +344,47 @@ VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $
             .await?;
         for b in data_before {
             sqlx::query!(
-              r#"
+                r#"
 INSERT INTO clients
 (id, name, enabled, confidential, secret, secret_kid, redirect_uris, post_logout_redirect_uris,
 allowed_origins, flows_enabled, access_token_alg, id_token_alg, auth_code_lifetime,
-access_token_lifetime, scopes, default_scopes, challenge, force_mfa, client_uri, contacts)
-VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18, $19, $20)"#,
-            b.id,
-            b.name,
-            b.enabled,
-            b.confidential,
-            b.secret,
-            b.secret_kid,
-            b.redirect_uris,
-            b.post_logout_redirect_uris,
-            b.allowed_origins,
-            b.flows_enabled,
-            b.access_token_alg,
-            b.id_token_alg,
-            b.auth_code_lifetime,
-            b.access_token_lifetime,
-            b.scopes,
-            b.default_scopes,
-            b.challenge,
-            b.force_mfa,
-            b.client_uri,
-            b.contacts
+access_token_lifetime, scopes, default_scopes, challenge, force_mfa, client_uri, contacts,
+allowed_providers, allow_password_login, passkey_only, auto_redirect_provider, access_roles,
+access_groups, access_email_domains, geo_allow_countries, geo_deny_countries)
+VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18, $19, $20,
+$21, $22, $23, $24, $25, $26, $27, $28, $29)"#,
+                b.id,
+                b.name,
+                b.enabled,
+                b.confidential,
+                b.secret,
+                b.secret_kid,
+                b.redirect_uris,
+                b.post_logout_redirect_uris,
+                b.allowed_origins,
+                b.flows_enabled,
+                b.access_token_alg,
+                b.id_token_alg,
+                b.auth_code_lifetime,
+                b.access_token_lifetime,
+                b.scopes,
+                b.default_scopes,
+                b.challenge,
+                b.force_mfa,
+                b.client_uri,
+                b.contacts,
+                b.allowed_providers,
+                b.allow_password_login,
+                b.passkey_only,
+                b.auto_redirect_provider,
+                b.access_roles,
+                b.access_groups,
+                b.access_email_domains,
+                b.geo_allow_countries,
+                b.geo_deny_countries
             )
-                .execute(DB::conn())
-                .await?;
+            .execute(DB::conn())
+            .await?;
         }
     }
     Ok(())
@@ -348,6 +435,53 @@ VALUES ($1, $2, $3, $4, $5)"#,
     Ok(())
 }
 
+pub async fn clients_saml(data_before: Vec<ClientSaml>) -> Result<(), ErrorResponse> {
+    if is_hiqlite() {
+        DB::client()
+            .execute("DELETE FROM clients_saml", params!())
+            .await?;
+        for b in data_before {
+            DB::client()
+                .execute(
+                    r#"
+INSERT INTO clients_saml (id, entity_id, acs_url, slo_url, name_id_format, sp_cert, attr_mapping)
+VALUES ($1, $2, $3, $4, $5, $6, $7)"#,
+                    params!(
+                        b.id,
+                        b.entity_id,
+                        b.acs_url,
+                        b.slo_url,
+                        b.name_id_format,
+                        b.sp_cert,
+                        b.attr_mapping
+                    ),
+                )
+                .await?;
+        }
+    } else {
+        sqlx::query("DELETE FROM clients_saml")
+            .execute(DB::conn())
+            .await?;
+        for b in data_before {
+            sqlx::query!(
+                r#"
+INSERT INTO clients_saml (id, entity_id, acs_url, slo_url, name_id_format, sp_cert, attr_mapping)
+VALUES ($1, $2, $3, $4, $5, $6, $7)"#,
+                b.id,
+                b.entity_id,
+                b.acs_url,
+                b.slo_url,
+                b.name_id_format,
+                b.sp_cert,
+                b.attr_mapping
+            )
+            .execute(DB::conn())
+            .await?;
+        }
+    }
+    Ok(())
+}
+
 pub async fn colors(data_before: Vec<ColorEntity>) -> Result<(), ErrorResponse> {
     if is_hiqlite() {
         DB::client()
@@ -536,6 +670,77 @@ pub async fn groups(data_before: Vec<Group>) -> Result<(), ErrorResponse> {
     Ok(())
 }
 
+pub async fn ip_allowlist(data_before: Vec<IpAllowlistEntry>) -> Result<(), ErrorResponse> {
+    if is_hiqlite() {
+        DB::client()
+            .execute("DELETE FROM ip_allowlist", params!())
+            .await?;
+        for b in data_before {
+            DB::client()
+                .execute(
+                    r#"
+INSERT INTO ip_allowlist (cidr, description, created_at)
+VALUES ($1, $2, $3)"#,
+                    params!(b.cidr, b.description, b.created_at),
+                )
+                .await?;
+        }
+    } else {
+        sqlx::query("DELETE FROM ip_allowlist")
+            .execute(DB::conn())
+            .await?;
+        for b in data_before {
+            sqlx::query!(
+                r#"
+INSERT INTO ip_allowlist (cidr, description, created_at)
+VALUES ($1, $2, $3)"#,
+                b.cidr,
+                b.description,
+                b.created_at
+            )
+            .execute(DB::conn())
+            .await?;
+        }
+    }
+    Ok(())
+}
+
+pub async fn ip_blacklist(data_before: Vec<IpBlacklistEntry>) -> Result<(), ErrorResponse> {
+    if is_hiqlite() {
+        DB::client()
+            .execute("DELETE FROM ip_blacklist", params!())
+            .await?;
+        for b in data_before {
+            DB::client()
+                .execute(
+                    r#"
+INSERT INTO ip_blacklist (cidr, exp, reason, created_at)
+VALUES ($1, $2, $3, $4)"#,
+                    params!(b.cidr, b.exp, b.reason, b.created_at),
+                )
+                .await?;
+        }
+    } else {
+        sqlx::query("DELETE FROM ip_blacklist")
+            .execute(DB::conn())
+            .await?;
+        for b in data_before {
+            sqlx::query!(
+                r#"
+INSERT INTO ip_blacklist (cidr, exp, reason, created_at)
+VALUES ($1, $2, $3, $4)"#,
+                b.cidr,
+                b.exp,
+                b.reason,
+                b.created_at
+            )
+            .execute(DB::conn())
+            .await?;
+        }
+    }
+    Ok(())
+}
+
 pub async fn jwks(data_before: Vec<Jwk>) -> Result<(), ErrorResponse> {
     if is_hiqlite() {
         DB::client().execute("DELETE FROM jwks", params!()).await?;
@@ -624,6 +829,78 @@ VALUES ($1, $2, $3, $4, $5, $6, $7)"#,
     Ok(())
 }
 
+pub async fn org_members(data_before: Vec<OrgMember>) -> Result<(), ErrorResponse> {
+    if is_hiqlite() {
+        DB::client()
+            .execute("DELETE FROM org_members", params!())
+            .await?;
+        for b in data_before {
+            DB::client()
+                .execute(
+                    r#"
+INSERT INTO org_members (org_id, user_id, roles, is_org_admin)
+VALUES ($1, $2, $3, $4)"#,
+                    params!(b.org_id, b.user_id, b.roles, b.is_org_admin),
+                )
+                .await?;
+        }
+    } else {
+        sqlx::query("DELETE FROM org_members")
+            .execute(DB::conn())
+            .await?;
+        for b in data_before {
+            sqlx::query!(
+                r#"
+INSERT INTO org_members (org_id, user_id, roles, is_org_admin)
+VALUES ($1, $2, $3, $4)"#,
+                b.org_id,
+                b.user_id,
+                b.roles,
+                b.is_org_admin
+            )
+            .execute(DB::conn())
+            .await?;
+        }
+    }
+    Ok(())
+}
+
+pub async fn organizations(data_before: Vec<Organization>) -> Result<(), ErrorResponse> {
+    if is_hiqlite() {
+        DB::client()
+            .execute("DELETE FROM organizations", params!())
+            .await?;
+        for b in data_before {
+            DB::client()
+                .execute(
+                    r#"
+INSERT INTO organizations (id, name, description, roles)
+VALUES ($1, $2, $3, $4)"#,
+                    params!(b.id, b.name, b.description, b.roles),
+                )
+                .await?;
+        }
+    } else {
+        sqlx::query("DELETE FROM organizations")
+            .execute(DB::conn())
+            .await?;
+        for b in data_before {
+            sqlx::query!(
+                r#"
+INSERT INTO organizations (id, name, description, roles)
+VALUES ($1, $2, $3, $4)"#,
+                b.id,
+                b.name,
+                b.description,
+                b.roles
+            )
+            .execute(DB::conn())
+            .await?;
+        }
+    }
+    Ok(())
+}
+
 pub async fn passkeys(data_before: Vec<PasskeyEntity>) -> Result<(), ErrorResponse> {
     if is_hiqlite() {
         DB::client()
@@ -675,6 +952,45 @@ VALUES ($1, $2, $3, $4, $5, $6, $7, $8)"#,
     Ok(())
 }
 
+pub async fn password_policies(
+    data_before: Vec<ScopedPasswordPolicyEntity>,
+) -> Result<(), ErrorResponse> {
+    if is_hiqlite() {
+        DB::client()
+            .execute("DELETE FROM password_policies", params!())
+            .await?;
+        for b in data_before {
+            DB::client()
+                .execute(
+                    r#"
+INSERT INTO password_policies (id, name, policy, groups, clients)
+VALUES ($1, $2, $3, $4, $5)"#,
+                    params!(b.id, b.name, b.policy, b.groups, b.clients),
+                )
+                .await?;
+        }
+    } else {
+        sqlx::query("DELETE FROM password_policies")
+            .execute(DB::conn())
+            .await?;
+        for b in data_before {
+            sqlx::query!(
+                r#"
+INSERT INTO password_policies (id, name, policy, groups, clients)
+VALUES ($1, $2, $3, $4, $5)"#,
+                b.id,
+                b.name,
+                b.policy,
+                b.groups,
+                b.clients
+            )
+            .execute(DB::conn())
+            .await?;
+        }
+    }
+    Ok(())
+}
+
 pub async fn password_policy(bytes: Vec<u8>) -> Result<(), ErrorResponse> {
     if is_hiqlite() {
         DB::client()
@@ -827,6 +1143,41 @@ pub async fn roles(data_before: Vec<Role>) -> Result<(), ErrorResponse> {
     Ok(())
 }
 
+pub async fn saml_certs(data_before: Vec<SamlCert>) -> Result<(), ErrorResponse> {
+    if is_hiqlite() {
+        DB::client()
+            .execute("DELETE FROM saml_certs", params!())
+            .await?;
+        for b in data_before {
+            DB::client()
+                .execute(
+                    r#"
+INSERT INTO saml_certs (kid, created_at, cert_der)
+VALUES ($1, $2, $3)"#,
+                    params!(b.kid, b.created_at, b.cert_der),
+                )
+                .await?;
+        }
+    } else {
+        sqlx::query("DELETE FROM saml_certs")
+            .execute(DB::conn())
+            .await?;
+        for b in data_before {
+            sqlx::query!(
+                r#"
+INSERT INTO saml_certs (kid, created_at, cert_der)
+VALUES ($1, $2, $3)"#,
+                b.kid,
+                b.created_at,
+                b.cert_der
+            )
+            .execute(DB::conn())
+            .await?;
+        }
+    }
+    Ok(())
+}
+
 pub async fn scopes(data_before: Vec<Scope>) -> Result<(), ErrorResponse> {
     if is_hiqlite() {
         DB::client()
@@ -873,8 +1224,8 @@ pub async fn sessions(data_before: Vec<Session>) -> Result<(), ErrorResponse> {
                 .execute(
                     r#"
 INSERT INTO
-sessions (id, csrf_token, user_id, roles, groups, is_mfa, state, exp, last_seen)
-VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)"#,
+sessions (id, csrf_token, user_id, roles, groups, is_mfa, state, exp, last_seen, impersonated_by)
+VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)"#,
                     params!(
                         b.id,
                         b.csrf_token,
@@ -884,7 +1235,8 @@ VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)"#,
                         b.is_mfa,
                         b.state,
                         b.exp,
-                        b.last_seen
+                        b.last_seen,
+                        b.impersonated_by
                     ),
                 )
                 .await?;
@@ -897,8 +1249,8 @@ VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)"#,
             sqlx::query!(
                 r#"
 INSERT INTO
-sessions (id, csrf_token, user_id, roles, groups, is_mfa, state, exp, last_seen)
-VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)"#,
+sessions (id, csrf_token, user_id, roles, groups, is_mfa, state, exp, last_seen, impersonated_by)
+VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)"#,
                 b.id,
                 b.csrf_token,
                 b.user_id,
@@ -907,7 +1259,59 @@ VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)"#,
                 b.is_mfa,
                 b.state,
                 b.exp,
-                b.last_seen
+                b.last_seen,
+                b.impersonated_by
+            )
+            .execute(DB::conn())
+            .await?;
+        }
+    }
+    Ok(())
+}
+
+pub async fn trusted_devices(data_before: Vec<TrustedDevice>) -> Result<(), ErrorResponse> {
+    if is_hiqlite() {
+        DB::client()
+            .execute("DELETE FROM trusted_devices", params!())
+            .await?;
+        for b in data_before {
+            DB::client()
+                .execute(
+                    r#"
+INSERT INTO trusted_devices (id, user_id, name, created, last_used, exp, ip, country, asn)
+VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)"#,
+                    params!(
+                        b.id,
+                        b.user_id,
+                        b.name,
+                        b.created,
+                        b.last_used,
+                        b.exp,
+                        b.ip,
+                        b.country,
+                        b.asn
+                    ),
+                )
+                .await?;
+        }
+    } else {
+        sqlx::query("DELETE FROM trusted_devices")
+            .execute(DB::conn())
+            .await?;
+        for b in data_before {
+            sqlx::query!(
+                r#"
+INSERT INTO trusted_devices (id, user_id, name, created, last_used, exp, ip, country, asn)
+VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)"#,
+                b.id,
+                b.user_id,
+                b.name,
+                b.created,
+                b.last_used,
+                b.exp,
+                b.ip,
+                b.country,
+                b.asn
             )
             .execute(DB::conn())
             .await?;
@@ -924,8 +1328,8 @@ pub async fn user_attr_config(data_before: Vec<UserAttrConfigEntity>) -> Result<
         for b in data_before {
             DB::client()
                 .execute(
-                    "INSERT INTO user_attr_config (name, desc) VALUES ($1, $2)",
-                    params!(b.name, b.desc),
+                    "INSERT INTO user_attr_config (name, desc, registration) VALUES ($1, $2, $3)",
+                    params!(b.name, b.desc, b.registration),
                 )
                 .await?;
         }
@@ -935,9 +1339,10 @@ pub async fn user_attr_config(data_before: Vec<UserAttrConfigEntity>) -> Result<
             .await?;
         for b in data_before {
             sqlx::query!(
-                "INSERT INTO user_attr_config (name, \"desc\") VALUES ($1, $2)",
+                r#"INSERT INTO user_attr_config (name, "desc", registration) VALUES ($1, $2, $3)"#,
                 b.name,
-                b.desc
+                b.desc,
+                b.registration
             )
             .execute(DB::conn())
             .await?;
@@ -977,6 +1382,159 @@ pub async fn user_attr_values(data_before: Vec<UserAttrValueEntity>) -> Result<(
     Ok(())
 }
 
+pub async fn user_federations(data_before: Vec<UserFederation>) -> Result<(), ErrorResponse> {
+    if is_hiqlite() {
+        DB::client()
+            .execute("DELETE FROM user_federations", params!())
+            .await?;
+        for b in data_before {
+            DB::client()
+                .execute(
+                    r#"
+INSERT INTO user_federations (user_id, provider_id, federation_uid, created_at, last_login)
+VALUES ($1, $2, $3, $4, $5)"#,
+                    params!(
+                        b.user_id,
+                        b.provider_id,
+                        b.federation_uid,
+                        b.created_at,
+                        b.last_login
+                    ),
+                )
+                .await?;
+        }
+    } else {
+        sqlx::query("DELETE FROM user_federations")
+            .execute(DB::conn())
+            .await?;
+        for b in data_before {
+            sqlx::query!(
+                r#"
+INSERT INTO user_federations (user_id, provider_id, federation_uid, created_at, last_login)
+VALUES ($1, $2, $3, $4, $5)"#,
+                b.user_id,
+                b.provider_id,
+                b.federation_uid,
+                b.created_at,
+                b.last_login
+            )
+            .execute(DB::conn())
+            .await?;
+        }
+    }
+    Ok(())
+}
+
+pub async fn user_invitations(data_before: Vec<UserInvitation>) -> Result<(), ErrorResponse> {
+    if is_hiqlite() {
+        DB::client()
+            .execute("DELETE FROM user_invitations", params!())
+            .await?;
+        for b in data_before {
+            DB::client()
+                .execute(
+                    r#"
+INSERT INTO user_invitations (id, user_id, email, roles, groups, attributes, org_id, org_roles, redirect_uri, created_by, created_at, exp, accepted_at)
+VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13)"#,
+                    params!(
+                        b.id,
+                        b.user_id,
+                        b.email,
+                        b.roles,
+                        b.groups,
+                        b.attributes,
+                        b.org_id,
+                        b.org_roles,
+                        b.redirect_uri,
+                        b.created_by,
+                        b.created_at,
+                        b.exp,
+                        b.accepted_at
+                    ),
+                )
+                .await?;
+        }
+    } else {
+        sqlx::query("DELETE FROM user_invitations")
+            .execute(DB::conn())
+            .await?;
+        for b in data_before {
+            sqlx::query!(
+                r#"
+INSERT INTO user_invitations (id, user_id, email, roles, groups, attributes, org_id, org_roles, redirect_uri, created_by, created_at, exp, accepted_at)
+VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13)"#,
+                b.id,
+                b.user_id,
+                b.email,
+                b.roles,
+                b.groups,
+                b.attributes,
+                b.org_id,
+                b.org_roles,
+                b.redirect_uri,
+                b.created_by,
+                b.created_at,
+                b.exp,
+                b.accepted_at
+            )
+            .execute(DB::conn())
+            .await?;
+        }
+    }
+    Ok(())
+}
+
+pub async fn user_registrations(data_before: Vec<UserRegistration>) -> Result<(), ErrorResponse> {
+    if is_hiqlite() {
+        DB::client()
+            .execute("DELETE FROM user_registrations", params!())
+            .await?;
+        for b in data_before {
+            DB::client()
+                .execute(
+                    r#"
+INSERT INTO user_registrations (id, email, given_name, family_name, language, redirect_uri, attributes, ip, created_at)
+VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)"#,
+                    params!(
+                        b.id,
+                        b.email,
+                        b.given_name,
+                        b.family_name,
+                        b.language,
+                        b.redirect_uri,
+                        b.attributes,
+                        b.ip,
+                        b.created_at
+                    ),
+                )
+                .await?;
+        }
+    } else {
+        sqlx::query("DELETE FROM user_registrations")
+            .execute(DB::conn())
+            .await?;
+        for b in data_before {
+            sqlx::query!(
+                r#"
+INSERT INTO user_registrations (id, email, given_name, family_name, language, redirect_uri, attributes, ip, created_at)
+VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)"#,
+                b.id,
+                b.email,
+                b.given_name,
+                b.family_name,
+                b.language,
+                b.redirect_uri,
+                b.attributes,
+                b.ip,
+                b.created_at
+            )
+            .execute(DB::conn())
+            .await?;
+        }
+    }
+    Ok(())
+}
+
 pub async fn users(data_before: Vec<User>) -> Result<(), ErrorResponse> {
     if is_hiqlite() {
         DB::client().execute("DELETE FROM users", params!()).await?;
@@ -987,8 +1545,8 @@ pub async fn users(data_before: Vec<User>) -> Result<(), ErrorResponse> {
 INSERT INTO users
 (id, email, given_name, family_name, password, roles, groups, enabled, email_verified,
 password_expires, created_at, last_login, last_failed_login, failed_login_attempts, language,
-webauthn_user_id, user_expires, auth_provider_id, federation_uid)
-VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18, $19)"#,
+webauthn_user_id, user_expires, auth_provider_id, federation_uid, locked_until)
+VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18, $19, $20)"#,
                     params!(
                         b.id,
                         b.email,
@@ -1008,7 +1566,8 @@ VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $
                         b.webauthn_user_id,
                         b.user_expires,
                         b.auth_provider_id,
-                        b.federation_uid
+                        b.federation_uid,
+                        b.locked_until
                     ),
                 )
                 .await?;
@@ -1021,8 +1580,8 @@ VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $
 INSERT INTO users
 (id, email, given_name, family_name, password, roles, groups, enabled, email_verified,
 password_expires, created_at, last_login, last_failed_login, failed_login_attempts, language,
-webauthn_user_id, user_expires, auth_provider_id, federation_uid)
-VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18, $19)"#,
+webauthn_user_id, user_expires, auth_provider_id, federation_uid, locked_until)
+VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18, $19, $20)"#,
                 b.id,
                 b.email,
                 b.given_name,
@@ -1041,7 +1600,8 @@ VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $
                 b.webauthn_user_id,
                 b.user_expires,
                 b.auth_provider_id,
-                b.federation_uid
+                b.federation_uid,
+                b.locked_until
             )
             .execute(DB::conn())
             .await?;
@@ -0,0 +1,539 @@
+//! XML signature validation for messages received from upstream SAML IdPs.
+//!
+//! In contrast to the XML Rauthy builds itself, incoming messages are not in canonical form.
+//! This module keeps a complete node tree including text nodes and namespace declarations,
+//! and implements Exclusive XML Canonicalization (without comments) on top of it, which is
+//! the only canonicalization method used with SAML in practice.
+
+use crate::saml::{
+    escape_attr, escape_text, local_name, ssl_err, xml_err, XmlElement, ALG_ENVELOPED,
+    ALG_EXC_C14N, ALG_RSA_SHA256, ALG_RSA_SHA512, ALG_SHA256, MAX_XML_DEPTH, NS_DS,
+};
+use openssl::hash::MessageDigest;
+use openssl::sign::Verifier;
+use openssl::x509::X509;
+use quick_xml::escape::unescape;
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::Reader;
+use rauthy_common::utils::{base64_decode, base64_encode};
+use rauthy_error::{ErrorResponse, ErrorResponseType};
+use std::ptr;
+
+const ALG_SHA512: &str = "http://www.w3.org/2001/04/xmlenc#sha512";
+const NS_XML: &str = "http://www.w3.org/XML/1998/namespace";
+
+/// `(prefix, namespace URI)` - the default namespace has an empty prefix
+pub type NsScope = Vec<(String, String)>;
+
+#[derive(Debug, Clone)]
+pub enum XmlNode {
+    Element(DsigElement),
+    Text(String),
+}
+
+/// An XML element, which keeps everything needed for the canonicalization.
+#[derive(Debug, Default, Clone)]
+pub struct DsigElement {
+    pub name: String,
+    /// All attributes including namespace declarations in document order
+    pub attrs: Vec<(String, String)>,
+    pub nodes: Vec<XmlNode>,
+}
+
+impl DsigElement {
+    pub fn parse(xml: &str) -> Result<Self, ErrorResponse> {
+        let mut reader = Reader::from_str(xml);
+
+        let mut stack: Vec<DsigElement> = Vec::with_capacity(8);
+        loop {
+            match reader.read_event().map_err(xml_err)? {
+                Event::Start(start) => {
+                    if stack.len() >= MAX_XML_DEPTH {
+                        return Err(ErrorResponse::new(
+                            ErrorResponseType::BadRequest,
+                            "XML document is nested too deeply",
+                        ));
+                    }
+                    stack.push(Self::from_start(&start)?);
+                }
+                Event::Empty(start) => {
+                    let elem = Self::from_start(&start)?;
+                    match stack.last_mut() {
+                        Some(parent) => parent.nodes.push(XmlNode::Element(elem)),
+                        None => return Ok(elem),
+                    }
+                }
+                Event::End(_) => {
+                    let elem = stack.pop().ok_or_else(|| xml_err("unexpected end tag"))?;
+                    match stack.last_mut() {
+                        Some(parent) => parent.nodes.push(XmlNode::Element(elem)),
+                        None => return Ok(elem),
+                    }
+                }
+                Event::Text(text) => {
+                    if let Some(elem) = stack.last_mut() {
+                        // line endings are normalized by any XML parser before canonicalization
+                        let raw = String::from_utf8_lossy(&text)
+                            .replace("\r\n", "\n")
+                            .replace('\r', "\n");
+                        let value = unescape(&raw).map_err(xml_err)?;
+                        elem.push_text(&value);
+                    }
+                }
+                Event::CData(data) => {
+                    if let Some(elem) = stack.last_mut() {
+                        elem.push_text(&String::from_utf8_lossy(&data.into_inner()));
+                    }
+                }
+                Event::DocType(_) => {
+                    return Err(ErrorResponse::new(
+                        ErrorResponseType::BadRequest,
+                        "DOCTYPE is not allowed in SAML messages",
+                    ));
+                }
+                Event::Eof => return Err(xml_err("unexpected end of document")),
+                // comments are removed by the canonicalization anyway
+                Event::Decl(_) | Event::Comment(_) | Event::PI(_) => {}
+            }
+        }
+    }
+
+    fn from_start(start: &BytesStart) -> Result<Self, ErrorResponse> {
+        let name = String::from_utf8_lossy(start.name().as_ref()).to_string();
+        let mut attrs = Vec::with_capacity(4);
+        for attr in start.attributes() {
+            let attr = attr.map_err(xml_err)?;
+            let key = String::from_utf8_lossy(attr.key.as_ref()).to_string();
+            // attribute value normalization for whitespace characters
+            let raw = String::from_utf8_lossy(&attr.value).replace(['\t', '\n', '\r'], " ");
+            let value = unescape(&raw).map_err(xml_err)?.to_string();
+            attrs.push((key, value));
+        }
+
+        Ok(Self {
+            name,
+            attrs,
+            nodes: Vec::default(),
+        })
+    }
+
+    fn push_text(&mut self, value: &str) {
+        if let Some(XmlNode::Text(text)) = self.nodes.last_mut() {
+            text.push_str(value);
+        } else {
+            self.nodes.push(XmlNode::Text(value.to_string()));
+        }
+    }
+
+    pub fn local_name(&self) -> &str {
+        local_name(&self.name)
+    }
+
+    /// Returns the value of the attribute with the given local name
+    pub fn attr(&self, name: &str) -> Option<&str> {
+        self.attrs
+            .iter()
+            .find(|(k, _)| local_name(k) == name && !is_ns_decl(k))
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Returns all direct child elements with the given local name
+    pub fn children<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a DsigElement> {
+        self.nodes.iter().filter_map(move |n| match n {
+            XmlNode::Element(e) if e.local_name() == name => Some(e),
+            _ => None,
+        })
+    }
+
+    /// Returns the first direct child element with the given local name
+    pub fn child(&self, name: &str) -> Option<&DsigElement> {
+        self.children(name).next()
+    }
+
+    /// The concatenated, trimmed direct text content
+    pub fn text(&self) -> String {
+        let mut res = String::new();
+        for node in &self.nodes {
+            if let XmlNode::Text(t) = node {
+                res.push_str(t);
+            }
+        }
+        res.trim().to_string()
+    }
+
+    /// Converts this subtree into the simplified [XmlElement] for easier value extraction.
+    pub fn to_element(&self) -> XmlElement {
+        let mut elem = XmlElement {
+            name: self.name.clone(),
+            attrs: self.attrs.clone(),
+            children: Vec::default(),
+            text: String::default(),
+        };
+        for node in &self.nodes {
+            match node {
+                XmlNode::Element(e) => elem.children.push(e.to_element()),
+                XmlNode::Text(t) => elem.text.push_str(t),
+            }
+        }
+        elem
+    }
+
+    /// The namespace scope for the children of this element
+    pub fn scope_for_children(&self, parent_scope: &[(String, String)]) -> NsScope {
+        let mut scope = parent_scope.to_vec();
+        for (k, v) in &self.attrs {
+            if let Some(prefix) = ns_decl_prefix(k) {
+                scope.retain(|(p, _)| p != prefix);
+                scope.push((prefix.to_string(), v.clone()));
+            }
+        }
+        scope
+    }
+
+    /// Exclusive canonical form of this element.
+    ///
+    /// `scope` contains the namespaces declared by the ancestors of this element. The optional
+    /// `exclude` element will be left out, which is how the enveloped signature transform works.
+    pub fn exc_c14n(
+        &self,
+        scope: &[(String, String)],
+        exclude: Option<&DsigElement>,
+        inclusive_prefixes: &[String],
+    ) -> Result<String, ErrorResponse> {
+        let mut c14n = ExcC14n {
+            exclude,
+            inclusive_prefixes,
+            out: String::with_capacity(2048),
+        };
+        c14n.write_elem(self, scope, &[])?;
+        Ok(c14n.out)
+    }
+
+    /// Validates the enveloped signature of this element, which is checked against its own `ID`.
+    ///
+    /// Returns `Ok(false)` if the element is not signed at all. Since the digest is always
+    /// computed over this exact element, signature wrapping attacks are not possible, as long
+    /// as the caller only extracts values from this element afterward.
+    pub fn verify_enveloped(
+        &self,
+        scope: &[(String, String)],
+        cert: &X509,
+    ) -> Result<bool, ErrorResponse> {
+        let mut signatures = self.children("Signature");
+        let Some(signature) = signatures.next() else {
+            return Ok(false);
+        };
+        if signatures.next().is_some() {
+            return Err(sig_err("Multiple signatures on a single element"));
+        }
+
+        let sig_scope = self.scope_for_children(scope);
+        if ns_uri(&sig_scope, &signature.name) != Some(NS_DS) {
+            return Err(sig_err("Invalid Signature namespace"));
+        }
+
+        let signed_info = signature
+            .child("SignedInfo")
+            .ok_or_else(|| sig_err("Missing SignedInfo"))?;
+
+        let c14n_method = signed_info
+            .child("CanonicalizationMethod")
+            .ok_or_else(|| sig_err("Missing CanonicalizationMethod"))?;
+        if c14n_method.attr("Algorithm") != Some(ALG_EXC_C14N) {
+            return Err(sig_err("Only exclusive canonicalization is supported"));
+        }
+
+        let sig_digest = match signed_info
+            .child("SignatureMethod")
+            .and_then(|m| m.attr("Algorithm"))
+        {
+            Some(ALG_RSA_SHA256) => MessageDigest::sha256(),
+            Some(ALG_RSA_SHA512) => MessageDigest::sha512(),
+            alg => {
+                return Err(sig_err(&format!(
+                    "Unsupported SignatureMethod: {}",
+                    alg.unwrap_or_default()
+                )))
+            }
+        };
+
+        let mut references = signed_info.children("Reference");
+        let reference = references
+            .next()
+            .ok_or_else(|| sig_err("Missing Reference"))?;
+        if references.next().is_some() {
+            return Err(sig_err("Only a single Reference is supported"));
+        }
+
+        let id = self
+            .attr("ID")
+            .ok_or_else(|| sig_err("Signed element without an ID"))?;
+        if reference.attr("URI") != Some(format!("#{}", id).as_str()) {
+            return Err(sig_err("Reference URI does not match the signed element"));
+        }
+
+        let mut ref_prefixes = Vec::new();
+        if let Some(transforms) = reference.child("Transforms") {
+            for transform in transforms.children("Transform") {
+                match transform.attr("Algorithm") {
+                    Some(ALG_ENVELOPED) => {}
+                    Some(ALG_EXC_C14N) => ref_prefixes = inclusive_prefixes(transform),
+                    alg => {
+                        return Err(sig_err(&format!(
+                            "Unsupported Transform: {}",
+                            alg.unwrap_or_default()
+                        )))
+                    }
+                }
+            }
+        }
+
+        let digest = match reference
+            .child("DigestMethod")
+            .and_then(|m| m.attr("Algorithm"))
+        {
+            Some(ALG_SHA256) => openssl::sha::sha256(
+                self.exc_c14n(scope, Some(signature), &ref_prefixes)?
+                    .as_bytes(),
+            )
+            .to_vec(),
+            Some(ALG_SHA512) => openssl::sha::sha512(
+                self.exc_c14n(scope, Some(signature), &ref_prefixes)?
+                    .as_bytes(),
+            )
+            .to_vec(),
+            alg => {
+                return Err(sig_err(&format!(
+                    "Unsupported DigestMethod: {}",
+                    alg.unwrap_or_default()
+                )))
+            }
+        };
+        let digest_value = reference
+            .child("DigestValue")
+            .map(|d| strip_whitespace(&d.text()))
+            .ok_or_else(|| sig_err("Missing DigestValue"))?;
+        if base64_encode(&digest) != digest_value {
+            return Err(sig_err("Digest does not match"));
+        }
+
+        let signed_info_scope = signature.scope_for_children(&sig_scope);
+        let signed_info_c14n =
+            signed_info.exc_c14n(&signed_info_scope, None, &inclusive_prefixes(c14n_method))?;
+        let signature_value = signature
+            .child("SignatureValue")
+            .map(|v| strip_whitespace(&v.text()))
+            .ok_or_else(|| sig_err("Missing SignatureValue"))?;
+        let signature_value = base64_decode(&signature_value)?;
+
+        let pub_key = cert.public_key().map_err(ssl_err)?;
+        let mut verifier = Verifier::new(sig_digest, &pub_key).map_err(ssl_err)?;
+        verifier
+            .update(signed_info_c14n.as_bytes())
+            .map_err(ssl_err)?;
+        if verifier.verify(&signature_value).unwrap_or(false) {
+            Ok(true)
+        } else {
+            Err(sig_err("Invalid signature"))
+        }
+    }
+}
+
+struct ExcC14n<'a> {
+    exclude: Option<&'a DsigElement>,
+    inclusive_prefixes: &'a [String],
+    out: String,
+}
+
+impl ExcC14n<'_> {
+    /// `scope` are the namespaces in scope of the parent, `rendered` the ones which have been
+    /// rendered already by output ancestors.
+    fn write_elem(
+        &mut self,
+        elem: &DsigElement,
+        scope: &[(String, String)],
+        rendered: &[(String, String)],
+    ) -> Result<(), ErrorResponse> {
+        if self.exclude.is_some_and(|ex| ptr::eq(ex, elem)) {
+            return Ok(());
+        }
+
+        let scope = elem.scope_for_children(scope);
+
+        // namespaces, which are visibly utilized by the element or its attributes
+        let mut prefixes = vec![prefix(&elem.name).to_string()];
+        for (k, _) in &elem.attrs {
+            if !is_ns_decl(k) && k.contains(':') {
+                let p = prefix(k);
+                if p != "xml" {
+                    prefixes.push(p.to_string());
+                }
+            }
+        }
+        for p in self.inclusive_prefixes {
+            let p = if p == "#default" { "" } else { p.as_str() };
+            if lookup(&scope, p).is_some() {
+                prefixes.push(p.to_string());
+            }
+        }
+        prefixes.sort();
+        prefixes.dedup();
+
+        let mut new_rendered = rendered.to_vec();
+        let mut ns_decls = Vec::with_capacity(prefixes.len());
+        for p in prefixes {
+            let uri = lookup(&scope, &p);
+            if uri.is_none() && !p.is_empty() {
+                return Err(xml_err(format!("unbound namespace prefix '{}'", p)));
+            }
+            let uri = uri.unwrap_or_default();
+            let rendered_uri = lookup(rendered, &p).unwrap_or_default();
+            if uri == rendered_uri && (p.is_empty() || lookup(rendered, &p).is_some()) {
+                continue;
+            }
+            new_rendered.retain(|(rp, _)| rp != &p);
+            new_rendered.push((p.clone(), uri.to_string()));
+            ns_decls.push((p, uri.to_string()));
+        }
+
+        let mut attrs = elem
+            .attrs
+            .iter()
+            .filter(|(k, _)| !is_ns_decl(k))
+            .map(|(k, v)| {
+                let uri = if k.contains(':') {
+                    match prefix(k) {
+                        "xml" => NS_XML,
+                        p => lookup(&scope, p).unwrap_or_default(),
+                    }
+                } else {
+                    ""
+                };
+                (uri, local_name(k), k.as_str(), v.as_str())
+            })
+            .collect::<Vec<_>>();
+        attrs.sort_by(|a, b| (a.0, a.1).cmp(&(b.0, b.1)));
+
+        self.out.push('<');
+        self.out.push_str(&elem.name);
+        for (p, uri) in ns_decls {
+            if p.is_empty() {
+                self.out.push_str(" xmlns=\"");
+            } else {
+                self.out.push_str(" xmlns:");
+                self.out.push_str(&p);
+                self.out.push_str("=\"");
+            }
+            self.out.push_str(&escape_attr(&uri));
+            self.out.push('"');
+        }
+        for (_, _, k, v) in attrs {
+            self.out.push(' ');
+            self.out.push_str(k);
+            self.out.push_str("=\"");
+            self.out.push_str(&escape_attr(v));
+            self.out.push('"');
+        }
+        self.out.push('>');
+
+        for node in &elem.nodes {
+            match node {
+                XmlNode::Element(child) => self.write_elem(child, &scope, &new_rendered)?,
+                XmlNode::Text(text) => self.out.push_str(&escape_text(text)),
+            }
+        }
+
+        self.out.push_str("</");
+        self.out.push_str(&elem.name);
+        self.out.push('>');
+
+        Ok(())
+    }
+}
+
+#[inline]
+fn is_ns_decl(key: &str) -> bool {
+    key == "xmlns" || key.starts_with("xmlns:")
+}
+
+#[inline]
+fn ns_decl_prefix(key: &str) -> Option<&str> {
+    if key == "xmlns" {
+        Some("")
+    } else {
+        key.strip_prefix("xmlns:")
+    }
+}
+
+#[inline]
+fn prefix(name: &str) -> &str {
+    name.split_once(':').map(|(p, _)| p).unwrap_or_default()
+}
+
+fn lookup<'a>(scope: &'a [(String, String)], prefix: &str) -> Option<&'a str> {
+    scope
+        .iter()
+        .rev()
+        .find(|(p, _)| p == prefix)
+        .map(|(_, uri)| uri.as_str())
+}
+
+fn ns_uri<'a>(scope: &'a [(String, String)], name: &str) -> Option<&'a str> {
+    lookup(scope, prefix(name))
+}
+
+fn inclusive_prefixes(elem: &DsigElement) -> Vec<String> {
+    elem.child("InclusiveNamespaces")
+        .and_then(|i| i.attr("PrefixList"))
+        .map(|list| list.split_whitespace().map(String::from).collect())
+        .unwrap_or_default()
+}
+
+fn strip_whitespace(value: &str) -> String {
+    value.chars().filter(|c| !c.is_ascii_whitespace()).collect()
+}
+
+fn sig_err(msg: &str) -> ErrorResponse {
+    ErrorResponse::new(
+        ErrorResponseType::Forbidden,
+        format!("SAML signature validation failed: {}", msg),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_exc_c14n() {
+        let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
+<samlp:Response xmlns:samlp="urn:oasis:names:tc:SAML:2.0:protocol" xmlns:unused="urn:unused" ID="_r1">
+  <!-- a comment -->
+  <saml:Assertion xmlns:saml="urn:oasis:names:tc:SAML:2.0:assertion" xmlns:xs="http://www.w3.org/2001/XMLSchema" xmlns:xsi="http://www.w3.org/2001/XMLSchema-instance" Version="2.0" ID="_a1">
+    <saml:AttributeValue xsi:type="xs:string" b="x" a="1 &amp; 2">v&gt;1</saml:AttributeValue>
+    <samlp:Empty/>
+  </saml:Assertion>
+</samlp:Response>"#;
+
+        let root = DsigElement::parse(xml).unwrap();
+        let scope = root.scope_for_children(&[]);
+        let assertion = root.child("Assertion").unwrap();
+
+        let c14n = assertion.exc_c14n(&scope, None, &[]).unwrap();
+        assert_eq!(
+            c14n,
+            "<saml:Assertion xmlns:saml=\"urn:oasis:names:tc:SAML:2.0:assertion\" ID=\"_a1\" Version=\"2.0\">\n    \
+<saml:AttributeValue xmlns:xsi=\"http://www.w3.org/2001/XMLSchema-instance\" a=\"1 &amp; 2\" b=\"x\" xsi:type=\"xs:string\">v&gt;1</saml:AttributeValue>\n    \
+<samlp:Empty xmlns:samlp=\"urn:oasis:names:tc:SAML:2.0:protocol\"></samlp:Empty>\n  \
+</saml:Assertion>"
+        );
+
+        // the `xs` prefix is only used inside an attribute value and must be included explicitly
+        let c14n = assertion
+            .exc_c14n(&scope, None, &["xs".to_string()])
+            .unwrap();
+        assert!(c14n.starts_with(
+            "<saml:Assertion xmlns:saml=\"urn:oasis:names:tc:SAML:2.0:assertion\" xmlns:xs=\"http://www.w3.org/2001/XMLSchema\" ID"
+        ));
+    }
+}
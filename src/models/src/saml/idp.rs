@@ -0,0 +1,506 @@
+use crate::saml::{
+    escape_attr, escape_text, instant, new_id, normalize_cert_pem, sign_enveloped, XmlElement,
+    BINDING_POST, BINDING_REDIRECT, CLOCK_SKEW, NS_DS, NS_MD, NS_SAML, NS_SAMLP, STATUS_SUCCESS,
+};
+use openssl::pkey::{PKey, Private};
+use rauthy_api_types::saml::SamlNameIdFormat;
+use rauthy_error::{ErrorResponse, ErrorResponseType};
+use std::fmt::Write;
+
+/// The lifetime of issued assertions in seconds
+const ASSERTION_LIFETIME: i64 = 300;
+
+/// The relevant parts of an incoming `<samlp:AuthnRequest>`
+#[derive(Debug, PartialEq)]
+pub struct AuthnRequest {
+    pub id: String,
+    pub issuer: String,
+    pub acs_url: Option<String>,
+    pub name_id_format: Option<String>,
+    pub force_authn: bool,
+}
+
+impl AuthnRequest {
+    pub fn parse(xml: &str) -> Result<Self, ErrorResponse> {
+        let root = XmlElement::parse(xml)?;
+        if root.local_name() != "AuthnRequest" {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                "Expected a SAML AuthnRequest",
+            ));
+        }
+        check_version(&root)?;
+
+        Ok(Self {
+            id: required_attr(&root, "ID")?,
+            issuer: required_issuer(&root)?,
+            acs_url: root.attr("AssertionConsumerServiceURL").map(String::from),
+            name_id_format: root
+                .child("NameIDPolicy")
+                .and_then(|p| p.attr("Format"))
+                .map(String::from),
+            force_authn: root
+                .attr("ForceAuthn")
+                .map(|v| v == "true" || v == "1")
+                .unwrap_or(false),
+        })
+    }
+}
+
+/// The relevant parts of an incoming `<samlp:LogoutRequest>`
+#[derive(Debug, PartialEq)]
+pub struct LogoutRequest {
+    pub id: String,
+    pub issuer: String,
+    pub session_index: Option<String>,
+}
+
+impl LogoutRequest {
+    pub fn parse(xml: &str) -> Result<Self, ErrorResponse> {
+        let root = XmlElement::parse(xml)?;
+        if root.local_name() != "LogoutRequest" {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                "Expected a SAML LogoutRequest",
+            ));
+        }
+        check_version(&root)?;
+
+        Ok(Self {
+            id: required_attr(&root, "ID")?,
+            issuer: required_issuer(&root)?,
+            session_index: root
+                .child("SessionIndex")
+                .map(|s| s.text_trimmed().to_string()),
+        })
+    }
+}
+
+/// The values Rauthy needs from a Service Providers `<md:EntityDescriptor>`
+#[derive(Debug, PartialEq)]
+pub struct SpMetadata {
+    pub entity_id: String,
+    pub acs_url: String,
+    pub slo_url: Option<String>,
+    pub name_id_format: Option<SamlNameIdFormat>,
+    pub cert_pem: Option<String>,
+    /// `AuthnRequestsSigned` from the `SPSSODescriptor`
+    pub authn_requests_signed: bool,
+}
+
+impl SpMetadata {
+    pub fn parse(xml: &str) -> Result<Self, ErrorResponse> {
+        let root = XmlElement::parse(xml)?;
+        let entity = if root.local_name() == "EntitiesDescriptor" {
+            root.find("EntityDescriptor").ok_or_else(|| {
+                ErrorResponse::new(
+                    ErrorResponseType::BadRequest,
+                    "No EntityDescriptor found in SAML metadata",
+                )
+            })?
+        } else {
+            &root
+        };
+        if entity.local_name() != "EntityDescriptor" {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                "Expected a SAML EntityDescriptor",
+            ));
+        }
+
+        let entity_id = required_attr(entity, "entityID")?;
+        let sp = entity.child("SPSSODescriptor").ok_or_else(|| {
+            ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                "SAML metadata does not contain an SPSSODescriptor",
+            )
+        })?;
+
+        // we only ever send responses with the POST binding
+        let mut acs = sp
+            .children("AssertionConsumerService")
+            .filter(|acs| acs.attr("Binding") == Some(BINDING_POST))
+            .collect::<Vec<_>>();
+        acs.sort_by_key(|acs| {
+            (
+                acs.attr("isDefault") != Some("true"),
+                acs.attr("index")
+                    .and_then(|i| i.parse::<u32>().ok())
+                    .unwrap_or(u32::MAX),
+            )
+        });
+        let acs_url = acs
+            .first()
+            .and_then(|acs| acs.attr("Location"))
+            .ok_or_else(|| {
+                ErrorResponse::new(
+                    ErrorResponseType::BadRequest,
+                    "SAML metadata does not contain an AssertionConsumerService with the \
+                    HTTP-POST binding",
+                )
+            })?
+            .to_string();
+
+        let slo_url = sp
+            .children("SingleLogoutService")
+            .find(|slo| slo.attr("Binding") == Some(BINDING_POST))
+            .and_then(|slo| slo.attr("Location"))
+            .map(String::from);
+
+        let name_id_format = sp
+            .children("NameIDFormat")
+            .find_map(|f| SamlNameIdFormat::from_urn(f.text_trimmed()));
+
+        let cert_pem = sp
+            .children("KeyDescriptor")
+            .filter(|kd| kd.attr("use").map(|u| u == "signing").unwrap_or(true))
+            .find_map(|kd| kd.find("X509Certificate"))
+            .map(|c| normalize_cert_pem(c.text_trimmed()))
+            .transpose()?;
+
+        Ok(Self {
+            entity_id,
+            acs_url,
+            slo_url,
+            name_id_format,
+            cert_pem,
+            authn_requests_signed: sp.attr("AuthnRequestsSigned") == Some("true"),
+        })
+    }
+}
+
+/// A single attribute with all its values, which will end up inside the assertion
+#[derive(Debug)]
+pub struct SamlAttribute {
+    pub name: String,
+    pub values: Vec<String>,
+}
+
+/// Everything needed to build a signed `<samlp:Response>`
+#[derive(Debug)]
+pub struct SamlResponse<'a> {
+    pub idp_entity_id: &'a str,
+    pub sp_entity_id: &'a str,
+    pub acs_url: &'a str,
+    /// `None` for IdP-initiated logins
+    pub in_response_to: Option<&'a str>,
+    pub name_id: &'a str,
+    pub name_id_format: SamlNameIdFormat,
+    pub session_index: &'a str,
+    pub auth_time: i64,
+    pub attributes: Vec<SamlAttribute>,
+}
+
+impl SamlResponse<'_> {
+    /// Builds the `<samlp:Response>` with a signed assertion
+    pub fn build_signed(
+        &self,
+        now: i64,
+        key: &PKey<Private>,
+        cert_b64: &str,
+    ) -> Result<String, ErrorResponse> {
+        let response_id = new_id();
+        let assertion_id = new_id();
+        let issue_instant = instant(now);
+        let not_before = instant(now - CLOCK_SKEW);
+        let not_on_or_after = instant(now + ASSERTION_LIFETIME);
+        let in_response_to = self
+            .in_response_to
+            .map(|id| format!(" InResponseTo=\"{}\"", escape_attr(id)))
+            .unwrap_or_default();
+
+        let head = format!(
+            "<saml:Assertion xmlns:saml=\"{NS_SAML}\" ID=\"{assertion_id}\" \
+IssueInstant=\"{issue_instant}\" Version=\"2.0\">\
+<saml:Issuer>{}</saml:Issuer>",
+            escape_text(self.idp_entity_id),
+        );
+
+        let mut tail = format!(
+            "<saml:Subject>\
+<saml:NameID Format=\"{}\">{}</saml:NameID>\
+<saml:SubjectConfirmation Method=\"urn:oasis:names:tc:SAML:2.0:cm:bearer\">\
+<saml:SubjectConfirmationData{in_response_to} NotOnOrAfter=\"{not_on_or_after}\" Recipient=\"{}\">\
+</saml:SubjectConfirmationData>\
+</saml:SubjectConfirmation>\
+</saml:Subject>\
+<saml:Conditions NotBefore=\"{not_before}\" NotOnOrAfter=\"{not_on_or_after}\">\
+<saml:AudienceRestriction><saml:Audience>{}</saml:Audience></saml:AudienceRestriction>\
+</saml:Conditions>\
+<saml:AuthnStatement AuthnInstant=\"{}\" SessionIndex=\"{}\">\
+<saml:AuthnContext>\
+<saml:AuthnContextClassRef>\
+urn:oasis:names:tc:SAML:2.0:ac:classes:PasswordProtectedTransport\
+</saml:AuthnContextClassRef>\
+</saml:AuthnContext>\
+</saml:AuthnStatement>",
+            self.name_id_format.as_urn(),
+            escape_text(self.name_id),
+            escape_attr(self.acs_url),
+            escape_text(self.sp_entity_id),
+            instant(self.auth_time),
+            escape_attr(self.session_index),
+        );
+
+        let attributes = self
+            .attributes
+            .iter()
+            .filter(|attr| !attr.values.is_empty())
+            .collect::<Vec<_>>();
+        if !attributes.is_empty() {
+            tail.push_str("<saml:AttributeStatement>");
+            for attr in attributes {
+                write!(
+                    tail,
+                    "<saml:Attribute Name=\"{}\" \
+NameFormat=\"urn:oasis:names:tc:SAML:2.0:attrname-format:basic\">",
+                    escape_attr(&attr.name)
+                )?;
+                for value in &attr.values {
+                    write!(
+                        tail,
+                        "<saml:AttributeValue>{}</saml:AttributeValue>",
+                        escape_text(value)
+                    )?;
+                }
+                tail.push_str("</saml:Attribute>");
+            }
+            tail.push_str("</saml:AttributeStatement>");
+        }
+        tail.push_str("</saml:Assertion>");
+
+        let assertion = sign_enveloped(&head, &tail, &assertion_id, key, cert_b64)?;
+
+        Ok(format!(
+            "<samlp:Response xmlns:samlp=\"{NS_SAMLP}\" Destination=\"{}\" ID=\"{response_id}\"\
+{in_response_to} IssueInstant=\"{issue_instant}\" Version=\"2.0\">\
+<saml:Issuer xmlns:saml=\"{NS_SAML}\">{}</saml:Issuer>\
+<samlp:Status><samlp:StatusCode Value=\"{STATUS_SUCCESS}\"></samlp:StatusCode></samlp:Status>\
+{assertion}\
+</samlp:Response>",
+            escape_attr(self.acs_url),
+            escape_text(self.idp_entity_id),
+        ))
+    }
+}
+
+/// Builds a signed `<samlp:LogoutResponse>`
+pub fn build_logout_response(
+    idp_entity_id: &str,
+    destination: &str,
+    in_response_to: &str,
+    status: &str,
+    now: i64,
+    key: &PKey<Private>,
+    cert_b64: &str,
+) -> Result<String, ErrorResponse> {
+    let id = new_id();
+    let head = format!(
+        "<samlp:LogoutResponse xmlns:samlp=\"{NS_SAMLP}\" Destination=\"{}\" ID=\"{id}\" \
+InResponseTo=\"{}\" IssueInstant=\"{}\" Version=\"2.0\">\
+<saml:Issuer xmlns:saml=\"{NS_SAML}\">{}</saml:Issuer>",
+        escape_attr(destination),
+        escape_attr(in_response_to),
+        instant(now),
+        escape_text(idp_entity_id),
+    );
+    let tail = format!(
+        "<samlp:Status><samlp:StatusCode Value=\"{}\"></samlp:StatusCode></samlp:Status>\
+</samlp:LogoutResponse>",
+        escape_attr(status),
+    );
+
+    sign_enveloped(&head, &tail, &id, key, cert_b64)
+}
+
+/// Builds the IdP metadata `<md:EntityDescriptor>` containing all currently valid signing
+/// certificates.
+pub fn build_idp_metadata(issuer: &str, certs_b64: &[String]) -> String {
+    let entity_id = idp_entity_id(issuer);
+    let sso_url = format!("{}/saml/sso", issuer);
+    let slo_url = format!("{}/saml/slo", issuer);
+
+    let mut xml = format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\
+<md:EntityDescriptor xmlns:md=\"{NS_MD}\" entityID=\"{}\">\
+<md:IDPSSODescriptor WantAuthnRequestsSigned=\"false\" \
+protocolSupportEnumeration=\"{NS_SAMLP}\">",
+        escape_attr(&entity_id)
+    );
+
+    for cert in certs_b64 {
+        let _ = write!(
+            xml,
+            "<md:KeyDescriptor use=\"signing\">\
+<ds:KeyInfo xmlns:ds=\"{NS_DS}\"><ds:X509Data><ds:X509Certificate>{cert}</ds:X509Certificate>\
+</ds:X509Data></ds:KeyInfo></md:KeyDescriptor>",
+        );
+    }
+
+    for binding in [BINDING_REDIRECT, BINDING_POST] {
+        let _ = write!(
+            xml,
+            "<md:SingleLogoutService Binding=\"{binding}\" Location=\"{}\"></md:SingleLogoutService>",
+            escape_attr(&slo_url),
+        );
+    }
+    for format in [
+        SamlNameIdFormat::Email,
+        SamlNameIdFormat::Persistent,
+        SamlNameIdFormat::Transient,
+        SamlNameIdFormat::Unspecified,
+    ] {
+        let _ = write!(
+            xml,
+            "<md:NameIDFormat>{}</md:NameIDFormat>",
+            format.as_urn()
+        );
+    }
+    for binding in [BINDING_REDIRECT, BINDING_POST] {
+        let _ = write!(
+            xml,
+            "<md:SingleSignOnService Binding=\"{binding}\" Location=\"{}\"></md:SingleSignOnService>",
+            escape_attr(&sso_url),
+        );
+    }
+
+    xml.push_str("</md:IDPSSODescriptor></md:EntityDescriptor>");
+    xml
+}
+
+/// The `entityID` Rauthy uses as an IdP
+pub fn idp_entity_id(issuer: &str) -> String {
+    format!("{}/saml/metadata", issuer)
+}
+
+pub(super) fn check_version(root: &XmlElement) -> Result<(), ErrorResponse> {
+    if root.attr("Version") != Some("2.0") {
+        return Err(ErrorResponse::new(
+            ErrorResponseType::BadRequest,
+            "Only SAML Version 2.0 is supported",
+        ));
+    }
+    Ok(())
+}
+
+pub(super) fn required_attr(elem: &XmlElement, name: &str) -> Result<String, ErrorResponse> {
+    elem.attr(name).map(String::from).ok_or_else(|| {
+        ErrorResponse::new(
+            ErrorResponseType::BadRequest,
+            format!("Missing '{}' on SAML {}", name, elem.local_name()),
+        )
+    })
+}
+
+pub(super) fn required_issuer(root: &XmlElement) -> Result<String, ErrorResponse> {
+    root.child("Issuer")
+        .map(|i| i.text_trimmed().to_string())
+        .filter(|i| !i.is_empty())
+        .ok_or_else(|| {
+            ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                format!("Missing Issuer on SAML {}", root.local_name()),
+            )
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::saml::XmlElement;
+    use openssl::rsa::Rsa;
+
+    #[test]
+    fn test_parse_authn_request() {
+        let xml = r#"<samlp:AuthnRequest xmlns:samlp="urn:oasis:names:tc:SAML:2.0:protocol"
+    xmlns:saml="urn:oasis:names:tc:SAML:2.0:assertion" ID="_req1" Version="2.0"
+    IssueInstant="2024-01-01T00:00:00Z" ForceAuthn="true"
+    AssertionConsumerServiceURL="https://sp.example.com/acs">
+  <saml:Issuer>https://sp.example.com/metadata</saml:Issuer>
+  <samlp:NameIDPolicy Format="urn:oasis:names:tc:SAML:2.0:nameid-format:persistent" AllowCreate="true"/>
+</samlp:AuthnRequest>"#;
+
+        let req = AuthnRequest::parse(xml).unwrap();
+        assert_eq!(
+            req,
+            AuthnRequest {
+                id: "_req1".to_string(),
+                issuer: "https://sp.example.com/metadata".to_string(),
+                acs_url: Some("https://sp.example.com/acs".to_string()),
+                name_id_format: Some(
+                    "urn:oasis:names:tc:SAML:2.0:nameid-format:persistent".to_string()
+                ),
+                force_authn: true,
+            }
+        );
+
+        let wrong = xml.replace("Version=\"2.0\"", "Version=\"1.1\"");
+        assert!(AuthnRequest::parse(&wrong).is_err());
+    }
+
+    #[test]
+    fn test_parse_sp_metadata() {
+        let xml = r#"<?xml version="1.0"?>
+<md:EntityDescriptor xmlns:md="urn:oasis:names:tc:SAML:2.0:metadata" entityID="https://sp.example.com/metadata">
+  <md:SPSSODescriptor protocolSupportEnumeration="urn:oasis:names:tc:SAML:2.0:protocol">
+    <md:SingleLogoutService Binding="urn:oasis:names:tc:SAML:2.0:bindings:HTTP-Redirect" Location="https://sp.example.com/slo/redirect"/>
+    <md:SingleLogoutService Binding="urn:oasis:names:tc:SAML:2.0:bindings:HTTP-POST" Location="https://sp.example.com/slo"/>
+    <md:NameIDFormat>urn:oasis:names:tc:SAML:1.1:nameid-format:emailAddress</md:NameIDFormat>
+    <md:AssertionConsumerService Binding="urn:oasis:names:tc:SAML:2.0:bindings:HTTP-Artifact" Location="https://sp.example.com/artifact" index="0"/>
+    <md:AssertionConsumerService Binding="urn:oasis:names:tc:SAML:2.0:bindings:HTTP-POST" Location="https://sp.example.com/acs2" index="2"/>
+    <md:AssertionConsumerService Binding="urn:oasis:names:tc:SAML:2.0:bindings:HTTP-POST" Location="https://sp.example.com/acs" index="1"/>
+  </md:SPSSODescriptor>
+</md:EntityDescriptor>"#;
+
+        let md = SpMetadata::parse(xml).unwrap();
+        assert_eq!(
+            md,
+            SpMetadata {
+                entity_id: "https://sp.example.com/metadata".to_string(),
+                acs_url: "https://sp.example.com/acs".to_string(),
+                slo_url: Some("https://sp.example.com/slo".to_string()),
+                name_id_format: Some(SamlNameIdFormat::Email),
+                cert_pem: None,
+                authn_requests_signed: false,
+            }
+        );
+    }
+
+    #[test]
+    fn test_build_signed_response() {
+        let key = PKey::from_rsa(Rsa::generate(2048).unwrap()).unwrap();
+        let resp = SamlResponse {
+            idp_entity_id: "https://idp.example.com/auth/v1/saml/metadata",
+            sp_entity_id: "https://sp.example.com/metadata",
+            acs_url: "https://sp.example.com/acs?x=1&y=2",
+            in_response_to: Some("_req1"),
+            name_id: "admin@localhost.de",
+            name_id_format: SamlNameIdFormat::Email,
+            session_index: "_sidx",
+            auth_time: 1700000000,
+            attributes: vec![SamlAttribute {
+                name: "roles".to_string(),
+                values: vec!["admin".to_string(), "<user>".to_string()],
+            }],
+        }
+        .build_signed(1700000000, &key, "Y2VydA==")
+        .unwrap();
+
+        let root = XmlElement::parse(&resp).unwrap();
+        assert_eq!(root.local_name(), "Response");
+        assert_eq!(root.attr("InResponseTo"), Some("_req1"));
+        let assertion = root.child("Assertion").unwrap();
+        assert!(assertion.child("Signature").is_some());
+        let values = assertion
+            .find("Attribute")
+            .unwrap()
+            .children("AttributeValue")
+            .map(|v| v.text_trimmed().to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(values, vec!["admin".to_string(), "<user>".to_string()]);
+        assert_eq!(
+            assertion.find("Audience").unwrap().text_trimmed(),
+            "https://sp.example.com/metadata"
+        );
+    }
+}
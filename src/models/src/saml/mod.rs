@@ -0,0 +1,574 @@
+//! Minimal SAML 2.0 protocol support.
+//!
+//! All XML which Rauthy signs is built directly in its exclusive canonical form (no whitespace
+//! between elements, sorted attributes, explicit end tags, namespaces declared where they are
+//! used first). This way, digests and signatures can be computed over the output as-is, without
+//! the need for a full C14N implementation.
+
+use chrono::DateTime;
+use flate2::read::DeflateDecoder;
+use openssl::hash::MessageDigest;
+use openssl::pkey::{PKey, Private};
+use openssl::sign::{Signer, Verifier};
+use openssl::x509::X509;
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::Reader;
+use rauthy_common::utils::{base64_decode, base64_encode, get_rand};
+use rauthy_error::{ErrorResponse, ErrorResponseType};
+use std::borrow::Cow;
+use std::fmt::Write;
+use std::io::Read;
+
+pub mod dsig;
+pub mod idp;
+pub mod sp;
+
+pub const NS_SAML: &str = "urn:oasis:names:tc:SAML:2.0:assertion";
+pub const NS_SAMLP: &str = "urn:oasis:names:tc:SAML:2.0:protocol";
+pub const NS_MD: &str = "urn:oasis:names:tc:SAML:2.0:metadata";
+pub const NS_DS: &str = "http://www.w3.org/2000/09/xmldsig#";
+
+pub const BINDING_POST: &str = "urn:oasis:names:tc:SAML:2.0:bindings:HTTP-POST";
+pub const BINDING_REDIRECT: &str = "urn:oasis:names:tc:SAML:2.0:bindings:HTTP-Redirect";
+
+pub const STATUS_SUCCESS: &str = "urn:oasis:names:tc:SAML:2.0:status:Success";
+pub const STATUS_REQUESTER: &str = "urn:oasis:names:tc:SAML:2.0:status:Requester";
+
+const ALG_EXC_C14N: &str = "http://www.w3.org/2001/10/xml-exc-c14n#";
+const ALG_ENVELOPED: &str = "http://www.w3.org/2000/09/xmldsig#enveloped-signature";
+const ALG_RSA_SHA256: &str = "http://www.w3.org/2001/04/xmldsig-more#rsa-sha256";
+const ALG_RSA_SHA512: &str = "http://www.w3.org/2001/04/xmldsig-more#rsa-sha512";
+const ALG_SHA256: &str = "http://www.w3.org/2001/04/xmlenc#sha256";
+
+/// Upper limit for inflated `HTTP-Redirect` messages to not be vulnerable to deflate bombs.
+const MAX_INFLATED_LEN: u64 = 256 * 1024;
+/// Max nesting depth for parsed XML documents.
+const MAX_XML_DEPTH: usize = 32;
+/// Allowed clock skew for the validation of timestamps in seconds
+const CLOCK_SKEW: i64 = 60;
+
+/// A very simple XML element tree, which is enough to extract values from SAML messages
+/// and metadata. Names are kept with their original prefix.
+#[derive(Debug, Default, Clone)]
+pub struct XmlElement {
+    pub name: String,
+    pub attrs: Vec<(String, String)>,
+    pub children: Vec<XmlElement>,
+    pub text: String,
+}
+
+impl XmlElement {
+    pub fn parse(xml: &str) -> Result<Self, ErrorResponse> {
+        let mut reader = Reader::from_str(xml);
+        reader.config_mut().trim_text(true);
+
+        let mut stack: Vec<XmlElement> = Vec::with_capacity(8);
+        loop {
+            match reader.read_event().map_err(xml_err)? {
+                Event::Start(start) => {
+                    if stack.len() >= MAX_XML_DEPTH {
+                        return Err(ErrorResponse::new(
+                            ErrorResponseType::BadRequest,
+                            "XML document is nested too deeply",
+                        ));
+                    }
+                    stack.push(Self::from_start(&start)?);
+                }
+                Event::Empty(start) => {
+                    let elem = Self::from_start(&start)?;
+                    match stack.last_mut() {
+                        Some(parent) => parent.children.push(elem),
+                        None => return Ok(elem),
+                    }
+                }
+                Event::End(_) => {
+                    let elem = stack.pop().ok_or_else(|| xml_err("unexpected end tag"))?;
+                    match stack.last_mut() {
+                        Some(parent) => parent.children.push(elem),
+                        None => return Ok(elem),
+                    }
+                }
+                Event::Text(text) => {
+                    if let Some(elem) = stack.last_mut() {
+                        elem.text.push_str(&text.unescape().map_err(xml_err)?);
+                    }
+                }
+                Event::CData(data) => {
+                    if let Some(elem) = stack.last_mut() {
+                        elem.text
+                            .push_str(&String::from_utf8_lossy(&data.into_inner()));
+                    }
+                }
+                Event::DocType(_) => {
+                    // we never need a DTD and reject it to not open any doors for entity attacks
+                    return Err(ErrorResponse::new(
+                        ErrorResponseType::BadRequest,
+                        "DOCTYPE is not allowed in SAML messages",
+                    ));
+                }
+                Event::Eof => return Err(xml_err("unexpected end of document")),
+                Event::Decl(_) | Event::Comment(_) | Event::PI(_) => {}
+            }
+        }
+    }
+
+    fn from_start(start: &BytesStart) -> Result<Self, ErrorResponse> {
+        let name = String::from_utf8_lossy(start.name().as_ref()).to_string();
+        let mut attrs = Vec::with_capacity(4);
+        for attr in start.attributes() {
+            let attr = attr.map_err(xml_err)?;
+            let key = String::from_utf8_lossy(attr.key.as_ref()).to_string();
+            let value = attr.unescape_value().map_err(xml_err)?.to_string();
+            attrs.push((key, value));
+        }
+
+        Ok(Self {
+            name,
+            attrs,
+            children: Vec::default(),
+            text: String::default(),
+        })
+    }
+
+    /// The element name without its namespace prefix
+    pub fn local_name(&self) -> &str {
+        local_name(&self.name)
+    }
+
+    /// Returns the value of the attribute with the given local name
+    pub fn attr(&self, name: &str) -> Option<&str> {
+        self.attrs
+            .iter()
+            .find(|(k, _)| local_name(k) == name && !k.starts_with("xmlns"))
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Returns the first direct child with the given local name
+    pub fn child(&self, name: &str) -> Option<&XmlElement> {
+        self.children.iter().find(|c| c.local_name() == name)
+    }
+
+    /// Returns all direct children with the given local name
+    pub fn children<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a XmlElement> {
+        self.children.iter().filter(move |c| c.local_name() == name)
+    }
+
+    /// Returns the first element with the given local name in the whole subtree (depth-first)
+    pub fn find(&self, name: &str) -> Option<&XmlElement> {
+        for child in &self.children {
+            if child.local_name() == name {
+                return Some(child);
+            }
+            if let Some(elem) = child.find(name) {
+                return Some(elem);
+            }
+        }
+        None
+    }
+
+    pub fn text_trimmed(&self) -> &str {
+        self.text.trim()
+    }
+}
+
+#[inline]
+fn local_name(name: &str) -> &str {
+    name.rsplit_once(':').map(|(_, n)| n).unwrap_or(name)
+}
+
+fn xml_err<E: std::fmt::Display>(err: E) -> ErrorResponse {
+    ErrorResponse::new(
+        ErrorResponseType::BadRequest,
+        format!("Invalid XML: {}", err),
+    )
+}
+
+fn ssl_err(err: openssl::error::ErrorStack) -> ErrorResponse {
+    ErrorResponse::new(
+        ErrorResponseType::Internal,
+        format!("SAML signature error: {}", err),
+    )
+}
+
+/// Escapes text content like the canonical XML form does.
+pub fn escape_text(value: &str) -> Cow<str> {
+    if !value.contains(['&', '<', '>', '\r']) {
+        return Cow::Borrowed(value);
+    }
+
+    let mut res = String::with_capacity(value.len() + 8);
+    for c in value.chars() {
+        match c {
+            '&' => res.push_str("&amp;"),
+            '<' => res.push_str("&lt;"),
+            '>' => res.push_str("&gt;"),
+            '\r' => res.push_str("&#xD;"),
+            c => res.push(c),
+        }
+    }
+    Cow::Owned(res)
+}
+
+/// Escapes attribute values like the canonical XML form does.
+pub fn escape_attr(value: &str) -> Cow<str> {
+    if !value.contains(['&', '<', '"', '\t', '\n', '\r']) {
+        return Cow::Borrowed(value);
+    }
+
+    let mut res = String::with_capacity(value.len() + 8);
+    for c in value.chars() {
+        match c {
+            '&' => res.push_str("&amp;"),
+            '<' => res.push_str("&lt;"),
+            '"' => res.push_str("&quot;"),
+            '\t' => res.push_str("&#x9;"),
+            '\n' => res.push_str("&#xA;"),
+            '\r' => res.push_str("&#xD;"),
+            c => res.push(c),
+        }
+    }
+    Cow::Owned(res)
+}
+
+/// A new random ID, which is a valid `xs:ID` (`NCName`)
+pub fn new_id() -> String {
+    format!("_{}", get_rand(40))
+}
+
+/// Formats a unix timestamp as `xs:dateTime` in UTC
+pub fn instant(ts: i64) -> String {
+    DateTime::from_timestamp(ts, 0)
+        .unwrap_or_default()
+        .format("%Y-%m-%dT%H:%M:%SZ")
+        .to_string()
+}
+
+/// Decodes a message received via the `HTTP-Redirect` binding (base64 + raw DEFLATE).
+pub fn decode_redirect(value: &str) -> Result<String, ErrorResponse> {
+    let deflated = base64_decode(value)?;
+    let mut xml = String::with_capacity(deflated.len() * 4);
+    DeflateDecoder::new(deflated.as_slice())
+        .take(MAX_INFLATED_LEN)
+        .read_to_string(&mut xml)
+        .map_err(|_| {
+            ErrorResponse::new(ErrorResponseType::BadRequest, "Cannot inflate SAML message")
+        })?;
+    Ok(xml)
+}
+
+/// Decodes a message received via the `HTTP-POST` binding (base64 only).
+pub fn decode_post(value: &str) -> Result<String, ErrorResponse> {
+    let cleaned = value
+        .chars()
+        .filter(|c| !c.is_ascii_whitespace())
+        .collect::<String>();
+    let bytes = base64_decode(&cleaned)?;
+    String::from_utf8(bytes).map_err(|_| {
+        ErrorResponse::new(
+            ErrorResponseType::BadRequest,
+            "SAML message is not valid UTF-8",
+        )
+    })
+}
+
+/// Converts a certificate given either as PEM or as the raw base64 DER (like found inside
+/// `<ds:X509Certificate>`) into a validated PEM.
+pub fn normalize_cert_pem(cert: &str) -> Result<String, ErrorResponse> {
+    let b64 = cert
+        .trim()
+        .trim_start_matches("-----BEGIN CERTIFICATE-----")
+        .trim_end_matches("-----END CERTIFICATE-----")
+        .chars()
+        .filter(|c| !c.is_ascii_whitespace())
+        .collect::<String>();
+    let der = base64_decode(&b64)?;
+    X509::from_der(&der).map_err(|_| {
+        ErrorResponse::new(ErrorResponseType::BadRequest, "Invalid X.509 certificate")
+    })?;
+
+    let lines = b64
+        .as_bytes()
+        .chunks(64)
+        .map(|c| String::from_utf8_lossy(c).to_string())
+        .collect::<Vec<_>>()
+        .join("\n");
+    Ok(format!(
+        "-----BEGIN CERTIFICATE-----\n{}\n-----END CERTIFICATE-----",
+        lines
+    ))
+}
+
+/// Signs an XML element with an enveloped signature.
+///
+/// `head` must contain the opening tag of the element up to and including its `<saml:Issuer>`,
+/// `tail` the rest of the element including its closing tag. Both parts must already be in
+/// exclusive canonical form. The `<ds:Signature>` will be inserted in between.
+pub fn sign_enveloped(
+    head: &str,
+    tail: &str,
+    ref_id: &str,
+    key: &PKey<Private>,
+    cert_b64: &str,
+) -> Result<String, ErrorResponse> {
+    let digest = openssl::sha::sha256(format!("{}{}", head, tail).as_bytes());
+
+    let signed_info = format!(
+        "<ds:SignedInfo xmlns:ds=\"{NS_DS}\">\
+<ds:CanonicalizationMethod Algorithm=\"{ALG_EXC_C14N}\"></ds:CanonicalizationMethod>\
+<ds:SignatureMethod Algorithm=\"{ALG_RSA_SHA256}\"></ds:SignatureMethod>\
+<ds:Reference URI=\"#{}\">\
+<ds:Transforms>\
+<ds:Transform Algorithm=\"{ALG_ENVELOPED}\"></ds:Transform>\
+<ds:Transform Algorithm=\"{ALG_EXC_C14N}\"></ds:Transform>\
+</ds:Transforms>\
+<ds:DigestMethod Algorithm=\"{ALG_SHA256}\"></ds:DigestMethod>\
+<ds:DigestValue>{}</ds:DigestValue>\
+</ds:Reference>\
+</ds:SignedInfo>",
+        escape_attr(ref_id),
+        base64_encode(&digest),
+    );
+
+    let mut signer = Signer::new(MessageDigest::sha256(), key).map_err(ssl_err)?;
+    signer.update(signed_info.as_bytes()).map_err(ssl_err)?;
+    let signature = signer.sign_to_vec().map_err(ssl_err)?;
+
+    Ok(format!(
+        "{head}<ds:Signature xmlns:ds=\"{NS_DS}\">{signed_info}\
+<ds:SignatureValue>{}</ds:SignatureValue>\
+<ds:KeyInfo><ds:X509Data><ds:X509Certificate>{cert_b64}</ds:X509Certificate></ds:X509Data></ds:KeyInfo>\
+</ds:Signature>{tail}",
+        base64_encode(&signature),
+    ))
+}
+
+/// Validates the signature of a message received via the `HTTP-Redirect` binding.
+///
+/// The signature is built over the raw, still URL-encoded query parameters
+/// `SAMLRequest|SAMLResponse`, `RelayState` and `SigAlg` in exactly this order.
+pub fn validate_redirect_signature(raw_query: &str, cert_pem: &str) -> Result<(), ErrorResponse> {
+    let mut msg = None;
+    let mut relay_state = None;
+    let mut sig_alg = None;
+    let mut signature = None;
+    for pair in raw_query.split('&') {
+        let (k, _) = pair.split_once('=').unwrap_or((pair, ""));
+        match k {
+            "SAMLRequest" | "SAMLResponse" => msg = Some(pair),
+            "RelayState" => relay_state = Some(pair),
+            "SigAlg" => sig_alg = Some(pair),
+            "Signature" => signature = Some(pair),
+            _ => {}
+        }
+    }
+
+    let err = || {
+        ErrorResponse::new(
+            ErrorResponseType::Forbidden,
+            "Invalid or missing SAML message signature",
+        )
+    };
+    let (Some(msg), Some(sig_alg), Some(signature)) = (msg, sig_alg, signature) else {
+        return Err(err());
+    };
+
+    let alg = url_decode(sig_alg.split_once('=').map(|(_, v)| v).unwrap_or_default());
+    let digest = match alg.as_str() {
+        ALG_RSA_SHA256 => MessageDigest::sha256(),
+        ALG_RSA_SHA512 => MessageDigest::sha512(),
+        _ => {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                format!("Unsupported SigAlg: {}", alg),
+            ))
+        }
+    };
+    let signature = base64_decode(&url_decode(
+        signature
+            .split_once('=')
+            .map(|(_, v)| v)
+            .unwrap_or_default(),
+    ))?;
+
+    let signed = match relay_state {
+        Some(rs) => format!("{}&{}&{}", msg, rs, sig_alg),
+        None => format!("{}&{}", msg, sig_alg),
+    };
+
+    let cert = X509::from_pem(cert_pem.as_bytes()).map_err(ssl_err)?;
+    let pub_key = cert.public_key().map_err(ssl_err)?;
+    let mut verifier = Verifier::new(digest, &pub_key).map_err(ssl_err)?;
+    verifier.update(signed.as_bytes()).map_err(ssl_err)?;
+    if verifier.verify(&signature).unwrap_or(false) {
+        Ok(())
+    } else {
+        Err(err())
+    }
+}
+
+/// Validates the enveloped signature of a message received via the `HTTP-POST` binding.
+///
+/// In contrast to the `HTTP-Redirect` binding, the signature is embedded inside the XML and
+/// must cover the root element itself. Unsigned messages are rejected.
+pub fn validate_post_signature(xml: &str, cert_pem: &str) -> Result<(), ErrorResponse> {
+    let root = dsig::DsigElement::parse(xml)?;
+    let cert = X509::from_pem(cert_pem.as_bytes()).map_err(ssl_err)?;
+
+    match root.verify_enveloped(&[], &cert) {
+        Ok(true) => Ok(()),
+        Ok(false) | Err(_) => Err(ErrorResponse::new(
+            ErrorResponseType::Forbidden,
+            "Invalid or missing SAML message signature",
+        )),
+    }
+}
+
+/// Percent-encoding for query values, which keeps only unreserved characters.
+pub fn url_encode(value: &str) -> String {
+    let mut res = String::with_capacity(value.len() * 3 / 2);
+    for b in value.bytes() {
+        match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                res.push(b as char)
+            }
+            b => {
+                let _ = write!(res, "%{:02X}", b);
+            }
+        }
+    }
+    res
+}
+
+/// Percent-decoding for query values, where `+` is a space as well.
+fn url_decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut res = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'+' => res.push(b' '),
+            b'%' if i + 2 < bytes.len() => {
+                let hex = std::str::from_utf8(&bytes[i + 1..i + 3]).unwrap_or_default();
+                match u8::from_str_radix(hex, 16) {
+                    Ok(b) => {
+                        res.push(b);
+                        i += 2;
+                    }
+                    Err(_) => res.push(b'%'),
+                }
+            }
+            b => res.push(b),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&res).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use openssl::asn1::Asn1Time;
+    use openssl::rsa::Rsa;
+    use openssl::x509::X509NameBuilder;
+
+    fn test_key_cert() -> (PKey<Private>, X509) {
+        let key = PKey::from_rsa(Rsa::generate(2048).unwrap()).unwrap();
+        let mut name = X509NameBuilder::new().unwrap();
+        name.append_entry_by_text("CN", "sp.example.com").unwrap();
+        let name = name.build();
+        let mut builder = X509::builder().unwrap();
+        builder.set_subject_name(&name).unwrap();
+        builder.set_issuer_name(&name).unwrap();
+        builder.set_pubkey(&key).unwrap();
+        builder
+            .set_not_before(Asn1Time::days_from_now(0).unwrap().as_ref())
+            .unwrap();
+        builder
+            .set_not_after(Asn1Time::days_from_now(1).unwrap().as_ref())
+            .unwrap();
+        builder.sign(&key, MessageDigest::sha256()).unwrap();
+        (key, builder.build())
+    }
+
+    #[test]
+    fn test_xml_parse() {
+        let xml = r#"<?xml version="1.0"?>
+<samlp:AuthnRequest xmlns:samlp="urn:oasis:names:tc:SAML:2.0:protocol"
+    xmlns:saml="urn:oasis:names:tc:SAML:2.0:assertion"
+    ID="_abc123" Version="2.0" IssueInstant="2024-01-01T00:00:00Z"
+    AssertionConsumerServiceURL="https://sp.example.com/acs?a=1&amp;b=2">
+  <saml:Issuer>https://sp.example.com</saml:Issuer>
+  <samlp:NameIDPolicy Format="urn:oasis:names:tc:SAML:1.1:nameid-format:emailAddress"/>
+</samlp:AuthnRequest>"#;
+
+        let root = XmlElement::parse(xml).unwrap();
+        assert_eq!(root.local_name(), "AuthnRequest");
+        assert_eq!(root.attr("ID"), Some("_abc123"));
+        assert_eq!(
+            root.attr("AssertionConsumerServiceURL"),
+            Some("https://sp.example.com/acs?a=1&b=2")
+        );
+        assert_eq!(
+            root.child("Issuer").unwrap().text_trimmed(),
+            "https://sp.example.com"
+        );
+        assert!(root.child("NameIDPolicy").is_some());
+        assert!(root.find("NameIDPolicy").is_some());
+
+        let doctype = r#"<!DOCTYPE foo [<!ENTITY xxe SYSTEM "file:///etc/passwd">]><a>&xxe;</a>"#;
+        assert!(XmlElement::parse(doctype).is_err());
+    }
+
+    #[test]
+    fn test_escape() {
+        assert_eq!(escape_text("a < b & c > d"), "a &lt; b &amp; c &gt; d");
+        assert_eq!(escape_attr("\"x\"\t&"), "&quot;x&quot;&#x9;&amp;");
+        assert_eq!(escape_attr("plain"), Cow::Borrowed("plain"));
+    }
+
+    #[test]
+    fn test_url_decode() {
+        assert_eq!(
+            url_decode("http%3A%2F%2Fwww.w3.org%2F2001%2F04%2Fxmldsig-more%23rsa-sha256"),
+            ALG_RSA_SHA256
+        );
+        assert_eq!(url_decode("a+b%2"), "a b%2");
+        assert_eq!(
+            url_encode(ALG_RSA_SHA256),
+            "http%3A%2F%2Fwww.w3.org%2F2001%2F04%2Fxmldsig-more%23rsa-sha256"
+        );
+        assert_eq!(url_decode(&url_encode("a b+c/ä")), "a b+c/ä");
+    }
+
+    #[test]
+    fn test_validate_post_signature() {
+        let (key, cert) = test_key_cert();
+        let cert_b64 = base64_encode(&cert.to_der().unwrap());
+        let cert_pem = String::from_utf8(cert.to_pem().unwrap()).unwrap();
+
+        let head = format!(
+            "<samlp:LogoutRequest xmlns:samlp=\"{NS_SAMLP}\" ID=\"_lr1\" \
+IssueInstant=\"2024-01-01T00:00:00Z\" Version=\"2.0\">\
+<saml:Issuer xmlns:saml=\"{NS_SAML}\">https://sp.example.com</saml:Issuer>"
+        );
+        let tail = "<samlp:SessionIndex>_sidx</samlp:SessionIndex></samlp:LogoutRequest>";
+        let xml = sign_enveloped(&head, tail, "_lr1", &key, &cert_b64).unwrap();
+        validate_post_signature(&xml, &cert_pem).unwrap();
+
+        // modified content
+        let modified = xml.replace("_sidx", "_other");
+        let err = validate_post_signature(&modified, &cert_pem).unwrap_err();
+        assert_eq!(err.error, ErrorResponseType::Forbidden);
+
+        // unsigned
+        let unsigned = format!("{}{}", head, tail);
+        let err = validate_post_signature(&unsigned, &cert_pem).unwrap_err();
+        assert_eq!(err.error, ErrorResponseType::Forbidden);
+
+        // signed with another key
+        let (_, other_cert) = test_key_cert();
+        let other_pem = String::from_utf8(other_cert.to_pem().unwrap()).unwrap();
+        let err = validate_post_signature(&xml, &other_pem).unwrap_err();
+        assert_eq!(err.error, ErrorResponseType::Forbidden);
+    }
+}
@@ -0,0 +1,550 @@
+use crate::saml::dsig::DsigElement;
+use crate::saml::idp::{check_version, required_attr};
+use crate::saml::{
+    decode_post, escape_attr, escape_text, instant, new_id, normalize_cert_pem, ssl_err,
+    url_encode, XmlElement, ALG_RSA_SHA256, BINDING_POST, BINDING_REDIRECT, CLOCK_SKEW, NS_DS,
+    NS_MD, NS_SAML, NS_SAMLP, STATUS_SUCCESS,
+};
+use chrono::DateTime;
+use flate2::write::DeflateEncoder;
+use flate2::Compression;
+use openssl::hash::MessageDigest;
+use openssl::pkey::{PKey, Private};
+use openssl::sign::Signer;
+use openssl::x509::X509;
+use rauthy_common::utils::base64_encode;
+use rauthy_error::{ErrorResponse, ErrorResponseType};
+use std::fmt::Write as _;
+use std::io::Write;
+
+const CM_BEARER: &str = "urn:oasis:names:tc:SAML:2.0:cm:bearer";
+
+/// The relevant parts of an upstream IdP's metadata
+#[derive(Debug, PartialEq)]
+pub struct IdpMetadata {
+    pub entity_id: String,
+    /// The `SingleSignOnService` location for the `HTTP-Redirect` binding
+    pub sso_url: String,
+    pub cert_pem: String,
+}
+
+impl IdpMetadata {
+    pub fn parse(xml: &str) -> Result<Self, ErrorResponse> {
+        let root = XmlElement::parse(xml)?;
+        let entity = if root.local_name() == "EntitiesDescriptor" {
+            root.children("EntityDescriptor")
+                .find(|e| e.child("IDPSSODescriptor").is_some())
+        } else {
+            Some(&root)
+        }
+        .filter(|e| e.local_name() == "EntityDescriptor")
+        .ok_or_else(|| meta_err("No IdP EntityDescriptor found"))?;
+
+        let entity_id = required_attr(entity, "entityID")?;
+        let idp = entity
+            .child("IDPSSODescriptor")
+            .ok_or_else(|| meta_err("Missing IDPSSODescriptor"))?;
+
+        let sso_url = idp
+            .children("SingleSignOnService")
+            .find(|s| s.attr("Binding") == Some(BINDING_REDIRECT))
+            .and_then(|s| s.attr("Location"))
+            .ok_or_else(|| meta_err("The IdP does not support the HTTP-Redirect binding for SSO"))?
+            .to_string();
+
+        let cert = idp
+            .children("KeyDescriptor")
+            .filter(|k| matches!(k.attr("use"), None | Some("signing")))
+            .find_map(|k| k.find("X509Certificate"))
+            .map(|c| c.text_trimmed())
+            .ok_or_else(|| meta_err("No signing certificate found"))?;
+
+        Ok(Self {
+            entity_id,
+            sso_url,
+            cert_pem: normalize_cert_pem(cert)?,
+        })
+    }
+}
+
+/// A signed `<samlp:AuthnRequest>` for the `HTTP-Redirect` binding
+#[derive(Debug)]
+pub struct SpAuthnRequest<'a> {
+    pub sp_entity_id: &'a str,
+    pub acs_url: &'a str,
+    pub sso_url: &'a str,
+}
+
+impl SpAuthnRequest<'_> {
+    /// Returns the request `ID`, which must be validated as `InResponseTo` later on, and the
+    /// full redirect location including the signature.
+    pub fn build_redirect(
+        &self,
+        relay_state: &str,
+        now: i64,
+        key: &PKey<Private>,
+    ) -> Result<(String, String), ErrorResponse> {
+        let id = new_id();
+        let xml = format!(
+            "<samlp:AuthnRequest xmlns:samlp=\"{NS_SAMLP}\" \
+AssertionConsumerServiceURL=\"{}\" Destination=\"{}\" ID=\"{}\" IssueInstant=\"{}\" \
+ProtocolBinding=\"{BINDING_POST}\" Version=\"2.0\">\
+<saml:Issuer xmlns:saml=\"{NS_SAML}\">{}</saml:Issuer>\
+<samlp:NameIDPolicy AllowCreate=\"true\"></samlp:NameIDPolicy>\
+</samlp:AuthnRequest>",
+            escape_attr(self.acs_url),
+            escape_attr(self.sso_url),
+            id,
+            instant(now),
+            escape_text(self.sp_entity_id),
+        );
+
+        let mut encoder = DeflateEncoder::new(Vec::with_capacity(512), Compression::default());
+        encoder
+            .write_all(xml.as_bytes())
+            .and_then(|_| encoder.flush())
+            .map_err(|err| {
+                ErrorResponse::new(
+                    ErrorResponseType::Internal,
+                    format!("Cannot deflate SAML AuthnRequest: {}", err),
+                )
+            })?;
+        let deflated = encoder.finish().map_err(|err| {
+            ErrorResponse::new(
+                ErrorResponseType::Internal,
+                format!("Cannot deflate SAML AuthnRequest: {}", err),
+            )
+        })?;
+
+        let mut query = format!(
+            "SAMLRequest={}&RelayState={}&SigAlg={}",
+            url_encode(&base64_encode(&deflated)),
+            url_encode(relay_state),
+            url_encode(ALG_RSA_SHA256),
+        );
+        let mut signer = Signer::new(MessageDigest::sha256(), key).map_err(ssl_err)?;
+        signer.update(query.as_bytes()).map_err(ssl_err)?;
+        let signature = signer.sign_to_vec().map_err(ssl_err)?;
+        write!(
+            query,
+            "&Signature={}",
+            url_encode(&base64_encode(&signature))
+        )?;
+
+        let location = format!(
+            "{}{}{}",
+            self.sso_url,
+            if self.sso_url.contains('?') { '&' } else { '?' },
+            query
+        );
+        Ok((id, location))
+    }
+}
+
+/// A single attribute from an upstream assertion
+#[derive(Debug, PartialEq)]
+pub struct UpstreamAttribute {
+    pub name: String,
+    pub friendly_name: Option<String>,
+    pub values: Vec<String>,
+}
+
+/// The validated content of an upstream assertion
+#[derive(Debug)]
+pub struct UpstreamAssertion {
+    pub name_id: String,
+    pub session_index: Option<String>,
+    pub attributes: Vec<UpstreamAttribute>,
+}
+
+/// Everything needed to validate a `<samlp:Response>` from an upstream IdP
+#[derive(Debug)]
+pub struct ResponseValidation<'a> {
+    pub idp_entity_id: &'a str,
+    pub idp_cert_pem: &'a str,
+    pub sp_entity_id: &'a str,
+    pub acs_url: &'a str,
+    /// The `ID` of our `AuthnRequest` - unsolicited responses are not accepted
+    pub request_id: &'a str,
+    pub now: i64,
+}
+
+impl ResponseValidation<'_> {
+    /// Validates the base64 encoded `SAMLResponse` received via the `HTTP-POST` binding.
+    ///
+    /// Either the response or the assertion must be signed by the IdP. All values are only
+    /// ever extracted from the element that has been validated.
+    pub fn validate(&self, saml_response: &str) -> Result<UpstreamAssertion, ErrorResponse> {
+        let xml = decode_post(saml_response)?;
+        let root = DsigElement::parse(&xml)?;
+        if root.local_name() != "Response" {
+            return Err(resp_err("Expected a SAML Response"));
+        }
+
+        let cert = X509::from_pem(self.idp_cert_pem.as_bytes()).map_err(ssl_err)?;
+        let response_signed = root.verify_enveloped(&[], &cert)?;
+
+        let mut assertions = root.children("Assertion");
+        let assertion = assertions.next();
+        if assertions.next().is_some() {
+            return Err(resp_err("Only a single Assertion is supported"));
+        }
+        let assertion_signed = match assertion {
+            Some(assertion) => assertion.verify_enveloped(&root.scope_for_children(&[]), &cert)?,
+            None => false,
+        };
+
+        let response = root.to_element();
+        check_version(&response)?;
+        if let Some(issuer) = response.child("Issuer") {
+            if issuer.text_trimmed() != self.idp_entity_id {
+                return Err(resp_err("Invalid Issuer"));
+            }
+        }
+        if let Some(destination) = response.attr("Destination") {
+            if destination != self.acs_url {
+                return Err(resp_err("Invalid Destination"));
+            }
+        }
+        if response.attr("InResponseTo") != Some(self.request_id) {
+            return Err(resp_err("InResponseTo does not match the AuthnRequest"));
+        }
+
+        let status = response
+            .child("Status")
+            .and_then(|s| s.child("StatusCode"))
+            .and_then(|c| c.attr("Value"));
+        if status != Some(STATUS_SUCCESS) {
+            let msg = response
+                .child("Status")
+                .and_then(|s| s.child("StatusMessage"))
+                .map(|m| m.text_trimmed())
+                .unwrap_or_default();
+            return Err(ErrorResponse::new(
+                ErrorResponseType::Forbidden,
+                format!(
+                    "Upstream SAML login failed with status {}: {}",
+                    status.unwrap_or("unknown"),
+                    msg
+                ),
+            ));
+        }
+
+        if response.child("EncryptedAssertion").is_some() {
+            return Err(resp_err("Encrypted assertions are not supported"));
+        }
+        let Some(assertion) = response.child("Assertion") else {
+            return Err(resp_err("Missing Assertion"));
+        };
+        if !response_signed && !assertion_signed {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::Forbidden,
+                "Neither the SAML Response nor the Assertion is signed",
+            ));
+        }
+
+        self.validate_assertion(assertion)
+    }
+
+    fn validate_assertion(
+        &self,
+        assertion: &XmlElement,
+    ) -> Result<UpstreamAssertion, ErrorResponse> {
+        check_version(assertion)?;
+        let issuer = assertion
+            .child("Issuer")
+            .map(|i| i.text_trimmed())
+            .unwrap_or_default();
+        if issuer != self.idp_entity_id {
+            return Err(resp_err("Invalid Assertion Issuer"));
+        }
+
+        let subject = assertion
+            .child("Subject")
+            .ok_or_else(|| resp_err("Missing Subject"))?;
+        let name_id = subject
+            .child("NameID")
+            .map(|n| n.text_trimmed().to_string())
+            .filter(|n| !n.is_empty())
+            .ok_or_else(|| resp_err("Missing NameID"))?;
+
+        let confirmed = subject
+            .children("SubjectConfirmation")
+            .filter(|c| c.attr("Method") == Some(CM_BEARER))
+            .filter_map(|c| c.child("SubjectConfirmationData"))
+            .any(|data| {
+                data.attr("Recipient") == Some(self.acs_url)
+                    && data.attr("InResponseTo").unwrap_or(self.request_id) == self.request_id
+                    && data
+                        .attr("NotOnOrAfter")
+                        .and_then(timestamp)
+                        .is_some_and(|ts| ts > self.now - CLOCK_SKEW)
+            });
+        if !confirmed {
+            return Err(resp_err("No valid bearer SubjectConfirmation"));
+        }
+
+        let conditions = assertion
+            .child("Conditions")
+            .ok_or_else(|| resp_err("Missing Conditions"))?;
+        if let Some(not_before) = conditions.attr("NotBefore") {
+            match timestamp(not_before) {
+                Some(ts) if ts <= self.now + CLOCK_SKEW => {}
+                _ => return Err(resp_err("Assertion is not valid yet")),
+            }
+        }
+        if let Some(not_on_or_after) = conditions.attr("NotOnOrAfter") {
+            match timestamp(not_on_or_after) {
+                Some(ts) if ts > self.now - CLOCK_SKEW => {}
+                _ => return Err(resp_err("Assertion has expired")),
+            }
+        }
+        let mut restrictions = conditions.children("AudienceRestriction").peekable();
+        if restrictions.peek().is_none() {
+            return Err(resp_err("Missing AudienceRestriction"));
+        }
+        for restriction in restrictions {
+            if !restriction
+                .children("Audience")
+                .any(|a| a.text_trimmed() == self.sp_entity_id)
+            {
+                return Err(resp_err("Invalid Audience"));
+            }
+        }
+
+        let session_index = assertion
+            .child("AuthnStatement")
+            .and_then(|s| s.attr("SessionIndex"))
+            .map(String::from);
+
+        let attributes = assertion
+            .children("AttributeStatement")
+            .flat_map(|s| s.children("Attribute"))
+            .filter_map(|attr| {
+                Some(UpstreamAttribute {
+                    name: attr.attr("Name")?.to_string(),
+                    friendly_name: attr.attr("FriendlyName").map(String::from),
+                    values: attr
+                        .children("AttributeValue")
+                        .map(|v| v.text_trimmed().to_string())
+                        .collect(),
+                })
+            })
+            .collect();
+
+        Ok(UpstreamAssertion {
+            name_id,
+            session_index,
+            attributes,
+        })
+    }
+}
+
+/// Builds the SP metadata, which can be imported by upstream IdPs.
+pub fn build_sp_metadata(entity_id: &str, acs_url: &str, certs_b64: &[String]) -> String {
+    let mut xml = format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\
+<md:EntityDescriptor xmlns:md=\"{NS_MD}\" entityID=\"{}\">\
+<md:SPSSODescriptor AuthnRequestsSigned=\"true\" WantAssertionsSigned=\"true\" \
+protocolSupportEnumeration=\"{NS_SAMLP}\">",
+        escape_attr(entity_id)
+    );
+
+    for cert in certs_b64 {
+        let _ = write!(
+            xml,
+            "<md:KeyDescriptor use=\"signing\">\
+<ds:KeyInfo xmlns:ds=\"{NS_DS}\"><ds:X509Data><ds:X509Certificate>{cert}</ds:X509Certificate>\
+</ds:X509Data></ds:KeyInfo></md:KeyDescriptor>",
+        );
+    }
+
+    let _ = write!(
+        xml,
+        "<md:AssertionConsumerService Binding=\"{BINDING_POST}\" Location=\"{}\" index=\"0\" \
+isDefault=\"true\"></md:AssertionConsumerService>\
+</md:SPSSODescriptor></md:EntityDescriptor>",
+        escape_attr(acs_url),
+    );
+    xml
+}
+
+/// Parses an `xs:dateTime` into a unix timestamp
+fn timestamp(value: &str) -> Option<i64> {
+    DateTime::parse_from_rfc3339(value)
+        .ok()
+        .map(|dt| dt.timestamp())
+}
+
+fn meta_err(msg: &str) -> ErrorResponse {
+    ErrorResponse::new(
+        ErrorResponseType::BadRequest,
+        format!("Invalid SAML IdP metadata: {}", msg),
+    )
+}
+
+fn resp_err(msg: &str) -> ErrorResponse {
+    ErrorResponse::new(
+        ErrorResponseType::Forbidden,
+        format!("Invalid upstream SAML Response: {}", msg),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::saml::idp::{SamlAttribute, SamlResponse};
+    use crate::saml::{decode_redirect, validate_redirect_signature};
+    use openssl::asn1::Asn1Time;
+    use openssl::rsa::Rsa;
+    use openssl::x509::X509NameBuilder;
+    use rauthy_api_types::saml::SamlNameIdFormat;
+
+    fn test_key_cert() -> (PKey<Private>, X509) {
+        let key = PKey::from_rsa(Rsa::generate(2048).unwrap()).unwrap();
+        let mut name = X509NameBuilder::new().unwrap();
+        name.append_entry_by_text("CN", "idp.example.com").unwrap();
+        let name = name.build();
+        let mut builder = X509::builder().unwrap();
+        builder.set_subject_name(&name).unwrap();
+        builder.set_issuer_name(&name).unwrap();
+        builder.set_pubkey(&key).unwrap();
+        builder
+            .set_not_before(Asn1Time::days_from_now(0).unwrap().as_ref())
+            .unwrap();
+        builder
+            .set_not_after(Asn1Time::days_from_now(1).unwrap().as_ref())
+            .unwrap();
+        builder.sign(&key, MessageDigest::sha256()).unwrap();
+        (key, builder.build())
+    }
+
+    #[test]
+    fn test_parse_idp_metadata() {
+        let (_, cert) = test_key_cert();
+        let cert_b64 = base64_encode(&cert.to_der().unwrap());
+        let xml = format!(
+            r#"<md:EntityDescriptor xmlns:md="urn:oasis:names:tc:SAML:2.0:metadata" entityID="https://idp.example.com/adfs/services/trust">
+  <md:IDPSSODescriptor protocolSupportEnumeration="urn:oasis:names:tc:SAML:2.0:protocol">
+    <md:KeyDescriptor use="encryption"><ds:KeyInfo xmlns:ds="http://www.w3.org/2000/09/xmldsig#"><ds:X509Data><ds:X509Certificate>invalid</ds:X509Certificate></ds:X509Data></ds:KeyInfo></md:KeyDescriptor>
+    <md:KeyDescriptor use="signing"><ds:KeyInfo xmlns:ds="http://www.w3.org/2000/09/xmldsig#"><ds:X509Data><ds:X509Certificate>{cert_b64}</ds:X509Certificate></ds:X509Data></ds:KeyInfo></md:KeyDescriptor>
+    <md:SingleSignOnService Binding="urn:oasis:names:tc:SAML:2.0:bindings:HTTP-POST" Location="https://idp.example.com/post"/>
+    <md:SingleSignOnService Binding="urn:oasis:names:tc:SAML:2.0:bindings:HTTP-Redirect" Location="https://idp.example.com/redirect"/>
+  </md:IDPSSODescriptor>
+</md:EntityDescriptor>"#
+        );
+
+        let meta = IdpMetadata::parse(&xml).unwrap();
+        assert_eq!(
+            meta.entity_id,
+            "https://idp.example.com/adfs/services/trust"
+        );
+        assert_eq!(meta.sso_url, "https://idp.example.com/redirect");
+        assert!(meta.cert_pem.starts_with("-----BEGIN CERTIFICATE-----\n"));
+    }
+
+    #[test]
+    fn test_authn_request_redirect() {
+        let (key, cert) = test_key_cert();
+        let req = SpAuthnRequest {
+            sp_entity_id: "https://iam.example.com/auth/v1/providers/saml/metadata",
+            acs_url: "https://iam.example.com/auth/v1/providers/saml/acs",
+            sso_url: "https://idp.example.com/sso?tenant=1",
+        };
+        let (id, location) = req.build_redirect("callback123", 1700000000, &key).unwrap();
+
+        let (base, query) = location.split_once('?').unwrap();
+        assert_eq!(base, "https://idp.example.com/sso");
+        assert!(query.starts_with("tenant=1&SAMLRequest="));
+
+        let cert_pem = String::from_utf8(cert.to_pem().unwrap()).unwrap();
+        validate_redirect_signature(query, &cert_pem).unwrap();
+
+        let saml_request = query
+            .split('&')
+            .find_map(|p| p.strip_prefix("SAMLRequest="))
+            .unwrap();
+        let xml = decode_redirect(&crate::saml::url_decode(saml_request)).unwrap();
+        let root = XmlElement::parse(&xml).unwrap();
+        assert_eq!(root.attr("ID"), Some(id.as_str()));
+        assert_eq!(root.attr("AssertionConsumerServiceURL"), Some(req.acs_url));
+        assert_eq!(
+            root.child("Issuer").unwrap().text_trimmed(),
+            req.sp_entity_id
+        );
+    }
+
+    #[test]
+    fn test_validate_response() {
+        let (key, cert) = test_key_cert();
+        let cert_b64 = base64_encode(&cert.to_der().unwrap());
+        let cert_pem = String::from_utf8(cert.to_pem().unwrap()).unwrap();
+        let now = 1700000000;
+
+        // Rauthy's own IdP implementation builds exactly what we need for a roundtrip
+        let response = SamlResponse {
+            idp_entity_id: "https://idp.example.com",
+            sp_entity_id: "https://sp.example.com",
+            acs_url: "https://sp.example.com/acs",
+            in_response_to: Some("_req1"),
+            name_id: "user@example.com",
+            name_id_format: SamlNameIdFormat::Email,
+            session_index: "_sidx",
+            auth_time: now,
+            attributes: vec![SamlAttribute {
+                name: "groups".to_string(),
+                values: vec!["admins".to_string(), "users".to_string()],
+            }],
+        };
+        let xml = response.build_signed(now, &key, &cert_b64).unwrap();
+        let b64 = base64_encode(xml.as_bytes());
+
+        let validation = ResponseValidation {
+            idp_entity_id: "https://idp.example.com",
+            idp_cert_pem: &cert_pem,
+            sp_entity_id: "https://sp.example.com",
+            acs_url: "https://sp.example.com/acs",
+            request_id: "_req1",
+            now,
+        };
+        let assertion = validation.validate(&b64).unwrap();
+        assert_eq!(assertion.name_id, "user@example.com");
+        assert_eq!(assertion.session_index.as_deref(), Some("_sidx"));
+        assert_eq!(
+            assertion.attributes,
+            vec![UpstreamAttribute {
+                name: "groups".to_string(),
+                friendly_name: None,
+                values: vec!["admins".to_string(), "users".to_string()],
+            }]
+        );
+
+        // wrong request id
+        let v = ResponseValidation {
+            request_id: "_other",
+            ..validation
+        };
+        assert!(v.validate(&b64).is_err());
+
+        // expired
+        let v = ResponseValidation {
+            now: now + 3600,
+            request_id: "_req1",
+            ..v
+        };
+        assert!(v.validate(&b64).is_err());
+
+        // tampered content
+        let tampered = xml.replace("user@example.com", "admin@example.com");
+        let v = ResponseValidation { now, ..v };
+        assert!(v.validate(&base64_encode(tampered.as_bytes())).is_err());
+
+        // whitespace inside the signed assertion is significant
+        let pretty = xml.replace("<saml:Subject>", "\n  <saml:Subject>");
+        assert!(v.validate(&base64_encode(pretty.as_bytes())).is_err());
+
+        // but formatting outside of it must not break the signature
+        let pretty = xml.replace("<saml:Assertion ", "\n  <saml:Assertion ");
+        assert!(v.validate(&base64_encode(pretty.as_bytes())).is_ok());
+    }
+}
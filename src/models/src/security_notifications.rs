@@ -0,0 +1,175 @@
+use crate::api_cookie::ApiCookie;
+use crate::app_state::AppState;
+use crate::email;
+use crate::entity::magic_links::{MagicLink, MagicLinkUsage};
+use crate::entity::trusted_devices;
+use crate::entity::users::User;
+use crate::geoip;
+use actix_web::cookie::Cookie;
+use actix_web::http::header;
+use actix_web::{web, HttpRequest};
+use rauthy_common::constants::{COOKIE_KNOWN_DEVICES, SECURITY_NOTIFICATIONS};
+use rauthy_common::utils::real_ip_from_req;
+use tracing::error;
+
+/// Lifetime of the "this wasn't me" link in minutes. It is a lot longer than for a password
+/// reset, because users may read the notification quite some time later.
+const NOT_ME_LINK_LIFETIME: i64 = 24 * 60;
+/// Browsers cap the lifetime of cookies at 400 days anyway.
+const KNOWN_DEVICES_LIFETIME: i64 = 400 * 24 * 3600;
+/// Upper bound for remembered users per browser to keep the cookie small.
+const KNOWN_DEVICES_MAX_USERS: usize = 10;
+
+/// Security relevant changes to a user account, which the user will be notified about via
+/// E-Mail, if `SECURITY_NOTIFICATIONS` is enabled.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SecurityNotification {
+    NewLogin {
+        ip: String,
+        country: Option<String>,
+        device: String,
+    },
+    /// Contains the name of the passkey
+    PasskeyAdded(String),
+    /// Contains the name of the passkey
+    PasskeyRemoved(String),
+    PasswordChanged,
+    /// Contains the name of the upstream auth provider
+    ProviderLinked(String),
+}
+
+impl SecurityNotification {
+    #[inline]
+    pub fn is_enabled() -> bool {
+        *SECURITY_NOTIFICATIONS
+    }
+
+    /// Sends out the notification together with a "this wasn't me" link. Errors are only logged,
+    /// because a failed notification must never fail the action it informs about.
+    pub async fn send(&self, data: &web::Data<AppState>, user: &User) {
+        if !Self::is_enabled() {
+            return;
+        }
+
+        match MagicLink::create(user.id.clone(), NOT_ME_LINK_LIFETIME, MagicLinkUsage::NotMe).await
+        {
+            Ok(ml) => email::send_security_notification(data, &ml, user, self).await,
+            Err(err) => {
+                error!(
+                    "Error creating the magic link for a security notification for user '{}': {:?}",
+                    user.email, err
+                );
+            }
+        }
+    }
+
+    /// Checks the known devices cookie after a successful login. Sends out a `NewLogin`
+    /// notification, if the user has never logged in with this browser before, and returns the
+    /// updated cookie, which must be added to the response.
+    pub async fn check_new_login(
+        data: &web::Data<AppState>,
+        req: &HttpRequest,
+        user: &User,
+    ) -> Option<Cookie<'static>> {
+        if !Self::is_enabled() {
+            return None;
+        }
+
+        let cookie = ApiCookie::from_req(req, COOKIE_KNOWN_DEVICES);
+        let (is_known, value) = known_devices_update(cookie.as_deref(), &user.id);
+
+        if !is_known {
+            let ip = real_ip_from_req(req).ok();
+            let user_agent = req
+                .headers()
+                .get(header::USER_AGENT)
+                .and_then(|v| v.to_str().ok())
+                .unwrap_or_default();
+
+            let notification = Self::NewLogin {
+                ip: ip
+                    .map(|ip| ip.to_string())
+                    .unwrap_or_else(|| "UNKNOWN".to_string()),
+                country: ip.and_then(geoip::lookup).and_then(|l| l.country),
+                device: trusted_devices::device_name(user_agent),
+            };
+            notification.send(data, user).await;
+        }
+
+        Some(ApiCookie::build(
+            COOKIE_KNOWN_DEVICES,
+            value,
+            KNOWN_DEVICES_LIFETIME,
+        ))
+    }
+}
+
+/// Returns if the user is already known from the current known devices cookie value, together
+/// with the updated value, which always contains the user as the most recent one at the end.
+fn known_devices_update(cookie: Option<&str>, user_id: &str) -> (bool, String) {
+    let mut user_ids = cookie
+        .map(|value| {
+            value
+                .split(',')
+                .filter(|id| !id.is_empty())
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+
+    let is_known = match user_ids.iter().position(|id| *id == user_id) {
+        Some(pos) => {
+            user_ids.remove(pos);
+            true
+        }
+        None => false,
+    };
+
+    user_ids.push(user_id);
+    if user_ids.len() > KNOWN_DEVICES_MAX_USERS {
+        user_ids.drain(..user_ids.len() - KNOWN_DEVICES_MAX_USERS);
+    }
+
+    (is_known, user_ids.join(","))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_known_devices_update() {
+        // a browser without any cookie is always new
+        let (is_known, value) = known_devices_update(None, "user1");
+        assert!(!is_known);
+        assert_eq!(value, "user1");
+
+        let (is_known, value) = known_devices_update(Some(""), "user1");
+        assert!(!is_known);
+        assert_eq!(value, "user1");
+
+        let (is_known, value) = known_devices_update(Some("user1"), "user2");
+        assert!(!is_known);
+        assert_eq!(value, "user1,user2");
+
+        // a known user is moved to the end
+        let (is_known, value) = known_devices_update(Some("user1,user2,user3"), "user1");
+        assert!(is_known);
+        assert_eq!(value, "user2,user3,user1");
+
+        // only exact ids must match
+        let (is_known, _) = known_devices_update(Some("user10,user11"), "user1");
+        assert!(!is_known);
+
+        // the oldest users are dropped after the limit
+        let ids = (0..KNOWN_DEVICES_MAX_USERS)
+            .map(|i| format!("user{}", i))
+            .collect::<Vec<_>>()
+            .join(",");
+        let (is_known, value) = known_devices_update(Some(&ids), "new");
+        assert!(!is_known);
+        let value = value.split(',').collect::<Vec<_>>();
+        assert_eq!(value.len(), KNOWN_DEVICES_MAX_USERS);
+        assert_eq!(value.first(), Some(&"user1"));
+        assert_eq!(value.last(), Some(&"new"));
+    }
+}
@@ -7,8 +7,10 @@ use crate::i18n::email_confirm_change_html::I18nEmailConfirmChangeHtml;
 use crate::i18n::error::I18nError;
 use crate::i18n::index::I18nIndex;
 use crate::i18n::logout::I18nLogout;
+use crate::i18n::not_me::I18nNotMe;
 use crate::i18n::password_reset::I18nPasswordReset;
 use crate::i18n::register::I18nRegister;
+use crate::i18n::unlock::I18nUnlock;
 use crate::i18n::SsrJson;
 use crate::language::Language;
 use actix_web::http::StatusCode;
@@ -1442,6 +1444,29 @@ impl TooManyRequestsHtml {
     }
 }
 
+/// Auto-submitting form for the SAML `HTTP-POST` binding
+#[derive(Default, Template)]
+#[template(path = "saml/post_binding.html")]
+pub struct SamlPostBindingHtml<'a> {
+    pub action: &'a str,
+    pub param_name: &'a str,
+    pub value: &'a str,
+    pub relay_state: Option<&'a str>,
+}
+
+impl SamlPostBindingHtml<'_> {
+    pub fn build(action: &str, param_name: &str, value: &str, relay_state: Option<&str>) -> String {
+        SamlPostBindingHtml {
+            action,
+            param_name,
+            value,
+            relay_state,
+        }
+        .render()
+        .unwrap()
+    }
+}
+
 #[derive(Default, Template)]
 #[template(path = "html/users/{id}/email_confirm/email_confirm.html")]
 pub struct UserEmailChangeConfirmHtml<'a> {
@@ -1494,6 +1519,106 @@ impl UserEmailChangeConfirmHtml<'_> {
     }
 }
 
+#[derive(Default, Template)]
+#[template(path = "html/users/{id}/not_me/not_me.html")]
+pub struct UserNotMeHtml<'a> {
+    pub lang: &'a str,
+    pub csrf_token: &'a str,
+    pub data: &'a str,
+    pub action: bool,
+    pub col_act1: &'a str,
+    pub col_act1a: &'a str,
+    pub col_act2: &'a str,
+    pub col_act2a: &'a str,
+    pub col_acnt: &'a str,
+    pub col_acnta: &'a str,
+    pub col_ok: &'a str,
+    pub col_err: &'a str,
+    pub col_glow: &'a str,
+    pub col_gmid: &'a str,
+    pub col_ghigh: &'a str,
+    pub col_text: &'a str,
+    pub col_bg: &'a str,
+    pub i18n: String,
+    pub auth_providers: &'a str,
+}
+
+impl UserNotMeHtml<'_> {
+    pub fn build(colors: &Colors, lang: &Language, csrf_token: &str) -> String {
+        UserNotMeHtml {
+            lang: lang.as_str(),
+            csrf_token,
+            col_act1: &colors.act1,
+            col_act1a: &colors.act1a,
+            col_act2: &colors.act2,
+            col_act2a: &colors.act2a,
+            col_acnt: &colors.acnt,
+            col_acnta: &colors.acnta,
+            col_ok: &colors.ok,
+            col_err: &colors.err,
+            col_glow: &colors.glow,
+            col_gmid: &colors.gmid,
+            col_ghigh: &colors.ghigh,
+            col_text: &colors.text,
+            col_bg: &colors.bg,
+            i18n: I18nNotMe::build(lang).as_json(),
+            ..Default::default()
+        }
+        .render()
+        .expect("rendering not_me.html")
+    }
+}
+
+#[derive(Default, Template)]
+#[template(path = "html/users/{id}/unlock/unlock.html")]
+pub struct UserUnlockHtml<'a> {
+    pub lang: &'a str,
+    pub csrf_token: &'a str,
+    pub data: &'a str,
+    pub action: bool,
+    pub col_act1: &'a str,
+    pub col_act1a: &'a str,
+    pub col_act2: &'a str,
+    pub col_act2a: &'a str,
+    pub col_acnt: &'a str,
+    pub col_acnta: &'a str,
+    pub col_ok: &'a str,
+    pub col_err: &'a str,
+    pub col_glow: &'a str,
+    pub col_gmid: &'a str,
+    pub col_ghigh: &'a str,
+    pub col_text: &'a str,
+    pub col_bg: &'a str,
+    pub i18n: String,
+    pub auth_providers: &'a str,
+}
+
+impl UserUnlockHtml<'_> {
+    pub fn build(colors: &Colors, lang: &Language, csrf_token: &str) -> String {
+        UserUnlockHtml {
+            lang: lang.as_str(),
+            csrf_token,
+            col_act1: &colors.act1,
+            col_act1a: &colors.act1a,
+            col_act2: &colors.act2,
+            col_act2a: &colors.act2a,
+            col_acnt: &colors.acnt,
+            col_acnta: &colors.acnta,
+            col_ok: &colors.ok,
+            col_err: &colors.err,
+            col_glow: &colors.glow,
+            col_gmid: &colors.gmid,
+            col_ghigh: &colors.ghigh,
+            col_text: &colors.text,
+            col_bg: &colors.bg,
+            i18n: I18nUnlock::build(lang).as_json(),
+            ..Default::default()
+        }
+        .render()
+        .expect("rendering unlock.html")
+    }
+}
+
 #[derive(Default, Template)]
 #[template(path = "html/users/register.html")]
 pub struct UserRegisterHtml<'a> {
@@ -0,0 +1,32 @@
+use rauthy_models::database::DB;
+use rauthy_models::entity::ip_blacklist::IpBlacklistEntry;
+use std::time::Duration;
+use tracing::{debug, error};
+
+/// Cleans up expired IP blacklist entries. They are ignored anyway, this just keeps the
+/// database clean. Runs every hour.
+pub async fn ip_blacklist_cleanup() {
+    let mut interval = tokio::time::interval(Duration::from_secs(3600));
+
+    loop {
+        interval.tick().await;
+
+        if !DB::client().is_leader_cache().await {
+            debug!(
+                "Running HA mode without being the leader - skipping ip_blacklist_cleanup scheduler"
+            );
+            continue;
+        }
+
+        debug!("Running ip_blacklist_cleanup scheduler");
+
+        match IpBlacklistEntry::delete_expired().await {
+            Ok(rows_affected) => {
+                debug!("Cleaned up {} expired IP blacklist entries", rows_affected);
+            }
+            Err(err) => {
+                error!("ip_blacklist_cleanup error: {:?}", err);
+            }
+        }
+    }
+}
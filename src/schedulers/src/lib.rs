@@ -8,11 +8,13 @@ mod app_version;
 mod devices;
 mod dyn_clients;
 mod events;
+mod ip_blacklist;
 mod jwks;
 mod magic_links;
 mod passwords;
 mod sessions;
 mod tokens;
+mod trusted_devices;
 mod users;
 
 /// Spawn all Rauthy schedulers and periodic tasks
@@ -22,9 +24,11 @@ pub async fn spawn(data: web::Data<AppState>) {
     tokio::spawn(dyn_clients::dyn_client_cleanup());
     tokio::spawn(events::events_cleanup());
     tokio::spawn(devices::devices_cleanup());
+    tokio::spawn(ip_blacklist::ip_blacklist_cleanup());
     tokio::spawn(magic_links::magic_link_cleanup());
     tokio::spawn(tokens::refresh_tokens_cleanup());
     tokio::spawn(sessions::sessions_cleanup());
+    tokio::spawn(trusted_devices::trusted_devices_cleanup());
     tokio::spawn(jwks::jwks_auto_rotate(data.clone()));
     tokio::spawn(jwks::jwks_cleanup());
     tokio::spawn(passwords::password_expiry_checker(data.clone()));
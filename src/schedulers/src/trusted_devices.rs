@@ -0,0 +1,32 @@
+use rauthy_models::database::DB;
+use rauthy_models::entity::trusted_devices::TrustedDevice;
+use std::time::Duration;
+use tracing::{debug, error};
+
+/// Cleans up expired trusted devices. They are ignored during the login anyway, this just
+/// keeps the database clean. Runs every 6 hours.
+pub async fn trusted_devices_cleanup() {
+    let mut interval = tokio::time::interval(Duration::from_secs(6 * 3600));
+
+    loop {
+        interval.tick().await;
+
+        if !DB::client().is_leader_cache().await {
+            debug!(
+                "Running HA mode without being the leader - skipping trusted_devices_cleanup scheduler"
+            );
+            continue;
+        }
+
+        debug!("Running trusted_devices_cleanup scheduler");
+
+        match TrustedDevice::delete_expired().await {
+            Ok(rows_affected) => {
+                debug!("Cleaned up {} expired trusted devices", rows_affected);
+            }
+            Err(err) => {
+                error!("trusted_devices_cleanup error: {:?}", err);
+            }
+        }
+    }
+}
@@ -0,0 +1,257 @@
+use actix_web::web;
+use chrono::{TimeDelta, Utc};
+use rauthy_common::constants::{
+    BruteForceAction, BRUTE_FORCE_ACCOUNT_ACTION, BRUTE_FORCE_ACCOUNT_THRESHOLD,
+    BRUTE_FORCE_ACTION_DURATION, BRUTE_FORCE_DETECTION, BRUTE_FORCE_IP_ACTION,
+    BRUTE_FORCE_IP_THRESHOLD, BRUTE_FORCE_WINDOW, IDX_BRUTE_FORCE_ACCOUNT,
+    IDX_BRUTE_FORCE_DETECTED, IDX_BRUTE_FORCE_IP, IDX_BRUTE_FORCE_LOCK, IDX_BRUTE_FORCE_POW,
+};
+use rauthy_error::{ErrorResponse, ErrorResponseType};
+use rauthy_models::app_state::AppState;
+use rauthy_models::database::{Cache, DB};
+use rauthy_models::entity::pow::PowEntity;
+use rauthy_models::events::event::Event;
+use serde::{Deserialize, Serialize};
+use std::net::IpAddr;
+use tracing::{error, warn};
+
+/// Upper limit for tracked entries per account or IP to keep the cache values small, even
+/// under a heavy attack. The thresholds are way below this value anyway.
+const MAX_ENTRIES: usize = 256;
+
+/// Failed logins inside the detection window. For an account, the entries are the source IPs,
+/// and for an IP the targeted accounts, each with the timestamp of the latest failure.
+///
+/// The values live in the replicated cache, which makes the detection work across all nodes.
+/// Concurrent updates from different nodes may lose a single entry, which is fine for a
+/// threshold based detection.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct FailedLogins(Vec<(String, i64)>);
+
+impl FailedLogins {
+    /// Adds a failure and returns the amount of distinct values inside the window.
+    fn add(&mut self, value: String, now: i64, window: i64) -> usize {
+        self.0.retain(|(_, ts)| *ts > now - window);
+
+        if let Some(entry) = self.0.iter_mut().find(|(v, _)| v == &value) {
+            entry.1 = now;
+        } else {
+            if self.0.len() >= MAX_ENTRIES {
+                self.0.remove(0);
+            }
+            self.0.push((value, now));
+        }
+
+        self.0.len()
+    }
+}
+
+/// Rejects logins for temporarily locked accounts and enforces a solved PoW, if a brute-force
+/// attack against this account or from this IP has been detected.
+pub async fn check_login(email: &str, ip: IpAddr, pow: Option<&str>) -> Result<(), ErrorResponse> {
+    if !*BRUTE_FORCE_DETECTION {
+        return Ok(());
+    }
+
+    check_account_lock(email).await?;
+
+    let client = DB::client();
+    let email = email.to_lowercase();
+    let pow_account: Option<bool> = client
+        .get(Cache::App, format!("{}{}", IDX_BRUTE_FORCE_POW, email))
+        .await?;
+    let pow_ip: Option<bool> = client
+        .get(Cache::App, format!("{}{}", IDX_BRUTE_FORCE_POW, ip))
+        .await?;
+    if pow_account.is_none() && pow_ip.is_none() {
+        return Ok(());
+    }
+
+    match pow {
+        Some(pow) => PowEntity::validate(pow).await,
+        None => Err(ErrorResponse::new(
+            ErrorResponseType::PowRequired,
+            "A Proof of Work is required for this login",
+        )),
+    }
+}
+
+/// Rejects logins for temporarily locked accounts. Logins without a UI, like the `password`
+/// grant or LDAP binds, can't solve a PoW and only check for an account lock.
+pub async fn check_account_lock(email: &str) -> Result<(), ErrorResponse> {
+    if !*BRUTE_FORCE_DETECTION {
+        return Ok(());
+    }
+
+    let idx = format!("{}{}", IDX_BRUTE_FORCE_LOCK, email.to_lowercase());
+    let locked_until: Option<i64> = DB::client().get(Cache::App, idx).await?;
+    match locked_until {
+        Some(exp) if exp > Utc::now().timestamp() => Err(ErrorResponse::new(
+            ErrorResponseType::TooManyRequests(exp),
+            "This account is temporarily locked",
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// Removes a temporary lock from a detected brute-force attack for this account.
+pub async fn unlock_account(email: &str) -> Result<(), ErrorResponse> {
+    let idx = format!("{}{}", IDX_BRUTE_FORCE_LOCK, email.to_lowercase());
+    DB::client().delete(Cache::App, idx).await?;
+    Ok(())
+}
+
+/// Tracks a failed login across all nodes and applies the configured actions, when either
+/// too many IPs target a single account, or a single IP targets too many accounts.
+pub async fn on_failed_login(data: &web::Data<AppState>, ip: IpAddr, email: &str) {
+    if !*BRUTE_FORCE_DETECTION {
+        return;
+    }
+
+    if let Err(err) = detect(data, ip, &email.to_lowercase()).await {
+        error!("Error during brute-force detection: {:?}", err);
+    }
+}
+
+async fn detect(data: &web::Data<AppState>, ip: IpAddr, email: &str) -> Result<(), ErrorResponse> {
+    let ip = ip.to_string();
+    let duration = *BRUTE_FORCE_ACTION_DURATION as i64;
+
+    let ips = track(IDX_BRUTE_FORCE_ACCOUNT, email, ip.clone()).await?;
+    if ips >= *BRUTE_FORCE_ACCOUNT_THRESHOLD && is_new_detection(email).await? {
+        warn!(
+            "Possible brute-force attack on account '{}' from {} IPs",
+            email, ips
+        );
+
+        match *BRUTE_FORCE_ACCOUNT_ACTION {
+            BruteForceAction::Pow => require_pow(email).await?,
+            BruteForceAction::Lock => {
+                let exp = Utc::now().timestamp() + duration;
+                DB::client()
+                    .put(
+                        Cache::App,
+                        format!("{}{}", IDX_BRUTE_FORCE_LOCK, email),
+                        &exp,
+                        Some(duration),
+                    )
+                    .await?;
+            }
+            BruteForceAction::None | BruteForceAction::Blacklist => {}
+        }
+
+        data.tx_events
+            .send_async(Event::brute_force(
+                ip.clone(),
+                format!("Account `{}` targeted from {} IPs", email, ips),
+            ))
+            .await
+            .unwrap();
+    }
+
+    let accounts = track(IDX_BRUTE_FORCE_IP, &ip, email.to_string()).await?;
+    if accounts >= *BRUTE_FORCE_IP_THRESHOLD && is_new_detection(&ip).await? {
+        warn!(
+            "Possible credential stuffing from IP '{}' against {} accounts",
+            ip, accounts
+        );
+
+        match *BRUTE_FORCE_IP_ACTION {
+            BruteForceAction::Pow => require_pow(&ip).await?,
+            BruteForceAction::Blacklist => {
+                let exp = Utc::now() + TimeDelta::seconds(duration);
+                data.tx_events
+                    .send_async(Event::ip_blacklisted(
+                        Some(exp),
+                        ip.clone(),
+                        Some("Credential stuffing".to_string()),
+                    ))
+                    .await
+                    .unwrap();
+            }
+            BruteForceAction::None | BruteForceAction::Lock => {}
+        }
+
+        data.tx_events
+            .send_async(Event::brute_force(
+                ip,
+                format!("Logins for {} different accounts", accounts),
+            ))
+            .await
+            .unwrap();
+    }
+
+    Ok(())
+}
+
+async fn track(idx_prefix: &str, key: &str, value: String) -> Result<usize, ErrorResponse> {
+    let window = *BRUTE_FORCE_WINDOW as i64;
+    let idx = format!("{}{}", idx_prefix, key);
+    let client = DB::client();
+
+    let mut logins: FailedLogins = client.get(Cache::App, &idx).await?.unwrap_or_default();
+    let count = logins.add(value, Utc::now().timestamp(), window);
+    client.put(Cache::App, idx, &logins, Some(window)).await?;
+
+    Ok(count)
+}
+
+/// Makes sure that actions and events are only triggered once per action duration.
+async fn is_new_detection(key: &str) -> Result<bool, ErrorResponse> {
+    let idx = format!("{}{}", IDX_BRUTE_FORCE_DETECTED, key);
+    let client = DB::client();
+
+    let detected: Option<bool> = client.get(Cache::App, &idx).await?;
+    if detected.is_some() {
+        return Ok(false);
+    }
+
+    client
+        .put(
+            Cache::App,
+            idx,
+            &true,
+            Some(*BRUTE_FORCE_ACTION_DURATION as i64),
+        )
+        .await?;
+    Ok(true)
+}
+
+async fn require_pow(key: &str) -> Result<(), ErrorResponse> {
+    DB::client()
+        .put(
+            Cache::App,
+            format!("{}{}", IDX_BRUTE_FORCE_POW, key),
+            &true,
+            Some(*BRUTE_FORCE_ACTION_DURATION as i64),
+        )
+        .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_failed_logins_window() {
+        let mut logins = FailedLogins::default();
+        assert_eq!(logins.add("10.0.0.1".to_string(), 100, 60), 1);
+        assert_eq!(logins.add("10.0.0.2".to_string(), 110, 60), 2);
+        // the same value only updates the timestamp
+        assert_eq!(logins.add("10.0.0.1".to_string(), 120, 60), 2);
+        // 10.0.0.2 is outside the window now
+        assert_eq!(logins.add("10.0.0.3".to_string(), 175, 60), 2);
+        assert_eq!(
+            logins.0,
+            vec![("10.0.0.1".to_string(), 120), ("10.0.0.3".to_string(), 175)]
+        );
+
+        let mut logins = FailedLogins::default();
+        for i in 0..MAX_ENTRIES + 10 {
+            logins.add(format!("user{}@example.com", i), 100, 60);
+        }
+        assert_eq!(logins.0.len(), MAX_ENTRIES);
+        assert_eq!(logins.0[0].0, "user10@example.com");
+    }
+}
@@ -1,5 +1,6 @@
 use rauthy_api_types::clients::{ClientSecretResponse, UpdateClientRequest};
 use rauthy_error::{ErrorResponse, ErrorResponseType};
+use rauthy_models::entity::auth_providers::AuthProvider;
 use rauthy_models::entity::clients::Client;
 
 pub async fn update_client(
@@ -50,10 +51,81 @@ pub async fn update_client(
     client.contacts = client_req.contacts.map(|c| c.join(","));
     client.client_uri = client_req.client_uri;
 
+    validate_login_methods(
+        &client_req.allowed_providers,
+        &client_req.auto_redirect_provider,
+    )
+    .await?;
+    client.allowed_providers = client_req.allowed_providers.map(|p| p.join(","));
+    client.allow_password_login = client_req.allow_password_login.unwrap_or(true);
+    client.passkey_only = client_req.passkey_only;
+    client.auto_redirect_provider = client_req.auto_redirect_provider;
+
+    client.access_roles = join_non_empty(client_req.access_roles);
+    client.access_groups = join_non_empty(client_req.access_groups);
+    client.access_email_domains = join_non_empty(client_req.access_email_domains);
+    client.geo_allow_countries = join_non_empty(
+        client_req
+            .geo_allow_countries
+            .map(|c| c.into_iter().map(|c| c.to_uppercase()).collect()),
+    );
+    client.geo_deny_countries = join_non_empty(
+        client_req
+            .geo_deny_countries
+            .map(|c| c.into_iter().map(|c| c.to_uppercase()).collect()),
+    );
+
     client.save().await?;
     Ok(client)
 }
 
+fn join_non_empty(values: Option<Vec<String>>) -> Option<String> {
+    values.filter(|v| !v.is_empty()).map(|v| v.join(","))
+}
+
+/// Makes sure that all given auth providers exist and that the `auto_redirect_provider`
+/// is one of the allowed ones.
+async fn validate_login_methods(
+    allowed_providers: &Option<Vec<String>>,
+    auto_redirect_provider: &Option<String>,
+) -> Result<(), ErrorResponse> {
+    if allowed_providers.is_none() && auto_redirect_provider.is_none() {
+        return Ok(());
+    }
+
+    let providers = AuthProvider::find_all().await?;
+    if let Some(ids) = allowed_providers {
+        if let Some(id) = ids
+            .iter()
+            .find(|id| !providers.iter().any(|p| &p.id == *id))
+        {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                format!("Auth provider '{}' does not exist", id),
+            ));
+        }
+    }
+
+    if let Some(id) = auto_redirect_provider {
+        if !providers.iter().any(|p| &p.id == id) {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                format!("Auth provider '{}' does not exist", id),
+            ));
+        }
+        if let Some(ids) = allowed_providers {
+            if !ids.contains(id) {
+                return Err(ErrorResponse::new(
+                    ErrorResponseType::BadRequest,
+                    "The 'auto_redirect_provider' must be one of the 'allowed_providers'",
+                ));
+            }
+        }
+    }
+
+    Ok(())
+}
+
 /// Returns the clients secret in cleartext.
 pub async fn get_client_secret(id: String) -> Result<ClientSecretResponse, ErrorResponse> {
     let client = Client::find(id).await?;
@@ -0,0 +1,217 @@
+// A minimal BER codec for the subset of ASN.1, which is used by LDAPv3 (RFC 4511).
+// LDAP only uses single byte tags and definite lengths, everything else will be rejected.
+
+use rauthy_error::{ErrorResponse, ErrorResponseType};
+
+pub const TAG_BOOLEAN: u8 = 0x01;
+pub const TAG_INTEGER: u8 = 0x02;
+pub const TAG_OCTET_STRING: u8 = 0x04;
+pub const TAG_ENUMERATED: u8 = 0x0a;
+pub const TAG_SEQUENCE: u8 = 0x30;
+pub const TAG_SET: u8 = 0x31;
+
+#[inline]
+pub fn err(msg: &'static str) -> ErrorResponse {
+    ErrorResponse::new(ErrorResponseType::BadRequest, msg)
+}
+
+/// Returns the full length of the first TLV inside `buf` as soon as its header is complete.
+pub fn tlv_len(buf: &[u8]) -> Result<Option<usize>, ErrorResponse> {
+    if buf.len() < 2 {
+        return Ok(None);
+    }
+    if buf[0] & 0x1f == 0x1f {
+        return Err(err("multi byte BER tags are not supported"));
+    }
+
+    let first = buf[1];
+    if first & 0x80 == 0 {
+        return Ok(Some(2 + first as usize));
+    }
+
+    let n = (first & 0x7f) as usize;
+    if n == 0 || n > 4 {
+        return Err(err("unsupported BER length"));
+    }
+    if buf.len() < 2 + n {
+        return Ok(None);
+    }
+    let len = buf[2..2 + n]
+        .iter()
+        .fold(0usize, |acc, b| (acc << 8) | *b as usize);
+    Ok(Some(2 + n + len))
+}
+
+pub struct Reader<'a> {
+    buf: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    pub fn new(buf: &'a [u8]) -> Self {
+        Self { buf }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.buf.is_empty()
+    }
+
+    pub fn read(&mut self) -> Result<(u8, &'a [u8]), ErrorResponse> {
+        let len = tlv_len(self.buf)?.ok_or_else(|| err("truncated BER value"))?;
+        if len > self.buf.len() {
+            return Err(err("truncated BER value"));
+        }
+
+        let header = if self.buf[1] & 0x80 == 0 {
+            2
+        } else {
+            2 + (self.buf[1] & 0x7f) as usize
+        };
+        let tag = self.buf[0];
+        let value = &self.buf[header..len];
+        self.buf = &self.buf[len..];
+
+        Ok((tag, value))
+    }
+
+    pub fn read_tag(&mut self, tag: u8) -> Result<&'a [u8], ErrorResponse> {
+        let (t, value) = self.read()?;
+        if t != tag {
+            return Err(err("unexpected BER tag"));
+        }
+        Ok(value)
+    }
+
+    pub fn read_bool(&mut self) -> Result<bool, ErrorResponse> {
+        let value = self.read_tag(TAG_BOOLEAN)?;
+        match value {
+            [b] => Ok(*b != 0),
+            _ => Err(err("invalid BER boolean")),
+        }
+    }
+
+    pub fn read_int(&mut self, tag: u8) -> Result<i64, ErrorResponse> {
+        decode_int(self.read_tag(tag)?)
+    }
+
+    pub fn read_string(&mut self, tag: u8) -> Result<String, ErrorResponse> {
+        String::from_utf8(self.read_tag(tag)?.to_vec()).map_err(|_| err("invalid UTF-8 string"))
+    }
+}
+
+#[derive(Default)]
+pub struct Writer {
+    buf: Vec<u8>,
+}
+
+impl Writer {
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.buf
+    }
+
+    pub fn write(&mut self, tag: u8, value: &[u8]) {
+        self.buf.push(tag);
+        let len = value.len();
+        if len < 0x80 {
+            self.buf.push(len as u8);
+        } else {
+            let bytes = (len as u64).to_be_bytes();
+            let skip = bytes.iter().take_while(|b| **b == 0).count();
+            self.buf.push(0x80 | (bytes.len() - skip) as u8);
+            self.buf.extend_from_slice(&bytes[skip..]);
+        }
+        self.buf.extend_from_slice(value);
+    }
+
+    pub fn write_bool(&mut self, value: bool) {
+        self.write(TAG_BOOLEAN, &[if value { 0xff } else { 0 }]);
+    }
+
+    pub fn write_int(&mut self, tag: u8, value: i64) {
+        self.write(tag, &encode_int(value));
+    }
+
+    pub fn write_str(&mut self, tag: u8, value: &str) {
+        self.write(tag, value.as_bytes());
+    }
+
+    pub fn write_constructed<F>(&mut self, tag: u8, f: F)
+    where
+        F: FnOnce(&mut Writer),
+    {
+        let mut inner = Writer::default();
+        f(&mut inner);
+        self.write(tag, &inner.buf);
+    }
+}
+
+fn decode_int(bytes: &[u8]) -> Result<i64, ErrorResponse> {
+    if bytes.is_empty() || bytes.len() > 8 {
+        return Err(err("invalid BER integer"));
+    }
+
+    let init: i64 = if bytes[0] & 0x80 != 0 { -1 } else { 0 };
+    Ok(bytes.iter().fold(init, |acc, b| (acc << 8) | *b as i64))
+}
+
+fn encode_int(value: i64) -> Vec<u8> {
+    let bytes = value.to_be_bytes();
+    let mut start = 0;
+    // strip all redundant leading bytes while keeping the sign bit intact
+    while start < bytes.len() - 1 {
+        let next_negative = bytes[start + 1] & 0x80 != 0;
+        match bytes[start] {
+            0x00 if !next_negative => start += 1,
+            0xff if next_negative => start += 1,
+            _ => break,
+        }
+    }
+    bytes[start..].to_vec()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ber_roundtrip() {
+        for value in [
+            0,
+            1,
+            127,
+            128,
+            255,
+            256,
+            -1,
+            -128,
+            -129,
+            i32::MAX as i64,
+            i64::MIN,
+        ] {
+            let mut w = Writer::default();
+            w.write_int(TAG_INTEGER, value);
+            let bytes = w.into_bytes();
+            assert_eq!(Reader::new(&bytes).read_int(TAG_INTEGER).unwrap(), value);
+        }
+
+        let long = "a".repeat(300);
+        let mut w = Writer::default();
+        w.write_constructed(TAG_SEQUENCE, |w| {
+            w.write_str(TAG_OCTET_STRING, &long);
+            w.write_bool(true);
+        });
+        let bytes = w.into_bytes();
+        assert_eq!(&bytes[..4], &[TAG_SEQUENCE, 0x82, 0x01, 0x33]);
+        assert_eq!(tlv_len(&bytes[..3]).unwrap(), None);
+        assert_eq!(tlv_len(&bytes).unwrap(), Some(bytes.len()));
+
+        let mut r = Reader::new(&bytes);
+        let mut seq = Reader::new(r.read_tag(TAG_SEQUENCE).unwrap());
+        assert!(r.is_empty());
+        assert_eq!(seq.read_string(TAG_OCTET_STRING).unwrap(), long);
+        assert!(seq.read_bool().unwrap());
+        assert!(seq.is_empty());
+
+        // truncated values must never panic
+        assert!(Reader::new(&bytes[..bytes.len() - 1]).read().is_err());
+    }
+}
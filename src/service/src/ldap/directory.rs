@@ -0,0 +1,585 @@
+use crate::ldap::proto::{Filter, ResultCode, SearchRequest, SearchScope, OID_WHO_AM_I};
+use chrono::Utc;
+use rauthy_common::constants::{LDAP_BASE_DN, LDAP_CACHE_LIFETIME, RAUTHY_VERSION};
+use rauthy_error::ErrorResponse;
+use rauthy_models::entity::groups::Group;
+use rauthy_models::entity::users::User;
+use std::cmp::Ordering;
+use std::sync::{Arc, PoisonError, RwLock};
+
+/// Attributes, which contain DNs and must be compared in their normalized form.
+const DN_ATTRS: [&str; 3] = ["member", "memberof", "entrydn"];
+
+/// The last built directory together with its creation timestamp. Building it needs all users
+/// and groups, which is way too expensive to do for each single search.
+static DIRECTORY: RwLock<Option<(i64, Arc<Directory>)>> = RwLock::new(None);
+
+/// The static structure of the read-only directory tree:
+///
+/// ```text
+/// <base dn>
+/// ├── ou=users    -> uid=<email>,ou=users,<base dn>
+/// └── ou=groups   -> cn=<name>,ou=groups,<base dn>
+/// ```
+#[derive(Debug)]
+pub struct LdapTree {
+    pub base_dn: String,
+    pub users_dn: String,
+    pub groups_dn: String,
+    users_ndn: String,
+}
+
+impl LdapTree {
+    pub fn from_env() -> Self {
+        Self::new(&LDAP_BASE_DN)
+    }
+
+    pub fn new(base_dn: &str) -> Self {
+        let base_dn = base_dn.trim().to_string();
+        let users_dn = format!("ou=users,{}", base_dn);
+        let groups_dn = format!("ou=groups,{}", base_dn);
+
+        Self {
+            users_ndn: normalize_dn(&users_dn),
+            base_dn,
+            users_dn,
+            groups_dn,
+        }
+    }
+
+    pub fn user_dn(&self, email: &str) -> String {
+        format!("uid={},{}", escape_dn_value(email), self.users_dn)
+    }
+
+    pub fn group_dn(&self, name: &str) -> String {
+        format!("cn={},{}", escape_dn_value(name), self.groups_dn)
+    }
+
+    /// Resolves the login E-Mail from a bind DN. Apart from a full DN like
+    /// `uid=<email>,ou=users,<base dn>`, the plain E-Mail is accepted as well, which many clients
+    /// use like an Active Directory UPN.
+    pub fn bind_email(&self, name: &str) -> Option<String> {
+        let name = name.trim();
+        if !name.contains('=') {
+            return name.contains('@').then(|| name.to_lowercase());
+        }
+
+        let ndn = normalize_dn(name);
+        let (rdn, parent) = split_rdn(&ndn);
+        if parent != Some(self.users_ndn.as_str()) {
+            return None;
+        }
+        match rdn.split_once('=') {
+            Some(("uid", email)) | Some(("mail", email)) => Some(unescape_dn_value(email)),
+            _ => None,
+        }
+    }
+
+    /// The Root DSE, which clients use to discover the naming context.
+    pub fn root_dse(&self) -> Entry {
+        Entry::new(
+            String::default(),
+            vec![
+                ("objectClass", vec!["top".to_string()]),
+                ("namingContexts", vec![self.base_dn.clone()]),
+                ("supportedLDAPVersion", vec!["3".to_string()]),
+                ("supportedExtension", vec![OID_WHO_AM_I.to_string()]),
+                ("vendorName", vec!["Rauthy".to_string()]),
+                ("vendorVersion", vec![RAUTHY_VERSION.to_string()]),
+            ],
+        )
+    }
+}
+
+#[derive(Debug)]
+pub struct Entry {
+    pub dn: String,
+    ndn: String,
+    pub attrs: Vec<(&'static str, Vec<String>)>,
+}
+
+impl Entry {
+    fn new(dn: String, attrs: Vec<(&'static str, Vec<String>)>) -> Self {
+        Self {
+            ndn: normalize_dn(&dn),
+            dn,
+            attrs,
+        }
+    }
+
+    fn values(&self, attr: &str) -> Option<(&'static str, &[String])> {
+        self.attrs
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(attr))
+            .map(|(name, values)| (*name, values.as_slice()))
+    }
+
+    fn in_scope(&self, base_ndn: &str, scope: SearchScope) -> bool {
+        match scope {
+            SearchScope::Base => self.ndn == base_ndn,
+            SearchScope::One => split_rdn(&self.ndn).1 == Some(base_ndn),
+            SearchScope::Sub => {
+                self.ndn == base_ndn
+                    || self
+                        .ndn
+                        .strip_suffix(base_ndn)
+                        .is_some_and(|rest| rest.ends_with(','))
+            }
+        }
+    }
+
+    /// Returns the requested attributes. No attributes or `*` will return all of them and
+    /// `1.1` none at all (RFC 4511 4.5.1.8).
+    pub fn selected_attrs<'a>(
+        &'a self,
+        requested: &'a [String],
+    ) -> impl Iterator<Item = (&'a str, &'a [String])> + 'a {
+        let all = requested.is_empty() || requested.iter().any(|a| a == "*" || a == "+");
+        self.attrs
+            .iter()
+            .filter(move |(name, _)| all || requested.iter().any(|a| a.eq_ignore_ascii_case(name)))
+            .map(|(name, values)| (*name, values.as_slice()))
+    }
+
+    pub fn matches(&self, filter: &Filter) -> bool {
+        match filter {
+            Filter::And(filters) => filters.iter().all(|f| self.matches(f)),
+            Filter::Or(filters) => filters.iter().any(|f| self.matches(f)),
+            Filter::Not(filter) => !self.matches(filter),
+            Filter::Equal(attr, value) | Filter::Approx(attr, value) => {
+                self.compare(attr, value, |ord| ord == Ordering::Equal)
+            }
+            Filter::GreaterOrEqual(attr, value) => {
+                self.compare(attr, value, |ord| ord != Ordering::Less)
+            }
+            Filter::LessOrEqual(attr, value) => {
+                self.compare(attr, value, |ord| ord != Ordering::Greater)
+            }
+            Filter::Substrings {
+                attr,
+                initial,
+                any,
+                last,
+            } => self.values(attr).is_some_and(|(_, values)| {
+                values
+                    .iter()
+                    .any(|v| substrings_match(&v.to_lowercase(), initial, any, last))
+            }),
+            Filter::Present(attr) => self.values(attr).is_some(),
+            Filter::Undefined => false,
+        }
+    }
+
+    /// All values in this directory are compared case-insensitive.
+    fn compare<F>(&self, attr: &str, value: &str, f: F) -> bool
+    where
+        F: Fn(Ordering) -> bool,
+    {
+        let Some((name, values)) = self.values(attr) else {
+            return false;
+        };
+
+        let is_dn = DN_ATTRS.contains(&name.to_lowercase().as_str());
+        let value = if is_dn {
+            normalize_dn(value)
+        } else {
+            value.to_lowercase()
+        };
+        values.iter().any(|v| {
+            let v = if is_dn {
+                normalize_dn(v)
+            } else {
+                v.to_lowercase()
+            };
+            f(v.cmp(&value))
+        })
+    }
+}
+
+pub struct Directory {
+    entries: Vec<Entry>,
+}
+
+impl Directory {
+    /// Returns the cached directory, if it is younger than `LDAP_CACHE_LIFETIME`, or builds a
+    /// fresh one otherwise.
+    pub async fn load(tree: &LdapTree) -> Result<Arc<Self>, ErrorResponse> {
+        let now = Utc::now().timestamp();
+        let cached = DIRECTORY
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .as_ref()
+            .filter(|(created, _)| now - created < *LDAP_CACHE_LIFETIME as i64)
+            .map(|(_, dir)| dir.clone());
+        if let Some(dir) = cached {
+            return Ok(dir);
+        }
+
+        let users = User::find_all().await?;
+        let groups = Group::find_all().await?;
+        let dir = Arc::new(Self::build(tree, &users, &groups));
+        if *LDAP_CACHE_LIFETIME > 0 {
+            *DIRECTORY.write().unwrap_or_else(PoisonError::into_inner) = Some((now, dir.clone()));
+        }
+        Ok(dir)
+    }
+
+    fn build(tree: &LdapTree, users: &[User], groups: &[Group]) -> Self {
+        let mut entries = Vec::with_capacity(3 + users.len() + groups.len());
+
+        let base_rdn_value = split_rdn(&tree.base_dn)
+            .0
+            .split_once('=')
+            .map(|(_, v)| v.trim().to_string())
+            .unwrap_or_default();
+        entries.push(Entry::new(
+            tree.base_dn.clone(),
+            vec![
+                (
+                    "objectClass",
+                    vec![
+                        "top".to_string(),
+                        "dcObject".to_string(),
+                        "organization".to_string(),
+                    ],
+                ),
+                ("dc", vec![base_rdn_value.clone()]),
+                ("o", vec![base_rdn_value]),
+            ],
+        ));
+        for (ou, dn) in [("users", &tree.users_dn), ("groups", &tree.groups_dn)] {
+            entries.push(Entry::new(
+                dn.clone(),
+                vec![
+                    (
+                        "objectClass",
+                        vec!["top".to_string(), "organizationalUnit".to_string()],
+                    ),
+                    ("ou", vec![ou.to_string()]),
+                ],
+            ));
+        }
+
+        // disabled users are hidden from the directory, they can't log in anyway
+        let users = users.iter().filter(|u| u.enabled).collect::<Vec<_>>();
+
+        for user in &users {
+            let name = user.email_recipient_name();
+            let member_of = user
+                .get_groups()
+                .iter()
+                .filter(|g| !g.is_empty())
+                .map(|g| tree.group_dn(g))
+                .collect::<Vec<_>>();
+
+            let mut attrs = vec![
+                (
+                    "objectClass",
+                    vec![
+                        "top".to_string(),
+                        "person".to_string(),
+                        "organizationalPerson".to_string(),
+                        "inetOrgPerson".to_string(),
+                    ],
+                ),
+                ("uid", vec![user.email.clone()]),
+                ("mail", vec![user.email.clone()]),
+                ("cn", vec![name.clone()]),
+                ("displayName", vec![name]),
+                ("givenName", vec![user.given_name.clone()]),
+                // `sn` is mandatory for a `person`
+                (
+                    "sn",
+                    vec![user
+                        .family_name
+                        .clone()
+                        .unwrap_or_else(|| user.given_name.clone())],
+                ),
+                ("entryUUID", vec![user.id.clone()]),
+            ];
+            if !member_of.is_empty() {
+                attrs.push(("memberOf", member_of));
+            }
+
+            entries.push(Entry::new(tree.user_dn(&user.email), attrs));
+        }
+
+        for group in groups {
+            let members = users
+                .iter()
+                .filter(|u| u.get_groups().contains(&group.name))
+                .map(|u| tree.user_dn(&u.email))
+                .collect::<Vec<_>>();
+
+            let mut attrs = vec![
+                (
+                    "objectClass",
+                    vec!["top".to_string(), "groupOfNames".to_string()],
+                ),
+                ("cn", vec![group.name.clone()]),
+                ("entryUUID", vec![group.id.clone()]),
+            ];
+            if !members.is_empty() {
+                attrs.push(("member", members));
+            }
+
+            entries.push(Entry::new(tree.group_dn(&group.name), attrs));
+        }
+
+        Self { entries }
+    }
+
+    pub fn search(&self, req: &SearchRequest) -> Result<Vec<&Entry>, ResultCode> {
+        let base_ndn = normalize_dn(&req.base);
+        if !self.entries.iter().any(|e| e.ndn == base_ndn) {
+            return Err(ResultCode::NoSuchObject);
+        }
+
+        Ok(self
+            .entries
+            .iter()
+            .filter(|e| e.in_scope(&base_ndn, req.scope) && e.matches(&req.filter))
+            .collect())
+    }
+}
+
+fn substrings_match(
+    value: &str,
+    initial: &Option<String>,
+    any: &[String],
+    last: &Option<String>,
+) -> bool {
+    let mut rest = value;
+
+    if let Some(initial) = initial {
+        match rest.strip_prefix(initial.to_lowercase().as_str()) {
+            Some(r) => rest = r,
+            None => return false,
+        }
+    }
+
+    for sub in any {
+        let sub = sub.to_lowercase();
+        match rest.find(&sub) {
+            Some(idx) => rest = &rest[idx + sub.len()..],
+            None => return false,
+        }
+    }
+
+    match last {
+        Some(last) => rest.ends_with(&last.to_lowercase()),
+        None => true,
+    }
+}
+
+/// Splits off the first RDN at the first unescaped comma.
+fn split_rdn(dn: &str) -> (&str, Option<&str>) {
+    let mut escaped = false;
+    for (idx, c) in dn.char_indices() {
+        match c {
+            '\\' if !escaped => escaped = true,
+            ',' if !escaped => return (&dn[..idx], Some(&dn[idx + 1..])),
+            _ => escaped = false,
+        }
+    }
+    (dn, None)
+}
+
+/// Lower-cases a DN and removes any whitespace around its separators, so different
+/// spellings of the same DN can be compared directly.
+pub fn normalize_dn(dn: &str) -> String {
+    let mut res = String::with_capacity(dn.len());
+    let mut rest = Some(dn.trim());
+
+    while let Some(dn) = rest {
+        let (rdn, next) = split_rdn(dn);
+        if !res.is_empty() {
+            res.push(',');
+        }
+        match rdn.split_once('=') {
+            Some((attr, value)) => {
+                res.push_str(&attr.trim().to_lowercase());
+                res.push('=');
+                res.push_str(&value.trim().to_lowercase());
+            }
+            None => res.push_str(&rdn.trim().to_lowercase()),
+        }
+        rest = next;
+    }
+
+    res
+}
+
+fn escape_dn_value(value: &str) -> String {
+    let mut res = String::with_capacity(value.len());
+    for c in value.chars() {
+        if matches!(c, ',' | '+' | '"' | '\\' | '<' | '>' | ';' | '=') {
+            res.push('\\');
+        }
+        res.push(c);
+    }
+    res
+}
+
+fn unescape_dn_value(value: &str) -> String {
+    let mut res = String::with_capacity(value.len());
+    let mut escaped = false;
+    for c in value.chars() {
+        if c == '\\' && !escaped {
+            escaped = true;
+        } else {
+            res.push(c);
+            escaped = false;
+        }
+    }
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ldap_directory() {
+        let tree = LdapTree::new("dc=example,dc=com");
+        assert_eq!(
+            tree.bind_email("UID=Alice@example.com, ou=Users, dc=example, dc=com"),
+            Some("alice@example.com".to_string())
+        );
+        assert_eq!(
+            tree.bind_email("alice@example.com"),
+            Some("alice@example.com".to_string())
+        );
+        assert_eq!(tree.bind_email("cn=admin,dc=example,dc=com"), None);
+        assert_eq!(
+            tree.bind_email("uid=alice@example.com,ou=groups,dc=example,dc=com"),
+            None
+        );
+
+        let users = vec![
+            User {
+                id: "alice_id".to_string(),
+                email: "alice@example.com".to_string(),
+                given_name: "Alice".to_string(),
+                family_name: Some("Liddell".to_string()),
+                groups: Some("admins,users".to_string()),
+                enabled: true,
+                ..Default::default()
+            },
+            User {
+                id: "bob_id".to_string(),
+                email: "bob@example.com".to_string(),
+                given_name: "Bob".to_string(),
+                groups: Some("users".to_string()),
+                enabled: true,
+                ..Default::default()
+            },
+            User {
+                id: "eve_id".to_string(),
+                email: "eve@example.com".to_string(),
+                given_name: "Eve".to_string(),
+                groups: Some("admins".to_string()),
+                enabled: false,
+                ..Default::default()
+            },
+        ];
+        let groups = vec![
+            Group {
+                id: "admins_id".to_string(),
+                name: "admins".to_string(),
+            },
+            Group {
+                id: "users_id".to_string(),
+                name: "users".to_string(),
+            },
+        ];
+        let dir = Directory::build(&tree, &users, &groups);
+
+        let search = |base: &str, scope: SearchScope, filter: Filter| {
+            dir.search(&SearchRequest {
+                base: base.to_string(),
+                scope,
+                size_limit: 0,
+                types_only: false,
+                filter,
+                attrs: Vec::new(),
+            })
+            .map(|entries| entries.iter().map(|e| e.dn.clone()).collect::<Vec<_>>())
+        };
+
+        let res = search(
+            "dc=example,dc=com",
+            SearchScope::Sub,
+            Filter::Equal("uid".to_string(), "ALICE@example.com".to_string()),
+        )
+        .unwrap();
+        assert_eq!(
+            res,
+            vec!["uid=alice@example.com,ou=users,dc=example,dc=com"]
+        );
+
+        let res = search(
+            "ou=users,dc=example,dc=com",
+            SearchScope::One,
+            Filter::Equal(
+                "memberOf".to_string(),
+                "cn=admins, ou=groups, dc=example, dc=com".to_string(),
+            ),
+        )
+        .unwrap();
+        assert_eq!(
+            res,
+            vec!["uid=alice@example.com,ou=users,dc=example,dc=com"]
+        );
+
+        let res = search(
+            "dc=example,dc=com",
+            SearchScope::Sub,
+            Filter::And(vec![
+                Filter::Present("objectClass".to_string()),
+                Filter::Substrings {
+                    attr: "mail".to_string(),
+                    initial: None,
+                    any: Vec::new(),
+                    last: Some("@example.com".to_string()),
+                },
+            ]),
+        )
+        .unwrap();
+        assert_eq!(
+            res,
+            vec![
+                "uid=alice@example.com,ou=users,dc=example,dc=com",
+                "uid=bob@example.com,ou=users,dc=example,dc=com",
+            ]
+        );
+
+        let res = search(
+            "ou=groups,dc=example,dc=com",
+            SearchScope::Sub,
+            Filter::Equal(
+                "member".to_string(),
+                "uid=bob@example.com,ou=users,dc=example,dc=com".to_string(),
+            ),
+        )
+        .unwrap();
+        assert_eq!(res, vec!["cn=users,ou=groups,dc=example,dc=com"]);
+
+        let res = search(
+            "dc=example,dc=com",
+            SearchScope::Base,
+            Filter::Present("objectClass".to_string()),
+        )
+        .unwrap();
+        assert_eq!(res, vec!["dc=example,dc=com"]);
+
+        assert_eq!(
+            search(
+                "ou=unknown,dc=example,dc=com",
+                SearchScope::Sub,
+                Filter::Present("objectClass".to_string()),
+            ),
+            Err(ResultCode::NoSuchObject)
+        );
+    }
+}
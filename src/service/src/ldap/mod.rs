@@ -0,0 +1,524 @@
+use crate::ldap::directory::{Directory, LdapTree};
+use crate::ldap::proto::{
+    encode_entry, encode_extended, encode_result, LdapMessage, LdapOp, ResultCode, SearchRequest,
+    SearchScope, OID_NOTICE_OF_DISCONNECTION, OID_WHO_AM_I, OP_BIND_RESP, OP_SEARCH_DONE,
+};
+use crate::{brute_force, login_delay};
+use actix_web::{web, HttpResponse};
+use chrono::Utc;
+use rauthy_common::constants::{LDAP_ALLOW_MFA_USERS, LDAP_SEARCH_GROUP, LDAP_SIZE_LIMIT};
+use rauthy_common::password_hasher::HashPassword;
+use rauthy_error::{ErrorResponse, ErrorResponseType};
+use rauthy_models::app_state::AppState;
+use rauthy_models::entity::auth_providers::AuthProvider;
+use rauthy_models::entity::users::User;
+use rauthy_models::events::ip_blacklist_handler::{IpBlacklistCheck, IpBlacklistReq};
+use std::env;
+use std::net::IpAddr;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::TcpListener;
+use tokio::sync::oneshot;
+use tokio_rustls::rustls::ServerConfig;
+use tokio_rustls::TlsAcceptor;
+use tracing::{debug, error, info, warn};
+
+mod ber;
+mod directory;
+mod proto;
+
+/// Requests are tiny in LDAP. Anything bigger than this will close the connection.
+const MAX_MESSAGE_SIZE: usize = 128 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LdapListenScheme {
+    Ldap,
+    Ldaps,
+    LdapLdaps,
+}
+
+impl LdapListenScheme {
+    pub fn from_env() -> Self {
+        match env::var("LDAP_LISTEN_SCHEME")
+            .unwrap_or_else(|_| "ldap".to_string())
+            .as_str()
+        {
+            "ldap" => Self::Ldap,
+            "ldaps" => Self::Ldaps,
+            "ldap_ldaps" => Self::LdapLdaps,
+            s => panic!(
+                "Invalid LDAP_LISTEN_SCHEME '{}', allowed values: ldap | ldaps | ldap_ldaps",
+                s
+            ),
+        }
+    }
+
+    pub fn needs_tls(&self) -> bool {
+        matches!(self, Self::Ldaps | Self::LdapLdaps)
+    }
+}
+
+/// Runs the read-only LDAP facade. `tls` must be given for `ldaps` schemes.
+pub async fn run(
+    data: web::Data<AppState>,
+    scheme: LdapListenScheme,
+    tls: Option<ServerConfig>,
+) -> Result<(), ErrorResponse> {
+    let addr = env::var("LDAP_LISTEN_ADDRESS").ok();
+    let tree = Arc::new(LdapTree::from_env());
+    info!("LDAP base DN: {}", tree.base_dn);
+
+    if scheme != LdapListenScheme::Ldaps {
+        // simple binds send the password in clear text -> plain LDAP is only reachable locally,
+        // unless it has been opened up explicitly
+        let addr = addr.as_deref().unwrap_or("127.0.0.1");
+        let port = env::var("LDAP_PORT").unwrap_or_else(|_| "3389".to_string());
+        info!("LDAP listen address: {}:{}", addr, port);
+        if !is_loopback(addr) {
+            warn!(
+                "Plain LDAP is listening on the non-loopback address {} - passwords will be sent \
+                in clear text, you should use LDAPS instead",
+                addr
+            );
+        }
+        let listener = TcpListener::bind(format!("{}:{}", addr, port)).await?;
+        tokio::spawn(listen(listener, None, data.clone(), tree.clone()));
+    }
+
+    if scheme.needs_tls() {
+        let addr = addr.as_deref().unwrap_or("0.0.0.0");
+        let port = env::var("LDAPS_PORT").unwrap_or_else(|_| "6636".to_string());
+        info!("LDAPS listen address: {}:{}", addr, port);
+        let config = tls.expect("TLS config to be given for LDAPS");
+        let acceptor = TlsAcceptor::from(Arc::new(config));
+        let listener = TcpListener::bind(format!("{}:{}", addr, port)).await?;
+        tokio::spawn(listen(listener, Some(acceptor), data, tree));
+    }
+
+    Ok(())
+}
+
+fn is_loopback(addr: &str) -> bool {
+    addr == "localhost"
+        || addr
+            .trim_start_matches('[')
+            .trim_end_matches(']')
+            .parse::<IpAddr>()
+            .is_ok_and(|ip| ip.is_loopback())
+}
+
+async fn listen(
+    listener: TcpListener,
+    acceptor: Option<TlsAcceptor>,
+    data: web::Data<AppState>,
+    tree: Arc<LdapTree>,
+) {
+    loop {
+        let (stream, addr) = match listener.accept().await {
+            Ok(res) => res,
+            Err(err) => {
+                error!("Error accepting LDAP connection: {}", err);
+                continue;
+            }
+        };
+
+        let peer_ip = addr.ip();
+        if is_blacklisted(&data, peer_ip).await {
+            debug!("Dropping LDAP connection from blacklisted IP {}", peer_ip);
+            continue;
+        }
+
+        let data = data.clone();
+        let tree = tree.clone();
+        let acceptor = acceptor.clone();
+        tokio::spawn(async move {
+            let session = Session {
+                data,
+                tree,
+                peer_ip,
+                user: None,
+            };
+
+            let res = match acceptor {
+                None => session.handle(stream).await,
+                Some(acceptor) => match acceptor.accept(stream).await {
+                    Ok(stream) => session.handle(stream).await,
+                    Err(err) => {
+                        debug!("LDAPS handshake with {} failed: {}", peer_ip, err);
+                        return;
+                    }
+                },
+            };
+            if let Err(err) = res {
+                debug!("LDAP connection from {} closed: {}", peer_ip, err.message);
+            }
+        });
+    }
+}
+
+async fn is_blacklisted(data: &web::Data<AppState>, ip: IpAddr) -> bool {
+    let (tx, rx) = oneshot::channel();
+    if data
+        .tx_ip_blacklist
+        .send_async(IpBlacklistReq::BlacklistCheck(IpBlacklistCheck {
+            ip: ip.to_string(),
+            tx,
+        }))
+        .await
+        .is_err()
+    {
+        return false;
+    }
+
+    match rx.await {
+        Ok(exp) => exp.is_some_and(|exp| exp > Utc::now()),
+        Err(err) => {
+            error!(
+                "Checking IP Blacklist status for LDAP - this should never happen: {:?}",
+                err
+            );
+            false
+        }
+    }
+}
+
+struct Session {
+    data: web::Data<AppState>,
+    tree: Arc<LdapTree>,
+    peer_ip: IpAddr,
+    /// The currently bound user, `None` for anonymous connections.
+    user: Option<User>,
+}
+
+impl Session {
+    async fn handle<S>(mut self, mut stream: S) -> Result<(), ErrorResponse>
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+    {
+        let mut buf = Vec::with_capacity(1024);
+        let mut read_buf = [0u8; 4096];
+
+        loop {
+            while let Some(len) = ber::tlv_len(&buf)? {
+                if len > MAX_MESSAGE_SIZE {
+                    return Err(ber::err("LDAP message exceeds the max size"));
+                }
+                if buf.len() < len {
+                    break;
+                }
+
+                let msg = match LdapMessage::decode(&buf[..len]) {
+                    Ok(msg) => msg,
+                    Err(err) => {
+                        let notice = encode_extended(
+                            0,
+                            ResultCode::ProtocolError,
+                            &err.message,
+                            Some(OID_NOTICE_OF_DISCONNECTION),
+                            None,
+                        );
+                        stream.write_all(&notice).await?;
+                        return Err(err);
+                    }
+                };
+                buf.drain(..len);
+
+                if msg.op == LdapOp::Unbind {
+                    return Ok(());
+                }
+                for res in self.handle_message(msg).await {
+                    stream.write_all(&res).await?;
+                }
+                stream.flush().await?;
+            }
+
+            let read = stream.read(&mut read_buf).await?;
+            if read == 0 {
+                return Ok(());
+            }
+            buf.extend_from_slice(&read_buf[..read]);
+        }
+    }
+
+    async fn handle_message(&mut self, msg: LdapMessage) -> Vec<Vec<u8>> {
+        let id = msg.id;
+
+        match msg.op {
+            LdapOp::Bind {
+                version,
+                name,
+                password,
+            } => {
+                let (code, diag) = match (version, password) {
+                    (3, Some(password)) => self.bind(name, password).await,
+                    (3, None) => (
+                        ResultCode::AuthMethodNotSupported,
+                        "only simple binds are supported",
+                    ),
+                    _ => (ResultCode::ProtocolError, "only LDAPv3 is supported"),
+                };
+                vec![encode_result(id, OP_BIND_RESP, code, diag)]
+            }
+
+            LdapOp::Search(req) => self.search(id, req).await,
+
+            LdapOp::Extended { oid } if oid == OID_WHO_AM_I => {
+                let authz_id = self
+                    .user
+                    .as_ref()
+                    .map(|u| format!("dn:{}", self.tree.user_dn(&u.email)))
+                    .unwrap_or_default();
+                vec![encode_extended(
+                    id,
+                    ResultCode::Success,
+                    "",
+                    None,
+                    Some(&authz_id),
+                )]
+            }
+
+            LdapOp::Extended { oid } => {
+                debug!("Unsupported LDAP extended operation {}", oid);
+                vec![encode_extended(
+                    id,
+                    ResultCode::ProtocolError,
+                    "unsupported extended operation",
+                    None,
+                    None,
+                )]
+            }
+
+            LdapOp::Unsupported { response_tag } => vec![encode_result(
+                id,
+                response_tag,
+                ResultCode::UnwillingToPerform,
+                "this directory is read-only",
+            )],
+
+            // nothing to do here, each operation is answered before the next one is read
+            LdapOp::Abandon | LdapOp::Unbind => Vec::new(),
+        }
+    }
+
+    /// Simple binds use the same password validation and login delay as the Rauthy login.
+    async fn bind(&mut self, name: String, password: Vec<u8>) -> (ResultCode, &'static str) {
+        self.user = None;
+
+        if password.is_empty() {
+            // RFC 4513 5.1.2 - unauthenticated binds must not be treated as successful
+            return if name.is_empty() {
+                (ResultCode::Success, "")
+            } else {
+                (
+                    ResultCode::UnwillingToPerform,
+                    "unauthenticated binds are not allowed",
+                )
+            };
+        }
+
+        let start = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
+        let mut has_password_been_hashed = false;
+        let mut user_needs_mfa = false;
+
+        let res = self
+            .bind_user(
+                &name,
+                password,
+                &mut has_password_been_hashed,
+                &mut user_needs_mfa,
+            )
+            .await;
+        if user_needs_mfa {
+            // the credentials were valid -> no login delay, same as for the UI login
+            return (
+                ResultCode::InvalidCredentials,
+                "accounts with MFA cannot bind",
+            );
+        }
+
+        let delay_res = match &res {
+            Ok(_) => Ok(HttpResponse::Ok().finish()),
+            Err(err) => {
+                debug!("LDAP bind for '{}' failed: {}", name, err.message);
+                Err(ErrorResponse::new(
+                    ErrorResponseType::Unauthorized,
+                    "Invalid user credentials",
+                ))
+            }
+        };
+        let email = self.tree.bind_email(&name);
+        match login_delay::handle_login_delay(
+            &self.data,
+            self.peer_ip,
+            start,
+            delay_res,
+            has_password_been_hashed,
+            email.as_deref(),
+        )
+        .await
+        {
+            Ok(_) => {
+                self.user = res.ok();
+                (ResultCode::Success, "")
+            }
+            Err(err) if matches!(err.error, ErrorResponseType::TooManyRequests(_)) => {
+                (ResultCode::UnwillingToPerform, "too many invalid logins")
+            }
+            Err(_) => (ResultCode::InvalidCredentials, ""),
+        }
+    }
+
+    async fn bind_user(
+        &self,
+        name: &str,
+        password: Vec<u8>,
+        has_password_been_hashed: &mut bool,
+        user_needs_mfa: &mut bool,
+    ) -> Result<User, ErrorResponse> {
+        let password = String::from_utf8(password)
+            .map_err(|_| ErrorResponse::new(ErrorResponseType::BadRequest, "invalid password"))?;
+        let email = self
+            .tree
+            .bind_email(name)
+            .ok_or_else(|| ErrorResponse::new(ErrorResponseType::BadRequest, "invalid bind DN"))?;
+
+        if AuthProvider::find_home_realm(&email).await?.is_some() {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::Forbidden,
+                "Password logins are not allowed for this E-Mail domain",
+            ));
+        }
+
+        brute_force::check_account_lock(&email).await?;
+        let mut user = User::find_by_email(email).await?;
+        user.check_enabled()?;
+        user.check_expired()?;
+        user.check_locked()?;
+
+        *has_password_been_hashed = true;
+        match user.validate_password(&self.data, password.clone()).await {
+            Ok(_) => {
+                if user.has_webauthn_enabled() && !*LDAP_ALLOW_MFA_USERS {
+                    *user_needs_mfa = true;
+                    return Err(ErrorResponse::new(
+                        ErrorResponseType::MfaRequired,
+                        "MFA users are not allowed to bind",
+                    ));
+                }
+
+                user.last_login = Some(Utc::now().timestamp());
+                user.last_failed_login = None;
+                user.failed_login_attempts = None;
+
+                if !user.is_argon2_uptodate(&self.data.argon2_params)? {
+                    info!("Updating Argon2ID params for user '{}'", &user.email);
+                    user.password = Some(HashPassword::hash_password(password).await?);
+                }
+
+                user.save(None).await?;
+                Ok(user)
+            }
+            Err(err) => {
+                warn!(
+                    "False LDAP bind from Host: '{}' for user: '{}'",
+                    self.peer_ip, user.email
+                );
+
+                if err.error == ErrorResponseType::Unauthorized {
+                    user.on_failed_login(&self.data, Some(self.peer_ip.to_string()))
+                        .await?;
+                }
+
+                Err(err)
+            }
+        }
+    }
+
+    fn may_search(&self) -> bool {
+        match (&self.user, &*LDAP_SEARCH_GROUP) {
+            (None, _) => false,
+            (Some(_), None) => true,
+            (Some(user), Some(group)) => user.get_groups().contains(group),
+        }
+    }
+
+    async fn search(&self, id: i64, req: SearchRequest) -> Vec<Vec<u8>> {
+        // the Root DSE must be readable anonymously for the naming context discovery
+        if req.base.is_empty() && req.scope == SearchScope::Base {
+            let root = self.tree.root_dse();
+            let mut res = Vec::with_capacity(2);
+            if root.matches(&req.filter) {
+                res.push(encode_entry(
+                    id,
+                    &root.dn,
+                    root.selected_attrs(&req.attrs),
+                    req.types_only,
+                ));
+            }
+            res.push(encode_result(id, OP_SEARCH_DONE, ResultCode::Success, ""));
+            return res;
+        }
+
+        if !self.may_search() {
+            return vec![encode_result(
+                id,
+                OP_SEARCH_DONE,
+                ResultCode::InsufficientAccessRights,
+                "search needs a bind with an allowed user",
+            )];
+        }
+
+        let dir = match Directory::load(&self.tree).await {
+            Ok(dir) => dir,
+            Err(err) => {
+                error!("Error loading the LDAP directory: {}", err.message);
+                return vec![encode_result(
+                    id,
+                    OP_SEARCH_DONE,
+                    ResultCode::OperationsError,
+                    "",
+                )];
+            }
+        };
+        let entries = match dir.search(&req) {
+            Ok(entries) => entries,
+            Err(code) => return vec![encode_result(id, OP_SEARCH_DONE, code, "")],
+        };
+
+        let limit = if req.size_limit > 0 {
+            (*LDAP_SIZE_LIMIT).min(req.size_limit as usize)
+        } else {
+            *LDAP_SIZE_LIMIT
+        };
+        let mut res = entries
+            .iter()
+            .take(limit)
+            .map(|e| encode_entry(id, &e.dn, e.selected_attrs(&req.attrs), req.types_only))
+            .collect::<Vec<_>>();
+        let code = if entries.len() > limit {
+            ResultCode::SizeLimitExceeded
+        } else {
+            ResultCode::Success
+        };
+        res.push(encode_result(id, OP_SEARCH_DONE, code, ""));
+
+        res
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_loopback() {
+        assert!(is_loopback("127.0.0.1"));
+        assert!(is_loopback("localhost"));
+        assert!(is_loopback("::1"));
+        assert!(is_loopback("[::1]"));
+        assert!(!is_loopback("0.0.0.0"));
+        assert!(!is_loopback("::"));
+        assert!(!is_loopback("192.168.1.10"));
+    }
+}
@@ -0,0 +1,423 @@
+use crate::ldap::ber::{
+    err, Reader, Writer, TAG_ENUMERATED, TAG_INTEGER, TAG_OCTET_STRING, TAG_SEQUENCE, TAG_SET,
+};
+use rauthy_error::ErrorResponse;
+
+pub const OP_BIND_REQ: u8 = 0x60;
+pub const OP_BIND_RESP: u8 = 0x61;
+pub const OP_UNBIND_REQ: u8 = 0x42;
+pub const OP_SEARCH_REQ: u8 = 0x63;
+pub const OP_SEARCH_ENTRY: u8 = 0x64;
+pub const OP_SEARCH_DONE: u8 = 0x65;
+pub const OP_MODIFY_REQ: u8 = 0x66;
+pub const OP_MODIFY_RESP: u8 = 0x67;
+pub const OP_ADD_REQ: u8 = 0x68;
+pub const OP_ADD_RESP: u8 = 0x69;
+pub const OP_DEL_REQ: u8 = 0x4a;
+pub const OP_DEL_RESP: u8 = 0x6b;
+pub const OP_MODIFY_DN_REQ: u8 = 0x6c;
+pub const OP_MODIFY_DN_RESP: u8 = 0x6d;
+pub const OP_COMPARE_REQ: u8 = 0x6e;
+pub const OP_COMPARE_RESP: u8 = 0x6f;
+pub const OP_ABANDON_REQ: u8 = 0x50;
+pub const OP_EXTENDED_REQ: u8 = 0x77;
+pub const OP_EXTENDED_RESP: u8 = 0x78;
+
+pub const OID_NOTICE_OF_DISCONNECTION: &str = "1.3.6.1.4.1.1466.20036";
+pub const OID_WHO_AM_I: &str = "1.3.6.1.4.1.4203.1.11.3";
+
+/// Nested filters deeper than this will be rejected to protect the stack.
+const MAX_FILTER_DEPTH: usize = 32;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResultCode {
+    Success = 0,
+    OperationsError = 1,
+    ProtocolError = 2,
+    SizeLimitExceeded = 4,
+    AuthMethodNotSupported = 7,
+    NoSuchObject = 32,
+    InvalidCredentials = 49,
+    InsufficientAccessRights = 50,
+    UnwillingToPerform = 53,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct LdapMessage {
+    pub id: i64,
+    pub op: LdapOp,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum LdapOp {
+    /// `password` is `None` for SASL binds, which are not supported.
+    Bind {
+        version: i64,
+        name: String,
+        password: Option<Vec<u8>>,
+    },
+    Unbind,
+    Search(SearchRequest),
+    Abandon,
+    Extended {
+        oid: String,
+    },
+    /// Any write or compare operation, which will be rejected with the given response tag.
+    Unsupported {
+        response_tag: u8,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SearchScope {
+    Base,
+    One,
+    Sub,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct SearchRequest {
+    pub base: String,
+    pub scope: SearchScope,
+    pub size_limit: i64,
+    pub types_only: bool,
+    pub filter: Filter,
+    pub attrs: Vec<String>,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Filter {
+    And(Vec<Filter>),
+    Or(Vec<Filter>),
+    Not(Box<Filter>),
+    Equal(String, String),
+    Substrings {
+        attr: String,
+        initial: Option<String>,
+        any: Vec<String>,
+        last: Option<String>,
+    },
+    GreaterOrEqual(String, String),
+    LessOrEqual(String, String),
+    Present(String),
+    Approx(String, String),
+    /// Extensible matches are not supported and will never match.
+    Undefined,
+}
+
+impl LdapMessage {
+    pub fn decode(buf: &[u8]) -> Result<Self, ErrorResponse> {
+        let mut outer = Reader::new(buf);
+        let mut r = Reader::new(outer.read_tag(TAG_SEQUENCE)?);
+        let id = r.read_int(TAG_INTEGER)?;
+
+        // any following controls are ignored, since none of them is supported
+        let (tag, value) = r.read()?;
+        let op = match tag {
+            OP_BIND_REQ => {
+                let mut r = Reader::new(value);
+                let version = r.read_int(TAG_INTEGER)?;
+                let name = r.read_string(TAG_OCTET_STRING)?;
+                let (auth_tag, auth) = r.read()?;
+                LdapOp::Bind {
+                    version,
+                    name,
+                    password: (auth_tag == 0x80).then(|| auth.to_vec()),
+                }
+            }
+            OP_UNBIND_REQ => LdapOp::Unbind,
+            OP_SEARCH_REQ => LdapOp::Search(SearchRequest::decode(value)?),
+            OP_ABANDON_REQ => LdapOp::Abandon,
+            OP_EXTENDED_REQ => LdapOp::Extended {
+                oid: Reader::new(value).read_string(0x80)?,
+            },
+            OP_MODIFY_REQ => LdapOp::Unsupported {
+                response_tag: OP_MODIFY_RESP,
+            },
+            OP_ADD_REQ => LdapOp::Unsupported {
+                response_tag: OP_ADD_RESP,
+            },
+            OP_DEL_REQ => LdapOp::Unsupported {
+                response_tag: OP_DEL_RESP,
+            },
+            OP_MODIFY_DN_REQ => LdapOp::Unsupported {
+                response_tag: OP_MODIFY_DN_RESP,
+            },
+            OP_COMPARE_REQ => LdapOp::Unsupported {
+                response_tag: OP_COMPARE_RESP,
+            },
+            _ => return Err(err("unknown LDAP operation")),
+        };
+
+        Ok(Self { id, op })
+    }
+}
+
+impl SearchRequest {
+    fn decode(value: &[u8]) -> Result<Self, ErrorResponse> {
+        let mut r = Reader::new(value);
+        let base = r.read_string(TAG_OCTET_STRING)?;
+        let scope = match r.read_int(TAG_ENUMERATED)? {
+            0 => SearchScope::Base,
+            1 => SearchScope::One,
+            2 => SearchScope::Sub,
+            _ => return Err(err("invalid LDAP search scope")),
+        };
+        // aliases do not exist in this directory
+        let _deref_aliases = r.read_int(TAG_ENUMERATED)?;
+        let size_limit = r.read_int(TAG_INTEGER)?;
+        let _time_limit = r.read_int(TAG_INTEGER)?;
+        let types_only = r.read_bool()?;
+        let (tag, value) = r.read()?;
+        let filter = Filter::decode(tag, value, 0)?;
+
+        let mut attrs = Vec::new();
+        let mut r = Reader::new(r.read_tag(TAG_SEQUENCE)?);
+        while !r.is_empty() {
+            attrs.push(r.read_string(TAG_OCTET_STRING)?);
+        }
+
+        Ok(Self {
+            base,
+            scope,
+            size_limit,
+            types_only,
+            filter,
+            attrs,
+        })
+    }
+}
+
+impl Filter {
+    fn decode(tag: u8, value: &[u8], depth: usize) -> Result<Self, ErrorResponse> {
+        if depth > MAX_FILTER_DEPTH {
+            return Err(err("LDAP filter nesting is too deep"));
+        }
+
+        let slf = match tag {
+            0xa0 | 0xa1 => {
+                let mut filters = Vec::new();
+                let mut r = Reader::new(value);
+                while !r.is_empty() {
+                    let (tag, value) = r.read()?;
+                    filters.push(Self::decode(tag, value, depth + 1)?);
+                }
+                if tag == 0xa0 {
+                    Self::And(filters)
+                } else {
+                    Self::Or(filters)
+                }
+            }
+            0xa2 => {
+                let (tag, value) = Reader::new(value).read()?;
+                Self::Not(Box::new(Self::decode(tag, value, depth + 1)?))
+            }
+            0xa3 | 0xa5 | 0xa6 | 0xa8 => {
+                let mut r = Reader::new(value);
+                let attr = r.read_string(TAG_OCTET_STRING)?;
+                let value = r.read_string(TAG_OCTET_STRING)?;
+                match tag {
+                    0xa3 => Self::Equal(attr, value),
+                    0xa5 => Self::GreaterOrEqual(attr, value),
+                    0xa6 => Self::LessOrEqual(attr, value),
+                    _ => Self::Approx(attr, value),
+                }
+            }
+            0xa4 => {
+                let mut r = Reader::new(value);
+                let attr = r.read_string(TAG_OCTET_STRING)?;
+                let mut initial = None;
+                let mut any = Vec::new();
+                let mut last = None;
+
+                let mut r = Reader::new(r.read_tag(TAG_SEQUENCE)?);
+                while !r.is_empty() {
+                    let (tag, value) = r.read()?;
+                    let value = String::from_utf8(value.to_vec())
+                        .map_err(|_| err("invalid UTF-8 string"))?;
+                    match tag {
+                        0x80 => initial = Some(value),
+                        0x81 => any.push(value),
+                        0x82 => last = Some(value),
+                        _ => return Err(err("invalid LDAP substring filter")),
+                    }
+                }
+
+                Self::Substrings {
+                    attr,
+                    initial,
+                    any,
+                    last,
+                }
+            }
+            0x87 => Self::Present(
+                String::from_utf8(value.to_vec()).map_err(|_| err("invalid UTF-8 string"))?,
+            ),
+            0xa9 => Self::Undefined,
+            _ => return Err(err("invalid LDAP filter")),
+        };
+
+        Ok(slf)
+    }
+}
+
+fn encode_message<F>(id: i64, f: F) -> Vec<u8>
+where
+    F: FnOnce(&mut Writer),
+{
+    let mut w = Writer::default();
+    w.write_constructed(TAG_SEQUENCE, |w| {
+        w.write_int(TAG_INTEGER, id);
+        f(w);
+    });
+    w.into_bytes()
+}
+
+fn write_result(w: &mut Writer, code: ResultCode, msg: &str) {
+    w.write_int(TAG_ENUMERATED, code as i64);
+    w.write_str(TAG_OCTET_STRING, "");
+    w.write_str(TAG_OCTET_STRING, msg);
+}
+
+pub fn encode_result(id: i64, op_tag: u8, code: ResultCode, msg: &str) -> Vec<u8> {
+    encode_message(id, |w| {
+        w.write_constructed(op_tag, |w| write_result(w, code, msg));
+    })
+}
+
+pub fn encode_extended(
+    id: i64,
+    code: ResultCode,
+    msg: &str,
+    name: Option<&str>,
+    value: Option<&str>,
+) -> Vec<u8> {
+    encode_message(id, |w| {
+        w.write_constructed(OP_EXTENDED_RESP, |w| {
+            write_result(w, code, msg);
+            if let Some(name) = name {
+                w.write_str(0x8a, name);
+            }
+            if let Some(value) = value {
+                w.write_str(0x8b, value);
+            }
+        });
+    })
+}
+
+pub fn encode_entry<'a, I>(id: i64, dn: &str, attrs: I, types_only: bool) -> Vec<u8>
+where
+    I: Iterator<Item = (&'a str, &'a [String])>,
+{
+    encode_message(id, |w| {
+        w.write_constructed(OP_SEARCH_ENTRY, |w| {
+            w.write_str(TAG_OCTET_STRING, dn);
+            w.write_constructed(TAG_SEQUENCE, |w| {
+                for (name, values) in attrs {
+                    w.write_constructed(TAG_SEQUENCE, |w| {
+                        w.write_str(TAG_OCTET_STRING, name);
+                        w.write_constructed(TAG_SET, |w| {
+                            if !types_only {
+                                for value in values {
+                                    w.write_str(TAG_OCTET_STRING, value);
+                                }
+                            }
+                        });
+                    });
+                }
+            });
+        });
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_ldap_messages() {
+        // ldapsearch -x -D "uid=admin@localhost.de,ou=users,dc=localhost" -w 123SuperSafe
+        let bind = [
+            0x30, 0x44, 0x02, 0x01, 0x01, 0x60, 0x3f, 0x02, 0x01, 0x03, 0x04, 0x2c, 0x75, 0x69,
+            0x64, 0x3d, 0x61, 0x64, 0x6d, 0x69, 0x6e, 0x40, 0x6c, 0x6f, 0x63, 0x61, 0x6c, 0x68,
+            0x6f, 0x73, 0x74, 0x2e, 0x64, 0x65, 0x2c, 0x6f, 0x75, 0x3d, 0x75, 0x73, 0x65, 0x72,
+            0x73, 0x2c, 0x64, 0x63, 0x3d, 0x6c, 0x6f, 0x63, 0x61, 0x6c, 0x68, 0x6f, 0x73, 0x74,
+            0x80, 0x0c, 0x31, 0x32, 0x33, 0x53, 0x75, 0x70, 0x65, 0x72, 0x53, 0x61, 0x66, 0x65,
+        ];
+        let msg = LdapMessage::decode(&bind).unwrap();
+        assert_eq!(
+            msg,
+            LdapMessage {
+                id: 1,
+                op: LdapOp::Bind {
+                    version: 3,
+                    name: "uid=admin@localhost.de,ou=users,dc=localhost".to_string(),
+                    password: Some(b"123SuperSafe".to_vec()),
+                },
+            }
+        );
+
+        // (&(objectClass=inetOrgPerson)(|(uid=adm*)(!(memberOf=*))))
+        let mut w = Writer::default();
+        w.write_constructed(TAG_SEQUENCE, |w| {
+            w.write_int(TAG_INTEGER, 2);
+            w.write_constructed(OP_SEARCH_REQ, |w| {
+                w.write_str(TAG_OCTET_STRING, "ou=users,dc=localhost");
+                w.write_int(TAG_ENUMERATED, 2);
+                w.write_int(TAG_ENUMERATED, 0);
+                w.write_int(TAG_INTEGER, 10);
+                w.write_int(TAG_INTEGER, 0);
+                w.write_bool(false);
+                w.write_constructed(0xa0, |w| {
+                    w.write_constructed(0xa3, |w| {
+                        w.write_str(TAG_OCTET_STRING, "objectClass");
+                        w.write_str(TAG_OCTET_STRING, "inetOrgPerson");
+                    });
+                    w.write_constructed(0xa1, |w| {
+                        w.write_constructed(0xa4, |w| {
+                            w.write_str(TAG_OCTET_STRING, "uid");
+                            w.write_constructed(TAG_SEQUENCE, |w| {
+                                w.write_str(0x80, "adm");
+                            });
+                        });
+                        w.write_constructed(0xa2, |w| {
+                            w.write_str(0x87, "memberOf");
+                        });
+                    });
+                });
+                w.write_constructed(TAG_SEQUENCE, |w| {
+                    w.write_str(TAG_OCTET_STRING, "mail");
+                });
+            });
+        });
+        let msg = LdapMessage::decode(&w.into_bytes()).unwrap();
+        assert_eq!(msg.id, 2);
+        let LdapOp::Search(search) = msg.op else {
+            panic!("expected a search request");
+        };
+        assert_eq!(search.scope, SearchScope::Sub);
+        assert_eq!(search.size_limit, 10);
+        assert_eq!(search.attrs, vec!["mail".to_string()]);
+        assert_eq!(
+            search.filter,
+            Filter::And(vec![
+                Filter::Equal("objectClass".to_string(), "inetOrgPerson".to_string()),
+                Filter::Or(vec![
+                    Filter::Substrings {
+                        attr: "uid".to_string(),
+                        initial: Some("adm".to_string()),
+                        any: Vec::new(),
+                        last: None,
+                    },
+                    Filter::Not(Box::new(Filter::Present("memberOf".to_string()))),
+                ]),
+            ])
+        );
+
+        let done = encode_result(2, OP_SEARCH_DONE, ResultCode::Success, "");
+        assert_eq!(
+            done,
+            [0x30, 0x0c, 0x02, 0x01, 0x02, 0x65, 0x07, 0x0a, 0x01, 0x00, 0x04, 0x00, 0x04, 0x00]
+        );
+    }
+}
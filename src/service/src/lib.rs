@@ -2,10 +2,15 @@
 
 #![forbid(unsafe_code)]
 
+pub mod brute_force;
 pub mod client;
 pub mod encryption;
+pub mod ldap;
 pub mod login_delay;
 pub mod oidc;
 pub mod password_reset;
+pub mod saml;
 pub mod suspicious_request_block;
 pub mod token_set;
+pub mod user_export;
+pub mod user_import;
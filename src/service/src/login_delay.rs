@@ -1,11 +1,12 @@
+use crate::brute_force;
 use actix_web::{web, HttpResponse};
 use chrono::Utc;
-use rauthy_common::constants::IDX_LOGIN_TIME;
+use rauthy_common::constants::{IDX_LOGIN_FAILED_IP, IDX_LOGIN_TIME};
 use rauthy_error::{ErrorResponse, ErrorResponseType};
 use rauthy_models::app_state::AppState;
 use rauthy_models::database::{Cache, DB};
 use rauthy_models::events::event::Event;
-use rauthy_models::events::ip_blacklist_handler::{IpBlacklistReq, IpFailedLoginCheck};
+use rauthy_models::events::ip_blacklist_handler::{IpAllowCheck, IpBlacklistReq};
 use rauthy_models::templates::TooManyRequestsHtml;
 use std::net::IpAddr;
 use std::ops::{Add, Sub};
@@ -13,12 +14,20 @@ use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use tokio::sync::oneshot;
 use tracing::{debug, error};
 
+/// Saved together with blacklist entries, which have been created because of failed logins
+const BLACKLIST_REASON: &str = "Too many invalid logins";
+
+/// Failed logins for an IP are forgotten after this many seconds without another failed one,
+/// which matches the longest blacklisting duration.
+const FAILED_LOGINS_TTL: i64 = 86400;
+
 /**
 Handles the login delay.
 
 With every successful login, a new average login time is calculated for how
 long it took for a successful login. If a login failed though, the answer will be delayed by the
 current average for a successful login, to prevent things like username enumeration.
+If the `email` is known, failed logins will be tracked for the brute-force detection as well.
  */
 pub async fn handle_login_delay(
     data: &web::Data<AppState>,
@@ -26,6 +35,7 @@ pub async fn handle_login_delay(
     start: Duration,
     res: Result<HttpResponse, ErrorResponse>,
     has_password_been_hashed: bool,
+    email: Option<&str>,
 ) -> Result<HttpResponse, ErrorResponse> {
     let client = DB::client();
     let success_time: i64 = client
@@ -38,11 +48,10 @@ pub async fn handle_login_delay(
 
     match res {
         Ok(resp) => {
-            // cleanup possibly blacklisted IP
-            data.tx_ip_blacklist
-                .send_async(IpBlacklistReq::LoginFailedDelete(peer_ip.to_string()))
-                .await
-                .expect("ip blacklist recv not to be closed");
+            // cleanup failed logins for this IP
+            client
+                .delete(Cache::App, format!("{}{}", IDX_LOGIN_FAILED_IP, peer_ip))
+                .await?;
 
             // only calculate the new median login time base on the full duration incl password hash
             if has_password_been_hashed {
@@ -58,29 +67,23 @@ pub async fn handle_login_delay(
             Ok(resp)
         }
         Err(err) => {
-            let mut failed_logins = 1;
-
-            // check possibly blacklisted IP
-            let (tx, rx) = oneshot::channel();
-            data.tx_ip_blacklist
-                .send_async(IpBlacklistReq::LoginCheck(IpFailedLoginCheck {
-                    ip: peer_ip.to_string(),
-                    increase_counter: true,
-                    tx,
-                }))
-                .await
-                .expect("ip blacklist recv not to be closed");
-
-            match rx.await {
-                Ok(res) => {
-                    if let Some(counter) = res {
-                        failed_logins = counter;
-                    }
+            // only invalid credentials or unknown users are relevant for the brute-force detection
+            if let Some(email) = email {
+                if matches!(
+                    err.error,
+                    ErrorResponseType::Unauthorized | ErrorResponseType::NotFound
+                ) {
+                    brute_force::on_failed_login(data, peer_ip, email).await;
                 }
+            }
+
+            let failed_logins = match track_failed_login(data, peer_ip).await {
+                Ok(counter) => counter,
                 Err(err) => {
-                    error!("oneshot recv error in login delay handler - this should never happen: {:?}", err);
+                    error!("Error tracking failed login for {}: {:?}", peer_ip, err);
+                    1
                 }
-            }
+            };
 
             // event for failed login
             data.tx_events
@@ -106,7 +109,11 @@ pub async fn handle_login_delay(
                     let html = TooManyRequestsHtml::build(peer_ip.to_string(), ts);
 
                     data.tx_events
-                        .send_async(Event::ip_blacklisted(not_before, peer_ip.to_string()))
+                        .send_async(Event::ip_blacklisted(
+                            Some(not_before),
+                            peer_ip.to_string(),
+                            Some(BLACKLIST_REASON.to_string()),
+                        ))
                         .await
                         .unwrap();
 
@@ -125,7 +132,11 @@ pub async fn handle_login_delay(
                     let html = TooManyRequestsHtml::build(peer_ip.to_string(), ts);
 
                     data.tx_events
-                        .send_async(Event::ip_blacklisted(not_before, peer_ip.to_string()))
+                        .send_async(Event::ip_blacklisted(
+                            Some(not_before),
+                            peer_ip.to_string(),
+                            Some(BLACKLIST_REASON.to_string()),
+                        ))
                         .await
                         .unwrap();
 
@@ -144,7 +155,11 @@ pub async fn handle_login_delay(
                     let html = TooManyRequestsHtml::build(peer_ip.to_string(), ts);
 
                     data.tx_events
-                        .send_async(Event::ip_blacklisted(not_before, peer_ip.to_string()))
+                        .send_async(Event::ip_blacklisted(
+                            Some(not_before),
+                            peer_ip.to_string(),
+                            Some(BLACKLIST_REASON.to_string()),
+                        ))
                         .await
                         .unwrap();
 
@@ -163,7 +178,11 @@ pub async fn handle_login_delay(
                     let html = TooManyRequestsHtml::build(peer_ip.to_string(), ts);
 
                     data.tx_events
-                        .send_async(Event::ip_blacklisted(not_before, peer_ip.to_string()))
+                        .send_async(Event::ip_blacklisted(
+                            Some(not_before),
+                            peer_ip.to_string(),
+                            Some(BLACKLIST_REASON.to_string()),
+                        ))
                         .await
                         .unwrap();
 
@@ -182,7 +201,11 @@ pub async fn handle_login_delay(
                     let html = TooManyRequestsHtml::build(peer_ip.to_string(), ts);
 
                     data.tx_events
-                        .send_async(Event::ip_blacklisted(not_before, peer_ip.to_string()))
+                        .send_async(Event::ip_blacklisted(
+                            Some(not_before),
+                            peer_ip.to_string(),
+                            Some(BLACKLIST_REASON.to_string()),
+                        ))
                         .await
                         .unwrap();
 
@@ -206,3 +229,42 @@ pub async fn handle_login_delay(
         }
     }
 }
+
+/// Increases the failed logins counter for the given IP and returns the new value.
+///
+/// The counter lives in the replicated cache to be shared between all nodes. Just like the
+/// brute-force tracking, concurrent failed logins may lose a single increment, which is fine
+/// for this use case. IPs on the allowlist will never increase their counter.
+async fn track_failed_login(
+    data: &web::Data<AppState>,
+    peer_ip: IpAddr,
+) -> Result<u32, ErrorResponse> {
+    let idx = format!("{}{}", IDX_LOGIN_FAILED_IP, peer_ip);
+    let client = DB::client();
+    let counter: u32 = client.get(Cache::App, &idx).await?.unwrap_or_default();
+
+    let (tx, rx) = oneshot::channel();
+    data.tx_ip_blacklist
+        .send_async(IpBlacklistReq::AllowCheck(IpAllowCheck {
+            ip: peer_ip.to_string(),
+            tx,
+        }))
+        .await
+        .expect("ip blacklist recv not to be closed");
+    let is_allowed = rx.await.unwrap_or_else(|err| {
+        error!(
+            "oneshot recv error in login delay handler - this should never happen: {:?}",
+            err
+        );
+        false
+    });
+    if is_allowed {
+        return Ok(counter.max(1));
+    }
+
+    let counter = counter + 1;
+    client
+        .put(Cache::App, idx, &counter, Some(FAILED_LOGINS_TTL))
+        .await?;
+    Ok(counter)
+}
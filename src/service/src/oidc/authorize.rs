@@ -3,20 +3,28 @@ use actix_web::http::header::{HeaderName, HeaderValue};
 use actix_web::{web, HttpRequest};
 use chrono::Utc;
 use rauthy_api_types::oidc::{LoginRefreshRequest, LoginRequest};
-use rauthy_common::constants::{COOKIE_MFA, SESSION_RENEW_MFA, WEBAUTHN_REQ_EXP};
-use rauthy_common::utils::get_rand;
+use rauthy_common::constants::{
+    COOKIE_MFA, MFA_NEW_DEVICE_EMAIL_CODE, SESSION_RENEW_MFA, WEBAUTHN_REQ_EXP,
+};
+use rauthy_common::password_hasher::HashPassword;
+use rauthy_common::utils::{get_rand, real_ip_from_req};
 use rauthy_error::{ErrorResponse, ErrorResponseType};
 use rauthy_models::api_cookie::ApiCookie;
 use rauthy_models::app_state::AppState;
 use rauthy_models::entity::auth_codes::AuthCode;
+use rauthy_models::entity::auth_providers::AuthProvider;
 use rauthy_models::entity::clients::Client;
+use rauthy_models::entity::organizations::Organization;
 use rauthy_models::entity::sessions::Session;
+use rauthy_models::entity::trusted_devices::{LoginEmailCode, TrustedDevice};
 use rauthy_models::entity::users::{AccountType, User};
-use rauthy_models::entity::webauthn::{WebauthnCookie, WebauthnLoginReq};
+use rauthy_models::entity::webauthn::{WebauthnCookie, WebauthnLoginAccessCheck, WebauthnLoginReq};
+use rauthy_models::security_notifications::SecurityNotification;
 use rauthy_models::{AuthStep, AuthStepAwaitWebauthn, AuthStepLoggedIn};
 use std::fmt::Write;
-use tracing::trace;
+use tracing::{info, trace};
 
+#[allow(clippy::too_many_arguments)]
 pub async fn post_authorize(
     data: &web::Data<AppState>,
     req: &HttpRequest,
@@ -25,16 +33,61 @@ pub async fn post_authorize(
     has_password_been_hashed: &mut bool,
     add_login_delay: &mut bool,
     user_needs_mfa: &mut bool,
+    user_needs_org_selection: &mut bool,
+    user_needs_provider_login: &mut bool,
+    user_needs_email_code: &mut bool,
+    password_login_denied: &mut bool,
+    user_access_denied: &mut bool,
+    user_needs_password_change: &mut bool,
 ) -> Result<AuthStep, ErrorResponse> {
-    let mut user = User::find_by_email(req_data.email).await.inspect_err(|_| {
-        // The UI does not show the password input form when there is no user yet.
-        // To prevent username enumeration, we should not add a login delay if a user does not
-        // even exist, when the UI is in that phase where the user does not provide any
-        // password.
-        if req_data.password.is_none() {
+    let client = Client::find_maybe_ephemeral(req_data.client_id).await?;
+
+    // Home-realm discovery: users from an E-Mail domain bound to an upstream provider must always
+    // log in there, as long as the client allows this provider. This check happens before the
+    // user lookup to be able to create new users on the first upstream login. Only admins with
+    // local credentials are exempt to prevent a lockout.
+    if let Some(provider) = AuthProvider::find_home_realm(&req_data.email)
+        .await?
+        .filter(|p| client.is_provider_allowed(&p.id))
+    {
+        *user_needs_provider_login = true;
+        *add_login_delay = false;
+        return Err(ErrorResponse::new(
+            ErrorResponseType::ProviderLoginRequired,
+            provider.id,
+        ));
+    }
+
+    // The client policy is public anyway. It must be checked before the user lookup, so the
+    // response does not depend on the existence of the user. This saves the password hashing too.
+    let password_login_err = client.validate_password_login().err();
+    if req_data.password.is_some() {
+        if let Some(err) = &password_login_err {
+            *password_login_denied = true;
             *add_login_delay = false;
+            return Err(err.clone());
         }
-    })?;
+    }
+
+    let mut user = match User::find_by_email(req_data.email).await {
+        Ok(user) => user,
+        Err(err) => {
+            // The UI does not show the password input form when there is no user yet.
+            // To prevent username enumeration, we should not add a login delay if a user does not
+            // even exist, when the UI is in that phase where the user does not provide any
+            // password.
+            if req_data.password.is_none() {
+                *add_login_delay = false;
+            }
+            // An unknown user must look exactly like one, that would need a password.
+            if let Some(denied) = password_login_err {
+                *password_login_denied = true;
+                *add_login_delay = false;
+                return Err(denied);
+            }
+            return Err(err);
+        }
+    };
 
     let mfa_cookie =
         if let Ok(c) = WebauthnCookie::parse_validate(&ApiCookie::from_req(req, COOKIE_MFA)) {
@@ -55,6 +108,12 @@ pub async fn post_authorize(
     let user_must_provide_password =
         req_data.password.is_none() && account_type != AccountType::Passkey && mfa_cookie.is_none();
     if user_must_provide_password {
+        if let Some(err) = password_login_err {
+            *password_login_denied = true;
+            *add_login_delay = false;
+            return Err(err);
+        }
+
         // if we get here, the UI did the first step from the login form
         // -> username only without password
         // we should not add a delay in that case, because the user did nothing wrong, we just need
@@ -78,6 +137,9 @@ pub async fn post_authorize(
 
     user.check_enabled()?;
     user.check_expired()?;
+    user.check_locked()?;
+
+    client.validate_redirect_uri(&req_data.redirect_uri)?;
 
     // TODO should we move the password hashing as far back as possible? -> most expensive operation
     // maybe it makes sense to do additional DB requests instead of hashing a password?
@@ -85,7 +147,46 @@ pub async fn post_authorize(
     // -> identify the best ordering and if it maybe makes sense to check the client first
     if let Some(pwd) = req_data.password {
         *has_password_been_hashed = true;
-        user.validate_password(data, pwd).await?;
+        if let Err(err) = user.validate_password(data, pwd.clone()).await {
+            // an expired password or a refresh is not a failed login
+            if err.error == ErrorResponseType::Unauthorized {
+                let ip = real_ip_from_req(req).ok().map(|ip| ip.to_string());
+                user.on_failed_login(data, ip).await?;
+            }
+            return Err(err);
+        }
+
+        // The password is correct, but it may not be strong enough for this client.
+        user.validate_client_password_policy(data, &client.id, &pwd)
+            .await
+            .inspect_err(|err| {
+                if err.error == ErrorResponseType::PasswordRefresh {
+                    *user_needs_password_change = true;
+                    *add_login_delay = false;
+                }
+            })?;
+
+        // upgrade outdated argon2 params and imported legacy hashes
+        if !user.is_argon2_uptodate(&data.argon2_params)? {
+            info!("Updating the password hash for user '{}'", user.email);
+            user.password = Some(HashPassword::hash_password(pwd).await?);
+        }
+
+        // The password was correct, and the `redirect_uri` is valid, which makes it possible to
+        // redirect with an `access_denied` error to the client. Logins without a password are
+        // only authenticated by the passkey and will be checked in the webauthn finish step.
+        client
+            .validate_login_access(data, &user, real_ip_from_req(req).ok())
+            .await
+            .map_err(|err| {
+                *user_access_denied = true;
+                *add_login_delay = false;
+                Client::access_denied_redirect(
+                    err,
+                    &req_data.redirect_uri,
+                    req_data.state.as_deref(),
+                )
+            })?;
 
         // update user info
         // in case of webauthn login, the info will be updated in the oidc finish step
@@ -96,16 +197,23 @@ pub async fn post_authorize(
     }
 
     // client validations
-    let client = Client::find_maybe_ephemeral(req_data.client_id).await?;
     client.validate_mfa(&user).inspect_err(|_| {
         // in this case, we do not want to add a login delay
         // the user password was correct, we only need a passkey being added to the account
         *user_needs_mfa = true;
         *add_login_delay = false;
     })?;
-    client.validate_redirect_uri(&req_data.redirect_uri)?;
     client.validate_code_challenge(&req_data.code_challenge, &req_data.code_challenge_method)?;
     let header_origin = client.validate_origin(req, &data.listen_scheme, &data.public_url)?;
+    let access_check = if *has_password_been_hashed {
+        None
+    } else {
+        Some(WebauthnLoginAccessCheck {
+            client_id: client.id.clone(),
+            redirect_uri: req_data.redirect_uri.clone(),
+            state: req_data.state.clone(),
+        })
+    };
 
     // build authorization code
     let code_lifetime = if user.has_webauthn_enabled() {
@@ -114,6 +222,61 @@ pub async fn post_authorize(
         client.auth_code_lifetime
     };
     let scopes = client.sanitize_login_scopes(&req_data.scopes)?;
+    let org_id = if scopes.iter().any(|s| s == "organization") {
+        Organization::validate_selection(&user.id, req_data.org_id)
+            .await
+            .inspect_err(|err| {
+                // the credentials were correct, the user only needs to select an organization
+                if err.error == ErrorResponseType::OrgSelectionRequired {
+                    *user_needs_org_selection = true;
+                    *add_login_delay = false;
+                }
+            })?
+    } else {
+        None
+    };
+
+    // Adaptive MFA for password logins: a trusted device skips the 2nd factor, while a new
+    // device for a user without any passkey may need to be confirmed with an E-Mail code.
+    // This must happen after the organization selection, which would consume the code otherwise.
+    let trust_device = req_data.trust_device.unwrap_or(false) && TrustedDevice::is_enabled();
+    let mut skip_webauthn = false;
+    let mut trusted_device_cookie = None;
+    if *has_password_been_hashed {
+        let trusted_device = TrustedDevice::find_valid_from_req(req, &user.id).await;
+
+        if user.has_webauthn_enabled() {
+            skip_webauthn = trusted_device.is_some();
+        } else if *MFA_NEW_DEVICE_EMAIL_CODE && trusted_device.is_none() {
+            let is_confirmed = match req_data.email_code.as_deref() {
+                Some(code) => LoginEmailCode::validate(&user.id, code).await?,
+                None => false,
+            };
+            if !is_confirmed {
+                LoginEmailCode::send(data, &user).await?;
+                // the credentials were correct, the user only needs to provide the code
+                *user_needs_email_code = true;
+                *add_login_delay = false;
+                return Err(ErrorResponse::new(
+                    ErrorResponseType::EmailCodeRequired,
+                    "Login from a new device - a code has been sent via E-Mail",
+                ));
+            }
+
+            if trust_device {
+                let device = TrustedDevice::create(req, user.id.clone()).await?;
+                trusted_device_cookie = Some(device.build_cookie());
+            }
+        }
+    }
+
+    // A passkey login is only finished after the 2nd factor, which does its own check.
+    let known_devices_cookie = if !user.has_webauthn_enabled() || skip_webauthn {
+        SecurityNotification::check_new_login(data, req, &user).await
+    } else {
+        None
+    };
+
     let code = AuthCode::new(
         user.id.clone(),
         client.id,
@@ -122,6 +285,7 @@ pub async fn post_authorize(
         req_data.code_challenge_method,
         req_data.nonce,
         scopes,
+        org_id,
         code_lifetime,
     );
     code.save().await?;
@@ -133,9 +297,21 @@ pub async fn post_authorize(
     };
 
     // TODO double check that we do not have any problems with the direct webauthn login here
-    // TODO should we allow to skip this step if set so in the config?
     // check if we need to validate the 2nd factor
-    if user.has_webauthn_enabled() {
+    if user.has_webauthn_enabled() && skip_webauthn {
+        // the 2nd factor has been validated on this trusted device already
+        session.set_mfa(true).await?;
+
+        Ok(AuthStep::LoggedIn(AuthStepLoggedIn {
+            user_id: user.id,
+            email: user.email,
+            header_loc: (header::LOCATION, HeaderValue::from_str(&loc).unwrap()),
+            header_csrf: Session::get_csrf_header(&session.csrf_token),
+            header_origin,
+            trusted_device_cookie: None,
+            known_devices_cookie,
+        }))
+    } else if user.has_webauthn_enabled() {
         session.set_mfa(true).await?;
 
         let step = AuthStepAwaitWebauthn {
@@ -156,6 +332,8 @@ pub async fn post_authorize(
                 .header_origin
                 .as_ref()
                 .map(|h| h.1.to_str().unwrap().to_string()),
+            trust_device,
+            access_check,
         }
         .save()
         .await?;
@@ -168,11 +346,15 @@ pub async fn post_authorize(
             header_loc: (header::LOCATION, HeaderValue::from_str(&loc).unwrap()),
             header_csrf: Session::get_csrf_header(&session.csrf_token),
             header_origin,
+            trusted_device_cookie,
+            known_devices_cookie,
         }))
     }
 }
 
 pub async fn post_authorize_refresh(
+    data: &web::Data<AppState>,
+    req: &HttpRequest,
     session: &Session,
     client: Client,
     header_origin: Option<(HeaderName, HeaderValue)>,
@@ -189,6 +371,22 @@ pub async fn post_authorize_refresh(
     user.check_expired()?;
 
     client.validate_mfa(&user)?;
+    client
+        .validate_user_access(
+            data,
+            &user,
+            real_ip_from_req(req).ok().map(|ip| ip.to_string()),
+        )
+        .await
+        .map_err(|err| {
+            Client::access_denied_redirect(err, &req_data.redirect_uri, req_data.state.as_deref())
+        })?;
+    client
+        .validate_geo_access(data, &user, real_ip_from_req(req).ok())
+        .await
+        .map_err(|err| {
+            Client::access_denied_redirect(err, &req_data.redirect_uri, req_data.state.as_deref())
+        })?;
 
     let scopes = client.sanitize_login_scopes(&req_data.scopes)?;
     let code_lifetime = if user.has_webauthn_enabled() {
@@ -197,6 +395,12 @@ pub async fn post_authorize_refresh(
         client.auth_code_lifetime
     };
 
+    let org_id = if scopes.iter().any(|s| s == "organization") {
+        Organization::validate_selection(&user.id, req_data.org_id).await?
+    } else {
+        None
+    };
+
     let code = AuthCode::new(
         user.id.clone(),
         client.id,
@@ -205,6 +409,7 @@ pub async fn post_authorize_refresh(
         req_data.code_challenge_method,
         req_data.nonce,
         scopes,
+        org_id,
         code_lifetime,
     );
     code.save().await?;
@@ -236,6 +441,8 @@ pub async fn post_authorize_refresh(
                 .header_origin
                 .as_ref()
                 .map(|h| h.1.to_str().unwrap().to_string()),
+            trust_device: false,
+            access_check: None,
         };
         login_req.save().await?;
 
@@ -250,6 +457,8 @@ pub async fn post_authorize_refresh(
             ),
             header_csrf: Session::get_csrf_header(&session.csrf_token),
             header_origin,
+            trusted_device_cookie: None,
+            known_devices_cookie: None,
         }))
     }
 }
@@ -1,5 +1,6 @@
 use crate::token_set::{
-    AuthCodeFlow, AuthTime, DeviceCodeFlow, DpopFingerprint, TokenNonce, TokenScopes, TokenSet,
+    AuthCodeFlow, AuthTime, DeviceCodeFlow, DpopFingerprint, TokenActor, TokenNonce, TokenOrg,
+    TokenScopes, TokenSet,
 };
 use actix_web::http::header::{HeaderName, HeaderValue};
 use actix_web::{web, HttpRequest};
@@ -144,6 +145,16 @@ pub async fn grant_type_authorization_code(
     // An additional check at this point does not provide any security benefit but only uses resources.
 
     let user = User::find(code.user_id.clone()).await?;
+    let session = if let Some(sid) = &code.session_id {
+        Some(Session::find(sid.clone()).await?)
+    } else {
+        None
+    };
+    let actor = session
+        .as_ref()
+        .and_then(|s| s.impersonated_by.clone())
+        .map(TokenActor);
+
     let token_set = TokenSet::from_user(
         &user,
         data,
@@ -154,14 +165,13 @@ pub async fn grant_type_authorization_code(
         Some(TokenScopes(code.scopes.join(" "))),
         AuthCodeFlow::Yes,
         DeviceCodeFlow::No,
+        actor,
+        code.org_id.clone().map(TokenOrg),
     )
     .await?;
 
     // update session metadata
-    if code.session_id.is_some() {
-        let sid = code.session_id.as_ref().unwrap().clone();
-        let mut session = Session::find(sid).await?;
-
+    if let Some(mut session) = session {
         session.last_seen = Utc::now().timestamp();
         session.state = SessionState::Auth.as_str().to_string();
         if let Err(err) = session.validate_user_expiry(&user) {
@@ -119,6 +119,23 @@ pub async fn grant_type_device_code(
             }
         };
 
+        let access = match client
+            .validate_user_access(data, &user, Some(peer_ip.to_string()))
+            .await
+        {
+            Ok(_) => client.validate_geo_access(data, &user, Some(peer_ip)).await,
+            Err(err) => Err(err),
+        };
+        if let Err(err) = access {
+            if let Err(err) = code.delete().await {
+                error!("Error deleting DeviceAuthCode: {:?}", err);
+            }
+            return HttpResponse::Forbidden().json(OAuth2ErrorResponse {
+                error: OAuth2ErrorTypeResponse::AccessDenied,
+                error_description: Some(err.message),
+            });
+        }
+
         let access_exp = now.add(chrono::Duration::seconds(
             client.access_token_lifetime as i64,
         ));
@@ -170,6 +187,8 @@ pub async fn grant_type_device_code(
             code.scopes.map(TokenScopes),
             AuthCodeFlow::No,
             DeviceCodeFlow::Yes(id),
+            None,
+            None,
         )
         .await
         {
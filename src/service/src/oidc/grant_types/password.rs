@@ -1,3 +1,4 @@
+use crate::brute_force;
 use crate::token_set::{AuthCodeFlow, AuthTime, DeviceCodeFlow, DpopFingerprint, TokenSet};
 use actix_web::http::header::{HeaderName, HeaderValue};
 use actix_web::{web, HttpRequest};
@@ -8,6 +9,7 @@ use rauthy_common::password_hasher::HashPassword;
 use rauthy_common::utils::real_ip_from_req;
 use rauthy_error::{ErrorResponse, ErrorResponseType};
 use rauthy_models::app_state::AppState;
+use rauthy_models::entity::auth_providers::AuthProvider;
 use rauthy_models::entity::clients::Client;
 use rauthy_models::entity::clients_dyn::ClientDyn;
 use rauthy_models::entity::dpop_proof::DPoPProof;
@@ -47,6 +49,14 @@ pub async fn grant_type_password(
         client.validate_secret(&secret, &req)?;
     }
     client.validate_flow("password")?;
+    client.validate_password_login()?;
+
+    if AuthProvider::find_home_realm(email).await?.is_some() {
+        return Err(ErrorResponse::new(
+            ErrorResponseType::Forbidden,
+            "Password logins are not allowed for this E-Mail domain",
+        ));
+    }
 
     let mut headers = Vec::new();
     let dpop_fingerprint =
@@ -67,9 +77,11 @@ pub async fn grant_type_password(
 
     // This Error must be the same if user does not exist AND passwords do not match to prevent
     // username enumeration
+    brute_force::check_account_lock(email).await?;
     let mut user = User::find_by_email(String::from(email)).await?;
     user.check_enabled()?;
     user.check_expired()?;
+    user.check_locked()?;
 
     match user.validate_password(data, password.clone()).await {
         Ok(_) => {
@@ -77,6 +89,10 @@ pub async fn grant_type_password(
             user.last_failed_login = None;
             user.failed_login_attempts = None;
 
+            // The password is correct, but it may not be strong enough for this client.
+            user.validate_client_password_policy(data, &client.id, &password)
+                .await?;
+
             // check if the password hash should be upgraded
             let hash_uptodate = user.is_argon2_uptodate(&data.argon2_params)?;
             if !hash_uptodate {
@@ -86,6 +102,18 @@ pub async fn grant_type_password(
                 user.password = Some(new_hash);
             }
 
+            // check the access before saving, so a denied user does not get a successful login
+            client
+                .validate_user_access(
+                    data,
+                    &user,
+                    real_ip_from_req(&req).ok().map(|ip| ip.to_string()),
+                )
+                .await?;
+            client
+                .validate_geo_access(data, &user, real_ip_from_req(&req).ok())
+                .await?;
+
             user.save(None).await?;
 
             // update timestamp if it is a dynamic client
@@ -103,6 +131,8 @@ pub async fn grant_type_password(
                 None,
                 AuthCodeFlow::No,
                 DeviceCodeFlow::No,
+                None,
+                None,
             )
             .await?;
             Ok((ts, headers))
@@ -114,10 +144,10 @@ pub async fn grant_type_password(
                 user.email
             );
 
-            user.last_failed_login = Some(Utc::now().timestamp());
-            user.failed_login_attempts = Some(&user.failed_login_attempts.unwrap_or(0) + 1);
-
-            user.save(None).await?;
+            if err.error == ErrorResponseType::Unauthorized {
+                let ip = real_ip_from_req(&req).ok().map(|ip| ip.to_string());
+                user.on_failed_login(data, ip).await?;
+            }
 
             // TODO add expo increasing sleeps after failed login attempts here?
             Err(err)
@@ -1,5 +1,5 @@
 use crate::token_set::{
-    AuthCodeFlow, AuthTime, DeviceCodeFlow, DpopFingerprint, TokenScopes, TokenSet,
+    AuthCodeFlow, AuthTime, DeviceCodeFlow, DpopFingerprint, TokenOrg, TokenScopes, TokenSet,
 };
 use actix_web::http::header::{HeaderName, HeaderValue};
 use actix_web::{web, HttpRequest};
@@ -8,6 +8,7 @@ use jwt_simple::claims;
 use jwt_simple::claims::JWTClaims;
 use jwt_simple::common::VerificationOptions;
 use jwt_simple::prelude::*;
+use rauthy_common::utils::real_ip_from_req;
 use rauthy_error::{ErrorResponse, ErrorResponseType};
 use rauthy_models::app_state::AppState;
 use rauthy_models::entity::clients::Client;
@@ -154,6 +155,13 @@ pub async fn validate_refresh_token(
     let mut user = User::find(uid).await?;
     user.check_enabled()?;
     user.check_expired()?;
+    client
+        .validate_user_access(
+            data,
+            &user,
+            real_ip_from_req(req).ok().map(|ip| ip.to_string()),
+        )
+        .await?;
 
     // validate that it exists in the db and invalidate it afterward
     let (_, validation_str) = refresh_token.split_at(refresh_token.len() - 49);
@@ -216,6 +224,8 @@ pub async fn validate_refresh_token(
         rt_scope.map(TokenScopes),
         AuthCodeFlow::No,
         DeviceCodeFlow::No,
+        None,
+        claims.custom.org.map(TokenOrg),
     )
     .await?;
 
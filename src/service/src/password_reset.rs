@@ -11,12 +11,15 @@ use rauthy_models::app_state::AppState;
 use rauthy_models::entity::colors::ColorEntity;
 use rauthy_models::entity::magic_links::{MagicLink, MagicLinkUsage};
 use rauthy_models::entity::password::PasswordPolicy;
+use rauthy_models::entity::password_policies::ScopedPasswordPolicy;
 use rauthy_models::entity::sessions::Session;
+use rauthy_models::entity::user_invitations::UserInvitation;
 use rauthy_models::entity::users::User;
 use rauthy_models::entity::webauthn;
 use rauthy_models::entity::webauthn::WebauthnServiceReq;
 use rauthy_models::events::event::Event;
 use rauthy_models::language::Language;
+use rauthy_models::security_notifications::SecurityNotification;
 use rauthy_models::templates::PwdResetHtml;
 use tracing::{debug, error};
 
@@ -25,14 +28,15 @@ pub async fn handle_get_pwd_reset<'a>(
     user_id: String,
     reset_id: String,
     no_html: bool,
-) -> Result<(String, cookie::Cookie<'a>), ErrorResponse> {
+) -> Result<(String, PasswordPolicy, cookie::Cookie<'a>), ErrorResponse> {
     let mut ml = MagicLink::find(&reset_id).await?;
     ml.validate(&user_id, &req, false)?;
+    reject_unlock_link(&ml)?;
 
     let user = User::find(ml.user_id.clone()).await?;
 
     // get the html and insert values
-    let rules = PasswordPolicy::find().await?;
+    let rules = find_password_policy(&ml, &user).await?;
     let colors = ColorEntity::find_rauthy().await?;
     let lang = Language::try_from(&req).unwrap_or_default();
 
@@ -56,7 +60,7 @@ pub async fn handle_get_pwd_reset<'a>(
     let age_secs = ml.exp - Utc::now().timestamp();
     let cookie = ApiCookie::build(PWD_RESET_COOKIE, ml.cookie.unwrap(), age_secs);
 
-    Ok((content, cookie))
+    Ok((content, rules, cookie))
 }
 
 #[tracing::instrument(level = "debug", skip_all, fields(user_id = user_id))]
@@ -78,7 +82,7 @@ pub async fn handle_put_user_passkey_start<'a>(
 
     // if we register a new passkey, we need to make sure that the magic link is for a new user
     match MagicLinkUsage::try_from(&ml.usage)? {
-        MagicLinkUsage::NewUser(_) => {}
+        MagicLinkUsage::NewUser(_) | MagicLinkUsage::Invitation(_) => {}
         _ => {
             return Err(ErrorResponse::new(
                 ErrorResponseType::Forbidden,
@@ -103,6 +107,7 @@ pub async fn handle_put_user_passkey_finish<'a>(
     let ml_id = req_data.magic_link_id.as_ref().unwrap();
     let mut ml = MagicLink::find(ml_id).await?;
     ml.validate(&user_id, &req, true)?;
+    let invitation = find_valid_invitation(&ml).await?;
 
     // finish webauthn request -> always force UV for passkey only accounts
     debug!("ml is valid - finishing webauthn request");
@@ -127,9 +132,15 @@ pub async fn handle_put_user_passkey_finish<'a>(
     }
 
     debug!("invalidating magic link pwd");
-    // all good
+    // all good -> the invitation must be accepted before the magic link is gone
+    if let Some(invitation) = invitation {
+        let mut user = User::find(user_id).await?;
+        user.email_verified = true;
+        invitation.accept(user).await?;
+    } else {
+        User::set_email_verified(user_id, true).await?;
+    }
     ml.invalidate().await?;
-    User::set_email_verified(user_id, true).await?;
 
     // delete the cookie
     let cookie = ApiCookie::build(PWD_RESET_COOKIE, "", 0);
@@ -174,14 +185,24 @@ pub async fn handle_put_user_password_reset<'a>(
 
     let mut ml = MagicLink::find(&req_data.magic_link_id).await?;
     ml.validate(&user.id, &req, true)?;
+    reject_unlock_link(&ml)?;
+    let invitation = find_valid_invitation(&ml).await?;
 
     // validate password
-    user.apply_password_rules(&req_data.password).await?;
+    let rules = find_password_policy(&ml, &user).await?;
+    user.apply_password_policy(&req_data.password, &rules)
+        .await?;
 
-    // all good
-    ml.invalidate().await?;
+    // all good -> the invitation saves the new password in the same transaction
     user.email_verified = true;
-    user.save(None).await?;
+    let invitation = match invitation {
+        Some(invitation) => Some(invitation.accept(user.clone()).await?),
+        None => {
+            user.save(None).await?;
+            None
+        }
+    };
+    ml.invalidate().await?;
 
     let ip = match real_ip_from_req(&req).ok() {
         None => {
@@ -204,7 +225,20 @@ pub async fn handle_put_user_password_reset<'a>(
     // check if we got a custom `redirect_uri` during registration
     let redirect_uri = match MagicLinkUsage::try_from(&ml.usage)? {
         MagicLinkUsage::NewUser(redirect_uri) => redirect_uri,
-        MagicLinkUsage::PasswordReset(redirect_uri) => redirect_uri,
+        MagicLinkUsage::PasswordReset(redirect_uri) => {
+            // only an existing password has been changed, a new account is set up otherwise
+            SecurityNotification::PasswordChanged
+                .send(data, &user)
+                .await;
+            redirect_uri
+        }
+        MagicLinkUsage::PasswordChange(_) => {
+            SecurityNotification::PasswordChanged
+                .send(data, &user)
+                .await;
+            None
+        }
+        MagicLinkUsage::Invitation(_) => invitation.and_then(|i| i.redirect_uri),
         _ => None,
     };
 
@@ -212,3 +246,47 @@ pub async fn handle_put_user_password_reset<'a>(
     let cookie = ApiCookie::build(PWD_RESET_COOKIE, "", 0);
     Ok((cookie, redirect_uri))
 }
+
+/// Account unlock and "this wasn't me" links are sent out without any user interaction and must
+/// never be usable to set a new password.
+fn reject_unlock_link(ml: &MagicLink) -> Result<(), ErrorResponse> {
+    if matches!(
+        MagicLinkUsage::try_from(&ml.usage)?,
+        MagicLinkUsage::Unlock | MagicLinkUsage::NotMe
+    ) {
+        return Err(ErrorResponse::new(
+            ErrorResponseType::BadRequest,
+            "The Magic Link is not meant to be used for a password reset",
+        ));
+    }
+    Ok(())
+}
+
+/// Returns the effective password policy for the user. A forced password change for a client
+/// must satisfy the policy of this client on top.
+async fn find_password_policy(
+    ml: &MagicLink,
+    user: &User,
+) -> Result<PasswordPolicy, ErrorResponse> {
+    let mut rules = PasswordPolicy::find_for_user(user).await?;
+    if let MagicLinkUsage::PasswordChange(client_id) = MagicLinkUsage::try_from(&ml.usage)? {
+        if let Some(client_policy) = ScopedPasswordPolicy::find_for_client(&client_id).await? {
+            rules.merge_strictest(&client_policy);
+        }
+    }
+    Ok(rules)
+}
+
+/// Returns the `UserInvitation` for the given magic link, if it has been created for one.
+/// Makes sure, that it can still be accepted before any changes to the user are made.
+async fn find_valid_invitation(ml: &MagicLink) -> Result<Option<UserInvitation>, ErrorResponse> {
+    match MagicLinkUsage::try_from(&ml.usage)? {
+        MagicLinkUsage::Invitation(id) => {
+            let invitation = UserInvitation::find(&id).await?;
+            invitation.validate_pending()?;
+            invitation.validate_not_expired()?;
+            Ok(Some(invitation))
+        }
+        _ => Ok(None),
+    }
+}
@@ -0,0 +1,489 @@
+use actix_web::http::header;
+use actix_web::{web, HttpRequest, HttpResponse};
+use chrono::Utc;
+use rauthy_api_types::saml::{
+    SamlAttrSource, SamlClientRequest, SamlClientResponse, SamlNameIdFormat, SamlRequestParams,
+    SamlResumeParams,
+};
+use rauthy_common::constants::{COOKIE_SESSION, HEADER_HTML, SESSION_LIFETIME};
+use rauthy_common::utils::{base64_encode, get_rand};
+use rauthy_error::{ErrorResponse, ErrorResponseType};
+use rauthy_models::api_cookie::ApiCookie;
+use rauthy_models::app_state::AppState;
+use rauthy_models::entity::auth_codes::AuthCode;
+use rauthy_models::entity::clients::Client;
+use rauthy_models::entity::clients_saml::{ClientSaml, SamlAuthReq, SamlSessionIndex};
+use rauthy_models::entity::saml_certs::SamlCert;
+use rauthy_models::entity::sessions::Session;
+use rauthy_models::entity::user_attr::UserAttrValueEntity;
+use rauthy_models::entity::users::User;
+use rauthy_models::saml::idp::{
+    build_idp_metadata, build_logout_response, idp_entity_id, AuthnRequest, LogoutRequest,
+    SamlAttribute, SamlResponse, SpMetadata,
+};
+use rauthy_models::saml::{
+    decode_post, decode_redirect, new_id, normalize_cert_pem, validate_post_signature,
+    validate_redirect_signature, STATUS_SUCCESS,
+};
+use rauthy_models::templates::SamlPostBindingHtml;
+use std::fmt::Write;
+use tracing::debug;
+use validator::Validate;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SamlBinding {
+    Redirect,
+    Post,
+}
+
+impl SamlBinding {
+    fn decode(&self, value: &str) -> Result<String, ErrorResponse> {
+        match self {
+            Self::Redirect => decode_redirect(value),
+            Self::Post => decode_post(value),
+        }
+    }
+}
+
+/// The `redirect_uri` which is used for the internal `/authorize` flow. It will be added to each
+/// client with a SAML config automatically.
+pub fn resume_uri(data: &web::Data<AppState>) -> String {
+    format!("{}/saml/resume", data.issuer)
+}
+
+pub async fn idp_metadata(data: &web::Data<AppState>) -> Result<String, ErrorResponse> {
+    // makes sure that the cert for the latest key exists
+    SamlCert::find_signing_key(cert_common_name(data)).await?;
+    let certs = SamlCert::find_all()
+        .await?
+        .iter()
+        .map(|c| c.cert_b64())
+        .collect::<Vec<_>>();
+
+    Ok(build_idp_metadata(&data.issuer, &certs))
+}
+
+/// Handles an SP-initiated `AuthnRequest` and redirects to the `/authorize` flow, which
+/// will re-use an existing session or let the user log in.
+pub async fn sso(
+    data: &web::Data<AppState>,
+    req: &HttpRequest,
+    params: SamlRequestParams,
+    binding: SamlBinding,
+) -> Result<HttpResponse, ErrorResponse> {
+    let xml = binding.decode(&params.saml_request)?;
+    let authn_req = AuthnRequest::parse(&xml)?;
+    let sp = ClientSaml::find_by_entity_id(authn_req.issuer.clone()).await?;
+    validate_sp_signature(&sp, req, binding, &xml)?;
+
+    // The response will always be sent to the registered ACS, but we want to reject requests
+    // which expect it somewhere else to not fail silently.
+    if let Some(acs_url) = &authn_req.acs_url {
+        if acs_url != &sp.acs_url {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                "AssertionConsumerServiceURL does not match the registered one",
+            ));
+        }
+    }
+
+    let client = Client::find(sp.id).await?;
+    if !client.enabled {
+        return Err(ErrorResponse::new(
+            ErrorResponseType::BadRequest,
+            "Client is disabled",
+        ));
+    }
+
+    let auth_req = SamlAuthReq {
+        id: get_rand(32),
+        client_id: client.id.clone(),
+        request_id: Some(authn_req.id),
+        relay_state: params.relay_state,
+    };
+    auth_req.save().await?;
+
+    Ok(redirect_authorize(
+        data,
+        &client,
+        &auth_req.id,
+        authn_req.force_authn,
+    ))
+}
+
+/// Starts an IdP-initiated login for the given client.
+pub async fn idp_initiated(
+    data: &web::Data<AppState>,
+    client_id: String,
+    relay_state: Option<String>,
+) -> Result<HttpResponse, ErrorResponse> {
+    let sp = ClientSaml::find(client_id).await?;
+    let client = Client::find(sp.id).await?;
+    if !client.enabled {
+        return Err(ErrorResponse::new(
+            ErrorResponseType::BadRequest,
+            "Client is disabled",
+        ));
+    }
+
+    let auth_req = SamlAuthReq {
+        id: get_rand(32),
+        client_id: client.id.clone(),
+        request_id: None,
+        relay_state,
+    };
+    auth_req.save().await?;
+
+    Ok(redirect_authorize(data, &client, &auth_req.id, false))
+}
+
+fn redirect_authorize(
+    data: &web::Data<AppState>,
+    client: &Client,
+    state: &str,
+    force_login: bool,
+) -> HttpResponse {
+    let mut loc = format!(
+        "{}/oidc/authorize?client_id={}&redirect_uri={}&response_type=code&scope=openid&state={}",
+        data.issuer,
+        client.id,
+        resume_uri(data),
+        state,
+    );
+    if let Some(method) = client.get_challenges().and_then(|c| c.into_iter().next()) {
+        // The code will never be exchanged at the token endpoint. It is only validated inside
+        // the resume step, which is why any valid challenge is fine here.
+        let _ = write!(
+            loc,
+            "&code_challenge={}&code_challenge_method={}",
+            get_rand(64),
+            method
+        );
+    }
+    if force_login {
+        loc.push_str("&prompt=login");
+    }
+
+    HttpResponse::Found()
+        .insert_header((header::LOCATION, loc))
+        .finish()
+}
+
+/// The `redirect_uri` target of the internal `/authorize` flow. Validates the code and
+/// sends the signed assertion to the SP.
+pub async fn resume(
+    data: &web::Data<AppState>,
+    params: SamlResumeParams,
+    session: Option<&Session>,
+) -> Result<HttpResponse, ErrorResponse> {
+    let auth_req = SamlAuthReq::find(&params.state).await?;
+    auth_req.delete().await?;
+
+    let code = AuthCode::find(params.code).await?.ok_or_else(|| {
+        ErrorResponse::new(ErrorResponseType::Unauthorized, "Invalid or expired code")
+    })?;
+    code.delete().await?;
+
+    let now = Utc::now().timestamp();
+    if code.client_id != auth_req.client_id || code.exp < now {
+        return Err(ErrorResponse::new(
+            ErrorResponseType::Unauthorized,
+            "Invalid or expired code",
+        ));
+    }
+    // the code must belong to this browser to prevent login CSRF with an injected code
+    if code.session_id.is_some() && code.session_id.as_deref() != session.map(|s| s.id.as_str()) {
+        return Err(ErrorResponse::new(
+            ErrorResponseType::Unauthorized,
+            "The code does not belong to this session",
+        ));
+    }
+
+    let sp = ClientSaml::find(auth_req.client_id).await?;
+    let user = User::find(code.user_id).await?;
+    user.check_enabled()?;
+    user.check_expired()?;
+
+    let name_id_format = sp.get_name_id_format();
+    let name_id = match name_id_format {
+        SamlNameIdFormat::Email => user.email.clone(),
+        SamlNameIdFormat::Persistent | SamlNameIdFormat::Unspecified => user.id.clone(),
+        SamlNameIdFormat::Transient => new_id(),
+    };
+
+    let session_index = match &code.session_id {
+        Some(sid) => {
+            let idx = SamlSessionIndex::build(sid, &sp.entity_id);
+            SamlSessionIndex::save(&idx, sid, *SESSION_LIFETIME as i64).await?;
+            idx
+        }
+        None => get_rand(32),
+    };
+
+    let (key, cert) = SamlCert::find_signing_key(cert_common_name(data)).await?;
+    let idp_entity_id = idp_entity_id(&data.issuer);
+    let xml = SamlResponse {
+        idp_entity_id: &idp_entity_id,
+        sp_entity_id: &sp.entity_id,
+        acs_url: &sp.acs_url,
+        in_response_to: auth_req.request_id.as_deref(),
+        name_id: &name_id,
+        name_id_format,
+        session_index: &session_index,
+        auth_time: now,
+        attributes: build_attributes(&sp, &user).await?,
+    }
+    .build_signed(now, &key, &cert.cert_b64())?;
+
+    debug!(
+        "Sending SAML assertion for user {} to {}",
+        user.id, sp.entity_id
+    );
+
+    let body = SamlPostBindingHtml::build(
+        &sp.acs_url,
+        "SAMLResponse",
+        &base64_encode(xml.as_bytes()),
+        auth_req.relay_state.as_deref(),
+    );
+    Ok(HttpResponse::Ok().insert_header(HEADER_HTML).body(body))
+}
+
+/// Handles an SP-initiated `LogoutRequest`, invalidates the session and sends the
+/// `LogoutResponse` back to the SP.
+pub async fn slo(
+    data: &web::Data<AppState>,
+    req: &HttpRequest,
+    params: SamlRequestParams,
+    binding: SamlBinding,
+    session: Option<Session>,
+) -> Result<HttpResponse, ErrorResponse> {
+    let xml = binding.decode(&params.saml_request)?;
+    let logout_req = LogoutRequest::parse(&xml)?;
+    let sp = ClientSaml::find_by_entity_id(logout_req.issuer.clone()).await?;
+    let is_signed = validate_sp_signature(&sp, req, binding, &xml)?;
+
+    let slo_url = sp.slo_url.as_deref().ok_or_else(|| {
+        ErrorResponse::new(
+            ErrorResponseType::BadRequest,
+            "No Single Logout URL configured for this Service Provider",
+        )
+    })?;
+
+    // Depending on the binding and cookie settings, the browser might not send the session
+    // cookie. This is why we prefer the `SessionIndex` from the assertion.
+    let mut session_id = None;
+    if let Some(idx) = &logout_req.session_index {
+        session_id = SamlSessionIndex::find_session_id(idx, &sp.entity_id).await?;
+    }
+    // An unsigned request can be forged by any website the user visits. It may only end the
+    // session the SP knows via the `SessionIndex`, but never the one from the cookie.
+    let session = match session_id {
+        Some(sid) => Session::find(sid).await.ok(),
+        None if is_signed => session,
+        None => None,
+    };
+    let clear_cookie = session.is_some();
+    if let Some(session) = session {
+        session.invalidate().await?;
+    }
+
+    let (key, cert) = SamlCert::find_signing_key(cert_common_name(data)).await?;
+    let xml = build_logout_response(
+        &idp_entity_id(&data.issuer),
+        slo_url,
+        &logout_req.id,
+        STATUS_SUCCESS,
+        Utc::now().timestamp(),
+        &key,
+        &cert.cert_b64(),
+    )?;
+
+    let body = SamlPostBindingHtml::build(
+        slo_url,
+        "SAMLResponse",
+        &base64_encode(xml.as_bytes()),
+        params.relay_state.as_deref(),
+    );
+
+    let mut resp = HttpResponse::Ok();
+    resp.insert_header(HEADER_HTML);
+    if clear_cookie {
+        resp.cookie(ApiCookie::build(COOKIE_SESSION, "", 0));
+    }
+    Ok(resp.body(body))
+}
+
+pub async fn get_client_saml(
+    data: &web::Data<AppState>,
+    id: String,
+) -> Result<SamlClientResponse, ErrorResponse> {
+    let saml = ClientSaml::find(id).await?;
+    Ok(saml.into_response(resume_uri(data)))
+}
+
+pub async fn put_client_saml(
+    data: &web::Data<AppState>,
+    id: String,
+    mut payload: SamlClientRequest,
+) -> Result<SamlClientResponse, ErrorResponse> {
+    let mut client = Client::find(id.clone()).await?;
+
+    if let Some(cert) = &payload.sp_cert {
+        payload.sp_cert = Some(normalize_cert_pem(cert)?);
+    }
+    if let Ok(other) = ClientSaml::find_by_entity_id(payload.entity_id.clone()).await {
+        if other.id != id {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                format!("The entity ID is already in use by client '{}'", other.id),
+            ));
+        }
+    }
+
+    let saml = ClientSaml::from_request(id, payload)?;
+    saml.upsert().await?;
+
+    let resume_uri = resume_uri(data);
+    if !client.get_redirect_uris().contains(&resume_uri) {
+        client.redirect_uris = format!("{},{}", client.redirect_uris, resume_uri);
+        client.save().await?;
+    }
+
+    Ok(saml.into_response(resume_uri))
+}
+
+/// Imports the SAML metadata of an SP. Existing attribute mappings will be kept.
+pub async fn import_client_saml_metadata(
+    data: &web::Data<AppState>,
+    id: String,
+    metadata: &str,
+) -> Result<SamlClientResponse, ErrorResponse> {
+    let md = SpMetadata::parse(metadata)?;
+    let attr_mapping = ClientSaml::find(id.clone())
+        .await
+        .ok()
+        .and_then(|saml| saml.attr_mapping.as_ref().map(|_| saml.get_attr_mappings()));
+
+    let payload = SamlClientRequest {
+        entity_id: md.entity_id,
+        acs_url: md.acs_url,
+        slo_url: md.slo_url,
+        name_id_format: md.name_id_format.unwrap_or_default(),
+        sp_cert: md.cert_pem.filter(|_| md.authn_requests_signed),
+        attr_mapping,
+    };
+    payload.validate()?;
+
+    put_client_saml(data, id, payload).await
+}
+
+pub async fn delete_client_saml(
+    data: &web::Data<AppState>,
+    id: String,
+) -> Result<(), ErrorResponse> {
+    let mut client = Client::find(id.clone()).await?;
+    ClientSaml::delete(id).await?;
+
+    let resume_uri = resume_uri(data);
+    let uris = client
+        .get_redirect_uris()
+        .into_iter()
+        .filter(|uri| uri != &resume_uri)
+        .collect::<Vec<_>>();
+    if !uris.is_empty() {
+        client.redirect_uris = uris.join(",");
+        client.save().await?;
+    }
+
+    Ok(())
+}
+
+/// Validates the signature of an incoming message, if the SP has a signing certificate.
+/// Messages without a valid signature will be rejected in this case.
+///
+/// Returns `true` if the message has been signed.
+fn validate_sp_signature(
+    sp: &ClientSaml,
+    req: &HttpRequest,
+    binding: SamlBinding,
+    xml: &str,
+) -> Result<bool, ErrorResponse> {
+    match (&sp.sp_cert, binding) {
+        (Some(cert), SamlBinding::Redirect) => {
+            validate_redirect_signature(req.query_string(), cert)?;
+            Ok(true)
+        }
+        (Some(cert), SamlBinding::Post) => {
+            validate_post_signature(xml, cert)?;
+            Ok(true)
+        }
+        (None, _) => Ok(false),
+    }
+}
+
+async fn build_attributes(
+    sp: &ClientSaml,
+    user: &User,
+) -> Result<Vec<SamlAttribute>, ErrorResponse> {
+    let mappings = sp.get_attr_mappings();
+    let custom_attrs = if mappings
+        .iter()
+        .any(|m| m.source == SamlAttrSource::Attribute)
+    {
+        UserAttrValueEntity::find_for_user(&user.id).await?
+    } else {
+        Vec::default()
+    };
+
+    let attrs = mappings
+        .into_iter()
+        .map(|mapping| {
+            let values = match mapping.source {
+                SamlAttrSource::UserId => vec![user.id.clone()],
+                SamlAttrSource::Email => vec![user.email.clone()],
+                SamlAttrSource::GivenName => vec![user.given_name.clone()],
+                SamlAttrSource::FamilyName => user.family_name.clone().into_iter().collect(),
+                SamlAttrSource::Roles => user.get_roles(),
+                SamlAttrSource::Groups => user.get_groups(),
+                SamlAttrSource::Attribute => custom_attrs
+                    .iter()
+                    .find(|a| Some(&a.key) == mapping.attr_key.as_ref())
+                    .map(|a| attr_values(&a.value))
+                    .unwrap_or_default(),
+            };
+
+            SamlAttribute {
+                name: mapping.name,
+                values,
+            }
+        })
+        .collect();
+
+    Ok(attrs)
+}
+
+/// Custom user attributes are stored as JSON values. Arrays will become multiple SAML
+/// attribute values.
+fn attr_values(value: &[u8]) -> Vec<String> {
+    match serde_json::from_slice::<serde_json::Value>(value) {
+        Ok(serde_json::Value::Null) | Err(_) => Vec::default(),
+        Ok(serde_json::Value::String(s)) => vec![s],
+        Ok(serde_json::Value::Array(arr)) => arr
+            .into_iter()
+            .map(|v| match v {
+                serde_json::Value::String(s) => s,
+                v => v.to_string(),
+            })
+            .collect(),
+        Ok(v) => vec![v.to_string()],
+    }
+}
+
+#[inline]
+fn cert_common_name(data: &web::Data<AppState>) -> &str {
+    data.public_url
+        .split(':')
+        .next()
+        .unwrap_or(data.public_url.as_str())
+}
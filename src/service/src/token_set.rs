@@ -3,7 +3,7 @@ use chrono::Utc;
 use jwt_simple::algorithms::{EdDSAKeyPairLike, RSAKeyPairLike};
 use jwt_simple::claims::Claims;
 use jwt_simple::prelude::{coarsetime, UnixTimeStamp};
-use rauthy_api_types::oidc::JktClaim;
+use rauthy_api_types::oidc::{ActClaim, JktClaim, OrgClaim};
 use rauthy_common::constants::{
     DEVICE_GRANT_REFRESH_TOKEN_LIFETIME, DISABLE_REFRESH_TOKEN_NBF, ENABLE_SOLID_AUD,
     ENABLE_WEB_ID, REFRESH_TOKEN_LIFETIME,
@@ -13,6 +13,7 @@ use rauthy_error::{ErrorResponse, ErrorResponseType};
 use rauthy_models::app_state::AppState;
 use rauthy_models::entity::clients::Client;
 use rauthy_models::entity::jwk::{JwkKeyPair, JwkKeyPairAlg};
+use rauthy_models::entity::organizations::Organization;
 use rauthy_models::entity::refresh_tokens::RefreshToken;
 use rauthy_models::entity::refresh_tokens_devices::RefreshTokenDevice;
 use rauthy_models::entity::scopes::Scope;
@@ -106,6 +107,13 @@ pub struct DpopFingerprint(pub String);
 
 pub struct TokenNonce(pub String);
 
+/// Contains the user id of the admin, if tokens are issued for an impersonated session
+#[derive(Clone)]
+pub struct TokenActor(pub String);
+
+/// Contains the id of the organization, that has been selected during the authorization
+pub struct TokenOrg(pub String);
+
 /// Contains the scopes as a single String separated by `\s`
 pub struct TokenScopes(pub String);
 
@@ -133,6 +141,8 @@ impl TokenSet {
         scope: Option<TokenScopes>,
         scope_customs: Option<(Vec<&Scope>, &Option<HashMap<String, Vec<u8>>>)>,
         device_code_flow: DeviceCodeFlow,
+        actor: Option<TokenActor>,
+        org: Option<OrgClaim>,
     ) -> Result<String, ErrorResponse> {
         let did = match device_code_flow {
             DeviceCodeFlow::Yes(did) => Some(did),
@@ -151,6 +161,8 @@ impl TokenSet {
             roles: None,
             groups: None,
             cnf: dpop_fingerprint.map(|jkt| JktClaim { jkt: jkt.0 }),
+            act: actor.map(|a| ActClaim { sub: a.0 }),
+            org,
             custom: None,
         };
 
@@ -223,6 +235,8 @@ impl TokenSet {
         scope: &str,
         scope_customs: Option<(Vec<&Scope>, &Option<HashMap<String, Vec<u8>>>)>,
         auth_code_flow: AuthCodeFlow,
+        actor: Option<TokenActor>,
+        org: Option<OrgClaim>,
     ) -> Result<String, ErrorResponse> {
         let amr = if user.has_webauthn_enabled() && auth_code_flow == AuthCodeFlow::Yes {
             JwtAmrValue::Mfa.to_string()
@@ -251,6 +265,8 @@ impl TokenSet {
             roles: user.get_roles(),
             groups: None,
             cnf: dpop_fingerprint.map(|jkt| JktClaim { jkt: jkt.0 }),
+            act: actor.map(|a| ActClaim { sub: a.0 }),
+            org,
             custom: None,
             webid,
         };
@@ -367,6 +383,7 @@ impl TokenSet {
         scope: Option<TokenScopes>,
         is_mfa: bool,
         device_code_flow: DeviceCodeFlow,
+        org: Option<TokenOrg>,
     ) -> Result<String, ErrorResponse> {
         let did = if let DeviceCodeFlow::Yes(device_id) = device_code_flow {
             Some(device_id)
@@ -381,6 +398,7 @@ impl TokenSet {
             auth_time: Some(auth_time.get()),
             cnf: dpop_fingerprint.map(|jkt| JktClaim { jkt: jkt.0 }),
             did: did.clone(),
+            org: org.map(|o| o.0),
         };
 
         let nbf = if *DISABLE_REFRESH_TOKEN_NBF {
@@ -453,6 +471,8 @@ impl TokenSet {
             None,
             None,
             DeviceCodeFlow::No,
+            None,
+            None,
         )
         .await?;
 
@@ -477,6 +497,8 @@ impl TokenSet {
         scopes: Option<TokenScopes>,
         auth_code_flow: AuthCodeFlow,
         device_code_flow: DeviceCodeFlow,
+        actor: Option<TokenActor>,
+        org: Option<TokenOrg>,
     ) -> Result<Self, ErrorResponse> {
         let scopes = scopes.map(|s| s.0);
         let scope = if let Some(s) = &scopes {
@@ -557,6 +579,13 @@ impl TokenSet {
             client.access_token_lifetime.unsigned_abs() as i64
         };
 
+        let org_claim = if has_scope(&scope, "organization") {
+            Organization::claim_for_user(&user.id, org.as_ref().map(|o| o.0.as_str())).await?
+        } else {
+            None
+        };
+        let org_claim_id = org_claim.as_ref().map(|o| o.id.clone());
+
         let token_type = if dpop_fingerprint.is_some() {
             JwtTokenType::DPoP
         } else {
@@ -571,6 +600,8 @@ impl TokenSet {
             Some(TokenScopes(scope.clone())),
             customs_access,
             device_code_flow.clone(),
+            actor.clone(),
+            org_claim.clone(),
         )
         .await?;
 
@@ -590,9 +621,12 @@ impl TokenSet {
             &scope,
             customs_id,
             auth_code_flow,
+            actor.clone(),
+            org_claim,
         )
         .await?;
-        let refresh_token = if client.allow_refresh_token() {
+        // impersonated sessions are time-limited and must never be extended via refresh tokens
+        let refresh_token = if client.allow_refresh_token() && actor.is_none() {
             Some(
                 Self::build_refresh_token(
                     user,
@@ -604,6 +638,7 @@ impl TokenSet {
                     scopes.map(TokenScopes),
                     user.has_webauthn_enabled(),
                     device_code_flow,
+                    org_claim_id.map(TokenOrg),
                 )
                 .await?,
             )
@@ -621,6 +656,13 @@ impl TokenSet {
     }
 }
 
+/// Checks for an exact match of `name` inside the space separated `scope`.
+/// A plain `contains()` would also match custom scopes like `organization_admin`.
+#[inline]
+fn has_scope(scope: &str, name: &str) -> bool {
+    scope.split_whitespace().any(|s| s == name)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -639,4 +681,15 @@ mod tests {
         let sha512 = AtHash::build(ref_token, AtHashAlg::Sha512);
         assert_eq!(&sha512.0, "p2LHG4H-8pYDc0hyVOo3iIHvZJUqe9tbj3jESOuXbkY");
     }
+
+    #[test]
+    fn test_has_scope() {
+        assert!(has_scope("openid organization", "organization"));
+        assert!(has_scope("organization", "organization"));
+        assert!(has_scope("openid  organization email", "organization"));
+        assert!(!has_scope("openid organization_admin", "organization"));
+        assert!(!has_scope("openid my-organization", "organization"));
+        assert!(!has_scope("openid email", "organization"));
+        assert!(!has_scope("", "organization"));
+    }
 }
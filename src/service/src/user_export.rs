@@ -0,0 +1,159 @@
+use rauthy_error::ErrorResponse;
+use rauthy_models::entity::user_attr::UserAttrValueEntity;
+use rauthy_models::entity::user_federations::UserFederation;
+use rauthy_models::entity::users::User;
+use rauthy_models::entity::users_values::UserValues;
+use rauthy_models::entity::webauthn::PasskeyEntity;
+use serde::{Deserialize, Serialize};
+use tracing::{error, info};
+
+/// Users are loaded in pages of this size, which keeps the memory usage low, no matter how many
+/// users exist.
+const EXPORT_PAGE_SIZE: i64 = 500;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UserExportAttr {
+    pub key: String,
+    pub value: serde_json::Value,
+}
+
+/// A single line of the JSON lines export. It contains everything that belongs to a user.
+/// The password hash and passkeys are only included, if `secrets` is `true`, which makes it
+/// possible to restore the user as it was with the import.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UserExport {
+    /// `true` if the credentials are included. Without them, `user.password`,
+    /// `user.webauthn_user_id` and `passkeys` are always empty and will be ignored on import.
+    #[serde(default)]
+    pub secrets: bool,
+    pub user: User,
+    pub values: Option<UserValues>,
+    #[serde(default)]
+    pub attributes: Vec<UserExportAttr>,
+    #[serde(default)]
+    pub federations: Vec<UserFederation>,
+    #[serde(default)]
+    pub passkeys: Vec<PasskeyEntity>,
+}
+
+impl UserExport {
+    async fn build(mut user: User, with_secrets: bool) -> Result<Self, ErrorResponse> {
+        let values = UserValues::find(&user.id).await?;
+        let attributes = UserAttrValueEntity::find_for_user(&user.id)
+            .await?
+            .into_iter()
+            .map(|attr| {
+                Ok(UserExportAttr {
+                    key: attr.key,
+                    value: serde_json::from_slice(&attr.value)?,
+                })
+            })
+            .collect::<Result<Vec<_>, ErrorResponse>>()?;
+        let federations = UserFederation::find_for_user(&user.id).await?;
+        let passkeys = if with_secrets {
+            PasskeyEntity::find_for_user(&user.id).await?
+        } else {
+            user.password = None;
+            user.webauthn_user_id = None;
+            Vec::default()
+        };
+
+        Ok(Self {
+            secrets: with_secrets,
+            user,
+            values,
+            attributes,
+            federations,
+            passkeys,
+        })
+    }
+}
+
+/// Sends all users as JSON lines into `tx`. An error will be sent as the last message before
+/// the export stops. If the receiver has been dropped, for instance because an HTTP client
+/// disconnected, the export stops early.
+///
+/// Password hashes and passkeys are only included with `with_secrets`.
+pub async fn export_users(tx: flume::Sender<Result<String, ErrorResponse>>, with_secrets: bool) {
+    match export_pages(&tx, with_secrets).await {
+        Ok(count) => info!("Exported {} users", count),
+        Err(err) => {
+            error!("User export failed: {}", err);
+            let _ = tx.send_async(Err(err)).await;
+        }
+    }
+}
+
+async fn export_pages(
+    tx: &flume::Sender<Result<String, ErrorResponse>>,
+    with_secrets: bool,
+) -> Result<usize, ErrorResponse> {
+    let mut count = 0;
+    let mut last_id = String::default();
+
+    loop {
+        let users = User::find_page_after(&last_id, EXPORT_PAGE_SIZE).await?;
+        let is_last_page = users.len() < EXPORT_PAGE_SIZE as usize;
+        if let Some(user) = users.last() {
+            last_id.clone_from(&user.id);
+        }
+
+        for user in users {
+            let mut line = serde_json::to_string(&UserExport::build(user, with_secrets).await?)?;
+            line.push('\n');
+            if tx.send_async(Ok(line)).await.is_err() {
+                return Ok(count);
+            }
+            count += 1;
+        }
+
+        if is_last_page {
+            return Ok(count);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_user_export_format() {
+        let export = UserExport {
+            secrets: false,
+            user: User {
+                email: "alice@example.com".to_string(),
+                ..Default::default()
+            },
+            values: None,
+            attributes: vec![UserExportAttr {
+                key: "department".to_string(),
+                value: serde_json::Value::String("IT".to_string()),
+            }],
+            federations: Vec::default(),
+            passkeys: Vec::default(),
+        };
+
+        let line = serde_json::to_string(&export).unwrap();
+        assert!(!line.contains('\n'));
+        let parsed = serde_json::from_str::<UserExport>(&line).unwrap();
+        assert_eq!(parsed.user.id, export.user.id);
+        assert_eq!(parsed.user.email, "alice@example.com");
+        assert_eq!(parsed.attributes.len(), 1);
+        assert_eq!(parsed.attributes[0].value, "IT");
+
+        // hand-written lines may omit the lists
+        let mut value = serde_json::to_value(&export).unwrap();
+        let obj = value.as_object_mut().unwrap();
+        obj.remove("secrets");
+        obj.remove("attributes");
+        obj.remove("federations");
+        obj.remove("passkeys");
+        let parsed = serde_json::from_value::<UserExport>(value).unwrap();
+        assert!(!parsed.secrets);
+        assert!(parsed.attributes.is_empty());
+        assert!(parsed.federations.is_empty());
+        assert!(parsed.passkeys.is_empty());
+    }
+}
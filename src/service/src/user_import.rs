@@ -0,0 +1,540 @@
+use crate::user_export::UserExport;
+use actix_web::web;
+use rauthy_api_types::generic::Language;
+use rauthy_api_types::users::{
+    NewUserRequest, UserAttrValueRequest, UserAttrValuesUpdateRequest, UserImportErrorResponse,
+    UserImportRequest, UserImportResponse, UserUpsertResponse, UserValuesRequest,
+};
+use rauthy_common::password_hasher::LegacyHash;
+use rauthy_error::{ErrorResponse, ErrorResponseType};
+use rauthy_models::app_state::AppState;
+use rauthy_models::entity::auth_providers::AuthProvider;
+use rauthy_models::entity::groups::Group;
+use rauthy_models::entity::roles::Role;
+use rauthy_models::entity::user_attr::{UserAttrConfigEntity, UserAttrValueEntity};
+use rauthy_models::entity::users::User;
+use rauthy_models::entity::users_values::UserValues;
+use rauthy_models::events::event::Event;
+use serde::Deserialize;
+use std::collections::HashSet;
+use tracing::info;
+use validator::{Validate, ValidateEmail};
+
+/// Max payload size for a single import request
+pub const USER_IMPORT_MAX_BYTES: usize = 32 * 1024 * 1024;
+/// Max amount of users for a single import request
+pub const USER_IMPORT_MAX_ENTRIES: usize = 10_000;
+/// Max length of a single line for the JSON lines upsert
+pub const USER_UPSERT_MAX_LINE_BYTES: usize = 1024 * 1024;
+/// Max amount of detailed errors in the upsert report. Only the count increases afterward.
+const USER_UPSERT_MAX_ERRORS: usize = 1000;
+
+/// A single row for the CSV import.
+/// `groups` and `roles` are comma separated inside a single (quoted) field.
+#[derive(Debug, Deserialize)]
+struct UserImportCsvRow {
+    email: String,
+    given_name: String,
+    family_name: Option<String>,
+    language: Option<Language>,
+    groups: Option<String>,
+    roles: Option<String>,
+    email_verified: Option<bool>,
+    password_hash: Option<String>,
+    user_expires: Option<i64>,
+}
+
+impl From<UserImportCsvRow> for UserImportRequest {
+    fn from(row: UserImportCsvRow) -> Self {
+        Self {
+            email: row.email,
+            family_name: row.family_name,
+            given_name: row.given_name,
+            language: row.language,
+            groups: row.groups.as_deref().and_then(split_list),
+            roles: row.roles.as_deref().and_then(split_list),
+            email_verified: row.email_verified,
+            password_hash: row.password_hash,
+            user_expires: row.user_expires,
+        }
+    }
+}
+
+fn split_list(value: &str) -> Option<Vec<String>> {
+    let res = value
+        .split(',')
+        .map(|v| v.trim())
+        .filter(|v| !v.is_empty())
+        .map(String::from)
+        .collect::<Vec<_>>();
+    if res.is_empty() {
+        None
+    } else {
+        Some(res)
+    }
+}
+
+/// Parses a CSV import. The first line must be a header with the field names of a
+/// `UserImportRequest`, the column order does not matter.
+pub fn parse_csv(body: &[u8]) -> Result<Vec<UserImportRequest>, ErrorResponse> {
+    let mut reader = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .from_reader(body);
+
+    let mut res = Vec::new();
+    for (i, row) in reader.deserialize::<UserImportCsvRow>().enumerate() {
+        let row = row.map_err(|err| {
+            ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                // +2 for the header and because lines start at 1
+                format!("Invalid CSV in line {}: {}", i + 2, err),
+            )
+        })?;
+        res.push(UserImportRequest::from(row));
+    }
+    Ok(res)
+}
+
+pub fn parse_json(body: &[u8]) -> Result<Vec<UserImportRequest>, ErrorResponse> {
+    Ok(serde_json::from_slice::<Vec<UserImportRequest>>(body)?)
+}
+
+/// Roles and password hashes make it possible to create accounts with full access to this
+/// instance. They must only be imported with `privileged` access, which must only be granted
+/// to a `rauthy_admin` session.
+pub fn validate_import_privileges(
+    users: &[UserImportRequest],
+    privileged: bool,
+) -> Result<(), ErrorResponse> {
+    if privileged {
+        return Ok(());
+    }
+
+    let has_privileged_values = users.iter().any(|u| {
+        u.password_hash.is_some() || u.roles.as_ref().is_some_and(|roles| !roles.is_empty())
+    });
+    if has_privileged_values {
+        Err(ErrorResponse::new(
+            ErrorResponseType::Forbidden,
+            "Roles and password hashes can only be imported with a rauthy_admin session",
+        ))
+    } else {
+        Ok(())
+    }
+}
+
+/// Imports all given users. Invalid entries and already existing E-Mails will be skipped and
+/// returned with the reason instead of aborting the whole import.
+pub async fn import_users(
+    data: &web::Data<AppState>,
+    users: Vec<UserImportRequest>,
+    ip: String,
+) -> Result<UserImportResponse, ErrorResponse> {
+    if users.len() > USER_IMPORT_MAX_ENTRIES {
+        return Err(ErrorResponse::new(
+            ErrorResponseType::BadRequest,
+            format!(
+                "A single import must not contain more than {} users",
+                USER_IMPORT_MAX_ENTRIES
+            ),
+        ));
+    }
+
+    let mut imported = 0;
+    let mut errors = Vec::new();
+    for (i, req) in users.into_iter().enumerate() {
+        let email = req.email.clone();
+        match import_user(data, req).await {
+            Ok(is_admin) => {
+                imported += 1;
+                if is_admin {
+                    data.tx_events
+                        .send_async(Event::new_rauthy_admin(email, ip.clone()))
+                        .await
+                        .unwrap();
+                }
+            }
+            Err(err) => errors.push(UserImportErrorResponse {
+                entry: i + 1,
+                email,
+                error: err.message.to_string(),
+            }),
+        }
+    }
+    info!(
+        "User import finished: {} imported, {} skipped",
+        imported,
+        errors.len()
+    );
+
+    Ok(UserImportResponse { imported, errors })
+}
+
+/// Returns `true` if the imported user is a `rauthy_admin`.
+async fn import_user(
+    data: &web::Data<AppState>,
+    req: UserImportRequest,
+) -> Result<bool, ErrorResponse> {
+    req.validate()?;
+    if let Some(hash) = &req.password_hash {
+        LegacyHash::validate_import(hash)?;
+    }
+    if User::find_by_email(req.email.clone()).await.is_ok() {
+        return Err(ErrorResponse::new(
+            ErrorResponseType::BadRequest,
+            "E-Mail is already in use",
+        ));
+    }
+
+    let mut user = User::from_new_user_req(NewUserRequest {
+        email: req.email,
+        family_name: req.family_name,
+        given_name: req.given_name,
+        language: req.language.unwrap_or(Language::En),
+        groups: req.groups,
+        roles: req.roles.unwrap_or_default(),
+        user_expires: req.user_expires,
+    })
+    .await?;
+    user.email_verified = req.email_verified.unwrap_or(false);
+    let is_admin = user.is_admin();
+
+    if let Some(hash) = req.password_hash {
+        // The hash is kept as it is and will be upgraded to argon2id with the next login.
+        let mut user = User::insert(user).await?;
+        user.password = Some(hash);
+        user.save(None).await?;
+    } else {
+        User::create(data, user, None).await?;
+    }
+
+    Ok(is_admin)
+}
+
+/// Imports the JSON lines format from `user_export::export_users` line by line, which makes it
+/// possible to process any amount of users without buffering the whole input.
+///
+/// Existing users are matched by id first and by E-Mail second and will be overwritten, all
+/// others are created. Running the same import multiple times leads to the same result.
+/// Roles, groups, custom attributes and federation links, which do not exist in this instance,
+/// are dropped.
+///
+/// Roles, credentials (password hash and passkeys) and upstream links are only imported with
+/// `privileged` access, which must only be granted to a `rauthy_admin` session or the CLI.
+/// Otherwise, only the profile of existing users is updated and new users are created without
+/// any of them.
+pub struct UserUpsertImport {
+    line: usize,
+    privileged: bool,
+    provider_ids: HashSet<String>,
+    attr_keys: HashSet<String>,
+    report: UserUpsertResponse,
+}
+
+impl UserUpsertImport {
+    pub async fn new(dry_run: bool, privileged: bool) -> Result<Self, ErrorResponse> {
+        let provider_ids = AuthProvider::find_all()
+            .await?
+            .into_iter()
+            .map(|p| p.id)
+            .collect();
+        let attr_keys = UserAttrConfigEntity::find_all_as_set().await?;
+
+        Ok(Self {
+            line: 0,
+            privileged,
+            provider_ids,
+            attr_keys,
+            report: UserUpsertResponse {
+                dry_run,
+                ..Default::default()
+            },
+        })
+    }
+
+    pub async fn import_line(&mut self, line: &[u8]) {
+        self.line += 1;
+        if line.iter().all(|b| b.is_ascii_whitespace()) {
+            return;
+        }
+
+        let (email, res) = match serde_json::from_slice::<UserExport>(line) {
+            Ok(export) => (export.user.email.clone(), self.upsert(export).await),
+            Err(err) => (String::default(), Err(ErrorResponse::from(err))),
+        };
+        match res {
+            Ok(true) => self.report.created += 1,
+            Ok(false) => self.report.updated += 1,
+            Err(err) => {
+                self.report.failed += 1;
+                if self.report.errors.len() < USER_UPSERT_MAX_ERRORS {
+                    self.report.errors.push(UserImportErrorResponse {
+                        entry: self.line,
+                        email,
+                        error: err.message.to_string(),
+                    });
+                }
+            }
+        }
+    }
+
+    pub fn finish(self) -> UserUpsertResponse {
+        info!(
+            "User upsert finished (dry run: {}): {} created, {} updated, {} failed",
+            self.report.dry_run, self.report.created, self.report.updated, self.report.failed,
+        );
+        self.report
+    }
+
+    /// Returns `true` if the user has been created and `false` if it has been updated.
+    async fn upsert(&self, export: UserExport) -> Result<bool, ErrorResponse> {
+        let UserExport {
+            secrets,
+            mut user,
+            values,
+            attributes,
+            mut federations,
+            mut passkeys,
+        } = export;
+
+        user.email = user.email.to_lowercase();
+        if !user.email.validate_email() {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                "Invalid E-Mail",
+            ));
+        }
+        if let Some(provider_id) = &user.auth_provider_id {
+            if !self.provider_ids.contains(provider_id) {
+                return Err(ErrorResponse::new(
+                    ErrorResponseType::BadRequest,
+                    format!("Unknown auth provider '{}'", provider_id),
+                ));
+            }
+        }
+        user.groups = Group::sanitize(Some(user.get_groups())).await?;
+
+        let existing = match User::find(user.id.clone()).await {
+            Ok(existing) => Some(existing),
+            Err(_) => User::find_by_email(user.email.clone()).await.ok(),
+        };
+
+        if self.privileged {
+            user.roles = Role::sanitize(user.get_roles()).await?;
+            if secrets {
+                if let Some(hash) = &user.password {
+                    LegacyHash::validate_import(hash)?;
+                }
+            } else {
+                keep_credentials(&mut user, existing.as_ref());
+                passkeys.clear();
+            }
+        } else {
+            keep_protected_values(&mut user, existing.as_ref());
+            passkeys.clear();
+            federations.clear();
+        }
+
+        let is_new = existing.is_none();
+        let mut old_email = None;
+        if let Some(existing) = existing {
+            if existing.email != user.email {
+                // only possible if it was matched by id
+                if User::find_by_email(user.email.clone()).await.is_ok() {
+                    return Err(ErrorResponse::new(
+                        ErrorResponseType::BadRequest,
+                        "E-Mail is already in use by another user",
+                    ));
+                }
+                old_email = Some(existing.email);
+            }
+            user.id = existing.id;
+        }
+
+        if self.report.dry_run {
+            return Ok(is_new);
+        }
+
+        if is_new {
+            User::insert(user.clone()).await?;
+        }
+        // `insert()` only covers the values for new users, `save()` takes care of all others
+        user.save(old_email).await?;
+
+        if let Some(values) = values {
+            UserValues::upsert(
+                user.id.clone(),
+                UserValuesRequest {
+                    birthdate: values.birthdate,
+                    phone: values.phone,
+                    street: values.street,
+                    zip: values.zip,
+                    city: values.city,
+                    country: values.country,
+                },
+            )
+            .await?;
+        }
+
+        let values = attributes
+            .into_iter()
+            .filter(|attr| self.attr_keys.contains(&attr.key))
+            .map(|attr| UserAttrValueRequest {
+                key: attr.key,
+                value: attr.value,
+            })
+            .collect::<Vec<_>>();
+        if !values.is_empty() {
+            UserAttrValueEntity::update_for_user(&user.id, UserAttrValuesUpdateRequest { values })
+                .await?;
+        }
+
+        for mut link in federations {
+            if self.provider_ids.contains(&link.provider_id) {
+                link.user_id.clone_from(&user.id);
+                link.upsert().await?;
+            }
+        }
+
+        for mut passkey in passkeys {
+            passkey.user_id.clone_from(&user.id);
+            passkey.upsert().await?;
+        }
+
+        Ok(is_new)
+    }
+}
+
+/// Credentials must never be overwritten without `privileged` access and without secrets
+/// in the export. New users are created without any.
+fn keep_credentials(user: &mut User, existing: Option<&User>) {
+    user.password = existing.and_then(|e| e.password.clone());
+    user.password_expires = existing.and_then(|e| e.password_expires);
+    user.webauthn_user_id = existing.and_then(|e| e.webauthn_user_id.clone());
+}
+
+/// Without `privileged` access, only the profile of existing users can be updated. Everything
+/// that grants access to an account is kept, otherwise the import could take over any account,
+/// for instance with a password reset to a new E-Mail or a login via an upstream provider.
+fn keep_protected_values(user: &mut User, existing: Option<&User>) {
+    keep_credentials(user, existing);
+    user.roles = existing.map(|e| e.roles.clone()).unwrap_or_default();
+    user.auth_provider_id = existing.and_then(|e| e.auth_provider_id.clone());
+    user.federation_uid = existing.and_then(|e| e.federation_uid.clone());
+
+    if let Some(existing) = existing {
+        user.email.clone_from(&existing.email);
+        user.enabled = existing.enabled;
+        user.email_verified = existing.email_verified;
+        user.locked_until = existing.locked_until;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_parse_csv() {
+        let csv = r#"email,given_name,family_name,language,groups,roles,email_verified,password_hash,user_expires
+alice@example.com,Alice,Liddell,en,"admin, user",user,true,$2b$04$7CWRI46zDTe9WdvdFGnEt.wrNYDaRNHQZJ5TiB6CFsAtY8wBPv3Fm,
+bob@example.com,Bob,,,,,,,
+"#;
+        let users = parse_csv(csv.as_bytes()).unwrap();
+        assert_eq!(users.len(), 2);
+
+        let alice = &users[0];
+        assert_eq!(alice.email, "alice@example.com");
+        assert_eq!(alice.family_name.as_deref(), Some("Liddell"));
+        assert_eq!(alice.language, Some(Language::En));
+        assert_eq!(
+            alice.groups,
+            Some(vec!["admin".to_string(), "user".to_string()])
+        );
+        assert_eq!(alice.roles, Some(vec!["user".to_string()]));
+        assert_eq!(alice.email_verified, Some(true));
+        assert!(alice.password_hash.as_deref().unwrap().starts_with("$2b$"));
+        assert_eq!(alice.user_expires, None);
+
+        let bob = &users[1];
+        assert_eq!(bob.family_name, None);
+        assert_eq!(bob.language, None);
+        assert_eq!(bob.groups, None);
+        assert_eq!(bob.roles, None);
+        assert_eq!(bob.password_hash, None);
+
+        let invalid = "email,given_name\nalice@example.com";
+        assert!(parse_csv(invalid.as_bytes()).is_err());
+    }
+
+    #[test]
+    fn test_validate_import_privileges() {
+        let csv = r#"email,given_name,roles,password_hash
+alice@example.com,Alice,,
+bob@example.com,Bob,rauthy_admin,
+carol@example.com,Carol,,$2b$04$7CWRI46zDTe9WdvdFGnEt.wrNYDaRNHQZJ5TiB6CFsAtY8wBPv3Fm
+"#;
+        let users = parse_csv(csv.as_bytes()).unwrap();
+
+        assert!(validate_import_privileges(&users[..1], false).is_ok());
+        for user in &users[1..] {
+            let err = validate_import_privileges(std::slice::from_ref(user), false).unwrap_err();
+            assert_eq!(err.error, ErrorResponseType::Forbidden);
+        }
+        assert!(validate_import_privileges(&users, true).is_ok());
+    }
+
+    #[test]
+    fn test_keep_protected_values() {
+        let admin = User {
+            id: "admin123".to_string(),
+            email: "admin@localhost.de".to_string(),
+            given_name: "Admin".to_string(),
+            password: Some("$argon2id$v=19$m=32768,t=3,p=2$hash".to_string()),
+            roles: "admin,rauthy_admin".to_string(),
+            enabled: true,
+            email_verified: true,
+            webauthn_user_id: Some("webauthn123".to_string()),
+            ..Default::default()
+        };
+        let import = User {
+            id: admin.id.clone(),
+            email: "attacker@evil.com".to_string(),
+            given_name: "Changed".to_string(),
+            password: Some(
+                "$2b$04$7CWRI46zDTe9WdvdFGnEt.wrNYDaRNHQZJ5TiB6CFsAtY8wBPv3Fm".to_string(),
+            ),
+            roles: "user".to_string(),
+            enabled: false,
+            email_verified: false,
+            auth_provider_id: Some("provider123".to_string()),
+            federation_uid: Some("attacker".to_string()),
+            locked_until: Some(User::LOCKED_UNTIL_UNLOCK),
+            ..Default::default()
+        };
+
+        // an unprivileged upsert against an existing admin must only update the profile
+        let mut user = import.clone();
+        keep_protected_values(&mut user, Some(&admin));
+        assert_eq!(user.given_name, "Changed");
+        assert_eq!(user.email, admin.email);
+        assert_eq!(user.password, admin.password);
+        assert_eq!(user.roles, admin.roles);
+        assert_eq!(user.webauthn_user_id, admin.webauthn_user_id);
+        assert_eq!(user.auth_provider_id, None);
+        assert_eq!(user.federation_uid, None);
+        assert!(user.enabled);
+        assert!(user.email_verified);
+        assert_eq!(user.locked_until, None);
+
+        // new users never receive any roles, credentials or upstream links
+        let mut user = import.clone();
+        keep_protected_values(&mut user, None);
+        assert_eq!(user.email, import.email);
+        assert_eq!(user.password, None);
+        assert_eq!(user.roles, "");
+        assert_eq!(user.auth_provider_id, None);
+        assert_eq!(user.federation_uid, None);
+    }
+}